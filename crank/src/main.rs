@@ -0,0 +1,403 @@
+//! Keeper for the price oracle: reads the header and data accounts, works out which
+//! assets are due, and sends (or, with `--dry-run`, previews) the updates covering them.
+//!
+//! Every line written to stdout is one JSON object with an `event` field. The process
+//! exits with 0 when nothing failed, 1 on RPC or transaction errors and 2 when the
+//! oracle rejected an update, so it can be run from cron or a systemd timer.
+mod schedule;
+
+use std::process::ExitCode;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anchor_lang::{AccountDeserialize, AnchorDeserialize, InstructionData, ToAccountMetas};
+use base64::Engine;
+use clap::Parser;
+use oracles::client::{apy_history_address, data_address, decode_supported_assets, get_supported_assets_ix, header_address, history_address, subscriber_address, subscriber_callback_metas, updater_stats_address, AssetInfo, AssetType, Subscriber, DEFAULT_INSTANCE};
+use oracles::price_oracle::{OracleError, PriceOracleData, PriceOracleHeader, UpdatePreview};
+use oracles::switchboard_utils::{DEVNET_AGGREGATOR_PUBKEY, SOL_PRICE_AGGREGATOR_PUBKEY};
+use schedule::{due_assets, plan, shard_feeds, Update};
+use serde_json::{json, Value};
+use solana_client::client_error::ClientError;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::account::from_account;
+use solana_sdk::clock::Clock;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::compute_budget::ComputeBudgetInstruction;
+use solana_sdk::instruction::{AccountMeta, Instruction, InstructionError};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair_file, Keypair, Signature, Signer};
+use solana_sdk::{system_program, sysvar};
+use solana_sdk::transaction::{Transaction, TransactionError};
+use solana_transaction_status::UiReturnDataEncoding;
+
+#[derive(Parser, Debug)]
+#[clap(name = "crank", about = "Pushes due price updates to the oracle program")]
+struct Args {
+    /// RPC endpoint
+    #[clap(long, default_value = "https://api.devnet.solana.com")]
+    url: String,
+    /// Keypair of the updater (operator or admin), also the fee payer
+    #[clap(long)]
+    keypair: String,
+    /// Refresh assets this many seconds after their last update instead of at half their max age
+    #[clap(long)]
+    interval: Option<i64>,
+    /// Priority fee in micro-lamports per compute unit; 0 sends no fee instruction
+    #[clap(long, default_value_t = 0)]
+    priority_fee: u64,
+    /// Compute unit limit requested for each update
+    #[clap(long, default_value_t = 400_000)]
+    compute_unit_limit: u32,
+    /// Times a transaction is re-signed with a fresh blockhash after expiring
+    #[clap(long, default_value_t = 3)]
+    max_retries: u32,
+    /// Preview the due updates with `preview_update` instead of sending them
+    #[clap(long)]
+    dry_run: bool,
+    /// Leave out the registered subscriber, so a failing callback cannot hold updates back
+    #[clap(long)]
+    no_subscriber: bool,
+    /// Oracle instance to crank; the default instance when omitted
+    #[clap(long)]
+    instance: Option<Pubkey>,
+}
+
+/// Why a run did not complete cleanly
+enum Failure {
+    /// RPC, account decoding or transaction errors
+    Error(String),
+    /// The oracle refused an update or an asset in it
+    Rejected,
+}
+
+impl From<ClientError> for Failure {
+    fn from(error: ClientError) -> Self {
+        Failure::Error(error.to_string())
+    }
+}
+
+/// Writes one structured log line
+fn log(event: &str, fields: Value) {
+    let mut line = json!({
+        "ts": SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs()).unwrap_or(0),
+        "event": event,
+    });
+    if let (Some(line), Value::Object(fields)) = (line.as_object_mut(), fields) {
+        line.extend(fields);
+    }
+    println!("{}", line);
+}
+
+fn asset_names(assets: &[AssetType]) -> Vec<String> {
+    assets.iter().map(|asset_type| format!("{:?}", asset_type)).collect()
+}
+
+/// Assets whose latest observation a per-asset check rejected, with the `OracleError` code
+/// and the time of the rejection
+fn last_errors(header: &PriceOracleHeader) -> Vec<Value> {
+    AssetType::ALL.into_iter()
+        .filter(|asset_type| header.last_error(*asset_type).is_set())
+        .map(|asset_type| {
+            let last_error = header.last_error(asset_type);
+            json!({ "asset": format!("{:?}", asset_type), "code": last_error.code, "time": last_error.time })
+        })
+        .collect()
+}
+
+struct Crank {
+    rpc: RpcClient,
+    payer: Keypair,
+    args: Args,
+}
+
+impl Crank {
+    fn new(args: Args) -> Result<Self, Failure> {
+        let payer = read_keypair_file(&args.keypair)
+            .map_err(|e| Failure::Error(format!("cannot read keypair {}: {}", args.keypair, e)))?;
+        Ok(Crank {
+            rpc: RpcClient::new_with_commitment(args.url.clone(), CommitmentConfig::confirmed()),
+            payer,
+            args,
+        })
+    }
+
+    fn account<T: AccountDeserialize>(&self, address: &Pubkey) -> Result<T, Failure> {
+        let account = self.rpc.get_account(address)?;
+        T::try_deserialize(&mut account.data.as_slice())
+            .map_err(|e| Failure::Error(format!("cannot decode account {}: {}", address, e)))
+    }
+
+    fn instance(&self) -> Pubkey {
+        self.args.instance.unwrap_or(DEFAULT_INSTANCE)
+    }
+
+    fn clock(&self) -> Result<Clock, Failure> {
+        let account = self.rpc.get_account(&sysvar::clock::id())?;
+        from_account::<Clock, _>(&account).ok_or_else(|| Failure::Error("cannot decode the clock sysvar".to_string()))
+    }
+
+    fn run(&self) -> Result<(), Failure> {
+        let instance = &self.instance();
+        let header: PriceOracleHeader = self.account(&header_address(instance))?;
+        let data: PriceOracleData = self.account(&data_address(instance))?;
+        let clock = self.clock()?;
+
+        if header.emergency_stop {
+            log("emergency_stop", json!({ "slot": clock.slot }));
+            return Err(Failure::Rejected);
+        }
+        if header.updates_paused {
+            log("updates_paused", json!({ "slot": clock.slot }));
+            return Ok(());
+        }
+
+        let supported = self.supported_assets()?;
+        let due = due_assets(&header, &data, &supported, clock.unix_timestamp, self.args.interval);
+        let updates = plan(&header, &due);
+        log("due", json!({
+            "slot": clock.slot,
+            "timestamp": clock.unix_timestamp,
+            "assets": asset_names(&due),
+            "updates": updates.iter().map(Update::name).collect::<Vec<_>>(),
+            "last_errors": last_errors(&header),
+            "unknown_assets": supported.iter().filter(|asset| asset.asset_type().is_none()).map(|asset| asset.id).collect::<Vec<_>>(),
+        }));
+        if updates.is_empty() {
+            return Ok(());
+        }
+
+        if self.args.dry_run {
+            return self.preview(&due);
+        }
+
+        // Both histories are optional; record into each once it has been initialized
+        let history = self.rpc.get_account(&history_address(instance)).ok().map(|_| history_address(instance));
+        let apy_history = self.rpc.get_account(&apy_history_address(instance)).ok().map(|_| apy_history_address(instance));
+        // So is the subscriber; call it back when registered and enabled
+        let subscriber = if self.args.no_subscriber {
+            None
+        } else {
+            self.account::<Subscriber>(&subscriber_address(instance)).ok().filter(|subscriber| subscriber.enabled)
+        };
+        let mut rejected = false;
+        for update in updates {
+            match self.send(&[self.update_instruction(update, &header, &due, history, apy_history, subscriber.as_ref())]) {
+                Ok(signature) => {
+                    let header: PriceOracleHeader = self.account(&header_address(instance))?;
+                    // An asset whose feed has no new round yet is simply picked up by the next run
+                    let skipped: Vec<Value> = update.assets(&header).into_iter()
+                        .filter(|asset_type| due.contains(asset_type) && header.is_rejected(*asset_type))
+                        .filter(|asset_type| header.rejection_codes[asset_type.index()] != NO_NEW_ROUND)
+                        .map(|asset_type| json!({
+                            "asset": format!("{:?}", asset_type),
+                            "code": header.rejection_codes[asset_type.index()],
+                        }))
+                        .collect();
+                    rejected |= !skipped.is_empty();
+                    log("updated", json!({ "instruction": update.name(), "signature": signature.to_string(), "rejected": skipped }));
+                }
+                Err(error) => match program_error_code(&error) {
+                    Some(NO_NEW_ROUND) => {
+                        log("no_new_round", json!({ "instruction": update.name() }));
+                    }
+                    Some(code) => {
+                        rejected = true;
+                        log("rejected", json!({ "instruction": update.name(), "code": code, "error": error.to_string() }));
+                    }
+                    None => return Err((*error).into()),
+                },
+            }
+        }
+        if rejected { Err(Failure::Rejected) } else { Ok(()) }
+    }
+
+    /// Assets the oracle supports, with their status, from `get_supported_assets`
+    fn supported_assets(&self) -> Result<Vec<AssetInfo>, Failure> {
+        let bytes = self.simulate(get_supported_assets_ix(&self.instance()), "get_supported_assets")?;
+        decode_supported_assets(&bytes)
+            .map_err(|e| Failure::Error(format!("cannot decode supported assets: {}", e)))
+    }
+
+    /// Simulates `instruction`, named `name` in errors, and returns its return data
+    fn simulate(&self, instruction: Instruction, name: &str) -> Result<Vec<u8>, Failure> {
+        let blockhash = self.rpc.get_latest_blockhash()?;
+        let transaction = Transaction::new_signed_with_payer(&[instruction], Some(&self.payer.pubkey()), &[&self.payer], blockhash);
+        let simulation = self.rpc.simulate_transaction(&transaction)?.value;
+        if let Some(error) = simulation.err {
+            return Err(Failure::Error(format!("{} failed: {}", name, error)));
+        }
+        let return_data = simulation.return_data
+            .ok_or_else(|| Failure::Error(format!("{} returned no data", name)))?;
+        let (encoded, encoding) = return_data.data;
+        if encoding != UiReturnDataEncoding::Base64 {
+            return Err(Failure::Error(format!("unexpected return data encoding {:?}", encoding)));
+        }
+        base64::engine::general_purpose::STANDARD.decode(encoded)
+            .map_err(|e| Failure::Error(format!("cannot decode {} return data: {}", name, e)))
+    }
+
+    /// Simulates `preview_update` and reports the verdict for each due asset
+    fn preview(&self, due: &[AssetType]) -> Result<(), Failure> {
+        let instance = &self.instance();
+        let instruction = self.instruction(
+            oracles::accounts::PreviewUpdate {
+                header: header_address(instance),
+                data: data_address(instance),
+                oracle_feed: DEVNET_AGGREGATOR_PUBKEY,
+                sol_oracle_feed: SOL_PRICE_AGGREGATOR_PUBKEY,
+                sol_history_buffer: None,
+            },
+            oracles::instruction::PreviewUpdate {},
+        );
+        let bytes = self.simulate(instruction, "preview_update")?;
+        let preview = UpdatePreview::deserialize(&mut bytes.as_slice())
+            .map_err(|e| Failure::Error(format!("cannot decode preview: {}", e)))?;
+
+        let mut rejected = false;
+        for asset in preview.assets.iter().filter(|asset| due.contains(&asset.asset_type)) {
+            rejected |= !asset.accepted;
+            log("preview", json!({
+                "asset": format!("{:?}", asset.asset_type),
+                "accepted": asset.accepted,
+                "code": asset.reason_code,
+                "price": asset.price,
+                "apy": asset.apy,
+            }));
+        }
+        if rejected { Err(Failure::Rejected) } else { Ok(()) }
+    }
+
+    /// Builds `update`, recording into the keeper's stats account, which the first update creates,
+    /// and calling `subscriber` back when given. Only the multi-asset updates sample APYs, and
+    /// `update_feed_shards` reads the shards carrying `due` assets and calls no subscriber back.
+    fn update_instruction(&self, update: Update, header: &PriceOracleHeader, due: &[AssetType], history: Option<Pubkey>, apy_history: Option<Pubkey>, subscriber: Option<&Subscriber>) -> Instruction {
+        let instance = &self.instance();
+        let authority = self.payer.pubkey();
+        let mut instruction = match update {
+            Update::PricesAndApys => self.instruction(
+                oracles::accounts::UpdatePricesAndApys {
+                    header: header_address(instance),
+                    data: data_address(instance),
+                    oracle_feed: DEVNET_AGGREGATOR_PUBKEY,
+                    lease: None,
+                    lease_escrow: None,
+                    history,
+                    apy_history,
+                    updater_stats: Some(updater_stats_address(instance, &authority)),
+                    authority,
+                    system_program: Some(system_program::ID),
+                    subscriber: subscriber.map(|_| subscriber_address(instance)),
+                },
+                oracles::instruction::UpdatePricesAndApys {},
+            ),
+            Update::FeedShards => {
+                let mut instruction = self.instruction(
+                    oracles::accounts::UpdateFeedShards {
+                        header: header_address(instance),
+                        data: data_address(instance),
+                        history,
+                        apy_history,
+                        updater_stats: Some(updater_stats_address(instance, &authority)),
+                        authority,
+                        system_program: Some(system_program::ID),
+                    },
+                    oracles::instruction::UpdateFeedShards {},
+                );
+                instruction.accounts.extend(shard_feeds(header, due).into_iter().map(|feed| AccountMeta::new_readonly(feed, false)));
+                instruction
+            }
+            Update::SolPrice => self.instruction(
+                oracles::accounts::UpdateSolPrice {
+                    header: header_address(instance),
+                    data: data_address(instance),
+                    oracle_feed: SOL_PRICE_AGGREGATOR_PUBKEY,
+                    history_buffer: None,
+                    lease: None,
+                    lease_escrow: None,
+                    history,
+                    updater_stats: Some(updater_stats_address(instance, &authority)),
+                    authority,
+                    system_program: Some(system_program::ID),
+                    subscriber: subscriber.map(|_| subscriber_address(instance)),
+                },
+                oracles::instruction::UpdateSolPrice { expected_price: None, tolerance_bps: 0 },
+            ),
+        };
+        match subscriber {
+            Some(subscriber) if update != Update::FeedShards => instruction.accounts.extend(subscriber_callback_metas(subscriber)),
+            _ => {}
+        }
+        instruction
+    }
+
+    fn instruction(&self, accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+        Instruction {
+            program_id: oracles::ID,
+            accounts: accounts.to_account_metas(None),
+            data: data.data(),
+        }
+    }
+
+    /// Sends `instructions` behind the compute budget ones, re-signing with a fresh
+    /// blockhash up to `max_retries` times when the transaction expires unconfirmed
+    fn send(&self, instructions: &[Instruction]) -> Result<Signature, Box<ClientError>> {
+        let mut all = vec![ComputeBudgetInstruction::set_compute_unit_limit(self.args.compute_unit_limit)];
+        if self.args.priority_fee > 0 {
+            all.push(ComputeBudgetInstruction::set_compute_unit_price(self.args.priority_fee));
+        }
+        all.extend_from_slice(instructions);
+
+        let mut attempt = 0;
+        loop {
+            let blockhash = self.rpc.get_latest_blockhash().map_err(Box::new)?;
+            let transaction = Transaction::new_signed_with_payer(&all, Some(&self.payer.pubkey()), &[&self.payer], blockhash);
+            match self.rpc.send_and_confirm_transaction(&transaction) {
+                Err(error) if is_expired(&error) && attempt < self.args.max_retries => {
+                    attempt += 1;
+                    log("retry", json!({ "attempt": attempt, "error": error.to_string() }));
+                }
+                result => return result.map_err(Box::new),
+            }
+        }
+    }
+}
+
+/// The transaction expired before confirming and can be re-signed
+fn is_expired(error: &ClientError) -> bool {
+    if let Some(TransactionError::BlockhashNotFound) = error.get_transaction_error() {
+        return true;
+    }
+    let message = error.to_string();
+    message.contains("block height exceeded") || message.contains("unable to confirm transaction")
+}
+
+/// Custom error code of a failed instruction, i.e. a rejection by the oracle program
+/// Code of `NoNewRound`, returned when the feed has not published since the last update
+const NO_NEW_ROUND: u32 = anchor_lang::error::ERROR_CODE_OFFSET + OracleError::NoNewRound as u32;
+
+fn program_error_code(error: &ClientError) -> Option<u32> {
+    match error.get_transaction_error() {
+        Some(TransactionError::InstructionError(_, InstructionError::Custom(code))) => Some(code),
+        _ => None,
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let dry_run = args.dry_run;
+    let result = Crank::new(args).and_then(|crank| crank.run());
+    match result {
+        Ok(()) => {
+            log("done", json!({ "dry_run": dry_run }));
+            ExitCode::SUCCESS
+        }
+        Err(Failure::Rejected) => {
+            log("done", json!({ "dry_run": dry_run, "rejected": true }));
+            ExitCode::from(2)
+        }
+        Err(Failure::Error(message)) => {
+            log("error", json!({ "error": message }));
+            ExitCode::from(1)
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//! Decides which assets are due for an update and which instructions cover them.
+use oracles::client::{AssetInfo, AssetType};
+use oracles::price_oracle::{PriceOracleData, PriceOracleHeader, SourceKind};
+use solana_sdk::pubkey::Pubkey;
+
+/// Update instructions the crank sends, each covering a fixed set of assets
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Update {
+    /// `update_prices_and_apys`: the assets the header maps to the multi-asset feed
+    PricesAndApys,
+    /// `update_feed_shards`: the assets of the shards registered besides the primary feed
+    FeedShards,
+    /// `update_sol_price`: SOL alone, from the SOL feed
+    SolPrice,
+}
+
+impl Update {
+    pub const ALL: [Update; 3] = [Update::PricesAndApys, Update::FeedShards, Update::SolPrice];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Update::PricesAndApys => "update_prices_and_apys",
+            Update::FeedShards => "update_feed_shards",
+            Update::SolPrice => "update_sol_price",
+        }
+    }
+
+    /// Assets the instruction writes
+    pub fn assets(&self, header: &PriceOracleHeader) -> Vec<AssetType> {
+        match self {
+            Update::PricesAndApys => header.multi_asset_feed().to_vec(),
+            Update::FeedShards => header.sharded_assets(),
+            Update::SolPrice => vec![AssetType::SOL],
+        }
+    }
+}
+
+/// Aggregators of the registered shards carrying one of `due`, the feeds `update_feed_shards`
+/// is sent with, so a shard with nothing due, or a dead one, is left out
+pub fn shard_feeds(header: &PriceOracleHeader, due: &[AssetType]) -> Vec<Pubkey> {
+    header.feed_shards.iter()
+        .filter(|shard| shard.asset_types().iter().any(|asset_type| due.contains(asset_type)))
+        .map(|shard| shard.feed)
+        .collect()
+}
+
+/// Seconds after its last update at which an asset is refreshed: `interval` when given,
+/// otherwise half the asset's staleness tolerance so it is renewed well before going stale
+pub fn refresh_after(header: &PriceOracleHeader, asset_type: AssetType, interval: Option<i64>) -> i64 {
+    interval.unwrap_or_else(|| header.max_age_secs(asset_type) / 2)
+}
+
+/// Assets of `supported`, as `get_supported_assets` lists them, that are active at `now`,
+/// Switchboard-sourced and covered by one of the crank's updates, and that were never
+/// updated or whose price a feed last confirmed at least `refresh_after` before `now`.
+/// Entries with ids this build does not know are left out.
+pub fn due_assets(header: &PriceOracleHeader, data: &PriceOracleData, supported: &[AssetInfo], now: i64, interval: Option<i64>) -> Vec<AssetType> {
+    supported.iter()
+        .filter(|asset| asset.is_active(now))
+        .filter_map(AssetInfo::asset_type)
+        .filter(|asset_type| {
+            let price_data = &data.price_data[asset_type.index()];
+            if !Update::ALL.iter().any(|update| update.assets(header).contains(asset_type)) {
+                return false;
+            }
+            if header.source(*asset_type) != SourceKind::Switchboard {
+                return false;
+            }
+            price_data.last_update_time == 0 || now - price_data.last_checked_time >= refresh_after(header, *asset_type, interval)
+        })
+        .collect()
+}
+
+/// Instructions needed to refresh `due`, in the order they are sent
+pub fn plan(header: &PriceOracleHeader, due: &[AssetType]) -> Vec<Update> {
+    Update::ALL
+        .into_iter()
+        .filter(|update| update.assets(header).iter().any(|asset_type| due.contains(asset_type)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oracles::client::{ASSET_COUNT, ASSET_TYPES};
+    use oracles::price_oracle::{FeedMapping, FeedPosition, PriceOracle};
+
+    fn header() -> PriceOracleHeader {
+        PriceOracleHeader { max_age_secs: [300; ASSET_COUNT], ..PriceOracleHeader::default() }
+    }
+
+    fn data_updated_at(time: i64) -> PriceOracleData {
+        let mut data = PriceOracleData::default();
+        for price_data in data.price_data.iter_mut() {
+            price_data.last_update_time = time;
+            price_data.last_checked_time = time;
+        }
+        data
+    }
+
+    fn due(header: &PriceOracleHeader, data: &PriceOracleData, now: i64, interval: Option<i64>) -> Vec<AssetType> {
+        due_assets(header, data, &PriceOracle::get_supported_assets(header, data), now, interval)
+    }
+
+    #[test]
+    fn test_due_assets_follow_half_the_max_age() {
+        let mut header = header();
+        header.max_age_secs[AssetType::SOL.index()] = 60;
+        let data = data_updated_at(1_000);
+
+        assert_eq!(due(&header, &data, 1_029, None), vec![]);
+        assert_eq!(due(&header, &data, 1_030, None), vec![AssetType::SOL]);
+        // Assets added after SOL are only priced from their own feeds, which the crank does not send
+        let covered = ASSET_TYPES[..=AssetType::SOL.index()].to_vec();
+        assert_eq!(due(&header, &data, 1_150, None), covered);
+        // An explicit interval applies to every asset
+        assert_eq!(due(&header, &data, 1_010, Some(10)), covered);
+    }
+
+    #[test]
+    fn test_due_assets_count_from_the_last_check() {
+        // SOL's price has not changed since 1_000, but a feed confirmed it at 1_100
+        let mut data = data_updated_at(1_000);
+        data.price_data[AssetType::SOL.index()].last_checked_time = 1_100;
+
+        let due = due(&header(), &data, 1_150, None);
+        assert!(!due.contains(&AssetType::SOL));
+        assert_eq!(due.len(), 6);
+    }
+
+    #[test]
+    fn test_due_assets_skip_delisted_paused_and_other_sources() {
+        let mut header = header();
+        header.sources[AssetType::MSOL.index()] = SourceKind::Chainlink;
+        header.paused[AssetType::HSOL.index()] = true;
+        let mut data = data_updated_at(0);
+        data.price_data[AssetType::BSOL.index()].delisted = true;
+
+        let due = due(&header, &data, 1_000, None);
+        assert!(!due.contains(&AssetType::MSOL));
+        assert!(!due.contains(&AssetType::BSOL));
+        assert!(!due.contains(&AssetType::HSOL));
+        assert_eq!(due.len(), 4);
+    }
+
+    #[test]
+    fn test_due_assets_follow_the_supported_list() {
+        let header = header();
+        let data = data_updated_at(0);
+        let mut supported = PriceOracle::get_supported_assets(&header, &data);
+        // Not yet active, and an asset a newer program added
+        supported[AssetType::MSOL.index()].activation_time = 2_000;
+        let mut unknown = supported[0].clone();
+        unknown.id = ASSET_COUNT as u8;
+        supported.push(unknown);
+
+        let due = due_assets(&header, &data, &supported, 1_000, None);
+        assert!(!due.contains(&AssetType::MSOL));
+        assert_eq!(due.len(), 6);
+        assert!(due_assets(&header, &data, &supported, 2_000, None).contains(&AssetType::MSOL));
+        assert_eq!(due_assets(&header, &data, &[], 1_000, None), vec![]);
+    }
+
+    #[test]
+    fn test_plan_sends_only_needed_instructions() {
+        let header = header();
+        assert_eq!(plan(&header, &[]), vec![]);
+        assert_eq!(plan(&header, &[AssetType::SOL]), vec![Update::SolPrice]);
+        assert_eq!(plan(&header, &[AssetType::MSOL]), vec![Update::PricesAndApys]);
+        assert_eq!(plan(&header, &[AssetType::SOL, AssetType::JupSOL]), vec![Update::PricesAndApys, Update::SolPrice]);
+    }
+
+    #[test]
+    fn test_multi_asset_update_follows_the_feed_mapping() {
+        let mut header = header();
+        let mut assets = AssetType::MULTI_ASSET_FEED;
+        assets[4] = AssetType::INF;
+        header.feed_mapping = FeedMapping { configured: true, assets };
+
+        let due = due(&header, &data_updated_at(0), 1_000, None);
+        assert!(due.contains(&AssetType::INF) && !due.contains(&AssetType::HSOL));
+        assert_eq!(plan(&header, &[AssetType::INF]), vec![Update::PricesAndApys]);
+        assert_eq!(plan(&header, &[AssetType::HSOL]), vec![]);
+    }
+
+    #[test]
+    fn test_feed_shards_are_sent_for_their_due_assets() {
+        let mut header = header();
+        let (inf_feed, bonksol_feed) = (Pubkey::new_unique(), Pubkey::new_unique());
+        PriceOracle::set_feed_shard(&mut header, 1, inf_feed, &[FeedPosition { position: 0, asset_type: AssetType::INF }]).unwrap();
+        PriceOracle::set_feed_shard(&mut header, 2, bonksol_feed, &[FeedPosition { position: 0, asset_type: AssetType::BonkSOL }]).unwrap();
+        let mut data = data_updated_at(1_000);
+        data.price_data[AssetType::BonkSOL.index()].last_checked_time = 1_100;
+
+        // Only the shard whose asset is due is sent
+        let due = due(&header, &data, 1_150, None);
+        assert!(due.contains(&AssetType::INF) && !due.contains(&AssetType::BonkSOL) && !due.contains(&AssetType::StSOL));
+        assert_eq!(plan(&header, &due), vec![Update::PricesAndApys, Update::FeedShards, Update::SolPrice]);
+        assert_eq!(shard_feeds(&header, &due), vec![inf_feed]);
+        assert_eq!(plan(&header, &[AssetType::BonkSOL]), vec![Update::FeedShards]);
+        assert_eq!(shard_feeds(&header, &[AssetType::SOL]), vec![]);
+    }
+}
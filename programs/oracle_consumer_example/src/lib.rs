@@ -0,0 +1,75 @@
+//! Example consumer of the oracle's prices, kept in the workspace as a template for
+//! integrators and as an end-to-end test of the read interface. `log_collateral_value`
+//! values an amount of an asset the way a lending program would before acting on it:
+//!
+//! 1. A CPI to `assert_price_fresh` fails the instruction unless the oracle is not
+//!    emergency stopped and the asset's price was confirmed at most `max_age_secs` ago.
+//! 2. The price is then read in place from the data account with `oracles::layout`,
+//!    which costs no second CPI and no Borsh decoding of the whole account.
+//!
+//! Both oracle accounts are checked to be the oracle's PDAs before anything is read. The
+//! oracle checks them again in the CPI, but a consumer reading an account itself must
+//! never rely on a check it does not make.
+//!
+//! Only assets priced in the data account are read here; one moved to its own price
+//! account fails the CPI with `PriceNotAvailable`.
+use anchor_lang::prelude::*;
+use oracles::layout::PriceSlot;
+use oracles::price_oracle::{AssetType, PriceOracle};
+use oracles::program::Oracles;
+use oracles::switchboard_utils::PRICE_SCALE;
+
+declare_id!("F5D3291SxLvXgm2Dkt4PNB8fwkDspYcNQmmUzuN8PK42");
+
+#[program]
+pub mod oracle_consumer_example {
+    use super::*;
+
+    /// Logs and returns the value of `amount` base units of `asset_type`, in its quote
+    /// currency with 9 decimals. Fails unless the price is at most `max_age_secs` old and
+    /// the oracle is running.
+    pub fn log_collateral_value(ctx: Context<LogCollateralValue>, asset_type: AssetType, amount: u64, max_age_secs: u32) -> Result<u64> {
+        let accounts = oracles::cpi::accounts::AssertPriceFresh {
+            header: ctx.accounts.oracle_header.to_account_info(),
+            data: ctx.accounts.oracle_data.to_account_info(),
+            instructions: None,
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.oracle_program.to_account_info(), accounts);
+        oracles::cpi::assert_price_fresh(cpi_ctx, asset_type, max_age_secs, false)?;
+
+        let data = ctx.accounts.oracle_data.try_borrow_data()?;
+        let slot = PriceSlot::from_data_account(&data, asset_type).ok_or(ConsumerError::UnreadablePrice)?;
+        let quote = slot.quote().ok_or(ConsumerError::UnreadablePrice)?;
+        let value = u64::try_from(amount as u128 * slot.price() as u128 / PRICE_SCALE as u128)
+            .map_err(|_| ConsumerError::Overflow)?;
+        msg!("{} base units of {:?} are worth {} {:?}", amount, asset_type, value, quote);
+        Ok(value)
+    }
+}
+
+#[derive(Accounts)]
+pub struct LogCollateralValue<'info> {
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED],
+        bump,
+        seeds::program = oracles::ID,
+    )]
+    /// CHECK: the oracle's header PDA, read by the oracle in the CPI
+    pub oracle_header: UncheckedAccount<'info>,
+    #[account(
+        seeds = [PriceOracle::DATA_SEED],
+        bump,
+        seeds::program = oracles::ID,
+    )]
+    /// CHECK: the oracle's data PDA, whose discriminator `PriceSlot` checks before reading
+    pub oracle_data: UncheckedAccount<'info>,
+    pub oracle_program: Program<'info, Oracles>,
+}
+
+#[error_code]
+pub enum ConsumerError {
+    #[msg("The oracle's data account does not hold the asset's price")]
+    UnreadablePrice,
+    #[msg("The collateral value does not fit in a u64")]
+    Overflow,
+}
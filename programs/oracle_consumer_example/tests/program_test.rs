@@ -0,0 +1,158 @@
+//! Runs the example against the deployed oracle.
+//!
+//! Build both programs with `anchor build`, then run `cargo test-sbf` in this directory.
+//! A plain `cargo test` runs both programs natively instead.
+//! The oracle's header and data accounts are written straight into the bank, with the
+//! prices and flags each test needs.
+use anchor_lang::error::ErrorCode;
+use anchor_lang::solana_program::account_info::AccountInfo;
+use anchor_lang::solana_program::entrypoint::ProgramResult;
+use anchor_lang::{AccountSerialize, InstructionData, ToAccountMetas};
+use oracle_consumer_example::ConsumerError;
+use oracles::price_oracle::{AssetType, OracleError, PriceOracle, PriceOracleData, PriceOracleHeader, QuoteCurrency, DEFAULT_INSTANCE};
+use oracles::switchboard_utils::PRICE_SCALE;
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::account::Account;
+use solana_sdk::clock::Clock;
+use solana_sdk::instruction::{Instruction, InstructionError};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
+use solana_sdk::signature::Signer;
+use solana_sdk::transaction::{Transaction, TransactionError};
+
+const SOL_PRICE: u64 = 150 * PRICE_SCALE;
+const MAX_AGE_SECS: u32 = 60;
+
+/// An Anchor program's `entry` as a native processor. Anchor ties the account infos to the
+/// lifetime of their slice, so they are leaked for the rest of the test.
+macro_rules! native_entry {
+    ($entry:path) => {
+        processor!(|program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]| -> ProgramResult {
+            $entry(program_id, Box::leak(accounts.to_vec().into_boxed_slice()), data)
+        })
+    };
+}
+
+async fn start() -> ProgramTestContext {
+    let mut program_test = ProgramTest::new("oracle_consumer_example", oracle_consumer_example::ID, native_entry!(oracle_consumer_example::entry));
+    program_test.add_program("oracles", oracles::ID, native_entry!(oracles::entry));
+    program_test.start_with_context().await
+}
+
+fn write_oracle_account<T: AccountSerialize>(context: &mut ProgramTestContext, key: Pubkey, value: &T) {
+    let mut data = vec![];
+    value.try_serialize(&mut data).unwrap();
+    let account = Account { lamports: Rent::default().minimum_balance(data.len()), data, owner: oracles::ID, executable: false, rent_epoch: 0 };
+    context.set_account(&key, &account.into());
+}
+
+/// Writes the oracle's accounts with SOL at `SOL_PRICE`, last confirmed `age_secs` ago
+async fn write_oracle(context: &mut ProgramTestContext, age_secs: i64, emergency_stop: bool) {
+    let clock = context.banks_client.get_sysvar::<Clock>().await.unwrap();
+    let (header_key, header_bump) = PriceOracle::get_price_oracle_header_pda(&oracles::ID, &DEFAULT_INSTANCE);
+    let (data_key, data_bump) = PriceOracle::get_price_oracle_data_pda(&oracles::ID, &DEFAULT_INSTANCE);
+
+    let header = PriceOracleHeader { bump: header_bump, emergency_stop, ..PriceOracleHeader::default() };
+    let mut data = PriceOracleData { bump: data_bump, ..PriceOracleData::default() };
+    let sol = &mut data.price_data[AssetType::SOL.index()];
+    sol.price = SOL_PRICE;
+    sol.quote = QuoteCurrency::USD;
+    sol.last_update_time = clock.unix_timestamp - age_secs;
+    sol.last_update_slot = clock.slot.saturating_sub(1);
+    sol.last_checked_time = clock.unix_timestamp - age_secs;
+    sol.last_checked_slot = clock.slot.saturating_sub(1);
+    sol.feed_timestamp = clock.unix_timestamp - age_secs;
+    write_oracle_account(context, header_key, &header);
+    write_oracle_account(context, data_key, &data);
+}
+
+fn log_collateral_value_ix(data_key: Pubkey, asset_type: AssetType, amount: u64) -> Instruction {
+    Instruction {
+        program_id: oracle_consumer_example::ID,
+        accounts: oracle_consumer_example::accounts::LogCollateralValue {
+            oracle_header: PriceOracle::get_price_oracle_header_pda(&oracles::ID, &DEFAULT_INSTANCE).0,
+            oracle_data: data_key,
+            oracle_program: oracles::ID,
+        }.to_account_metas(None),
+        data: oracle_consumer_example::instruction::LogCollateralValue { asset_type, amount, max_age_secs: MAX_AGE_SECS }.data(),
+    }
+}
+
+/// Simulates `instruction` and returns the value it logged. The runtime drops the trailing
+/// zero bytes of return data, so the value is padded back to eight bytes.
+async fn collateral_value(context: &mut ProgramTestContext, instruction: Instruction) -> Result<u64, BanksClientError> {
+    let blockhash = context.get_new_latest_blockhash().await.unwrap();
+    let transaction = Transaction::new_signed_with_payer(&[instruction], Some(&context.payer.pubkey()), &[&context.payer], blockhash);
+    let simulation = context.banks_client.simulate_transaction(transaction).await?;
+    simulation.result.unwrap().map_err(BanksClientError::TransactionError)?;
+    let return_data = simulation.simulation_details.and_then(|details| details.return_data).unwrap();
+    assert_eq!(return_data.program_id, oracle_consumer_example::ID);
+    let mut bytes = [0u8; 8];
+    bytes[..return_data.data.len()].copy_from_slice(&return_data.data);
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn custom_error(result: Result<u64, BanksClientError>) -> u32 {
+    match result {
+        Err(BanksClientError::TransactionError(TransactionError::InstructionError(0, InstructionError::Custom(code)))) => code,
+        other => panic!("expected a custom error, got {:?}", other),
+    }
+}
+
+fn sol_ix(amount: u64) -> Instruction {
+    log_collateral_value_ix(PriceOracle::get_price_oracle_data_pda(&oracles::ID, &DEFAULT_INSTANCE).0, AssetType::SOL, amount)
+}
+
+#[tokio::test]
+async fn test_values_collateral_at_a_fresh_price() {
+    let mut context = start().await;
+    write_oracle(&mut context, 10, false).await;
+
+    // 2.5 SOL at $150
+    let value = collateral_value(&mut context, sol_ix(2_500_000_000)).await.unwrap();
+    assert_eq!(value, 375 * PRICE_SCALE);
+
+    // Close to `MAX_AGE_SECS` old, leaving the bank clock room to move
+    write_oracle(&mut context, MAX_AGE_SECS as i64 - 5, false).await;
+    assert_eq!(collateral_value(&mut context, sol_ix(PRICE_SCALE)).await.unwrap(), SOL_PRICE);
+
+    // Too large a value fails instead of wrapping
+    let result = collateral_value(&mut context, sol_ix(u64::MAX)).await;
+    assert_eq!(custom_error(result), u32::from(ConsumerError::Overflow));
+}
+
+#[tokio::test]
+async fn test_rejects_a_stale_price() {
+    let mut context = start().await;
+    write_oracle(&mut context, MAX_AGE_SECS as i64 + 1, false).await;
+    let result = collateral_value(&mut context, sol_ix(PRICE_SCALE)).await;
+    assert_eq!(custom_error(result), u32::from(OracleError::StaleData));
+
+    // An asset that was never priced is not available rather than stale
+    let data_key = PriceOracle::get_price_oracle_data_pda(&oracles::ID, &DEFAULT_INSTANCE).0;
+    let result = collateral_value(&mut context, log_collateral_value_ix(data_key, AssetType::MSOL, PRICE_SCALE)).await;
+    assert_eq!(custom_error(result), u32::from(OracleError::PriceNotAvailable));
+}
+
+#[tokio::test]
+async fn test_rejects_reads_during_an_emergency_stop() {
+    let mut context = start().await;
+    write_oracle(&mut context, 0, true).await;
+    let result = collateral_value(&mut context, sol_ix(PRICE_SCALE)).await;
+    assert_eq!(custom_error(result), u32::from(OracleError::EmergencyStop));
+}
+
+#[tokio::test]
+async fn test_rejects_an_account_that_is_not_the_oracle_data() {
+    let mut context = start().await;
+    write_oracle(&mut context, 0, false).await;
+
+    // A copy of the data account at another address, as an attacker would pass
+    let data_key = PriceOracle::get_price_oracle_data_pda(&oracles::ID, &DEFAULT_INSTANCE).0;
+    let copy = context.banks_client.get_account(data_key).await.unwrap().unwrap();
+    let fake = Pubkey::new_unique();
+    context.set_account(&fake, &copy.into());
+
+    let result = collateral_value(&mut context, log_collateral_value_ix(fake, AssetType::SOL, PRICE_SCALE)).await;
+    assert_eq!(custom_error(result), u32::from(ErrorCode::ConstraintSeeds));
+}
@@ -0,0 +1,137 @@
+//! What a deployment supports, for clients that discover its assets instead of hardcoding
+//! `AssetType`.
+//!
+//! `get_supported_assets` returns one `AssetInfo` per asset the program knows, in index
+//! order. An entry names its asset by the raw id rather than by `AssetType`, so a client
+//! built before an asset was added still decodes the whole list and can skip the ids it
+//! does not know. The list is a Borsh `Vec` with a `u32` length and ids are a `u8`, so
+//! nothing in the encoding stops at 32 assets. The 1 KiB of return data holds
+//! `MAX_SUPPORTED_ASSETS_RETURNED` entries; the list will take a starting id before the
+//! program supports more.
+use anchor_lang::prelude::*;
+use crate::price_oracle::{AssetType, PriceOracleData, PriceOracleHeader, QuoteCurrency};
+
+/// Longest `AssetType::symbol`
+pub const MAX_SYMBOL_LEN: usize = 8;
+/// Entries of the longest symbol `get_supported_assets` fits in the return data
+pub const MAX_SUPPORTED_ASSETS_RETURNED: usize = (anchor_lang::solana_program::program::MAX_RETURN_DATA - 4) / AssetInfo::MAX_LEN;
+
+/// Whether an asset is updated and served
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[repr(u8)]
+pub enum AssetStatus {
+    /// Updated and served
+    #[default]
+    Enabled = 0,
+    /// Updates suspended by `pause_asset` until `resume_asset`
+    Paused = 1,
+    /// Removed by `delist_asset`: no longer updated or served, only its history is kept
+    Delisted = 2,
+}
+
+/// One supported asset, as `get_supported_assets` returns it
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Debug, Default)]
+pub struct AssetInfo {
+    /// The asset's discriminant, its index in the per-asset arrays
+    pub id: u8,
+    /// Ticker, such as `"JitoSOL"`
+    pub symbol: String,
+    /// Mint registered with `set_asset_mint`, `Pubkey::default()` when unset
+    pub mint: Pubkey,
+    /// Decimals of the asset's token amounts, see `PriceOracleHeader::decimals`
+    pub decimals: u8,
+    /// Currency the asset's price is expressed in
+    pub quote: QuoteCurrency,
+    pub status: AssetStatus,
+    /// Time from which the asset is updated and served. Listed assets are active at once,
+    /// so it is `0` for all of them until activation delays exist.
+    pub activation_time: i64,
+}
+
+impl AssetInfo {
+    /// Largest serialized size, for the longest symbol
+    pub const MAX_LEN: usize = 1 // id
+        + 4 + MAX_SYMBOL_LEN // symbol
+        + 32 // mint
+        + 1 // decimals
+        + 1 // quote
+        + 1 // status
+        + 8; // activation_time
+
+    pub fn new(header: &PriceOracleHeader, data: &PriceOracleData, asset_type: AssetType) -> Self {
+        let price_data = &data.price_data[asset_type.index()];
+        let status = if price_data.delisted {
+            AssetStatus::Delisted
+        } else if header.is_paused(asset_type) {
+            AssetStatus::Paused
+        } else {
+            AssetStatus::Enabled
+        };
+        AssetInfo {
+            id: u8::from(asset_type),
+            symbol: asset_type.symbol().to_string(),
+            mint: header.asset_mints[asset_type.index()],
+            decimals: header.decimals(asset_type),
+            quote: price_data.quote,
+            status,
+            activation_time: 0,
+        }
+    }
+
+    /// The asset, or `None` for an id this build of the program does not know
+    pub fn asset_type(&self) -> Option<AssetType> {
+        AssetType::try_from(self.id).ok()
+    }
+
+    /// Whether a keeper should update the asset at `now`
+    pub fn is_active(&self, now: i64) -> bool {
+        self.status == AssetStatus::Enabled && self.activation_time <= now
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_asset_fits_the_return_data() {
+        for asset_type in AssetType::ALL {
+            assert!(asset_type.symbol().len() <= MAX_SYMBOL_LEN);
+        }
+        let longest = AssetInfo { id: u8::MAX, symbol: "x".repeat(MAX_SYMBOL_LEN), status: AssetStatus::Delisted, activation_time: i64::MAX, ..AssetInfo::default() };
+        assert_eq!(longest.try_to_vec().unwrap().len(), AssetInfo::MAX_LEN);
+        assert!(vec![longest; MAX_SUPPORTED_ASSETS_RETURNED].try_to_vec().unwrap().len() <= anchor_lang::solana_program::program::MAX_RETURN_DATA);
+        const { assert!(crate::price_oracle::ASSET_COUNT <= MAX_SUPPORTED_ASSETS_RETURNED) };
+    }
+
+    #[test]
+    fn test_status_and_unknown_ids() {
+        let mut header = PriceOracleHeader::default();
+        let mut data = PriceOracleData::default();
+        header.paused[AssetType::HSOL.index()] = true;
+        data.price_data[AssetType::BSOL.index()].delisted = true;
+        header.paused[AssetType::BSOL.index()] = true;
+        data.price_data[AssetType::SOL.index()].quote = QuoteCurrency::USD;
+        let mint = Pubkey::new_unique();
+        header.asset_mints[AssetType::JitoSOL.index()] = mint;
+        header.mint_decimals[AssetType::JitoSOL.index()] = 6;
+
+        let jitosol = AssetInfo::new(&header, &data, AssetType::JitoSOL);
+        let expected = AssetInfo { id: 5, symbol: "JitoSOL".to_string(), mint, decimals: 6, quote: QuoteCurrency::SOL, status: AssetStatus::Enabled, activation_time: 0 };
+        assert_eq!(jitosol, expected);
+        assert!(jitosol.is_active(0));
+        assert_eq!(AssetInfo::new(&header, &data, AssetType::SOL).quote, QuoteCurrency::USD);
+        assert_eq!(AssetInfo::new(&header, &data, AssetType::SOL).decimals, 9);
+        // Delisting outranks pausing
+        assert_eq!(AssetInfo::new(&header, &data, AssetType::BSOL).status, AssetStatus::Delisted);
+        let hsol = AssetInfo::new(&header, &data, AssetType::HSOL);
+        assert_eq!(hsol.status, AssetStatus::Paused);
+        assert!(!hsol.is_active(i64::MAX));
+
+        // An asset this build does not know decodes, and is recognized as unknown
+        let unknown = AssetInfo { id: crate::price_oracle::ASSET_COUNT as u8, activation_time: 100, ..jitosol.clone() };
+        assert_eq!(AssetInfo::try_from_slice(&unknown.try_to_vec().unwrap()).unwrap().asset_type(), None);
+        assert_eq!(jitosol.asset_type(), Some(AssetType::JitoSOL));
+        assert!(!unknown.is_active(99) && unknown.is_active(100));
+    }
+}
@@ -0,0 +1,204 @@
+//! Canonical encoding of the current price set for consumers on other chains.
+//!
+//! `attest_prices` encodes every listed, priced asset into a versioned payload suitable
+//! for posting as a Wormhole message, stamps it with the next `attestation_sequence` of
+//! the header and emits it. Integers are big-endian, as EVM receivers read them.
+//!
+//! | Offset | Size | Field |
+//! |--------|------|-------|
+//! | 0 | 4 | magic `FXOP` |
+//! | 4 | 1 | version, `1` |
+//! | 5 | 1 | decimals of every fixed-point value, `9` |
+//! | 6 | 8 | sequence, `u64`, one more than the previous attestation |
+//! | 14 | 8 | attestation time, `i64` unix seconds |
+//! | 22 | 1 | entry count |
+//! | 23 | 26 × count | entries, in asset id order |
+//!
+//! Each entry is the asset id (`AssetType` as `u8`), the quote currency (`0` USD, `1` SOL),
+//! the price and the APY as `u64` and the time the source produced the round that last
+//! confirmed the price as `i64`.
+//!
+//! A change to this layout needs a new version; receivers must reject versions they do
+//! not know, and sequences that do not increase.
+use anchor_lang::prelude::*;
+use crate::layout::{read_array, Field};
+use crate::price_oracle::{AssetType, OracleError, PriceOracleData, QuoteCurrency};
+use crate::switchboard_utils::PRICE_SCALE;
+
+pub const ATTESTATION_MAGIC: [u8; 4] = *b"FXOP";
+pub const ATTESTATION_VERSION: u8 = 1;
+/// Decimals of the fixed-point prices and APYs in the payload
+pub const ATTESTATION_DECIMALS: u8 = 9;
+const _: () = assert!(10u64.pow(ATTESTATION_DECIMALS as u32) == PRICE_SCALE);
+/// Size of the payload before the entries
+pub const ATTESTATION_HEADER_LEN: usize = 4 // magic
+    + 1 // version
+    + 1 // decimals
+    + 8 // sequence
+    + 8 // timestamp
+    + 1; // count
+/// Size of one entry
+pub const ATTESTATION_ENTRY_LEN: usize = 1 // asset
+    + 1 // quote
+    + 8 // price
+    + 8 // apy
+    + 8; // updated_at
+
+/// One asset of an attestation
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct AttestedPrice {
+    pub asset_type: AssetType,
+    pub quote: QuoteCurrency,
+    pub price: u64,
+    pub apy: u64,
+    /// Time the source produced the round that last confirmed the stored price, which
+    /// staleness is measured from
+    pub updated_at: i64,
+}
+
+/// A decoded attestation payload
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PriceAttestation {
+    pub sequence: u64,
+    pub timestamp: i64,
+    pub prices: Vec<AttestedPrice>,
+}
+
+impl PriceAttestation {
+    /// The assets of `data` that are listed and have a price, in asset id order
+    pub fn new(data: &PriceOracleData, sequence: u64, timestamp: i64) -> Self {
+        let prices = AssetType::ALL.iter().filter_map(|asset_type| {
+            let price_data = &data.price_data[asset_type.index()];
+            (!price_data.delisted && price_data.price != 0).then_some(AttestedPrice {
+                asset_type: *asset_type,
+                quote: price_data.quote,
+                price: price_data.price,
+                apy: price_data.apy,
+                updated_at: price_data.feed_timestamp,
+            })
+        }).collect();
+        PriceAttestation { sequence, timestamp, prices }
+    }
+
+    /// The canonical payload, see the module documentation
+    pub fn encode(&self) -> Vec<u8> {
+        let mut payload = Vec::with_capacity(ATTESTATION_HEADER_LEN + ATTESTATION_ENTRY_LEN * self.prices.len());
+        payload.extend_from_slice(&ATTESTATION_MAGIC);
+        payload.push(ATTESTATION_VERSION);
+        payload.push(ATTESTATION_DECIMALS);
+        payload.extend_from_slice(&self.sequence.to_be_bytes());
+        payload.extend_from_slice(&self.timestamp.to_be_bytes());
+        payload.push(self.prices.len() as u8);
+        for price in &self.prices {
+            payload.push(u8::from(price.asset_type));
+            payload.push(price.quote as u8);
+            payload.extend_from_slice(&price.price.to_be_bytes());
+            payload.extend_from_slice(&price.apy.to_be_bytes());
+            payload.extend_from_slice(&price.updated_at.to_be_bytes());
+        }
+        payload
+    }
+
+    /// Decodes a payload of this version, for tests and off-chain checks. Receivers on
+    /// other chains implement the same layout.
+    pub fn decode(payload: &[u8]) -> std::result::Result<Self, OracleError> {
+        if payload.len() < ATTESTATION_HEADER_LEN
+            || payload[..4] != ATTESTATION_MAGIC
+            || payload[4] != ATTESTATION_VERSION
+            || payload[5] != ATTESTATION_DECIMALS
+        {
+            return Err(OracleError::InvalidAccountData);
+        }
+        let count = payload[22] as usize;
+        if payload.len() != ATTESTATION_HEADER_LEN + ATTESTATION_ENTRY_LEN * count {
+            return Err(OracleError::InvalidAccountData);
+        }
+        let be_bytes = |bytes: &[u8], at: usize| read_array::<8>(bytes, Field::at(at, 8)).ok_or(OracleError::InvalidAccountData);
+        let prices = payload[ATTESTATION_HEADER_LEN..].chunks_exact(ATTESTATION_ENTRY_LEN).map(|entry| {
+            let quote = match entry[1] {
+                0 => QuoteCurrency::USD,
+                1 => QuoteCurrency::SOL,
+                _ => return Err(OracleError::InvalidAccountData),
+            };
+            Ok(AttestedPrice {
+                asset_type: AssetType::try_from(entry[0])?,
+                quote,
+                price: u64::from_be_bytes(be_bytes(entry, 2)?),
+                apy: u64::from_be_bytes(be_bytes(entry, 10)?),
+                updated_at: i64::from_be_bytes(be_bytes(entry, 18)?),
+            })
+        }).collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(PriceAttestation {
+            sequence: u64::from_be_bytes(be_bytes(payload, 6)?),
+            timestamp: u64::from_be_bytes(be_bytes(payload, 14)?) as i64,
+            prices,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /// SOL at $156.1052385, mSOL at 1.18 SOL yielding 7%, JitoSOL delisted and the rest unpriced
+    fn golden_data() -> PriceOracleData {
+        let mut data = PriceOracleData::default();
+        let sol = &mut data.price_data[AssetType::SOL.index()];
+        sol.price = 156_105_238_500;
+        sol.quote = QuoteCurrency::USD;
+        sol.last_update_time = 1_700_000_000;
+        sol.last_checked_time = 1_700_000_000;
+        sol.feed_timestamp = 1_700_000_000;
+        let msol = &mut data.price_data[AssetType::MSOL.index()];
+        msol.price = 1_180_000_000;
+        msol.apy = 70_000_000;
+        msol.quote = QuoteCurrency::SOL;
+        msol.last_update_time = 1_699_999_940;
+        msol.last_checked_time = 1_699_999_940;
+        msol.feed_timestamp = 1_699_999_940;
+        let jitosol = &mut data.price_data[AssetType::JitoSOL.index()];
+        jitosol.price = 1_100_000_000;
+        jitosol.delisted = true;
+        data
+    }
+
+    #[test]
+    fn test_golden_vectors() {
+        let attestation = PriceAttestation::new(&golden_data(), 42, 1_700_000_060);
+        assert_eq!(
+            hex(&attestation.encode()),
+            concat!(
+                "46584f50", "01", "09", "000000000000002a", "000000006553f13c", "02",
+                "03", "01", "0000000046555f00", "00000000042c1d80", "000000006553f0c4",
+                "06", "00", "000000245898e7e4", "0000000000000000", "000000006553f100",
+            ),
+        );
+
+        // Nothing priced still encodes, with no entries
+        let empty = PriceAttestation::new(&PriceOracleData::default(), 1, 0);
+        assert_eq!(hex(&empty.encode()), "46584f5001090000000000000001000000000000000000");
+    }
+
+    #[test]
+    fn test_decode_round_trip_and_rejections() {
+        let attestation = PriceAttestation::new(&golden_data(), u64::MAX, -1);
+        let payload = attestation.encode();
+        assert_eq!(payload.len(), ATTESTATION_HEADER_LEN + 2 * ATTESTATION_ENTRY_LEN);
+        assert_eq!(PriceAttestation::decode(&payload).unwrap(), attestation);
+
+        let mut wrong_version = payload.clone();
+        wrong_version[4] = 2;
+        let mut wrong_quote = payload.clone();
+        wrong_quote[ATTESTATION_HEADER_LEN + 1] = 2;
+        let mut wrong_asset = payload.clone();
+        wrong_asset[ATTESTATION_HEADER_LEN] = crate::price_oracle::ASSET_COUNT as u8;
+        for wrong in [&payload[..payload.len() - 1], &payload[1..], &wrong_version[..], &wrong_quote[..]] {
+            assert!(matches!(PriceAttestation::decode(wrong), Err(OracleError::InvalidAccountData)));
+        }
+        assert!(matches!(PriceAttestation::decode(&wrong_asset), Err(OracleError::InvalidAssetType)));
+    }
+}
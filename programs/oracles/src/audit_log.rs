@@ -0,0 +1,226 @@
+//! On-chain trail of privileged actions, for auditors.
+//!
+//! `initialize_audit_log` creates the log at `[AUDIT_LOG_SEED]` and makes it mandatory:
+//! from then on every privileged instruction fails with `AuditLogRequired` unless it is
+//! passed the log, and appends an entry to it before doing anything else. A failed
+//! instruction rolls its entry back with everything else.
+//!
+//! The log keeps the latest `AUDIT_LOG_CAPACITY` entries. Once full, each new entry
+//! overwrites the oldest; `total_entries` still counts every action ever recorded, so a gap
+//! between two reads shows as entries that were overwritten in between.
+//!
+//! `payload_hash` is the SHA-256 of the instruction data, discriminator included, which
+//! matches an entry to the instruction that made it. Layout migrations are not recorded:
+//! they run before the accounts can be read in the current layout.
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hash;
+use crate::price_oracle::AssetType;
+
+/// Number of entries kept before the oldest is overwritten
+pub const AUDIT_LOG_CAPACITY: usize = 32;
+/// Most entries `get_audit_log` returns, so they fit the 1 KiB of return data
+pub const MAX_AUDIT_ENTRIES_RETURNED: usize = 13;
+
+/// Privileged instruction an audit entry records. Codes are only ever appended.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[repr(u8)]
+pub enum AuditAction {
+    /// Ring slot not written yet
+    #[default]
+    None = 0,
+    InitializeAuditLog = 1,
+    InitializeRegistry = 2,
+    SetAssetFeed = 3,
+    SetAssetSources = 4,
+    SetAssetStakePool = 5,
+    SetAssetSource = 6,
+    SetAssetQuote = 7,
+    DelistAsset = 8,
+    ResetWatermarks = 9,
+    SetSubscriber = 10,
+    InitializeHistory = 11,
+    ProposeConfig = 12,
+    ApplyConfig = 13,
+    CancelConfig = 14,
+    SetEmergencyStop = 15,
+    ApproveResume = 16,
+    ExecuteResume = 17,
+    PauseAsset = 18,
+    ResumeAsset = 19,
+    SetMinStopChangeInterval = 20,
+    SetOperator = 21,
+    SetEmergencyCouncil = 22,
+    SetTrustedPublisher = 23,
+    SetSwitchboardProgramId = 24,
+    LockOracle = 25,
+    SetLeasePolicy = 26,
+    SetLargeMovePolicy = 27,
+    SetMoveConfirmation = 28,
+    SetApyChangeLimit = 29,
+    SetHaircut = 30,
+    SetPartialUpdates = 31,
+    SetAssetMaxAge = 32,
+    SetMaxFeedAgeSlots = 33,
+    SetUpdatesPaused = 34,
+    CloseCheckpoint = 35,
+    SetThreadAuthority = 36,
+    InitializeApyHistory = 37,
+    SetMinApySamples = 38,
+    SetThreshold = 39,
+    ClearThreshold = 40,
+    ProposeFeedMapping = 41,
+    ApplyFeedMapping = 42,
+    CancelFeedMapping = 43,
+    SetTrustWeights = 44,
+    ForceUpdate = 45,
+    SetAssetMint = 46,
+    SetYieldConvention = 47,
+    ReinitializeData = 48,
+    SetSourcePolicy = 49,
+    SetResumeGrace = 50,
+    PruneHistory = 51,
+    PruneEpochSnapshots = 52,
+    SetWatcher = 53,
+    TriggerEmergencyStopCpi = 54,
+    ProposeFeedShard = 55,
+    SetAssetAuthority = 56,
+    SetOnDemandQueue = 57,
+    ProposeAssetFallback = 58,
+    SetPythFeedId = 59,
+    ApplyAssetFallback = 60,
+    CancelAssetFallback = 61,
+    ApplyFeedShard = 62,
+    CancelFeedShard = 63,
+}
+
+/// One recorded privileged action
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct AuditEntry {
+    pub timestamp: i64,
+    /// Signer the instruction was authorized by, or its payer when anyone may send it
+    pub actor: Pubkey,
+    pub action: AuditAction,
+    /// Asset the action applies to, `None` for oracle-wide actions
+    pub subject_asset: Option<AssetType>,
+    /// SHA-256 of the instruction data, discriminator included
+    pub payload_hash: [u8; 32],
+}
+
+impl AuditEntry {
+    /// Largest serialized size, with a subject asset
+    pub const LEN: usize = 8 // timestamp
+        + 32 // actor
+        + 1 // action
+        + 2 // subject_asset
+        + 32; // payload_hash
+
+    pub fn new(timestamp: i64, actor: Pubkey, action: AuditAction, subject_asset: Option<AssetType>, instruction_data: &[u8]) -> Self {
+        AuditEntry { timestamp, actor, action, subject_asset, payload_hash: hash(instruction_data).to_bytes() }
+    }
+}
+
+/// Ring of the latest privileged actions, see the module documentation
+#[account]
+#[derive(Default)]
+pub struct AuditLog {
+    pub bump: u8,
+    /// Slot the next entry is written to
+    pub next_index: u8,
+    /// Entries ever recorded, overwritten ones included
+    pub total_entries: u64,
+    pub entries: [AuditEntry; AUDIT_LOG_CAPACITY],
+}
+
+impl AuditLog {
+    /// Largest serialized size, excluding the 8-byte account discriminator
+    pub const LEN: usize = 1 // bump
+        + 1 // next_index
+        + 8 // total_entries
+        + AuditEntry::LEN * AUDIT_LOG_CAPACITY; // entries
+
+    /// Appends an entry, overwriting the oldest once full
+    pub fn record(&mut self, entry: AuditEntry) {
+        self.entries[self.next_index as usize] = entry;
+        self.next_index = ((self.next_index as usize + 1) % AUDIT_LOG_CAPACITY) as u8;
+        self.total_entries = self.total_entries.saturating_add(1);
+    }
+
+    /// Number of entries held, at most `AUDIT_LOG_CAPACITY`
+    pub fn len(&self) -> usize {
+        self.total_entries.min(AUDIT_LOG_CAPACITY as u64) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total_entries == 0
+    }
+
+    /// Up to `count` of the held entries, newest first
+    pub fn recent(&self, count: usize) -> Vec<AuditEntry> {
+        (1..=count.min(self.len()))
+            .map(|age| self.entries[(self.next_index as usize + AUDIT_LOG_CAPACITY - age) % AUDIT_LOG_CAPACITY])
+            .collect()
+    }
+}
+
+/// Latest entries of the audit log, as returned by `get_audit_log`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Debug)]
+pub struct AuditLogPage {
+    /// Entries ever recorded, overwritten ones included
+    pub total_entries: u64,
+    /// Newest first
+    pub entries: Vec<AuditEntry>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(timestamp: i64) -> AuditEntry {
+        AuditEntry::new(timestamp, Pubkey::new_unique(), AuditAction::SetHaircut, Some(AssetType::MSOL), &timestamp.to_le_bytes())
+    }
+
+    #[test]
+    fn test_account_len_matches_serialized_size() {
+        let log = AuditLog {
+            bump: u8::MAX,
+            next_index: u8::MAX,
+            total_entries: u64::MAX,
+            entries: [entry(i64::MAX); AUDIT_LOG_CAPACITY],
+        };
+        assert_eq!(log.try_to_vec().unwrap().len(), AuditLog::LEN);
+
+        // The largest page still fits the return data
+        let page = AuditLogPage { total_entries: u64::MAX, entries: vec![entry(i64::MAX); MAX_AUDIT_ENTRIES_RETURNED] };
+        assert!(page.try_to_vec().unwrap().len() <= anchor_lang::solana_program::program::MAX_RETURN_DATA);
+    }
+
+    #[test]
+    fn test_ring_overwrites_the_oldest_and_counts_everything() {
+        let mut log = AuditLog::default();
+        assert!(log.is_empty() && log.recent(5).is_empty());
+
+        for timestamp in 1..=3 {
+            log.record(entry(timestamp));
+        }
+        let timestamps = |entries: Vec<AuditEntry>| entries.iter().map(|entry| entry.timestamp).collect::<Vec<_>>();
+        assert_eq!(timestamps(log.recent(10)), vec![3, 2, 1]);
+        assert_eq!(timestamps(log.recent(2)), vec![3, 2]);
+
+        for timestamp in 4..=AUDIT_LOG_CAPACITY as i64 + 5 {
+            log.record(entry(timestamp));
+        }
+        let newest = AUDIT_LOG_CAPACITY as i64 + 5;
+        assert_eq!((log.len(), log.total_entries), (AUDIT_LOG_CAPACITY, newest as u64));
+        let held = timestamps(log.recent(usize::MAX));
+        assert_eq!(held.len(), AUDIT_LOG_CAPACITY);
+        assert_eq!((held[0], held[AUDIT_LOG_CAPACITY - 1]), (newest, 6));
+    }
+
+    #[test]
+    fn test_payload_hash_covers_the_instruction_data() {
+        let actor = Pubkey::new_unique();
+        let entry = AuditEntry::new(1, actor, AuditAction::SetOperator, None, b"data");
+        assert_eq!(entry.payload_hash, hash(b"data").to_bytes());
+        assert_ne!(entry.payload_hash, AuditEntry::new(1, actor, AuditAction::SetOperator, None, b"date").payload_hash);
+    }
+}
@@ -0,0 +1,115 @@
+//! Baskets of assets defined by integrators, priced as one weighted value.
+//!
+//! `create_basket` creates a `Basket` at `[BASKET_SEED, creator, id]`, paid for and owned by
+//! its creator, with a weight in basis points for each component. The weights are positive,
+//! name each asset at most once and add up to `BASKET_TOTAL_WEIGHT_BPS`. Only the creator
+//! may change them with `update_basket_weights` or close the basket to recover its rent.
+//!
+//! `get_basket_price` values one unit of the basket in USD: the weighted sum of each
+//! component's USD price. Every component must be usable as for `get_collateral_value`:
+//! listed, priced in the data account, not paused and fresh.
+use anchor_lang::prelude::*;
+use crate::price_oracle::{AssetType, OracleError, PriceOracleData, ASSET_COUNT};
+
+/// Sum of a basket's weights
+pub const BASKET_TOTAL_WEIGHT_BPS: u16 = 10_000;
+
+/// One component of a basket, as passed to `create_basket` and `update_basket_weights`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct BasketWeight {
+    pub asset_type: AssetType,
+    pub weight_bps: u16,
+}
+
+/// A creator's basket, see the module documentation
+#[account]
+#[derive(Default)]
+pub struct Basket {
+    /// Payer of the rent and the only key allowed to change or close the basket
+    pub creator: Pubkey,
+    pub id: u64,
+    pub bump: u8,
+    /// Indexed by `AssetType::index`, zero for assets outside the basket
+    pub weights_bps: [u16; ASSET_COUNT],
+}
+
+impl Basket {
+    /// Serialized size, excluding the 8-byte account discriminator
+    pub const LEN: usize = 32 // creator
+        + 8 // id
+        + 1 // bump
+        + 2 * ASSET_COUNT; // weights_bps
+
+    /// Replaces the weights with `weights`, which must be valid and name no delisted asset
+    pub fn set_weights(&mut self, data: &PriceOracleData, weights: &[BasketWeight]) -> Result<()> {
+        let mut weights_bps = [0u16; ASSET_COUNT];
+        let mut total: u32 = 0;
+        for weight in weights {
+            let slot = &mut weights_bps[weight.asset_type.index()];
+            if weight.weight_bps == 0 || *slot != 0 {
+                msg!("{:?} must appear once, with a positive weight", weight.asset_type);
+                return Err(error!(OracleError::InvalidBasketWeights));
+            }
+            data.listed(weight.asset_type)?;
+            *slot = weight.weight_bps;
+            total += weight.weight_bps as u32;
+        }
+        if total != BASKET_TOTAL_WEIGHT_BPS as u32 {
+            msg!("Basket weights add up to {} bps, not {}", total, BASKET_TOTAL_WEIGHT_BPS);
+            return Err(error!(OracleError::InvalidBasketWeights));
+        }
+        self.weights_bps = weights_bps;
+        Ok(())
+    }
+
+    /// The basket's components in asset order
+    pub fn weights(&self) -> Vec<BasketWeight> {
+        AssetType::ALL
+            .into_iter()
+            .filter(|asset_type| self.weights_bps[asset_type.index()] > 0)
+            .map(|asset_type| BasketWeight { asset_type, weight_bps: self.weights_bps[asset_type.index()] })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn weight(asset_type: AssetType, weight_bps: u16) -> BasketWeight {
+        BasketWeight { asset_type, weight_bps }
+    }
+
+    #[test]
+    fn test_account_len_matches_serialized_size() {
+        assert_eq!(Basket::default().try_to_vec().unwrap().len(), Basket::LEN);
+    }
+
+    #[test]
+    fn test_set_weights_validates_the_components() {
+        let mut data = PriceOracleData::default();
+        let mut basket = Basket::default();
+        let weights = [weight(AssetType::JitoSOL, 5_000), weight(AssetType::MSOL, 3_000), weight(AssetType::BSOL, 2_000)];
+        basket.set_weights(&data, &weights).unwrap();
+        assert_eq!(basket.weights(), vec![weight(AssetType::BSOL, 2_000), weight(AssetType::MSOL, 3_000), weight(AssetType::JitoSOL, 5_000)]);
+
+        let invalid = [
+            vec![],
+            vec![weight(AssetType::JitoSOL, 5_000), weight(AssetType::MSOL, 4_999)],
+            vec![weight(AssetType::JitoSOL, 5_000), weight(AssetType::MSOL, 5_001)],
+            vec![weight(AssetType::JitoSOL, 5_000), weight(AssetType::JitoSOL, 5_000)],
+            vec![weight(AssetType::JitoSOL, 10_000), weight(AssetType::MSOL, 0)],
+        ];
+        for weights in invalid {
+            let err = basket.set_weights(&data, &weights).unwrap_err();
+            assert_eq!(err, error!(OracleError::InvalidBasketWeights), "{:?}", weights);
+        }
+
+        // A failed change keeps the previous weights
+        assert_eq!(basket.weights_bps[AssetType::JitoSOL.index()], 5_000);
+
+        data.price_data[AssetType::MSOL.index()].delisted = true;
+        let err = basket.set_weights(&data, &weights).unwrap_err();
+        assert_eq!(err, error!(OracleError::AssetDelisted));
+    }
+}
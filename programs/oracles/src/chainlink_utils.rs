@@ -0,0 +1,178 @@
+//! Reads Chainlink OCR2 feeds straight from the store program's `Transmissions` accounts.
+//!
+//! Layout (after the 8-byte Anchor discriminator): a packed header of
+//! `TRANSMISSIONS_HEADER_SIZE` bytes followed by `live_length` `Transmission` rows used
+//! as a ring buffer; the latest round sits just before `live_cursor`.
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::pubkey;
+use anchor_lang::solana_program::hash::hash;
+use crate::layout::{read_array, Field};
+use switchboard_v2::SwitchboardDecimal;
+use crate::price_oracle::{Confidence, MaxAge, OracleError, SourcePrice};
+use crate::switchboard_utils::{fixed_to_f64, switchboard_decimal_to_price};
+
+/// Chainlink OCR2 store program, the owner of every feed account
+pub const CHAINLINK_STORE_PROGRAM_ID: Pubkey = pubkey!("HEvSKofvBgfaexv23kMabbYqxasxU3mQ4ibBMEmJWHny");
+/// Chainlink SOL/USD feed on devnet and mainnet
+pub const CHAINLINK_SOL_USD_FEED: &str = "CH31Xns5z3M1cTAbKW34jcxPPciazARpijcHj9rxtemt";
+
+/// Size reserved for the `Transmissions` header, excluding the discriminator
+pub const TRANSMISSIONS_HEADER_SIZE: usize = 192;
+/// Size of one `Transmission` row
+pub const TRANSMISSION_LEN: usize = 48;
+
+// Offsets into the packed header, relative to the end of the discriminator
+const DECIMALS_OFFSET: usize = 130;
+const LATEST_ROUND_ID_OFFSET: usize = 135;
+const LIVE_LENGTH_OFFSET: usize = 140;
+const LIVE_CURSOR_OFFSET: usize = 144;
+
+/// Latest round of a Chainlink feed
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ChainlinkRound {
+    pub round_id: u32,
+    pub slot: u64,
+    pub timestamp: u32,
+    /// Raw answer with `decimals` decimals
+    pub answer: i128,
+    pub decimals: u8,
+}
+
+impl ChainlinkRound {
+    /// Answer in fixed point with `PRICE_DECIMALS` decimals
+    pub fn price(&self) -> std::result::Result<u64, OracleError> {
+        let decimal = SwitchboardDecimal { mantissa: self.answer, scale: self.decimals as u32 };
+        switchboard_decimal_to_price(&decimal).map_err(|_| OracleError::InvalidChainlinkData)
+    }
+}
+
+/// Anchor discriminator of the store program's `Transmissions` account
+pub fn transmissions_discriminator() -> [u8; 8] {
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&hash(b"account:Transmissions").to_bytes()[..8]);
+    discriminator
+}
+
+/// Decodes the latest round from the raw data of a `Transmissions` account
+pub fn decode_latest_round(data: &[u8]) -> std::result::Result<ChainlinkRound, OracleError> {
+    if data.len() < 8 + TRANSMISSIONS_HEADER_SIZE || data[..8] != transmissions_discriminator() {
+        return Err(OracleError::InvalidChainlinkAccount);
+    }
+    let header = &data[8..8 + TRANSMISSIONS_HEADER_SIZE];
+    let rows = &data[8 + TRANSMISSIONS_HEADER_SIZE..];
+
+    let live_length = read_u32(header, LIVE_LENGTH_OFFSET)? as usize;
+    let live_cursor = read_u32(header, LIVE_CURSOR_OFFSET)? as usize;
+    let round_id = read_u32(header, LATEST_ROUND_ID_OFFSET)?;
+    if live_length == 0 || rows.len() < live_length * TRANSMISSION_LEN || round_id == 0 {
+        return Err(OracleError::InvalidChainlinkData);
+    }
+
+    let index = (live_cursor + live_length - 1) % live_length;
+    let row = &rows[index * TRANSMISSION_LEN..(index + 1) * TRANSMISSION_LEN];
+    Ok(ChainlinkRound {
+        round_id,
+        slot: read_array(row, Field::at(0, 8)).map(u64::from_le_bytes).ok_or(OracleError::InvalidChainlinkData)?,
+        timestamp: read_u32(row, 8)?,
+        answer: read_array(row, Field::at(16, 16)).map(i128::from_le_bytes).ok_or(OracleError::InvalidChainlinkData)?,
+        decimals: header[DECIMALS_OFFSET],
+    })
+}
+
+/// Loads a Chainlink feed after checking its owner and that its latest round is no
+/// older than `max_age`, and returns the answer in fixed point with the round's slot and time
+pub fn get_chainlink_price(feed: &AccountInfo, clock: &Clock, max_age: MaxAge) -> Result<SourcePrice> {
+    if feed.owner != &CHAINLINK_STORE_PROGRAM_ID {
+        msg!("Invalid Chainlink account owner: expected {}, found {}", CHAINLINK_STORE_PROGRAM_ID, feed.owner);
+        return Err(error!(OracleError::InvalidChainlinkAccount));
+    }
+
+    let round = decode_latest_round(&feed.try_borrow_data()?).map_err(|e| {
+        msg!("Failed to decode Chainlink feed {}: {:?}", feed.key, e);
+        error!(e)
+    })?;
+    if max_age.is_exceeded(round.timestamp as i64, round.slot, clock) {
+        msg!("Chainlink feed {} is stale: round {} at {} (slot {}), max age {:?}",
+            feed.key, round.round_id, round.timestamp, round.slot, max_age);
+        return Err(error!(OracleError::StaleData));
+    }
+
+    let price = round.price().map_err(|e| {
+        msg!("Chainlink answer cannot be represented: answer={}, decimals={}", round.answer, round.decimals);
+        error!(e)
+    })?;
+    verbose_msg!("Chainlink result converted successfully: {}", fixed_to_f64(price));
+    // Chainlink rounds publish a single answer with no spread
+    Ok(SourcePrice { price, round_slot: round.slot, round_timestamp: round.timestamp as i64, confidence: Confidence::default() })
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> std::result::Result<u32, OracleError> {
+    read_array(bytes, Field::at(offset, 4)).map(u32::from_le_bytes).ok_or(OracleError::InvalidChainlinkData)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::switchboard_utils::PRICE_SCALE;
+    use crate::test_utils::{clock_at, MockChainlinkFeed};
+
+    #[test]
+    fn test_decode_latest_round_follows_live_cursor() {
+        // Three live rows; the cursor wrapped to 1, so row 0 is the latest
+        let data = MockChainlinkFeed::new(8)
+            .live_rows(3)
+            .round(0, 17, 1_000, 500, 15_612_345_678)
+            .round(1, 15, 900, 400, 1)
+            .round(2, 16, 950, 450, 2)
+            .live_cursor(1)
+            .to_account_data();
+        let round = decode_latest_round(&data).unwrap();
+        assert_eq!(round, ChainlinkRound { round_id: 17, slot: 500, timestamp: 1_000, answer: 15_612_345_678, decimals: 8 });
+        assert_eq!(round.price().unwrap(), 156_123_456_780);
+
+        // A cursor at the start wraps back to the last row
+        let data = MockChainlinkFeed::new(8).live_rows(3).round(2, 16, 950, 450, 2).live_cursor(0).to_account_data();
+        assert_eq!(decode_latest_round(&data).unwrap().answer, 2);
+    }
+
+    #[test]
+    fn test_decode_rejects_malformed_accounts() {
+        let valid = MockChainlinkFeed::new(8).live_rows(2).round(0, 1, 1_000, 500, 1).live_cursor(1).to_account_data();
+
+        let mut wrong_discriminator = valid.clone();
+        wrong_discriminator[0] ^= 0xff;
+        assert!(matches!(decode_latest_round(&wrong_discriminator), Err(OracleError::InvalidChainlinkAccount)));
+        assert!(matches!(decode_latest_round(&valid[..8 + TRANSMISSIONS_HEADER_SIZE - 1]), Err(OracleError::InvalidChainlinkAccount)));
+        // Header claims more rows than the account holds
+        assert!(matches!(decode_latest_round(&valid[..valid.len() - 1]), Err(OracleError::InvalidChainlinkData)));
+        // No round written yet
+        let empty = MockChainlinkFeed::new(8).live_rows(2).to_account_data();
+        assert!(matches!(decode_latest_round(&empty), Err(OracleError::InvalidChainlinkData)));
+    }
+
+    #[test]
+    fn test_chainlink_round_price_conversion() {
+        let round = |answer: i128, decimals: u8| ChainlinkRound { round_id: 1, slot: 0, timestamp: 0, answer, decimals };
+        assert_eq!(round(100, 0).price().unwrap(), 100 * PRICE_SCALE);
+        // Rounded half away from zero past 9 decimals
+        assert_eq!(round(15_000_000_050, 11).price().unwrap(), 150_000_001);
+        assert!(matches!(round(-1, 8).price(), Err(OracleError::InvalidChainlinkData)));
+    }
+
+    #[test]
+    fn test_get_chainlink_price_checks_owner_and_staleness() {
+        let feed = MockChainlinkFeed::new(8).live_rows(2).round(0, 7, 1_000, 5_000, 15_600_000_000).live_cursor(1);
+        let mut account = feed.into_account(Pubkey::new_unique());
+
+        let price = get_chainlink_price(&account.account_info(), &clock_at(1_060, 5_100), MaxAge::Seconds(60)).unwrap();
+        assert_eq!(price, SourcePrice { price: 156 * PRICE_SCALE, round_slot: 5_000, round_timestamp: 1_000, confidence: Confidence::default() });
+        let err = get_chainlink_price(&account.account_info(), &clock_at(1_061, 5_100), MaxAge::Seconds(60)).unwrap_err();
+        assert_eq!(err, error!(OracleError::StaleData));
+        let err = get_chainlink_price(&account.account_info(), &clock_at(1_000, 5_101), MaxAge::Slots(100)).unwrap_err();
+        assert_eq!(err, error!(OracleError::StaleData));
+
+        account.owner = Pubkey::new_unique();
+        let err = get_chainlink_price(&account.account_info(), &clock_at(1_000, 5_000), MaxAge::Seconds(60)).unwrap_err();
+        assert_eq!(err, error!(OracleError::InvalidChainlinkAccount));
+    }
+}
@@ -0,0 +1,153 @@
+//! Frozen copies of the oracle's state, for settling disputes over what it served.
+//!
+//! `create_checkpoint` copies the header flags and every asset's price, APY and timestamps
+//! into a new account at `[CHECKPOINT_SEED, id]`, where `id` is chosen by the caller: the
+//! current slot, or any nonce. Anyone may create one and pays its rent, which is the only
+//! limit on how many exist. No instruction writes a checkpoint once created; the admin may
+//! only close one older than `CHECKPOINT_MIN_CLOSE_AGE_SECS`, refunding its creator.
+use anchor_lang::prelude::*;
+use crate::price_oracle::{AssetType, PriceData, PriceOracleData, PriceOracleHeader, QuoteCurrency, ASSET_COUNT};
+
+/// Age a checkpoint must reach before the admin can close it
+pub const CHECKPOINT_MIN_CLOSE_AGE_SECS: i64 = 365 * 86_400;
+
+/// One asset as the oracle held it when the checkpoint was taken
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct CheckpointPrice {
+    pub price: u64,
+    pub apy: u64,
+    pub quote: QuoteCurrency,
+    /// Time and slot the price last changed
+    pub last_update_time: i64,
+    pub last_update_slot: u64,
+    /// Time a feed round last confirmed the price, which staleness is measured from
+    pub last_checked_time: i64,
+    pub delisted: bool,
+    pub paused: bool,
+}
+
+impl CheckpointPrice {
+    /// Serialized size
+    pub const LEN: usize = 8 // price
+        + 8 // apy
+        + 1 // quote
+        + 8 // last_update_time
+        + 8 // last_update_slot
+        + 8 // last_checked_time
+        + 1 // delisted
+        + 1; // paused
+
+    pub fn new(price_data: &PriceData, paused: bool) -> Self {
+        CheckpointPrice {
+            price: price_data.price,
+            apy: price_data.apy,
+            quote: price_data.quote,
+            last_update_time: price_data.last_update_time,
+            last_update_slot: price_data.last_update_slot,
+            last_checked_time: price_data.last_checked_time,
+            delisted: price_data.delisted,
+            paused,
+        }
+    }
+}
+
+/// The oracle's state at one moment, see the module documentation
+#[account]
+#[derive(Default, PartialEq, Eq, Debug)]
+pub struct Checkpoint {
+    pub id: u64,
+    pub bump: u8,
+    /// Payer of the rent, refunded when the checkpoint is closed
+    pub creator: Pubkey,
+    pub slot: u64,
+    pub timestamp: i64,
+    pub emergency_stop: bool,
+    pub updates_paused: bool,
+    pub locked: bool,
+    /// `PriceOracleData::nonce` at the time
+    pub data_nonce: u64,
+    /// Indexed by `AssetType::index`
+    pub prices: [CheckpointPrice; ASSET_COUNT],
+}
+
+impl Checkpoint {
+    /// Serialized size, excluding the 8-byte account discriminator
+    pub const LEN: usize = 8 // id
+        + 1 // bump
+        + 32 // creator
+        + 8 // slot
+        + 8 // timestamp
+        + 1 // emergency_stop
+        + 1 // updates_paused
+        + 1 // locked
+        + 8 // data_nonce
+        + CheckpointPrice::LEN * ASSET_COUNT; // prices
+
+    /// The state held by the header and the data account
+    pub fn capture(id: u64, bump: u8, creator: Pubkey, header: &PriceOracleHeader, data: &PriceOracleData, clock: &Clock) -> Self {
+        Checkpoint {
+            id,
+            bump,
+            creator,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+            emergency_stop: header.emergency_stop,
+            updates_paused: header.updates_paused,
+            locked: header.locked,
+            data_nonce: data.nonce,
+            prices: AssetType::ALL.map(|asset_type| CheckpointPrice::new(&data.price_data[asset_type.index()], header.is_paused(asset_type))),
+        }
+    }
+
+    pub fn price(&self, asset_type: AssetType) -> &CheckpointPrice {
+        &self.prices[asset_type.index()]
+    }
+}
+
+// `get_checkpoint` returns the whole account
+const _: () = assert!(Checkpoint::LEN <= anchor_lang::solana_program::program::MAX_RETURN_DATA);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_account_len_matches_serialized_size() {
+        // Every field is fixed size, so the default value has the full length
+        assert_eq!(Checkpoint::default().try_to_vec().unwrap().len(), Checkpoint::LEN);
+    }
+
+    #[test]
+    fn test_capture_copies_flags_and_prices() {
+        let mut header = PriceOracleHeader { emergency_stop: true, locked: true, ..PriceOracleHeader::default() };
+        header.paused[AssetType::MSOL.index()] = true;
+        let mut data = PriceOracleData { nonce: 7, ..PriceOracleData::default() };
+        let msol = &mut data.price_data[AssetType::MSOL.index()];
+        msol.price = 1_180_000_000;
+        msol.apy = 70_000_000;
+        msol.quote = QuoteCurrency::SOL;
+        msol.last_update_time = 1_699_999_940;
+        msol.last_update_slot = 250_000_000;
+        msol.last_checked_time = 1_699_999_990;
+        data.price_data[AssetType::JitoSOL.index()].delisted = true;
+        let clock = Clock { slot: 250_000_100, unix_timestamp: 1_700_000_000, ..Clock::default() };
+        let creator = Pubkey::new_unique();
+
+        let checkpoint = Checkpoint::capture(42, 254, creator, &header, &data, &clock);
+        assert_eq!((checkpoint.id, checkpoint.bump, checkpoint.creator), (42, 254, creator));
+        assert_eq!((checkpoint.slot, checkpoint.timestamp, checkpoint.data_nonce), (250_000_100, 1_700_000_000, 7));
+        assert!(checkpoint.emergency_stop && checkpoint.locked && !checkpoint.updates_paused);
+        assert_eq!(*checkpoint.price(AssetType::MSOL), CheckpointPrice {
+            price: 1_180_000_000,
+            apy: 70_000_000,
+            quote: QuoteCurrency::SOL,
+            last_update_time: 1_699_999_940,
+            last_update_slot: 250_000_000,
+            last_checked_time: 1_699_999_990,
+            delisted: false,
+            paused: true,
+        });
+        assert!(checkpoint.price(AssetType::JitoSOL).delisted);
+        assert_eq!(*checkpoint.price(AssetType::SOL), CheckpointPrice::default());
+    }
+}
@@ -0,0 +1,1113 @@
+//! Off-chain helpers for clients of the oracle program: asset conversions, account
+//! addresses and instruction builders, kept next to the on-chain definitions they mirror.
+//!
+//! # Instances
+//!
+//! One deployed program can host several oracles, each `initialize`d under its own
+//! `instance` key, which follows the family seed in every one of its PDAs. Address
+//! functions and builders take the instance first; `DEFAULT_INSTANCE` is the oracle at the
+//! addresses from before instances existed. Each instance has its own admin, so pin the one
+//! you trust rather than taking it from user input.
+//!
+//! # Detecting concurrent changes
+//!
+//! `PriceOracleData::nonce` advances by one with every instruction that changes the data
+//! account, and `PriceDataChanged` reports the new value. A client that read the data can
+//! compare nonces to know whether it is acting on the latest state. The nonce wraps at
+//! `u64::MAX`, so compare for equality, never order.
+//!
+//! The same event carries `state_hash`, which the header keeps as `state_hash`: a SHA-256
+//! commitment to every asset's price, APY and feed timestamp. `PriceOracleData::state_hash`
+//! recomputes it from a copy of the data, and `state_encoding` gives the bytes it hashes.
+//!
+//! # Audit log
+//!
+//! Once `initialize_audit_log` has run, every privileged instruction fails unless it is
+//! passed `audit_log_address(instance)`. Builders of privileged instructions here take a
+//! `with_audit_log` flag for it. `get_audit_log_ix` returns the latest entries;
+//! `decode_audit_log` reads any number of them from the account itself.
+//!
+//! # Checkpoints
+//!
+//! `create_checkpoint_ix` freezes the current state into a new account that is never
+//! written again. Read it back with `get_checkpoint_ix`, or fetch
+//! `checkpoint_address(instance, id)` and decode it with `decode_checkpoint`.
+//!
+//! # Quote buffers
+//!
+//! A consumer that cannot read the oracle's PDAs creates a buffer with
+//! `create_quote_buffer_ix`, choosing its size and the offset quotes land at, then has
+//! `write_quote_to_ix` sent whenever it needs a fresh quote. `decode_pushed_quote` reads the
+//! quote back; its layout is documented in `quote_buffer`.
+//!
+//! # Supported assets
+//!
+//! Rather than hardcoding `AssetType`, a client can simulate `get_supported_assets_ix` and
+//! read the list with `decode_supported_assets`. Each `AssetInfo` carries the asset's raw
+//! id, so entries for assets added after the client was built decode too and can be skipped.
+//!
+//! # Automation threads
+//!
+//! An automation program such as Clockwork can crank the oracle instead of a keeper bot.
+//! `thread_create_params` gives the thread's address, trigger and instruction; create the
+//! thread with the automation program's own client, then register the thread address with
+//! `set_thread_authority_ix`. The thread signs `update_prices_and_apys` through CPI. It
+//! cannot pay rent, so the instruction is built without `updater_stats`.
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program::MAX_RETURN_DATA;
+use anchor_lang::InstructionData;
+
+pub use crate::asset_info::{AssetInfo, AssetStatus};
+pub use crate::attestation::{AttestedPrice, PriceAttestation};
+pub use crate::audit_log::{AuditAction, AuditEntry, AuditLog, AuditLogPage, MAX_AUDIT_ENTRIES_RETURNED};
+pub use crate::basket::{Basket, BasketWeight, BASKET_TOTAL_WEIGHT_BPS};
+pub use crate::checkpoint::{Checkpoint, CheckpointPrice, CHECKPOINT_MIN_CLOSE_AGE_SECS};
+pub use crate::quote_buffer::{PushedQuote, QuoteBuffer, MAX_QUOTE_BUFFER_LEN};
+pub use crate::portfolio::{PortfolioPosition, PortfolioValue, MAX_PORTFOLIO_POSITIONS};
+pub use crate::price_oracle::{AssetType, ASSET_COUNT, DEFAULT_INSTANCE, FeedMapping, FeedPosition, FeedShard, FeedShardHealth, LastError, LenientPrice, LstPremium, MaxAge, MAX_FEED_SHARDS, MoveVerdict, OracleError, OracleStats, PriceChangeSimulation, QuoteCurrency, QuotedPrice, SimulatedCheck, SourceKind, SourcePolicy, SourceSlot, UpdateStats, Watermarks};
+pub use crate::signed_price::signed_price_message;
+pub use crate::subscriber::{CallbackPrice, PriceCallback, Subscriber};
+pub use crate::thresholds::{PriceThreshold, PriceThresholds, ThresholdDirection, MAX_THRESHOLDS_PER_ASSET};
+pub use crate::trust_score::{TrustScore, TrustWeights, DEFAULT_TRUST_WEIGHTS, TRUST_SCORE_VERSION};
+pub use crate::updater_stats::UpdaterStats;
+pub use crate::yield_convention::{RawYield, YieldConfig, YieldConvention, MAX_COMPOUNDING_PERIODS};
+use crate::price_oracle::PriceOracle;
+use crate::switchboard_utils::DEVNET_AGGREGATOR_PUBKEY;
+
+/// Schedule of the thread `thread_create_params` describes: every five minutes, on the
+/// minute. Clockwork cron schedules start with a seconds field.
+pub const THREAD_CRON_SCHEDULE: &str = "0 */5 * * * * *";
+/// Seed prefix of a Clockwork thread address, followed by the thread's owner and id
+pub const THREAD_SEED: &[u8] = b"thread";
+
+/// When an automation thread runs, mirroring Clockwork's `Trigger`
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ThreadTrigger {
+    /// On a cron schedule; a `skippable` thread skips runs it missed instead of catching up
+    Cron { schedule: String, skippable: bool },
+}
+
+/// What an automation program needs to create a thread that cranks the oracle
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ThreadCreateParams {
+    /// Thread id, part of the thread's address
+    pub id: Vec<u8>,
+    /// Thread address, which signs the instructions; register it with `set_thread_authority_ix`
+    pub thread: Pubkey,
+    pub trigger: ThreadTrigger,
+    /// Run on every trigger, in order
+    pub instructions: Vec<Instruction>,
+}
+
+/// Every supported asset, in the order used by the per-asset arrays
+pub const ASSET_TYPES: [AssetType; ASSET_COUNT] = AssetType::ALL;
+
+/// Converts a raw asset index, e.g. from instruction data or account bytes
+pub fn asset_type_from_u8(value: u8) -> std::result::Result<AssetType, OracleError> {
+    AssetType::try_from(value)
+}
+
+/// Raw asset index, identical to the asset's Borsh encoding
+pub fn asset_type_to_u8(asset_type: AssetType) -> u8 {
+    u8::from(asset_type)
+}
+
+/// Address of the header account of the oracle `instance`
+pub fn header_address(instance: &Pubkey) -> Pubkey {
+    PriceOracle::get_price_oracle_header_pda(&crate::ID, instance).0
+}
+
+/// Address of the price data account of the oracle `instance`
+pub fn data_address(instance: &Pubkey) -> Pubkey {
+    PriceOracle::get_price_oracle_data_pda(&crate::ID, instance).0
+}
+
+/// Address of the price history of the oracle `instance`
+pub fn history_address(instance: &Pubkey) -> Pubkey {
+    PriceOracle::get_price_history_pda(&crate::ID, instance).0
+}
+
+/// Address of the APY history of the oracle `instance`
+pub fn apy_history_address(instance: &Pubkey) -> Pubkey {
+    PriceOracle::get_apy_history_pda(&crate::ID, instance).0
+}
+
+/// Address of the asset registry of the oracle `instance`
+pub fn registry_address(instance: &Pubkey) -> Pubkey {
+    PriceOracle::get_asset_registry_pda(&crate::ID, instance).0
+}
+
+/// Address of an asset's epoch snapshots of the oracle `instance`
+pub fn epoch_snapshots_address(instance: &Pubkey, asset_type: AssetType) -> Pubkey {
+    PriceOracle::get_epoch_snapshots_pda(&crate::ID, instance, asset_type).0
+}
+
+/// Address of an updater's stats of the oracle `instance`; pass it as `updater_stats`
+/// to the update instructions to have it created and kept up to date
+pub fn updater_stats_address(instance: &Pubkey, updater: &Pubkey) -> Pubkey {
+    PriceOracle::get_updater_stats_pda(&crate::ID, instance, updater).0
+}
+
+/// Address of the subscriber registration of the oracle `instance`
+pub fn subscriber_address(instance: &Pubkey) -> Pubkey {
+    PriceOracle::get_subscriber_pda(&crate::ID, instance).0
+}
+
+/// Address of the audit log of the oracle `instance`
+pub fn audit_log_address(instance: &Pubkey) -> Pubkey {
+    PriceOracle::get_audit_log_pda(&crate::ID, instance).0
+}
+
+/// Address of the checkpoint with the given id of the oracle `instance`
+pub fn checkpoint_address(instance: &Pubkey, id: u64) -> Pubkey {
+    PriceOracle::get_checkpoint_pda(&crate::ID, instance, id).0
+}
+
+/// Address of `creator`'s basket with the given id for the deployed program
+pub fn basket_address(creator: &Pubkey, id: u64) -> Pubkey {
+    PriceOracle::get_basket_pda(&crate::ID, creator, id).0
+}
+
+/// Address of `authority`'s quote buffer with the given id of the oracle `instance`
+pub fn quote_buffer_address(instance: &Pubkey, authority: &Pubkey, id: u64) -> Pubkey {
+    PriceOracle::get_quote_buffer_pda(&crate::ID, instance, authority, id).0
+}
+
+/// Address of the price thresholds of the oracle `instance`
+pub fn thresholds_address(instance: &Pubkey) -> Pubkey {
+    PriceOracle::get_thresholds_pda(&crate::ID, instance).0
+}
+
+/// Header to pass to the reads that take it optionally: omitted for the default instance,
+/// whose accounts they check without it
+pub fn optional_header_address(instance: &Pubkey) -> Option<Pubkey> {
+    (*instance != DEFAULT_INSTANCE).then(|| header_address(instance))
+}
+
+/// Remaining accounts of `update_prices_and_apys` and `update_sol_price` when they are
+/// passed `subscriber`: the subscriber program followed by its registered accounts
+pub fn subscriber_callback_metas(subscriber: &Subscriber) -> Vec<AccountMeta> {
+    let mut metas = vec![AccountMeta::new_readonly(subscriber.program_id, false)];
+    metas.extend(subscriber.callback_account_metas());
+    metas
+}
+
+/// `attest_prices`: emits and returns the canonical payload of the current prices, signed
+/// by an updater `authority`. Decode it with `PriceAttestation::decode`.
+pub fn attest_prices_ix(instance: &Pubkey, authority: Pubkey) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::AttestPrices { header: header_address(instance), data: data_address(instance), authority }.to_account_metas(None),
+        data: crate::instruction::AttestPrices {}.data(),
+    }
+}
+
+/// `get_updater_stats`: returns the `UpdaterStats` of `updater`
+pub fn get_updater_stats_ix(instance: &Pubkey, updater: Pubkey) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::GetUpdaterStats { stats: updater_stats_address(instance, &updater), header: optional_header_address(instance) }.to_account_metas(None),
+        data: crate::instruction::GetUpdaterStats { updater }.data(),
+    }
+}
+
+/// `get_oracle_stats`: returns the oracle-wide `OracleStats`
+pub fn get_oracle_stats_ix(instance: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::GetOracleStats { header: header_address(instance) }.to_account_metas(None),
+        data: crate::instruction::GetOracleStats {}.data(),
+    }
+}
+
+/// `get_watermarks`: returns the asset's `Watermarks`, its highest and lowest accepted prices
+/// since the last reset
+pub fn get_watermarks_ix(instance: &Pubkey, asset_type: AssetType) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::GetPrice { data: data_address(instance), header: optional_header_address(instance) }.to_account_metas(None),
+        data: crate::instruction::GetWatermarks { asset_type }.data(),
+    }
+}
+
+/// `get_data_nonce`: returns the data account's nonce. Read it with the data, and compare
+/// before acting on that data: a different nonce means the data changed in between.
+pub fn get_data_nonce_ix(instance: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::GetPrice { data: data_address(instance), header: optional_header_address(instance) }.to_account_metas(None),
+        data: crate::instruction::GetDataNonce {}.data(),
+    }
+}
+
+/// `set_mock_price`: writes `price` and `apy` for `asset_type` without any feed, signed by
+/// the admin `authority`. Only for programs built with `mock-oracle` on localnet; a
+/// production deployment must never be built with it, and rejects this instruction.
+#[cfg(feature = "mock-oracle")]
+#[allow(clippy::expect_used)]
+pub fn set_mock_price_ix(instance: &Pubkey, authority: Pubkey, asset_type: AssetType, price: u64, apy: u64) -> Instruction {
+    let mut data = crate::mock_oracle::set_mock_price_discriminator().to_vec();
+    // Serializing into a `Vec` cannot fail
+    data.extend(crate::mock_oracle::SetMockPriceArgs { asset_type, price, apy }.try_to_vec().expect("arguments serialize"));
+    Instruction {
+        program_id: crate::ID,
+        accounts: vec![
+            AccountMeta::new(header_address(instance), false),
+            AccountMeta::new(data_address(instance), false),
+            AccountMeta::new_readonly(authority, true),
+        ],
+        data,
+    }
+}
+
+/// Ed25519 program instruction verifying `signature` of `message` by `publisher`. Put it
+/// immediately before `update_price_signed_ix` in the same transaction.
+pub fn ed25519_verify_ix(publisher: Pubkey, signature: [u8; 64], message: &[u8]) -> Instruction {
+    Instruction {
+        program_id: anchor_lang::solana_program::ed25519_program::ID,
+        accounts: vec![],
+        data: crate::signed_price::ed25519_instruction_data(&publisher, &signature, message),
+    }
+}
+
+/// `update_price_signed`, signed by an updater `authority`. `signature` is the trusted
+/// publisher's signature of `signed_price_message(asset_type, quote, price, timestamp)`,
+/// `quote` being the asset's current quote currency, and must be verified by
+/// `ed25519_verify_ix` just before. Pass `with_history` once the price history is
+/// initialized so the price is recorded there.
+pub fn update_price_signed_ix(
+    instance: &Pubkey, authority: Pubkey,
+    asset_type: AssetType,
+    price: u64,
+    timestamp: i64,
+    signature: [u8; 64],
+    with_history: bool,
+) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::UpdatePriceSigned {
+            header: header_address(instance),
+            data: data_address(instance),
+            history: with_history.then(|| history_address(instance)),
+            updater_stats: Some(updater_stats_address(instance, &authority)),
+            authority,
+            system_program: Some(anchor_lang::system_program::ID),
+            instructions: anchor_lang::solana_program::sysvar::instructions::ID,
+        }.to_account_metas(None),
+        data: crate::instruction::UpdatePriceSigned { asset_type, price, timestamp, signature }.data(),
+    }
+}
+
+/// `get_price_no_older_than`: returns a `QuotedPrice` only when it is no older than `max_age`
+/// and fails otherwise. The one to use for anything that moves value. `reject_same_slot`
+/// also fails for a price written in the current slot, and `reject_same_transaction` for a
+/// price update earlier in the same transaction.
+pub fn get_price_strict_ix(
+    instance: &Pubkey, asset_type: AssetType,
+    max_age: MaxAge,
+    reject_same_slot: bool,
+    reject_same_transaction: bool,
+) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::GetPriceStrict {
+            data: data_address(instance),
+            instructions: reject_same_transaction.then_some(anchor_lang::solana_program::sysvar::instructions::ID),
+            header: optional_header_address(instance),
+        }.to_account_metas(None),
+        data: crate::instruction::GetPriceNoOlderThan { asset_type, max_age, reject_same_slot }.data(),
+    }
+}
+
+/// `assert_price_fresh`: fails the transaction unless the oracle is running and the price is
+/// at most `max_age_secs` old, with the same opt-in guards as `get_price_strict_ix`
+pub fn assert_price_fresh_ix(
+    instance: &Pubkey, asset_type: AssetType,
+    max_age_secs: u32,
+    reject_same_slot: bool,
+    reject_same_transaction: bool,
+) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::AssertPriceFresh {
+            header: header_address(instance),
+            data: data_address(instance),
+            instructions: reject_same_transaction.then_some(anchor_lang::solana_program::sysvar::instructions::ID),
+        }.to_account_metas(None),
+        data: crate::instruction::AssertPriceFresh { asset_type, max_age_secs, reject_same_slot }.data(),
+    }
+}
+
+/// `reinitialize_data`: rebuilds a data account no layout decodes, signed and paid for by the
+/// admin `authority` while the emergency stop is active
+pub fn reinitialize_data_ix(instance: &Pubkey, authority: Pubkey, with_audit_log: bool) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::ReinitializeData {
+            header: header_address(instance),
+            data: data_address(instance),
+            authority,
+            system_program: anchor_lang::solana_program::system_program::ID,
+            audit_log: with_audit_log.then(|| audit_log_address(instance)),
+        }.to_account_metas(None),
+        data: crate::instruction::ReinitializeData {}.data(),
+    }
+}
+
+/// `force_update`: writes the price of the asset's registered `feed` past the change limit,
+/// signed by the admin `authority`. Returns the price it replaced.
+pub fn force_update_ix(instance: &Pubkey, authority: Pubkey, asset_type: AssetType, feed: Pubkey, with_audit_log: bool) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::ForceUpdate {
+            header: header_address(instance),
+            data: data_address(instance),
+            registry: registry_address(instance),
+            feed,
+            authority,
+            audit_log: with_audit_log.then(|| audit_log_address(instance)),
+        }.to_account_metas(None),
+        data: crate::instruction::ForceUpdate { asset_type }.data(),
+    }
+}
+
+/// `set_asset_mint`: registers `mint`, owned by SPL Token or Token-2022, as the asset's mint
+pub fn set_asset_mint_ix(instance: &Pubkey, authority: Pubkey, asset_type: AssetType, mint: Pubkey, with_audit_log: bool) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::SetAssetMint {
+            header: header_address(instance),
+            mint,
+            authority,
+            audit_log: with_audit_log.then(|| audit_log_address(instance)),
+        }.to_account_metas(None),
+        data: crate::instruction::SetAssetMint { asset_type }.data(),
+    }
+}
+
+/// `set_yield_convention`: sets the convention the asset's yield is reported in, signed by
+/// the admin `authority`
+pub fn set_yield_convention_ix(instance: &Pubkey, authority: Pubkey, asset_type: AssetType, config: YieldConfig, with_audit_log: bool) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::SetStalenessConfig {
+            header: header_address(instance),
+            authority,
+            audit_log: with_audit_log.then(|| audit_log_address(instance)),
+        }.to_account_metas(None),
+        data: crate::instruction::SetYieldConvention { asset_type, config }.data(),
+    }
+}
+
+/// `set_source_policy`: sets whether the asset's registered sources are combined by median
+/// or tried in priority order, signed by the admin `authority`
+pub fn set_source_policy_ix(instance: &Pubkey, authority: Pubkey, asset_type: AssetType, policy: SourcePolicy, with_audit_log: bool) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::SetStalenessConfig {
+            header: header_address(instance),
+            authority,
+            audit_log: with_audit_log.then(|| audit_log_address(instance)),
+        }.to_account_metas(None),
+        data: crate::instruction::SetSourcePolicy { asset_type, policy }.data(),
+    }
+}
+
+/// `propose_asset_fallback`: queues the source `update_assets` reads for the asset when its
+/// registered feed is stale, or clearing it with `None`, signed by the admin `authority`
+pub fn propose_asset_fallback_ix(instance: &Pubkey, authority: Pubkey, asset_type: AssetType, fallback: Option<SourceSlot>, with_audit_log: bool) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::ProposeAssetFallback {
+            header: header_address(instance),
+            registry: registry_address(instance),
+            authority,
+            audit_log: with_audit_log.then(|| audit_log_address(instance)),
+        }.to_account_metas(None),
+        data: crate::instruction::ProposeAssetFallback { asset_type, fallback }.data(),
+    }
+}
+
+/// `apply_asset_fallback`: registers the asset's pending fallback once its timelock has
+/// elapsed; any `payer` may send it
+pub fn apply_asset_fallback_ix(instance: &Pubkey, payer: Pubkey, asset_type: AssetType, with_audit_log: bool) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::ApplyConfig {
+            header: header_address(instance),
+            registry: registry_address(instance),
+            payer,
+            audit_log: with_audit_log.then(|| audit_log_address(instance)),
+        }.to_account_metas(None),
+        data: crate::instruction::ApplyAssetFallback { asset_type }.data(),
+    }
+}
+
+/// `cancel_asset_fallback`: drops the asset's pending fallback, signed by the admin `authority`
+pub fn cancel_asset_fallback_ix(instance: &Pubkey, authority: Pubkey, asset_type: AssetType, with_audit_log: bool) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::CancelConfig {
+            header: header_address(instance),
+            authority,
+            audit_log: with_audit_log.then(|| audit_log_address(instance)),
+        }.to_account_metas(None),
+        data: crate::instruction::CancelAssetFallback { asset_type }.data(),
+    }
+}
+
+/// `set_pyth_feed_id`: binds the Pyth feed id the asset's Pyth sources must carry, or
+/// clears it with all zeros, signed by an admin `authority`
+pub fn set_pyth_feed_id_ix(instance: &Pubkey, authority: Pubkey, asset_type: AssetType, feed_id: [u8; 32], with_audit_log: bool) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::SetAssetFeed {
+            header: header_address(instance),
+            registry: registry_address(instance),
+            authority,
+            audit_log: with_audit_log.then(|| audit_log_address(instance)),
+        }.to_account_metas(None),
+        data: crate::instruction::SetPythFeedId { asset_type, feed_id }.data(),
+    }
+}
+
+/// `set_resume_grace`: widens the change limit by `multiplier_bps` for `grace_secs` after
+/// each resume, signed by the admin `authority`
+pub fn set_resume_grace_ix(instance: &Pubkey, authority: Pubkey, grace_secs: u32, multiplier_bps: u32, with_audit_log: bool) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::SetStalenessConfig {
+            header: header_address(instance),
+            authority,
+            audit_log: with_audit_log.then(|| audit_log_address(instance)),
+        }.to_account_metas(None),
+        data: crate::instruction::SetResumeGrace { grace_secs, multiplier_bps }.data(),
+    }
+}
+
+/// `prune_history`: drops all but the `keep_last_n` most recent entries of the asset from
+/// the price history and, with `with_apy_history`, the APY history, signed by the admin
+/// `authority`
+pub fn prune_history_ix(
+    instance: &Pubkey, authority: Pubkey, asset_type: AssetType, keep_last_n: u8,
+    with_history: bool, with_apy_history: bool, with_audit_log: bool,
+) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::PruneHistory {
+            header: header_address(instance),
+            history: with_history.then(|| history_address(instance)),
+            apy_history: with_apy_history.then(|| apy_history_address(instance)),
+            authority,
+            audit_log: with_audit_log.then(|| audit_log_address(instance)),
+        }.to_account_metas(None),
+        data: crate::instruction::PruneHistory { asset_type, keep_last_n }.data(),
+    }
+}
+
+/// `prune_epoch_snapshots`: removes the asset's snapshots before `before_epoch`, signed by
+/// the admin `authority`
+pub fn prune_epoch_snapshots_ix(instance: &Pubkey, authority: Pubkey, asset_type: AssetType, before_epoch: u64, with_audit_log: bool) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::PruneEpochSnapshots {
+            header: header_address(instance),
+            snapshots: epoch_snapshots_address(instance, asset_type),
+            authority,
+            audit_log: with_audit_log.then(|| audit_log_address(instance)),
+        }.to_account_metas(None),
+        data: crate::instruction::PruneEpochSnapshots { asset_type, before_epoch }.data(),
+    }
+}
+
+/// `get_price_lenient`: returns a `LenientPrice` however old the price is, flagged `degraded`
+/// when stale. For dashboards and analytics only, never for liquidations or collateral.
+pub fn get_price_lenient_display_only_ix(instance: &Pubkey, asset_type: AssetType) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::GetAllPrices { header: header_address(instance), data: data_address(instance) }.to_account_metas(None),
+        data: crate::instruction::GetPriceLenient { asset_type }.data(),
+    }
+}
+
+/// `get_collateral_value`: returns the USD value of `amount` base units of the asset net of
+/// its haircut, failing when the price is stale, paused or missing
+pub fn get_collateral_value_ix(instance: &Pubkey, asset_type: AssetType, amount: u64) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::GetAllPrices { header: header_address(instance), data: data_address(instance) }.to_account_metas(None),
+        data: crate::instruction::GetCollateralValue { asset_type, amount }.data(),
+    }
+}
+
+/// `get_portfolio_value`: returns the `PortfolioValue` of `token_accounts`, SPL Token or
+/// Token-2022 accounts of registered mints, at most `MAX_PORTFOLIO_POSITIONS`
+pub fn get_portfolio_value_ix(instance: &Pubkey, token_accounts: &[Pubkey], skip_unpriced: bool) -> Instruction {
+    let mut accounts = crate::accounts::GetAllPrices { header: header_address(instance), data: data_address(instance) }.to_account_metas(None);
+    accounts.extend(token_accounts.iter().map(|token_account| AccountMeta::new_readonly(*token_account, false)));
+    Instruction {
+        program_id: crate::ID,
+        accounts,
+        data: crate::instruction::GetPortfolioValue { skip_unpriced }.data(),
+    }
+}
+
+/// `get_raw_yield`: returns the asset's `RawYield`, its yield as last reported
+pub fn get_raw_yield_ix(instance: &Pubkey, asset_type: AssetType) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::GetAllPrices { header: header_address(instance), data: data_address(instance) }.to_account_metas(None),
+        data: crate::instruction::GetRawYield { asset_type }.data(),
+    }
+}
+
+/// `get_last_error`: returns the asset's `LastError`, why its latest observation was rejected
+pub fn get_last_error_ix(instance: &Pubkey, asset_type: AssetType) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::GetAllPrices { header: header_address(instance), data: data_address(instance) }.to_account_metas(None),
+        data: crate::instruction::GetLastError { asset_type }.data(),
+    }
+}
+
+/// `get_premium`: returns the asset's `LstPremium`, its market premium over its stake pool
+/// rate as of the latest update passed the pool
+pub fn get_premium_ix(instance: &Pubkey, asset_type: AssetType) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::GetPremium { header: header_address(instance), registry: registry_address(instance) }.to_account_metas(None),
+        data: crate::instruction::GetPremium { asset_type }.data(),
+    }
+}
+
+/// `get_supported_assets`: returns an `AssetInfo` for every asset the program supports,
+/// decoded with `decode_supported_assets`
+pub fn get_supported_assets_ix(instance: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::GetAllPrices { header: header_address(instance), data: data_address(instance) }.to_account_metas(None),
+        data: crate::instruction::GetSupportedAssets {}.data(),
+    }
+}
+
+/// Reads the return data of `get_supported_assets`. Entries whose id this build does not
+/// know are kept; their `asset_type()` is `None`.
+pub fn decode_supported_assets(return_data: &[u8]) -> Result<Vec<AssetInfo>> {
+    decode_return_data(return_data)
+}
+
+/// Reads the return data of a view instruction as `T`. The runtime drops trailing zero bytes
+/// from the return data it reports, so the bytes are padded back before decoding.
+pub fn decode_return_data<T: AnchorDeserialize>(return_data: &[u8]) -> Result<T> {
+    let mut padded = return_data.to_vec();
+    padded.resize(MAX_RETURN_DATA.max(return_data.len()), 0);
+    Ok(T::deserialize(&mut padded.as_slice())?)
+}
+
+/// `simulate_price_change`: returns the `PriceChangeSimulation` of an update reading
+/// `candidate_price` and `candidate_apy` for the asset. Pass the asset's registered
+/// `stake_pool` to include the pool rate check.
+pub fn simulate_price_change_ix(
+    instance: &Pubkey,
+    asset_type: AssetType,
+    candidate_price: u64,
+    candidate_apy: Option<u64>,
+    stake_pool: Option<Pubkey>,
+) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::SimulatePriceChange {
+            header: header_address(instance),
+            data: data_address(instance),
+            registry: registry_address(instance),
+            stake_pool,
+        }.to_account_metas(None),
+        data: crate::instruction::SimulatePriceChange { asset_type, candidate_price, candidate_apy }.data(),
+    }
+}
+
+/// `verify_invariants`: returns the mask of the oracle's violated invariants, see the
+/// `invariants` module. `previous_nonce` and `previous_generation` are the caller's last
+/// reading of the data nonce and data generation, zeros to skip that check.
+pub fn verify_invariants_ix(instance: &Pubkey, previous_nonce: u64, previous_generation: u32) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::VerifyInvariants { header: header_address(instance), data: data_address(instance) }.to_account_metas(None),
+        data: crate::instruction::VerifyInvariants { previous_nonce, previous_generation }.data(),
+    }
+}
+
+/// `get_trust_score`: returns the asset's `TrustScore`. Check its `version` against
+/// `TRUST_SCORE_VERSION` before relying on the formula.
+pub fn get_trust_score_ix(instance: &Pubkey, asset_type: AssetType) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::GetAllPrices { header: header_address(instance), data: data_address(instance) }.to_account_metas(None),
+        data: crate::instruction::GetTrustScore { asset_type }.data(),
+    }
+}
+
+/// `set_trust_weights`: sets the weights of `get_trust_score`, signed by the admin `authority`
+pub fn set_trust_weights_ix(instance: &Pubkey, authority: Pubkey, weights: TrustWeights, with_audit_log: bool) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::SetStalenessConfig {
+            header: header_address(instance),
+            authority,
+            audit_log: with_audit_log.then(|| audit_log_address(instance)),
+        }.to_account_metas(None),
+        data: crate::instruction::SetTrustWeights { weights }.data(),
+    }
+}
+
+/// `initialize_audit_log`: creates the audit log, signed and paid for by the admin
+/// `authority`. Every privileged instruction must be passed it from then on.
+pub fn initialize_audit_log_ix(instance: &Pubkey, authority: Pubkey) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::InitializeAuditLog {
+            header: header_address(instance),
+            audit_log: audit_log_address(instance),
+            authority,
+            system_program: anchor_lang::solana_program::system_program::ID,
+        }.to_account_metas(None),
+        data: crate::instruction::InitializeAuditLog {}.data(),
+    }
+}
+
+/// `get_audit_log`: returns an `AuditLogPage` of up to `count` of the latest entries, newest
+/// first. At most `MAX_AUDIT_ENTRIES_RETURNED` fit the return data.
+pub fn get_audit_log_ix(instance: &Pubkey, count: u8) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::GetAuditLog { audit_log: audit_log_address(instance), header: optional_header_address(instance) }.to_account_metas(None),
+        data: crate::instruction::GetAuditLog { count }.data(),
+    }
+}
+
+/// Up to `count` of the latest entries of the audit log account `account_data`, newest first,
+/// without the return data limit of `get_audit_log_ix`
+pub fn decode_audit_log(account_data: &[u8], count: usize) -> Result<AuditLogPage> {
+    let audit_log = AuditLog::try_deserialize(&mut &account_data[..])?;
+    Ok(AuditLogPage { total_entries: audit_log.total_entries, entries: audit_log.recent(count) })
+}
+
+/// `create_checkpoint`: freezes the current state into the checkpoint `id`, paid for by
+/// `payer`
+pub fn create_checkpoint_ix(instance: &Pubkey, payer: Pubkey, id: u64) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::CreateCheckpoint {
+            header: header_address(instance),
+            data: data_address(instance),
+            checkpoint: checkpoint_address(instance, id),
+            payer,
+            system_program: anchor_lang::solana_program::system_program::ID,
+        }.to_account_metas(None),
+        data: crate::instruction::CreateCheckpoint { id }.data(),
+    }
+}
+
+/// `get_checkpoint`: returns the `Checkpoint` with the given id
+pub fn get_checkpoint_ix(instance: &Pubkey, id: u64) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::GetCheckpoint { checkpoint: checkpoint_address(instance, id), header: optional_header_address(instance) }.to_account_metas(None),
+        data: crate::instruction::GetCheckpoint { id }.data(),
+    }
+}
+
+/// `close_checkpoint`: closes a checkpoint older than `CHECKPOINT_MIN_CLOSE_AGE_SECS`, signed
+/// by the admin `authority`. The rent goes back to `creator`, the checkpoint's creator.
+pub fn close_checkpoint_ix(instance: &Pubkey, authority: Pubkey, id: u64, creator: Pubkey, with_audit_log: bool) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::CloseCheckpoint {
+            header: header_address(instance),
+            checkpoint: checkpoint_address(instance, id),
+            creator,
+            authority,
+            audit_log: with_audit_log.then(|| audit_log_address(instance)),
+        }.to_account_metas(None),
+        data: crate::instruction::CloseCheckpoint { id }.data(),
+    }
+}
+
+/// The checkpoint held by the account data `account_data`
+pub fn decode_checkpoint(account_data: &[u8]) -> Result<Checkpoint> {
+    Checkpoint::try_deserialize(&mut &account_data[..])
+}
+
+/// `create_basket`: creates `creator`'s basket `id` with `weights`, paid for by `creator`
+pub fn create_basket_ix(instance: &Pubkey, creator: Pubkey, id: u64, weights: Vec<BasketWeight>) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::CreateBasket {
+            data: data_address(instance),
+            basket: basket_address(&creator, id),
+            creator,
+            system_program: anchor_lang::solana_program::system_program::ID,
+            header: optional_header_address(instance),
+        }.to_account_metas(None),
+        data: crate::instruction::CreateBasket { id, weights }.data(),
+    }
+}
+
+/// `update_basket_weights`: replaces the weights of `creator`'s basket `id`, signed by `creator`
+pub fn update_basket_weights_ix(instance: &Pubkey, creator: Pubkey, id: u64, weights: Vec<BasketWeight>) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::UpdateBasketWeights { data: data_address(instance), basket: basket_address(&creator, id), creator, header: optional_header_address(instance) }.to_account_metas(None),
+        data: crate::instruction::UpdateBasketWeights { id, weights }.data(),
+    }
+}
+
+/// `close_basket`: closes `creator`'s basket `id` and refunds its rent, signed by `creator`
+pub fn close_basket_ix(creator: Pubkey, id: u64) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::CloseBasket { basket: basket_address(&creator, id), creator }.to_account_metas(None),
+        data: crate::instruction::CloseBasket { id }.data(),
+    }
+}
+
+/// `get_basket_price`: returns the USD value of one unit of `creator`'s basket `id`
+pub fn get_basket_price_ix(instance: &Pubkey, creator: &Pubkey, id: u64) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::GetBasketPrice { header: header_address(instance), data: data_address(instance), basket: basket_address(creator, id) }.to_account_metas(None),
+        data: crate::instruction::GetBasketPrice {}.data(),
+    }
+}
+
+/// `create_quote_buffer`: creates `authority`'s quote buffer `id`, `space` bytes paid for by
+/// `authority`, with quotes written at `offset`
+pub fn create_quote_buffer_ix(instance: &Pubkey, authority: Pubkey, id: u64, space: u32, offset: u32, layout_version: u32) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::CreateQuoteBuffer {
+            buffer: quote_buffer_address(instance, &authority, id),
+            authority,
+            system_program: anchor_lang::solana_program::system_program::ID,
+            header: optional_header_address(instance),
+        }.to_account_metas(None),
+        data: crate::instruction::CreateQuoteBuffer { id, space, offset, layout_version }.data(),
+    }
+}
+
+/// `write_quote_to`: writes the asset's current quote into `buffer`. Anyone may send it.
+pub fn write_quote_to_ix(instance: &Pubkey, buffer: Pubkey, asset_type: AssetType) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::WriteQuoteTo { header: header_address(instance), data: data_address(instance), buffer }.to_account_metas(None),
+        data: crate::instruction::WriteQuoteTo { asset_type }.data(),
+    }
+}
+
+/// The quote held by the quote buffer's account data `account_data`, zero until the first
+/// write. Check `timestamp` against the consumer's own staleness limit.
+pub fn decode_pushed_quote(account_data: &[u8]) -> Result<PushedQuote> {
+    QuoteBuffer::read_quote(account_data)
+}
+
+/// `check_thresholds`: triggers the thresholds the stored prices cross. Anyone may send it.
+pub fn check_thresholds_ix(instance: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::CheckThresholds { header: header_address(instance), data: data_address(instance), thresholds: thresholds_address(instance) }.to_account_metas(None),
+        data: crate::instruction::CheckThresholds {}.data(),
+    }
+}
+
+/// The thresholds held by the account data `account_data`
+pub fn decode_price_thresholds(account_data: &[u8]) -> Result<PriceThresholds> {
+    PriceThresholds::try_deserialize(&mut &account_data[..])
+}
+
+/// `update_prices_and_apys` from the multi-asset feed, signed by an updater `authority`. The
+/// price history is passed when `with_history`, the APY history when `with_apy_history`.
+pub fn update_prices_and_apys_ix(instance: &Pubkey, authority: Pubkey, with_history: bool, with_apy_history: bool) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::UpdatePricesAndApys {
+            header: header_address(instance),
+            data: data_address(instance),
+            oracle_feed: DEVNET_AGGREGATOR_PUBKEY,
+            lease: None,
+            lease_escrow: None,
+            history: with_history.then(|| history_address(instance)),
+            apy_history: with_apy_history.then(|| apy_history_address(instance)),
+            updater_stats: None,
+            authority,
+            system_program: None,
+            subscriber: None,
+        }.to_account_metas(None),
+        data: crate::instruction::UpdatePricesAndApys {}.data(),
+    }
+}
+
+/// `update_sol_price_chainlink` from `chainlink_feed`, a registered Chainlink source of SOL,
+/// signed by an updater `authority`. The price history is passed when `with_history`.
+pub fn update_sol_price_chainlink_ix(
+    instance: &Pubkey,
+    authority: Pubkey,
+    chainlink_feed: Pubkey,
+    expected_price: Option<u64>,
+    tolerance_bps: u16,
+    with_history: bool,
+) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::UpdateSolPriceChainlink {
+            header: header_address(instance),
+            data: data_address(instance),
+            registry: registry_address(instance),
+            chainlink_feed,
+            history: with_history.then(|| history_address(instance)),
+            updater_stats: None,
+            authority,
+            system_program: None,
+            subscriber: None,
+        }.to_account_metas(None),
+        data: crate::instruction::UpdateSolPriceChainlink { expected_price, tolerance_bps }.data(),
+    }
+}
+
+/// `update_prices_and_apys_chunk` for the `count` multi-asset feed assets from position
+/// `start_index`, with the accounts of `update_prices_and_apys_ix`
+pub fn update_prices_and_apys_chunk_ix(instance: &Pubkey, authority: Pubkey, start_index: u8, count: u8, with_history: bool, with_apy_history: bool) -> Instruction {
+    Instruction {
+        data: crate::instruction::UpdatePricesAndApysChunk { start_index, count }.data(),
+        ..update_prices_and_apys_ix(instance, authority, with_history, with_apy_history)
+    }
+}
+
+/// `refresh_if_stale` with the accounts of `update_prices_and_apys_ix`. Prepend it to a
+/// transaction that reads prices: it is a no-op while the data is fresh, whoever signs.
+pub fn refresh_if_stale_ix(instance: &Pubkey, authority: Pubkey, with_history: bool, with_apy_history: bool) -> Instruction {
+    Instruction { data: crate::instruction::RefreshIfStale {}.data(), ..update_prices_and_apys_ix(instance, authority, with_history, with_apy_history) }
+}
+
+/// `set_thread_authority`: lets `thread_authority` sign updates, signed by the admin
+/// `authority`. `Pubkey::default()` revokes the thread.
+pub fn set_thread_authority_ix(instance: &Pubkey, authority: Pubkey, thread_authority: Pubkey, with_audit_log: bool) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::SetOperator {
+            header: header_address(instance),
+            authority,
+            audit_log: with_audit_log.then(|| audit_log_address(instance)),
+        }.to_account_metas(None),
+        data: crate::instruction::SetThreadAuthority { thread_authority }.data(),
+    }
+}
+
+/// `set_asset_authority`: lets `asset_authority` take the scoped admin actions on
+/// `asset_type`, signed by the admin `authority`. `None` revokes it.
+pub fn set_asset_authority_ix(instance: &Pubkey, authority: Pubkey, asset_type: AssetType, asset_authority: Option<Pubkey>, with_audit_log: bool) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::SetOperator {
+            header: header_address(instance),
+            authority,
+            audit_log: with_audit_log.then(|| audit_log_address(instance)),
+        }.to_account_metas(None),
+        data: crate::instruction::SetAssetAuthority { asset_type, authority: asset_authority }.data(),
+    }
+}
+
+/// `set_on_demand_queue`: sets the queue Switchboard On-Demand feeds must belong to,
+/// signed by the admin `authority`
+pub fn set_on_demand_queue_ix(instance: &Pubkey, authority: Pubkey, queue: Pubkey, with_audit_log: bool) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::SetOperator {
+            header: header_address(instance),
+            authority,
+            audit_log: with_audit_log.then(|| audit_log_address(instance)),
+        }.to_account_metas(None),
+        data: crate::instruction::SetOnDemandQueue { queue }.data(),
+    }
+}
+
+/// `set_watcher`: adds `program_id` to the watcher allowlist, or removes it when
+/// `!allowed`, signed by the admin `authority`
+pub fn set_watcher_ix(instance: &Pubkey, authority: Pubkey, program_id: Pubkey, allowed: bool, with_audit_log: bool) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::SetOperator {
+            header: header_address(instance),
+            authority,
+            audit_log: with_audit_log.then(|| audit_log_address(instance)),
+        }.to_account_metas(None),
+        data: crate::instruction::SetWatcher { program_id, allowed }.data(),
+    }
+}
+
+/// Address of the PDA `watcher_program` must sign `trigger_emergency_stop_cpi` with
+pub fn watcher_authority_address(watcher_program: &Pubkey) -> Pubkey {
+    PriceOracle::get_watcher_authority_pda(watcher_program).0
+}
+
+/// `propose_feed_mapping`: queues `positions` as the multi-asset feed mapping behind the
+/// config timelock, signed by the admin `authority`
+pub fn propose_feed_mapping_ix(instance: &Pubkey, authority: Pubkey, positions: Vec<FeedPosition>, with_audit_log: bool) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::ProposeConfig {
+            header: header_address(instance),
+            authority,
+            audit_log: with_audit_log.then(|| audit_log_address(instance)),
+        }.to_account_metas(None),
+        data: crate::instruction::ProposeFeedMapping { positions }.data(),
+    }
+}
+
+/// `propose_feed_shard`: queues `feed` as shard `shard` of the multi-asset feed, read at
+/// `positions`, or clearing the shard when `positions` is empty, signed by the admin `authority`
+pub fn propose_feed_shard_ix(instance: &Pubkey, authority: Pubkey, shard: u8, feed: Pubkey, positions: Vec<FeedPosition>, with_audit_log: bool) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::ProposeConfig {
+            header: header_address(instance),
+            authority,
+            audit_log: with_audit_log.then(|| audit_log_address(instance)),
+        }.to_account_metas(None),
+        data: crate::instruction::ProposeFeedShard { shard, feed, positions }.data(),
+    }
+}
+
+/// `apply_feed_shard`: registers the shard's pending change once its timelock has elapsed;
+/// any `payer` may send it
+pub fn apply_feed_shard_ix(instance: &Pubkey, payer: Pubkey, shard: u8, with_audit_log: bool) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::ApplyFeedMapping {
+            header: header_address(instance),
+            payer,
+            audit_log: with_audit_log.then(|| audit_log_address(instance)),
+        }.to_account_metas(None),
+        data: crate::instruction::ApplyFeedShard { shard }.data(),
+    }
+}
+
+/// `cancel_feed_shard`: drops the shard's pending change, signed by the admin `authority`
+pub fn cancel_feed_shard_ix(instance: &Pubkey, authority: Pubkey, shard: u8, with_audit_log: bool) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::CancelConfig {
+            header: header_address(instance),
+            authority,
+            audit_log: with_audit_log.then(|| audit_log_address(instance)),
+        }.to_account_metas(None),
+        data: crate::instruction::CancelFeedShard { shard }.data(),
+    }
+}
+
+/// `update_feed_shards` from `feeds`, any of the primary multi-asset feed and the registered
+/// shards, signed by an updater `authority`. The price history is passed when
+/// `with_history`, the APY history when `with_apy_history`.
+pub fn update_feed_shards_ix(instance: &Pubkey, authority: Pubkey, feeds: &[Pubkey], with_history: bool, with_apy_history: bool) -> Instruction {
+    let mut accounts = crate::accounts::UpdateFeedShards {
+        header: header_address(instance),
+        data: data_address(instance),
+        history: with_history.then(|| history_address(instance)),
+        apy_history: with_apy_history.then(|| apy_history_address(instance)),
+        updater_stats: None,
+        authority,
+        system_program: None,
+    }.to_account_metas(None);
+    accounts.extend(feeds.iter().map(|feed| AccountMeta::new_readonly(*feed, false)));
+    Instruction {
+        program_id: crate::ID,
+        accounts,
+        data: crate::instruction::UpdateFeedShards {}.data(),
+    }
+}
+
+/// `get_feed_shard_health`: returns a `FeedShardHealth` for the primary multi-asset feed
+/// and every registered shard
+pub fn get_feed_shard_health_ix(instance: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::GetAllPrices { header: header_address(instance), data: data_address(instance) }.to_account_metas(None),
+        data: crate::instruction::GetFeedShardHealth {}.data(),
+    }
+}
+
+/// `apply_feed_mapping` once the timelock has elapsed, which any `payer` may send
+pub fn apply_feed_mapping_ix(instance: &Pubkey, payer: Pubkey, with_audit_log: bool) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::ApplyFeedMapping {
+            header: header_address(instance),
+            payer,
+            audit_log: with_audit_log.then(|| audit_log_address(instance)),
+        }.to_account_metas(None),
+        data: crate::instruction::ApplyFeedMapping {}.data(),
+    }
+}
+
+/// `cancel_feed_mapping`: drops the pending mapping, signed by the admin `authority`
+pub fn cancel_feed_mapping_ix(instance: &Pubkey, authority: Pubkey, with_audit_log: bool) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::CancelConfig {
+            header: header_address(instance),
+            authority,
+            audit_log: with_audit_log.then(|| audit_log_address(instance)),
+        }.to_account_metas(None),
+        data: crate::instruction::CancelFeedMapping {}.data(),
+    }
+}
+
+/// Address of the thread `id` of `owner` under the automation program `thread_program_id`,
+/// derived as Clockwork derives it
+pub fn thread_address(thread_program_id: &Pubkey, owner: &Pubkey, id: &[u8]) -> Pubkey {
+    Pubkey::find_program_address(&[THREAD_SEED, owner.as_ref(), id], thread_program_id).0
+}
+
+/// Parameters of a thread of `owner` under `thread_program_id` that runs
+/// `update_prices_and_apys` every five minutes, see the module documentation. Pass
+/// `with_history` and `with_apy_history` once the respective history has been initialized.
+pub fn thread_create_params(instance: &Pubkey, thread_program_id: Pubkey, owner: Pubkey, id: &[u8], with_history: bool, with_apy_history: bool) -> ThreadCreateParams {
+    let thread = thread_address(&thread_program_id, &owner, id);
+    ThreadCreateParams {
+        id: id.to_vec(),
+        thread,
+        trigger: ThreadTrigger::Cron { schedule: THREAD_CRON_SCHEDULE.to_string(), skippable: true },
+        instructions: vec![update_prices_and_apys_ix(instance, thread, with_history, with_apy_history)],
+    }
+}
+
+/// `migrate_header`, `migrate_price_data` and `migrate_registry`, in the order they must run,
+/// for the admin `authority` to upgrade a deployment written for fewer assets. Send them in
+/// one transaction so no update lands between them. The price history is migrated when
+/// `with_history`, which it must be once it has been initialized. Only the default
+/// instance has accounts from before instances existed.
+pub fn migrate_accounts_ixs(authority: Pubkey, with_history: bool) -> Vec<Instruction> {
+    let instance = &DEFAULT_INSTANCE;
+    let system_program = anchor_lang::solana_program::system_program::ID;
+    let instruction = |accounts: Vec<AccountMeta>, data: Vec<u8>| Instruction { program_id: crate::ID, accounts, data };
+    vec![
+        instruction(
+            crate::accounts::MigrateHeader { header: header_address(instance), authority, system_program }.to_account_metas(None),
+            crate::instruction::MigrateHeader {}.data(),
+        ),
+        instruction(
+            crate::accounts::MigratePriceData { header: header_address(instance), data: data_address(instance), authority, system_program }.to_account_metas(None),
+            crate::instruction::MigratePriceData {}.data(),
+        ),
+        instruction(
+            crate::accounts::MigrateRegistry {
+                header: header_address(instance),
+                registry: registry_address(instance),
+                history: with_history.then(|| history_address(instance)),
+                authority,
+                system_program,
+            }.to_account_metas(None),
+            crate::instruction::MigrateRegistry {}.data(),
+        ),
+    ]
+}
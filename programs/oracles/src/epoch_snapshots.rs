@@ -0,0 +1,118 @@
+use anchor_lang::prelude::*;
+use crate::price_oracle::{AssetType, OracleError, QuoteCurrency};
+
+/// Epochs kept per asset. At roughly two days per epoch this covers well over a year;
+/// once full, `snapshot_epoch` fails with `EpochSnapshotsFull`.
+pub const EPOCH_SNAPSHOT_CAPACITY: usize = 256;
+
+/// Price and APY of an asset as recorded during one epoch
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct EpochSnapshot {
+    pub epoch: u64,
+    pub price: u64,
+    pub apy: u64,
+    /// Currency `price` was quoted in at the time
+    pub quote: QuoteCurrency,
+}
+
+impl EpochSnapshot {
+    pub const LEN: usize = 8 // epoch
+        + 8 // price
+        + 8 // apy
+        + 1; // quote
+}
+
+/// Append-only list of per-epoch snapshots of one asset, ordered by epoch
+#[account]
+pub struct EpochSnapshots {
+    pub asset_type: AssetType,
+    pub bump: u8,
+    pub snapshots: Vec<EpochSnapshot>,
+}
+
+impl EpochSnapshots {
+    /// Serialized size at full capacity, excluding the 8-byte account discriminator
+    pub const LEN: usize = 1 // asset_type
+        + 1 // bump
+        + 4 + EpochSnapshot::LEN * EPOCH_SNAPSHOT_CAPACITY; // snapshots
+
+    /// Appends a snapshot for `epoch`, which must be later than the last recorded one
+    pub fn record(&mut self, snapshot: EpochSnapshot) -> Result<()> {
+        if matches!(self.snapshots.last(), Some(last) if last.epoch >= snapshot.epoch) {
+            msg!("{:?} already has a snapshot for epoch {}", self.asset_type, snapshot.epoch);
+            return Err(error!(OracleError::AlreadySnapshotted));
+        }
+        if self.snapshots.len() >= EPOCH_SNAPSHOT_CAPACITY {
+            msg!("{:?} snapshot list is full ({} epochs)", self.asset_type, EPOCH_SNAPSHOT_CAPACITY);
+            return Err(error!(OracleError::EpochSnapshotsFull));
+        }
+        self.snapshots.push(snapshot);
+        Ok(())
+    }
+
+    /// Removes the snapshots of epochs before `before_epoch`, making room for new ones.
+    /// Returns the number removed.
+    pub fn prune_before(&mut self, before_epoch: u64) -> usize {
+        let removed = self.snapshots.partition_point(|snapshot| snapshot.epoch < before_epoch);
+        self.snapshots.drain(..removed);
+        removed
+    }
+
+    /// The snapshot recorded for `epoch`, if any
+    pub fn get(&self, epoch: u64) -> Option<&EpochSnapshot> {
+        self.snapshots
+            .binary_search_by_key(&epoch, |snapshot| snapshot.epoch)
+            .ok()
+            .map(|index| &self.snapshots[index])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshots() -> EpochSnapshots {
+        EpochSnapshots { asset_type: AssetType::SOL, bump: 255, snapshots: Vec::new() }
+    }
+
+    fn snapshot(epoch: u64) -> EpochSnapshot {
+        EpochSnapshot { epoch, price: epoch * 10, apy: epoch, quote: QuoteCurrency::SOL }
+    }
+
+    #[test]
+    fn test_record_once_per_epoch() {
+        let mut snapshots = snapshots();
+        snapshots.record(snapshot(500)).unwrap();
+        snapshots.record(snapshot(502)).unwrap();
+
+        let error = snapshots.record(snapshot(502)).unwrap_err();
+        assert_eq!(error, error!(OracleError::AlreadySnapshotted));
+        let error = snapshots.record(snapshot(501)).unwrap_err();
+        assert_eq!(error, error!(OracleError::AlreadySnapshotted));
+
+        assert_eq!(snapshots.get(502), Some(&snapshot(502)));
+        assert_eq!(snapshots.get(500), Some(&snapshot(500)));
+        assert_eq!(snapshots.get(501), None);
+    }
+
+    #[test]
+    fn test_record_fails_when_full() {
+        let mut snapshots = snapshots();
+        for epoch in 0..EPOCH_SNAPSHOT_CAPACITY as u64 {
+            snapshots.record(snapshot(epoch)).unwrap();
+        }
+        let error = snapshots.record(snapshot(EPOCH_SNAPSHOT_CAPACITY as u64)).unwrap_err();
+        assert_eq!(error, error!(OracleError::EpochSnapshotsFull));
+
+        let mut data = Vec::new();
+        snapshots.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), 8 + EpochSnapshots::LEN);
+
+        // Pruning the oldest epochs makes room again
+        assert_eq!(snapshots.prune_before(2), 2);
+        assert_eq!(snapshots.get(1), None);
+        assert_eq!(snapshots.get(2), Some(&snapshot(2)));
+        snapshots.record(snapshot(EPOCH_SNAPSHOT_CAPACITY as u64)).unwrap();
+        assert_eq!(snapshots.prune_before(0), 0);
+    }
+}
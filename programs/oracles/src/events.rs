@@ -0,0 +1,696 @@
+use anchor_lang::prelude::*;
+use crate::basket::BasketWeight;
+use crate::price_oracle::{AssetOutcome, AssetType, Confidence, LargeMovePolicy, OracleConfig, PriceProvenance, QuoteCurrency, SourceKind, SourcePolicy, SourceReading, SourceSlot, MAX_WATCHERS};
+use crate::thresholds::ThresholdDirection;
+use crate::yield_convention::YieldConfig;
+
+/// Emitted whenever the emergency stop state changes. `actor` is the program itself when
+/// the circuit breaker tripped it.
+#[event]
+pub struct EmergencyStopChanged {
+    pub actor: Pubkey,
+    pub old_state: bool,
+    pub new_state: bool,
+    pub forced: bool,
+    pub reason_code: u8,
+    pub change_count: u64,
+    /// The stop was triggered by the emergency council rather than the admin or operator
+    pub by_council: bool,
+    /// Id of the stop incident begun or ended
+    pub incident: u64,
+    /// Seconds the incident lasted when this change ended it, `0` when it began one
+    pub stopped_secs: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when a resume approver votes to clear the current stop incident
+#[event]
+pub struct ResumeApproved {
+    pub approver: Pubkey,
+    pub incident: u64,
+    /// Approvals the incident has, this one included
+    pub approvals: u8,
+    pub threshold: u8,
+    pub timestamp: i64,
+}
+
+/// Emitted when `set_emergency_stop(false)` schedules the stop to clear after the config timelock
+#[event]
+pub struct EmergencyStopClearScheduled {
+    pub actor: Pubkey,
+    pub effective_at: i64,
+    pub timestamp: i64,
+}
+
+/// Emitted when `set_switchboard_program_id` points the oracle at a new Switchboard program
+#[event]
+pub struct SwitchboardProgramIdChanged {
+    pub actor: Pubkey,
+    pub old_program_id: Pubkey,
+    pub new_program_id: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Emitted by an update when the supplied lease escrow is below `header.min_lease_balance`
+#[event]
+pub struct LeaseLow {
+    pub aggregator: Pubkey,
+    pub lease: Pubkey,
+    pub balance: u64,
+    pub threshold: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when `set_asset_quote` changes the currency an asset is quoted in
+#[event]
+pub struct QuoteCurrencyChanged {
+    pub actor: Pubkey,
+    pub asset_type: AssetType,
+    pub old_quote: QuoteCurrency,
+    pub new_quote: QuoteCurrency,
+    pub timestamp: i64,
+}
+
+/// Emitted when `set_emergency_council` changes the council key
+#[event]
+pub struct EmergencyCouncilChanged {
+    pub actor: Pubkey,
+    pub old_council: Pubkey,
+    pub new_council: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Emitted when `pause_asset` or `resume_asset` changes an asset's pause state
+#[event]
+pub struct AssetPauseChanged {
+    pub actor: Pubkey,
+    pub asset_type: AssetType,
+    pub paused: bool,
+    /// The pause was triggered by the emergency council rather than the admin or operator
+    pub by_council: bool,
+    pub timestamp: i64,
+}
+
+/// Emitted when `delist_asset` retires an asset
+#[event]
+pub struct AssetDelisted {
+    pub actor: Pubkey,
+    pub asset_type: AssetType,
+    pub timestamp: i64,
+}
+
+/// Emitted by every update instruction for each asset whose price it stored
+#[event]
+pub struct PriceUpdated {
+    pub asset_type: AssetType,
+    pub price: u64,
+    /// Stored APY in basis points, rounded as in `fixed_to_bps` and capped at `u32::MAX`
+    pub apy_bps: u32,
+    pub provenance: PriceProvenance,
+    pub confidence: Confidence,
+    /// Time the source produced the price; `timestamp` is when it was stored
+    pub feed_timestamp: i64,
+    pub timestamp: i64,
+}
+
+/// Emitted by the fixed-feed updates with what happened to each asset they cover
+#[event]
+pub struct AssetsUpdated {
+    pub outcomes: Vec<AssetOutcome>,
+    pub timestamp: i64,
+}
+
+/// Emitted by `update_asset_from_sources` with every source's reading
+#[event]
+pub struct SourcesAggregated {
+    pub asset_type: AssetType,
+    /// Median of the accepted readings, or the serving source's value under
+    /// `SourcePolicy::Priority`, now the asset's price
+    pub price: u64,
+    pub readings: Vec<SourceReading>,
+    pub timestamp: i64,
+    /// Level of the source that served the price under `SourcePolicy::Priority`, `0` for
+    /// the primary; `None` for a median
+    pub priority_level: Option<u8>,
+}
+
+/// Emitted by `update_asset_from_sources` when a source below the primary served the
+/// price because every source ahead of it was stale or failed validation. The reasons
+/// are in the `SourcesAggregated` readings emitted beside it. Also emitted by
+/// `update_assets`, at level `1`, for an asset read from its fallback source because its
+/// registered feed was stale.
+#[event]
+pub struct SourceFailover {
+    pub asset_type: AssetType,
+    /// Level of the serving source, `1` for the first fallback
+    pub priority_level: u8,
+    pub kind: SourceKind,
+    pub feed: Pubkey,
+    pub price: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted by every update instruction and by `heartbeat`, so monitoring can tell a
+/// live keeper whose feeds did not change from one that stopped running
+#[event]
+pub struct Heartbeat {
+    pub keeper: Pubkey,
+    pub slot: u64,
+    pub timestamp: i64,
+    /// Assets whose price the run stored
+    pub updated: u8,
+    /// Assets the run left untouched after rejecting them
+    pub skipped: u8,
+    /// Assets whose feed confirmed the stored price, so only their check time was written
+    pub unchanged: u8,
+}
+
+/// Emitted when `propose_config` queues a config change
+#[event]
+pub struct ConfigProposed {
+    pub actor: Pubkey,
+    pub config: OracleConfig,
+    /// Time from which `apply_config` can activate it
+    pub effective_at: i64,
+    pub timestamp: i64,
+}
+
+/// Emitted when `apply_config` activates the pending config
+#[event]
+pub struct ConfigApplied {
+    /// Whoever sent `apply_config`, not necessarily the proposer
+    pub actor: Pubkey,
+    pub config: OracleConfig,
+    pub timestamp: i64,
+}
+
+/// Emitted when `cancel_config` drops the pending config
+#[event]
+pub struct ConfigCancelled {
+    pub actor: Pubkey,
+    pub config: OracleConfig,
+    pub timestamp: i64,
+}
+
+/// Emitted when an update holds a move past the price change limit for confirmation,
+/// including when a new candidate replaces an unconfirmed one
+#[event]
+pub struct PriceMoveProposed {
+    pub asset_type: AssetType,
+    /// Stored price the move is measured from, unchanged until the move is confirmed
+    pub current_price: u64,
+    pub candidate_price: u64,
+    /// Earliest time an observation can confirm the candidate
+    pub confirmable_at: i64,
+    pub timestamp: i64,
+}
+
+/// Emitted when a pending move is committed by a confirming observation
+#[event]
+pub struct PriceMoveConfirmed {
+    pub asset_type: AssetType,
+    pub candidate_price: u64,
+    /// Price stored by the confirming observation
+    pub price: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when a pending move is dropped by a reverting observation, a replacing
+/// candidate or the asset leaving `LargeMovePolicy::Confirm`
+#[event]
+pub struct PriceMoveDiscarded {
+    pub asset_type: AssetType,
+    pub candidate_price: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when `set_large_move_policy` changes an asset's policy
+#[event]
+pub struct LargeMovePolicyChanged {
+    pub actor: Pubkey,
+    pub asset_type: AssetType,
+    pub old_policy: LargeMovePolicy,
+    pub new_policy: LargeMovePolicy,
+    pub timestamp: i64,
+}
+
+/// Emitted when `set_source_policy` changes how an asset's sources are combined
+#[event]
+pub struct SourcePolicySet {
+    pub actor: Pubkey,
+    pub asset_type: AssetType,
+    pub old_policy: SourcePolicy,
+    pub policy: SourcePolicy,
+    pub timestamp: i64,
+}
+
+/// Emitted when an update writes an asset's price but holds back its incoming APY for
+/// moving more than the asset's APY change limit, or for not converting from the asset's
+/// yield convention
+#[event]
+pub struct ApyChangeRejected {
+    pub asset_type: AssetType,
+    /// APY kept in place of the incoming one
+    pub current_apy: u64,
+    pub rejected_apy: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when `reset_watermarks` clears an asset's highest and lowest accepted prices
+#[event]
+pub struct WatermarksReset {
+    pub actor: Pubkey,
+    pub asset_type: AssetType,
+    /// Watermarks that were cleared, zero when none had been recorded
+    pub old_high: u64,
+    pub old_low: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when `set_subscriber` replaces the subscriber registration
+#[event]
+pub struct SubscriberSet {
+    pub actor: Pubkey,
+    pub program_id: Pubkey,
+    pub account_count: u8,
+    pub enabled: bool,
+    pub required: bool,
+    pub timestamp: i64,
+}
+
+/// Emitted after an update called the subscriber back with the assets it wrote
+#[event]
+pub struct SubscriberNotified {
+    pub program_id: Pubkey,
+    pub assets: Vec<AssetType>,
+    pub timestamp: i64,
+}
+
+/// Emitted when an update kept its prices but skipped the callback of a best-effort
+/// subscriber it could not deliver to
+#[event]
+pub struct SubscriberSkipped {
+    pub program_id: Pubkey,
+    pub assets: Vec<AssetType>,
+    /// Why the callback could not be delivered, an `OracleError` code
+    pub error_code: u32,
+    pub timestamp: i64,
+}
+
+/// Emitted when `set_trusted_publisher` sets or clears the key whose signed prices are accepted
+#[event]
+pub struct TrustedPublisherChanged {
+    pub actor: Pubkey,
+    pub old_publisher: Pubkey,
+    /// `Pubkey::default()` when signed prices were disabled
+    pub new_publisher: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Emitted when `set_on_demand_queue` sets or clears the queue On-Demand feeds must belong to
+#[event]
+pub struct OnDemandQueueChanged {
+    pub actor: Pubkey,
+    pub old_queue: Pubkey,
+    /// `Pubkey::default()` when On-Demand feeds were refused again
+    pub new_queue: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Emitted when `set_pyth_feed_id` binds or clears the Pyth feed id an asset's Pyth sources
+/// must carry
+#[event]
+pub struct PythFeedIdChanged {
+    pub actor: Pubkey,
+    pub asset_type: AssetType,
+    /// All zeros when no id was bound
+    pub old_feed_id: [u8; 32],
+    /// All zeros when the binding was cleared
+    pub new_feed_id: [u8; 32],
+    pub timestamp: i64,
+}
+
+/// Emitted by `attest_prices` with the payload to post as a cross-chain message
+#[event]
+pub struct PricesAttested {
+    pub sequence: u64,
+    /// Canonical encoding, see the `attestation` module
+    pub payload: Vec<u8>,
+    pub timestamp: i64,
+}
+
+/// Emitted once by every instruction that changed the data account, with its new nonce
+#[event]
+pub struct PriceDataChanged {
+    pub nonce: u64,
+    /// Commitment to every stored price after the change, see `PriceOracleData::state_hash`
+    pub state_hash: [u8; 32],
+    pub timestamp: i64,
+}
+
+/// Emitted by `update_assets` for the assets it skipped because their feed had not
+/// published a new round since the stored price
+#[event]
+pub struct RoundNotAdvanced {
+    pub assets: Vec<AssetType>,
+    pub timestamp: i64,
+}
+
+/// Emitted by `update_assets` for the assets whose feed confirmed the stored price, so
+/// that only their check time was written
+#[event]
+pub struct PricesUnchanged {
+    pub assets: Vec<AssetType>,
+    pub timestamp: i64,
+}
+
+/// Emitted whenever `set_updates_paused` pauses or resumes price updates. Unlike
+/// `EmergencyStopChanged`, reads keep serving the stored prices throughout.
+#[event]
+pub struct UpdatesPausedChanged {
+    pub actor: Pubkey,
+    pub paused: bool,
+    pub timestamp: i64,
+}
+
+/// Emitted by `create_checkpoint`; the checkpoint at `account` is never written again
+#[event]
+pub struct CheckpointCreated {
+    pub creator: Pubkey,
+    pub id: u64,
+    pub account: Pubkey,
+    pub slot: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted by `close_checkpoint` once the rent has gone back to the creator
+#[event]
+pub struct CheckpointClosed {
+    pub actor: Pubkey,
+    pub id: u64,
+    pub creator: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Emitted when `set_watcher` adds a program to the watcher allowlist or removes it
+#[event]
+pub struct WatchersChanged {
+    pub actor: Pubkey,
+    pub program_id: Pubkey,
+    pub allowed: bool,
+    /// The allowlist after the change, empty slots `Pubkey::default()`
+    pub watchers: [Pubkey; MAX_WATCHERS],
+    pub timestamp: i64,
+}
+
+/// Emitted by every `trigger_emergency_stop_cpi`, even when the oracle was already stopped.
+/// `EmergencyStopChanged` follows when it was not, with the watcher program as `actor`.
+#[event]
+pub struct WatcherStopTriggered {
+    pub watcher_program: Pubkey,
+    pub reason_code: u8,
+    pub already_stopped: bool,
+    /// Id of the stop incident in effect
+    pub incident: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when `set_thread_authority` changes the automation thread allowed to sign updates
+#[event]
+pub struct ThreadAuthorityChanged {
+    pub actor: Pubkey,
+    pub old_thread_authority: Pubkey,
+    /// `Pubkey::default()` when the thread was revoked
+    pub new_thread_authority: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Emitted when `set_asset_authority` changes the key allowed the asset's scoped admin actions
+#[event]
+pub struct AssetAuthorityChanged {
+    pub actor: Pubkey,
+    pub asset_type: AssetType,
+    pub old_authority: Option<Pubkey>,
+    pub new_authority: Option<Pubkey>,
+    pub timestamp: i64,
+}
+
+/// Emitted by `check_thresholds` for each threshold it triggers. It is not emitted again for
+/// that threshold until `clear_threshold` resets it.
+#[event]
+pub struct ThresholdCrossed {
+    pub asset_type: AssetType,
+    /// Position of the threshold among the asset's
+    pub index: u8,
+    pub direction: ThresholdDirection,
+    /// The level crossed
+    pub threshold: u64,
+    /// Stored price that crossed it, in `quote`
+    pub price: u64,
+    pub quote: QuoteCurrency,
+    pub timestamp: i64,
+}
+
+/// Emitted by `refresh_if_stale` when every multi-asset feed asset was fresh, so it wrote nothing
+#[event]
+pub struct RefreshSkipped {
+    pub caller: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Emitted by `create_basket` and `update_basket_weights` with the basket's new components
+#[event]
+pub struct BasketWeightsSet {
+    pub creator: Pubkey,
+    pub basket: Pubkey,
+    pub id: u64,
+    /// In asset order
+    pub weights: Vec<BasketWeight>,
+    pub timestamp: i64,
+}
+
+/// Emitted by `close_basket` once the rent has gone back to the creator
+#[event]
+pub struct BasketClosed {
+    pub creator: Pubkey,
+    pub basket: Pubkey,
+    pub id: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when `propose_feed_mapping` queues a new feed mapping
+#[event]
+pub struct FeedMappingProposed {
+    pub actor: Pubkey,
+    /// Asset of each position of the multi-asset feed
+    pub assets: Vec<AssetType>,
+    /// Time from which `apply_feed_mapping` can activate it
+    pub effective_at: i64,
+    pub timestamp: i64,
+}
+
+/// Emitted when `apply_feed_mapping` activates the pending feed mapping
+#[event]
+pub struct FeedMappingApplied {
+    /// Whoever sent `apply_feed_mapping`, not necessarily the proposer
+    pub actor: Pubkey,
+    pub assets: Vec<AssetType>,
+    pub timestamp: i64,
+}
+
+/// Emitted when `cancel_feed_mapping` drops the pending feed mapping
+#[event]
+pub struct FeedMappingCancelled {
+    pub actor: Pubkey,
+    pub assets: Vec<AssetType>,
+    pub timestamp: i64,
+}
+
+/// Emitted when `propose_asset_fallback` queues a fallback source for an asset
+#[event]
+pub struct AssetFallbackProposed {
+    pub actor: Pubkey,
+    pub asset_type: AssetType,
+    /// `None` when the proposal clears the fallback
+    pub fallback: Option<SourceSlot>,
+    /// Time from which `apply_asset_fallback` can register it
+    pub effective_at: i64,
+    pub timestamp: i64,
+}
+
+/// Emitted when `apply_asset_fallback` registers the pending fallback
+#[event]
+pub struct AssetFallbackApplied {
+    /// Whoever sent `apply_asset_fallback`, not necessarily the proposer
+    pub actor: Pubkey,
+    pub asset_type: AssetType,
+    pub old_fallback: Option<SourceSlot>,
+    pub new_fallback: Option<SourceSlot>,
+    pub timestamp: i64,
+}
+
+/// Emitted when `cancel_asset_fallback` drops the pending fallback
+#[event]
+pub struct AssetFallbackCancelled {
+    pub actor: Pubkey,
+    pub asset_type: AssetType,
+    pub fallback: Option<SourceSlot>,
+    pub timestamp: i64,
+}
+
+/// Emitted when `propose_feed_shard` queues registering, remapping or clearing a shard of
+/// the multi-asset feed
+#[event]
+pub struct FeedShardProposed {
+    pub actor: Pubkey,
+    pub shard: u8,
+    /// `Pubkey::default()` when the proposal clears the shard
+    pub feed: Pubkey,
+    /// Assets the shard would carry, in position order; empty when it clears the shard
+    pub assets: Vec<AssetType>,
+    /// Time from which `apply_feed_shard` can register it
+    pub effective_at: i64,
+    pub timestamp: i64,
+}
+
+/// Emitted when `apply_feed_shard` registers the shard's pending change
+#[event]
+pub struct FeedShardApplied {
+    /// Whoever sent `apply_feed_shard`, not necessarily the proposer
+    pub actor: Pubkey,
+    pub shard: u8,
+    /// `Pubkey::default()` when the shard was cleared
+    pub feed: Pubkey,
+    /// Assets the shard carries, in position order; empty when it was cleared
+    pub assets: Vec<AssetType>,
+    pub timestamp: i64,
+}
+
+/// Emitted when `cancel_feed_shard` drops the shard's pending change
+#[event]
+pub struct FeedShardCancelled {
+    pub actor: Pubkey,
+    pub shard: u8,
+    pub feed: Pubkey,
+    pub assets: Vec<AssetType>,
+    pub timestamp: i64,
+}
+
+/// Emitted when `force_update` writes a feed price past the change limit
+#[event]
+pub struct PriceForced {
+    pub actor: Pubkey,
+    pub asset_type: AssetType,
+    /// Stored price the forced one replaced
+    pub old_price: u64,
+    pub new_price: u64,
+    pub quote: QuoteCurrency,
+    /// Move from the old price in basis points, `u32::MAX` when it does not fit or there was none
+    pub change_bps: u32,
+    pub provenance: PriceProvenance,
+    pub feed_timestamp: i64,
+    pub timestamp: i64,
+}
+
+/// Emitted when an update stores an asset's price clamped to the change limit under
+/// `LargeMovePolicy::Clamp`, short of what its feed reported
+#[event]
+pub struct PriceClamped {
+    pub asset_type: AssetType,
+    /// Value the feed reported
+    pub feed_price: u64,
+    /// Value stored, `limit_bps` away from `previous_price` toward `feed_price`
+    pub price: u64,
+    pub previous_price: u64,
+    pub quote: QuoteCurrency,
+    pub limit_bps: u32,
+    pub timestamp: i64,
+}
+
+/// Emitted when an update stores a move past the price change limit that only the widened
+/// limit of the grace period after a resume let through
+#[event]
+pub struct ResumeGraceLimitApplied {
+    pub asset_type: AssetType,
+    pub price: u64,
+    pub previous_price: u64,
+    /// Limit outside the grace period
+    pub limit_bps: u32,
+    /// Widened limit the move was accepted under
+    pub widened_limit_bps: u32,
+    pub grace_ends_at: i64,
+    pub timestamp: i64,
+}
+
+/// Emitted when `set_asset_mint` registers an asset's token mint and the decimals read from it
+#[event]
+pub struct AssetMintSet {
+    pub actor: Pubkey,
+    pub asset_type: AssetType,
+    /// `Pubkey::default()` when the asset had no mint
+    pub old_mint: Pubkey,
+    pub mint: Pubkey,
+    /// SPL Token or Token-2022, whichever owns the mint
+    pub token_program: Pubkey,
+    pub decimals: u8,
+    pub timestamp: i64,
+}
+
+/// Emitted when `reinitialize_data` rebuilds the data account, wiping every stored price.
+/// Readers should treat everything they cached from the data account as gone.
+#[event]
+pub struct PriceDataReinitialized {
+    pub actor: Pubkey,
+    /// `PriceOracleHeader::data_generation` after the rebuild
+    pub data_generation: u32,
+    /// Size of the account before it was rebuilt, discriminator included
+    pub previous_len: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when `set_yield_convention` changes the convention an asset's yield is reported in
+#[event]
+pub struct YieldConventionSet {
+    pub actor: Pubkey,
+    pub asset_type: AssetType,
+    pub old_config: YieldConfig,
+    pub config: YieldConfig,
+    pub timestamp: i64,
+}
+
+/// Emitted by `verify_invariants` with the mask of the invariants it found violated
+#[event]
+pub struct InvariantsVerified {
+    pub header: Pubkey,
+    /// Bits of the `invariants` module, `0` when every invariant holds
+    pub violations: u32,
+    pub nonce: u64,
+    pub data_generation: u32,
+    pub timestamp: i64,
+}
+
+/// Emitted by `prune_history` with the entries it dropped from each history passed. The
+/// write cursor is unchanged, so the kept entries are read as before.
+#[event]
+pub struct HistoryPruned {
+    pub actor: Pubkey,
+    pub asset_type: AssetType,
+    pub keep_last_n: u8,
+    /// Price observations dropped, `0` when the price history was not passed
+    pub observations_removed: u8,
+    /// APY samples dropped, `0` when the APY history was not passed
+    pub apy_samples_removed: u8,
+    pub timestamp: i64,
+}
+
+/// Emitted by `prune_epoch_snapshots` with the number of snapshots it removed
+#[event]
+pub struct EpochSnapshotsPruned {
+    pub actor: Pubkey,
+    pub asset_type: AssetType,
+    pub before_epoch: u64,
+    pub removed: u32,
+    /// Snapshots still held, all of epoch `before_epoch` or later
+    pub remaining: u32,
+    pub timestamp: i64,
+}
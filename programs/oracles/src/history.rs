@@ -0,0 +1,399 @@
+use anchor_lang::prelude::*;
+use crate::price_oracle::{AssetType, ASSET_COUNT};
+use crate::switchboard_utils::PRICE_SCALE;
+
+/// Number of observations kept per asset
+pub const HISTORY_CAPACITY: usize = 32;
+/// Fewest observations in the window for a volatility figure
+pub const MIN_VOLATILITY_OBSERVATIONS: usize = 5;
+/// Number of APY samples kept per asset
+pub const APY_HISTORY_CAPACITY: usize = 64;
+/// Least time between two APY samples of an asset, so the ring spans eight days
+pub const APY_SAMPLE_INTERVAL_SECS: i64 = 3 * 3_600;
+
+/// A single recorded price
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct PriceObservation {
+    pub price: u64,
+    pub timestamp: i64,
+}
+
+impl PriceObservation {
+    pub const LEN: usize = 8 // price
+        + 8; // timestamp
+}
+
+/// Ring buffer of the most recent observations of one asset
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct AssetHistory {
+    pub observations: [PriceObservation; HISTORY_CAPACITY],
+    /// Slot the next observation is written to
+    pub next_index: u8,
+    /// Number of valid observations, at most `HISTORY_CAPACITY`
+    pub len: u8,
+}
+
+impl Default for AssetHistory {
+    fn default() -> Self {
+        AssetHistory {
+            observations: [PriceObservation::default(); HISTORY_CAPACITY],
+            next_index: 0,
+            len: 0,
+        }
+    }
+}
+
+impl AssetHistory {
+    pub const LEN: usize = PriceObservation::LEN * HISTORY_CAPACITY // observations
+        + 1 // next_index
+        + 1; // len
+
+    /// Appends an observation, overwriting the oldest once full
+    pub fn push(&mut self, price: u64, timestamp: i64) {
+        self.observations[self.next_index as usize] = PriceObservation { price, timestamp };
+        self.next_index = ((self.next_index as usize + 1) % HISTORY_CAPACITY) as u8;
+        self.len = (self.len as usize + 1).min(HISTORY_CAPACITY) as u8;
+    }
+
+    /// Valid observations, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = PriceObservation> + '_ {
+        let start = (self.next_index as usize + HISTORY_CAPACITY - self.len as usize) % HISTORY_CAPACITY;
+        (0..self.len as usize).map(move |offset| self.observations[(start + offset) % HISTORY_CAPACITY])
+    }
+
+    /// Prices observed within `window` seconds of `current_time`, oldest first
+    pub fn prices_within(&self, current_time: i64, window: i64) -> Vec<u64> {
+        self.iter()
+            .filter(|observation| current_time.saturating_sub(observation.timestamp) <= window)
+            .map(|observation| observation.price)
+            .collect()
+    }
+
+    /// Most recent observation at or before `timestamp`, the price in effect at that time.
+    /// `None` when `timestamp` predates the oldest observation held.
+    pub fn at_or_before(&self, timestamp: i64) -> Option<PriceObservation> {
+        // Observations are recorded in time order, so the ones at or before `timestamp` are
+        // a prefix of the oldest-first iteration
+        self.iter().take_while(|observation| observation.timestamp <= timestamp).last()
+    }
+
+    /// Drops all but the `keep` most recent observations, clearing their slots. The kept
+    /// ones stay where they are and `next_index` is unchanged, so the next push lands where
+    /// it would have. Returns the number dropped.
+    pub fn retain_latest(&mut self, keep: usize) -> usize {
+        let removed = (self.len as usize).saturating_sub(keep);
+        let start = (self.next_index as usize + HISTORY_CAPACITY - self.len as usize) % HISTORY_CAPACITY;
+        for offset in 0..removed {
+            self.observations[(start + offset) % HISTORY_CAPACITY] = PriceObservation::default();
+        }
+        self.len -= removed as u8;
+        removed
+    }
+}
+
+/// Per-asset price history fed by the update instructions
+#[account]
+#[derive(Default)]
+pub struct PriceHistory {
+    pub assets: [AssetHistory; ASSET_COUNT],
+    pub bump: u8,
+}
+
+impl PriceHistory {
+    /// Serialized size, excluding the 8-byte account discriminator
+    pub const LEN: usize = AssetHistory::LEN * ASSET_COUNT // assets
+        + 1; // bump
+
+    pub fn asset(&self, asset_type: AssetType) -> &AssetHistory {
+        &self.assets[asset_type.index()]
+    }
+}
+
+/// An asset's stored APY at one time, in basis points
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct ApySample {
+    pub apy_bps: u32,
+    pub timestamp: i64,
+}
+
+impl ApySample {
+    pub const LEN: usize = 4 // apy_bps
+        + 8; // timestamp
+}
+
+/// Ring buffer of the most recent APY samples of one asset, at least
+/// `APY_SAMPLE_INTERVAL_SECS` apart
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct AssetApyHistory {
+    pub samples: [ApySample; APY_HISTORY_CAPACITY],
+    /// Slot the next sample is written to
+    pub next_index: u8,
+    /// Number of valid samples, at most `APY_HISTORY_CAPACITY`
+    pub len: u8,
+}
+
+impl Default for AssetApyHistory {
+    fn default() -> Self {
+        AssetApyHistory {
+            samples: [ApySample::default(); APY_HISTORY_CAPACITY],
+            next_index: 0,
+            len: 0,
+        }
+    }
+}
+
+impl AssetApyHistory {
+    pub const LEN: usize = ApySample::LEN * APY_HISTORY_CAPACITY // samples
+        + 1 // next_index
+        + 1; // len
+
+    /// Appends a sample unless the latest one is less than `APY_SAMPLE_INTERVAL_SECS` old,
+    /// overwriting the oldest once full. Returns whether it was appended.
+    pub fn push(&mut self, apy_bps: u32, timestamp: i64) -> bool {
+        if let Some(latest) = self.latest() {
+            if timestamp < latest.timestamp.saturating_add(APY_SAMPLE_INTERVAL_SECS) {
+                return false;
+            }
+        }
+        self.samples[self.next_index as usize] = ApySample { apy_bps, timestamp };
+        self.next_index = ((self.next_index as usize + 1) % APY_HISTORY_CAPACITY) as u8;
+        self.len = (self.len as usize + 1).min(APY_HISTORY_CAPACITY) as u8;
+        true
+    }
+
+    pub fn latest(&self) -> Option<ApySample> {
+        (self.len > 0).then(|| self.samples[(self.next_index as usize + APY_HISTORY_CAPACITY - 1) % APY_HISTORY_CAPACITY])
+    }
+
+    /// Valid samples, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = ApySample> + '_ {
+        let start = (self.next_index as usize + APY_HISTORY_CAPACITY - self.len as usize) % APY_HISTORY_CAPACITY;
+        (0..self.len as usize).map(move |offset| self.samples[(start + offset) % APY_HISTORY_CAPACITY])
+    }
+
+    /// Number of samples within `window` seconds of `current_time` and their simple
+    /// average in basis points (truncated), `None` when there are none
+    pub fn average_within(&self, current_time: i64, window: i64) -> (usize, Option<u32>) {
+        let (count, sum) = self
+            .iter()
+            .filter(|sample| current_time.saturating_sub(sample.timestamp) <= window)
+            .fold((0u64, 0u64), |(count, sum), sample| (count + 1, sum + sample.apy_bps as u64));
+        (count as usize, (count > 0).then(|| (sum / count) as u32))
+    }
+
+    /// Drops all but the `keep` most recent samples, as `AssetHistory::retain_latest` does.
+    /// Returns the number dropped.
+    pub fn retain_latest(&mut self, keep: usize) -> usize {
+        let removed = (self.len as usize).saturating_sub(keep);
+        let start = (self.next_index as usize + APY_HISTORY_CAPACITY - self.len as usize) % APY_HISTORY_CAPACITY;
+        for offset in 0..removed {
+            self.samples[(start + offset) % APY_HISTORY_CAPACITY] = ApySample::default();
+        }
+        self.len -= removed as u8;
+        removed
+    }
+}
+
+/// Per-asset APY samples fed by the instructions that write APYs. Each sample is the APY
+/// the oracle serves after the update, so an APY held back by the change limit never
+/// enters the average.
+#[account]
+#[derive(Default)]
+pub struct ApyHistory {
+    pub assets: [AssetApyHistory; ASSET_COUNT],
+    /// Fewest samples in the window for `get_average_apy`, at least 1
+    pub min_samples: u8,
+    pub bump: u8,
+}
+
+impl ApyHistory {
+    /// Serialized size, excluding the 8-byte account discriminator
+    pub const LEN: usize = AssetApyHistory::LEN * ASSET_COUNT // assets
+        + 1 // min_samples
+        + 1; // bump
+
+    pub fn asset(&self, asset_type: AssetType) -> &AssetApyHistory {
+        &self.assets[asset_type.index()]
+    }
+}
+
+/// Sample standard deviation of simple returns `(p[i] - p[i-1]) / p[i-1]` over
+/// consecutive prices, in basis points (truncated). Returns are fixed point with
+/// `PRICE_DECIMALS` decimals; a zero price is skipped as a return base.
+/// `None` when fewer than two returns are available.
+pub fn simple_return_volatility_bps(prices: &[u64]) -> Option<u64> {
+    let scale = PRICE_SCALE as i128;
+    let returns: Vec<i128> = prices
+        .windows(2)
+        .filter(|pair| pair[0] > 0)
+        .map(|pair| (pair[1] as i128 - pair[0] as i128) * scale / pair[0] as i128)
+        .collect();
+    if returns.len() < 2 {
+        return None;
+    }
+
+    let count = returns.len() as i128;
+    let mean = returns.iter().sum::<i128>() / count;
+    let sum_of_squares = returns.iter().try_fold(0u128, |sum, r| {
+        let deviation = (r - mean).unsigned_abs();
+        deviation.checked_mul(deviation).and_then(|square| sum.checked_add(square))
+    });
+    let sum_of_squares = match sum_of_squares {
+        Some(sum_of_squares) => sum_of_squares,
+        None => return Some(u64::MAX),
+    };
+
+    let standard_deviation = isqrt(sum_of_squares / (count as u128 - 1));
+    Some(u64::try_from(standard_deviation * 10_000 / PRICE_SCALE as u128).unwrap_or(u64::MAX))
+}
+
+/// Floor of the square root
+fn isqrt(value: u128) -> u128 {
+    if value < 2 {
+        return value;
+    }
+    let mut x = value / 2 + 1;
+    let mut y = (x + value / x) / 2;
+    while y < x {
+        x = y;
+        y = (x + value / x) / 2;
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_buffer_keeps_most_recent() {
+        let mut history = AssetHistory::default();
+        for i in 0..(HISTORY_CAPACITY as i64 + 3) {
+            history.push(i as u64, i);
+        }
+        assert_eq!(history.len as usize, HISTORY_CAPACITY);
+        let timestamps: Vec<i64> = history.iter().map(|observation| observation.timestamp).collect();
+        assert_eq!(timestamps, (3..HISTORY_CAPACITY as i64 + 3).collect::<Vec<_>>());
+        assert_eq!(history.prices_within(HISTORY_CAPACITY as i64 + 2, 1), vec![HISTORY_CAPACITY as u64 + 1, HISTORY_CAPACITY as u64 + 2]);
+    }
+
+    #[test]
+    fn test_at_or_before_across_the_wrap() {
+        let mut history = AssetHistory::default();
+        assert_eq!(history.at_or_before(i64::MAX), None);
+
+        // Ten seconds apart, until the ring has wrapped five slots past its start
+        let count = HISTORY_CAPACITY as i64 + 5;
+        for i in 0..count {
+            history.push(1_000 + i as u64, 100 + i * 10);
+        }
+        assert_eq!(history.next_index, 5);
+        let observation = |i: i64| Some(PriceObservation { price: 1_000 + i as u64, timestamp: 100 + i * 10 });
+
+        // The five overwritten observations are gone
+        assert_eq!(history.at_or_before(100 + 4 * 10), None);
+        assert_eq!(history.at_or_before(100 + 5 * 10 - 1), None);
+        // The oldest held sits at `next_index`, at the end of the array scan
+        assert_eq!(history.at_or_before(100 + 5 * 10), observation(5));
+        // Last slot of the array and first one after it, either side of the boundary
+        let last_slot = HISTORY_CAPACITY as i64 - 1;
+        assert_eq!(history.at_or_before(100 + last_slot * 10), observation(last_slot));
+        assert_eq!(history.at_or_before(100 + last_slot * 10 + 9), observation(last_slot));
+        assert_eq!(history.at_or_before(100 + (last_slot + 1) * 10), observation(last_slot + 1));
+        // The newest holds for any later time
+        assert_eq!(history.at_or_before(100 + (count - 1) * 10), observation(count - 1));
+        assert_eq!(history.at_or_before(i64::MAX), observation(count - 1));
+    }
+
+    #[test]
+    fn test_retain_latest_keeps_the_cursor() {
+        let mut history = AssetHistory::default();
+        for i in 0..(HISTORY_CAPACITY as i64 + 5) {
+            history.push(1_000 + i as u64, i);
+        }
+        assert_eq!(history.retain_latest(HISTORY_CAPACITY + 1), 0);
+        assert_eq!(history.retain_latest(3), HISTORY_CAPACITY - 3);
+        assert_eq!(history.len, 3);
+        assert_eq!(history.next_index, 5);
+        let timestamps: Vec<i64> = history.iter().map(|observation| observation.timestamp).collect();
+        let newest = HISTORY_CAPACITY as i64 + 4;
+        assert_eq!(timestamps, vec![newest - 2, newest - 1, newest]);
+        // Dropped slots are cleared and no longer visible to lookups
+        assert_eq!(history.observations.iter().filter(|observation| observation.timestamp != 0).count(), 3);
+        assert_eq!(history.at_or_before(newest - 3), None);
+
+        // New observations continue from the same cursor
+        history.push(9_999, newest + 1);
+        assert_eq!(history.next_index, 6);
+        assert_eq!(history.prices_within(newest + 1, 1), vec![1_000 + newest as u64, 9_999]);
+
+        assert_eq!(history.retain_latest(0), 4);
+        assert_eq!(history.iter().count(), 0);
+        assert_eq!(history.next_index, 6);
+
+        let mut apy_history = AssetApyHistory::default();
+        for i in 0..4 {
+            apy_history.push(700 + i as u32, i * APY_SAMPLE_INTERVAL_SECS);
+        }
+        assert_eq!(apy_history.retain_latest(1), 3);
+        assert_eq!(apy_history.latest(), Some(ApySample { apy_bps: 703, timestamp: 3 * APY_SAMPLE_INTERVAL_SECS }));
+        assert_eq!(apy_history.average_within(3 * APY_SAMPLE_INTERVAL_SECS, i64::MAX), (1, Some(703)));
+    }
+
+    #[test]
+    fn test_apy_samples_are_spaced_and_averaged() {
+        let mut history = AssetApyHistory::default();
+        assert_eq!(history.average_within(0, i64::MAX), (0, None));
+
+        assert!(history.push(700, 1_000));
+        // Too soon after the latest sample
+        assert!(!history.push(9_000, 1_000 + APY_SAMPLE_INTERVAL_SECS - 1));
+        assert!(history.push(710, 1_000 + APY_SAMPLE_INTERVAL_SECS));
+        assert!(history.push(725, 1_000 + 2 * APY_SAMPLE_INTERVAL_SECS));
+        let now = 1_000 + 2 * APY_SAMPLE_INTERVAL_SECS;
+        assert_eq!(history.average_within(now, 2 * APY_SAMPLE_INTERVAL_SECS), (3, Some(711)));
+        assert_eq!(history.average_within(now, APY_SAMPLE_INTERVAL_SECS), (2, Some(717)));
+
+        // The ring keeps the latest `APY_HISTORY_CAPACITY` samples
+        for i in 3..APY_HISTORY_CAPACITY as i64 + 3 {
+            assert!(history.push(800, 1_000 + i * APY_SAMPLE_INTERVAL_SECS));
+        }
+        assert_eq!(history.len as usize, APY_HISTORY_CAPACITY);
+        assert_eq!(history.iter().next().unwrap().timestamp, 1_000 + 3 * APY_SAMPLE_INTERVAL_SECS);
+        assert_eq!(history.average_within(i64::MAX, i64::MAX), (APY_HISTORY_CAPACITY, Some(800)));
+    }
+
+    #[test]
+    fn test_apy_history_len_matches_serialized_size() {
+        assert_eq!(ApyHistory::default().try_to_vec().unwrap().len(), ApyHistory::LEN);
+    }
+
+    #[test]
+    fn test_simple_return_volatility() {
+        let scale = PRICE_SCALE;
+        // Alternating +10% / -10%: sample std dev is sqrt(0.04 / 3) = 11.547%
+        let alternating = [100 * scale, 110 * scale, 99 * scale, 108_900_000_000, 98_010_000_000];
+        assert_eq!(simple_return_volatility_bps(&alternating), Some(1_154));
+
+        // Steady +1 per step: returns shrink from 1% to 0.97%
+        let trending = [100 * scale, 101 * scale, 102 * scale, 103 * scale, 104 * scale];
+        assert_eq!(simple_return_volatility_bps(&trending), Some(1));
+
+        let choppy = [100 * scale, 102 * scale, 99 * scale, 105 * scale, 101 * scale, 103 * scale];
+        assert_eq!(simple_return_volatility_bps(&choppy), Some(405));
+
+        assert_eq!(simple_return_volatility_bps(&[7 * scale; 6]), Some(0));
+        assert_eq!(simple_return_volatility_bps(&[100 * scale, 110 * scale]), None);
+    }
+
+    #[test]
+    fn test_isqrt() {
+        for value in [0u128, 1, 2, 3, 4, 15, 16, 17, 1 << 64, u128::MAX] {
+            let root = isqrt(value);
+            assert!(root * root <= value);
+            if let Some(square) = (root + 1).checked_mul(root + 1) {
+                assert!(square > value);
+            }
+        }
+    }
+}
@@ -0,0 +1,30 @@
+//! Internal consistency checks `verify_invariants` runs over an instance's header and data
+//! account, for auditors and monitoring.
+//!
+//! The check never fails on a violation: it returns a mask with one bit set per invariant
+//! that does not hold, so a monitor can record partial failures. Each bit below names what
+//! it flags. Per-asset checks cover the entries of the data account.
+
+/// The header is not the PDA of its instance at its stored, canonical bump
+pub const HEADER_ADDRESS: u32 = 1 << 0;
+/// The data account is not the PDA of the header's instance at its stored, canonical bump
+pub const DATA_ADDRESS: u32 = 1 << 1;
+/// An asset's `last_update_time` or `last_checked_time`, or the header's
+/// `last_global_update`, is after the current clock
+pub const UPDATE_TIME_IN_FUTURE: u32 = 1 << 2;
+/// One of those times is negative
+pub const NEGATIVE_UPDATE_TIME: u32 = 1 << 3;
+/// `last_global_update` is older than an asset's `last_update_time`
+pub const GLOBAL_UPDATE_BEHIND: u32 = 1 << 4;
+/// A non-zero price lies outside the watermarks recorded since their last reset
+pub const PRICE_OUTSIDE_WATERMARKS: u32 = 1 << 5;
+/// An asset holds a pending move while its policy is not `LargeMovePolicy::Confirm`
+pub const PENDING_WITHOUT_CONFIRM: u32 = 1 << 6;
+/// A stop clear is scheduled while the emergency stop is not active
+pub const CLEAR_WITHOUT_STOP: u32 = 1 << 7;
+/// The data nonce is below the caller's previous reading of the same data generation,
+/// or the data generation went backwards
+pub const NONCE_REGRESSED: u32 = 1 << 8;
+
+/// Every invariant bit
+pub const ALL_INVARIANTS: u32 = (NONCE_REGRESSED << 1) - 1;
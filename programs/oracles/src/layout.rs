@@ -0,0 +1,590 @@
+//! Byte layout of the oracle's accounts, for readers that do not use Anchor.
+//!
+//! Native programs and off-chain services fetching the accounts with `getAccountInfo` can
+//! read fields at these positions instead of decoding Borsh. Offsets in `header` and `data`
+//! are from the start of the account data, discriminator included. Offsets in `price_data`
+//! are from the start of one asset's `PriceData` slot, found with `data::price_slot`.
+//!
+//! Integers are little-endian, booleans and fieldless enums one byte, keys 32 bytes, and
+//! arrays are laid out element after element in `AssetType` index order.
+//!
+//! The tests serialize real accounts and compare every field against these constants, so
+//! a layout change cannot land without updating them. Readers still break on one: fields
+//! are only ever appended and deployed accounts are rewritten by the `migrate_*`
+//! instructions, so check the account length before reading a field added late.
+use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+use crate::price_oracle::{AssetType, FeedMapping, FeedShard, LastError, LstPremium, PendingConfig, PendingFallback, PendingFeedMapping, PendingFeedShard, PendingPrice, PriceOracleData, QuoteCurrency, UpdateStats, ASSET_COUNT, MAX_FEED_SHARDS, MAX_RESUME_APPROVERS, MAX_WATCHERS};
+use crate::trust_score::TrustWeights;
+use crate::yield_convention::{RawYield, YieldConfig};
+
+/// Position of a field in an account or a `PriceData` slot
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Field {
+    pub offset: usize,
+    pub len: usize,
+}
+
+impl Field {
+    pub const fn at(offset: usize, len: usize) -> Self {
+        Field { offset, len }
+    }
+
+    /// The field of `len` bytes right after this one
+    pub const fn then(self, len: usize) -> Self {
+        Field { offset: self.end(), len }
+    }
+
+    /// Offset of the first byte after the field
+    pub const fn end(self) -> usize {
+        self.offset + self.len
+    }
+
+    /// The field's bytes in `data`, `None` when `data` is too short
+    pub fn bytes(self, data: &[u8]) -> Option<&[u8]> {
+        data.get(self.offset..self.end())
+    }
+}
+
+/// Anchor discriminator at the start of every account, the first 8 bytes of
+/// SHA-256(`account:<AccountName>`)
+pub const DISCRIMINATOR: Field = Field::at(0, 8);
+
+/// `PriceOracleHeader`, at `[b"price_oracle_header"]`
+pub mod header {
+    use super::*;
+
+    pub const LAST_GLOBAL_UPDATE: Field = DISCRIMINATOR.then(8);
+    pub const EMERGENCY_STOP: Field = LAST_GLOBAL_UPDATE.then(1);
+    pub const AUTHORITY: Field = EMERGENCY_STOP.then(32);
+    pub const SWITCHBOARD_PROGRAM_ID: Field = AUTHORITY.then(32);
+    pub const BUMP: Field = SWITCHBOARD_PROGRAM_ID.then(1);
+    pub const MAX_FEED_AGE_SLOTS: Field = BUMP.then(8);
+    pub const LAST_STOP_CHANGE_TIME: Field = MAX_FEED_AGE_SLOTS.then(8);
+    pub const STOP_CHANGE_COUNT: Field = LAST_STOP_CHANGE_TIME.then(8);
+    pub const MIN_STOP_CHANGE_INTERVAL: Field = STOP_CHANGE_COUNT.then(8);
+    pub const OPERATOR: Field = MIN_STOP_CHANGE_INTERVAL.then(32);
+    pub const LOCKED: Field = OPERATOR.then(1);
+    pub const MIN_LEASE_BALANCE: Field = LOCKED.then(8);
+    pub const REJECT_EMPTY_LEASE: Field = MIN_LEASE_BALANCE.then(1);
+    pub const SOURCES: Field = REJECT_EMPTY_LEASE.then(ASSET_COUNT);
+    pub const PARTIAL_UPDATES: Field = SOURCES.then(1);
+    pub const REJECTION_CODES: Field = PARTIAL_UPDATES.then(4 * ASSET_COUNT);
+    pub const MAX_AGE_SECS: Field = REJECTION_CODES.then(4 * ASSET_COUNT);
+    pub const PRICE_CHANGE_LIMIT_BPS: Field = MAX_AGE_SECS.then(4);
+    pub const CONFIG_TIMELOCK_SECS: Field = PRICE_CHANGE_LIMIT_BPS.then(4);
+    pub const PENDING_CONFIG: Field = CONFIG_TIMELOCK_SECS.then(PendingConfig::LEN);
+    pub const STOP_CLEAR_EFFECTIVE_AT: Field = PENDING_CONFIG.then(8);
+    pub const EMERGENCY_COUNCIL: Field = STOP_CLEAR_EFFECTIVE_AT.then(32);
+    pub const PAUSED: Field = EMERGENCY_COUNCIL.then(ASSET_COUNT);
+    pub const RESUME_APPROVERS: Field = PAUSED.then(32 * MAX_RESUME_APPROVERS);
+    pub const RESUME_THRESHOLD: Field = RESUME_APPROVERS.then(1);
+    pub const MANUAL_RESUME_NEEDS_APPROVAL: Field = RESUME_THRESHOLD.then(1);
+    pub const STOP_INCIDENT: Field = MANUAL_RESUME_NEEDS_APPROVAL.then(8);
+    pub const STOP_AUTOMATIC: Field = STOP_INCIDENT.then(1);
+    pub const RESUME_APPROVALS: Field = STOP_AUTOMATIC.then(8 * MAX_RESUME_APPROVERS);
+    pub const LARGE_MOVE_POLICY: Field = RESUME_APPROVALS.then(ASSET_COUNT);
+    pub const MOVE_CONFIRM_DELAY_SECS: Field = LARGE_MOVE_POLICY.then(4);
+    pub const MOVE_CONFIRM_TOLERANCE_BPS: Field = MOVE_CONFIRM_DELAY_SECS.then(4);
+    pub const PENDING_PRICES: Field = MOVE_CONFIRM_TOLERANCE_BPS.then(PendingPrice::LEN * ASSET_COUNT);
+    pub const UPDATE_STATS: Field = PENDING_PRICES.then(UpdateStats::LEN);
+    pub const APY_CHANGE_LIMIT_BPS: Field = UPDATE_STATS.then(4 * ASSET_COUNT);
+    pub const TRUSTED_PUBLISHER: Field = APY_CHANGE_LIMIT_BPS.then(32);
+    pub const SIGNED_PRICE_TIMES: Field = TRUSTED_PUBLISHER.then(8 * ASSET_COUNT);
+    pub const ATTESTATION_SEQUENCE: Field = SIGNED_PRICE_TIMES.then(8);
+    pub const HAIRCUT_BPS: Field = ATTESTATION_SEQUENCE.then(2 * ASSET_COUNT);
+    pub const AUDIT_LOG_ENABLED: Field = HAIRCUT_BPS.then(1);
+    pub const UPDATES_PAUSED: Field = AUDIT_LOG_ENABLED.then(1);
+    pub const THREAD_AUTHORITY: Field = UPDATES_PAUSED.then(32);
+    pub const FEED_MAPPING: Field = THREAD_AUTHORITY.then(FeedMapping::LEN);
+    pub const PENDING_FEED_MAPPING: Field = FEED_MAPPING.then(PendingFeedMapping::LEN);
+    pub const TRUST_WEIGHTS: Field = PENDING_FEED_MAPPING.then(TrustWeights::LEN);
+    pub const INSTANCE: Field = TRUST_WEIGHTS.then(32);
+    pub const CLAMPED_PRICES: Field = INSTANCE.then(8 * ASSET_COUNT);
+    pub const ASSET_MINTS: Field = CLAMPED_PRICES.then(32 * ASSET_COUNT);
+    pub const MINT_DECIMALS: Field = ASSET_MINTS.then(ASSET_COUNT);
+    pub const YIELD_CONFIGS: Field = MINT_DECIMALS.then(YieldConfig::LEN * ASSET_COUNT);
+    pub const RAW_YIELDS: Field = YIELD_CONFIGS.then(RawYield::LEN * ASSET_COUNT);
+    pub const DATA_GENERATION: Field = RAW_YIELDS.then(4);
+    pub const LAST_ERRORS: Field = DATA_GENERATION.then(LastError::LEN * ASSET_COUNT);
+    pub const SOURCE_POLICIES: Field = LAST_ERRORS.then(ASSET_COUNT);
+    pub const RESUMED_AT: Field = SOURCE_POLICIES.then(8);
+    pub const RESUME_GRACE_SECS: Field = RESUMED_AT.then(4);
+    pub const RESUME_GRACE_MULTIPLIER_BPS: Field = RESUME_GRACE_SECS.then(4);
+    pub const LAST_CHANGED_MASK: Field = RESUME_GRACE_MULTIPLIER_BPS.then(4);
+    pub const LAST_CHANGED_SLOT: Field = LAST_CHANGED_MASK.then(8);
+    pub const WATCHERS: Field = LAST_CHANGED_SLOT.then(32 * MAX_WATCHERS);
+    pub const STOP_WATCHER: Field = WATCHERS.then(32);
+    pub const PREMIUMS: Field = STOP_WATCHER.then(LstPremium::LEN * ASSET_COUNT);
+    pub const FEED_SHARDS: Field = PREMIUMS.then(FeedShard::LEN * MAX_FEED_SHARDS);
+    pub const STOP_STARTED_AT: Field = FEED_SHARDS.then(8);
+    pub const TOTAL_STOPPED_SECS: Field = STOP_STARTED_AT.then(8);
+    pub const ASSET_AUTHORITIES: Field = TOTAL_STOPPED_SECS.then(32 * ASSET_COUNT);
+    pub const STATE_HASH: Field = ASSET_AUTHORITIES.then(32);
+    pub const ON_DEMAND_QUEUE: Field = STATE_HASH.then(32);
+    pub const PENDING_FALLBACKS: Field = ON_DEMAND_QUEUE.then(PendingFallback::LEN * ASSET_COUNT);
+    pub const PENDING_FEED_SHARDS: Field = PENDING_FALLBACKS.then(PendingFeedShard::LEN * MAX_FEED_SHARDS);
+    /// Size of the account, discriminator included
+    pub const LEN: usize = PENDING_FEED_SHARDS.end();
+}
+
+/// One asset's `PriceData`; offsets are from the start of the slot
+pub mod price_data {
+    use super::*;
+
+    pub const PRICE: Field = Field::at(0, 8);
+    pub const LAST_PRICE: Field = PRICE.then(8);
+    pub const LAST_UPDATE_TIME: Field = LAST_PRICE.then(8);
+    pub const APY: Field = LAST_UPDATE_TIME.then(8);
+    pub const LAST_UPDATE_SLOT: Field = APY.then(8);
+    pub const LAST_PRICE_TIME: Field = LAST_UPDATE_SLOT.then(8);
+    /// `0` for USD, `1` for SOL
+    pub const QUOTE: Field = LAST_PRICE_TIME.then(1);
+    pub const PROVENANCE_SOURCE_FEED: Field = QUOTE.then(32);
+    pub const PROVENANCE_SOURCE_ROUND_SLOT: Field = PROVENANCE_SOURCE_FEED.then(8);
+    pub const PROVENANCE_SOURCE_KIND: Field = PROVENANCE_SOURCE_ROUND_SLOT.then(1);
+    pub const DELISTED: Field = PROVENANCE_SOURCE_KIND.then(1);
+    pub const CONFIDENCE_VALUE: Field = DELISTED.then(8);
+    pub const CONFIDENCE_STATUS: Field = CONFIDENCE_VALUE.then(1);
+    pub const HIGH_WATERMARK: Field = CONFIDENCE_STATUS.then(8);
+    pub const HIGH_WATERMARK_TIME: Field = HIGH_WATERMARK.then(8);
+    pub const LOW_WATERMARK: Field = HIGH_WATERMARK_TIME.then(8);
+    pub const LOW_WATERMARK_TIME: Field = LOW_WATERMARK.then(8);
+    pub const LAST_CHECKED_TIME: Field = LOW_WATERMARK_TIME.then(8);
+    pub const LAST_CHECKED_SLOT: Field = LAST_CHECKED_TIME.then(8);
+    pub const FEED_TIMESTAMP: Field = LAST_CHECKED_SLOT.then(8);
+    /// Size of one slot
+    pub const LEN: usize = FEED_TIMESTAMP.end();
+}
+
+/// `PriceOracleData`, at `[b"price_oracle_data"]`
+pub mod data {
+    use super::*;
+
+    /// Every asset's slot, in `AssetType` index order
+    pub const PRICE_DATA: Field = DISCRIMINATOR.then(price_data::LEN * ASSET_COUNT);
+    pub const BUMP: Field = PRICE_DATA.then(1);
+    pub const NONCE: Field = BUMP.then(8);
+    /// Size of the account, discriminator included
+    pub const LEN: usize = NONCE.end();
+
+    /// The slot of the asset within the account
+    pub fn price_slot(asset_type: AssetType) -> Field {
+        Field::at(PRICE_DATA.offset + price_data::LEN * asset_type.index(), price_data::LEN)
+    }
+}
+
+/// The `N` bytes at `field`, `None` when `data` is too short or `field` is not `N` bytes long
+pub fn read_array<const N: usize>(data: &[u8], field: Field) -> Option<[u8; N]> {
+    if field.len != N {
+        return None;
+    }
+    field.bytes(data)?.try_into().ok()
+}
+
+/// The byte at `field`. `None` for any of these readers when `data` is too short or
+/// `field` has another size than the type read.
+pub fn read_u8(data: &[u8], field: Field) -> Option<u8> {
+    read_array::<1>(data, field).map(|[byte]| byte)
+}
+
+/// The boolean at `field`, `None` as well for a byte other than `0` or `1`
+pub fn read_bool(data: &[u8], field: Field) -> Option<bool> {
+    match read_u8(data, field)? {
+        0 => Some(false),
+        1 => Some(true),
+        _ => None,
+    }
+}
+
+pub fn read_u16(data: &[u8], field: Field) -> Option<u16> {
+    read_array(data, field).map(u16::from_le_bytes)
+}
+
+pub fn read_u32(data: &[u8], field: Field) -> Option<u32> {
+    read_array(data, field).map(u32::from_le_bytes)
+}
+
+pub fn read_u64(data: &[u8], field: Field) -> Option<u64> {
+    read_array(data, field).map(u64::from_le_bytes)
+}
+
+pub fn read_i64(data: &[u8], field: Field) -> Option<i64> {
+    read_array(data, field).map(i64::from_le_bytes)
+}
+
+pub fn read_pubkey(data: &[u8], field: Field) -> Option<Pubkey> {
+    read_array(data, field).map(Pubkey::new_from_array)
+}
+
+/// Whether `data` starts with the discriminator of the account type `T`
+pub fn has_discriminator<T: Discriminator>(data: &[u8]) -> bool {
+    DISCRIMINATOR.bytes(data) == Some(&T::DISCRIMINATOR[..])
+}
+
+/// One asset's `PriceData`, read in place
+#[derive(Clone, Copy, Debug)]
+pub struct PriceSlot<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> PriceSlot<'a> {
+    /// The asset's slot in the raw data account, `None` unless `data` has the data
+    /// account's discriminator and holds the slot
+    pub fn from_data_account(data: &'a [u8], asset_type: AssetType) -> Option<Self> {
+        if !has_discriminator::<PriceOracleData>(data) {
+            return None;
+        }
+        data::price_slot(asset_type).bytes(data).map(|bytes| PriceSlot { bytes })
+    }
+
+    /// The slot's bytes, for the `price_data` fields without an accessor here
+    pub fn bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    // The slot was checked to hold every `price_data` field when it was built, so these
+    // never fall back to zero
+    fn u64_at(&self, field: Field) -> u64 {
+        read_u64(self.bytes, field).unwrap_or_default()
+    }
+
+    fn i64_at(&self, field: Field) -> i64 {
+        read_i64(self.bytes, field).unwrap_or_default()
+    }
+
+    pub fn price(&self) -> u64 {
+        self.u64_at(price_data::PRICE)
+    }
+
+    pub fn apy(&self) -> u64 {
+        self.u64_at(price_data::APY)
+    }
+
+    /// `None` for a byte that is no `QuoteCurrency`
+    pub fn quote(&self) -> Option<QuoteCurrency> {
+        match read_u8(self.bytes, price_data::QUOTE)? {
+            0 => Some(QuoteCurrency::USD),
+            1 => Some(QuoteCurrency::SOL),
+            _ => None,
+        }
+    }
+
+    pub fn last_update_time(&self) -> i64 {
+        self.i64_at(price_data::LAST_UPDATE_TIME)
+    }
+
+    pub fn last_update_slot(&self) -> u64 {
+        self.u64_at(price_data::LAST_UPDATE_SLOT)
+    }
+
+    /// Time a feed round last confirmed the price, on the oracle's clock
+    pub fn last_checked_time(&self) -> i64 {
+        self.i64_at(price_data::LAST_CHECKED_TIME)
+    }
+
+    pub fn last_checked_slot(&self) -> u64 {
+        self.u64_at(price_data::LAST_CHECKED_SLOT)
+    }
+
+    /// Time the source produced the round that last confirmed the price, which staleness
+    /// is measured from
+    pub fn feed_timestamp(&self) -> i64 {
+        self.i64_at(price_data::FEED_TIMESTAMP)
+    }
+
+    pub fn delisted(&self) -> bool {
+        self.bytes[price_data::DELISTED.offset] != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::price_oracle::{
+        Confidence, ConfidenceStatus, LargeMovePolicy, OracleConfig, PriceData, PriceOracleHeader, PriceProvenance, SourceKind, SourcePolicy, SourceSlot,
+    };
+    use crate::yield_convention::YieldConvention;
+
+    /// Asserts the bytes at each field are the Borsh encoding of the matching value
+    macro_rules! assert_fields {
+        ($data:expr, $($field:expr => $value:expr),+ $(,)?) => {
+            $(
+                assert_eq!($field.bytes(&$data), Some(&$value.try_to_vec().unwrap()[..]), "{}", stringify!($field));
+            )+
+        };
+    }
+
+    /// A value whose 8 bytes all equal `seed`, so that fields read at a shifted offset differ
+    fn pattern(seed: u8) -> u64 {
+        u64::from_le_bytes([seed; 8])
+    }
+
+    fn key(seed: u8) -> Pubkey {
+        Pubkey::new_from_array([seed; 32])
+    }
+
+    fn sample_price_data(seed: u8) -> PriceData {
+        PriceData {
+            price: pattern(seed),
+            last_price: pattern(seed + 1),
+            last_update_time: pattern(seed + 2) as i64,
+            apy: pattern(seed + 3),
+            last_update_slot: pattern(seed + 4),
+            last_price_time: pattern(seed + 5) as i64,
+            quote: QuoteCurrency::SOL,
+            provenance: PriceProvenance { source_feed: key(seed + 6), source_round_slot: pattern(seed + 7), source_kind: seed + 8 },
+            delisted: true,
+            confidence: Confidence { value: pattern(seed + 9), status: ConfidenceStatus::ZeroDeviation },
+            high_watermark: pattern(seed + 10),
+            high_watermark_time: pattern(seed + 11) as i64,
+            low_watermark: pattern(seed + 12),
+            low_watermark_time: pattern(seed + 13) as i64,
+            last_checked_time: pattern(seed + 14) as i64,
+            last_checked_slot: pattern(seed + 15),
+            feed_timestamp: pattern(seed + 16) as i64,
+        }
+    }
+
+    fn serialize<T: AccountSerialize>(account: &T) -> Vec<u8> {
+        let mut data = Vec::new();
+        account.try_serialize(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_header_offsets_match_serialized_account() {
+        let mut header = PriceOracleHeader {
+            last_global_update: pattern(1) as i64,
+            emergency_stop: true,
+            authority: key(2),
+            switchboard_program_id: key(3),
+            bump: 4,
+            max_feed_age_slots: pattern(5),
+            last_stop_change_time: pattern(6) as i64,
+            stop_change_count: pattern(7),
+            min_stop_change_interval: pattern(8) as i64,
+            operator: key(9),
+            locked: true,
+            min_lease_balance: pattern(10),
+            reject_empty_lease: true,
+            partial_updates: true,
+            price_change_limit_bps: 11,
+            config_timelock_secs: 12,
+            stop_clear_effective_at: pattern(13) as i64,
+            emergency_council: key(14),
+            resume_threshold: 15,
+            manual_resume_needs_approval: true,
+            stop_incident: pattern(16),
+            stop_automatic: true,
+            move_confirm_delay_secs: 17,
+            move_confirm_tolerance_bps: 18,
+            trusted_publisher: key(19),
+            attestation_sequence: pattern(20),
+            audit_log_enabled: true,
+            updates_paused: true,
+            thread_authority: key(26),
+            feed_mapping: FeedMapping { configured: true, assets: [AssetType::BonkSOL, AssetType::INF, AssetType::BSOL, AssetType::MSOL, AssetType::HSOL, AssetType::JitoSOL] },
+            trust_weights: TrustWeights { freshness_bps: 28, confidence_bps: 29, source_bps: 30, stability_bps: 31 },
+            instance: key(32),
+            data_generation: 33,
+            resumed_at: pattern(34) as i64,
+            resume_grace_secs: 35,
+            resume_grace_multiplier_bps: 36,
+            last_changed_mask: 0b10_0100_0001,
+            last_changed_slot: pattern(37),
+            watchers: [key(70), Pubkey::default(), key(71), key(72)],
+            stop_watcher: key(73),
+            stop_started_at: pattern(38) as i64,
+            total_stopped_secs: pattern(39),
+            asset_authorities: std::array::from_fn(|i| if i % 3 == 0 { key(74 + i as u8) } else { Pubkey::default() }),
+            state_hash: key(90).to_bytes(),
+            on_demand_queue: key(91),
+            ..PriceOracleHeader::default()
+        };
+        header.pending_config.config = OracleConfig { price_change_limit_bps: 21, timelock_secs: 22, resume_threshold: 23, ..OracleConfig::default() };
+        header.pending_config.effective_at = pattern(24) as i64;
+        header.pending_feed_mapping.effective_at = pattern(27) as i64;
+        header.update_stats.successful_updates = pattern(25);
+        for i in 0..ASSET_COUNT {
+            let seed = 30 + i as u8;
+            header.sources[i] = if i % 2 == 0 { SourceKind::Chainlink } else { SourceKind::Switchboard };
+            header.rejection_codes[i] = seed as u32;
+            header.max_age_secs[i] = 100 + seed as u32;
+            header.paused[i] = i % 3 == 0;
+            header.large_move_policy[i] = if i % 2 == 1 { LargeMovePolicy::Confirm } else { LargeMovePolicy::Halt };
+            header.pending_prices[i] = PendingPrice { price: pattern(seed), observed_at: pattern(seed + 1) as i64 };
+            header.apy_change_limit_bps[i] = 200 + seed as u32;
+            header.signed_price_times[i] = pattern(seed + 2) as i64;
+            header.haircut_bps[i] = 300 + i as u16;
+            header.clamped_prices[i] = pattern(seed + 3);
+            header.asset_mints[i] = key(80 + i as u8);
+            header.mint_decimals[i] = seed;
+            header.yield_configs[i] = YieldConfig { convention: YieldConvention::Apr, compounding_periods: 300 + i as u16 };
+            header.raw_yields[i] = RawYield { value: pattern(seed + 4), config: header.yield_configs[i] };
+            header.last_errors[i] = LastError { code: 6_000 + seed as u32, time: pattern(seed + 5) as i64 };
+            header.source_policies[i] = if i % 2 == 0 { SourcePolicy::Priority } else { SourcePolicy::Median };
+            header.premiums[i] = LstPremium { premium_bps: -(seed as i64), feed_price: pattern(seed + 6), intrinsic_rate: pattern(seed + 7), time: pattern(seed + 8) as i64 };
+            header.pending_fallbacks[i] = PendingFallback { fallback: SourceSlot { kind: SourceKind::Pyth, feed: key(100 + i as u8) }, effective_at: pattern(seed + 9) as i64 };
+        }
+        for i in 0..MAX_RESUME_APPROVERS {
+            header.resume_approvers[i] = key(60 + i as u8);
+            header.resume_approvals[i] = pattern(70 + i as u8);
+        }
+        for i in 0..MAX_FEED_SHARDS {
+            header.feed_shards[i] = FeedShard { feed: key(80 + i as u8), assets: [AssetType::ALL[7 + i]; 6], mapped: 1 << i };
+            header.pending_feed_shards[i] = PendingFeedShard { shard: FeedShard { feed: key(110 + i as u8), assets: [AssetType::ALL[9 - i]; 6], mapped: 2 << i }, effective_at: pattern(70 + i as u8) as i64 };
+        }
+
+        let data = serialize(&header);
+        assert_eq!(data.len(), header::LEN);
+        assert_eq!(header::LEN, 8 + PriceOracleHeader::LEN);
+        assert!(has_discriminator::<PriceOracleHeader>(&data));
+        assert_fields!(data,
+            DISCRIMINATOR => PriceOracleHeader::DISCRIMINATOR,
+            header::LAST_GLOBAL_UPDATE => header.last_global_update,
+            header::EMERGENCY_STOP => header.emergency_stop,
+            header::AUTHORITY => header.authority,
+            header::SWITCHBOARD_PROGRAM_ID => header.switchboard_program_id,
+            header::BUMP => header.bump,
+            header::MAX_FEED_AGE_SLOTS => header.max_feed_age_slots,
+            header::LAST_STOP_CHANGE_TIME => header.last_stop_change_time,
+            header::STOP_CHANGE_COUNT => header.stop_change_count,
+            header::MIN_STOP_CHANGE_INTERVAL => header.min_stop_change_interval,
+            header::OPERATOR => header.operator,
+            header::LOCKED => header.locked,
+            header::MIN_LEASE_BALANCE => header.min_lease_balance,
+            header::REJECT_EMPTY_LEASE => header.reject_empty_lease,
+            header::SOURCES => header.sources,
+            header::PARTIAL_UPDATES => header.partial_updates,
+            header::REJECTION_CODES => header.rejection_codes,
+            header::MAX_AGE_SECS => header.max_age_secs,
+            header::PRICE_CHANGE_LIMIT_BPS => header.price_change_limit_bps,
+            header::CONFIG_TIMELOCK_SECS => header.config_timelock_secs,
+            header::PENDING_CONFIG => header.pending_config,
+            header::STOP_CLEAR_EFFECTIVE_AT => header.stop_clear_effective_at,
+            header::EMERGENCY_COUNCIL => header.emergency_council,
+            header::PAUSED => header.paused,
+            header::RESUME_APPROVERS => header.resume_approvers,
+            header::RESUME_THRESHOLD => header.resume_threshold,
+            header::MANUAL_RESUME_NEEDS_APPROVAL => header.manual_resume_needs_approval,
+            header::STOP_INCIDENT => header.stop_incident,
+            header::STOP_AUTOMATIC => header.stop_automatic,
+            header::RESUME_APPROVALS => header.resume_approvals,
+            header::LARGE_MOVE_POLICY => header.large_move_policy,
+            header::MOVE_CONFIRM_DELAY_SECS => header.move_confirm_delay_secs,
+            header::MOVE_CONFIRM_TOLERANCE_BPS => header.move_confirm_tolerance_bps,
+            header::PENDING_PRICES => header.pending_prices,
+            header::UPDATE_STATS => header.update_stats,
+            header::APY_CHANGE_LIMIT_BPS => header.apy_change_limit_bps,
+            header::TRUSTED_PUBLISHER => header.trusted_publisher,
+            header::SIGNED_PRICE_TIMES => header.signed_price_times,
+            header::ATTESTATION_SEQUENCE => header.attestation_sequence,
+            header::HAIRCUT_BPS => header.haircut_bps,
+            header::AUDIT_LOG_ENABLED => header.audit_log_enabled,
+            header::UPDATES_PAUSED => header.updates_paused,
+            header::THREAD_AUTHORITY => header.thread_authority,
+            header::FEED_MAPPING => header.feed_mapping,
+            header::PENDING_FEED_MAPPING => header.pending_feed_mapping,
+            header::TRUST_WEIGHTS => header.trust_weights,
+            header::INSTANCE => header.instance,
+            header::CLAMPED_PRICES => header.clamped_prices,
+            header::ASSET_MINTS => header.asset_mints,
+            header::MINT_DECIMALS => header.mint_decimals,
+            header::YIELD_CONFIGS => header.yield_configs,
+            header::RAW_YIELDS => header.raw_yields,
+            header::DATA_GENERATION => header.data_generation,
+            header::LAST_ERRORS => header.last_errors,
+            header::SOURCE_POLICIES => header.source_policies,
+            header::RESUMED_AT => header.resumed_at,
+            header::RESUME_GRACE_SECS => header.resume_grace_secs,
+            header::RESUME_GRACE_MULTIPLIER_BPS => header.resume_grace_multiplier_bps,
+            header::LAST_CHANGED_MASK => header.last_changed_mask,
+            header::LAST_CHANGED_SLOT => header.last_changed_slot,
+            header::WATCHERS => header.watchers,
+            header::STOP_WATCHER => header.stop_watcher,
+            header::PREMIUMS => header.premiums,
+            header::FEED_SHARDS => header.feed_shards,
+            header::STOP_STARTED_AT => header.stop_started_at,
+            header::TOTAL_STOPPED_SECS => header.total_stopped_secs,
+            header::ASSET_AUTHORITIES => header.asset_authorities,
+            header::STATE_HASH => header.state_hash,
+            header::ON_DEMAND_QUEUE => header.on_demand_queue,
+            header::PENDING_FALLBACKS => header.pending_fallbacks,
+            header::PENDING_FEED_SHARDS => header.pending_feed_shards,
+        );
+
+        // The typed readers agree
+        assert_eq!(read_bool(&data, header::EMERGENCY_STOP), Some(true));
+        assert_eq!(read_pubkey(&data, header::OPERATOR), Some(header.operator));
+        assert_eq!(read_u16(&data, Field::at(header::HAIRCUT_BPS.offset, 2)), Some(header.haircut_bps[0]));
+        assert_eq!(read_u32(&data, header::PRICE_CHANGE_LIMIT_BPS), Some(11));
+        assert_eq!(read_i64(&data, header::LAST_GLOBAL_UPDATE), Some(header.last_global_update));
+        assert_eq!(read_u8(&data, header::BUMP), Some(4));
+    }
+
+    #[test]
+    fn test_price_data_offsets_match_serialized_accounts() {
+        let mut data_account = PriceOracleData { bump: 7, nonce: pattern(8), ..PriceOracleData::default() };
+        for asset_type in AssetType::ALL {
+            data_account.price_data[asset_type.index()] = sample_price_data(17 * asset_type.index() as u8 + 1);
+        }
+        let data = serialize(&data_account);
+        assert_eq!(data.len(), data::LEN);
+        assert_eq!(data::LEN, 8 + PriceOracleData::LEN);
+        assert_eq!(price_data::LEN, PriceData::LEN);
+        assert_fields!(data,
+            DISCRIMINATOR => PriceOracleData::DISCRIMINATOR,
+            data::PRICE_DATA => data_account.price_data,
+            data::BUMP => data_account.bump,
+            data::NONCE => data_account.nonce,
+        );
+
+        for asset_type in AssetType::ALL {
+            let expected = data_account.price_data[asset_type.index()];
+            let slot = data::price_slot(asset_type).bytes(&data).unwrap();
+            assert_fields!(slot,
+                price_data::PRICE => expected.price,
+                price_data::LAST_PRICE => expected.last_price,
+                price_data::LAST_UPDATE_TIME => expected.last_update_time,
+                price_data::APY => expected.apy,
+                price_data::LAST_UPDATE_SLOT => expected.last_update_slot,
+                price_data::LAST_PRICE_TIME => expected.last_price_time,
+                price_data::QUOTE => expected.quote,
+                price_data::PROVENANCE_SOURCE_FEED => expected.provenance.source_feed,
+                price_data::PROVENANCE_SOURCE_ROUND_SLOT => expected.provenance.source_round_slot,
+                price_data::PROVENANCE_SOURCE_KIND => expected.provenance.source_kind,
+                price_data::DELISTED => expected.delisted,
+                price_data::CONFIDENCE_VALUE => expected.confidence.value,
+                price_data::CONFIDENCE_STATUS => expected.confidence.status,
+                price_data::HIGH_WATERMARK => expected.high_watermark,
+                price_data::HIGH_WATERMARK_TIME => expected.high_watermark_time,
+                price_data::LOW_WATERMARK => expected.low_watermark,
+                price_data::LOW_WATERMARK_TIME => expected.low_watermark_time,
+                price_data::LAST_CHECKED_TIME => expected.last_checked_time,
+                price_data::LAST_CHECKED_SLOT => expected.last_checked_slot,
+                price_data::FEED_TIMESTAMP => expected.feed_timestamp,
+            );
+
+            let slot = PriceSlot::from_data_account(&data, asset_type).unwrap();
+            assert_eq!((slot.price(), slot.apy(), slot.quote()), (expected.price, expected.apy, Some(expected.quote)));
+            assert_eq!((slot.last_update_time(), slot.last_update_slot()), (expected.last_update_time, expected.last_update_slot));
+            assert_eq!((slot.last_checked_time(), slot.last_checked_slot()), (expected.last_checked_time, expected.last_checked_slot));
+            assert_eq!(slot.feed_timestamp(), expected.feed_timestamp);
+            assert!(slot.delisted());
+        }
+    }
+
+    #[test]
+    fn test_readers_reject_short_or_foreign_data() {
+        let data = serialize(&PriceOracleData::default());
+        assert!(PriceSlot::from_data_account(&data[..data.len() - PriceData::LEN], AssetType::ALL[ASSET_COUNT - 1]).is_none());
+        let header = serialize(&PriceOracleHeader::default());
+        assert!(PriceSlot::from_data_account(&header, AssetType::SOL).is_none());
+
+        // Wrong size for the type, out of bounds, or not a boolean
+        assert_eq!(read_u64(&data, Field::at(8, 4)), None);
+        assert_eq!(read_u64(&data, Field::at(data.len() - 4, 8)), None);
+        assert_eq!(read_bool(&[2], Field::at(0, 1)), None);
+    }
+}
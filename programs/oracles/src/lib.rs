@@ -1,198 +1,4208 @@
+#![cfg_attr(not(test), deny(clippy::unwrap_used, clippy::expect_used, clippy::panic))]
+
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::clock::Clock;
+use anchor_lang::solana_program::sysvar;
+use anchor_lang::InstructionData;
+use std::collections::BTreeMap;
+#[cfg(feature = "verbose-logs")]
 use anchor_lang::solana_program::log::sol_log_compute_units;
-use switchboard_v2::AggregatorAccountData;
 
+/// `msg!` that is only compiled in with the `verbose-logs` feature.
+/// Error paths keep using `msg!` so failures are always explained.
+macro_rules! verbose_msg {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "verbose-logs")]
+        msg!($($arg)*);
+        #[cfg(not(feature = "verbose-logs"))]
+        let _ = format_args!($($arg)*);
+    };
+}
+
+pub mod asset_info;
+pub mod attestation;
+pub mod audit_log;
+pub mod basket;
+pub mod chainlink_utils;
+pub mod checkpoint;
+#[cfg(not(target_os = "solana"))]
+pub mod client;
+pub mod epoch_snapshots;
+pub mod events;
+pub mod history;
+pub mod invariants;
+pub mod layout;
+pub mod marinade_utils;
+pub mod migration;
+pub mod mint_utils;
+/// Localnet-only instructions; production builds must never enable `mock-oracle`
+#[cfg(feature = "mock-oracle")]
+pub mod mock_oracle;
+pub mod portfolio;
 pub mod price_oracle;
+pub mod pyth_utils;
+pub mod quote_buffer;
+pub mod sandwich_guard;
+pub mod signed_price;
+pub mod stake_pool_utils;
+pub mod subscriber;
 pub mod switchboard_utils;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+pub mod thresholds;
+pub mod trust_score;
+pub mod updater_stats;
+pub mod yield_convention;
 
-use price_oracle::{AssetType, PriceOracle, PriceOracleHeader, PriceOracleData, OracleError};
-use switchboard_utils::{DEVNET_AGGREGATOR_PUBKEY, SOL_PRICE_AGGREGATOR_PUBKEY};
+use price_oracle::{change_magnitude_bps, fixed_to_bps, AssetOutcome, AssetPrice, AssetRegistry, AssetType, ASSET_COUNT, FeedPosition, FeedShardHealth, LargeMovePolicy, LastError, LenientPrice, LstPremium, MaxAge, OracleConfig, OracleError, OracleStats, PendingPrice, PoolKind, PriceChange, PriceChangeSimulation, PriceData, PriceOracle, PriceWithConfidence, PriceOracleHeader, PriceOracleData, PriceProvenance, QuoteCurrency, QuotedPrice, Role, SourceKind, SourcePolicy, SourceSlot, UpdatePreview, UpdatedAssets, Watermarks};
+use asset_info::AssetInfo;
+use audit_log::{AuditAction, AuditEntry, AuditLog, AuditLogPage, MAX_AUDIT_ENTRIES_RETURNED};
+use basket::{Basket, BasketWeight};
+use checkpoint::Checkpoint;
+use epoch_snapshots::EpochSnapshots;
+use events::{ApyChangeRejected, AssetAuthorityChanged, AssetFallbackApplied, AssetFallbackCancelled, AssetFallbackProposed, AssetDelisted, AssetMintSet, AssetPauseChanged, AssetsUpdated, BasketClosed, BasketWeightsSet, CheckpointClosed, CheckpointCreated, ConfigApplied, ConfigCancelled, ConfigProposed, EmergencyCouncilChanged, EmergencyStopChanged, EmergencyStopClearScheduled, EpochSnapshotsPruned, FeedMappingApplied, FeedMappingCancelled, FeedMappingProposed, FeedShardApplied, FeedShardCancelled, FeedShardProposed, Heartbeat, HistoryPruned, InvariantsVerified, LargeMovePolicyChanged, LeaseLow, OnDemandQueueChanged, PriceClamped, PriceDataChanged, PriceDataReinitialized, PricesAttested, PricesUnchanged, PriceForced, PriceMoveConfirmed, PriceMoveDiscarded, PriceMoveProposed, PriceUpdated, PythFeedIdChanged, ResumeApproved, ResumeGraceLimitApplied, QuoteCurrencyChanged, RefreshSkipped, RoundNotAdvanced, SourceFailover, SourcePolicySet, SourcesAggregated, SubscriberNotified, SubscriberSkipped, SubscriberSet, SwitchboardProgramIdChanged, ThreadAuthorityChanged, ThresholdCrossed, TrustedPublisherChanged, UpdatesPausedChanged, WatchersChanged, WatcherStopTriggered, WatermarksReset, YieldConventionSet};
+use history::{ApyHistory, PriceHistory, PriceObservation};
+use quote_buffer::QuoteBuffer;
+use sandwich_guard::reject_earlier_price_update;
+use signed_price::{signed_price_message, verify_publisher_signature};
+use subscriber::{PriceCallback, Subscriber};
+use thresholds::{PriceThresholds, ThresholdDirection};
+use trust_score::{TrustScore, TrustWeights};
+use portfolio::PortfolioValue;
+use updater_stats::UpdaterStats;
+use yield_convention::{RawYield, YieldConfig};
+use switchboard_utils::{fixed_to_f64, AggregatorFeed, DEVNET_AGGREGATOR_PUBKEY, SOL_PRICE_AGGREGATOR_PUBKEY};
 
 declare_id!("GqYaWFTAy3dTNZ8zRb9EyWLqTQ4gRHUUwCCuD5GmRihY");
 
+/// Logs the remaining compute units, only with the `verbose-logs` feature
+#[inline(always)]
+fn log_compute_units() {
+    #[cfg(feature = "verbose-logs")]
+    sol_log_compute_units();
+}
+
+/// Applies the lease policy when the feed's lease and escrow are supplied, emitting `LeaseLow`
+/// when the balance is below the threshold. Supplying only one of the two is rejected.
+fn check_feed_lease(
+    header: &PriceOracleHeader,
+    feed: &AggregatorFeed,
+    lease: Option<&UncheckedAccount>,
+    escrow: Option<&UncheckedAccount>,
+    clock: &Clock,
+) -> Result<()> {
+    match (lease, escrow) {
+        (Some(lease), Some(escrow)) => {
+            if let Some(balance) = PriceOracle::check_lease(header, feed, lease, escrow)? {
+                msg!("Lease {} of aggregator {} is low: {}", lease.key(), feed.key(), balance);
+                emit!(LeaseLow {
+                    aggregator: feed.key(),
+                    lease: lease.key(),
+                    balance,
+                    threshold: header.min_lease_balance,
+                    timestamp: clock.unix_timestamp,
+                });
+            }
+            Ok(())
+        }
+        (None, None) => Ok(()),
+        _ => {
+            msg!("Lease and lease escrow must be supplied together");
+            Err(error!(OracleError::InvalidLeaseAccount))
+        }
+    }
+}
+
+/// Emits `PriceUpdated` with the stored price and provenance of each of `assets`
+fn emit_price_updates(data: &PriceOracleData, assets: &[AssetType], clock: &Clock) {
+    for asset_type in assets {
+        let price_data = &data.price_data[asset_type.index()];
+        emit!(PriceUpdated {
+            asset_type: *asset_type,
+            price: price_data.price,
+            apy_bps: fixed_to_bps(price_data.apy).unwrap_or(u32::MAX),
+            provenance: price_data.provenance,
+            confidence: price_data.confidence,
+            feed_timestamp: price_data.feed_timestamp,
+            timestamp: clock.unix_timestamp,
+        });
+    }
+}
+
+/// Emits `PriceClamped` for the assets among `assets`, just written, whose stored price was
+/// clamped short of their feed
+fn emit_clamped_prices(header: &PriceOracleHeader, data: &PriceOracleData, assets: &[AssetType], clock: &Clock) {
+    for asset_type in assets.iter().filter(|asset_type| header.is_clamped(**asset_type)) {
+        let price_data = &data.price_data[asset_type.index()];
+        emit!(PriceClamped {
+            asset_type: *asset_type,
+            feed_price: header.clamped_prices[asset_type.index()],
+            price: price_data.price,
+            previous_price: price_data.last_price,
+            quote: price_data.quote,
+            limit_bps: header.effective_price_change_limit_bps(clock.unix_timestamp),
+            timestamp: clock.unix_timestamp,
+        });
+    }
+}
+
+/// Emits `ResumeGraceLimitApplied` for the assets among `assets`, just written, whose move
+/// only the widened limit of a resume grace period accepted
+fn emit_resume_grace_uses(header: &PriceOracleHeader, data: &PriceOracleData, assets: &[AssetType], clock: &Clock) {
+    for asset_type in assets {
+        emit_resume_grace_use(header, *asset_type, &data.price_data[asset_type.index()], clock);
+    }
+}
+
+fn emit_resume_grace_use(header: &PriceOracleHeader, asset_type: AssetType, price_data: &PriceData, clock: &Clock) {
+    if !PriceOracle::uses_resume_grace(header, price_data.last_price, price_data.price, clock.unix_timestamp) {
+        return;
+    }
+    let widened_limit_bps = header.effective_price_change_limit_bps(clock.unix_timestamp);
+    msg!("{:?} moved under the {} bps limit widened after the resume", asset_type, widened_limit_bps);
+    emit!(ResumeGraceLimitApplied {
+        asset_type,
+        price: price_data.price,
+        previous_price: price_data.last_price,
+        limit_bps: header.price_change_limit_bps(),
+        widened_limit_bps,
+        grace_ends_at: header.resume_grace_ends_at().unwrap_or_default(),
+        timestamp: clock.unix_timestamp,
+    });
+}
+
+/// When the instruction advanced the data account's nonce from `before`, stores the new
+/// state hash in the header and emits `PriceDataChanged`
+fn record_data_change(header: &mut PriceOracleHeader, data: &PriceOracleData, before: u64, clock: &Clock) {
+    if data.nonce != before {
+        header.state_hash = data.state_hash();
+        emit!(PriceDataChanged {
+            nonce: data.nonce,
+            state_hash: header.state_hash,
+            timestamp: clock.unix_timestamp,
+        });
+    }
+}
+
+/// Emits `Heartbeat` for a keeper run that wrote `updated` assets, found `unchanged` at
+/// their stored price and skipped `skipped`
+fn emit_heartbeat(keeper: &Pubkey, updated: usize, unchanged: usize, skipped: usize, clock: &Clock) {
+    emit!(Heartbeat {
+        keeper: *keeper,
+        slot: clock.slot,
+        timestamp: clock.unix_timestamp,
+        updated: updated as u8,
+        skipped: skipped as u8,
+        unchanged: unchanged as u8,
+    });
+}
+
+/// Emits `PriceMoveProposed`, `PriceMoveConfirmed` and `PriceMoveDiscarded` for the
+/// difference between the pending moves held before an instruction, `before`, and after it
+fn emit_pending_move_changes(header: &PriceOracleHeader, data: &PriceOracleData, before: &[PendingPrice; ASSET_COUNT], clock: &Clock) {
+    for asset_type in AssetType::ALL {
+        let i = asset_type.index();
+        emit_pending_move_change(header, asset_type, before[i], header.pending_prices[i], data.price_data[i].price, clock);
+    }
+}
+
+/// `emit_pending_move_changes` for one asset whose pending move went from `old` to `new`,
+/// `price` being its stored price afterwards
+fn emit_pending_move_change(header: &PriceOracleHeader, asset_type: AssetType, old: PendingPrice, new: PendingPrice, price: u64, clock: &Clock) {
+    if old == new {
+        return;
+    }
+    if old.is_pending() {
+        if !new.is_pending() && PriceOracle::confirms_pending_move(header, &old, price) {
+            emit!(PriceMoveConfirmed {
+                asset_type,
+                candidate_price: old.price,
+                price,
+                timestamp: clock.unix_timestamp,
+            });
+        } else {
+            emit!(PriceMoveDiscarded {
+                asset_type,
+                candidate_price: old.price,
+                timestamp: clock.unix_timestamp,
+            });
+        }
+    }
+    if new.is_pending() {
+        emit!(PriceMoveProposed {
+            asset_type,
+            current_price: price,
+            candidate_price: new.price,
+            confirmable_at: new.observed_at.saturating_add(header.move_confirm_delay_secs() as i64),
+            timestamp: clock.unix_timestamp,
+        });
+    }
+}
+
+/// The header and data account state an update started from, which `finish_data_update`
+/// compares the accounts against once the update ran
+struct DataUpdateBaseline {
+    pending_prices: [PendingPrice; ASSET_COUNT],
+    nonce: u64,
+    values: [(u64, u64); ASSET_COUNT],
+    stop_incident: u64,
+}
+
+impl DataUpdateBaseline {
+    fn capture(header: &PriceOracleHeader, data: &PriceOracleData) -> Self {
+        Self {
+            pending_prices: header.pending_prices,
+            nonce: data.nonce,
+            values: data.stored_values(),
+            stop_incident: header.stop_incident,
+        }
+    }
+}
+
+/// Common tail of every instruction that writes the data account: emits
+/// `EmergencyStopChanged` when the update tripped the breaker, the pending move changes
+/// since `before`, `PriceClamped` and `ResumeGraceLimitApplied` for `limited`, the assets
+/// just written under the change limit, then records the changed assets and the new state
+/// hash
+fn finish_data_update(header: &mut PriceOracleHeader, data: &PriceOracleData, before: &DataUpdateBaseline, limited: &[AssetType], clock: &Clock) {
+    if header.stop_incident != before.stop_incident {
+        emit_breaker_trip(header, clock);
+    }
+    emit_pending_move_changes(header, data, &before.pending_prices, clock);
+    emit_clamped_prices(header, data, limited, clock);
+    emit_resume_grace_uses(header, data, limited, clock);
+    header.record_changed_assets(&before.values, data, clock.slot);
+    record_data_change(header, data, before.nonce, clock);
+}
+
+/// Emits `EmergencyStopChanged` for a stop the circuit breaker just tripped, with the
+/// program as its actor
+fn emit_breaker_trip(header: &PriceOracleHeader, clock: &Clock) {
+    msg!("Emergency stop tripped by the circuit breaker (incident {})", header.stop_incident);
+    emit!(EmergencyStopChanged {
+        actor: crate::ID,
+        old_state: false,
+        new_state: true,
+        forced: false,
+        reason_code: 0,
+        change_count: header.stop_change_count,
+        by_council: false,
+        incident: header.stop_incident,
+        stopped_secs: 0,
+        timestamp: clock.unix_timestamp,
+    });
+}
+
+/// The clock sysvar, failing with `ClockUnavailable` rather than panicking when it cannot be read
+fn current_clock() -> Result<Clock> {
+    Clock::get().map_err(|e| {
+        msg!("Failed to read the clock sysvar: {}", e);
+        error!(OracleError::ClockUnavailable)
+    })
+}
+
+/// Bump Anchor found for the PDA account `name`, failing with `MissingBump` when it has none
+fn pda_bump(bumps: &BTreeMap<String, u8>, name: &str) -> Result<u8> {
+    bumps.get(name).copied().ok_or_else(|| {
+        msg!("No bump found for account {}", name);
+        error!(OracleError::MissingBump)
+    })
+}
+
+/// Records a privileged action in the audit log, see `audit_log`. `instruction` rebuilds the
+/// instruction being run from its arguments, so the entry can hash its data.
+fn audit(
+    header: &PriceOracleHeader,
+    audit_log: &mut Option<Box<Account<'_, AuditLog>>>,
+    actor: &Pubkey,
+    action: AuditAction,
+    subject_asset: Option<AssetType>,
+    instruction: impl InstructionData,
+) -> Result<()> {
+    let entry = AuditEntry::new(current_clock()?.unix_timestamp, *actor, action, subject_asset, &instruction.data());
+    PriceOracle::record_audit(header, audit_log.as_deref_mut().map(|audit_log| &mut **audit_log), entry)
+}
+
+/// Adds a run that wrote `updated` assets and skipped `skipped` to the keeper's stats, when supplied
+fn record_updater_stats(
+    stats: Option<&mut Box<Account<'_, UpdaterStats>>>,
+    bump: Option<&u8>,
+    keeper: &Pubkey,
+    updated: usize,
+    skipped: usize,
+    clock: &Clock,
+) {
+    if let Some(stats) = stats {
+        stats.record(*keeper, bump.copied().unwrap_or_default(), updated, skipped, clock.slot);
+    }
+}
+
+/// Records history, the keeper's stats and emits `PriceUpdated` for the assets a fixed-feed
+/// update wrote, `ApyChangeRejected` for the APYs it held back, then `AssetsUpdated` with
+/// every outcome and the keeper's `Heartbeat`. Samples into `apy_history` the APY of every
+/// multi-asset feed asset written or confirmed. Returns the assets written.
+#[allow(clippy::too_many_arguments)]
+fn finish_fixed_feed_update(
+    data: &PriceOracleData,
+    history: Option<&mut Box<Account<'_, PriceHistory>>>,
+    apy_history: Option<&mut Box<Account<'_, ApyHistory>>>,
+    stats: Option<&mut Box<Account<'_, UpdaterStats>>>,
+    stats_bump: Option<&u8>,
+    keeper: &Pubkey,
+    outcomes: Vec<AssetOutcome>,
+    clock: &Clock,
+) -> Vec<AssetType> {
+    let updated: Vec<AssetType> = outcomes.iter().filter(|outcome| outcome.updated).map(|outcome| outcome.asset_type).collect();
+    let unchanged = outcomes.iter().filter(|outcome| outcome.unchanged).count();
+    let skipped = outcomes.len() - updated.len() - unchanged;
+    if let Some(history) = history {
+        PriceOracle::record_history(history, data, &updated);
+    }
+    if let Some(apy_history) = apy_history {
+        // Only the multi-asset feed carries APYs; SOL comes from its own feed
+        let sampled: Vec<AssetType> = outcomes
+            .iter()
+            .filter(|outcome| (outcome.updated || outcome.unchanged) && outcome.asset_type != AssetType::SOL)
+            .map(|outcome| outcome.asset_type)
+            .collect();
+        PriceOracle::record_apy_history(apy_history, data, &sampled, clock.unix_timestamp);
+    }
+    record_updater_stats(stats, stats_bump, keeper, updated.len(), skipped, clock);
+    emit_price_updates(data, &updated, clock);
+    for outcome in &outcomes {
+        if let Some(rejected_apy) = outcome.rejected_apy {
+            emit!(ApyChangeRejected {
+                asset_type: outcome.asset_type,
+                current_apy: data.price_data[outcome.asset_type.index()].apy,
+                rejected_apy,
+                timestamp: clock.unix_timestamp,
+            });
+        }
+    }
+    emit_heartbeat(keeper, updated.len(), unchanged, skipped, clock);
+    emit!(AssetsUpdated {
+        outcomes,
+        timestamp: clock.unix_timestamp,
+    });
+    updated
+}
+
+/// Calls the subscriber back with the assets an update wrote, when the keeper supplied an
+/// enabled registration and anything was written, and emits `SubscriberNotified`.
+/// `callback_accounts` are the subscriber program followed by its registered accounts.
+/// A best-effort subscriber they cannot reach is skipped with `SubscriberSkipped`.
+fn notify_subscriber<'info>(
+    subscriber: Option<&Account<'info, Subscriber>>,
+    header: &Account<'info, PriceOracleHeader>,
+    callback_accounts: &[AccountInfo<'info>],
+    data: &PriceOracleData,
+    updated: &[AssetType],
+    clock: &Clock,
+) -> Result<()> {
+    let subscriber = match subscriber {
+        Some(subscriber) if subscriber.enabled && !updated.is_empty() => subscriber,
+        _ => return Ok(()),
+    };
+    if !subscriber.required {
+        if let Err(e) = subscriber.check_deliverable(callback_accounts) {
+            msg!("Skipping the callback of best-effort subscriber {}: {:?}", subscriber.program_id, e);
+            emit!(SubscriberSkipped {
+                program_id: subscriber.program_id,
+                assets: updated.to_vec(),
+                error_code: u32::from(e),
+                timestamp: clock.unix_timestamp,
+            });
+            return Ok(());
+        }
+    }
+    let callback = PriceCallback::new(data, updated, clock.unix_timestamp);
+    subscriber.invoke(&header.to_account_info(), header.instance_seed(), header.bump, callback_accounts, &callback)?;
+    emit!(SubscriberNotified {
+        program_id: subscriber.program_id,
+        assets: updated.to_vec(),
+        timestamp: clock.unix_timestamp,
+    });
+    Ok(())
+}
+
+/// Runs `update_prices_and_apys` once the caller's role is checked, restricted to the
+/// `(start_index, count)` positions of the multi-asset feed when `chunk` is given
+fn apply_prices_and_apys_update<'info>(ctx: Context<'_, '_, '_, 'info, UpdatePricesAndApys<'info>>, chunk: Option<(u8, u8)>) -> Result<()> {
+    let clock = current_clock()?;
+
+    // Validate Switchboard program ID
+    let oracle_feed = PriceOracle::load_feed(&ctx.accounts.header, &ctx.accounts.oracle_feed.to_account_info())?;
+    check_feed_lease(&ctx.accounts.header, &oracle_feed, ctx.accounts.lease.as_ref(), ctx.accounts.lease_escrow.as_ref(), &clock)?;
+
+    let before = DataUpdateBaseline::capture(&ctx.accounts.header, &ctx.accounts.data);
+    let outcomes = match chunk {
+        Some((start_index, count)) => PriceOracle::update_prices_and_apys_chunk(
+            &mut ctx.accounts.header,
+            &mut ctx.accounts.data,
+            &oracle_feed,
+            start_index,
+            count,
+            &clock,
+        )?,
+        None => PriceOracle::update_prices_and_apys(
+            &mut ctx.accounts.header,
+            &mut ctx.accounts.data,
+            &oracle_feed,
+            &clock,
+        )?,
+    };
+    let updated = finish_fixed_feed_update(
+        &ctx.accounts.data,
+        ctx.accounts.history.as_mut(),
+        ctx.accounts.apy_history.as_mut(),
+        ctx.accounts.updater_stats.as_mut(),
+        ctx.bumps.get("updater_stats"),
+        ctx.accounts.authority.key,
+        outcomes,
+        &clock,
+    );
+    finish_data_update(&mut ctx.accounts.header, &ctx.accounts.data, &before, &updated, &clock);
+    notify_subscriber(
+        ctx.accounts.subscriber.as_deref(),
+        &ctx.accounts.header,
+        ctx.remaining_accounts,
+        &ctx.accounts.data,
+        &updated,
+        &clock,
+    )?;
+    Ok(())
+}
+
+/// Emits `BasketWeightsSet` with the basket's current components
+fn emit_basket_weights(basket: &Basket, key: Pubkey) -> Result<()> {
+    emit!(BasketWeightsSet {
+        creator: basket.creator,
+        basket: key,
+        id: basket.id,
+        weights: basket.weights(),
+        timestamp: current_clock()?.unix_timestamp,
+    });
+    Ok(())
+}
+
 #[program]
 pub mod oracles {
     use super::*;
 
-    pub fn initialize(ctx: Context<Initialize>, switchboard_program_id: Pubkey) -> Result<()> {
-        msg!("Initializing Price Oracle");
+    /// Creates the header and data account of the oracle `instance`, the signer being its
+    /// admin. `DEFAULT_INSTANCE` is the oracle at the addresses from before instances existed.
+    pub fn initialize(ctx: Context<Initialize>, switchboard_program_id: Pubkey, instance: Pubkey) -> Result<()> {
+        msg!("Initializing Price Oracle instance {}", instance);
         PriceOracle::initialize(
             &mut ctx.accounts.header,
             &mut ctx.accounts.data,
             &ctx.accounts.authority,
             switchboard_program_id,
-            *ctx.bumps.get("header").unwrap(),
-            *ctx.bumps.get("data").unwrap(),
+            instance,
+            pda_bump(&ctx.bumps, "header")?,
+            pda_bump(&ctx.bumps, "data")?,
         )?;
         msg!("Price oracle initialized successfully");
         Ok(())
     }
 
-    pub fn update_prices_and_apys(ctx: Context<UpdatePricesAndApys>) -> Result<()> {
-        sol_log_compute_units();
-        msg!("Updating prices and APYs for all assets");
+    #[access_control(PriceOracle::require_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Updater))]
+    pub fn update_prices_and_apys<'info>(ctx: Context<'_, '_, '_, 'info, UpdatePricesAndApys<'info>>) -> Result<()> {
+        log_compute_units();
+        verbose_msg!("Updating prices and APYs for all assets");
+        apply_prices_and_apys_update(ctx, None)?;
+        verbose_msg!("Prices and APYs updated successfully");
+        log_compute_units();
+        Ok(())
+    }
+
+    /// `update_prices_and_apys` for the `count` multi-asset feed assets from position
+    /// `start_index`, so a keeper can split one round across transactions. See
+    /// `PriceOracle::update_prices_and_apys_chunk` for how chunks stamp the update.
+    #[access_control(PriceOracle::require_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Updater))]
+    pub fn update_prices_and_apys_chunk<'info>(ctx: Context<'_, '_, '_, 'info, UpdatePricesAndApys<'info>>, start_index: u8, count: u8) -> Result<()> {
+        log_compute_units();
+        verbose_msg!("Updating prices and APYs for feed positions {}..{}", start_index, start_index as usize + count as usize);
+        apply_prices_and_apys_update(ctx, Some((start_index, count)))?;
+        log_compute_units();
+        Ok(())
+    }
+
+    /// `update_prices_and_apys` when a multi-asset feed asset is stale, otherwise a no-op
+    /// emitting `RefreshSkipped`. Fresh data never fails it, whoever signs, so consumers can
+    /// prepend it to their own transactions; the update itself still needs the updater role.
+    pub fn refresh_if_stale<'info>(ctx: Context<'_, '_, '_, 'info, UpdatePricesAndApys<'info>>) -> Result<()> {
+        let clock = current_clock()?;
+        let stale = PriceOracle::stale_feed_assets(&ctx.accounts.header, &ctx.accounts.data, &clock);
+        if stale.is_empty() {
+            msg!("Every multi-asset feed asset is fresh, nothing to refresh");
+            emit!(RefreshSkipped {
+                caller: ctx.accounts.authority.key(),
+                timestamp: clock.unix_timestamp,
+            });
+            return Ok(());
+        }
+        PriceOracle::require_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Updater)?;
+        msg!("Refreshing stale {:?}", stale);
+        apply_prices_and_apys_update(ctx, None)
+    }
+
+    /// Updates the assets of the multi-asset feed shards passed as remaining accounts, the
+    /// primary feed and any registered with `propose_feed_shard`, in any combination. Only the
+    /// assets of the supplied shards are written, so a dead shard does not hold the others
+    /// back. The subscriber is not called back.
+    #[access_control(PriceOracle::require_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Updater))]
+    pub fn update_feed_shards<'info>(ctx: Context<'_, '_, '_, 'info, UpdateFeedShards<'info>>) -> Result<()> {
+        log_compute_units();
+        verbose_msg!("Updating from {} feed shard(s)", ctx.remaining_accounts.len());
+        let clock = current_clock()?;
+        let feeds = ctx.remaining_accounts.iter()
+            .map(|feed| PriceOracle::load_feed(&ctx.accounts.header, feed))
+            .collect::<Result<Vec<_>>>()?;
+
+        let before = DataUpdateBaseline::capture(&ctx.accounts.header, &ctx.accounts.data);
+        let outcomes = PriceOracle::update_feed_shards(&mut ctx.accounts.header, &mut ctx.accounts.data, &feeds, &clock)?;
+        let updated = finish_fixed_feed_update(
+            &ctx.accounts.data,
+            ctx.accounts.history.as_mut(),
+            ctx.accounts.apy_history.as_mut(),
+            ctx.accounts.updater_stats.as_mut(),
+            ctx.bumps.get("updater_stats"),
+            ctx.accounts.authority.key,
+            outcomes,
+            &clock,
+        );
+        finish_data_update(&mut ctx.accounts.header, &ctx.accounts.data, &before, &updated, &clock);
+        log_compute_units();
+        Ok(())
+    }
+
+    /// Updates the SOL price from the SOL feed. A keeper that priced the update off-chain
+    /// passes that price as `expected_price`, and the update fails with
+    /// `ExpectedPriceMismatch` when the feed reads further than `tolerance_bps` from it.
+    #[access_control(PriceOracle::require_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Updater))]
+    pub fn update_sol_price<'info>(ctx: Context<'_, '_, '_, 'info, UpdateSolPrice<'info>>, expected_price: Option<u64>, tolerance_bps: u16) -> Result<()> {
+        log_compute_units();
+        verbose_msg!("Updating SOL price");
 
-        let clock = Clock::get().unwrap();
+        let clock = current_clock()?;
 
         // Validate Switchboard program ID
-        if ctx.accounts.oracle_feed.to_account_info().owner != &ctx.accounts.header.switchboard_program_id {
-            msg!("Invalid Switchboard account owner: expected {}, found {}", 
-                ctx.accounts.header.switchboard_program_id, 
-                ctx.accounts.oracle_feed.to_account_info().owner);
-            return Err(error!(OracleError::InvalidSwitchboardAccount));
+        let oracle_feed = PriceOracle::load_feed(&ctx.accounts.header, &ctx.accounts.oracle_feed.to_account_info())?;
+        check_feed_lease(&ctx.accounts.header, &oracle_feed, ctx.accounts.lease.as_ref(), ctx.accounts.lease_escrow.as_ref(), &clock)?;
+
+        let before = DataUpdateBaseline::capture(&ctx.accounts.header, &ctx.accounts.data);
+        let outcomes = PriceOracle::update_sol_price(
+            &mut ctx.accounts.header,
+            &mut ctx.accounts.data,
+            &oracle_feed,
+            ctx.accounts.history_buffer.as_ref().map(|history_buffer| history_buffer.as_ref()),
+            expected_price,
+            tolerance_bps,
+            &clock,
+        )?;
+        let updated = finish_fixed_feed_update(
+            &ctx.accounts.data,
+            ctx.accounts.history.as_mut(),
+            None,
+            ctx.accounts.updater_stats.as_mut(),
+            ctx.bumps.get("updater_stats"),
+            ctx.accounts.authority.key,
+            outcomes,
+            &clock,
+        );
+        finish_data_update(&mut ctx.accounts.header, &ctx.accounts.data, &before, &updated, &clock);
+        notify_subscriber(
+            ctx.accounts.subscriber.as_deref(),
+            &ctx.accounts.header,
+            ctx.remaining_accounts,
+            &ctx.accounts.data,
+            &updated,
+            &clock,
+        )?;
+
+        verbose_msg!("SOL price updated successfully");
+        log_compute_units();
+        Ok(())
+    }
+
+    /// Updates the SOL price from a Chainlink feed registered among SOL's sources with
+    /// `set_asset_sources`, as a secondary source to the Switchboard SOL feed. Takes the
+    /// same `expected_price` guard as `update_sol_price`.
+    #[access_control(PriceOracle::require_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Updater))]
+    pub fn update_sol_price_chainlink<'info>(ctx: Context<'_, '_, '_, 'info, UpdateSolPriceChainlink<'info>>, expected_price: Option<u64>, tolerance_bps: u16) -> Result<()> {
+        log_compute_units();
+        verbose_msg!("Updating SOL price from Chainlink");
+
+        let clock = current_clock()?;
+        let before = DataUpdateBaseline::capture(&ctx.accounts.header, &ctx.accounts.data);
+        let outcomes = PriceOracle::update_sol_price_chainlink(
+            &mut ctx.accounts.header,
+            &mut ctx.accounts.data,
+            &ctx.accounts.registry,
+            &ctx.accounts.chainlink_feed.to_account_info(),
+            expected_price,
+            tolerance_bps,
+            &clock,
+        )?;
+        let updated = finish_fixed_feed_update(
+            &ctx.accounts.data,
+            ctx.accounts.history.as_mut(),
+            None,
+            ctx.accounts.updater_stats.as_mut(),
+            ctx.bumps.get("updater_stats"),
+            ctx.accounts.authority.key,
+            outcomes,
+            &clock,
+        );
+        finish_data_update(&mut ctx.accounts.header, &ctx.accounts.data, &before, &updated, &clock);
+        notify_subscriber(
+            ctx.accounts.subscriber.as_deref(),
+            &ctx.accounts.header,
+            ctx.remaining_accounts,
+            &ctx.accounts.data,
+            &updated,
+            &clock,
+        )?;
+
+        verbose_msg!("SOL price updated from Chainlink successfully");
+        log_compute_units();
+        Ok(())
+    }
+
+    #[access_control(PriceOracle::require_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Updater))]
+    pub fn update_all(ctx: Context<UpdateAll>) -> Result<()> {
+        log_compute_units();
+        verbose_msg!("Updating prices and APYs for all assets and SOL price");
+
+        let clock = current_clock()?;
+
+        // Validate Switchboard program ID for both feeds before touching any state
+        let oracle_feed = PriceOracle::load_feed(&ctx.accounts.header, &ctx.accounts.oracle_feed.to_account_info())?;
+        let sol_oracle_feed = PriceOracle::load_feed(&ctx.accounts.header, &ctx.accounts.sol_oracle_feed.to_account_info())?;
+        check_feed_lease(&ctx.accounts.header, &oracle_feed, ctx.accounts.lease.as_ref(), ctx.accounts.lease_escrow.as_ref(), &clock)?;
+        check_feed_lease(&ctx.accounts.header, &sol_oracle_feed, ctx.accounts.sol_lease.as_ref(), ctx.accounts.sol_lease_escrow.as_ref(), &clock)?;
+
+        let before = DataUpdateBaseline::capture(&ctx.accounts.header, &ctx.accounts.data);
+        let outcomes = PriceOracle::update_all(
+            &mut ctx.accounts.header,
+            &mut ctx.accounts.data,
+            &oracle_feed,
+            &sol_oracle_feed,
+            ctx.accounts.sol_history_buffer.as_ref().map(|history_buffer| history_buffer.as_ref()),
+            &clock,
+        )?;
+        let updated = finish_fixed_feed_update(
+            &ctx.accounts.data,
+            ctx.accounts.history.as_mut(),
+            ctx.accounts.apy_history.as_mut(),
+            ctx.accounts.updater_stats.as_mut(),
+            ctx.bumps.get("updater_stats"),
+            ctx.accounts.authority.key,
+            outcomes,
+            &clock,
+        );
+        finish_data_update(&mut ctx.accounts.header, &ctx.accounts.data, &before, &updated, &clock);
+
+        verbose_msg!("All prices updated successfully");
+        log_compute_units();
+        Ok(())
+    }
+
+    /// Break-glass update from a price signed by the trusted publisher. The instruction
+    /// before this one must be an Ed25519 program instruction verifying `signature`.
+    #[access_control(PriceOracle::require_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Updater))]
+    pub fn update_price_signed(
+        ctx: Context<UpdatePriceSigned>,
+        asset_type: AssetType,
+        price: u64,
+        timestamp: i64,
+        signature: [u8; 64],
+    ) -> Result<()> {
+        log_compute_units();
+        verbose_msg!("Updating {:?} from a signed price", asset_type);
+
+        let clock = current_clock()?;
+
+        let publisher = PriceOracle::trusted_publisher(&ctx.accounts.header)?;
+        let quote = ctx.accounts.data.price_data[asset_type.index()].quote;
+        let message = signed_price_message(asset_type, quote, price, timestamp);
+        verify_publisher_signature(&ctx.accounts.instructions, &publisher, &signature, &message)?;
+
+        let before = DataUpdateBaseline::capture(&ctx.accounts.header, &ctx.accounts.data);
+        let outcomes = PriceOracle::update_price_signed(
+            &mut ctx.accounts.header,
+            &mut ctx.accounts.data,
+            asset_type,
+            price,
+            timestamp,
+            &clock,
+        )?;
+        let updated = finish_fixed_feed_update(
+            &ctx.accounts.data,
+            ctx.accounts.history.as_mut(),
+            None,
+            ctx.accounts.updater_stats.as_mut(),
+            ctx.bumps.get("updater_stats"),
+            ctx.accounts.authority.key,
+            outcomes,
+            &clock,
+        );
+        finish_data_update(&mut ctx.accounts.header, &ctx.accounts.data, &before, &updated, &clock);
+
+        msg!("{:?} price set to {} from the trusted publisher, signed at {}", asset_type, fixed_to_f64(price), timestamp);
+        log_compute_units();
+        Ok(())
+    }
+
+    pub fn preview_update(ctx: Context<PreviewUpdate>) -> Result<UpdatePreview> {
+        let clock = current_clock()?;
+        // Ownership is reported per asset by the preview rather than failing here
+        let oracle_feed = AggregatorFeed::new(&ctx.accounts.oracle_feed);
+        let sol_oracle_feed = AggregatorFeed::new(&ctx.accounts.sol_oracle_feed);
+        let preview = PriceOracle::preview_update(
+            &ctx.accounts.header,
+            &ctx.accounts.data,
+            &oracle_feed,
+            &sol_oracle_feed,
+            ctx.accounts.sol_history_buffer.as_ref().map(|history_buffer| history_buffer.as_ref()),
+            clock.unix_timestamp,
+        );
+        for asset in preview.assets.iter() {
+            verbose_msg!("Preview for {:?}: accepted: {}, reason code: {}, price: {}, APY: {}",
+                asset.asset_type, asset.accepted, asset.reason_code, fixed_to_f64(asset.price), fixed_to_f64(asset.apy));
+        }
+        Ok(preview)
+    }
+
+    /// Anyone may call. What an update would make of `candidate_price` and
+    /// `candidate_apy` for the asset, check by check, without writing anything. Pass the
+    /// asset's registered stake pool to include the pool rate check.
+    pub fn simulate_price_change(
+        ctx: Context<SimulatePriceChange>,
+        asset_type: AssetType,
+        candidate_price: u64,
+        candidate_apy: Option<u64>,
+    ) -> Result<PriceChangeSimulation> {
+        let clock = current_clock()?;
+        let simulation = PriceOracle::simulate_price_change(
+            &ctx.accounts.header,
+            &ctx.accounts.data,
+            &ctx.accounts.registry,
+            asset_type,
+            candidate_price,
+            candidate_apy,
+            ctx.accounts.stake_pool.as_ref().map(|pool| pool.as_ref()),
+            clock.unix_timestamp,
+        );
+        msg!("Simulated {:?} at {}: accepted: {}, reason code: {}, move: {:?}, trips breaker: {}",
+            asset_type, fixed_to_f64(candidate_price), simulation.accepted, simulation.reason_code, simulation.move_verdict, simulation.trips_breaker);
+        Ok(simulation)
+    }
+
+    #[access_control(PriceOracle::require_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Updater))]
+    pub fn update_assets(ctx: Context<UpdateAssets>) -> Result<Vec<AssetType>> {
+        log_compute_units();
+        verbose_msg!("Updating {} asset(s) from registered feeds", ctx.remaining_accounts.len());
+
+        let clock = current_clock()?;
+
+        let before = DataUpdateBaseline::capture(&ctx.accounts.header, &ctx.accounts.data);
+        let UpdatedAssets { updated, unchanged, no_new_round, failed_over } = PriceOracle::update_assets(
+            &mut ctx.accounts.header,
+            &mut ctx.accounts.data,
+            &ctx.accounts.registry,
+            ctx.remaining_accounts,
+            &clock,
+        )?;
+
+        if let Some(history) = ctx.accounts.history.as_mut() {
+            PriceOracle::record_history(history, &ctx.accounts.data, &updated);
+        }
+        emit_price_updates(&ctx.accounts.data, &updated, &clock);
+        let skipped = ctx.remaining_accounts.len() - updated.len() - unchanged.len();
+        record_updater_stats(ctx.accounts.updater_stats.as_mut(), ctx.bumps.get("updater_stats"), ctx.accounts.authority.key, updated.len(), skipped, &clock);
+        emit_heartbeat(ctx.accounts.authority.key, updated.len(), unchanged.len(), skipped, &clock);
+        finish_data_update(&mut ctx.accounts.header, &ctx.accounts.data, &before, &updated, &clock);
+        if !unchanged.is_empty() {
+            emit!(PricesUnchanged { assets: unchanged, timestamp: clock.unix_timestamp });
+        }
+        if !no_new_round.is_empty() {
+            emit!(RoundNotAdvanced { assets: no_new_round, timestamp: clock.unix_timestamp });
+        }
+        for (asset_type, fallback) in failed_over {
+            emit!(SourceFailover {
+                asset_type,
+                priority_level: 1,
+                kind: fallback.kind,
+                feed: fallback.feed,
+                price: ctx.accounts.data.price_data[asset_type.index()].price,
+                timestamp: clock.unix_timestamp,
+            });
+        }
+
+        verbose_msg!("Updated assets: {:?}", updated);
+        log_compute_units();
+        Ok(updated)
+    }
+
+    #[access_control(PriceOracle::require_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Updater))]
+    pub fn update_asset_from_sources(ctx: Context<UpdateAssets>, asset_type: AssetType) -> Result<u64> {
+        log_compute_units();
+        verbose_msg!("Aggregating {} source(s) for {:?}", ctx.remaining_accounts.len(), asset_type);
+
+        let clock = current_clock()?;
+
+        let before = DataUpdateBaseline::capture(&ctx.accounts.header, &ctx.accounts.data);
+        let aggregation = PriceOracle::update_asset_from_sources(
+            &mut ctx.accounts.header,
+            &mut ctx.accounts.data,
+            &ctx.accounts.registry,
+            asset_type,
+            ctx.remaining_accounts,
+            &clock,
+        )?;
+
+        let updated = usize::from(!aggregation.pending && !aggregation.halted);
+        if updated > 0 {
+            if let Some(history) = ctx.accounts.history.as_mut() {
+                PriceOracle::record_history(history, &ctx.accounts.data, &[asset_type]);
+            }
+            emit_price_updates(&ctx.accounts.data, &[asset_type], &clock);
+        }
+        record_updater_stats(ctx.accounts.updater_stats.as_mut(), ctx.bumps.get("updater_stats"), ctx.accounts.authority.key, updated, 1 - updated, &clock);
+        emit_heartbeat(ctx.accounts.authority.key, updated, 0, 1 - updated, &clock);
+        let written: &[AssetType] = if updated > 0 { &[asset_type] } else { &[] };
+        finish_data_update(&mut ctx.accounts.header, &ctx.accounts.data, &before, written, &clock);
+
+        if let Some(level) = aggregation.priority_level.filter(|level| *level > 0) {
+            let served = aggregation.readings.iter().find(|reading| reading.accepted);
+            msg!("{:?} fell back to its priority {} source", asset_type, level);
+            emit!(SourceFailover {
+                asset_type,
+                priority_level: level,
+                kind: served.map(|reading| reading.kind).unwrap_or_default(),
+                feed: served.map(|reading| reading.feed).unwrap_or_default(),
+                price: aggregation.price,
+                timestamp: clock.unix_timestamp,
+            });
         }
+        emit!(SourcesAggregated {
+            asset_type,
+            price: aggregation.price,
+            readings: aggregation.readings,
+            timestamp: clock.unix_timestamp,
+            priority_level: aggregation.priority_level,
+        });
+        log_compute_units();
+        Ok(aggregation.price)
+    }
 
-        PriceOracle::update_prices_and_apys(
+    /// Writes the price of the asset's registered feed past the change limit, after a
+    /// genuine market move the limit keeps rejecting. Every other check of `update_assets`
+    /// applies. Returns the price it replaced.
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn force_update(ctx: Context<ForceUpdate>, asset_type: AssetType) -> Result<u64> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::ForceUpdate, Some(asset_type), crate::instruction::ForceUpdate { asset_type },
+        )?;
+        let clock = current_clock()?;
+        let before = DataUpdateBaseline::capture(&ctx.accounts.header, &ctx.accounts.data);
+        let old_price = PriceOracle::force_update(
             &mut ctx.accounts.header,
             &mut ctx.accounts.data,
-            &ctx.accounts.oracle_feed,
+            &ctx.accounts.registry,
+            asset_type,
+            &ctx.accounts.feed,
             &clock,
         )?;
 
-        msg!("Prices and APYs updated successfully");
-        sol_log_compute_units();
+        let price_data = &ctx.accounts.data.price_data[asset_type.index()];
+        emit!(PriceForced {
+            actor: ctx.accounts.authority.key(),
+            asset_type,
+            old_price,
+            new_price: price_data.price,
+            quote: price_data.quote,
+            change_bps: change_magnitude_bps(old_price, price_data.price).map_or(u32::MAX, |bps| bps.min(u32::MAX as u128) as u32),
+            provenance: price_data.provenance,
+            feed_timestamp: price_data.feed_timestamp,
+            timestamp: clock.unix_timestamp,
+        });
+        emit_price_updates(&ctx.accounts.data, &[asset_type], &clock);
+        // A forced price is written past the limit, so neither clamped nor under a grace period
+        finish_data_update(&mut ctx.accounts.header, &ctx.accounts.data, &before, &[], &clock);
+        Ok(old_price)
+    }
+
+    /// Emits the current price set as a canonical, sequenced payload for cross-chain
+    /// consumers and returns it
+    #[access_control(PriceOracle::require_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Updater))]
+    pub fn attest_prices(ctx: Context<AttestPrices>) -> Result<Vec<u8>> {
+        let clock = current_clock()?;
+        let attestation = PriceOracle::attest_prices(&mut ctx.accounts.header, &ctx.accounts.data, &clock)?;
+        let payload = attestation.encode();
+        emit!(PricesAttested {
+            sequence: attestation.sequence,
+            payload: payload.clone(),
+            timestamp: clock.unix_timestamp,
+        });
+        msg!("Attested {} price(s) under sequence {}", attestation.prices.len(), attestation.sequence);
+        Ok(payload)
+    }
+
+    #[access_control(PriceOracle::require_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Updater))]
+    pub fn heartbeat(ctx: Context<KeeperHeartbeat>) -> Result<()> {
+        let clock = current_clock()?;
+        emit_heartbeat(ctx.accounts.authority.key, 0, 0, 0, &clock);
+        Ok(())
+    }
+
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn initialize_registry(ctx: Context<InitializeRegistry>, queue: Pubkey) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::InitializeRegistry, None, crate::instruction::InitializeRegistry { queue },
+        )?;
+        PriceOracle::initialize_registry(
+            &mut ctx.accounts.registry,
+            queue,
+            pda_bump(&ctx.bumps, "registry")?,
+        )?;
+        msg!("Asset registry initialized with queue {}", queue);
+        Ok(())
+    }
+
+    #[access_control(PriceOracle::require_unlocked_asset_role(&ctx.accounts.header, ctx.accounts.authority.key, asset_type, Role::Admin))]
+    pub fn set_asset_feed(ctx: Context<SetAssetFeed>, asset_type: AssetType, feed: Pubkey) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::SetAssetFeed, Some(asset_type), crate::instruction::SetAssetFeed { asset_type, feed },
+        )?;
+        PriceOracle::set_asset_feed(&mut ctx.accounts.registry, asset_type, feed)?;
+        msg!("Feed for {:?} set to {}", asset_type, feed);
+        Ok(())
+    }
+
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn propose_config(ctx: Context<ProposeConfig>, new_config: OracleConfig) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::ProposeConfig, None, crate::instruction::ProposeConfig { new_config },
+        )?;
+        let clock = current_clock()?;
+        let effective_at = PriceOracle::propose_config(&mut ctx.accounts.header, new_config, clock.unix_timestamp)?;
+        emit!(ConfigProposed {
+            actor: ctx.accounts.authority.key(),
+            config: new_config,
+            effective_at,
+            timestamp: clock.unix_timestamp,
+        });
+        msg!("Config change proposed, effective at {}", effective_at);
+        Ok(())
+    }
+
+    /// Activates the pending config once its timelock has elapsed; anyone may send it
+    pub fn apply_config(ctx: Context<ApplyConfig>) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.payer.key,
+            AuditAction::ApplyConfig, None, crate::instruction::ApplyConfig {},
+        )?;
+        let clock = current_clock()?;
+        let config = PriceOracle::apply_config(&mut ctx.accounts.header, &mut ctx.accounts.registry, clock.unix_timestamp)?;
+        emit!(ConfigApplied {
+            actor: ctx.accounts.payer.key(),
+            config,
+            timestamp: clock.unix_timestamp,
+        });
+        msg!("Config applied: {} bps change limit, {}s timelock", config.price_change_limit_bps, config.timelock_secs);
+        Ok(())
+    }
+
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn cancel_config(ctx: Context<CancelConfig>) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::CancelConfig, None, crate::instruction::CancelConfig {},
+        )?;
+        let clock = current_clock()?;
+        let config = PriceOracle::cancel_config(&mut ctx.accounts.header)?;
+        emit!(ConfigCancelled {
+            actor: ctx.accounts.authority.key(),
+            config,
+            timestamp: clock.unix_timestamp,
+        });
+        msg!("Pending config change cancelled");
+        Ok(())
+    }
+
+    /// Queues a new mapping from multi-asset feed positions to assets behind the config
+    /// timelock. `positions` must name every position once, with distinct assets other than SOL.
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn propose_feed_mapping(ctx: Context<ProposeConfig>, positions: Vec<FeedPosition>) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::ProposeFeedMapping, None, crate::instruction::ProposeFeedMapping { positions: positions.clone() },
+        )?;
+        let clock = current_clock()?;
+        let (mapping, effective_at) = PriceOracle::propose_feed_mapping(&mut ctx.accounts.header, &positions, clock.unix_timestamp)?;
+        emit!(FeedMappingProposed {
+            actor: ctx.accounts.authority.key(),
+            assets: mapping.assets().to_vec(),
+            effective_at,
+            timestamp: clock.unix_timestamp,
+        });
+        msg!("Feed mapping {:?} proposed, effective at {}", mapping.assets(), effective_at);
+        Ok(())
+    }
+
+    /// Queues `feed` as shard `shard` (1 to `MAX_FEED_SHARDS`) of the multi-asset feed, read
+    /// at `positions`, behind the config timelock, or clearing the shard when `positions` is
+    /// empty. Positions left out are not read; an asset carried by another shard fails with
+    /// `FeedShardOverlap`.
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn propose_feed_shard(ctx: Context<ProposeConfig>, shard: u8, feed: Pubkey, positions: Vec<FeedPosition>) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::ProposeFeedShard, None, crate::instruction::ProposeFeedShard { shard, feed, positions: positions.clone() },
+        )?;
+        let clock = current_clock()?;
+        let (feed_shard, effective_at) = PriceOracle::propose_feed_shard(&mut ctx.accounts.header, shard, feed, &positions, clock.unix_timestamp)?;
+        emit!(FeedShardProposed {
+            actor: ctx.accounts.authority.key(),
+            shard,
+            feed: feed_shard.feed,
+            assets: feed_shard.asset_types(),
+            effective_at,
+            timestamp: clock.unix_timestamp,
+        });
+        msg!("Feed shard {} proposed as {} carrying {:?}, effective at {}", shard, feed_shard.feed, feed_shard.asset_types(), effective_at);
+        Ok(())
+    }
+
+    /// Registers the shard's pending change once its timelock has elapsed; anyone may send it
+    pub fn apply_feed_shard(ctx: Context<ApplyFeedMapping>, shard: u8) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.payer.key,
+            AuditAction::ApplyFeedShard, None, crate::instruction::ApplyFeedShard { shard },
+        )?;
+        let clock = current_clock()?;
+        let feed_shard = PriceOracle::apply_feed_shard(&mut ctx.accounts.header, shard, clock.unix_timestamp)?;
+        emit!(FeedShardApplied {
+            actor: ctx.accounts.payer.key(),
+            shard,
+            feed: feed_shard.feed,
+            assets: feed_shard.asset_types(),
+            timestamp: clock.unix_timestamp,
+        });
+        msg!("Feed shard {} set to {} carrying {:?}", shard, feed_shard.feed, feed_shard.asset_types());
+        Ok(())
+    }
+
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn cancel_feed_shard(ctx: Context<CancelConfig>, shard: u8) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::CancelFeedShard, None, crate::instruction::CancelFeedShard { shard },
+        )?;
+        let clock = current_clock()?;
+        let feed_shard = PriceOracle::cancel_feed_shard(&mut ctx.accounts.header, shard)?;
+        emit!(FeedShardCancelled {
+            actor: ctx.accounts.authority.key(),
+            shard,
+            feed: feed_shard.feed,
+            assets: feed_shard.asset_types(),
+            timestamp: clock.unix_timestamp,
+        });
+        msg!("Pending change of feed shard {} cancelled", shard);
+        Ok(())
+    }
+
+    /// Activates the pending feed mapping once its timelock has elapsed; anyone may send it
+    pub fn apply_feed_mapping(ctx: Context<ApplyFeedMapping>) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.payer.key,
+            AuditAction::ApplyFeedMapping, None, crate::instruction::ApplyFeedMapping {},
+        )?;
+        let clock = current_clock()?;
+        let mapping = PriceOracle::apply_feed_mapping(&mut ctx.accounts.header, clock.unix_timestamp)?;
+        emit!(FeedMappingApplied {
+            actor: ctx.accounts.payer.key(),
+            assets: mapping.assets().to_vec(),
+            timestamp: clock.unix_timestamp,
+        });
+        msg!("Feed mapping applied: {:?}", mapping.assets());
+        Ok(())
+    }
+
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn cancel_feed_mapping(ctx: Context<CancelConfig>) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::CancelFeedMapping, None, crate::instruction::CancelFeedMapping {},
+        )?;
+        let clock = current_clock()?;
+        let mapping = PriceOracle::cancel_feed_mapping(&mut ctx.accounts.header)?;
+        emit!(FeedMappingCancelled {
+            actor: ctx.accounts.authority.key(),
+            assets: mapping.assets().to_vec(),
+            timestamp: clock.unix_timestamp,
+        });
+        msg!("Pending feed mapping cancelled");
+        Ok(())
+    }
+
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn set_asset_sources(ctx: Context<SetAssetFeed>, asset_type: AssetType, sources: Vec<SourceSlot>, min_sources: u8) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::SetAssetSources, Some(asset_type), crate::instruction::SetAssetSources { asset_type, sources: sources.clone(), min_sources },
+        )?;
+        PriceOracle::set_asset_sources(&mut ctx.accounts.registry, asset_type, &sources, min_sources)?;
+        msg!("{:?} aggregates {} source(s), requiring {}", asset_type, sources.len(), min_sources);
+        Ok(())
+    }
+
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn set_asset_stake_pool(ctx: Context<SetAssetFeed>, asset_type: AssetType, stake_pool: Pubkey, kind: PoolKind, nav_tolerance_bps: u32) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::SetAssetStakePool, Some(asset_type), crate::instruction::SetAssetStakePool { asset_type, stake_pool, kind, nav_tolerance_bps },
+        )?;
+        PriceOracle::set_asset_stake_pool(&mut ctx.accounts.registry, asset_type, stake_pool, kind, nav_tolerance_bps)?;
+        let tolerance_bps = ctx.accounts.registry.assets[asset_type.index()].nav_tolerance_bps();
+        msg!("Stake pool for {:?} set to {:?} {}, tolerance {} bps", asset_type, kind, stake_pool, tolerance_bps);
+        Ok(())
+    }
+
+    /// Queues the source `update_assets` reads for the asset when its registered feed is
+    /// stale behind the config timelock; `None` queues clearing it. Global admin only, as the
+    /// fallback is read in place of the feed.
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn propose_asset_fallback(ctx: Context<ProposeAssetFallback>, asset_type: AssetType, fallback: Option<SourceSlot>) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::ProposeAssetFallback, Some(asset_type), crate::instruction::ProposeAssetFallback { asset_type, fallback },
+        )?;
+        let clock = current_clock()?;
+        let effective_at = PriceOracle::propose_asset_fallback(&mut ctx.accounts.header, &ctx.accounts.registry, asset_type, fallback, clock.unix_timestamp)?;
+        emit!(AssetFallbackProposed {
+            actor: ctx.accounts.authority.key(),
+            asset_type,
+            fallback,
+            effective_at,
+            timestamp: clock.unix_timestamp,
+        });
+        msg!("Fallback source {:?} for {:?} proposed, effective at {}", fallback, asset_type, effective_at);
+        Ok(())
+    }
+
+    /// Registers the asset's pending fallback once its timelock has elapsed; anyone may send it
+    pub fn apply_asset_fallback(ctx: Context<ApplyConfig>, asset_type: AssetType) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.payer.key,
+            AuditAction::ApplyAssetFallback, Some(asset_type), crate::instruction::ApplyAssetFallback { asset_type },
+        )?;
+        let clock = current_clock()?;
+        let (old_fallback, new_fallback) =
+            PriceOracle::apply_asset_fallback(&mut ctx.accounts.header, &mut ctx.accounts.registry, asset_type, clock.unix_timestamp)?;
+        emit!(AssetFallbackApplied {
+            actor: ctx.accounts.payer.key(),
+            asset_type,
+            old_fallback,
+            new_fallback,
+            timestamp: clock.unix_timestamp,
+        });
+        msg!("Fallback source for {:?} changed from {:?} to {:?}", asset_type, old_fallback, new_fallback);
+        Ok(())
+    }
+
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn cancel_asset_fallback(ctx: Context<CancelConfig>, asset_type: AssetType) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::CancelAssetFallback, Some(asset_type), crate::instruction::CancelAssetFallback { asset_type },
+        )?;
+        let clock = current_clock()?;
+        let fallback = PriceOracle::cancel_asset_fallback(&mut ctx.accounts.header, asset_type)?;
+        emit!(AssetFallbackCancelled {
+            actor: ctx.accounts.authority.key(),
+            asset_type,
+            fallback,
+            timestamp: clock.unix_timestamp,
+        });
+        msg!("Pending fallback source for {:?} cancelled", asset_type);
+        Ok(())
+    }
+
+    /// Binds the Pyth feed id the asset's Pyth sources must carry; all zeros clears it. SOL
+    /// is held to the SOL/USD feed while none is bound, other assets refuse Pyth reads.
+    #[access_control(PriceOracle::require_unlocked_asset_role(&ctx.accounts.header, ctx.accounts.authority.key, asset_type, Role::Admin))]
+    pub fn set_pyth_feed_id(ctx: Context<SetAssetFeed>, asset_type: AssetType, feed_id: [u8; 32]) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::SetPythFeedId, Some(asset_type), crate::instruction::SetPythFeedId { asset_type, feed_id },
+        )?;
+        let clock = current_clock()?;
+        let old_feed_id = PriceOracle::set_pyth_feed_id(&mut ctx.accounts.registry, asset_type, feed_id);
+        emit!(PythFeedIdChanged {
+            actor: ctx.accounts.authority.key(),
+            asset_type,
+            old_feed_id,
+            new_feed_id: feed_id,
+            timestamp: clock.unix_timestamp,
+        });
+        msg!("Pyth feed id for {:?} set to {}", asset_type, pyth_utils::feed_id_hex(&feed_id));
+        Ok(())
+    }
+
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn set_asset_source(ctx: Context<SetAssetSource>, asset_type: AssetType, source: SourceKind) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::SetAssetSource, Some(asset_type), crate::instruction::SetAssetSource { asset_type, source },
+        )?;
+        PriceOracle::set_asset_source(&mut ctx.accounts.header, asset_type, source);
+        msg!("Source for {:?} set to {:?}", asset_type, source);
+        Ok(())
+    }
+
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn set_asset_quote(ctx: Context<SetAssetQuote>, asset_type: AssetType, quote: QuoteCurrency) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::SetAssetQuote, Some(asset_type), crate::instruction::SetAssetQuote { asset_type, quote },
+        )?;
+        let clock = current_clock()?;
+        let nonce_before = ctx.accounts.data.nonce;
+        let old_quote = PriceOracle::set_asset_quote(&mut ctx.accounts.data, asset_type, quote);
+        record_data_change(&mut ctx.accounts.header, &ctx.accounts.data, nonce_before, &clock);
+        if old_quote != quote {
+            emit!(QuoteCurrencyChanged {
+                actor: ctx.accounts.authority.key(),
+                asset_type,
+                old_quote,
+                new_quote: quote,
+                timestamp: clock.unix_timestamp,
+            });
+        }
+        msg!("Quote currency for {:?} set to {:?}", asset_type, quote);
+        Ok(())
+    }
+
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn delist_asset(ctx: Context<SetAssetQuote>, asset_type: AssetType) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::DelistAsset, Some(asset_type), crate::instruction::DelistAsset { asset_type },
+        )?;
+        let clock = current_clock()?;
+        let nonce_before = ctx.accounts.data.nonce;
+        PriceOracle::delist_asset(&mut ctx.accounts.data, asset_type)?;
+        record_data_change(&mut ctx.accounts.header, &ctx.accounts.data, nonce_before, &clock);
+        emit!(AssetDelisted {
+            actor: ctx.accounts.authority.key(),
+            asset_type,
+            timestamp: clock.unix_timestamp,
+        });
+        msg!("{:?} delisted", asset_type);
         Ok(())
     }
 
-    pub fn update_sol_price(ctx: Context<UpdateSolPrice>) -> Result<()> {
-        sol_log_compute_units();
-        msg!("Updating SOL price");
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn reset_watermarks(ctx: Context<SetAssetQuote>, asset_type: AssetType) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::ResetWatermarks, Some(asset_type), crate::instruction::ResetWatermarks { asset_type },
+        )?;
+        let clock = current_clock()?;
+        let nonce_before = ctx.accounts.data.nonce;
+        let old = PriceOracle::reset_watermarks(&mut ctx.accounts.data, asset_type);
+        record_data_change(&mut ctx.accounts.header, &ctx.accounts.data, nonce_before, &clock);
+        emit!(WatermarksReset {
+            actor: ctx.accounts.authority.key(),
+            asset_type,
+            old_high: old.map_or(0, |watermarks| watermarks.high),
+            old_low: old.map_or(0, |watermarks| watermarks.low),
+            timestamp: clock.unix_timestamp,
+        });
+        msg!("Watermarks of {:?} reset", asset_type);
+        Ok(())
+    }
+
+    /// Registers the program `update_prices_and_apys` and `update_sol_price` call back after
+    /// writing prices, replacing any earlier registration. `writable_mask` bit `i` marks
+    /// `accounts[i]` writable. A `required` callback that cannot be delivered fails the
+    /// update; otherwise it is skipped, see `subscriber`.
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn set_subscriber(
+        ctx: Context<SetSubscriber>,
+        program_id: Pubkey,
+        discriminator: [u8; 8],
+        accounts: Vec<Pubkey>,
+        writable_mask: u8,
+        enabled: bool,
+        required: bool,
+    ) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::SetSubscriber, None, crate::instruction::SetSubscriber { program_id, discriminator, accounts: accounts.clone(), writable_mask, enabled, required },
+        )?;
+        let clock = current_clock()?;
+        let bump = pda_bump(&ctx.bumps, "subscriber")?;
+        ctx.accounts.subscriber.set(program_id, discriminator, &accounts, writable_mask, enabled, required, bump)?;
+        emit!(SubscriberSet {
+            actor: ctx.accounts.authority.key(),
+            program_id,
+            account_count: accounts.len() as u8,
+            enabled,
+            required,
+            timestamp: clock.unix_timestamp,
+        });
+        msg!("Subscriber set to {} with {} account(s), enabled: {}, required: {}", program_id, accounts.len(), enabled, required);
+        Ok(())
+    }
+
+    pub fn get_current_price(ctx: Context<GetPrice>, asset_type: AssetType) -> Result<()> {
+        let price = PriceOracle::get_current_price(&ctx.accounts.data, asset_type)?;
+        let quote = ctx.accounts.data.price_data[asset_type.index()].quote;
+        msg!("Current price for {:?}: {} {:?}", asset_type, fixed_to_f64(price), quote);
+        Ok(())
+    }
+
+    /// Strict price read. `reject_same_slot` and the optional instructions sysvar opt into
+    /// the guards of `sandwich_guard` against prices moved by the reading transaction.
+    pub fn get_price_no_older_than(
+        ctx: Context<GetPriceStrict>,
+        asset_type: AssetType,
+        max_age: MaxAge,
+        reject_same_slot: bool,
+    ) -> Result<QuotedPrice> {
+        let clock = current_clock()?;
+        if let Some(instructions) = &ctx.accounts.instructions {
+            reject_earlier_price_update(instructions)?;
+        }
+        let price = PriceOracle::get_price_no_older_than(&ctx.accounts.data, asset_type, max_age, reject_same_slot, &clock)?;
+        msg!("Current price for {:?}: {} {:?} (max age {:?})", asset_type, fixed_to_f64(price.price), price.quote, max_age);
+        Ok(price)
+    }
+
+    /// Nonce of the data account, advanced by every instruction that changes a price in it.
+    /// Read it before and after an off-chain computation to detect a concurrent change.
+    pub fn get_data_nonce(ctx: Context<GetPrice>) -> Result<u64> {
+        Ok(ctx.accounts.data.nonce)
+    }
+
+    pub fn get_price_with_confidence(ctx: Context<GetPrice>, asset_type: AssetType) -> Result<PriceWithConfidence> {
+        let price = PriceOracle::get_price_with_confidence(&ctx.accounts.data, asset_type)?;
+        msg!("Price for {:?}: {} {:?}, confidence {} ({:?})", asset_type, fixed_to_f64(price.price), price.quote, fixed_to_f64(price.confidence.value), price.confidence.status);
+        Ok(price)
+    }
+
+    /// Display-grade price: the last stored value however old, flagged `degraded` instead of
+    /// failing when stale. Never use it to value collateral; see `get_price_no_older_than`.
+    pub fn get_price_lenient(ctx: Context<GetAllPrices>, asset_type: AssetType) -> Result<LenientPrice> {
+        let clock = current_clock()?;
+        let price = PriceOracle::get_price_lenient(&ctx.accounts.header, &ctx.accounts.data, asset_type, &clock)?;
+        msg!("Last price for {:?}: {} {:?}, {}s old, degraded: {}", asset_type, fixed_to_f64(price.price), price.quote, price.staleness_secs, price.degraded);
+        Ok(price)
+    }
+
+    /// Guard for the front of consumer transactions: fails the whole transaction unless the
+    /// oracle is running and the asset's price is at most `max_age_secs` old. Logs nothing
+    /// on success to stay within a couple thousand compute units. `reject_same_slot` and the
+    /// optional instructions sysvar opt into the guards of `sandwich_guard`.
+    pub fn assert_price_fresh(
+        ctx: Context<AssertPriceFresh>,
+        asset_type: AssetType,
+        max_age_secs: u32,
+        reject_same_slot: bool,
+    ) -> Result<()> {
+        let clock = current_clock()?;
+        if let Some(instructions) = &ctx.accounts.instructions {
+            reject_earlier_price_update(instructions)?;
+        }
+        PriceOracle::assert_price_fresh(&ctx.accounts.header, &ctx.accounts.data, asset_type, max_age_secs, reject_same_slot, &clock)
+    }
+
+    pub fn get_exchange_rate(ctx: Context<GetPrice>, base: AssetType, counter: AssetType, convert_quotes: bool) -> Result<u64> {
+        let rate = PriceOracle::get_exchange_rate(&ctx.accounts.data, base, counter, convert_quotes)?;
+        msg!("1 {:?} = {} {:?}", base, fixed_to_f64(rate), counter);
+        Ok(rate)
+    }
+
+    pub fn convert_amount(ctx: Context<GetPrice>, from: AssetType, to: AssetType, amount: u64, convert_quotes: bool) -> Result<u64> {
+        let converted = PriceOracle::convert_amount(&ctx.accounts.data, from, to, amount, convert_quotes)?;
+        msg!("{} {:?} = {} {:?}", amount, from, converted, to);
+        Ok(converted)
+    }
+
+    /// Risk-adjusted USD value of `amount` base units of the asset, for lending. Fails
+    /// rather than value collateral at a stale, paused or missing price.
+    pub fn get_collateral_value(ctx: Context<GetAllPrices>, asset_type: AssetType, amount: u64) -> Result<u64> {
+        let clock = current_clock()?;
+        let value = PriceOracle::get_collateral_value(&ctx.accounts.header, &ctx.accounts.data, asset_type, amount, &clock)?;
+        msg!("{} {:?} = ${} as collateral, {} bps haircut", amount, asset_type, fixed_to_f64(value), ctx.accounts.header.haircut_bps(asset_type));
+        Ok(value)
+    }
+
+    /// USD value of the SPL Token or Token-2022 accounts passed as remaining accounts, at
+    /// most `MAX_PORTFOLIO_POSITIONS`, with each account's value. With `skip_unpriced`,
+    /// accounts of unknown mints or of assets that cannot be priced now are reported with
+    /// their error and left out of the total instead of failing the instruction.
+    pub fn get_portfolio_value<'info>(ctx: Context<'_, '_, '_, 'info, GetAllPrices<'info>>, skip_unpriced: bool) -> Result<PortfolioValue> {
+        let clock = current_clock()?;
+        let value = PriceOracle::get_portfolio_value(&ctx.accounts.header, &ctx.accounts.data, ctx.remaining_accounts, skip_unpriced, &clock)?;
+        let skipped = value.positions.iter().filter(|position| position.skipped()).count();
+        msg!("{} position(s) = ${}, {} skipped", value.positions.len(), fixed_to_f64(value.total), skipped);
+        Ok(value)
+    }
+
+    /// The asset's yield as its feed last reported it, with the convention it was read in.
+    /// The stored `apy` is this value converted to an APY.
+    pub fn get_raw_yield(ctx: Context<GetAllPrices>, asset_type: AssetType) -> Result<RawYield> {
+        let raw = PriceOracle::get_raw_yield(&ctx.accounts.header, &ctx.accounts.data, asset_type)?;
+        msg!("Raw yield of {:?}: {} as {:?}", asset_type, fixed_to_f64(raw.value), raw.config);
+        Ok(raw)
+    }
+
+    /// How far the asset's stored price can be trusted now, from 0 to 10000, with the
+    /// components and inputs behind it. Never fails: unusable prices score 0.
+    pub fn get_trust_score(ctx: Context<GetAllPrices>, asset_type: AssetType) -> Result<TrustScore> {
+        let clock = current_clock()?;
+        let trust = PriceOracle::get_trust_score(&ctx.accounts.header, &ctx.accounts.data, asset_type, &clock);
+        msg!("{:?} trust score: {} (freshness {}, confidence {}, source {}, stability {})", asset_type, trust.score, trust.freshness_bps, trust.confidence_bps, trust.source_bps, trust.stability_bps);
+        Ok(trust)
+    }
+
+    pub fn get_all_prices(ctx: Context<GetAllPrices>) -> Result<Vec<AssetPrice>> {
+        let clock = current_clock()?;
+        let prices = PriceOracle::get_all_prices(&ctx.accounts.header, &ctx.accounts.data, &clock);
+        for price in prices.iter() {
+            verbose_msg!("{:?}: {} ({:?}), max age {}s, stale: {}", price.asset_type, fixed_to_f64(price.price), price.quote, price.max_age_secs, price.stale);
+        }
+        Ok(prices)
+    }
+
+    pub fn get_updater_stats(ctx: Context<GetUpdaterStats>, updater: Pubkey) -> Result<UpdaterStats> {
+        let stats = ctx.accounts.stats.clone().into_inner();
+        msg!("Updater {}: {} update(s), {} rejected, last at slot {}", updater, stats.successful_updates, stats.rejected_attempts, stats.last_update_slot);
+        Ok(stats)
+    }
+
+    pub fn get_oracle_stats(ctx: Context<GetOracleStats>) -> Result<OracleStats> {
+        let clock = current_clock()?;
+        let stats = PriceOracle::get_oracle_stats(&ctx.accounts.header, clock.unix_timestamp);
+        let update_stats = stats.update_stats;
+        msg!(
+            "{} update(s), {} stale and {} change-limit rejection(s), {} stop incident(s) totalling {}s, longest gap {}s ending at {}",
+            update_stats.successful_updates,
+            update_stats.rejected_stale,
+            update_stats.rejected_change_limit,
+            stats.stop_incidents,
+            stats.total_stopped_secs,
+            update_stats.longest_gap_secs,
+            update_stats.longest_gap_at,
+        );
+        Ok(stats)
+    }
+
+    /// Up to `count` of the latest audit entries, newest first, capped at `MAX_AUDIT_ENTRIES_RETURNED`
+    pub fn get_audit_log(ctx: Context<GetAuditLog>, count: u8) -> Result<AuditLogPage> {
+        let audit_log = &ctx.accounts.audit_log;
+        let entries = audit_log.recent((count as usize).min(MAX_AUDIT_ENTRIES_RETURNED));
+        msg!("{} of {} audit entries", entries.len(), audit_log.total_entries);
+        Ok(AuditLogPage { total_entries: audit_log.total_entries, entries })
+    }
+
+    /// The asset's move held for confirmation; a zero `price` means nothing is pending
+    pub fn get_pending_price(ctx: Context<GetAllPrices>, asset_type: AssetType) -> Result<PendingPrice> {
+        let pending = PriceOracle::get_pending_price(&ctx.accounts.header, asset_type);
+        msg!("Pending move for {:?}: {}, observed at {}", asset_type, fixed_to_f64(pending.price), pending.observed_at);
+        Ok(pending)
+    }
+
+    /// Why the asset's latest observation was rejected, as an `OracleError` code and the
+    /// time of the rejection; a zero `code` means the last update accepted it
+    pub fn get_last_error(ctx: Context<GetAllPrices>, asset_type: AssetType) -> Result<LastError> {
+        let last_error = PriceOracle::get_last_error(&ctx.accounts.header, asset_type);
+        msg!("Last error of {:?}: code {} at {}", asset_type, last_error.code, last_error.time);
+        Ok(last_error)
+    }
+
+    /// The LST's market premium over its stake pool rate in basis points, negative for a
+    /// discount, with the feed price and intrinsic rate behind it, as of the latest update
+    /// passed the pool. Fails with `DataNotAvailable` for assets without a registered pool.
+    pub fn get_premium(ctx: Context<GetPremium>, asset_type: AssetType) -> Result<LstPremium> {
+        let premium = PriceOracle::get_premium(&ctx.accounts.header, &ctx.accounts.registry, asset_type)?;
+        msg!("{:?} premium: {} bps (feed {}, intrinsic {}) at {}", asset_type, premium.premium_bps, fixed_to_f64(premium.feed_price), fixed_to_f64(premium.intrinsic_rate), premium.time);
+        Ok(premium)
+    }
+
+    /// Anyone may call. The freshness of each shard of the multi-asset feed, the primary feed
+    /// first, so a dead shard shows even while the others keep updating
+    pub fn get_feed_shard_health(ctx: Context<GetAllPrices>) -> Result<Vec<FeedShardHealth>> {
+        let clock = current_clock()?;
+        let health = PriceOracle::get_feed_shard_health(&ctx.accounts.header, &ctx.accounts.data, &clock);
+        for shard in health.iter() {
+            msg!("Feed shard {} ({}): oldest round at {}, stale: {}", shard.shard, shard.feed, shard.oldest_feed_timestamp, shard.stale);
+        }
+        Ok(health)
+    }
+
+    /// Anyone may call. Every asset the program supports with its symbol, mint, decimals,
+    /// quote currency and status, in index order, for clients that discover assets instead
+    /// of hardcoding them, see the `asset_info` module.
+    pub fn get_supported_assets(ctx: Context<GetAllPrices>) -> Result<Vec<AssetInfo>> {
+        let assets = PriceOracle::get_supported_assets(&ctx.accounts.header, &ctx.accounts.data);
+        for asset in assets.iter() {
+            verbose_msg!("{} ({}): {:?}, mint {}", asset.symbol, asset.id, asset.status, asset.mint);
+        }
+        Ok(assets)
+    }
+
+    /// Anyone may call. Checks the oracle's internal consistency and returns the mask of the
+    /// violated invariants, `0` when all hold, see the `invariants` module. Violations are
+    /// reported, never failed on. `previous_nonce` and `previous_generation` are the
+    /// caller's last reading of the data nonce and data generation, zeros to skip that check.
+    pub fn verify_invariants(ctx: Context<VerifyInvariants>, previous_nonce: u64, previous_generation: u32) -> Result<u32> {
+        let clock = current_clock()?;
+        let (header, data) = (&ctx.accounts.header, &ctx.accounts.data);
+        let violations = PriceOracle::verify_invariants(ctx.program_id, header, data, previous_nonce, previous_generation, clock.unix_timestamp);
+        if violations == 0 {
+            msg!("All invariants hold");
+        } else {
+            msg!("Invariants violated: {:#b}", violations);
+        }
+        emit!(InvariantsVerified {
+            header: header.key(),
+            violations,
+            nonce: data.nonce,
+            data_generation: header.data_generation,
+            timestamp: clock.unix_timestamp,
+        });
+        Ok(violations)
+    }
+
+    pub fn get_price_provenance(ctx: Context<GetPrice>, asset_type: AssetType) -> Result<PriceProvenance> {
+        let provenance = PriceOracle::get_price_provenance(&ctx.accounts.data, asset_type)?;
+        msg!("{:?} price from feed {} (kind {}), round slot {}", asset_type, provenance.source_feed, provenance.source_kind, provenance.source_round_slot);
+        Ok(provenance)
+    }
+
+    pub fn get_price_change(ctx: Context<GetPrice>, asset_type: AssetType) -> Result<PriceChange> {
+        let change = PriceOracle::get_price_change(&ctx.accounts.data, asset_type)?;
+        msg!("Price change for {:?}: {} bps ({:?})", asset_type, change.change_bps, change.quote);
+        Ok(change)
+    }
+
+    pub fn get_watermarks(ctx: Context<GetPrice>, asset_type: AssetType) -> Result<Watermarks> {
+        let watermarks = PriceOracle::get_watermarks(&ctx.accounts.data, asset_type)?;
+        msg!("Watermarks for {:?}: high {} at {}, low {} at {} ({:?})", asset_type, fixed_to_f64(watermarks.high), watermarks.high_time, fixed_to_f64(watermarks.low), watermarks.low_time, watermarks.quote);
+        Ok(watermarks)
+    }
+
+    #[access_control(PriceOracle::require_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn initialize_history(ctx: Context<InitializeHistory>) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::InitializeHistory, None, crate::instruction::InitializeHistory {},
+        )?;
+        PriceOracle::initialize_history(&mut ctx.accounts.history, pda_bump(&ctx.bumps, "history")?)?;
+        msg!("Price history initialized");
+        Ok(())
+    }
+
+    /// Creates the audit log, after which every privileged instruction must be passed it.
+    /// Allowed once locked, as it only adds accountability.
+    #[access_control(PriceOracle::require_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn initialize_audit_log(ctx: Context<InitializeAuditLog>) -> Result<()> {
+        PriceOracle::initialize_audit_log(&mut ctx.accounts.header, &mut ctx.accounts.audit_log, pda_bump(&ctx.bumps, "audit_log")?);
+        let entry = AuditEntry::new(
+            current_clock()?.unix_timestamp,
+            ctx.accounts.authority.key(),
+            AuditAction::InitializeAuditLog,
+            None,
+            &crate::instruction::InitializeAuditLog {}.data(),
+        );
+        ctx.accounts.audit_log.record(entry);
+        msg!("Audit log initialized");
+        Ok(())
+    }
+
+    pub fn get_volatility(ctx: Context<GetVolatility>, asset_type: AssetType, window_secs: i64) -> Result<u64> {
+        let clock = current_clock()?;
+        let volatility = PriceOracle::get_volatility(&ctx.accounts.history, asset_type, window_secs, clock.unix_timestamp)?;
+        msg!("Volatility for {:?} over {}s: {} bps", asset_type, window_secs, volatility);
+        Ok(volatility)
+    }
+
+    /// Creates the `ApyHistory` account, which the APY-writing updates then sample
+    #[access_control(PriceOracle::require_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn initialize_apy_history(ctx: Context<InitializeApyHistory>, min_samples: u8) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::InitializeApyHistory, None, crate::instruction::InitializeApyHistory { min_samples },
+        )?;
+        PriceOracle::initialize_apy_history(&mut ctx.accounts.apy_history, min_samples, pda_bump(&ctx.bumps, "apy_history")?)?;
+        msg!("APY history initialized, averages need {} sample(s)", min_samples);
+        Ok(())
+    }
+
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn set_min_apy_samples(ctx: Context<SetMinApySamples>, min_samples: u8) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::SetMinApySamples, None, crate::instruction::SetMinApySamples { min_samples },
+        )?;
+        let old = PriceOracle::set_min_apy_samples(&mut ctx.accounts.apy_history, min_samples)?;
+        msg!("Minimum APY samples changed from {} to {}", old, min_samples);
+        Ok(())
+    }
+
+    pub fn get_average_apy(ctx: Context<GetAverageApy>, asset_type: AssetType, window_secs: i64) -> Result<u32> {
+        let clock = current_clock()?;
+        let average = PriceOracle::get_average_apy(&ctx.accounts.apy_history, asset_type, window_secs, clock.unix_timestamp)?;
+        msg!("Average APY for {:?} over {}s: {} bps", asset_type, window_secs, average);
+        Ok(average)
+    }
+
+    pub fn get_price_at(ctx: Context<GetVolatility>, asset_type: AssetType, timestamp: i64) -> Result<PriceObservation> {
+        let observation = PriceOracle::get_price_at(&ctx.accounts.history, asset_type, timestamp)?;
+        msg!("Price of {:?} at {}: {}, observed at {}", asset_type, timestamp, observation.price, observation.timestamp);
+        Ok(observation)
+    }
+
+    pub fn snapshot_epoch(ctx: Context<SnapshotEpoch>, asset_type: AssetType) -> Result<()> {
+        let clock = current_clock()?;
+        let snapshot = PriceOracle::snapshot_epoch(
+            &mut ctx.accounts.snapshots,
+            &ctx.accounts.data,
+            asset_type,
+            pda_bump(&ctx.bumps, "snapshots")?,
+            &clock,
+        )?;
+        msg!("Epoch {} snapshot for {:?}: price {}, APY {}", snapshot.epoch, asset_type, fixed_to_f64(snapshot.price), fixed_to_f64(snapshot.apy));
+        Ok(())
+    }
+
+    pub fn get_epoch_price(ctx: Context<GetEpochPrice>, asset_type: AssetType, epoch: u64) -> Result<QuotedPrice> {
+        let price = PriceOracle::get_epoch_price(&ctx.accounts.snapshots, epoch)?;
+        msg!("Price for {:?} in epoch {}: {} {:?}", asset_type, epoch, fixed_to_f64(price.price), price.quote);
+        Ok(price)
+    }
+
+    /// Drops all but the `keep_last_n` most recent entries of an asset from the price
+    /// history and the APY history, whichever are passed; zero clears them. Allowed once
+    /// locked, as it only discards past records.
+    #[access_control(PriceOracle::require_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn prune_history(ctx: Context<PruneHistory>, asset_type: AssetType, keep_last_n: u8) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::PruneHistory, Some(asset_type), crate::instruction::PruneHistory { asset_type, keep_last_n },
+        )?;
+        if ctx.accounts.history.is_none() && ctx.accounts.apy_history.is_none() {
+            msg!("Pass the price history, the APY history or both");
+            return Err(error!(OracleError::InvalidConfig));
+        }
+        let clock = current_clock()?;
+        let observations_removed = ctx.accounts.history.as_mut()
+            .map_or(0, |history| PriceOracle::prune_price_history(history, asset_type, keep_last_n));
+        let apy_samples_removed = ctx.accounts.apy_history.as_mut()
+            .map_or(0, |apy_history| PriceOracle::prune_apy_history(apy_history, asset_type, keep_last_n));
+        emit!(HistoryPruned {
+            actor: ctx.accounts.authority.key(),
+            asset_type,
+            keep_last_n,
+            observations_removed,
+            apy_samples_removed,
+            timestamp: clock.unix_timestamp,
+        });
+        msg!("Pruned {} observation(s) and {} APY sample(s) of {:?}, keeping the last {}", observations_removed, apy_samples_removed, asset_type, keep_last_n);
+        Ok(())
+    }
+
+    /// Removes an asset's epoch snapshots before `before_epoch`, making room once the list
+    /// is full. Allowed once locked, as it only discards past records.
+    #[access_control(PriceOracle::require_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn prune_epoch_snapshots(ctx: Context<PruneEpochSnapshots>, asset_type: AssetType, before_epoch: u64) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::PruneEpochSnapshots, Some(asset_type), crate::instruction::PruneEpochSnapshots { asset_type, before_epoch },
+        )?;
+        let clock = current_clock()?;
+        let removed = PriceOracle::prune_epoch_snapshots(&mut ctx.accounts.snapshots, before_epoch);
+        emit!(EpochSnapshotsPruned {
+            actor: ctx.accounts.authority.key(),
+            asset_type,
+            before_epoch,
+            removed,
+            remaining: ctx.accounts.snapshots.snapshots.len() as u32,
+            timestamp: clock.unix_timestamp,
+        });
+        msg!("Pruned {} snapshot(s) of {:?} before epoch {}", removed, asset_type, before_epoch);
+        Ok(())
+    }
+
+    /// Copies the header flags and every asset's price into a new checkpoint at
+    /// `[CHECKPOINT_SEED, id]`, paid for by the caller
+    pub fn create_checkpoint(ctx: Context<CreateCheckpoint>, id: u64) -> Result<()> {
+        let clock = current_clock()?;
+        PriceOracle::create_checkpoint(
+            &mut ctx.accounts.checkpoint,
+            &ctx.accounts.header,
+            &ctx.accounts.data,
+            id,
+            pda_bump(&ctx.bumps, "checkpoint")?,
+            ctx.accounts.payer.key(),
+            &clock,
+        )?;
+        emit!(CheckpointCreated {
+            creator: ctx.accounts.payer.key(),
+            id,
+            account: ctx.accounts.checkpoint.key(),
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+        msg!("Checkpoint {} taken at slot {}", id, clock.slot);
+        Ok(())
+    }
+
+    pub fn get_checkpoint(ctx: Context<GetCheckpoint>, id: u64) -> Result<Checkpoint> {
+        let checkpoint = ctx.accounts.checkpoint.clone().into_inner();
+        msg!("Checkpoint {} taken at slot {} by {}", id, checkpoint.slot, checkpoint.creator);
+        Ok(checkpoint)
+    }
+
+    /// Closes a checkpoint older than `CHECKPOINT_MIN_CLOSE_AGE_SECS`, refunding the rent to
+    /// its creator. Allowed once the oracle is locked: it changes nothing the oracle serves.
+    #[access_control(PriceOracle::require_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn close_checkpoint(ctx: Context<CloseCheckpoint>, id: u64) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::CloseCheckpoint, None, crate::instruction::CloseCheckpoint { id },
+        )?;
+        let clock = current_clock()?;
+        PriceOracle::require_checkpoint_closable(&ctx.accounts.checkpoint, &clock)?;
+        emit!(CheckpointClosed {
+            actor: ctx.accounts.authority.key(),
+            id,
+            creator: ctx.accounts.creator.key(),
+            timestamp: clock.unix_timestamp,
+        });
+        msg!("Checkpoint {} closed, rent refunded to {}", id, ctx.accounts.creator.key());
+        Ok(())
+    }
+
+    /// Creates the signer's basket `id`, paid for and owned by them. `weights` must be
+    /// positive, name each asset once, no delisted one, and add up to 10000 bps.
+    pub fn create_basket(ctx: Context<CreateBasket>, id: u64, weights: Vec<BasketWeight>) -> Result<()> {
+        PriceOracle::create_basket(
+            &mut ctx.accounts.basket,
+            &ctx.accounts.data,
+            ctx.accounts.creator.key(),
+            id,
+            &weights,
+            pda_bump(&ctx.bumps, "basket")?,
+        )?;
+        emit_basket_weights(&ctx.accounts.basket, ctx.accounts.basket.key())?;
+        msg!("Basket {} created with {} component(s)", id, weights.len());
+        Ok(())
+    }
+
+    /// Replaces the weights of the signer's basket `id`, validated as by `create_basket`
+    pub fn update_basket_weights(ctx: Context<UpdateBasketWeights>, id: u64, weights: Vec<BasketWeight>) -> Result<()> {
+        ctx.accounts.basket.set_weights(&ctx.accounts.data, &weights)?;
+        emit_basket_weights(&ctx.accounts.basket, ctx.accounts.basket.key())?;
+        msg!("Basket {} now has {} component(s)", id, weights.len());
+        Ok(())
+    }
+
+    /// Closes the signer's basket `id`, refunding its rent
+    pub fn close_basket(ctx: Context<CloseBasket>, id: u64) -> Result<()> {
+        emit!(BasketClosed {
+            creator: ctx.accounts.creator.key(),
+            basket: ctx.accounts.basket.key(),
+            id,
+            timestamp: current_clock()?.unix_timestamp,
+        });
+        msg!("Basket {} closed", id);
+        Ok(())
+    }
+
+    /// USD value of one unit of `basket` with 9 decimals. Fails, naming the component, when
+    /// any component is unpriced, delisted, paused or stale, or during an emergency stop.
+    pub fn get_basket_price(ctx: Context<GetBasketPrice>) -> Result<u64> {
+        let clock = current_clock()?;
+        let price = PriceOracle::get_basket_price(&ctx.accounts.header, &ctx.accounts.data, &ctx.accounts.basket, &clock)?;
+        msg!("Basket {} of {} is worth ${}", ctx.accounts.basket.id, ctx.accounts.basket.creator, fixed_to_f64(price));
+        Ok(price)
+    }
+
+    /// Creates the signer's quote buffer `id`, `space` bytes paid for by them, whose quotes
+    /// are written at `offset`. `layout_version` is kept for the consumer's own use.
+    pub fn create_quote_buffer(ctx: Context<CreateQuoteBuffer>, id: u64, space: u32, offset: u32, layout_version: u32) -> Result<()> {
+        QuoteBuffer::validate_layout(space as usize, offset as usize)?;
+        let buffer = &mut ctx.accounts.buffer;
+        buffer.authority = ctx.accounts.authority.key();
+        buffer.id = id;
+        buffer.bump = pda_bump(&ctx.bumps, "buffer")?;
+        buffer.layout_version = layout_version;
+        buffer.offset = offset;
+        msg!("Quote buffer {} of {} created, {} bytes with quotes at offset {}", id, buffer.authority, space, offset);
+        Ok(())
+    }
+
+    /// Writes the asset's current quote into `buffer` at its offset. Anyone may send it;
+    /// it fails when the oracle is stopped or the asset is unpriced, delisted, paused or stale.
+    pub fn write_quote_to(ctx: Context<WriteQuoteTo>, asset_type: AssetType) -> Result<()> {
+        let clock = current_clock()?;
+        let quote = PriceOracle::pushed_quote(&ctx.accounts.header, &ctx.accounts.data, asset_type, &clock)?;
+        QuoteBuffer::write_quote(ctx.accounts.buffer.key, &mut ctx.accounts.buffer.try_borrow_mut_data()?, ctx.accounts.header.instance_seed(), &quote)?;
+        verbose_msg!("{:?} quote {} written to {} (nonce {})", asset_type, fixed_to_f64(quote.price), ctx.accounts.buffer.key(), quote.nonce);
+        Ok(())
+    }
+
+    /// Sets the asset's threshold at `index` (below `MAX_THRESHOLDS_PER_ASSET`) to trigger
+    /// when its price moves past `price` in `direction`, untriggered. A zero `price` removes
+    /// it. The threshold applies in the currency the asset is quoted in now.
+    #[access_control(PriceOracle::require_unlocked_asset_role(&ctx.accounts.header, ctx.accounts.authority.key, asset_type, Role::Admin))]
+    pub fn set_threshold(ctx: Context<SetThreshold>, asset_type: AssetType, index: u8, direction: ThresholdDirection, price: u64) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::SetThreshold, Some(asset_type), crate::instruction::SetThreshold { asset_type, index, direction, price },
+        )?;
+        let bump = pda_bump(&ctx.bumps, "thresholds")?;
+        PriceOracle::set_threshold(&mut ctx.accounts.thresholds, &ctx.accounts.data, asset_type, index, direction, price, bump)?;
+        msg!("Threshold {} of {:?} set to {:?} {}", index, asset_type, direction, fixed_to_f64(price));
+        Ok(())
+    }
+
+    /// Resets a triggered threshold so `check_thresholds` reports its next crossing. Allowed
+    /// once the oracle is locked, so flags raised after the lock can still be acknowledged.
+    #[access_control(PriceOracle::require_asset_role(&ctx.accounts.header, ctx.accounts.authority.key, asset_type, Role::Admin))]
+    pub fn clear_threshold(ctx: Context<ClearThreshold>, asset_type: AssetType, index: u8) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::ClearThreshold, Some(asset_type), crate::instruction::ClearThreshold { asset_type, index },
+        )?;
+        if ctx.accounts.thresholds.clear(asset_type, index)? {
+            msg!("Threshold {} of {:?} cleared", index, asset_type);
+        } else {
+            msg!("Threshold {} of {:?} was not triggered", index, asset_type);
+        }
+        Ok(())
+    }
+
+    /// Compares every asset's stored price with its thresholds, triggering and emitting
+    /// `ThresholdCrossed` for each newly crossed one. Anyone may send it.
+    pub fn check_thresholds(ctx: Context<CheckThresholds>) -> Result<()> {
+        let clock = current_clock()?;
+        let crossings = PriceOracle::check_thresholds(&mut ctx.accounts.thresholds, &ctx.accounts.header, &ctx.accounts.data, &clock)?;
+        for crossing in &crossings {
+            emit!(ThresholdCrossed {
+                asset_type: crossing.asset_type,
+                index: crossing.index,
+                direction: crossing.threshold.direction,
+                threshold: crossing.threshold.price,
+                price: crossing.threshold.triggered_price,
+                quote: crossing.threshold.quote,
+                timestamp: clock.unix_timestamp,
+            });
+        }
+        msg!("{} threshold(s) crossed", crossings.len());
+        Ok(())
+    }
+
+    pub fn get_current_apy(ctx: Context<GetApy>, asset_type: AssetType) -> Result<()> {
+        let apy = PriceOracle::get_current_apy(&ctx.accounts.data, asset_type)?;
+        msg!("Current APY for {:?}: {}", asset_type, fixed_to_f64(apy));
+        Ok(())
+    }
+
+    pub fn get_current_apy_bps(ctx: Context<GetApy>, asset_type: AssetType) -> Result<u32> {
+        let apy_bps = PriceOracle::get_current_apy_bps(&ctx.accounts.data, asset_type)?;
+        msg!("Current APY for {:?}: {} bps", asset_type, apy_bps);
+        Ok(apy_bps)
+    }
+
+    #[access_control(PriceOracle::require_emergency_stop_role(&ctx.accounts.header, ctx.accounts.authority.key, stop, force))]
+    pub fn set_emergency_stop(ctx: Context<SetEmergencyStop>, stop: bool, force: bool, reason_code: u8) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::SetEmergencyStop, None, crate::instruction::SetEmergencyStop { stop, force, reason_code },
+        )?;
+        let clock = current_clock()?;
+        let header = &mut ctx.accounts.header;
+
+        let scheduled_clear = header.stop_clear_effective_at;
+        // Only a change that clears the stop ends an incident with a duration
+        let stopped_secs = header.stop_duration(clock.unix_timestamp);
+        let old_state = PriceOracle::set_emergency_stop(header, stop, force, clock.unix_timestamp)?;
+        if !stop && scheduled_clear == 0 && header.stop_clear_effective_at != 0 {
+            emit!(EmergencyStopClearScheduled {
+                actor: ctx.accounts.authority.key(),
+                effective_at: header.stop_clear_effective_at,
+                timestamp: clock.unix_timestamp,
+            });
+            msg!("Emergency stop clears at {} (forced: {}, reason code: {})", header.stop_clear_effective_at, force, reason_code);
+            return Ok(());
+        }
+        if old_state != stop {
+            emit!(EmergencyStopChanged {
+                actor: ctx.accounts.authority.key(),
+                old_state,
+                new_state: stop,
+                forced: force,
+                reason_code,
+                change_count: header.stop_change_count,
+                by_council: PriceOracle::is_council(header, ctx.accounts.authority.key),
+                incident: header.stop_incident,
+                stopped_secs,
+                timestamp: clock.unix_timestamp,
+            });
+        }
+        if old_state && !stop {
+            msg!("Stop incident {} ended after {}s, {}s stopped in total", header.stop_incident, stopped_secs, header.total_stopped_secs);
+        }
+        msg!("Emergency stop set to: {} (forced: {}, reason code: {})", stop, force, reason_code);
+        Ok(())
+    }
+
+    /// Votes to resume from the current stop incident; only `resume_approvers` may sign
+    pub fn approve_resume(ctx: Context<ApproveResume>) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.approver.key,
+            AuditAction::ApproveResume, None, crate::instruction::ApproveResume {},
+        )?;
+        let clock = current_clock()?;
+        let header = &mut ctx.accounts.header;
+        let approvals = PriceOracle::approve_resume(header, ctx.accounts.approver.key)?;
+        emit!(ResumeApproved {
+            approver: ctx.accounts.approver.key(),
+            incident: header.stop_incident,
+            approvals,
+            threshold: header.resume_threshold,
+            timestamp: clock.unix_timestamp,
+        });
+        msg!("Resume from stop incident {} approved ({} of {})", header.stop_incident, approvals, header.resume_threshold);
+        Ok(())
+    }
+
+    /// Clears the stop once its incident has `resume_threshold` approvals; anyone may send it
+    pub fn execute_resume(ctx: Context<ExecuteResume>) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.payer.key,
+            AuditAction::ExecuteResume, None, crate::instruction::ExecuteResume {},
+        )?;
+        let clock = current_clock()?;
+        let header = &mut ctx.accounts.header;
+        let stopped_secs = PriceOracle::execute_resume(header, clock.unix_timestamp)?;
+        emit!(EmergencyStopChanged {
+            actor: ctx.accounts.payer.key(),
+            old_state: true,
+            new_state: false,
+            forced: false,
+            reason_code: 0,
+            change_count: header.stop_change_count,
+            by_council: false,
+            incident: header.stop_incident,
+            stopped_secs,
+            timestamp: clock.unix_timestamp,
+        });
+        msg!("Emergency stop incident {} resumed with {} approvals after {}s", header.stop_incident, header.resume_approval_count(), stopped_secs);
+        Ok(())
+    }
+
+    /// Allowed once locked, like stopping the oracle
+    #[access_control(PriceOracle::require_asset_role(&ctx.accounts.header, ctx.accounts.authority.key, asset_type, Role::Guardian))]
+    pub fn pause_asset(ctx: Context<SetAssetPaused>, asset_type: AssetType) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::PauseAsset, Some(asset_type), crate::instruction::PauseAsset { asset_type },
+        )?;
+        let clock = current_clock()?;
+        let header = &mut ctx.accounts.header;
+        if !PriceOracle::set_asset_paused(header, asset_type, true) {
+            emit!(AssetPauseChanged {
+                actor: ctx.accounts.authority.key(),
+                asset_type,
+                paused: true,
+                by_council: PriceOracle::is_council(header, ctx.accounts.authority.key),
+                timestamp: clock.unix_timestamp,
+            });
+        }
+        msg!("{:?} updates paused", asset_type);
+        Ok(())
+    }
+
+    #[access_control(PriceOracle::require_unlocked_asset_role(&ctx.accounts.header, ctx.accounts.authority.key, asset_type, Role::Admin))]
+    pub fn resume_asset(ctx: Context<SetAssetPaused>, asset_type: AssetType) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::ResumeAsset, Some(asset_type), crate::instruction::ResumeAsset { asset_type },
+        )?;
+        let clock = current_clock()?;
+        if PriceOracle::set_asset_paused(&mut ctx.accounts.header, asset_type, false) {
+            emit!(AssetPauseChanged {
+                actor: ctx.accounts.authority.key(),
+                asset_type,
+                paused: false,
+                by_council: false,
+                timestamp: clock.unix_timestamp,
+            });
+        }
+        msg!("{:?} updates resumed", asset_type);
+        Ok(())
+    }
+
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn set_min_stop_change_interval(ctx: Context<SetEmergencyStop>, interval: i64) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::SetMinStopChangeInterval, None, crate::instruction::SetMinStopChangeInterval { interval },
+        )?;
+        PriceOracle::set_min_stop_change_interval(&mut ctx.accounts.header, interval)?;
+        msg!("Minimum emergency stop change interval set to: {}s", interval);
+        Ok(())
+    }
+
+    /// Pauses or resumes every price update for maintenance. Unlike the emergency stop,
+    /// reads keep serving the stored prices.
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::OperatorOrAdmin))]
+    pub fn set_updates_paused(ctx: Context<SetStalenessConfig>, paused: bool) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::SetUpdatesPaused, None, crate::instruction::SetUpdatesPaused { paused },
+        )?;
+        let clock = current_clock()?;
+        if PriceOracle::set_updates_paused(&mut ctx.accounts.header, paused) != paused {
+            emit!(UpdatesPausedChanged {
+                actor: ctx.accounts.authority.key(),
+                paused,
+                timestamp: clock.unix_timestamp,
+            });
+        }
+        msg!("Updates {}", if paused { "paused" } else { "resumed" });
+        Ok(())
+    }
+
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn set_operator(ctx: Context<SetOperator>, operator: Pubkey) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::SetOperator, None, crate::instruction::SetOperator { operator },
+        )?;
+        PriceOracle::set_operator(&mut ctx.accounts.header, operator);
+        msg!("Operator set to: {}", operator);
+        Ok(())
+    }
+
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn set_emergency_council(ctx: Context<SetOperator>, council: Pubkey) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::SetEmergencyCouncil, None, crate::instruction::SetEmergencyCouncil { council },
+        )?;
+        let clock = current_clock()?;
+        let old_council = PriceOracle::set_emergency_council(&mut ctx.accounts.header, council);
+        emit!(EmergencyCouncilChanged {
+            actor: ctx.accounts.authority.key(),
+            old_council,
+            new_council: council,
+            timestamp: clock.unix_timestamp,
+        });
+        msg!("Emergency council set to: {}", council);
+        Ok(())
+    }
+
+    /// Sets the key whose signed prices `update_price_signed` accepts; `Pubkey::default()`
+    /// disables signed prices again
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn set_trusted_publisher(ctx: Context<SetOperator>, publisher: Pubkey) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::SetTrustedPublisher, None, crate::instruction::SetTrustedPublisher { publisher },
+        )?;
+        let clock = current_clock()?;
+        let old_publisher = PriceOracle::set_trusted_publisher(&mut ctx.accounts.header, publisher);
+        emit!(TrustedPublisherChanged {
+            actor: ctx.accounts.authority.key(),
+            old_publisher,
+            new_publisher: publisher,
+            timestamp: clock.unix_timestamp,
+        });
+        msg!("Trusted publisher set to: {}", publisher);
+        Ok(())
+    }
+
+    /// Sets the queue Switchboard On-Demand feeds must belong to; `Pubkey::default()` refuses
+    /// every On-Demand feed again
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn set_on_demand_queue(ctx: Context<SetOperator>, queue: Pubkey) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::SetOnDemandQueue, None, crate::instruction::SetOnDemandQueue { queue },
+        )?;
+        let clock = current_clock()?;
+        let old_queue = PriceOracle::set_on_demand_queue(&mut ctx.accounts.header, queue);
+        emit!(OnDemandQueueChanged {
+            actor: ctx.accounts.authority.key(),
+            old_queue,
+            new_queue: queue,
+            timestamp: clock.unix_timestamp,
+        });
+        msg!("Switchboard On-Demand queue set to: {}", queue);
+        Ok(())
+    }
+
+    /// Sets the automation thread allowed to sign updates, such as a Clockwork thread PDA
+    /// signing through CPI; `Pubkey::default()` revokes it
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn set_thread_authority(ctx: Context<SetOperator>, thread_authority: Pubkey) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::SetThreadAuthority, None, crate::instruction::SetThreadAuthority { thread_authority },
+        )?;
+        let clock = current_clock()?;
+        let old_thread_authority = PriceOracle::set_thread_authority(&mut ctx.accounts.header, thread_authority);
+        emit!(ThreadAuthorityChanged {
+            actor: ctx.accounts.authority.key(),
+            old_thread_authority,
+            new_thread_authority: thread_authority,
+            timestamp: clock.unix_timestamp,
+        });
+        msg!("Thread authority set to: {}", thread_authority);
+        Ok(())
+    }
+
+    /// Sets the key allowed the asset-scoped admin actions on `asset_type` besides the
+    /// global authority: its feed, thresholds, limits and haircut, and pausing or resuming
+    /// it. `None` revokes it.
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn set_asset_authority(ctx: Context<SetOperator>, asset_type: AssetType, authority: Option<Pubkey>) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::SetAssetAuthority, Some(asset_type), crate::instruction::SetAssetAuthority { asset_type, authority },
+        )?;
+        let clock = current_clock()?;
+        let old_authority = PriceOracle::set_asset_authority(&mut ctx.accounts.header, asset_type, authority);
+        let new_authority = ctx.accounts.header.asset_authority(asset_type);
+        emit!(AssetAuthorityChanged {
+            actor: ctx.accounts.authority.key(),
+            asset_type,
+            old_authority,
+            new_authority,
+            timestamp: clock.unix_timestamp,
+        });
+        msg!("Authority of {:?} set to: {:?}", asset_type, new_authority);
+        Ok(())
+    }
+
+    /// Adds a program to the watcher allowlist, or removes it when `!allowed`. Listed
+    /// programs may stop the oracle through `trigger_emergency_stop_cpi`, never clear it.
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn set_watcher(ctx: Context<SetOperator>, program_id: Pubkey, allowed: bool) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::SetWatcher, None, crate::instruction::SetWatcher { program_id, allowed },
+        )?;
+        let clock = current_clock()?;
+        if PriceOracle::set_watcher(&mut ctx.accounts.header, program_id, allowed)? {
+            emit!(WatchersChanged {
+                actor: ctx.accounts.authority.key(),
+                program_id,
+                allowed,
+                watchers: ctx.accounts.header.watchers,
+                timestamp: clock.unix_timestamp,
+            });
+        }
+        msg!("Watcher {} {}", program_id, if allowed { "allowed" } else { "removed" });
+        Ok(())
+    }
+
+    /// Stops the oracle on behalf of an allowlisted watcher program, which invokes this
+    /// through CPI signing with its `WATCHER_SEED` PDA. Allowed once locked, like any stop.
+    pub fn trigger_emergency_stop_cpi(ctx: Context<TriggerEmergencyStopCpi>, reason_code: u8) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.watcher_authority.key,
+            AuditAction::TriggerEmergencyStopCpi, None, crate::instruction::TriggerEmergencyStopCpi { reason_code },
+        )?;
+        let clock = current_clock()?;
+        let watcher_program = ctx.accounts.watcher_program.key();
+        let header = &mut ctx.accounts.header;
+        let old_state = PriceOracle::trigger_watcher_stop(header, &watcher_program, ctx.accounts.watcher_authority.key, clock.unix_timestamp)?;
+        emit!(WatcherStopTriggered {
+            watcher_program,
+            reason_code,
+            already_stopped: old_state,
+            incident: header.stop_incident,
+            timestamp: clock.unix_timestamp,
+        });
+        if !old_state {
+            emit!(EmergencyStopChanged {
+                actor: watcher_program,
+                old_state,
+                new_state: true,
+                forced: false,
+                reason_code,
+                change_count: header.stop_change_count,
+                by_council: false,
+                incident: header.stop_incident,
+                stopped_secs: 0,
+                timestamp: clock.unix_timestamp,
+            });
+        }
+        msg!("Emergency stop triggered by watcher {} (reason code: {}, already stopped: {})", watcher_program, reason_code, old_state);
+        Ok(())
+    }
+
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn set_switchboard_program_id(ctx: Context<SetSwitchboardProgramId>, new_id: Pubkey) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::SetSwitchboardProgramId, None, crate::instruction::SetSwitchboardProgramId { new_id },
+        )?;
+        require_keys_eq!(ctx.accounts.switchboard_program.key(), new_id, OracleError::InvalidConfig);
+        let clock = current_clock()?;
+        let old_program_id = PriceOracle::set_switchboard_program_id(&mut ctx.accounts.header, &ctx.accounts.switchboard_program)?;
+        emit!(SwitchboardProgramIdChanged {
+            actor: ctx.accounts.authority.key(),
+            old_program_id,
+            new_program_id: new_id,
+            timestamp: clock.unix_timestamp,
+        });
+        msg!("Switchboard program ID changed from {} to {}", old_program_id, new_id);
+        Ok(())
+    }
+
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn lock_oracle(ctx: Context<LockOracle>, confirmation: String) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::LockOracle, None, crate::instruction::LockOracle { confirmation: confirmation.clone() },
+        )?;
+        PriceOracle::lock(&mut ctx.accounts.header, &confirmation)?;
+        msg!("Oracle locked by {}", ctx.accounts.authority.key());
+        Ok(())
+    }
+
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::OperatorOrAdmin))]
+    pub fn set_lease_policy(ctx: Context<SetStalenessConfig>, min_lease_balance: u64, reject_empty_lease: bool) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::SetLeasePolicy, None, crate::instruction::SetLeasePolicy { min_lease_balance, reject_empty_lease },
+        )?;
+        PriceOracle::set_lease_policy(&mut ctx.accounts.header, min_lease_balance, reject_empty_lease);
+        msg!("Lease policy set to: min balance {}, reject empty: {}", min_lease_balance, reject_empty_lease);
+        Ok(())
+    }
+
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn set_large_move_policy(ctx: Context<SetStalenessConfig>, asset_type: AssetType, policy: LargeMovePolicy) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::SetLargeMovePolicy, Some(asset_type), crate::instruction::SetLargeMovePolicy { asset_type, policy },
+        )?;
+        let clock = current_clock()?;
+        let pending = PriceOracle::get_pending_price(&ctx.accounts.header, asset_type);
+        let old_policy = PriceOracle::set_large_move_policy(&mut ctx.accounts.header, asset_type, policy);
+        emit!(LargeMovePolicyChanged {
+            actor: ctx.accounts.authority.key(),
+            asset_type,
+            old_policy,
+            new_policy: policy,
+            timestamp: clock.unix_timestamp,
+        });
+        if pending.is_pending() && policy != LargeMovePolicy::Confirm {
+            emit!(PriceMoveDiscarded {
+                asset_type,
+                candidate_price: pending.price,
+                timestamp: clock.unix_timestamp,
+            });
+        }
+        msg!("Large move policy for {:?} set to {:?}", asset_type, policy);
+        Ok(())
+    }
+
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn set_move_confirmation(ctx: Context<SetStalenessConfig>, delay_secs: u32, tolerance_bps: u32) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::SetMoveConfirmation, None, crate::instruction::SetMoveConfirmation { delay_secs, tolerance_bps },
+        )?;
+        PriceOracle::set_move_confirmation(&mut ctx.accounts.header, delay_secs, tolerance_bps)?;
+        msg!("Move confirmation set to: delay {}s, tolerance {} bps", delay_secs, tolerance_bps);
+        Ok(())
+    }
+
+    /// Sets how long after a resume the change limit stays widened, and by what factor in
+    /// basis points, so the first updates after a long stop can catch up with the market.
+    /// A zero period disables the grace.
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn set_resume_grace(ctx: Context<SetStalenessConfig>, grace_secs: u32, multiplier_bps: u32) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::SetResumeGrace, None, crate::instruction::SetResumeGrace { grace_secs, multiplier_bps },
+        )?;
+        PriceOracle::set_resume_grace(&mut ctx.accounts.header, grace_secs, multiplier_bps)?;
+        msg!("Resume grace set to: {}s at {} bps of the change limit", grace_secs, multiplier_bps);
+        Ok(())
+    }
+
+    #[access_control(PriceOracle::require_unlocked_asset_role(&ctx.accounts.header, ctx.accounts.authority.key, asset_type, Role::OperatorOrAdmin))]
+    pub fn set_apy_change_limit(ctx: Context<SetStalenessConfig>, asset_type: AssetType, limit_bps: u32) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::SetApyChangeLimit, Some(asset_type), crate::instruction::SetApyChangeLimit { asset_type, limit_bps },
+        )?;
+        PriceOracle::set_apy_change_limit(&mut ctx.accounts.header, asset_type, limit_bps);
+        msg!("APY change limit for {:?} set to {} bps", asset_type, limit_bps);
+        Ok(())
+    }
+
+    #[access_control(PriceOracle::require_unlocked_asset_role(&ctx.accounts.header, ctx.accounts.authority.key, asset_type, Role::Admin))]
+    pub fn set_haircut(ctx: Context<SetStalenessConfig>, asset_type: AssetType, haircut_bps: u16) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::SetHaircut, Some(asset_type), crate::instruction::SetHaircut { asset_type, haircut_bps },
+        )?;
+        PriceOracle::set_haircut(&mut ctx.accounts.header, asset_type, haircut_bps)?;
+        msg!("Collateral haircut for {:?} set to {} bps", asset_type, haircut_bps);
+        Ok(())
+    }
+
+    /// Registers the asset's token mint, passed as `mint`, and records the decimals read from
+    /// it for `get_collateral_value`. The mint must be an initialized SPL Token or Token-2022
+    /// mint whose accounts do not start frozen, and not registered for another asset.
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn set_asset_mint(ctx: Context<SetAssetMint>, asset_type: AssetType) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::SetAssetMint, Some(asset_type), crate::instruction::SetAssetMint { asset_type },
+        )?;
+        let clock = current_clock()?;
+        let (old_mint, info) = PriceOracle::set_asset_mint(&mut ctx.accounts.header, asset_type, &ctx.accounts.mint)?;
+        msg!("Mint of {:?} set to {} with {} decimals", asset_type, ctx.accounts.mint.key, info.decimals);
+        emit!(AssetMintSet {
+            actor: ctx.accounts.authority.key(),
+            asset_type,
+            old_mint,
+            mint: ctx.accounts.mint.key(),
+            token_program: info.token_program,
+            decimals: info.decimals,
+            timestamp: clock.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    /// Sets how `update_asset_from_sources` combines the asset's registered sources: their
+    /// median, or the first acceptable one in the order they were registered
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn set_source_policy(ctx: Context<SetStalenessConfig>, asset_type: AssetType, policy: SourcePolicy) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::SetSourcePolicy, Some(asset_type), crate::instruction::SetSourcePolicy { asset_type, policy },
+        )?;
+        let clock = current_clock()?;
+        let old_policy = PriceOracle::set_source_policy(&mut ctx.accounts.header, asset_type, policy);
+        msg!("Source policy of {:?} set to {:?}", asset_type, policy);
+        emit!(SourcePolicySet {
+            actor: ctx.accounts.authority.key(),
+            asset_type,
+            old_policy,
+            policy,
+            timestamp: clock.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    /// Sets the convention the asset's feeds report its yield in. Yields reported as an APR
+    /// are stored as the APY they compound to, `compounding_periods` times a year.
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn set_yield_convention(ctx: Context<SetStalenessConfig>, asset_type: AssetType, config: YieldConfig) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::SetYieldConvention, Some(asset_type), crate::instruction::SetYieldConvention { asset_type, config },
+        )?;
+        let clock = current_clock()?;
+        let old_config = PriceOracle::set_yield_convention(&mut ctx.accounts.header, asset_type, config)?;
+        msg!("Yield convention of {:?} set to {:?}", asset_type, config);
+        emit!(YieldConventionSet {
+            actor: ctx.accounts.authority.key(),
+            asset_type,
+            old_config,
+            config,
+            timestamp: clock.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    /// Sets the weights `get_trust_score` gives freshness, confidence, source and stability,
+    /// in basis points adding up to 10000
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn set_trust_weights(ctx: Context<SetStalenessConfig>, weights: TrustWeights) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::SetTrustWeights, None, crate::instruction::SetTrustWeights { weights },
+        )?;
+        PriceOracle::set_trust_weights(&mut ctx.accounts.header, weights)?;
+        msg!("Trust weights set to {:?}", weights);
+        Ok(())
+    }
+
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn set_partial_updates(ctx: Context<SetStalenessConfig>, enabled: bool) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::SetPartialUpdates, None, crate::instruction::SetPartialUpdates { enabled },
+        )?;
+        PriceOracle::set_partial_updates(&mut ctx.accounts.header, enabled);
+        msg!("Partial updates {}", if enabled { "enabled" } else { "disabled" });
+        Ok(())
+    }
+
+    #[access_control(PriceOracle::require_unlocked_asset_role(&ctx.accounts.header, ctx.accounts.authority.key, asset_type, Role::OperatorOrAdmin))]
+    pub fn set_asset_max_age(ctx: Context<SetStalenessConfig>, asset_type: AssetType, max_age_secs: u32) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::SetAssetMaxAge, Some(asset_type), crate::instruction::SetAssetMaxAge { asset_type, max_age_secs },
+        )?;
+        PriceOracle::set_asset_max_age(&mut ctx.accounts.header, asset_type, max_age_secs)?;
+        msg!("Max age for {:?} set to {}s", asset_type, max_age_secs);
+        Ok(())
+    }
+
+    #[access_control(PriceOracle::require_unlocked_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::OperatorOrAdmin))]
+    pub fn set_max_feed_age_slots(ctx: Context<SetStalenessConfig>, max_feed_age_slots: u64) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::SetMaxFeedAgeSlots, None, crate::instruction::SetMaxFeedAgeSlots { max_feed_age_slots },
+        )?;
+        PriceOracle::set_max_feed_age_slots(&mut ctx.accounts.header, max_feed_age_slots);
+        msg!("Max feed age in slots set to: {}", max_feed_age_slots);
+        Ok(())
+    }
+
+    /// Rewrites the data account in the current layout
+    #[access_control(PriceOracle::require_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn migrate_price_data(ctx: Context<MigratePriceData>) -> Result<()> {
+        let authority = ctx.accounts.authority.to_account_info();
+        let system_program = ctx.accounts.system_program.to_account_info();
+        let migrated = migration::migrate_price_oracle_data(&ctx.accounts.data.to_account_info(), &authority, &system_program)?;
+        if migrated {
+            msg!("Price data migrated to the current layout");
+        } else {
+            msg!("Price data already uses the current layout");
+        }
+        Ok(())
+    }
+
+    /// Recovery for a data account that no longer decodes: rebuilds it in place as
+    /// `initialize` writes it, keeping its bump, and forgets the header's pending and clamped
+    /// moves. Only while the emergency stop is active; the first update of each asset
+    /// afterwards is a first observation. Checks the admin role even once locked, like the
+    /// migrations.
+    #[access_control(PriceOracle::require_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn reinitialize_data(ctx: Context<ReinitializeData>) -> Result<()> {
+        audit(
+            &ctx.accounts.header, &mut ctx.accounts.audit_log, ctx.accounts.authority.key,
+            AuditAction::ReinitializeData, None, crate::instruction::ReinitializeData {},
+        )?;
+        let clock = current_clock()?;
+        let data_generation = PriceOracle::reinitialize_data(&mut ctx.accounts.header)?;
+        let data = ctx.accounts.data.to_account_info();
+        let previous_len = data.data_len() as u64;
+        migration::reinitialize_price_oracle_data(
+            &data,
+            &ctx.accounts.authority.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            pda_bump(&ctx.bumps, "data")?,
+        )?;
+        msg!("PRICE DATA REINITIALIZED: every stored price was wiped, data generation {}", data_generation);
+        emit!(PriceDataReinitialized {
+            actor: ctx.accounts.authority.key(),
+            data_generation,
+            previous_len,
+            timestamp: clock.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    /// Rewrites a header written by an earlier layout in the current one; checks the admin
+    /// itself because the header cannot be decoded until it is migrated
+    pub fn migrate_header(ctx: Context<MigrateHeader>) -> Result<()> {
+        let header = ctx.accounts.header.to_account_info();
+        let authority = migration::header_authority(&header.try_borrow_data()?).map_err(|e| error!(e))?;
+        if authority != ctx.accounts.authority.key() {
+            return Err(error!(OracleError::UnauthorizedAccess));
+        }
+        let migrated = migration::migrate_price_oracle_header(
+            &header,
+            &ctx.accounts.authority.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+        )?;
+        if migrated {
+            msg!("Header migrated to the current layout");
+        } else {
+            msg!("Header already uses the current layout");
+        }
+        Ok(())
+    }
+
+    /// Grows the asset registry, and the price history when passed, written for fewer
+    /// assets so every asset has a slot. Needs the header in the current layout.
+    #[access_control(PriceOracle::require_role(&ctx.accounts.header, ctx.accounts.authority.key, Role::Admin))]
+    pub fn migrate_registry(ctx: Context<MigrateRegistry>) -> Result<()> {
+        let authority = ctx.accounts.authority.to_account_info();
+        let system_program = ctx.accounts.system_program.to_account_info();
+        if migration::migrate_asset_registry(&ctx.accounts.registry.to_account_info(), &authority, &system_program)? {
+            msg!("Registry migrated to the current layout");
+        } else {
+            msg!("Registry already uses the current layout");
+        }
+        if let Some(history) = &ctx.accounts.history {
+            if migration::migrate_price_history(&history.to_account_info(), &authority, &system_program)? {
+                msg!("Price history migrated to the current layout");
+            }
+        }
+        Ok(())
+    }
+
+    /// Receives every instruction outside the IDL. Default builds handle none and fail
+    /// exactly as without a fallback; `mock-oracle` builds route `set_mock_price` through
+    /// here, because Anchor 0.28 cannot leave a `#[program]` instruction out of a build.
+    #[cfg_attr(not(feature = "mock-oracle"), allow(unused_variables))]
+    pub fn fallback(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> Result<()> {
+        #[cfg(feature = "mock-oracle")]
+        if let Some(result) = mock_oracle::dispatch(program_id, accounts, data) {
+            return result;
+        }
+        Err(anchor_lang::error::ErrorCode::InstructionFallbackNotFound.into())
+    }
+}
+
+/// Budget for the inline size of the contexts that load the header and the data account.
+/// `try_accounts` builds them within a single 4 KiB SBF stack frame, so both accounts are
+/// boxed there and only their pointers count against it. Checked at compile time.
+const MAX_BOXED_CONTEXT_SIZE: usize = 1024;
+const _: () = assert!(std::mem::size_of::<Initialize>() <= MAX_BOXED_CONTEXT_SIZE);
+const _: () = assert!(std::mem::size_of::<UpdatePricesAndApys>() <= MAX_BOXED_CONTEXT_SIZE);
+const _: () = assert!(std::mem::size_of::<UpdateSolPrice>() <= MAX_BOXED_CONTEXT_SIZE);
+const _: () = assert!(std::mem::size_of::<UpdateAll>() <= MAX_BOXED_CONTEXT_SIZE);
+const _: () = assert!(std::mem::size_of::<UpdatePriceSigned>() <= MAX_BOXED_CONTEXT_SIZE);
+
+#[derive(Accounts)]
+#[instruction(switchboard_program_id: Pubkey, instance: Pubkey)]
+pub struct Initialize<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + PriceOracleHeader::LEN,
+        seeds = [PriceOracle::HEADER_SEED, PriceOracle::instance_seed(&instance)],
+        bump
+    )]
+    pub header: Box<Account<'info, PriceOracleHeader>>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + PriceOracleData::LEN,
+        seeds = [PriceOracle::DATA_SEED, PriceOracle::instance_seed(&instance)],
+        bump
+    )]
+    pub data: Box<Account<'info, PriceOracleData>>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdatePricesAndApys<'info> {
+    #[account(
+        mut,
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Box<Account<'info, PriceOracleHeader>>,
+    #[account(
+        mut,
+        seeds = [PriceOracle::DATA_SEED, header.instance_seed()],
+        bump = data.bump,
+    )]
+    pub data: Box<Account<'info, PriceOracleData>>,
+    #[account(
+        constraint = oracle_feed.key() == DEVNET_AGGREGATOR_PUBKEY
+    )]
+    /// CHECK: owner checked against `header.switchboard_program_id` when loaded
+    pub oracle_feed: UncheckedAccount<'info>,
+    /// CHECK: validated against the aggregator when supplied together with `lease_escrow`
+    pub lease: Option<UncheckedAccount<'info>>,
+    /// CHECK: must be the lease's escrow token account
+    pub lease_escrow: Option<UncheckedAccount<'info>>,
+    #[account(
+        mut,
+        seeds = [PriceOracle::HISTORY_SEED, header.instance_seed()],
+        bump = history.bump,
+    )]
+    pub history: Option<Box<Account<'info, PriceHistory>>>,
+    /// Samples the APYs the update writes or confirms, once created
+    #[account(
+        mut,
+        seeds = [PriceOracle::APY_HISTORY_SEED, header.instance_seed()],
+        bump = apy_history.bump,
+    )]
+    pub apy_history: Option<Box<Account<'info, ApyHistory>>>,
+    /// Created on the authority's first update that supplies it
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + UpdaterStats::LEN,
+        seeds = [PriceOracle::UPDATER_STATS_SEED, header.instance_seed(), authority.key().as_ref()],
+        bump
+    )]
+    pub updater_stats: Option<Box<Account<'info, UpdaterStats>>>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// Needed with `updater_stats` to create it
+    pub system_program: Option<Program<'info, System>>,
+    /// The registered subscriber, called back after the update when enabled. The subscriber
+    /// program and its registered accounts follow as remaining accounts.
+    #[account(
+        seeds = [PriceOracle::SUBSCRIBER_SEED, header.instance_seed()],
+        bump = subscriber.bump,
+    )]
+    pub subscriber: Option<Box<Account<'info, Subscriber>>>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateFeedShards<'info> {
+    #[account(
+        mut,
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Box<Account<'info, PriceOracleHeader>>,
+    #[account(
+        mut,
+        seeds = [PriceOracle::DATA_SEED, header.instance_seed()],
+        bump = data.bump,
+    )]
+    pub data: Box<Account<'info, PriceOracleData>>,
+    #[account(
+        mut,
+        seeds = [PriceOracle::HISTORY_SEED, header.instance_seed()],
+        bump = history.bump,
+    )]
+    pub history: Option<Box<Account<'info, PriceHistory>>>,
+    /// Samples the APYs the update writes or confirms, once created
+    #[account(
+        mut,
+        seeds = [PriceOracle::APY_HISTORY_SEED, header.instance_seed()],
+        bump = apy_history.bump,
+    )]
+    pub apy_history: Option<Box<Account<'info, ApyHistory>>>,
+    /// Created on the authority's first update that supplies it
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + UpdaterStats::LEN,
+        seeds = [PriceOracle::UPDATER_STATS_SEED, header.instance_seed(), authority.key().as_ref()],
+        bump
+    )]
+    pub updater_stats: Option<Box<Account<'info, UpdaterStats>>>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// Needed with `updater_stats` to create it
+    pub system_program: Option<Program<'info, System>>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateSolPrice<'info> {
+    #[account(
+        mut,
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Box<Account<'info, PriceOracleHeader>>,
+    #[account(
+        mut,
+        seeds = [PriceOracle::DATA_SEED, header.instance_seed()],
+        bump = data.bump,
+    )]
+    pub data: Box<Account<'info, PriceOracleData>>,
+    #[account(
+        constraint = oracle_feed.key() == SOL_PRICE_AGGREGATOR_PUBKEY
+    )]
+    /// CHECK: owner checked against `header.switchboard_program_id` when loaded
+    pub oracle_feed: UncheckedAccount<'info>,
+    /// CHECK: validated against the aggregator's configured history buffer
+    pub history_buffer: Option<UncheckedAccount<'info>>,
+    /// CHECK: validated against the aggregator when supplied together with `lease_escrow`
+    pub lease: Option<UncheckedAccount<'info>>,
+    /// CHECK: must be the lease's escrow token account
+    pub lease_escrow: Option<UncheckedAccount<'info>>,
+    #[account(
+        mut,
+        seeds = [PriceOracle::HISTORY_SEED, header.instance_seed()],
+        bump = history.bump,
+    )]
+    pub history: Option<Box<Account<'info, PriceHistory>>>,
+    /// Created on the authority's first update that supplies it
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + UpdaterStats::LEN,
+        seeds = [PriceOracle::UPDATER_STATS_SEED, header.instance_seed(), authority.key().as_ref()],
+        bump
+    )]
+    pub updater_stats: Option<Box<Account<'info, UpdaterStats>>>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// Needed with `updater_stats` to create it
+    pub system_program: Option<Program<'info, System>>,
+    /// The registered subscriber, called back after the update when enabled. The subscriber
+    /// program and its registered accounts follow as remaining accounts.
+    #[account(
+        seeds = [PriceOracle::SUBSCRIBER_SEED, header.instance_seed()],
+        bump = subscriber.bump,
+    )]
+    pub subscriber: Option<Box<Account<'info, Subscriber>>>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateSolPriceChainlink<'info> {
+    #[account(
+        mut,
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Box<Account<'info, PriceOracleHeader>>,
+    #[account(
+        mut,
+        seeds = [PriceOracle::DATA_SEED, header.instance_seed()],
+        bump = data.bump,
+    )]
+    pub data: Box<Account<'info, PriceOracleData>>,
+    #[account(
+        seeds = [PriceOracle::REGISTRY_SEED, header.instance_seed()],
+        bump = registry.bump,
+    )]
+    pub registry: Box<Account<'info, AssetRegistry>>,
+    /// CHECK: must be a registered Chainlink source of SOL; owner checked when read
+    pub chainlink_feed: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [PriceOracle::HISTORY_SEED, header.instance_seed()],
+        bump = history.bump,
+    )]
+    pub history: Option<Box<Account<'info, PriceHistory>>>,
+    /// Created on the authority's first update that supplies it
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + UpdaterStats::LEN,
+        seeds = [PriceOracle::UPDATER_STATS_SEED, header.instance_seed(), authority.key().as_ref()],
+        bump
+    )]
+    pub updater_stats: Option<Box<Account<'info, UpdaterStats>>>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// Needed with `updater_stats` to create it
+    pub system_program: Option<Program<'info, System>>,
+    /// The registered subscriber, called back after the update when enabled. The subscriber
+    /// program and its registered accounts follow as remaining accounts.
+    #[account(
+        seeds = [PriceOracle::SUBSCRIBER_SEED, header.instance_seed()],
+        bump = subscriber.bump,
+    )]
+    pub subscriber: Option<Box<Account<'info, Subscriber>>>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateAll<'info> {
+    #[account(
+        mut,
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Box<Account<'info, PriceOracleHeader>>,
+    #[account(
+        mut,
+        seeds = [PriceOracle::DATA_SEED, header.instance_seed()],
+        bump = data.bump,
+    )]
+    pub data: Box<Account<'info, PriceOracleData>>,
+    #[account(
+        constraint = oracle_feed.key() == DEVNET_AGGREGATOR_PUBKEY
+    )]
+    /// CHECK: owner checked against `header.switchboard_program_id` when loaded
+    pub oracle_feed: UncheckedAccount<'info>,
+    #[account(
+        constraint = sol_oracle_feed.key() == SOL_PRICE_AGGREGATOR_PUBKEY
+    )]
+    /// CHECK: owner checked against `header.switchboard_program_id` when loaded
+    pub sol_oracle_feed: UncheckedAccount<'info>,
+    /// CHECK: validated against the SOL aggregator's configured history buffer
+    pub sol_history_buffer: Option<UncheckedAccount<'info>>,
+    /// CHECK: validated against the aggregator when supplied together with `lease_escrow`
+    pub lease: Option<UncheckedAccount<'info>>,
+    /// CHECK: must be the lease's escrow token account
+    pub lease_escrow: Option<UncheckedAccount<'info>>,
+    /// CHECK: validated against the SOL aggregator when supplied together with `sol_lease_escrow`
+    pub sol_lease: Option<UncheckedAccount<'info>>,
+    /// CHECK: must be the SOL lease's escrow token account
+    pub sol_lease_escrow: Option<UncheckedAccount<'info>>,
+    #[account(
+        mut,
+        seeds = [PriceOracle::HISTORY_SEED, header.instance_seed()],
+        bump = history.bump,
+    )]
+    pub history: Option<Box<Account<'info, PriceHistory>>>,
+    /// Samples the APYs the update writes or confirms, once created
+    #[account(
+        mut,
+        seeds = [PriceOracle::APY_HISTORY_SEED, header.instance_seed()],
+        bump = apy_history.bump,
+    )]
+    pub apy_history: Option<Box<Account<'info, ApyHistory>>>,
+    /// Created on the authority's first update that supplies it
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + UpdaterStats::LEN,
+        seeds = [PriceOracle::UPDATER_STATS_SEED, header.instance_seed(), authority.key().as_ref()],
+        bump
+    )]
+    pub updater_stats: Option<Box<Account<'info, UpdaterStats>>>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// Needed with `updater_stats` to create it
+    pub system_program: Option<Program<'info, System>>,
+}
+
+#[derive(Accounts)]
+pub struct UpdatePriceSigned<'info> {
+    #[account(
+        mut,
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Box<Account<'info, PriceOracleHeader>>,
+    #[account(
+        mut,
+        seeds = [PriceOracle::DATA_SEED, header.instance_seed()],
+        bump = data.bump,
+    )]
+    pub data: Box<Account<'info, PriceOracleData>>,
+    #[account(
+        mut,
+        seeds = [PriceOracle::HISTORY_SEED, header.instance_seed()],
+        bump = history.bump,
+    )]
+    pub history: Option<Box<Account<'info, PriceHistory>>>,
+    /// Created on the authority's first update that supplies it
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + UpdaterStats::LEN,
+        seeds = [PriceOracle::UPDATER_STATS_SEED, header.instance_seed(), authority.key().as_ref()],
+        bump
+    )]
+    pub updater_stats: Option<Box<Account<'info, UpdaterStats>>>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// Needed with `updater_stats` to create it
+    pub system_program: Option<Program<'info, System>>,
+    #[account(address = sysvar::instructions::ID)]
+    /// CHECK: the instructions sysvar, read for the Ed25519 instruction before this one
+    pub instructions: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct PreviewUpdate<'info> {
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+    #[account(
+        seeds = [PriceOracle::DATA_SEED, header.instance_seed()],
+        bump = data.bump,
+    )]
+    pub data: Account<'info, PriceOracleData>,
+    #[account(
+        constraint = oracle_feed.key() == DEVNET_AGGREGATOR_PUBKEY
+    )]
+    /// CHECK: owner checked against `header.switchboard_program_id` when loaded
+    pub oracle_feed: UncheckedAccount<'info>,
+    #[account(
+        constraint = sol_oracle_feed.key() == SOL_PRICE_AGGREGATOR_PUBKEY
+    )]
+    /// CHECK: owner checked against `header.switchboard_program_id` when loaded
+    pub sol_oracle_feed: UncheckedAccount<'info>,
+    /// CHECK: validated against the SOL aggregator's configured history buffer
+    pub sol_history_buffer: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct SimulatePriceChange<'info> {
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+    #[account(
+        seeds = [PriceOracle::DATA_SEED, header.instance_seed()],
+        bump = data.bump,
+    )]
+    pub data: Account<'info, PriceOracleData>,
+    #[account(
+        seeds = [PriceOracle::REGISTRY_SEED, header.instance_seed()],
+        bump = registry.bump,
+    )]
+    pub registry: Account<'info, AssetRegistry>,
+    /// CHECK: checked against the asset's registered stake pool by the simulation, which
+    /// reports a mismatch as a failed check
+    pub stake_pool: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateAssets<'info> {
+    #[account(
+        mut,
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+    #[account(
+        mut,
+        seeds = [PriceOracle::DATA_SEED, header.instance_seed()],
+        bump = data.bump,
+    )]
+    pub data: Account<'info, PriceOracleData>,
+    #[account(
+        seeds = [PriceOracle::REGISTRY_SEED, header.instance_seed()],
+        bump = registry.bump,
+    )]
+    pub registry: Account<'info, AssetRegistry>,
+    #[account(
+        mut,
+        seeds = [PriceOracle::HISTORY_SEED, header.instance_seed()],
+        bump = history.bump,
+    )]
+    pub history: Option<Box<Account<'info, PriceHistory>>>,
+    /// Created on the authority's first update that supplies it
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + UpdaterStats::LEN,
+        seeds = [PriceOracle::UPDATER_STATS_SEED, header.instance_seed(), authority.key().as_ref()],
+        bump
+    )]
+    pub updater_stats: Option<Box<Account<'info, UpdaterStats>>>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// Needed with `updater_stats` to create it
+    pub system_program: Option<Program<'info, System>>,
+}
+
+#[derive(Accounts)]
+pub struct ForceUpdate<'info> {
+    #[account(
+        mut,
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Box<Account<'info, PriceOracleHeader>>,
+    #[account(
+        mut,
+        seeds = [PriceOracle::DATA_SEED, header.instance_seed()],
+        bump = data.bump,
+    )]
+    pub data: Account<'info, PriceOracleData>,
+    #[account(
+        seeds = [PriceOracle::REGISTRY_SEED, header.instance_seed()],
+        bump = registry.bump,
+    )]
+    pub registry: Account<'info, AssetRegistry>,
+    /// CHECK: checked against the asset's registered feed and read by `force_update`
+    pub feed: UncheckedAccount<'info>,
+    pub authority: Signer<'info>,
+    /// Required once `initialize_audit_log` has run, see `audit_log`
+    #[account(
+        mut,
+        seeds = [PriceOracle::AUDIT_LOG_SEED, header.instance_seed()],
+        bump = audit_log.bump,
+    )]
+    pub audit_log: Option<Box<Account<'info, AuditLog>>>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeRegistry<'info> {
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + AssetRegistry::LEN,
+        seeds = [PriceOracle::REGISTRY_SEED, header.instance_seed()],
+        bump
+    )]
+    pub registry: Account<'info, AssetRegistry>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    /// Required once `initialize_audit_log` has run, see `audit_log`
+    #[account(
+        mut,
+        seeds = [PriceOracle::AUDIT_LOG_SEED, header.instance_seed()],
+        bump = audit_log.bump,
+    )]
+    pub audit_log: Option<Box<Account<'info, AuditLog>>>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeHistory<'info> {
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + PriceHistory::LEN,
+        seeds = [PriceOracle::HISTORY_SEED, header.instance_seed()],
+        bump
+    )]
+    pub history: Box<Account<'info, PriceHistory>>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    /// Required once `initialize_audit_log` has run, see `audit_log`
+    #[account(
+        mut,
+        seeds = [PriceOracle::AUDIT_LOG_SEED, header.instance_seed()],
+        bump = audit_log.bump,
+    )]
+    pub audit_log: Option<Box<Account<'info, AuditLog>>>,
+}
+
+#[derive(Accounts)]
+pub struct SetSubscriber<'info> {
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + Subscriber::LEN,
+        seeds = [PriceOracle::SUBSCRIBER_SEED, header.instance_seed()],
+        bump
+    )]
+    pub subscriber: Account<'info, Subscriber>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    /// Required once `initialize_audit_log` has run, see `audit_log`
+    #[account(
+        mut,
+        seeds = [PriceOracle::AUDIT_LOG_SEED, header.instance_seed()],
+        bump = audit_log.bump,
+    )]
+    pub audit_log: Option<Box<Account<'info, AuditLog>>>,
+}
+
+#[derive(Accounts)]
+pub struct SetAssetFeed<'info> {
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+    #[account(
+        mut,
+        seeds = [PriceOracle::REGISTRY_SEED, header.instance_seed()],
+        bump = registry.bump,
+    )]
+    pub registry: Account<'info, AssetRegistry>,
+    pub authority: Signer<'info>,
+    /// Required once `initialize_audit_log` has run, see `audit_log`
+    #[account(
+        mut,
+        seeds = [PriceOracle::AUDIT_LOG_SEED, header.instance_seed()],
+        bump = audit_log.bump,
+    )]
+    pub audit_log: Option<Box<Account<'info, AuditLog>>>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeAssetFallback<'info> {
+    #[account(
+        mut,
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+    #[account(
+        seeds = [PriceOracle::REGISTRY_SEED, header.instance_seed()],
+        bump = registry.bump,
+    )]
+    pub registry: Account<'info, AssetRegistry>,
+    pub authority: Signer<'info>,
+    /// Required once `initialize_audit_log` has run, see `audit_log`
+    #[account(
+        mut,
+        seeds = [PriceOracle::AUDIT_LOG_SEED, header.instance_seed()],
+        bump = audit_log.bump,
+    )]
+    pub audit_log: Option<Box<Account<'info, AuditLog>>>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeConfig<'info> {
+    #[account(
+        mut,
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+    pub authority: Signer<'info>,
+    /// Required once `initialize_audit_log` has run, see `audit_log`
+    #[account(
+        mut,
+        seeds = [PriceOracle::AUDIT_LOG_SEED, header.instance_seed()],
+        bump = audit_log.bump,
+    )]
+    pub audit_log: Option<Box<Account<'info, AuditLog>>>,
+}
+
+#[derive(Accounts)]
+pub struct ApplyConfig<'info> {
+    #[account(
+        mut,
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+    #[account(
+        mut,
+        seeds = [PriceOracle::REGISTRY_SEED, header.instance_seed()],
+        bump = registry.bump,
+    )]
+    pub registry: Account<'info, AssetRegistry>,
+    pub payer: Signer<'info>,
+    /// Required once `initialize_audit_log` has run, see `audit_log`
+    #[account(
+        mut,
+        seeds = [PriceOracle::AUDIT_LOG_SEED, header.instance_seed()],
+        bump = audit_log.bump,
+    )]
+    pub audit_log: Option<Box<Account<'info, AuditLog>>>,
+}
+
+#[derive(Accounts)]
+pub struct ApplyFeedMapping<'info> {
+    #[account(
+        mut,
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+    pub payer: Signer<'info>,
+    /// Required once `initialize_audit_log` has run, see `audit_log`
+    #[account(
+        mut,
+        seeds = [PriceOracle::AUDIT_LOG_SEED, header.instance_seed()],
+        bump = audit_log.bump,
+    )]
+    pub audit_log: Option<Box<Account<'info, AuditLog>>>,
+}
+
+#[derive(Accounts)]
+pub struct CancelConfig<'info> {
+    #[account(
+        mut,
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+    pub authority: Signer<'info>,
+    /// Required once `initialize_audit_log` has run, see `audit_log`
+    #[account(
+        mut,
+        seeds = [PriceOracle::AUDIT_LOG_SEED, header.instance_seed()],
+        bump = audit_log.bump,
+    )]
+    pub audit_log: Option<Box<Account<'info, AuditLog>>>,
+}
+
+#[derive(Accounts)]
+pub struct SetAssetSource<'info> {
+    #[account(
+        mut,
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+    pub authority: Signer<'info>,
+    /// Required once `initialize_audit_log` has run, see `audit_log`
+    #[account(
+        mut,
+        seeds = [PriceOracle::AUDIT_LOG_SEED, header.instance_seed()],
+        bump = audit_log.bump,
+    )]
+    pub audit_log: Option<Box<Account<'info, AuditLog>>>,
+}
+
+#[derive(Accounts)]
+pub struct SetAssetQuote<'info> {
+    #[account(
+        mut,
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+    #[account(
+        mut,
+        seeds = [PriceOracle::DATA_SEED, header.instance_seed()],
+        bump = data.bump,
+    )]
+    pub data: Account<'info, PriceOracleData>,
+    pub authority: Signer<'info>,
+    /// Required once `initialize_audit_log` has run, see `audit_log`
+    #[account(
+        mut,
+        seeds = [PriceOracle::AUDIT_LOG_SEED, header.instance_seed()],
+        bump = audit_log.bump,
+    )]
+    pub audit_log: Option<Box<Account<'info, AuditLog>>>,
+}
+
+#[derive(Accounts)]
+pub struct GetPrice<'info> {
+    #[account(
+        seeds = [PriceOracle::DATA_SEED, PriceOracle::optional_instance_seed(&header)],
+        bump = data.bump,
+    )]
+    pub data: Account<'info, PriceOracleData>,
+    /// Header of the oracle instance the accounts belong to; the default instance when omitted
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Option<Box<Account<'info, PriceOracleHeader>>>,
+}
+
+#[derive(Accounts)]
+pub struct GetPriceStrict<'info> {
+    #[account(
+        seeds = [PriceOracle::DATA_SEED, PriceOracle::optional_instance_seed(&header)],
+        bump = data.bump,
+    )]
+    pub data: Account<'info, PriceOracleData>,
+    #[account(address = sysvar::instructions::ID)]
+    /// CHECK: the instructions sysvar; supplying it rejects a read after a price update in the same transaction
+    pub instructions: Option<UncheckedAccount<'info>>,
+    /// Header of the oracle instance the accounts belong to; the default instance when omitted
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Option<Box<Account<'info, PriceOracleHeader>>>,
+}
+
+#[derive(Accounts)]
+pub struct AssertPriceFresh<'info> {
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+    #[account(
+        seeds = [PriceOracle::DATA_SEED, header.instance_seed()],
+        bump = data.bump,
+    )]
+    pub data: Account<'info, PriceOracleData>,
+    #[account(address = sysvar::instructions::ID)]
+    /// CHECK: the instructions sysvar; supplying it rejects a read after a price update in the same transaction
+    pub instructions: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct GetAllPrices<'info> {
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+    #[account(
+        seeds = [PriceOracle::DATA_SEED, header.instance_seed()],
+        bump = data.bump,
+    )]
+    pub data: Account<'info, PriceOracleData>,
+}
+
+#[derive(Accounts)]
+pub struct GetPremium<'info> {
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+    #[account(
+        seeds = [PriceOracle::REGISTRY_SEED, header.instance_seed()],
+        bump = registry.bump,
+    )]
+    pub registry: Account<'info, AssetRegistry>,
+}
+
+/// Unlike `GetAllPrices`, the accounts are not checked against their seeds here: a bump or
+/// address that does not match is one of the violations `verify_invariants` reports
+#[derive(Accounts)]
+pub struct VerifyInvariants<'info> {
+    pub header: Account<'info, PriceOracleHeader>,
+    pub data: Account<'info, PriceOracleData>,
+}
+
+#[derive(Accounts)]
+#[instruction(updater: Pubkey)]
+pub struct GetUpdaterStats<'info> {
+    #[account(
+        seeds = [PriceOracle::UPDATER_STATS_SEED, PriceOracle::optional_instance_seed(&header), updater.as_ref()],
+        bump = stats.bump,
+    )]
+    pub stats: Account<'info, UpdaterStats>,
+    /// Header of the oracle instance the accounts belong to; the default instance when omitted
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Option<Box<Account<'info, PriceOracleHeader>>>,
+}
+
+#[derive(Accounts)]
+pub struct GetOracleStats<'info> {
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+}
+
+#[derive(Accounts)]
+pub struct GetVolatility<'info> {
+    #[account(
+        seeds = [PriceOracle::HISTORY_SEED, PriceOracle::optional_instance_seed(&header)],
+        bump = history.bump,
+    )]
+    pub history: Box<Account<'info, PriceHistory>>,
+    /// Header of the oracle instance the accounts belong to; the default instance when omitted
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Option<Box<Account<'info, PriceOracleHeader>>>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeApyHistory<'info> {
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + ApyHistory::LEN,
+        seeds = [PriceOracle::APY_HISTORY_SEED, header.instance_seed()],
+        bump
+    )]
+    pub apy_history: Box<Account<'info, ApyHistory>>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    /// Required once `initialize_audit_log` has run, see `audit_log`
+    #[account(
+        mut,
+        seeds = [PriceOracle::AUDIT_LOG_SEED, header.instance_seed()],
+        bump = audit_log.bump,
+    )]
+    pub audit_log: Option<Box<Account<'info, AuditLog>>>,
+}
+
+#[derive(Accounts)]
+pub struct SetMinApySamples<'info> {
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+    #[account(
+        mut,
+        seeds = [PriceOracle::APY_HISTORY_SEED, header.instance_seed()],
+        bump = apy_history.bump,
+    )]
+    pub apy_history: Box<Account<'info, ApyHistory>>,
+    pub authority: Signer<'info>,
+    /// Required once `initialize_audit_log` has run, see `audit_log`
+    #[account(
+        mut,
+        seeds = [PriceOracle::AUDIT_LOG_SEED, header.instance_seed()],
+        bump = audit_log.bump,
+    )]
+    pub audit_log: Option<Box<Account<'info, AuditLog>>>,
+}
+
+#[derive(Accounts)]
+pub struct GetAverageApy<'info> {
+    #[account(
+        seeds = [PriceOracle::APY_HISTORY_SEED, PriceOracle::optional_instance_seed(&header)],
+        bump = apy_history.bump,
+    )]
+    pub apy_history: Box<Account<'info, ApyHistory>>,
+    /// Header of the oracle instance the accounts belong to; the default instance when omitted
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Option<Box<Account<'info, PriceOracleHeader>>>,
+}
+
+#[derive(Accounts)]
+#[instruction(asset_type: AssetType)]
+pub struct SnapshotEpoch<'info> {
+    #[account(
+        seeds = [PriceOracle::DATA_SEED, PriceOracle::optional_instance_seed(&header)],
+        bump = data.bump,
+    )]
+    pub data: Account<'info, PriceOracleData>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + EpochSnapshots::LEN,
+        seeds = [PriceOracle::EPOCH_SNAPSHOTS_SEED, PriceOracle::optional_instance_seed(&header), &[u8::from(asset_type)]],
+        bump
+    )]
+    pub snapshots: Box<Account<'info, EpochSnapshots>>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    /// Header of the oracle instance the accounts belong to; the default instance when omitted
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Option<Box<Account<'info, PriceOracleHeader>>>,
+}
+
+#[derive(Accounts)]
+pub struct PruneHistory<'info> {
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+    #[account(
+        mut,
+        seeds = [PriceOracle::HISTORY_SEED, header.instance_seed()],
+        bump = history.bump,
+    )]
+    pub history: Option<Box<Account<'info, PriceHistory>>>,
+    #[account(
+        mut,
+        seeds = [PriceOracle::APY_HISTORY_SEED, header.instance_seed()],
+        bump = apy_history.bump,
+    )]
+    pub apy_history: Option<Box<Account<'info, ApyHistory>>>,
+    pub authority: Signer<'info>,
+    /// Required once `initialize_audit_log` has run, see `audit_log`
+    #[account(
+        mut,
+        seeds = [PriceOracle::AUDIT_LOG_SEED, header.instance_seed()],
+        bump = audit_log.bump,
+    )]
+    pub audit_log: Option<Box<Account<'info, AuditLog>>>,
+}
+
+#[derive(Accounts)]
+#[instruction(asset_type: AssetType)]
+pub struct PruneEpochSnapshots<'info> {
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+    #[account(
+        mut,
+        seeds = [PriceOracle::EPOCH_SNAPSHOTS_SEED, header.instance_seed(), &[u8::from(asset_type)]],
+        bump = snapshots.bump,
+    )]
+    pub snapshots: Box<Account<'info, EpochSnapshots>>,
+    pub authority: Signer<'info>,
+    /// Required once `initialize_audit_log` has run, see `audit_log`
+    #[account(
+        mut,
+        seeds = [PriceOracle::AUDIT_LOG_SEED, header.instance_seed()],
+        bump = audit_log.bump,
+    )]
+    pub audit_log: Option<Box<Account<'info, AuditLog>>>,
+}
+
+#[derive(Accounts)]
+#[instruction(asset_type: AssetType)]
+pub struct GetEpochPrice<'info> {
+    #[account(
+        seeds = [PriceOracle::EPOCH_SNAPSHOTS_SEED, PriceOracle::optional_instance_seed(&header), &[u8::from(asset_type)]],
+        bump = snapshots.bump,
+    )]
+    pub snapshots: Box<Account<'info, EpochSnapshots>>,
+    /// Header of the oracle instance the accounts belong to; the default instance when omitted
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Option<Box<Account<'info, PriceOracleHeader>>>,
+}
+
+#[derive(Accounts)]
+pub struct GetApy<'info> {
+    #[account(
+        seeds = [PriceOracle::DATA_SEED, PriceOracle::optional_instance_seed(&header)],
+        bump = data.bump,
+    )]
+    pub data: Account<'info, PriceOracleData>,
+    /// Header of the oracle instance the accounts belong to; the default instance when omitted
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Option<Box<Account<'info, PriceOracleHeader>>>,
+}
+
+#[derive(Accounts)]
+pub struct SetEmergencyStop<'info> {
+    #[account(
+        mut,
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+    pub authority: Signer<'info>,
+    /// Required once `initialize_audit_log` has run, see `audit_log`
+    #[account(
+        mut,
+        seeds = [PriceOracle::AUDIT_LOG_SEED, header.instance_seed()],
+        bump = audit_log.bump,
+    )]
+    pub audit_log: Option<Box<Account<'info, AuditLog>>>,
+}
+
+#[derive(Accounts)]
+pub struct TriggerEmergencyStopCpi<'info> {
+    #[account(
+        mut,
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+    /// CHECK: only its key is read, checked against the watcher allowlist
+    pub watcher_program: UncheckedAccount<'info>,
+    /// The watcher program's `WATCHER_SEED` PDA, which only it can sign for
+    pub watcher_authority: Signer<'info>,
+    /// Required once `initialize_audit_log` has run, see `audit_log`
+    #[account(
+        mut,
+        seeds = [PriceOracle::AUDIT_LOG_SEED, header.instance_seed()],
+        bump = audit_log.bump,
+    )]
+    pub audit_log: Option<Box<Account<'info, AuditLog>>>,
+}
+
+#[derive(Accounts)]
+pub struct SetOperator<'info> {
+    #[account(
+        mut,
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+    pub authority: Signer<'info>,
+    /// Required once `initialize_audit_log` has run, see `audit_log`
+    #[account(
+        mut,
+        seeds = [PriceOracle::AUDIT_LOG_SEED, header.instance_seed()],
+        bump = audit_log.bump,
+    )]
+    pub audit_log: Option<Box<Account<'info, AuditLog>>>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveResume<'info> {
+    #[account(
+        mut,
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+    pub approver: Signer<'info>,
+    /// Required once `initialize_audit_log` has run, see `audit_log`
+    #[account(
+        mut,
+        seeds = [PriceOracle::AUDIT_LOG_SEED, header.instance_seed()],
+        bump = audit_log.bump,
+    )]
+    pub audit_log: Option<Box<Account<'info, AuditLog>>>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteResume<'info> {
+    #[account(
+        mut,
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+    pub payer: Signer<'info>,
+    /// Required once `initialize_audit_log` has run, see `audit_log`
+    #[account(
+        mut,
+        seeds = [PriceOracle::AUDIT_LOG_SEED, header.instance_seed()],
+        bump = audit_log.bump,
+    )]
+    pub audit_log: Option<Box<Account<'info, AuditLog>>>,
+}
+
+#[derive(Accounts)]
+pub struct SetAssetPaused<'info> {
+    #[account(
+        mut,
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+    pub authority: Signer<'info>,
+    /// Required once `initialize_audit_log` has run, see `audit_log`
+    #[account(
+        mut,
+        seeds = [PriceOracle::AUDIT_LOG_SEED, header.instance_seed()],
+        bump = audit_log.bump,
+    )]
+    pub audit_log: Option<Box<Account<'info, AuditLog>>>,
+}
+
+#[derive(Accounts)]
+pub struct SetSwitchboardProgramId<'info> {
+    #[account(
+        mut,
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+    /// CHECK: must be the executable account of the new program id
+    pub switchboard_program: UncheckedAccount<'info>,
+    pub authority: Signer<'info>,
+    /// Required once `initialize_audit_log` has run, see `audit_log`
+    #[account(
+        mut,
+        seeds = [PriceOracle::AUDIT_LOG_SEED, header.instance_seed()],
+        bump = audit_log.bump,
+    )]
+    pub audit_log: Option<Box<Account<'info, AuditLog>>>,
+}
+
+#[derive(Accounts)]
+pub struct LockOracle<'info> {
+    #[account(
+        mut,
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+    pub authority: Signer<'info>,
+    /// Required once `initialize_audit_log` has run, see `audit_log`
+    #[account(
+        mut,
+        seeds = [PriceOracle::AUDIT_LOG_SEED, header.instance_seed()],
+        bump = audit_log.bump,
+    )]
+    pub audit_log: Option<Box<Account<'info, AuditLog>>>,
+}
+
+#[derive(Accounts)]
+pub struct SetStalenessConfig<'info> {
+    #[account(
+        mut,
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+    pub authority: Signer<'info>,
+    /// Required once `initialize_audit_log` has run, see `audit_log`
+    #[account(
+        mut,
+        seeds = [PriceOracle::AUDIT_LOG_SEED, header.instance_seed()],
+        bump = audit_log.bump,
+    )]
+    pub audit_log: Option<Box<Account<'info, AuditLog>>>,
+}
 
-        let clock = Clock::get().unwrap();
+#[derive(Accounts)]
+pub struct SetAssetMint<'info> {
+    #[account(
+        mut,
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+    /// CHECK: owner and layout checked by `read_mint`
+    pub mint: UncheckedAccount<'info>,
+    pub authority: Signer<'info>,
+    /// Required once `initialize_audit_log` has run, see `audit_log`
+    #[account(
+        mut,
+        seeds = [PriceOracle::AUDIT_LOG_SEED, header.instance_seed()],
+        bump = audit_log.bump,
+    )]
+    pub audit_log: Option<Box<Account<'info, AuditLog>>>,
+}
 
-        // Validate Switchboard program ID
-        if ctx.accounts.oracle_feed.to_account_info().owner != &ctx.accounts.header.switchboard_program_id {
-            msg!("Invalid Switchboard account owner: expected {}, found {}", 
-                ctx.accounts.header.switchboard_program_id, 
-                ctx.accounts.oracle_feed.to_account_info().owner);
-            return Err(error!(OracleError::InvalidSwitchboardAccount));
-        }
+#[derive(Accounts)]
+pub struct AttestPrices<'info> {
+    #[account(
+        mut,
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Box<Account<'info, PriceOracleHeader>>,
+    #[account(
+        seeds = [PriceOracle::DATA_SEED, header.instance_seed()],
+        bump = data.bump,
+    )]
+    pub data: Box<Account<'info, PriceOracleData>>,
+    pub authority: Signer<'info>,
+}
 
-        PriceOracle::update_sol_price(
-            &mut ctx.accounts.header,
-            &mut ctx.accounts.data,
-            &ctx.accounts.oracle_feed,
-            &clock,
-        )?;
+#[derive(Accounts)]
+pub struct KeeperHeartbeat<'info> {
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+    pub authority: Signer<'info>,
+}
 
-        msg!("SOL price updated successfully");
-        sol_log_compute_units();
-        Ok(())
-    }
+#[derive(Accounts)]
+pub struct MigratePriceData<'info> {
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+    /// CHECK: may still hold a legacy layout; decoded and rewritten by `migrate_price_oracle_data`
+    #[account(
+        mut,
+        seeds = [PriceOracle::DATA_SEED, header.instance_seed()],
+        bump,
+    )]
+    pub data: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
 
-    pub fn get_current_price(ctx: Context<GetPrice>, asset_type: AssetType) -> Result<()> {
-        let price = PriceOracle::get_current_price(&ctx.accounts.data, asset_type)?;
-        msg!("Current price for {:?}: {}", asset_type, price);
-        Ok(())
-    }
+#[derive(Accounts)]
+pub struct ReinitializeData<'info> {
+    #[account(
+        mut,
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Box<Account<'info, PriceOracleHeader>>,
+    /// CHECK: may hold anything; overwritten by `reinitialize_price_oracle_data`
+    #[account(
+        mut,
+        seeds = [PriceOracle::DATA_SEED, header.instance_seed()],
+        bump,
+    )]
+    pub data: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    /// Required once `initialize_audit_log` has run, see `audit_log`
+    #[account(
+        mut,
+        seeds = [PriceOracle::AUDIT_LOG_SEED, header.instance_seed()],
+        bump = audit_log.bump,
+    )]
+    pub audit_log: Option<Box<Account<'info, AuditLog>>>,
+}
 
-    pub fn get_current_apy(ctx: Context<GetApy>, asset_type: AssetType) -> Result<()> {
-        let apy = PriceOracle::get_current_apy(&ctx.accounts.data, asset_type)?;
-        msg!("Current APY for {:?}: {}", asset_type, apy);
-        Ok(())
-    }
+#[derive(Accounts)]
+pub struct MigrateHeader<'info> {
+    /// CHECK: may still hold a legacy layout; its admin is read and it is rewritten by `migrate_price_oracle_header`
+    #[account(
+        mut,
+        seeds = [PriceOracle::HEADER_SEED],
+        bump,
+    )]
+    pub header: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
 
-    pub fn set_emergency_stop(ctx: Context<SetEmergencyStop>, stop: bool) -> Result<()> {
-        PriceOracle::set_emergency_stop(&mut ctx.accounts.header, stop);
-        msg!("Emergency stop set to: {}", stop);
-        Ok(())
-    }
+#[derive(Accounts)]
+pub struct MigrateRegistry<'info> {
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+    /// CHECK: may still hold a legacy layout; checked and grown by `migrate_asset_registry`
+    #[account(
+        mut,
+        seeds = [PriceOracle::REGISTRY_SEED, header.instance_seed()],
+        bump,
+    )]
+    pub registry: UncheckedAccount<'info>,
+    /// CHECK: may still hold a legacy layout; checked and grown by `migrate_price_history`
+    #[account(
+        mut,
+        seeds = [PriceOracle::HISTORY_SEED, header.instance_seed()],
+        bump,
+    )]
+    pub history: Option<UncheckedAccount<'info>>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct Initialize<'info> {
+pub struct InitializeAuditLog<'info> {
     #[account(
-        init,
-        payer = authority,
-        space = 8 + std::mem::size_of::<PriceOracleHeader>(),
-        seeds = [PriceOracle::HEADER_SEED],
-        bump
+        mut,
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
     )]
     pub header: Account<'info, PriceOracleHeader>,
     #[account(
         init,
         payer = authority,
-        space = 8 + std::mem::size_of::<PriceOracleData>(),
-        seeds = [PriceOracle::DATA_SEED],
+        space = 8 + AuditLog::LEN,
+        seeds = [PriceOracle::AUDIT_LOG_SEED, header.instance_seed()],
         bump
     )]
-    pub data: Account<'info, PriceOracleData>,
+    pub audit_log: Box<Account<'info, AuditLog>>,
     #[account(mut)]
     pub authority: Signer<'info>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct UpdatePricesAndApys<'info> {
+pub struct GetAuditLog<'info> {
     #[account(
-        mut,
-        seeds = [PriceOracle::HEADER_SEED],
+        seeds = [PriceOracle::AUDIT_LOG_SEED, PriceOracle::optional_instance_seed(&header)],
+        bump = audit_log.bump,
+    )]
+    pub audit_log: Box<Account<'info, AuditLog>>,
+    /// Header of the oracle instance the accounts belong to; the default instance when omitted
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
         bump = header.bump,
     )]
-    pub header: Account<'info, PriceOracleHeader>,
+    pub header: Option<Box<Account<'info, PriceOracleHeader>>>,
+}
+
+#[derive(Accounts)]
+#[instruction(id: u64)]
+pub struct CreateCheckpoint<'info> {
     #[account(
-        mut,
-        seeds = [PriceOracle::DATA_SEED],
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Box<Account<'info, PriceOracleHeader>>,
+    #[account(
+        seeds = [PriceOracle::DATA_SEED, header.instance_seed()],
         bump = data.bump,
     )]
-    pub data: Account<'info, PriceOracleData>,
+    pub data: Box<Account<'info, PriceOracleData>>,
     #[account(
-        constraint = oracle_feed.key() == DEVNET_AGGREGATOR_PUBKEY.parse::<Pubkey>().unwrap()
+        init,
+        payer = payer,
+        space = 8 + Checkpoint::LEN,
+        seeds = [PriceOracle::CHECKPOINT_SEED, header.instance_seed(), &id.to_le_bytes()],
+        bump
     )]
-    pub oracle_feed: AccountLoader<'info, AggregatorAccountData>,
-    #[account(constraint = authority.key() == header.authority @ OracleError::UnauthorizedAccess)]
-    pub authority: Signer<'info>,
+    pub checkpoint: Box<Account<'info, Checkpoint>>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct UpdateSolPrice<'info> {
+#[instruction(id: u64)]
+pub struct GetCheckpoint<'info> {
+    #[account(
+        seeds = [PriceOracle::CHECKPOINT_SEED, PriceOracle::optional_instance_seed(&header), &id.to_le_bytes()],
+        bump = checkpoint.bump,
+    )]
+    pub checkpoint: Box<Account<'info, Checkpoint>>,
+    /// Header of the oracle instance the accounts belong to; the default instance when omitted
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Option<Box<Account<'info, PriceOracleHeader>>>,
+}
+
+#[derive(Accounts)]
+pub struct SetThreshold<'info> {
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Box<Account<'info, PriceOracleHeader>>,
+    #[account(
+        seeds = [PriceOracle::DATA_SEED, header.instance_seed()],
+        bump = data.bump,
+    )]
+    pub data: Box<Account<'info, PriceOracleData>>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + PriceThresholds::LEN,
+        seeds = [PriceOracle::THRESHOLDS_SEED, header.instance_seed()],
+        bump
+    )]
+    pub thresholds: Box<Account<'info, PriceThresholds>>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    /// Required once `initialize_audit_log` has run, see `audit_log`
     #[account(
         mut,
-        seeds = [PriceOracle::HEADER_SEED],
+        seeds = [PriceOracle::AUDIT_LOG_SEED, header.instance_seed()],
+        bump = audit_log.bump,
+    )]
+    pub audit_log: Option<Box<Account<'info, AuditLog>>>,
+}
+
+#[derive(Accounts)]
+pub struct ClearThreshold<'info> {
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
         bump = header.bump,
     )]
-    pub header: Account<'info, PriceOracleHeader>,
+    pub header: Box<Account<'info, PriceOracleHeader>>,
+    #[account(
+        mut,
+        seeds = [PriceOracle::THRESHOLDS_SEED, header.instance_seed()],
+        bump = thresholds.bump,
+    )]
+    pub thresholds: Box<Account<'info, PriceThresholds>>,
+    pub authority: Signer<'info>,
+    /// Required once `initialize_audit_log` has run, see `audit_log`
     #[account(
         mut,
-        seeds = [PriceOracle::DATA_SEED],
+        seeds = [PriceOracle::AUDIT_LOG_SEED, header.instance_seed()],
+        bump = audit_log.bump,
+    )]
+    pub audit_log: Option<Box<Account<'info, AuditLog>>>,
+}
+
+#[derive(Accounts)]
+pub struct CheckThresholds<'info> {
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Box<Account<'info, PriceOracleHeader>>,
+    #[account(
+        seeds = [PriceOracle::DATA_SEED, header.instance_seed()],
         bump = data.bump,
     )]
-    pub data: Account<'info, PriceOracleData>,
+    pub data: Box<Account<'info, PriceOracleData>>,
+    #[account(
+        mut,
+        seeds = [PriceOracle::THRESHOLDS_SEED, header.instance_seed()],
+        bump = thresholds.bump,
+    )]
+    pub thresholds: Box<Account<'info, PriceThresholds>>,
+}
+
+#[derive(Accounts)]
+#[instruction(id: u64)]
+pub struct CloseCheckpoint<'info> {
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Box<Account<'info, PriceOracleHeader>>,
     #[account(
-        constraint = oracle_feed.key() == SOL_PRICE_AGGREGATOR_PUBKEY.parse::<Pubkey>().unwrap()
+        mut,
+        seeds = [PriceOracle::CHECKPOINT_SEED, header.instance_seed(), &id.to_le_bytes()],
+        bump = checkpoint.bump,
+        has_one = creator,
+        close = creator,
     )]
-    pub oracle_feed: AccountLoader<'info, AggregatorAccountData>,
-    #[account(constraint = authority.key() == header.authority @ OracleError::UnauthorizedAccess)]
+    pub checkpoint: Box<Account<'info, Checkpoint>>,
+    /// CHECK: only receives the rent; must be the checkpoint's creator
+    #[account(mut)]
+    pub creator: UncheckedAccount<'info>,
     pub authority: Signer<'info>,
+    /// Required once `initialize_audit_log` has run, see `audit_log`
+    #[account(
+        mut,
+        seeds = [PriceOracle::AUDIT_LOG_SEED, header.instance_seed()],
+        bump = audit_log.bump,
+    )]
+    pub audit_log: Option<Box<Account<'info, AuditLog>>>,
 }
 
 #[derive(Accounts)]
-pub struct GetPrice<'info> {
+#[instruction(id: u64)]
+pub struct CreateBasket<'info> {
     #[account(
-        seeds = [PriceOracle::DATA_SEED],
+        seeds = [PriceOracle::DATA_SEED, PriceOracle::optional_instance_seed(&header)],
         bump = data.bump,
     )]
-    pub data: Account<'info, PriceOracleData>,
+    pub data: Box<Account<'info, PriceOracleData>>,
+    #[account(
+        init,
+        payer = creator,
+        space = 8 + Basket::LEN,
+        seeds = [PriceOracle::BASKET_SEED, creator.key().as_ref(), &id.to_le_bytes()],
+        bump
+    )]
+    pub basket: Box<Account<'info, Basket>>,
+    #[account(mut)]
+    pub creator: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    /// Header of the oracle instance the accounts belong to; the default instance when omitted
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Option<Box<Account<'info, PriceOracleHeader>>>,
 }
 
 #[derive(Accounts)]
-pub struct GetApy<'info> {
+#[instruction(id: u64)]
+pub struct UpdateBasketWeights<'info> {
     #[account(
-        seeds = [PriceOracle::DATA_SEED],
+        seeds = [PriceOracle::DATA_SEED, PriceOracle::optional_instance_seed(&header)],
         bump = data.bump,
     )]
-    pub data: Account<'info, PriceOracleData>,
+    pub data: Box<Account<'info, PriceOracleData>>,
+    #[account(
+        mut,
+        seeds = [PriceOracle::BASKET_SEED, basket.creator.as_ref(), &id.to_le_bytes()],
+        bump = basket.bump,
+        has_one = creator @ OracleError::UnauthorizedAccess,
+    )]
+    pub basket: Box<Account<'info, Basket>>,
+    pub creator: Signer<'info>,
+    /// Header of the oracle instance the accounts belong to; the default instance when omitted
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Option<Box<Account<'info, PriceOracleHeader>>>,
 }
 
 #[derive(Accounts)]
-pub struct SetEmergencyStop<'info> {
+#[instruction(id: u64)]
+pub struct CloseBasket<'info> {
     #[account(
         mut,
-        seeds = [PriceOracle::HEADER_SEED],
-        bump = header.bump,
+        seeds = [PriceOracle::BASKET_SEED, basket.creator.as_ref(), &id.to_le_bytes()],
+        bump = basket.bump,
+        has_one = creator @ OracleError::UnauthorizedAccess,
+        close = creator,
     )]
-    pub header: Account<'info, PriceOracleHeader>,
-    #[account(constraint = authority.key() == header.authority @ OracleError::UnauthorizedAccess)]
+    pub basket: Box<Account<'info, Basket>>,
+    #[account(mut)]
+    pub creator: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(id: u64, space: u32)]
+pub struct CreateQuoteBuffer<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = space as usize,
+        seeds = [PriceOracle::QUOTE_BUFFER_SEED, PriceOracle::optional_instance_seed(&header), authority.key().as_ref(), &id.to_le_bytes()],
+        bump
+    )]
+    pub buffer: Box<Account<'info, QuoteBuffer>>,
+    #[account(mut)]
     pub authority: Signer<'info>,
-}
\ No newline at end of file
+    pub system_program: Program<'info, System>,
+    /// Header of the oracle instance the accounts belong to; the default instance when omitted
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Option<Box<Account<'info, PriceOracleHeader>>>,
+}
+
+#[derive(Accounts)]
+pub struct WriteQuoteTo<'info> {
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Box<Account<'info, PriceOracleHeader>>,
+    #[account(
+        seeds = [PriceOracle::DATA_SEED, header.instance_seed()],
+        bump = data.bump,
+    )]
+    pub data: Box<Account<'info, PriceOracleData>>,
+    /// CHECK: any quote buffer of the instance; written raw at its offset, so the consumer's
+    /// bytes around the quote are kept. `QuoteBuffer::write_quote` checks its discriminator,
+    /// address and size.
+    #[account(mut, owner = crate::ID)]
+    pub buffer: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct GetBasketPrice<'info> {
+    #[account(
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Box<Account<'info, PriceOracleHeader>>,
+    #[account(
+        seeds = [PriceOracle::DATA_SEED, header.instance_seed()],
+        bump = data.bump,
+    )]
+    pub data: Box<Account<'info, PriceOracleData>>,
+    /// Any creator's basket
+    pub basket: Box<Account<'info, Basket>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handler_helpers_fail_with_typed_errors() {
+        // Off-chain there is no clock sysvar to read
+        assert_eq!(current_clock().unwrap_err(), error!(OracleError::ClockUnavailable));
+
+        let mut bumps = BTreeMap::new();
+        bumps.insert("header".to_string(), 254);
+        assert_eq!(pda_bump(&bumps, "header").unwrap(), 254);
+        assert_eq!(pda_bump(&bumps, "data").unwrap_err(), error!(OracleError::MissingBump));
+    }
+}
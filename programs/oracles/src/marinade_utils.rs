@@ -0,0 +1,134 @@
+//! Reads the mSOL/SOL exchange rate straight from Marinade's `State` account.
+//!
+//! Layout (after the 8-byte Anchor discriminator): the mint and authority keys, the stake,
+//! validator and liquidity pool subsystems, then `available_reserve_balance`, `msol_supply`
+//! and `msol_price`, the SOL value of one mSOL scaled by `MSOL_PRICE_DENOMINATOR`. Only
+//! that prefix is read.
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::pubkey;
+use anchor_lang::solana_program::hash::hash;
+use crate::layout::{self, Field};
+use crate::price_oracle::OracleError;
+use crate::switchboard_utils::{fixed_to_f64, PRICE_SCALE};
+
+/// Marinade liquid staking program, the owner of the `State` account
+pub const MARINADE_PROGRAM_ID: Pubkey = pubkey!("MarBmsSgKXdrN1egZf5sqe1TMai9K1rChYNDJgjq7aD");
+/// Marinade's mainnet `State` account
+pub const MARINADE_STATE: Pubkey = pubkey!("8szGkuLTAux9XMgZ2vtY39jVSowEcpBfFfD8hXSEqdGC");
+
+/// `msol_price` is a binary fixed-point number with 32 fractional bits
+pub const MSOL_PRICE_DENOMINATOR: u64 = 1 << 32;
+
+// Offsets into the account, discriminator included
+pub const MSOL_SUPPLY_OFFSET: usize = 504;
+pub const MSOL_PRICE_OFFSET: usize = 512;
+/// Size of the prefix read from the state account
+pub const MARINADE_STATE_PREFIX_LEN: usize = MSOL_PRICE_OFFSET + 8;
+
+/// mSOL backing as of Marinade's last epoch update
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MarinadeRate {
+    pub msol_supply: u64,
+    /// SOL per mSOL scaled by `MSOL_PRICE_DENOMINATOR`
+    pub msol_price: u64,
+}
+
+impl MarinadeRate {
+    /// SOL per mSOL in fixed point with `PRICE_DECIMALS` decimals, truncated
+    pub fn price(&self) -> std::result::Result<u64, OracleError> {
+        if self.msol_price == 0 {
+            return Err(OracleError::InvalidMarinadeState);
+        }
+        let price = self.msol_price as u128 * PRICE_SCALE as u128 / MSOL_PRICE_DENOMINATOR as u128;
+        u64::try_from(price).map_err(|_| OracleError::ConversionOverflow)
+    }
+}
+
+/// Anchor discriminator of Marinade's `State` account
+pub fn marinade_state_discriminator() -> [u8; 8] {
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&hash(b"account:State").to_bytes()[..8]);
+    discriminator
+}
+
+/// Decodes the exchange rate from the raw data of Marinade's `State` account
+pub fn decode_marinade_state(data: &[u8]) -> std::result::Result<MarinadeRate, OracleError> {
+    if data.len() < MARINADE_STATE_PREFIX_LEN || data[..8] != marinade_state_discriminator() {
+        return Err(OracleError::InvalidMarinadeState);
+    }
+    Ok(MarinadeRate {
+        msol_supply: read_u64(data, MSOL_SUPPLY_OFFSET)?,
+        msol_price: read_u64(data, MSOL_PRICE_OFFSET)?,
+    })
+}
+
+/// Loads the Marinade state registered as `expected` after checking its key and owner,
+/// and returns the SOL value of one mSOL in fixed point
+pub fn get_marinade_price(state: &AccountInfo, expected: &Pubkey) -> Result<u64> {
+    if state.key != expected {
+        msg!("Marinade state {} is not the registered state {}", state.key, expected);
+        return Err(error!(OracleError::InvalidMarinadeState));
+    }
+    if state.owner != &MARINADE_PROGRAM_ID {
+        msg!("Invalid Marinade state owner: expected {}, found {}", MARINADE_PROGRAM_ID, state.owner);
+        return Err(error!(OracleError::InvalidMarinadeState));
+    }
+
+    let rate = decode_marinade_state(&state.try_borrow_data()?).map_err(|e| {
+        msg!("Failed to decode Marinade state {}: {:?}", state.key, e);
+        error!(e)
+    })?;
+    let price = rate.price().map_err(|e| {
+        msg!("Marinade state {} has no mSOL price", state.key);
+        error!(e)
+    })?;
+    verbose_msg!("Marinade mSOL rate: {} ({} mSOL outstanding)", fixed_to_f64(price), rate.msol_supply);
+    Ok(price)
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> std::result::Result<u64, OracleError> {
+    layout::read_u64(bytes, Field::at(offset, 8)).ok_or(OracleError::InvalidMarinadeState)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::MockMarinadeState;
+
+    #[test]
+    fn test_decode_marinade_state_rate() {
+        // 1.25 SOL per mSOL
+        let data = MockMarinadeState::new(5 * MSOL_PRICE_DENOMINATOR / 4).msol_supply(4_000_000_000_000_000).to_account_data();
+        let rate = decode_marinade_state(&data).unwrap();
+        assert_eq!(rate, MarinadeRate { msol_supply: 4_000_000_000_000_000, msol_price: 5_368_709_120 });
+        assert_eq!(rate.price().unwrap(), 1_250_000_000);
+
+        // Truncated toward zero: 1 + 2^-32 SOL is below one fixed-point unit over 1 SOL
+        let rate = MarinadeRate { msol_supply: 0, msol_price: MSOL_PRICE_DENOMINATOR + 1 };
+        assert_eq!(rate.price().unwrap(), PRICE_SCALE);
+        let unset = MarinadeRate { msol_supply: 0, msol_price: 0 };
+        assert!(matches!(unset.price(), Err(OracleError::InvalidMarinadeState)));
+    }
+
+    #[test]
+    fn test_decode_rejects_other_accounts() {
+        let valid = MockMarinadeState::new(MSOL_PRICE_DENOMINATOR).to_account_data();
+        assert!(matches!(decode_marinade_state(&valid[..MARINADE_STATE_PREFIX_LEN - 1]), Err(OracleError::InvalidMarinadeState)));
+        let mut wrong_discriminator = valid.clone();
+        wrong_discriminator[0] ^= 0xff;
+        assert!(matches!(decode_marinade_state(&wrong_discriminator), Err(OracleError::InvalidMarinadeState)));
+    }
+
+    #[test]
+    fn test_get_marinade_price_checks_key_and_owner() {
+        let key = Pubkey::new_unique();
+        let mut account = MockMarinadeState::new(MSOL_PRICE_DENOMINATOR * 2).into_account(key);
+        assert_eq!(get_marinade_price(&account.account_info(), &key).unwrap(), 2 * PRICE_SCALE);
+
+        let err = get_marinade_price(&account.account_info(), &Pubkey::new_unique()).unwrap_err();
+        assert_eq!(err, error!(OracleError::InvalidMarinadeState));
+        account.owner = Pubkey::new_unique();
+        let err = get_marinade_price(&account.account_info(), &key).unwrap_err();
+        assert_eq!(err, error!(OracleError::InvalidMarinadeState));
+    }
+}
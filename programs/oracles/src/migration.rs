@@ -0,0 +1,1462 @@
+//! In-place upgrades of accounts written by earlier layouts of the program.
+//!
+//! Each legacy layout is kept here only for as long as deployed accounts may still use it.
+use anchor_lang::prelude::*;
+use anchor_lang::system_program;
+use anchor_lang::Discriminator;
+use crate::history::{AssetHistory, PriceHistory};
+use crate::trust_score::TrustWeights;
+use crate::yield_convention::{RawYield, YieldConfig};
+use crate::price_oracle::{AssetConfig, AssetRegistry, AssetType, Confidence, LargeMovePolicy, FeedMapping, FeedShard, LastError, LstPremium, OracleConfig, OracleError, PendingConfig, PendingFallback, PendingFeedMapping, PendingFeedShard, PendingPrice, PoolKind, PriceData, PriceOracleData, PriceOracleHeader, PriceProvenance, QuoteCurrency, SourceKind, SourcePolicy, SourceSlot, UpdateStats, ASSET_COUNT, DEFAULT_INSTANCE, MAX_ASSET_SOURCES, MAX_FEED_SHARDS, MAX_RESUME_APPROVERS, MAX_WATCHERS};
+
+/// Number of assets before INF, stSOL and bonkSOL were added
+pub const LEGACY_ASSET_COUNT: usize = 7;
+
+/// Per-asset array of a legacy layout with the slots of later assets appended at their default
+fn widen<T: Copy + Default>(legacy: [T; LEGACY_ASSET_COUNT]) -> [T; ASSET_COUNT] {
+    let mut slots = [T::default(); ASSET_COUNT];
+    slots[..LEGACY_ASSET_COUNT].copy_from_slice(&legacy);
+    slots
+}
+
+/// Prices of a legacy data account, with never-updated entries for later assets, which
+/// their first update then treats as a first observation
+fn widen_price_data(legacy: [PriceData; LEGACY_ASSET_COUNT]) -> [PriceData; ASSET_COUNT] {
+    AssetType::ALL.map(|asset_type| match legacy.get(asset_type.index()) {
+        Some(price_data) => *price_data,
+        None => PriceData { quote: asset_type.default_quote(), ..PriceData::default() },
+    })
+}
+
+/// `PriceData` before provenance was recorded
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct PriceDataV1 {
+    pub price: u64,
+    pub last_price: u64,
+    pub last_update_time: i64,
+    pub apy: u64,
+    pub last_update_slot: u64,
+    pub last_price_time: i64,
+    pub quote: QuoteCurrency,
+}
+
+impl PriceDataV1 {
+    /// Serialized size
+    pub const LEN: usize = 8 * 6 + 1;
+}
+
+impl From<PriceDataV1> for PriceData {
+    /// Migrated prices keep their values with an unknown (default) provenance until the next update
+    fn from(legacy: PriceDataV1) -> Self {
+        PriceData {
+            price: legacy.price,
+            last_price: legacy.last_price,
+            last_update_time: legacy.last_update_time,
+            apy: legacy.apy,
+            last_update_slot: legacy.last_update_slot,
+            last_price_time: legacy.last_price_time,
+            quote: legacy.quote,
+            provenance: PriceProvenance::default(),
+            delisted: false,
+            confidence: Confidence::default(),
+            high_watermark: 0,
+            high_watermark_time: 0,
+            low_watermark: 0,
+            low_watermark_time: 0,
+            last_checked_time: legacy.last_update_time,
+            last_checked_slot: legacy.last_update_slot,
+            feed_timestamp: legacy.last_update_time,
+        }
+    }
+}
+
+/// `PriceOracleData` before provenance was recorded; same discriminator and seeds
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct PriceOracleDataV1 {
+    pub price_data: [PriceDataV1; LEGACY_ASSET_COUNT],
+    pub bump: u8,
+}
+
+impl PriceOracleDataV1 {
+    /// Serialized size, excluding the 8-byte account discriminator
+    pub const LEN: usize = PriceDataV1::LEN * LEGACY_ASSET_COUNT + 1;
+}
+
+impl From<PriceOracleDataV1> for PriceOracleData {
+    fn from(legacy: PriceOracleDataV1) -> Self {
+        PriceOracleData {
+            price_data: widen_price_data(legacy.price_data.map(PriceData::from)),
+            bump: legacy.bump,
+            nonce: 0,
+        }
+    }
+}
+
+/// `PriceData` with provenance, before assets could be delisted
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct PriceDataV2 {
+    pub price: u64,
+    pub last_price: u64,
+    pub last_update_time: i64,
+    pub apy: u64,
+    pub last_update_slot: u64,
+    pub last_price_time: i64,
+    pub quote: QuoteCurrency,
+    pub provenance: PriceProvenance,
+}
+
+impl PriceDataV2 {
+    /// Serialized size
+    pub const LEN: usize = PriceDataV1::LEN + PriceProvenance::LEN;
+}
+
+impl From<PriceDataV2> for PriceData {
+    /// Every asset was listed before delisting existed
+    fn from(legacy: PriceDataV2) -> Self {
+        PriceData {
+            price: legacy.price,
+            last_price: legacy.last_price,
+            last_update_time: legacy.last_update_time,
+            apy: legacy.apy,
+            last_update_slot: legacy.last_update_slot,
+            last_price_time: legacy.last_price_time,
+            quote: legacy.quote,
+            provenance: legacy.provenance,
+            delisted: false,
+            confidence: Confidence::default(),
+            high_watermark: 0,
+            high_watermark_time: 0,
+            low_watermark: 0,
+            low_watermark_time: 0,
+            last_checked_time: legacy.last_update_time,
+            last_checked_slot: legacy.last_update_slot,
+            feed_timestamp: legacy.last_update_time,
+        }
+    }
+}
+
+/// `PriceOracleData` before assets could be delisted; same discriminator and seeds
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct PriceOracleDataV2 {
+    pub price_data: [PriceDataV2; LEGACY_ASSET_COUNT],
+    pub bump: u8,
+}
+
+impl PriceOracleDataV2 {
+    /// Serialized size, excluding the 8-byte account discriminator
+    pub const LEN: usize = PriceDataV2::LEN * LEGACY_ASSET_COUNT + 1;
+}
+
+impl From<PriceOracleDataV2> for PriceOracleData {
+    fn from(legacy: PriceOracleDataV2) -> Self {
+        PriceOracleData {
+            price_data: widen_price_data(legacy.price_data.map(PriceData::from)),
+            bump: legacy.bump,
+            nonce: 0,
+        }
+    }
+}
+
+/// `PriceData` with delisting, before the confidence of each price was stored
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct PriceDataV3 {
+    pub price: u64,
+    pub last_price: u64,
+    pub last_update_time: i64,
+    pub apy: u64,
+    pub last_update_slot: u64,
+    pub last_price_time: i64,
+    pub quote: QuoteCurrency,
+    pub provenance: PriceProvenance,
+    pub delisted: bool,
+}
+
+impl PriceDataV3 {
+    /// Serialized size
+    pub const LEN: usize = PriceDataV2::LEN + 1;
+}
+
+impl From<PriceDataV3> for PriceData {
+    /// Migrated prices have no known confidence until the next update
+    fn from(legacy: PriceDataV3) -> Self {
+        PriceData {
+            price: legacy.price,
+            last_price: legacy.last_price,
+            last_update_time: legacy.last_update_time,
+            apy: legacy.apy,
+            last_update_slot: legacy.last_update_slot,
+            last_price_time: legacy.last_price_time,
+            quote: legacy.quote,
+            provenance: legacy.provenance,
+            delisted: legacy.delisted,
+            confidence: Confidence::default(),
+            high_watermark: 0,
+            high_watermark_time: 0,
+            low_watermark: 0,
+            low_watermark_time: 0,
+            last_checked_time: legacy.last_update_time,
+            last_checked_slot: legacy.last_update_slot,
+            feed_timestamp: legacy.last_update_time,
+        }
+    }
+}
+
+/// `PriceOracleData` before confidence was stored; same discriminator and seeds
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct PriceOracleDataV3 {
+    pub price_data: [PriceDataV3; LEGACY_ASSET_COUNT],
+    pub bump: u8,
+}
+
+impl PriceOracleDataV3 {
+    /// Serialized size, excluding the 8-byte account discriminator
+    pub const LEN: usize = PriceDataV3::LEN * LEGACY_ASSET_COUNT + 1;
+}
+
+impl From<PriceOracleDataV3> for PriceOracleData {
+    fn from(legacy: PriceOracleDataV3) -> Self {
+        PriceOracleData {
+            price_data: widen_price_data(legacy.price_data.map(PriceData::from)),
+            bump: legacy.bump,
+            nonce: 0,
+        }
+    }
+}
+
+/// `PriceData` with confidence, before watermarks were tracked
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct PriceDataV4 {
+    pub price: u64,
+    pub last_price: u64,
+    pub last_update_time: i64,
+    pub apy: u64,
+    pub last_update_slot: u64,
+    pub last_price_time: i64,
+    pub quote: QuoteCurrency,
+    pub provenance: PriceProvenance,
+    pub delisted: bool,
+    pub confidence: Confidence,
+}
+
+impl PriceDataV4 {
+    /// Serialized size
+    pub const LEN: usize = PriceDataV3::LEN + Confidence::LEN;
+}
+
+impl From<PriceDataV4> for PriceData {
+    /// Watermarks start with the next accepted price, as after a reset
+    fn from(legacy: PriceDataV4) -> Self {
+        PriceData {
+            price: legacy.price,
+            last_price: legacy.last_price,
+            last_update_time: legacy.last_update_time,
+            apy: legacy.apy,
+            last_update_slot: legacy.last_update_slot,
+            last_price_time: legacy.last_price_time,
+            quote: legacy.quote,
+            provenance: legacy.provenance,
+            delisted: legacy.delisted,
+            confidence: legacy.confidence,
+            high_watermark: 0,
+            high_watermark_time: 0,
+            low_watermark: 0,
+            low_watermark_time: 0,
+            last_checked_time: legacy.last_update_time,
+            last_checked_slot: legacy.last_update_slot,
+            feed_timestamp: legacy.last_update_time,
+        }
+    }
+}
+
+/// `PriceOracleData` before watermarks were tracked; same discriminator and seeds
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct PriceOracleDataV4 {
+    pub price_data: [PriceDataV4; LEGACY_ASSET_COUNT],
+    pub bump: u8,
+}
+
+impl PriceOracleDataV4 {
+    /// Serialized size, excluding the 8-byte account discriminator
+    pub const LEN: usize = PriceDataV4::LEN * LEGACY_ASSET_COUNT + 1;
+}
+
+impl From<PriceOracleDataV4> for PriceOracleData {
+    fn from(legacy: PriceOracleDataV4) -> Self {
+        PriceOracleData {
+            price_data: widen_price_data(legacy.price_data.map(PriceData::from)),
+            bump: legacy.bump,
+            nonce: 0,
+        }
+    }
+}
+
+/// `PriceData` with watermarks, before check times were kept apart from update times
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct PriceDataV5 {
+    pub price: u64,
+    pub last_price: u64,
+    pub last_update_time: i64,
+    pub apy: u64,
+    pub last_update_slot: u64,
+    pub last_price_time: i64,
+    pub quote: QuoteCurrency,
+    pub provenance: PriceProvenance,
+    pub delisted: bool,
+    pub confidence: Confidence,
+    pub high_watermark: u64,
+    pub high_watermark_time: i64,
+    pub low_watermark: u64,
+    pub low_watermark_time: i64,
+}
+
+impl PriceDataV5 {
+    /// Serialized size
+    pub const LEN: usize = PriceDataV4::LEN + 8 * 4;
+}
+
+impl From<PriceDataV5> for PriceData {
+    /// Every stored price was last confirmed when it was written
+    fn from(legacy: PriceDataV5) -> Self {
+        PriceData {
+            price: legacy.price,
+            last_price: legacy.last_price,
+            last_update_time: legacy.last_update_time,
+            apy: legacy.apy,
+            last_update_slot: legacy.last_update_slot,
+            last_price_time: legacy.last_price_time,
+            quote: legacy.quote,
+            provenance: legacy.provenance,
+            delisted: legacy.delisted,
+            confidence: legacy.confidence,
+            high_watermark: legacy.high_watermark,
+            high_watermark_time: legacy.high_watermark_time,
+            low_watermark: legacy.low_watermark,
+            low_watermark_time: legacy.low_watermark_time,
+            last_checked_time: legacy.last_update_time,
+            last_checked_slot: legacy.last_update_slot,
+            feed_timestamp: legacy.last_update_time,
+        }
+    }
+}
+
+/// `PriceData` with check times, before the time the source produced the price was kept
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct PriceDataV6 {
+    pub price: u64,
+    pub last_price: u64,
+    pub last_update_time: i64,
+    pub apy: u64,
+    pub last_update_slot: u64,
+    pub last_price_time: i64,
+    pub quote: QuoteCurrency,
+    pub provenance: PriceProvenance,
+    pub delisted: bool,
+    pub confidence: Confidence,
+    pub high_watermark: u64,
+    pub high_watermark_time: i64,
+    pub low_watermark: u64,
+    pub low_watermark_time: i64,
+    pub last_checked_time: i64,
+    pub last_checked_slot: u64,
+}
+
+impl PriceDataV6 {
+    /// Serialized size
+    pub const LEN: usize = PriceDataV5::LEN + 8 * 2;
+}
+
+impl From<PriceDataV6> for PriceData {
+    /// The source's own time is unknown; the check time is the closest it gets
+    fn from(legacy: PriceDataV6) -> Self {
+        PriceData {
+            price: legacy.price,
+            last_price: legacy.last_price,
+            last_update_time: legacy.last_update_time,
+            apy: legacy.apy,
+            last_update_slot: legacy.last_update_slot,
+            last_price_time: legacy.last_price_time,
+            quote: legacy.quote,
+            provenance: legacy.provenance,
+            delisted: legacy.delisted,
+            confidence: legacy.confidence,
+            high_watermark: legacy.high_watermark,
+            high_watermark_time: legacy.high_watermark_time,
+            low_watermark: legacy.low_watermark,
+            low_watermark_time: legacy.low_watermark_time,
+            last_checked_time: legacy.last_checked_time,
+            last_checked_slot: legacy.last_checked_slot,
+            feed_timestamp: legacy.last_checked_time,
+        }
+    }
+}
+
+/// `PriceOracleData` before the nonce was kept; same discriminator and seeds
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct PriceOracleDataV5 {
+    pub price_data: [PriceDataV5; LEGACY_ASSET_COUNT],
+    pub bump: u8,
+}
+
+impl PriceOracleDataV5 {
+    /// Serialized size, excluding the 8-byte account discriminator
+    pub const LEN: usize = PriceDataV5::LEN * LEGACY_ASSET_COUNT + 1;
+}
+
+impl From<PriceOracleDataV5> for PriceOracleData {
+    /// The nonce starts over; readers comparing nonces across the migration see a change
+    fn from(legacy: PriceOracleDataV5) -> Self {
+        PriceOracleData {
+            price_data: widen_price_data(legacy.price_data.map(PriceData::from)),
+            bump: legacy.bump,
+            nonce: 0,
+        }
+    }
+}
+
+/// `PriceOracleData` before check times were kept; same discriminator and seeds
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct PriceOracleDataV6 {
+    pub price_data: [PriceDataV5; LEGACY_ASSET_COUNT],
+    pub bump: u8,
+    pub nonce: u64,
+}
+
+impl PriceOracleDataV6 {
+    /// Serialized size, excluding the 8-byte account discriminator
+    pub const LEN: usize = PriceOracleDataV5::LEN + 8;
+}
+
+impl From<PriceOracleDataV6> for PriceOracleData {
+    fn from(legacy: PriceOracleDataV6) -> Self {
+        PriceOracleData {
+            price_data: widen_price_data(legacy.price_data.map(PriceData::from)),
+            bump: legacy.bump,
+            nonce: legacy.nonce,
+        }
+    }
+}
+
+/// `PriceOracleData` before INF, stSOL and bonkSOL were added; same discriminator and seeds
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct PriceOracleDataV7 {
+    pub price_data: [PriceDataV6; LEGACY_ASSET_COUNT],
+    pub bump: u8,
+    pub nonce: u64,
+}
+
+impl PriceOracleDataV7 {
+    /// Serialized size, excluding the 8-byte account discriminator
+    pub const LEN: usize = PriceDataV6::LEN * LEGACY_ASSET_COUNT + 1 + 8;
+}
+
+impl From<PriceOracleDataV7> for PriceOracleData {
+    /// Existing assets keep their slots and values
+    fn from(legacy: PriceOracleDataV7) -> Self {
+        PriceOracleData {
+            price_data: widen_price_data(legacy.price_data.map(PriceData::from)),
+            bump: legacy.bump,
+            nonce: legacy.nonce,
+        }
+    }
+}
+
+/// `PriceOracleData` before feed timestamps were kept; same discriminator and seeds
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct PriceOracleDataV8 {
+    pub price_data: [PriceDataV6; ASSET_COUNT],
+    pub bump: u8,
+    pub nonce: u64,
+}
+
+impl PriceOracleDataV8 {
+    /// Serialized size, excluding the 8-byte account discriminator
+    pub const LEN: usize = PriceDataV6::LEN * ASSET_COUNT + 1 + 8;
+}
+
+impl From<PriceOracleDataV8> for PriceOracleData {
+    fn from(legacy: PriceOracleDataV8) -> Self {
+        PriceOracleData {
+            price_data: legacy.price_data.map(PriceData::from),
+            bump: legacy.bump,
+            nonce: legacy.nonce,
+        }
+    }
+}
+
+/// `OracleConfig` before INF, stSOL and bonkSOL were added
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct OracleConfigV1 {
+    pub price_change_limit_bps: u32,
+    pub timelock_secs: u32,
+    pub feeds: [Pubkey; LEGACY_ASSET_COUNT],
+    pub resume_approvers: [Pubkey; MAX_RESUME_APPROVERS],
+    pub resume_threshold: u8,
+    pub manual_resume_needs_approval: bool,
+}
+
+impl From<OracleConfigV1> for OracleConfig {
+    fn from(legacy: OracleConfigV1) -> Self {
+        OracleConfig {
+            price_change_limit_bps: legacy.price_change_limit_bps,
+            timelock_secs: legacy.timelock_secs,
+            feeds: widen(legacy.feeds),
+            resume_approvers: legacy.resume_approvers,
+            resume_threshold: legacy.resume_threshold,
+            manual_resume_needs_approval: legacy.manual_resume_needs_approval,
+        }
+    }
+}
+
+/// `PendingConfig` before INF, stSOL and bonkSOL were added
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct PendingConfigV1 {
+    pub config: OracleConfigV1,
+    pub effective_at: i64,
+}
+
+/// `PriceOracleHeader` before INF, stSOL and bonkSOL were added; same discriminator and
+/// seeds. Earlier headers are this layout cut short, and read zeros for the missing tail.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct PriceOracleHeaderV1 {
+    pub last_global_update: i64,
+    pub emergency_stop: bool,
+    pub authority: Pubkey,
+    pub switchboard_program_id: Pubkey,
+    pub bump: u8,
+    pub max_feed_age_slots: u64,
+    pub last_stop_change_time: i64,
+    pub stop_change_count: u64,
+    pub min_stop_change_interval: i64,
+    pub operator: Pubkey,
+    pub locked: bool,
+    pub min_lease_balance: u64,
+    pub reject_empty_lease: bool,
+    pub sources: [SourceKind; LEGACY_ASSET_COUNT],
+    pub partial_updates: bool,
+    pub rejection_codes: [u32; LEGACY_ASSET_COUNT],
+    pub max_age_secs: [u32; LEGACY_ASSET_COUNT],
+    pub price_change_limit_bps: u32,
+    pub config_timelock_secs: u32,
+    pub pending_config: PendingConfigV1,
+    pub stop_clear_effective_at: i64,
+    pub emergency_council: Pubkey,
+    pub paused: [bool; LEGACY_ASSET_COUNT],
+    pub resume_approvers: [Pubkey; MAX_RESUME_APPROVERS],
+    pub resume_threshold: u8,
+    pub manual_resume_needs_approval: bool,
+    pub stop_incident: u64,
+    pub stop_automatic: bool,
+    pub resume_approvals: [u64; MAX_RESUME_APPROVERS],
+    pub large_move_policy: [LargeMovePolicy; LEGACY_ASSET_COUNT],
+    pub move_confirm_delay_secs: u32,
+    pub move_confirm_tolerance_bps: u32,
+    pub pending_prices: [PendingPrice; LEGACY_ASSET_COUNT],
+    pub update_stats: UpdateStats,
+    pub apy_change_limit_bps: [u32; LEGACY_ASSET_COUNT],
+    pub trusted_publisher: Pubkey,
+    pub signed_price_times: [i64; LEGACY_ASSET_COUNT],
+    pub attestation_sequence: u64,
+}
+
+impl PriceOracleHeaderV1 {
+    /// Serialized size, excluding the 8-byte account discriminator: the current header with
+    /// its per-asset arrays at the legacy count and none of the fields appended since
+    pub const LEN: usize = PriceOracleHeader::LEN
+        - (ASSET_COUNT - LEGACY_ASSET_COUNT) * (1 + 4 + 4 + 32 + 1 + 1 + PendingPrice::LEN + 4 + 8)
+        - 2 * ASSET_COUNT // haircut_bps
+        - 1 // audit_log_enabled
+        - 1 // updates_paused
+        - 32 // thread_authority
+        - FeedMapping::LEN // feed_mapping
+        - PendingFeedMapping::LEN // pending_feed_mapping
+        - TrustWeights::LEN // trust_weights
+        - 32 // instance
+        - 8 * ASSET_COUNT // clamped_prices
+        - 32 * ASSET_COUNT // asset_mints
+        - ASSET_COUNT // mint_decimals
+        - YieldConfig::LEN * ASSET_COUNT // yield_configs
+        - RawYield::LEN * ASSET_COUNT // raw_yields
+        - 4 // data_generation
+        - LastError::LEN * ASSET_COUNT // last_errors
+        - ASSET_COUNT // source_policies
+        - 8 // resumed_at
+        - 4 // resume_grace_secs
+        - 4 // resume_grace_multiplier_bps
+        - 4 // last_changed_mask
+        - 8 // last_changed_slot
+        - 32 * MAX_WATCHERS // watchers
+        - 32 // stop_watcher
+        - LstPremium::LEN * ASSET_COUNT // premiums
+        - FeedShard::LEN * MAX_FEED_SHARDS // feed_shards
+        - 8 // stop_started_at
+        - 8 // total_stopped_secs
+        - 32 * ASSET_COUNT // asset_authorities
+        - 32 // state_hash
+        - 32 // on_demand_queue
+        - PendingFallback::LEN * ASSET_COUNT // pending_fallbacks
+        - PendingFeedShard::LEN * MAX_FEED_SHARDS; // pending_feed_shards
+}
+
+impl From<PriceOracleHeaderV1> for PriceOracleHeader {
+    /// Later assets start out with every setting at its default, unpaused and with no
+    /// registered feed
+    fn from(legacy: PriceOracleHeaderV1) -> Self {
+        PriceOracleHeader {
+            last_global_update: legacy.last_global_update,
+            emergency_stop: legacy.emergency_stop,
+            authority: legacy.authority,
+            switchboard_program_id: legacy.switchboard_program_id,
+            bump: legacy.bump,
+            max_feed_age_slots: legacy.max_feed_age_slots,
+            last_stop_change_time: legacy.last_stop_change_time,
+            stop_change_count: legacy.stop_change_count,
+            min_stop_change_interval: legacy.min_stop_change_interval,
+            operator: legacy.operator,
+            locked: legacy.locked,
+            min_lease_balance: legacy.min_lease_balance,
+            reject_empty_lease: legacy.reject_empty_lease,
+            sources: widen(legacy.sources),
+            partial_updates: legacy.partial_updates,
+            rejection_codes: widen(legacy.rejection_codes),
+            max_age_secs: widen(legacy.max_age_secs),
+            price_change_limit_bps: legacy.price_change_limit_bps,
+            config_timelock_secs: legacy.config_timelock_secs,
+            pending_config: PendingConfig {
+                config: legacy.pending_config.config.into(),
+                effective_at: legacy.pending_config.effective_at,
+            },
+            stop_clear_effective_at: legacy.stop_clear_effective_at,
+            emergency_council: legacy.emergency_council,
+            paused: widen(legacy.paused),
+            resume_approvers: legacy.resume_approvers,
+            resume_threshold: legacy.resume_threshold,
+            manual_resume_needs_approval: legacy.manual_resume_needs_approval,
+            stop_incident: legacy.stop_incident,
+            stop_automatic: legacy.stop_automatic,
+            resume_approvals: legacy.resume_approvals,
+            large_move_policy: widen(legacy.large_move_policy),
+            move_confirm_delay_secs: legacy.move_confirm_delay_secs,
+            move_confirm_tolerance_bps: legacy.move_confirm_tolerance_bps,
+            pending_prices: widen(legacy.pending_prices),
+            update_stats: legacy.update_stats,
+            apy_change_limit_bps: widen(legacy.apy_change_limit_bps),
+            trusted_publisher: legacy.trusted_publisher,
+            signed_price_times: widen(legacy.signed_price_times),
+            attestation_sequence: legacy.attestation_sequence,
+            haircut_bps: [0; ASSET_COUNT],
+            audit_log_enabled: false,
+            updates_paused: false,
+            thread_authority: Pubkey::default(),
+            feed_mapping: FeedMapping::default(),
+            pending_feed_mapping: PendingFeedMapping::default(),
+            trust_weights: TrustWeights::default(),
+            instance: DEFAULT_INSTANCE,
+            clamped_prices: [0; ASSET_COUNT],
+            asset_mints: [Pubkey::default(); ASSET_COUNT],
+            mint_decimals: [0; ASSET_COUNT],
+            yield_configs: [YieldConfig::default(); ASSET_COUNT],
+            raw_yields: [RawYield::default(); ASSET_COUNT],
+            data_generation: 0,
+            last_errors: [LastError::default(); ASSET_COUNT],
+            source_policies: [SourcePolicy::default(); ASSET_COUNT],
+            resumed_at: 0,
+            resume_grace_secs: 0,
+            resume_grace_multiplier_bps: 0,
+            last_changed_mask: 0,
+            last_changed_slot: 0,
+            watchers: [Pubkey::default(); MAX_WATCHERS],
+            stop_watcher: Pubkey::default(),
+            premiums: [LstPremium::default(); ASSET_COUNT],
+            feed_shards: [FeedShard::default(); MAX_FEED_SHARDS],
+            stop_started_at: 0,
+            total_stopped_secs: 0,
+            asset_authorities: [Pubkey::default(); ASSET_COUNT],
+            state_hash: [0; 32],
+            on_demand_queue: Pubkey::default(),
+            pending_fallbacks: [PendingFallback::default(); ASSET_COUNT],
+            pending_feed_shards: [PendingFeedShard::default(); MAX_FEED_SHARDS],
+        }
+    }
+}
+
+/// Decodes a data account in the layout preceding provenance, discriminator included
+pub fn decode_price_oracle_data_v1(data: &[u8]) -> std::result::Result<PriceOracleData, OracleError> {
+    decode_legacy::<PriceOracleDataV1>(data, PriceOracleDataV1::LEN)
+}
+
+/// Decodes a data account in the layout preceding delisting, discriminator included
+pub fn decode_price_oracle_data_v2(data: &[u8]) -> std::result::Result<PriceOracleData, OracleError> {
+    decode_legacy::<PriceOracleDataV2>(data, PriceOracleDataV2::LEN)
+}
+
+/// Decodes a data account in the layout preceding confidence, discriminator included
+pub fn decode_price_oracle_data_v3(data: &[u8]) -> std::result::Result<PriceOracleData, OracleError> {
+    decode_legacy::<PriceOracleDataV3>(data, PriceOracleDataV3::LEN)
+}
+
+/// Decodes a data account in the layout preceding watermarks, discriminator included
+pub fn decode_price_oracle_data_v4(data: &[u8]) -> std::result::Result<PriceOracleData, OracleError> {
+    decode_legacy::<PriceOracleDataV4>(data, PriceOracleDataV4::LEN)
+}
+
+/// Decodes a data account in the layout preceding the nonce, discriminator included
+pub fn decode_price_oracle_data_v5(data: &[u8]) -> std::result::Result<PriceOracleData, OracleError> {
+    decode_legacy::<PriceOracleDataV5>(data, PriceOracleDataV5::LEN)
+}
+
+/// Decodes a data account in the layout preceding check times, discriminator included
+pub fn decode_price_oracle_data_v6(data: &[u8]) -> std::result::Result<PriceOracleData, OracleError> {
+    decode_legacy::<PriceOracleDataV6>(data, PriceOracleDataV6::LEN)
+}
+
+/// Decodes a data account in the layout preceding INF, stSOL and bonkSOL, discriminator included
+pub fn decode_price_oracle_data_v7(data: &[u8]) -> std::result::Result<PriceOracleData, OracleError> {
+    decode_legacy::<PriceOracleDataV7>(data, PriceOracleDataV7::LEN)
+}
+
+/// Decodes a data account in the layout preceding feed timestamps, discriminator included
+pub fn decode_price_oracle_data_v8(data: &[u8]) -> std::result::Result<PriceOracleData, OracleError> {
+    decode_legacy::<PriceOracleDataV8>(data, PriceOracleDataV8::LEN)
+}
+
+fn decode_legacy<T: AnchorDeserialize + Into<PriceOracleData>>(data: &[u8], len: usize) -> std::result::Result<PriceOracleData, OracleError> {
+    if data.len() != 8 + len || data[..8] != PriceOracleData::discriminator() {
+        return Err(OracleError::InvalidAccountData);
+    }
+    T::deserialize(&mut &data[8..])
+        .map(Into::into)
+        .map_err(|_| OracleError::InvalidAccountData)
+}
+
+/// Rewrites the price data account from any earlier layout in the current one, growing it
+/// and topping up its rent from `payer`. Returns false, without touching anything, when it
+/// is already current.
+pub fn migrate_price_oracle_data<'info>(
+    data: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> Result<bool> {
+    let current_len = 8 + PriceOracleData::LEN;
+    if data.data_len() == current_len {
+        // Only confirms the account decodes; nothing needs rewriting
+        PriceOracleData::try_deserialize(&mut &data.try_borrow_data()?[..])?;
+        return Ok(false);
+    }
+
+    let legacy = data.try_borrow_data()?;
+    let decoded = if legacy.len() == 8 + PriceOracleDataV1::LEN {
+        decode_price_oracle_data_v1(&legacy)
+    } else if legacy.len() == 8 + PriceOracleDataV2::LEN {
+        decode_price_oracle_data_v2(&legacy)
+    } else if legacy.len() == 8 + PriceOracleDataV3::LEN {
+        decode_price_oracle_data_v3(&legacy)
+    } else if legacy.len() == 8 + PriceOracleDataV4::LEN {
+        decode_price_oracle_data_v4(&legacy)
+    } else if legacy.len() == 8 + PriceOracleDataV5::LEN {
+        decode_price_oracle_data_v5(&legacy)
+    } else if legacy.len() == 8 + PriceOracleDataV6::LEN {
+        decode_price_oracle_data_v6(&legacy)
+    } else if legacy.len() == 8 + PriceOracleDataV7::LEN {
+        decode_price_oracle_data_v7(&legacy)
+    } else {
+        decode_price_oracle_data_v8(&legacy)
+    };
+    drop(legacy);
+    let migrated = decoded.map_err(|e| {
+        msg!("Price data account has an unknown layout of {} bytes", data.data_len());
+        error!(e)
+    })?;
+
+    rewrite(data, payer, system_program, current_len, &migrated)?;
+    Ok(true)
+}
+
+/// Overwrites the data account, whatever it holds, with `PriceOracleData::new(bump)`, as
+/// `initialize` writes it. Resizes the account to
+/// the current layout first, topping up its rent from `payer`, and zeroes every byte.
+pub fn reinitialize_price_oracle_data<'info>(
+    data: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    bump: u8,
+) -> Result<()> {
+    if data.owner != &crate::ID {
+        msg!("Data account {} is not owned by the oracle", data.key);
+        return Err(error!(OracleError::InvalidAccountData));
+    }
+    grow(data, payer, system_program, 8 + PriceOracleData::LEN)?;
+    let mut buffer = data.try_borrow_mut_data()?;
+    buffer.fill(0);
+    let mut writer: &mut [u8] = &mut buffer;
+    PriceOracleData::new(bump).try_serialize(&mut writer)?;
+    Ok(())
+}
+
+/// Grows `account` to `len`, topping up its rent from `payer`, and writes `value` over it
+fn rewrite<'info, T: AccountSerialize>(
+    account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    len: usize,
+    value: &T,
+) -> Result<()> {
+    grow(account, payer, system_program, len)?;
+    let mut buffer = account.try_borrow_mut_data()?;
+    let mut writer: &mut [u8] = &mut buffer;
+    value.try_serialize(&mut writer)?;
+    Ok(())
+}
+
+/// Grows `account` to `len`, zero-filling the new bytes and topping up its rent from `payer`
+fn grow<'info>(
+    account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    len: usize,
+) -> Result<()> {
+    let rent = Rent::get()?.minimum_balance(len);
+    let shortfall = rent.saturating_sub(account.lamports());
+    if shortfall > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                system_program.clone(),
+                system_program::Transfer { from: payer.clone(), to: account.clone() },
+            ),
+            shortfall,
+        )?;
+    }
+    account.realloc(len, true)?;
+    Ok(())
+}
+
+/// Offset of `PriceOracleHeader::authority`, after the discriminator, `last_global_update`
+/// and `emergency_stop`. It precedes every field that ever changed, so it holds in every layout.
+const HEADER_AUTHORITY_OFFSET: usize = 8 + 8 + 1;
+
+/// Reads the admin key of a header account in any layout, discriminator included
+pub fn header_authority(data: &[u8]) -> std::result::Result<Pubkey, OracleError> {
+    let end = HEADER_AUTHORITY_OFFSET + 32;
+    if data.len() < end || data[..8] != PriceOracleHeader::discriminator() {
+        return Err(OracleError::InvalidAccountData);
+    }
+    Pubkey::try_from(&data[HEADER_AUTHORITY_OFFSET..end]).map_err(|_| OracleError::InvalidAccountData)
+}
+
+/// Decodes a header account in the layout preceding INF, stSOL and bonkSOL, or any shorter
+/// layout before it, discriminator included. Fields missing from a shorter layout read a
+/// zero as their default.
+pub fn decode_price_oracle_header_v1(data: &[u8]) -> std::result::Result<PriceOracleHeader, OracleError> {
+    if data.len() > 8 + PriceOracleHeaderV1::LEN || data.len() < HEADER_AUTHORITY_OFFSET + 32 || data[..8] != PriceOracleHeader::discriminator() {
+        return Err(OracleError::InvalidAccountData);
+    }
+    let mut grown = data[8..].to_vec();
+    grown.resize(PriceOracleHeaderV1::LEN, 0);
+    PriceOracleHeaderV1::deserialize(&mut &grown[..])
+        .map(Into::into)
+        .map_err(|_| OracleError::InvalidAccountData)
+}
+
+/// Decodes a header account written by any earlier layout, discriminator included. Past
+/// `PriceOracleHeaderV1`, fields are only appended to the current layout and read a zero
+/// as their default.
+pub fn decode_legacy_price_oracle_header(data: &[u8]) -> std::result::Result<PriceOracleHeader, OracleError> {
+    if data.len() <= 8 + PriceOracleHeaderV1::LEN {
+        return decode_price_oracle_header_v1(data);
+    }
+    if data.len() >= 8 + PriceOracleHeader::LEN || data[..8] != PriceOracleHeader::discriminator() {
+        return Err(OracleError::InvalidAccountData);
+    }
+    let mut grown = data[8..].to_vec();
+    grown.resize(PriceOracleHeader::LEN, 0);
+    PriceOracleHeader::deserialize(&mut &grown[..]).map_err(|_| OracleError::InvalidAccountData)
+}
+
+/// Rewrites a header account written by an earlier layout in the current one, growing it
+/// and topping up its rent from `payer`. Returns false, without touching anything, when it
+/// is already current.
+pub fn migrate_price_oracle_header<'info>(
+    header: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> Result<bool> {
+    let current_len = 8 + PriceOracleHeader::LEN;
+    if header.data_len() == current_len {
+        // Only confirms the account decodes; nothing needs rewriting
+        PriceOracleHeader::try_deserialize(&mut &header.try_borrow_data()?[..])?;
+        return Ok(false);
+    }
+
+    let migrated = decode_legacy_price_oracle_header(&header.try_borrow_data()?).map_err(|e| {
+        msg!("Header account has an unknown layout of {} bytes", header.data_len());
+        error!(e)
+    })?;
+    rewrite(header, payer, system_program, current_len, &migrated)?;
+    Ok(true)
+}
+
+/// Grows an account laid out as `prefix_len` bytes, one `slot_len` entry per asset and a
+/// trailing bump from the legacy asset count to the current one. The added entries are
+/// zeroed, which reads as their default. Works on the raw bytes, since the price history
+/// is too large to decode twice on the stack. Returns false when it is already current.
+fn widen_asset_slots<'info>(
+    account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    discriminator: [u8; 8],
+    prefix_len: usize,
+    slot_len: usize,
+) -> Result<bool> {
+    let legacy_len = 8 + prefix_len + slot_len * LEGACY_ASSET_COUNT + 1;
+    let current_len = 8 + prefix_len + slot_len * ASSET_COUNT + 1;
+    let bump = {
+        let data = account.try_borrow_data()?;
+        if (data.len() != legacy_len && data.len() != current_len) || data[..8] != discriminator {
+            msg!("Account {} has an unknown layout of {} bytes", account.key, data.len());
+            return Err(error!(OracleError::InvalidAccountData));
+        }
+        if data.len() == current_len {
+            return Ok(false);
+        }
+        data[legacy_len - 1]
+    };
+
+    grow(account, payer, system_program, current_len)?;
+    let mut data = account.try_borrow_mut_data()?;
+    data[legacy_len - 1] = 0;
+    data[current_len - 1] = bump;
+    Ok(true)
+}
+
+/// `AssetConfig` before fallback sources were registered
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct AssetConfigV1 {
+    pub feed: Pubkey,
+    pub sources: [SourceSlot; MAX_ASSET_SOURCES],
+    pub min_sources: u8,
+    pub stake_pool: Pubkey,
+    pub nav_tolerance_bps: u32,
+    pub stake_pool_kind: PoolKind,
+}
+
+impl AssetConfigV1 {
+    /// Serialized size
+    pub const LEN: usize = AssetConfig::LEN - SourceSlot::LEN;
+}
+
+impl From<AssetConfigV1> for AssetConfig {
+    /// Migrated configs keep their settings with no fallback source
+    fn from(legacy: AssetConfigV1) -> Self {
+        AssetConfig {
+            feed: legacy.feed,
+            sources: legacy.sources,
+            min_sources: legacy.min_sources,
+            stake_pool: legacy.stake_pool,
+            nav_tolerance_bps: legacy.nav_tolerance_bps,
+            stake_pool_kind: legacy.stake_pool_kind,
+            fallback: SourceSlot::default(),
+        }
+    }
+}
+
+/// Decodes an asset registry written with `AssetConfigV1` entries for either the legacy
+/// or the current asset count, discriminator included. Later assets get an empty config.
+pub fn decode_asset_registry_v1(data: &[u8]) -> std::result::Result<AssetRegistry, OracleError> {
+    let asset_count = [LEGACY_ASSET_COUNT, ASSET_COUNT]
+        .into_iter()
+        .find(|count| data.len() == 8 + 32 + AssetConfigV1::LEN * count + 1)
+        .ok_or(OracleError::InvalidAccountData)?;
+    if data[..8] != AssetRegistry::discriminator() {
+        return Err(OracleError::InvalidAccountData);
+    }
+    let mut reader = &data[8..];
+    let queue = Pubkey::deserialize(&mut reader).map_err(|_| OracleError::InvalidAccountData)?;
+    let mut assets = [AssetConfig::default(); ASSET_COUNT];
+    for config in &mut assets[..asset_count] {
+        *config = AssetConfigV1::deserialize(&mut reader).map_err(|_| OracleError::InvalidAccountData)?.into();
+    }
+    let bump = u8::deserialize(&mut reader).map_err(|_| OracleError::InvalidAccountData)?;
+    Ok(AssetRegistry { queue, assets, bump, pyth_feed_ids: [[0; 32]; ASSET_COUNT] })
+}
+
+/// Size of an asset registry before Pyth feed ids were bound, discriminator included
+pub const ASSET_REGISTRY_V2_LEN: usize = 8 + AssetRegistry::LEN - 32 * ASSET_COUNT;
+
+/// Decodes an asset registry written by any earlier layout, discriminator included. Past
+/// `AssetConfigV1`, fields are only appended and read a zero as their default, so no Pyth
+/// feed id is bound.
+pub fn decode_legacy_asset_registry(data: &[u8]) -> std::result::Result<AssetRegistry, OracleError> {
+    if data.len() != ASSET_REGISTRY_V2_LEN {
+        return decode_asset_registry_v1(data);
+    }
+    if data[..8] != AssetRegistry::discriminator() {
+        return Err(OracleError::InvalidAccountData);
+    }
+    let mut grown = data[8..].to_vec();
+    grown.resize(AssetRegistry::LEN, 0);
+    AssetRegistry::deserialize(&mut &grown[..]).map_err(|_| OracleError::InvalidAccountData)
+}
+
+/// Rewrites an asset registry written for fewer assets, before fallback sources or before
+/// Pyth feed ids, in the current layout, like `migrate_price_oracle_header`. Later assets
+/// get an empty config.
+pub fn migrate_asset_registry<'info>(
+    registry: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> Result<bool> {
+    let current_len = 8 + AssetRegistry::LEN;
+    if registry.data_len() == current_len {
+        AssetRegistry::try_deserialize(&mut &registry.try_borrow_data()?[..])?;
+        return Ok(false);
+    }
+
+    let migrated = decode_legacy_asset_registry(&registry.try_borrow_data()?).map_err(|e| {
+        msg!("Registry account has an unknown layout of {} bytes", registry.data_len());
+        error!(e)
+    })?;
+    rewrite(registry, payer, system_program, current_len, &migrated)?;
+    Ok(true)
+}
+
+/// Grows the price history written for fewer assets, like `migrate_price_oracle_data`.
+/// Later assets get an empty history.
+pub fn migrate_price_history<'info>(
+    history: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> Result<bool> {
+    widen_asset_slots(history, payer, system_program, PriceHistory::discriminator(), 0, AssetHistory::LEN)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::price_oracle::{MoveVerdict, PriceOracle};
+    use crate::switchboard_utils::PRICE_SCALE;
+    use crate::trust_score::DEFAULT_TRUST_WEIGHTS;
+    use crate::yield_convention::YieldConvention;
+
+    #[test]
+    fn test_decode_price_oracle_data_v1() {
+        let mut legacy = PriceOracleDataV1 { bump: 254, ..PriceOracleDataV1::default() };
+        legacy.price_data[6] = PriceDataV1 {
+            price: 156_100_000_000,
+            last_price: 155_000_000_000,
+            last_update_time: 1_060,
+            apy: 7,
+            last_update_slot: 5_100,
+            last_price_time: 1_000,
+            quote: QuoteCurrency::USD,
+        };
+        let mut bytes = PriceOracleData::discriminator().to_vec();
+        bytes.extend(legacy.try_to_vec().unwrap());
+        assert_eq!(bytes.len(), 8 + PriceOracleDataV1::LEN);
+
+        let migrated = decode_price_oracle_data_v1(&bytes).unwrap();
+        assert_eq!(migrated.bump, 254);
+        let sol = migrated.price_data[6];
+        assert_eq!((sol.price, sol.last_price, sol.apy), (156_100_000_000, 155_000_000_000, 7));
+        assert_eq!((sol.last_update_time, sol.last_update_slot, sol.last_price_time), (1_060, 5_100, 1_000));
+        assert_eq!(sol.quote, QuoteCurrency::USD);
+        assert_eq!(sol.provenance, PriceProvenance::default());
+        assert!(!sol.delisted);
+
+        // Wrong discriminator or length
+        let mut wrong_discriminator = bytes.clone();
+        wrong_discriminator[0] ^= 0xff;
+        assert!(matches!(decode_price_oracle_data_v1(&wrong_discriminator), Err(OracleError::InvalidAccountData)));
+        assert!(matches!(decode_price_oracle_data_v1(&bytes[..bytes.len() - 1]), Err(OracleError::InvalidAccountData)));
+    }
+
+    #[test]
+    fn test_decode_price_oracle_data_v2() {
+        let mut legacy = PriceOracleDataV2 { bump: 253, ..PriceOracleDataV2::default() };
+        let provenance = PriceProvenance { source_feed: Pubkey::new_unique(), source_round_slot: 5_000, source_kind: 1 };
+        legacy.price_data[3] = PriceDataV2 {
+            price: 180 * PRICE_SCALE,
+            apy: 72_000_000,
+            last_update_time: 1_060,
+            quote: QuoteCurrency::SOL,
+            provenance,
+            ..PriceDataV2::default()
+        };
+        let mut bytes = PriceOracleData::discriminator().to_vec();
+        bytes.extend(legacy.try_to_vec().unwrap());
+        assert_eq!(bytes.len(), 8 + PriceOracleDataV2::LEN);
+        assert_eq!(PriceOracleDataV2::LEN + 7, PriceOracleDataV3::LEN);
+
+        let migrated = decode_price_oracle_data_v2(&bytes).unwrap();
+        assert_eq!(migrated.bump, 253);
+        let msol = migrated.price_data[3];
+        assert_eq!((msol.price, msol.apy, msol.last_update_time), (180 * PRICE_SCALE, 72_000_000, 1_060));
+        assert_eq!((msol.quote, msol.provenance), (QuoteCurrency::SOL, provenance));
+        assert!(migrated.price_data.iter().all(|price_data| !price_data.delisted));
+
+        // A V1-sized account is not a V2 one
+        assert!(matches!(decode_price_oracle_data_v2(&bytes[..8 + PriceOracleDataV1::LEN]), Err(OracleError::InvalidAccountData)));
+    }
+
+    #[test]
+    fn test_decode_price_oracle_data_v3() {
+        let mut legacy = PriceOracleDataV3 { bump: 252, ..PriceOracleDataV3::default() };
+        let provenance = PriceProvenance { source_feed: Pubkey::new_unique(), source_round_slot: 5_000, source_kind: 0 };
+        legacy.price_data[6] = PriceDataV3 {
+            price: 156 * PRICE_SCALE,
+            last_update_time: 1_060,
+            quote: QuoteCurrency::USD,
+            provenance,
+            ..PriceDataV3::default()
+        };
+        legacy.price_data[2].delisted = true;
+        let mut bytes = PriceOracleData::discriminator().to_vec();
+        bytes.extend(legacy.try_to_vec().unwrap());
+        assert_eq!(bytes.len(), 8 + PriceOracleDataV3::LEN);
+        assert_eq!(PriceOracleDataV3::LEN + 7 * Confidence::LEN, PriceOracleDataV4::LEN);
+
+        let migrated = decode_price_oracle_data_v3(&bytes).unwrap();
+        assert_eq!(migrated.bump, 252);
+        let sol = migrated.price_data[6];
+        assert_eq!((sol.price, sol.last_update_time, sol.quote, sol.provenance), (156 * PRICE_SCALE, 1_060, QuoteCurrency::USD, provenance));
+        // Delisting survives, and no confidence is invented
+        assert!(migrated.price_data[2].delisted);
+        assert!(migrated.price_data.iter().all(|price_data| price_data.confidence == Confidence::default()));
+
+        assert!(matches!(decode_price_oracle_data_v3(&bytes[..8 + PriceOracleDataV2::LEN]), Err(OracleError::InvalidAccountData)));
+    }
+
+    #[test]
+    fn test_decode_price_oracle_data_v4() {
+        let mut legacy = PriceOracleDataV4 { bump: 251, ..PriceOracleDataV4::default() };
+        let confidence = Confidence { value: 50_000_000, status: crate::price_oracle::ConfidenceStatus::Measured };
+        legacy.price_data[3] = PriceDataV4 {
+            price: 1_200_000_000,
+            last_update_time: 1_060,
+            quote: QuoteCurrency::SOL,
+            confidence,
+            ..PriceDataV4::default()
+        };
+        let mut bytes = PriceOracleData::discriminator().to_vec();
+        bytes.extend(legacy.try_to_vec().unwrap());
+        assert_eq!(bytes.len(), 8 + PriceOracleDataV4::LEN);
+        assert_eq!(PriceOracleDataV4::LEN + 7 * 32, PriceOracleDataV5::LEN);
+
+        let migrated = decode_price_oracle_data_v4(&bytes).unwrap();
+        assert_eq!(migrated.bump, 251);
+        let msol = migrated.price_data[3];
+        assert_eq!((msol.price, msol.last_update_time, msol.confidence), (1_200_000_000, 1_060, confidence));
+        // No watermark is invented from the migrated price
+        assert!(migrated.price_data.iter().all(|price_data| price_data.watermarks().is_none()));
+
+        assert!(matches!(decode_price_oracle_data_v4(&bytes[..8 + PriceOracleDataV3::LEN]), Err(OracleError::InvalidAccountData)));
+    }
+
+    #[test]
+    fn test_decode_price_oracle_data_v5() {
+        let mut legacy = PriceOracleDataV5 { bump: 250, ..PriceOracleDataV5::default() };
+        legacy.price_data[6].price = 156_100_000_000;
+        legacy.price_data[6].high_watermark = 157_000_000_000;
+        let mut bytes = PriceOracleData::discriminator().to_vec();
+        bytes.extend(legacy.try_to_vec().unwrap());
+        assert_eq!(PriceOracleDataV5::LEN + 8, PriceOracleDataV6::LEN);
+
+        let migrated = decode_price_oracle_data_v5(&bytes).unwrap();
+        assert_eq!((migrated.bump, migrated.nonce), (250, 0));
+        let sol = migrated.price_data[6];
+        assert_eq!((sol.price, sol.high_watermark), (156_100_000_000, 157_000_000_000));
+
+        assert!(matches!(decode_price_oracle_data_v5(&bytes[..8 + PriceOracleDataV4::LEN]), Err(OracleError::InvalidAccountData)));
+    }
+
+    #[test]
+    fn test_decode_price_oracle_data_v6() {
+        let mut legacy = PriceOracleDataV6 { bump: 249, nonce: 42, ..PriceOracleDataV6::default() };
+        legacy.price_data[6] = PriceDataV5 {
+            price: 156_100_000_000,
+            last_update_time: 1_060,
+            last_update_slot: 5_100,
+            ..PriceDataV5::default()
+        };
+        let mut bytes = PriceOracleData::discriminator().to_vec();
+        bytes.extend(legacy.try_to_vec().unwrap());
+        assert_eq!(PriceOracleDataV6::LEN + 7 * 16, PriceOracleDataV7::LEN);
+
+        let migrated = decode_price_oracle_data_v6(&bytes).unwrap();
+        assert_eq!((migrated.bump, migrated.nonce), (249, 42));
+        // A migrated price was last confirmed when it was written
+        let sol = migrated.price_data[6];
+        assert_eq!((sol.price, sol.last_checked_time, sol.last_checked_slot), (156_100_000_000, 1_060, 5_100));
+
+        assert!(matches!(decode_price_oracle_data_v6(&bytes[..8 + PriceOracleDataV5::LEN]), Err(OracleError::InvalidAccountData)));
+    }
+
+    #[test]
+    fn test_decode_price_oracle_data_v7() {
+        let mut legacy = PriceOracleDataV7 { bump: 247, nonce: 43, ..PriceOracleDataV7::default() };
+        for (index, price_data) in legacy.price_data.iter_mut().enumerate() {
+            price_data.price = (index as u64 + 1) * PRICE_SCALE;
+            price_data.apy = index as u64;
+            price_data.last_update_time = 1_060;
+            price_data.last_checked_time = 1_070;
+        }
+        legacy.price_data[AssetType::SOL.index()].quote = QuoteCurrency::USD;
+        let mut bytes = PriceOracleData::discriminator().to_vec();
+        bytes.extend(legacy.try_to_vec().unwrap());
+        assert_eq!(PriceOracleDataV7::LEN + 3 * PriceDataV6::LEN, PriceOracleDataV8::LEN);
+
+        // Existing assets keep their slots and values
+        let migrated = decode_price_oracle_data_v7(&bytes).unwrap();
+        assert_eq!((migrated.bump, migrated.nonce), (247, 43));
+        for (price_data, legacy_price_data) in migrated.price_data.iter().zip(legacy.price_data.iter()) {
+            assert_eq!(price_data.try_to_vec().unwrap(), PriceData::from(*legacy_price_data).try_to_vec().unwrap());
+        }
+
+        // Added assets were never updated, so their first price is a first observation
+        let header = PriceOracleHeader::default();
+        for asset_type in [AssetType::INF, AssetType::StSOL, AssetType::BonkSOL] {
+            let price_data = migrated.price_data[asset_type.index()];
+            assert_eq!(price_data.try_to_vec().unwrap(), PriceData { quote: QuoteCurrency::SOL, ..PriceData::default() }.try_to_vec().unwrap());
+            assert_eq!(PriceOracle::move_verdict(&header, asset_type, price_data.price, 1_300_000_000, 1_080), MoveVerdict::Accept);
+            assert!(PriceOracle::validate_apy_change(price_data.apy, 80_000_000, Some(100)).is_ok());
+        }
+
+        assert!(matches!(decode_price_oracle_data_v7(&bytes[..8 + PriceOracleDataV6::LEN]), Err(OracleError::InvalidAccountData)));
+    }
+
+    #[test]
+    fn test_decode_price_oracle_data_v8() {
+        let mut legacy = PriceOracleDataV8 { bump: 246, nonce: 44, ..PriceOracleDataV8::default() };
+        legacy.price_data[AssetType::BonkSOL.index()] = PriceDataV6 {
+            price: 1_050_000_000,
+            quote: QuoteCurrency::SOL,
+            last_update_time: 1_060,
+            last_checked_time: 1_070,
+            last_checked_slot: 5_110,
+            ..PriceDataV6::default()
+        };
+        let mut bytes = PriceOracleData::discriminator().to_vec();
+        bytes.extend(legacy.try_to_vec().unwrap());
+        assert_eq!(PriceOracleDataV8::LEN + ASSET_COUNT * 8, PriceOracleData::LEN);
+
+        let migrated = decode_price_oracle_data_v8(&bytes).unwrap();
+        assert_eq!((migrated.bump, migrated.nonce), (246, 44));
+        // The source's time is unknown, so staleness carries on from the check time
+        let bonk_sol = migrated.price_data[AssetType::BonkSOL.index()];
+        assert_eq!((bonk_sol.price, bonk_sol.last_update_time), (1_050_000_000, 1_060));
+        assert_eq!((bonk_sol.last_checked_time, bonk_sol.last_checked_slot, bonk_sol.feed_timestamp), (1_070, 5_110, 1_070));
+
+        assert!(matches!(decode_price_oracle_data_v8(&bytes[..8 + PriceOracleDataV7::LEN]), Err(OracleError::InvalidAccountData)));
+    }
+
+    #[test]
+    fn test_decode_price_oracle_header_v1() {
+        let feed = Pubkey::new_unique();
+        let mut legacy = PriceOracleHeaderV1 {
+            authority: Pubkey::new_unique(),
+            sources: [SourceKind::Chainlink; LEGACY_ASSET_COUNT],
+            max_age_secs: [120; LEGACY_ASSET_COUNT],
+            paused: [true; LEGACY_ASSET_COUNT],
+            large_move_policy: [LargeMovePolicy::Confirm; LEGACY_ASSET_COUNT],
+            pending_prices: [PendingPrice { price: 1_300_000_000, observed_at: 1_050 }; LEGACY_ASSET_COUNT],
+            signed_price_times: [1_040; LEGACY_ASSET_COUNT],
+            attestation_sequence: 9,
+            ..PriceOracleHeaderV1::default()
+        };
+        legacy.pending_config.config.feeds[AssetType::SOL.index()] = feed;
+        legacy.pending_config.effective_at = 2_000;
+        let mut bytes = PriceOracleHeader::discriminator().to_vec();
+        bytes.extend(legacy.try_to_vec().unwrap());
+        assert_eq!(bytes.len(), 8 + PriceOracleHeaderV1::LEN);
+
+        // Existing assets keep their settings, added ones start at the defaults
+        let migrated = decode_price_oracle_header_v1(&bytes).unwrap();
+        assert_eq!(migrated.authority, legacy.authority);
+        assert_eq!(migrated.attestation_sequence, 9);
+        assert_eq!(migrated.pending_config.config.feeds[AssetType::SOL.index()], feed);
+        assert_eq!(migrated.pending_config.effective_at, 2_000);
+        for asset_type in AssetType::ALL {
+            let legacy_asset = asset_type.index() < LEGACY_ASSET_COUNT;
+            assert_eq!(migrated.source(asset_type) == SourceKind::Chainlink, legacy_asset);
+            assert_eq!(migrated.is_paused(asset_type), legacy_asset);
+            assert_eq!(migrated.pending_prices[asset_type.index()].is_pending(), legacy_asset);
+            assert_eq!(migrated.max_age_secs(asset_type), if legacy_asset { 120 } else { 300 });
+        }
+        let mut current = Vec::new();
+        migrated.try_serialize(&mut current).unwrap();
+        assert_eq!(current.len(), 8 + PriceOracleHeader::LEN);
+
+        // A larger account is not a legacy header
+        let mut oversized = bytes.clone();
+        oversized.push(0);
+        assert!(matches!(decode_price_oracle_header_v1(&oversized), Err(OracleError::InvalidAccountData)));
+    }
+
+    #[test]
+    fn test_decode_legacy_price_oracle_header() {
+        let header = PriceOracleHeader {
+            authority: Pubkey::new_unique(),
+            max_age_secs: [120; ASSET_COUNT],
+            haircut_bps: [2_500; ASSET_COUNT],
+            audit_log_enabled: true,
+            updates_paused: true,
+            thread_authority: Pubkey::new_unique(),
+            feed_mapping: FeedMapping { configured: true, assets: AssetType::MULTI_ASSET_FEED },
+            trust_weights: TrustWeights { freshness_bps: 10_000, ..TrustWeights::default() },
+            instance: Pubkey::new_unique(),
+            clamped_prices: [7; ASSET_COUNT],
+            asset_mints: [Pubkey::new_unique(); ASSET_COUNT],
+            mint_decimals: [6; ASSET_COUNT],
+            yield_configs: [YieldConfig { convention: YieldConvention::Apr, compounding_periods: 365 }; ASSET_COUNT],
+            raw_yields: [RawYield { value: 7, config: YieldConfig::default() }; ASSET_COUNT],
+            data_generation: 3,
+            last_errors: [LastError { code: 6_006, time: 1_000 }; ASSET_COUNT],
+            source_policies: [SourcePolicy::Priority; ASSET_COUNT],
+            resumed_at: 1_000,
+            resume_grace_secs: 1_800,
+            resume_grace_multiplier_bps: 20_000,
+            last_changed_mask: 0b11,
+            last_changed_slot: 5_000,
+            watchers: [Pubkey::new_unique(); MAX_WATCHERS],
+            stop_watcher: Pubkey::new_unique(),
+            premiums: [LstPremium { premium_bps: -25, feed_price: 1_100, intrinsic_rate: 1_102, time: 1_000 }; ASSET_COUNT],
+            feed_shards: [FeedShard { feed: Pubkey::new_unique(), assets: [AssetType::INF; 6], mapped: 1 }; MAX_FEED_SHARDS],
+            stop_started_at: 900,
+            total_stopped_secs: 3_600,
+            asset_authorities: [Pubkey::new_unique(); ASSET_COUNT],
+            state_hash: [7; 32],
+            on_demand_queue: Pubkey::new_unique(),
+            pending_fallbacks: [PendingFallback { fallback: SourceSlot { kind: SourceKind::Pyth, feed: Pubkey::new_unique() }, effective_at: 1_000 }; ASSET_COUNT],
+            pending_feed_shards: [PendingFeedShard { shard: FeedShard { feed: Pubkey::new_unique(), assets: [AssetType::INF; 6], mapped: 1 }, effective_at: 1_000 }; MAX_FEED_SHARDS],
+            ..PriceOracleHeader::default()
+        };
+        let mut current = Vec::new();
+        header.try_serialize(&mut current).unwrap();
+
+        // A header from before the haircuts keeps every other field and has none, nor an audit
+        // log, paused updates, a thread authority, a feed mapping, trust weights, an instance,
+        // clamped prices, mints, yield conventions, data generation, last errors, source policies,
+        // resume grace, changed assets, watchers, premiums, feed shards, downtime, asset authorities,
+        // a state hash, an On-Demand queue, pending fallbacks or pending feed shards
+        let appended = 2 * ASSET_COUNT + 2 + 32 + FeedMapping::LEN + PendingFeedMapping::LEN + TrustWeights::LEN + 32 + 8 * ASSET_COUNT + 33 * ASSET_COUNT
+            + (YieldConfig::LEN + RawYield::LEN) * ASSET_COUNT + 4 + LastError::LEN * ASSET_COUNT
+            + ASSET_COUNT + 16 + 12 + 32 * MAX_WATCHERS + 32 + LstPremium::LEN * ASSET_COUNT + FeedShard::LEN * MAX_FEED_SHARDS + 16 + 32 * ASSET_COUNT + 32 + 32
+            + PendingFallback::LEN * ASSET_COUNT + PendingFeedShard::LEN * MAX_FEED_SHARDS;
+        let before_haircuts = &current[..current.len() - appended];
+        let migrated = decode_legacy_price_oracle_header(before_haircuts).unwrap();
+        assert_eq!(migrated.authority, header.authority);
+        assert_eq!(migrated.max_age_secs, [120; ASSET_COUNT]);
+        assert_eq!(migrated.haircut_bps, [0; ASSET_COUNT]);
+        assert!(!migrated.audit_log_enabled && !migrated.updates_paused);
+        assert_eq!(migrated.thread_authority, Pubkey::default());
+        assert!(!migrated.feed_mapping.configured && !migrated.pending_feed_mapping.is_pending());
+        assert_eq!(migrated.multi_asset_feed(), AssetType::MULTI_ASSET_FEED);
+        assert_eq!(migrated.trust_weights(), DEFAULT_TRUST_WEIGHTS);
+        assert_eq!(migrated.instance, DEFAULT_INSTANCE);
+        assert_eq!(migrated.clamped_prices, [0; ASSET_COUNT]);
+        assert_eq!(migrated.decimals(AssetType::JitoSOL), AssetType::JitoSOL.decimals());
+        assert_eq!((migrated.yield_config(AssetType::MSOL), migrated.raw_yield(AssetType::MSOL)), (YieldConfig::default(), RawYield::default()));
+        assert!(!migrated.last_error(AssetType::MSOL).is_set());
+        assert_eq!(migrated.source_policy(AssetType::MSOL), SourcePolicy::Median);
+        assert_eq!((migrated.resumed_at, migrated.resume_grace_ends_at()), (0, None));
+        assert!(migrated.changed_assets().is_empty());
+        assert_eq!((migrated.watchers, migrated.stop_watcher), ([Pubkey::default(); MAX_WATCHERS], Pubkey::default()));
+        assert_eq!(migrated.premium(AssetType::MSOL), LstPremium::default());
+        assert!(migrated.sharded_assets().is_empty());
+        assert_eq!((migrated.stop_started_at, migrated.stopped_secs(i64::MAX)), (0, 0));
+        assert_eq!(migrated.asset_authority(AssetType::JitoSOL), None);
+        assert_eq!((migrated.state_hash, migrated.on_demand_queue), ([0; 32], Pubkey::default()));
+        assert!(migrated.pending_fallbacks.iter().all(|pending| !pending.is_pending()));
+        assert!(migrated.pending_feed_shards.iter().all(|pending| !pending.is_pending()));
+
+        // The 7-asset layout still goes through its own decoding
+        let mut legacy = PriceOracleHeader::discriminator().to_vec();
+        legacy.extend(PriceOracleHeaderV1 { authority: header.authority, ..PriceOracleHeaderV1::default() }.try_to_vec().unwrap());
+        assert_eq!(decode_legacy_price_oracle_header(&legacy).unwrap().authority, header.authority);
+
+        // A current header needs no migration, and other accounts are not headers
+        let mut wrong_discriminator = before_haircuts.to_vec();
+        wrong_discriminator[0] ^= 1;
+        for wrong in [&current[..], &wrong_discriminator[..]] {
+            assert!(matches!(decode_legacy_price_oracle_header(wrong), Err(OracleError::InvalidAccountData)));
+        }
+    }
+
+    #[test]
+    fn test_header_authority_and_zero_filled_tail() {
+        let authority = Pubkey::new_unique();
+        let header = PriceOracleHeaderV1 { authority, last_global_update: 1_060, emergency_stop: true, ..PriceOracleHeaderV1::default() };
+        let mut bytes = PriceOracleHeader::discriminator().to_vec();
+        bytes.extend(header.try_to_vec().unwrap());
+        assert_eq!(header_authority(&bytes).unwrap(), authority);
+
+        // A header cut short before the latest fields decodes once zero-filled
+        let legacy_len = bytes.len() - crate::price_oracle::UpdateStats::LEN;
+        let legacy = &bytes[..legacy_len];
+        assert_eq!(header_authority(legacy).unwrap(), authority);
+        let migrated = decode_price_oracle_header_v1(legacy).unwrap();
+        assert_eq!((migrated.authority, migrated.last_global_update, migrated.emergency_stop), (authority, 1_060, true));
+        assert_eq!(migrated.update_stats, crate::price_oracle::UpdateStats::default());
+
+        let mut wrong_discriminator = bytes.clone();
+        wrong_discriminator[0] ^= 0xff;
+        assert!(matches!(header_authority(&wrong_discriminator), Err(OracleError::InvalidAccountData)));
+        assert!(matches!(header_authority(&bytes[..HEADER_AUTHORITY_OFFSET + 31]), Err(OracleError::InvalidAccountData)));
+    }
+
+    #[test]
+    fn test_decode_asset_registry_v1() {
+        let queue = Pubkey::new_unique();
+        let msol = AssetConfigV1 {
+            feed: Pubkey::new_unique(),
+            sources: [SourceSlot { kind: SourceKind::Pyth, feed: Pubkey::new_unique() }; MAX_ASSET_SOURCES],
+            min_sources: 2,
+            stake_pool: Pubkey::new_unique(),
+            nav_tolerance_bps: 150,
+            stake_pool_kind: PoolKind::Marinade,
+        };
+        for asset_count in [LEGACY_ASSET_COUNT, ASSET_COUNT] {
+            let mut bytes = AssetRegistry::discriminator().to_vec();
+            bytes.extend(queue.try_to_vec().unwrap());
+            for index in 0..asset_count {
+                let config = if index == AssetType::MSOL.index() { msol } else { AssetConfigV1::default() };
+                bytes.extend(config.try_to_vec().unwrap());
+            }
+            bytes.push(252);
+            assert_eq!(bytes.len(), ASSET_REGISTRY_V2_LEN - SourceSlot::LEN * ASSET_COUNT - AssetConfigV1::LEN * (ASSET_COUNT - asset_count));
+
+            let migrated = decode_asset_registry_v1(&bytes).unwrap();
+            assert_eq!((migrated.queue, migrated.bump), (queue, 252));
+            let config = migrated.assets[AssetType::MSOL.index()];
+            assert_eq!((config.feed, config.sources, config.min_sources), (msol.feed, msol.sources, 2));
+            assert_eq!((config.stake_pool, config.nav_tolerance_bps, config.stake_pool_kind), (msol.stake_pool, 150, PoolKind::Marinade));
+            assert_eq!(config.fallback(), None);
+            assert_eq!(migrated.assets[AssetType::BonkSOL.index()].try_to_vec().unwrap(), AssetConfig::default().try_to_vec().unwrap());
+
+            let mut wrong_discriminator = bytes.clone();
+            wrong_discriminator[0] ^= 0xff;
+            assert!(matches!(decode_asset_registry_v1(&wrong_discriminator), Err(OracleError::InvalidAccountData)));
+            assert!(matches!(decode_asset_registry_v1(&bytes[..bytes.len() - 1]), Err(OracleError::InvalidAccountData)));
+        }
+
+        // A current registry is not a legacy one
+        let mut current = AssetRegistry::discriminator().to_vec();
+        current.extend(AssetRegistry::default().try_to_vec().unwrap());
+        assert!(matches!(decode_asset_registry_v1(&current), Err(OracleError::InvalidAccountData)));
+        assert!(matches!(decode_legacy_asset_registry(&current), Err(OracleError::InvalidAccountData)));
+    }
+
+    #[test]
+    fn test_decode_legacy_asset_registry_binds_no_pyth_feed_id() {
+        let mut registry = AssetRegistry { queue: Pubkey::new_unique(), bump: 251, ..AssetRegistry::default() };
+        registry.assets[AssetType::JitoSOL.index()].fallback = SourceSlot { kind: SourceKind::Pyth, feed: Pubkey::new_unique() };
+        let mut bytes = AssetRegistry::discriminator().to_vec();
+        bytes.extend(registry.try_to_vec().unwrap());
+        bytes.truncate(ASSET_REGISTRY_V2_LEN);
+
+        let migrated = decode_legacy_asset_registry(&bytes).unwrap();
+        assert_eq!(migrated.try_to_vec().unwrap(), registry.try_to_vec().unwrap());
+        assert_eq!(migrated.pyth_feed_ids, [[0; 32]; ASSET_COUNT]);
+
+        bytes[0] ^= 0xff;
+        assert!(matches!(decode_legacy_asset_registry(&bytes), Err(OracleError::InvalidAccountData)));
+    }
+}
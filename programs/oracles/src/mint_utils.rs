@@ -0,0 +1,147 @@
+//! Reads the decimals of an asset's token mint straight from the mint account, so
+//! `set_asset_mint` never has to trust a hand-entered value, and the mint and balance of
+//! the token accounts `get_portfolio_value` values.
+//!
+//! Both the SPL Token and the Token-2022 program own mints. Their base layout is the same
+//! 82-byte `Mint`; a Token-2022 mint may carry extensions after it, of which only
+//! `DefaultAccountState` is read. Token accounts likewise share the 165-byte `Account`,
+//! and a Token-2022 account's extensions are skipped.
+use anchor_lang::prelude::*;
+use anchor_spl::token::spl_token;
+use anchor_spl::token_2022::spl_token_2022;
+use anchor_lang::solana_program::program_pack::Pack;
+use spl_token_2022::extension::default_account_state::DefaultAccountState;
+use spl_token_2022::extension::{BaseStateWithExtensions, StateWithExtensions};
+use spl_token_2022::state::AccountState;
+use crate::price_oracle::OracleError;
+
+/// What `set_asset_mint` records of a mint
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MintInfo {
+    /// Program owning the mint, SPL Token or Token-2022
+    pub token_program: Pubkey,
+    pub decimals: u8,
+}
+
+/// What `get_portfolio_value` reads of a token account
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TokenBalance {
+    pub mint: Pubkey,
+    /// Base units held
+    pub amount: u64,
+}
+
+/// Decodes a mint owned by `token_program` from its raw data. Fails with `InvalidMint`
+/// for anything but an initialized mint, and with `FrozenMint` for a Token-2022 mint
+/// that creates its token accounts frozen.
+pub fn decode_mint(token_program: &Pubkey, data: &[u8]) -> std::result::Result<MintInfo, OracleError> {
+    let decimals = if *token_program == spl_token::ID {
+        spl_token::state::Mint::unpack(data).map_err(|_| OracleError::InvalidMint)?.decimals
+    } else if *token_program == spl_token_2022::ID {
+        let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(data).map_err(|_| OracleError::InvalidMint)?;
+        if let Ok(default_state) = mint.get_extension::<DefaultAccountState>() {
+            if default_state.state == AccountState::Frozen as u8 {
+                return Err(OracleError::FrozenMint);
+            }
+        }
+        mint.base.decimals
+    } else {
+        return Err(OracleError::InvalidMint);
+    };
+    Ok(MintInfo { token_program: *token_program, decimals })
+}
+
+/// Loads the mint passed as `mint` after checking it is owned by one of the token programs
+pub fn read_mint(mint: &AccountInfo) -> Result<MintInfo> {
+    if mint.owner != &spl_token::ID && mint.owner != &spl_token_2022::ID {
+        msg!("Mint {} is owned by {}, not by the SPL Token or Token-2022 program", mint.key, mint.owner);
+        return Err(error!(OracleError::InvalidMint));
+    }
+    decode_mint(mint.owner, &mint.try_borrow_data()?).map_err(|e| {
+        msg!("Failed to read mint {}: {:?}", mint.key, e);
+        error!(e)
+    })
+}
+
+/// Decodes a token account owned by `token_program` from its raw data. Fails with
+/// `InvalidTokenAccount` for anything but an initialized token account; a frozen one
+/// still holds its balance and is read.
+pub fn decode_token_account(token_program: &Pubkey, data: &[u8]) -> std::result::Result<TokenBalance, OracleError> {
+    let account = if *token_program == spl_token::ID {
+        spl_token::state::Account::unpack(data).map_err(|_| OracleError::InvalidTokenAccount)?
+    } else if *token_program == spl_token_2022::ID {
+        let account = StateWithExtensions::<spl_token_2022::state::Account>::unpack(data).map_err(|_| OracleError::InvalidTokenAccount)?;
+        return Ok(TokenBalance { mint: account.base.mint, amount: account.base.amount });
+    } else {
+        return Err(OracleError::InvalidTokenAccount);
+    };
+    Ok(TokenBalance { mint: account.mint, amount: account.amount })
+}
+
+/// Loads the token account passed as `account` after checking it is owned by one of the
+/// token programs
+pub fn read_token_account(account: &AccountInfo) -> Result<TokenBalance> {
+    if account.owner != &spl_token::ID && account.owner != &spl_token_2022::ID {
+        msg!("Token account {} is owned by {}, not by the SPL Token or Token-2022 program", account.key, account.owner);
+        return Err(error!(OracleError::InvalidTokenAccount));
+    }
+    decode_token_account(account.owner, &account.try_borrow_data()?).map_err(|e| {
+        msg!("Failed to read token account {}: {:?}", account.key, e);
+        error!(e)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{mock_mint, mock_token_2022_account, mock_token_2022_mint, mock_token_account, mock_token_account_of};
+
+    #[test]
+    fn test_read_mint_from_both_token_programs() {
+        let mut classic = mock_mint(Pubkey::new_unique(), 6);
+        assert_eq!(read_mint(&classic.account_info()).unwrap(), MintInfo { token_program: spl_token::ID, decimals: 6 });
+
+        // A Token-2022 mint is read past its extensions
+        let mut extended = mock_token_2022_mint(Pubkey::new_unique(), 9, AccountState::Initialized);
+        assert_eq!(read_mint(&extended.account_info()).unwrap(), MintInfo { token_program: spl_token_2022::ID, decimals: 9 });
+    }
+
+    #[test]
+    fn test_read_mint_rejects_other_accounts() {
+        // Owned by another program
+        let mut foreign = mock_mint(Pubkey::new_unique(), 6);
+        foreign.owner = Pubkey::new_unique();
+        assert_eq!(read_mint(&foreign.account_info()).unwrap_err(), error!(OracleError::InvalidMint));
+
+        // A token account belongs to the token program but is not a mint
+        let mut token_account = mock_token_account(Pubkey::new_unique(), 1_000);
+        assert_eq!(read_mint(&token_account.account_info()).unwrap_err(), error!(OracleError::InvalidMint));
+
+        // An uninitialized mint has no decimals yet
+        let mut uninitialized = mock_mint(Pubkey::new_unique(), 6);
+        uninitialized.data[45] = 0;
+        assert_eq!(read_mint(&uninitialized.account_info()).unwrap_err(), error!(OracleError::InvalidMint));
+
+        // A Token-2022 mint whose accounts start frozen
+        let mut frozen = mock_token_2022_mint(Pubkey::new_unique(), 9, AccountState::Frozen);
+        assert_eq!(read_mint(&frozen.account_info()).unwrap_err(), error!(OracleError::FrozenMint));
+        assert!(matches!(decode_mint(&spl_token::ID, &frozen.data), Err(OracleError::InvalidMint)));
+    }
+
+    #[test]
+    fn test_read_token_account_from_both_token_programs() {
+        let mint = Pubkey::new_unique();
+        let mut classic = mock_token_account_of(Pubkey::new_unique(), mint, 1_000);
+        assert_eq!(read_token_account(&classic.account_info()).unwrap(), TokenBalance { mint, amount: 1_000 });
+
+        let mut extended = mock_token_2022_account(Pubkey::new_unique(), mint, 2_500);
+        assert_eq!(read_token_account(&extended.account_info()).unwrap(), TokenBalance { mint, amount: 2_500 });
+
+        // A mint is not a token account, nor is an account of another program
+        let mut not_an_account = mock_mint(Pubkey::new_unique(), 6);
+        assert_eq!(read_token_account(&not_an_account.account_info()).unwrap_err(), error!(OracleError::InvalidTokenAccount));
+        classic.owner = Pubkey::new_unique();
+        assert_eq!(read_token_account(&classic.account_info()).unwrap_err(), error!(OracleError::InvalidTokenAccount));
+        assert!(matches!(decode_token_account(&spl_token::ID, &extended.data), Err(OracleError::InvalidTokenAccount)));
+    }
+}
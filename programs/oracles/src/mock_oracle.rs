@@ -0,0 +1,76 @@
+//! Localnet price injection, compiled in only with the `mock-oracle` feature.
+//!
+//! # Never enable `mock-oracle` for a deployment anything of value reads
+//!
+//! `set_mock_price` lets the admin write any price and APY straight into `PriceData`,
+//! skipping every feed, staleness, change limit and emergency stop check. It exists so
+//! downstream programs can be tested on localnet, where there is no Switchboard aggregator
+//! to crank. A production build must be made with default features; the instruction then
+//! does not exist in the binary at all.
+//!
+//! Anchor 0.28 cannot compile an instruction of the `#[program]` module conditionally, so
+//! `set_mock_price` is dispatched from the program's fallback and never appears in the IDL,
+//! not even in `mock-oracle` builds. Build it with `client::set_mock_price_ix`.
+use std::collections::{BTreeMap, BTreeSet};
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hash;
+use crate::price_oracle::{AssetType, PriceOracle, PriceOracleData, PriceOracleHeader, Role};
+
+/// Arguments of `set_mock_price`, Borsh-encoded after its discriminator
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SetMockPriceArgs {
+    pub asset_type: AssetType,
+    /// Fixed point with `PRICE_DECIMALS` decimals, in the asset's current quote currency
+    pub price: u64,
+    /// Fixed point with `PRICE_DECIMALS` decimals
+    pub apy: u64,
+}
+
+#[derive(Accounts)]
+pub struct SetMockPrice<'info> {
+    #[account(
+        mut,
+        seeds = [PriceOracle::HEADER_SEED, header.instance_seed()],
+        bump = header.bump,
+    )]
+    pub header: Account<'info, PriceOracleHeader>,
+    #[account(
+        mut,
+        seeds = [PriceOracle::DATA_SEED, header.instance_seed()],
+        bump = data.bump,
+    )]
+    pub data: Account<'info, PriceOracleData>,
+    pub authority: Signer<'info>,
+}
+
+/// Discriminator of `set_mock_price`, derived like the ones of regular instructions
+pub fn set_mock_price_discriminator() -> [u8; 8] {
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&hash(b"global:set_mock_price").to_bytes()[..8]);
+    discriminator
+}
+
+/// Runs `set_mock_price` when `data` is one, `None` for any other instruction
+pub fn dispatch<'info>(program_id: &Pubkey, accounts: &[AccountInfo<'info>], data: &[u8]) -> Option<Result<()>> {
+    if data.len() < 8 || data[..8] != set_mock_price_discriminator() {
+        return None;
+    }
+    Some(process_set_mock_price(program_id, accounts, &data[8..]))
+}
+
+fn process_set_mock_price<'info>(program_id: &Pubkey, accounts: &[AccountInfo<'info>], ix_data: &[u8]) -> Result<()> {
+    let args = SetMockPriceArgs::deserialize(&mut &ix_data[..])
+        .map_err(|_| error!(anchor_lang::error::ErrorCode::InstructionDidNotDeserialize))?;
+    let mut remaining = accounts;
+    let mut bumps = BTreeMap::new();
+    let mut reallocs = BTreeSet::new();
+    let mut ctx_accounts = SetMockPrice::try_accounts(program_id, &mut remaining, ix_data, &mut bumps, &mut reallocs)?;
+    PriceOracle::require_role(&ctx_accounts.header, ctx_accounts.authority.key, Role::Admin)?;
+
+    let clock = crate::current_clock()?;
+    let nonce_before = ctx_accounts.data.nonce;
+    PriceOracle::set_mock_price(&mut ctx_accounts.data, args.asset_type, args.price, args.apy, &clock)?;
+    crate::record_data_change(&mut ctx_accounts.header, &ctx_accounts.data, nonce_before, &clock);
+    msg!("MOCK price for {:?} set to {} (APY {})", args.asset_type, args.price, args.apy);
+    ctx_accounts.exit(program_id)
+}
@@ -0,0 +1,93 @@
+//! One valuation for a set of token accounts, such as the LSTs a vault holds.
+//!
+//! `get_portfolio_value` reads each SPL Token or Token-2022 account passed as a remaining
+//! account, finds the asset whose mint `set_asset_mint` registered for the account's mint,
+//! and values the balance at the asset's USD price with the mint's decimals, SOL-quoted
+//! prices converted at the SOL price. The positions are summed in u128 and truncated once
+//! each, before the sum, so the total is exactly the sum of the reported values.
+//!
+//! Every position must be usable as for `get_collateral_value`, without the haircut. A
+//! position whose mint is unknown, or whose asset is unpriced, delisted, paused or stale,
+//! fails the valuation with `UnknownMint`, `PriceNotAvailable`, `AssetDelisted`,
+//! `AssetPaused` or `StaleData`. With `skip_unpriced`, such a position is reported with
+//! that error code and a zero value instead, and left out of the total. Anything that is
+//! not a token account fails with `InvalidTokenAccount` either way.
+use anchor_lang::prelude::*;
+use crate::price_oracle::{AssetType, OracleError};
+
+/// Most token accounts one valuation takes, so the breakdown fits the 1 KiB of return data
+pub const MAX_PORTFOLIO_POSITIONS: usize = 10;
+
+/// Errors `skip_unpriced` reports on the position instead of failing the valuation
+pub const SKIPPABLE_POSITION_ERRORS: [OracleError; 5] = [
+    OracleError::UnknownMint,
+    OracleError::PriceNotAvailable,
+    OracleError::AssetDelisted,
+    OracleError::AssetPaused,
+    OracleError::StaleData,
+];
+
+/// One token account of a valuation
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct PortfolioPosition {
+    pub token_account: Pubkey,
+    pub mint: Pubkey,
+    /// Asset registered for `mint`, `None` when no asset is
+    pub asset_type: Option<AssetType>,
+    /// Base units held
+    pub amount: u64,
+    /// USD value with 9 decimals, `0` when skipped
+    pub value: u64,
+    /// `0` when valued, otherwise the `OracleError` code the position was skipped with
+    pub error_code: u32,
+}
+
+impl PortfolioPosition {
+    /// Serialized size
+    pub const LEN: usize = 32 // token_account
+        + 32 // mint
+        + 2 // asset_type
+        + 8 // amount
+        + 8 // value
+        + 4; // error_code
+
+    pub fn skipped(&self) -> bool {
+        self.error_code != 0
+    }
+}
+
+/// What `get_portfolio_value` returns
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Debug, Default)]
+pub struct PortfolioValue {
+    /// Sum of the positions' values, USD with 9 decimals
+    pub total: u64,
+    /// One per token account, in the order they were passed
+    pub positions: Vec<PortfolioPosition>,
+}
+
+/// Whether `skip_unpriced` skips a position failing with `error_code`
+pub fn is_skippable(error_code: u32) -> bool {
+    SKIPPABLE_POSITION_ERRORS.iter().any(|error| u32::from(*error) == error_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_largest_breakdown_fits_the_return_data() {
+        let position = PortfolioPosition { asset_type: Some(AssetType::MSOL), error_code: u32::MAX, ..PortfolioPosition::default() };
+        assert_eq!(position.try_to_vec().unwrap().len(), PortfolioPosition::LEN);
+        let value = PortfolioValue { total: u64::MAX, positions: vec![position; MAX_PORTFOLIO_POSITIONS] };
+        assert!(value.try_to_vec().unwrap().len() <= anchor_lang::solana_program::program::MAX_RETURN_DATA);
+    }
+
+    #[test]
+    fn test_only_pricing_errors_are_skippable() {
+        assert!(is_skippable(u32::from(OracleError::UnknownMint)));
+        assert!(is_skippable(u32::from(OracleError::StaleData)));
+        assert!(!is_skippable(u32::from(OracleError::InvalidTokenAccount)));
+        assert!(!is_skippable(u32::from(OracleError::ConversionOverflow)));
+        assert!(!is_skippable(0));
+    }
+}
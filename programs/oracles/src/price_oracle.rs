@@ -1,32 +1,653 @@
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::clock;
+use anchor_lang::solana_program::hash::hash;
 use std::convert::TryInto;
-use switchboard_v2::AggregatorAccountData;
-use crate::switchboard_utils::{get_multi_asset_result, get_sol_price, MultiAssetResult, SwitchboardResult, DEVNET_AGGREGATOR_PUBKEY, SOL_PRICE_AGGREGATOR_PUBKEY};
+use std::ops::Range;
+use crate::asset_info::AssetInfo;
+use crate::attestation::PriceAttestation;
+use crate::audit_log::{AuditEntry, AuditLog};
+use crate::basket::{Basket, BasketWeight, BASKET_TOTAL_WEIGHT_BPS};
+use crate::checkpoint::{Checkpoint, CHECKPOINT_MIN_CLOSE_AGE_SECS};
+use crate::chainlink_utils::get_chainlink_price;
+use crate::pyth_utils::{get_pyth_price, PYTH_SOL_USD_FEED_ID};
+use crate::epoch_snapshots::{EpochSnapshot, EpochSnapshots};
+use crate::marinade_utils::get_marinade_price;
+use crate::mint_utils::{read_mint, read_token_account, MintInfo};
+use crate::portfolio::{is_skippable, PortfolioPosition, PortfolioValue, MAX_PORTFOLIO_POSITIONS};
+use crate::quote_buffer::PushedQuote;
+use crate::stake_pool_utils::get_stake_pool_price;
+use crate::invariants;
+use crate::history::{simple_return_volatility_bps, ApyHistory, AssetApyHistory, AssetHistory, PriceHistory, PriceObservation, MIN_VOLATILITY_OBSERVATIONS};
+use crate::switchboard_utils::{fixed_to_f64, median, AggregatorFeed, get_history_median, get_lease_balance, get_multi_asset_result, get_sol_price, get_pull_feed_price, get_validated_feed_result, MultiAssetResult, PRICE_SCALE, SwitchboardResult, DEVNET_AGGREGATOR_PUBKEY, SOL_PRICE_AGGREGATOR_PUBKEY};
+use crate::thresholds::{PriceThresholds, ThresholdCrossing, ThresholdDirection};
+use crate::trust_score::{TrustScore, TrustWeights, DEFAULT_TRUST_WEIGHTS};
+use crate::yield_convention::{RawYield, YieldConfig};
 
 // Define constants
 const MAX_SWITCHBOARD_DATA_AGE: i64 = 300; // 5 minutes
-const PRICE_CHANGE_LIMIT: f64 = 0.20; // 20%
+/// Bounds accepted by `set_asset_max_age`
+pub const MIN_ASSET_MAX_AGE_SECS: u32 = 10;
+pub const MAX_ASSET_MAX_AGE_SECS: u32 = 86_400; // 1 day
+const PRICE_CHANGE_LIMIT_BPS: u32 = 2_000; // 20%
+/// Bounds accepted for `OracleConfig::price_change_limit_bps`
+pub const MIN_PRICE_CHANGE_LIMIT_BPS: u32 = 1;
+pub const MAX_PRICE_CHANGE_LIMIT_BPS: u32 = 10_000; // 100%
+const DEFAULT_CONFIG_TIMELOCK_SECS: u32 = 86_400; // 1 day
+/// Bounds accepted for `OracleConfig::timelock_secs`
+pub const MIN_CONFIG_TIMELOCK_SECS: u32 = 3_600; // 1 hour
+pub const MAX_CONFIG_TIMELOCK_SECS: u32 = 2_592_000; // 30 days
+const DEFAULT_MOVE_CONFIRM_DELAY_SECS: u32 = 300; // 5 minutes
+const DEFAULT_MOVE_CONFIRM_TOLERANCE_BPS: u32 = 100; // 1%
+/// Bounds accepted by `set_move_confirmation`
+pub const MAX_MOVE_CONFIRM_DELAY_SECS: u32 = 86_400; // 1 day
+pub const MAX_MOVE_CONFIRM_TOLERANCE_BPS: u32 = 1_000; // 10%
+/// Bounds accepted by `set_resume_grace`
+pub const MAX_RESUME_GRACE_SECS: u32 = 86_400; // 1 day
+pub const MIN_RESUME_GRACE_MULTIPLIER_BPS: u32 = 10_000; // 1x
+pub const MAX_RESUME_GRACE_MULTIPLIER_BPS: u32 = 100_000; // 10x
+const DEFAULT_NAV_TOLERANCE_BPS: u32 = 500; // 5%
+/// Upper bound for an asset's tolerance around its stake pool rate
+pub const MAX_NAV_TOLERANCE_BPS: u32 = 5_000; // 50%
+const DEFAULT_MIN_STOP_CHANGE_INTERVAL: i64 = 60; // 1 minute
+const HISTORY_MEDIAN_WINDOW: i64 = 3_600; // 1 hour
+const HISTORY_DEVIATION_LIMIT_BPS: u64 = 1_000; // 10%
+/// Maximum number of sources `update_asset_from_sources` aggregates for one asset
+pub const MAX_ASSET_SOURCES: usize = 3;
+/// Maximum number of keys that can approve resuming from an emergency stop
+pub const MAX_RESUME_APPROVERS: usize = 5;
+/// Maximum number of watcher programs allowed to stop the oracle through CPI
+pub const MAX_WATCHERS: usize = 4;
+/// How far ahead of the cluster clock a signed price may be timestamped, for clock drift
+pub const SIGNED_PRICE_MAX_CLOCK_SKEW_SECS: i64 = 30;
+/// Phrase `lock_oracle` must be called with, so the lock is never engaged by accident
+pub const LOCK_CONFIRMATION: &str = "lock oracle permanently";
+/// Number of `AssetType` variants, and length of every per-asset array
+pub const ASSET_COUNT: usize = 10;
+/// Positions of the multi-asset feed, each carrying a price and an APY
+pub const MULTI_ASSET_FEED_LEN: usize = 6;
+/// Aggregators the multi-asset feed can be sharded over besides the primary one
+pub const MAX_FEED_SHARDS: usize = 3;
+/// Bytes one asset takes in `PriceOracleData::state_encoding`: its id, price, APY and feed timestamp
+pub const STATE_ENTRY_LEN: usize = 1 + 8 + 8 + 8;
+/// Largest collateral haircut, which values the asset at zero
+pub const MAX_HAIRCUT_BPS: u16 = 10_000; // 100%
+/// Namespace of the oracle the program was first deployed with, whose PDAs have no
+/// instance seed
+pub const DEFAULT_INSTANCE: Pubkey = Pubkey::new_from_array([0; 32]);
 
-/// Represents the different types of assets supported by the oracle
+/// Represents the different types of assets supported by the oracle.
+///
+/// The discriminant is the asset's index in `PriceOracleData::price_data` and
+/// `AssetRegistry::assets`, and matches its Borsh encoding.
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[repr(u8)]
 pub enum AssetType {
-    JupSOL,
-    VSOL,
-    BSOL,
-    MSOL,
-    HSOL,
-    JitoSOL,
-    SOL,
+    JupSOL = 0,
+    VSOL = 1,
+    BSOL = 2,
+    MSOL = 3,
+    HSOL = 4,
+    JitoSOL = 5,
+    SOL = 6,
+    /// Sanctum Infinity
+    INF = 7,
+    /// Lido staked SOL
+    StSOL = 8,
+    BonkSOL = 9,
 }
 
-/// Represents the price data for an asset
+impl AssetType {
+    /// Every asset, in index order
+    pub const ALL: [AssetType; ASSET_COUNT] = [
+        AssetType::JupSOL,
+        AssetType::VSOL,
+        AssetType::BSOL,
+        AssetType::MSOL,
+        AssetType::HSOL,
+        AssetType::JitoSOL,
+        AssetType::SOL,
+        AssetType::INF,
+        AssetType::StSOL,
+        AssetType::BonkSOL,
+    ];
+
+    /// Assets carried by the multi-asset feed, in its order, until the admin applies a
+    /// `FeedMapping`. Assets added later are only priced from their registered feeds.
+    pub const MULTI_ASSET_FEED: [AssetType; MULTI_ASSET_FEED_LEN] = [
+        AssetType::JupSOL,
+        AssetType::VSOL,
+        AssetType::BSOL,
+        AssetType::MSOL,
+        AssetType::HSOL,
+        AssetType::JitoSOL,
+    ];
+
+    /// Position of the asset in the per-asset arrays
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Quote currency of the asset's feed: the LSTs are priced in SOL, SOL itself in USD
+    pub fn default_quote(self) -> QuoteCurrency {
+        match self {
+            AssetType::SOL => QuoteCurrency::USD,
+            _ => QuoteCurrency::SOL,
+        }
+    }
+
+    /// Decimals of the asset's token amounts; SOL and every supported LST mint use 9
+    pub fn decimals(self) -> u8 {
+        9
+    }
+
+    /// Ticker the asset trades under
+    pub fn symbol(self) -> &'static str {
+        match self {
+            AssetType::JupSOL => "JupSOL",
+            AssetType::VSOL => "vSOL",
+            AssetType::BSOL => "bSOL",
+            AssetType::MSOL => "mSOL",
+            AssetType::HSOL => "hSOL",
+            AssetType::JitoSOL => "JitoSOL",
+            AssetType::SOL => "SOL",
+            AssetType::INF => "INF",
+            AssetType::StSOL => "stSOL",
+            AssetType::BonkSOL => "bonkSOL",
+        }
+    }
+}
+
+impl TryFrom<u8> for AssetType {
+    type Error = OracleError;
+
+    fn try_from(value: u8) -> std::result::Result<Self, Self::Error> {
+        AssetType::ALL
+            .get(value as usize)
+            .copied()
+            .ok_or(OracleError::InvalidAssetType)
+    }
+}
+
+impl From<AssetType> for u8 {
+    fn from(asset_type: AssetType) -> u8 {
+        asset_type as u8
+    }
+}
+
+/// Currency an asset's price is expressed in
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+#[repr(u8)]
+pub enum QuoteCurrency {
+    USD = 0,
+    #[default]
+    SOL = 1,
+}
+
+/// Oracle network an asset's registered feed belongs to
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[repr(u8)]
+pub enum SourceKind {
+    #[default]
+    Switchboard = 0,
+    Chainlink = 1,
+    /// A Pyth Solana Receiver `PriceUpdateV2` account
+    Pyth = 2,
+    /// A Switchboard On-Demand pull feed from the header's `on_demand_queue`
+    SwitchboardOnDemand = 3,
+}
+
+impl From<SourceKind> for u8 {
+    fn from(kind: SourceKind) -> u8 {
+        kind as u8
+    }
+}
+
+/// Program whose account gives an asset's intrinsic SOL value
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[repr(u8)]
+pub enum PoolKind {
+    /// An SPL stake pool account
+    #[default]
+    Spl = 0,
+    /// Marinade's `State` account, for mSOL
+    Marinade = 1,
+}
+
+/// What an update does with a move past the price change limit
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[repr(u8)]
+pub enum LargeMovePolicy {
+    /// Keep the stored price and trip the emergency stop
+    #[default]
+    Halt = 0,
+    /// Hold the price as pending and commit it once a later observation confirms it
+    Confirm = 1,
+    /// Store the price moved by the limit toward the observation and keep serving, so
+    /// consecutive updates walk it to the feed at the limited rate
+    Clamp = 2,
+}
+
+/// How `update_asset_from_sources` turns an asset's registered sources into its price
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[repr(u8)]
+pub enum SourcePolicy {
+    /// Store the median of the fresh sources, at least `min_sources` of them
+    #[default]
+    Median = 0,
+    /// Store the value of the first acceptable source in registration order
+    Priority = 1,
+}
+
+/// A move past the price change limit waiting for confirmation
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct PendingPrice {
+    /// Candidate price, `0` when nothing is pending
+    pub price: u64,
+    /// Time the candidate was observed
+    pub observed_at: i64,
+}
+
+impl PendingPrice {
+    /// Serialized size
+    pub const LEN: usize = 8 // price
+        + 8; // observed_at
+
+    pub fn is_pending(&self) -> bool {
+        self.price != 0
+    }
+}
+
+/// Why an asset's latest observation was rejected, see `PriceOracleHeader::last_error`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct LastError {
+    /// `OracleError` code the observation was rejected with, `0` when none is recorded
+    pub code: u32,
+    /// Time of the rejection
+    pub time: i64,
+}
+
+impl LastError {
+    /// Serialized size
+    pub const LEN: usize = 4 // code
+        + 8; // time
+
+    pub fn is_set(&self) -> bool {
+        self.code != 0
+    }
+}
+
+/// Market premium of a liquid staking token over the intrinsic value of its stake pool
+/// share, see `PriceOracleHeader::premium`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct LstPremium {
+    /// `(feed_price / intrinsic_rate - 1) * 10_000`, truncated toward zero; negative for a
+    /// discount
+    pub premium_bps: i64,
+    /// Price the asset's feed reported
+    pub feed_price: u64,
+    /// Value of one token by its stake pool rate, in the asset's quote currency: SOL-quoted
+    /// assets take the rate as is, USD-quoted ones convert it at the stored SOL price
+    pub intrinsic_rate: u64,
+    /// Time of the update that computed it, `0` before the first
+    pub time: i64,
+}
+
+impl LstPremium {
+    /// Serialized size
+    pub const LEN: usize = 8 // premium_bps
+        + 8 // feed_price
+        + 8 // intrinsic_rate
+        + 8; // time
+
+    /// The premium of `feed_price` over `intrinsic_rate`, observed at `time`
+    pub fn between(feed_price: u64, intrinsic_rate: u64, time: i64) -> Self {
+        let magnitude_bps = change_magnitude_bps(intrinsic_rate, feed_price)
+            .map_or(0, |bps| i64::try_from(bps).unwrap_or(i64::MAX));
+        let premium_bps = if feed_price < intrinsic_rate { -magnitude_bps } else { magnitude_bps };
+        LstPremium { premium_bps, feed_price, intrinsic_rate, time }
+    }
+}
+
+/// What the price change check decided for one observation
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MoveVerdict {
+    /// Within the limit, or confirming the pending move: write the price
+    Accept,
+    /// Past the limit under `LargeMovePolicy::Confirm`: hold it as the new pending move
+    Hold,
+    /// Within tolerance of the pending move before its delay has elapsed: keep waiting
+    Wait,
+    /// Past the limit under `LargeMovePolicy::Halt`: keep the stored price and trip the stop
+    Halt,
+    /// Past the limit under `LargeMovePolicy::Clamp`: write the price clamped to the limit
+    Clamp,
+}
+
+/// Where a stored price came from, written with every accepted update
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct PriceProvenance {
+    /// Feed account the price was read from; the asset registry for a median of several sources
+    pub source_feed: Pubkey,
+    /// Slot of the source round the price was taken from; the oldest contributing round for a median
+    pub source_round_slot: u64,
+    /// `SourceKind` of the feed as `u8`, `PriceProvenance::MEDIAN`, `PriceProvenance::SIGNED_PUBLISHER`,
+    /// `PriceProvenance::FORCED` or `PriceProvenance::priority` of the serving level
+    pub source_kind: u8,
+}
+
+impl PriceProvenance {
+    /// Serialized size
+    pub const LEN: usize = 32 // source_feed
+        + 8 // source_round_slot
+        + 1; // source_kind
+
+    /// `source_kind` of a price aggregated by `update_asset_from_sources`
+    pub const MEDIAN: u8 = u8::MAX;
+
+    /// `source_kind` of a price written by `update_price_signed`
+    pub const SIGNED_PUBLISHER: u8 = u8::MAX - 1;
+
+    /// `source_kind` of a price written by `force_update`, past the change limit
+    pub const FORCED: u8 = u8::MAX - 2;
+
+    /// `source_kind` of a price served by the primary source under `SourcePolicy::Priority`;
+    /// the fallbacks count down from it, see `priority`
+    pub const PRIORITY_PRIMARY: u8 = u8::MAX - 3;
+
+    /// `source_kind` of a price served by the source at `level` of the asset's priority
+    /// list, `0` being the primary
+    pub const fn priority(level: u8) -> u8 {
+        Self::PRIORITY_PRIMARY - level
+    }
+
+    /// Priority level of the source that served the price, `None` unless it was written
+    /// under `SourcePolicy::Priority`
+    pub fn priority_level(&self) -> Option<u8> {
+        let level = Self::PRIORITY_PRIMARY.checked_sub(self.source_kind)?;
+        (usize::from(level) < MAX_ASSET_SOURCES).then_some(level)
+    }
+
+    /// Provenance of a price read from a single feed
+    pub fn from_feed(kind: SourceKind, source_feed: Pubkey, source_round_slot: u64) -> Self {
+        PriceProvenance { source_feed, source_round_slot, source_kind: u8::from(kind) }
+    }
+
+    /// Provenance of a price signed by `publisher`, which has no round: `source_round_slot`
+    /// is the slot the price was written in
+    pub fn from_publisher(publisher: Pubkey, slot: u64) -> Self {
+        PriceProvenance { source_feed: publisher, source_round_slot: slot, source_kind: Self::SIGNED_PUBLISHER }
+    }
+
+    /// Whether this reading comes from the same source as the `stored` price and from a round
+    /// no newer than the one it was taken from, so writing it would only make stale data
+    /// look fresh. Signed prices have no round; their timestamps guard against replays.
+    pub fn repeats_round(&self, stored: &PriceProvenance) -> bool {
+        self.source_kind != Self::SIGNED_PUBLISHER
+            && self.source_kind == stored.source_kind
+            && self.source_feed == stored.source_feed
+            && self.source_round_slot <= stored.source_round_slot
+    }
+}
+
+/// How much weight `Confidence::value` carries
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[repr(u8)]
+pub enum ConfidenceStatus {
+    /// The source reports no spread for this price: encoded multi-asset and SOL results,
+    /// Chainlink feeds and medians of several sources
+    #[default]
+    Unavailable = 0,
+    /// `value` is the std deviation of the oracle responses in the round, or the
+    /// confidence interval of a Pyth price
+    Measured = 1,
+    /// The round reported zero deviation, typically a single-oracle round; says nothing
+    /// about consensus, so `value` is left at zero and must not be read as a tight spread
+    ZeroDeviation = 2,
+}
+
+/// Spread of the oracle responses behind a price, captured at update time
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Confidence {
+    /// Std deviation in the price's quote currency, fixed point; meaningful only when `Measured`
+    pub value: u64,
+    pub status: ConfidenceStatus,
+}
+
+impl Confidence {
+    /// Serialized size
+    pub const LEN: usize = 8 // value
+        + 1; // status
+
+    /// Confidence of a round that reported `std_deviation`, flagging a zero deviation
+    pub fn from_std_deviation(std_deviation: u64) -> Self {
+        if std_deviation == 0 {
+            Confidence { value: 0, status: ConfidenceStatus::ZeroDeviation }
+        } else {
+            Confidence { value: std_deviation, status: ConfidenceStatus::Measured }
+        }
+    }
+}
+
+/// A validated value read from a source, with the slot and time of the round it came from
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SourcePrice {
+    pub price: u64,
+    pub round_slot: u64,
+    /// Time the source produced the round, on its own clock
+    pub round_timestamp: i64,
+    pub confidence: Confidence,
+}
+
+/// A price together with the currency it is quoted in
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct QuotedPrice {
+    pub price: u64,
+    pub quote: QuoteCurrency,
+}
+
+/// Represents the price data for an asset.
+///
+/// `price`, `last_price`, `apy` and the watermarks are fixed point with `PRICE_DECIMALS` decimals.
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
 pub struct PriceData {
-    pub price: f64,
-    pub last_price: f64,
+    pub price: u64,
+    pub last_price: u64,
     pub last_update_time: i64,
-    pub apy: f64,
+    pub apy: u64,
+    pub last_update_slot: u64,
+    /// Time `last_price` was observed, `0` until the second observation
+    pub last_price_time: i64,
+    /// Currency `price` and `last_price` are expressed in
+    pub quote: QuoteCurrency,
+    /// Where `price` came from; default until the first update
+    pub provenance: PriceProvenance,
+    /// Set by `delist_asset`; the asset is no longer updated or served, only its history
+    pub delisted: bool,
+    /// Spread of the round `price` was taken from
+    pub confidence: Confidence,
+    /// Highest accepted price since the last reset, `0` until the first update after it
+    pub high_watermark: u64,
+    pub high_watermark_time: i64,
+    /// Lowest accepted price since the last reset, `0` until the first update after it
+    pub low_watermark: u64,
+    pub low_watermark_time: i64,
+    /// Time and slot a feed round last confirmed `price`, by writing it or by reporting it
+    /// unchanged, on our clock. Keepers watch it for liveness; `last_update_time` is when the
+    /// price last changed.
+    pub last_checked_time: i64,
+    pub last_checked_slot: u64,
+    /// Time the source produced the round that last confirmed `price`, on its own clock.
+    /// Staleness in seconds is measured from here, so a keeper relaying an old round does
+    /// not make it look fresh.
+    pub feed_timestamp: i64,
+}
+
+impl PriceData {
+    /// Serialized size
+    pub const LEN: usize = 8 // price
+        + 8 // last_price
+        + 8 // last_update_time
+        + 8 // apy
+        + 8 // last_update_slot
+        + 8 // last_price_time
+        + 1 // quote
+        + PriceProvenance::LEN // provenance
+        + 1 // delisted
+        + Confidence::LEN // confidence
+        + 8 // high_watermark
+        + 8 // high_watermark_time
+        + 8 // low_watermark
+        + 8 // low_watermark_time
+        + 8 // last_checked_time
+        + 8 // last_checked_slot
+        + 8; // feed_timestamp
+
+    /// Returns true when the source produced the stored value longer than `max_age` before
+    /// `clock`; in slots, when it was last confirmed longer ago
+    pub fn is_stale(&self, clock: &Clock, max_age: MaxAge) -> bool {
+        max_age.is_exceeded(self.feed_timestamp, self.last_checked_slot, clock)
+    }
+
+    /// Seconds since the source produced the stored value, zero when `clock` is behind it
+    pub fn feed_age_secs(&self, clock: &Clock) -> u64 {
+        clock.unix_timestamp.saturating_sub(self.feed_timestamp).max(0) as u64
+    }
+
+    /// Returns true when `price` and, when given, `apy` are the stored values, so writing
+    /// them would only move the timestamps
+    pub fn is_unchanged(&self, price: u64, apy: Option<u64>) -> bool {
+        self.last_update_time != 0 && self.price == price && apy.unwrap_or(self.apy) == self.apy
+    }
+
+    /// Returns true when the price was written in the slot of `clock`, possibly earlier in
+    /// the reading transaction
+    pub fn updated_in_slot(&self, clock: &Clock) -> bool {
+        self.last_update_slot == clock.slot
+    }
+
+    /// Moves the current observation into `last_price` and stores `price` as observed at `clock`
+    /// from a round the source produced at `feed_timestamp`
+    fn record_price(&mut self, price: u64, provenance: PriceProvenance, confidence: Confidence, feed_timestamp: i64, clock: &Clock) {
+        self.last_price = self.price;
+        self.last_price_time = self.last_update_time;
+        self.price = price;
+        self.confidence = confidence;
+        self.last_update_time = clock.unix_timestamp;
+        self.last_update_slot = clock.slot;
+        self.record_check(provenance, feed_timestamp, clock);
+        if price > self.high_watermark {
+            self.high_watermark = price;
+            self.high_watermark_time = clock.unix_timestamp;
+        }
+        if self.low_watermark == 0 || price < self.low_watermark {
+            self.low_watermark = price;
+            self.low_watermark_time = clock.unix_timestamp;
+        }
+    }
+
+    /// Records that the round behind `provenance`, produced at `feed_timestamp`, confirmed the
+    /// stored price at `clock`, leaving the price, its update time and the watermarks alone
+    fn record_check(&mut self, provenance: PriceProvenance, feed_timestamp: i64, clock: &Clock) {
+        self.provenance = provenance;
+        self.last_checked_time = clock.unix_timestamp;
+        self.last_checked_slot = clock.slot;
+        self.feed_timestamp = feed_timestamp;
+    }
+
+    /// Highest and lowest accepted prices, `None` when none was accepted since the last reset
+    pub fn watermarks(&self) -> Option<Watermarks> {
+        if self.high_watermark_time == 0 {
+            return None;
+        }
+        Some(Watermarks {
+            high: self.high_watermark,
+            high_time: self.high_watermark_time,
+            low: self.low_watermark,
+            low_time: self.low_watermark_time,
+            quote: self.quote,
+        })
+    }
+
+    /// Change between the two most recent observations, `None` before the second one
+    pub fn price_change(&self) -> Option<PriceChange> {
+        if self.last_price_time == 0 {
+            return None;
+        }
+        Some(PriceChange::between(self.last_price, self.last_price_time, self.price, self.last_update_time, self.quote))
+    }
+
+    /// Switches the quote currency and forgets the prices observed in the old one, watermarks
+    /// included, so the next observation is treated as the first and bypasses the change limit
+    fn set_quote(&mut self, quote: QuoteCurrency) {
+        *self = PriceData { apy: self.apy, quote, delisted: self.delisted, ..PriceData::default() };
+    }
+}
+
+/// Change between two observations of an asset, returned by `get_price_change`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PriceChange {
+    pub previous_price: u64,
+    pub current_price: u64,
+    /// `|current_price - previous_price|`, fixed point
+    pub absolute_delta: u64,
+    /// Signed change relative to `previous_price` in basis points, truncated toward zero
+    pub change_bps: i64,
+    pub previous_update_time: i64,
+    pub current_update_time: i64,
+    pub quote: QuoteCurrency,
+}
+
+impl PriceChange {
+    pub fn between(
+        previous_price: u64,
+        previous_update_time: i64,
+        current_price: u64,
+        current_update_time: i64,
+        quote: QuoteCurrency,
+    ) -> Self {
+        let absolute_delta = previous_price.abs_diff(current_price);
+        let magnitude_bps = change_magnitude_bps(previous_price, current_price)
+            .map_or(i64::MAX, |bps| i64::try_from(bps).unwrap_or(i64::MAX));
+        let change_bps = if current_price < previous_price { -magnitude_bps } else { magnitude_bps };
+        PriceChange {
+            previous_price,
+            current_price,
+            absolute_delta,
+            change_bps,
+            previous_update_time,
+            current_update_time,
+            quote,
+        }
+    }
+}
+
+/// Highest and lowest accepted prices of an asset, as returned by `get_watermarks`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Watermarks {
+    pub high: u64,
+    pub high_time: i64,
+    pub low: u64,
+    pub low_time: i64,
+    pub quote: QuoteCurrency,
+}
+
+/// Maximum tolerated age of an observation, expressed either in wall-clock
+/// seconds or in slots. Cluster timestamps can drift, so slot-based checks
+/// are available for consumers that reason in slots.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MaxAge {
+    Seconds(i64),
+    Slots(u64),
+}
+
+impl MaxAge {
+    /// Returns true when an observation made at `observed_time`/`observed_slot` is too old at `clock`
+    pub fn is_exceeded(&self, observed_time: i64, observed_slot: u64, clock: &Clock) -> bool {
+        match *self {
+            MaxAge::Seconds(max_age) => clock.unix_timestamp.saturating_sub(observed_time) > max_age,
+            MaxAge::Slots(max_age) => clock.slot.saturating_sub(observed_slot) > max_age,
+        }
+    }
 }
 
 /// Represents the header information for the price oracle
@@ -38,205 +659,8776 @@ pub struct PriceOracleHeader {
     pub authority: Pubkey,
     pub switchboard_program_id: Pubkey,
     pub bump: u8,
+    /// When non-zero, feed staleness in the update path is measured in slots instead of seconds
+    pub max_feed_age_slots: u64,
+    pub last_stop_change_time: i64,
+    pub stop_change_count: u64,
+    /// Minimum number of seconds between two emergency stop toggles unless forced
+    pub min_stop_change_interval: i64,
+    /// Day-to-day key for soft parameters and pausing; `Pubkey::default()` when unset
+    pub operator: Pubkey,
+    /// Set once by `lock_oracle` and never cleared; freezes feeds and configuration
+    pub locked: bool,
+    /// Lease escrow balance below which updates emit `LeaseLow`; `0` disables the warning
+    pub min_lease_balance: u64,
+    /// Refuse updates whose supplied lease escrow is empty
+    pub reject_empty_lease: bool,
+    /// Kind of each asset's registered feed, indexed like `AssetType`
+    pub sources: [SourceKind; ASSET_COUNT],
+    /// Fixed-feed updates skip assets that fail per-asset validation instead of reverting
+    pub partial_updates: bool,
+    /// Error code the latest fixed-feed update rejected each asset with, `0` once it is written again
+    pub rejection_codes: [u32; ASSET_COUNT],
+    /// Per-asset staleness tolerance in seconds, `0` for `MAX_SWITCHBOARD_DATA_AGE`
+    pub max_age_secs: [u32; ASSET_COUNT],
+    /// Largest accepted move between two updates in basis points, `0` for `PRICE_CHANGE_LIMIT_BPS`
+    pub price_change_limit_bps: u32,
+    /// Delay before a proposed config or a stop clear takes effect, `0` for `DEFAULT_CONFIG_TIMELOCK_SECS`
+    pub config_timelock_secs: u32,
+    /// Config waiting for its timelock, if any
+    pub pending_config: PendingConfig,
+    /// Time from which `set_emergency_stop(false)` clears the stop, `0` when no clear is scheduled
+    pub stop_clear_effective_at: i64,
+    /// Monitoring key that may stop the oracle and pause assets but never resume them,
+    /// `Pubkey::default()` for none
+    pub emergency_council: Pubkey,
+    /// Assets whose updates are suspended by `pause_asset` until `resume_asset`
+    pub paused: [bool; ASSET_COUNT],
+    /// Keys whose `approve_resume` votes count towards `resume_threshold`
+    pub resume_approvers: [Pubkey; MAX_RESUME_APPROVERS],
+    /// Approvals `execute_resume` needs, `0` to let the admin clear every stop alone
+    pub resume_threshold: u8,
+    /// Manual stops need approvals too instead of being clearable by the admin alone
+    pub manual_resume_needs_approval: bool,
+    /// Id of the current or latest stop, incremented each time the oracle is stopped
+    pub stop_incident: u64,
+    /// The current or latest stop was tripped by the price change breaker
+    pub stop_automatic: bool,
+    /// Incident each approver last approved resuming from, parallel to `resume_approvers`
+    pub resume_approvals: [u64; MAX_RESUME_APPROVERS],
+    /// Per-asset handling of moves past the price change limit
+    pub large_move_policy: [LargeMovePolicy; ASSET_COUNT],
+    /// Seconds a pending move must wait before an observation can confirm it,
+    /// `0` for `DEFAULT_MOVE_CONFIRM_DELAY_SECS`
+    pub move_confirm_delay_secs: u32,
+    /// How close in basis points a confirming observation must be to the pending move,
+    /// `0` for `DEFAULT_MOVE_CONFIRM_TOLERANCE_BPS`
+    pub move_confirm_tolerance_bps: u32,
+    /// Move each asset is holding for confirmation, indexed like `AssetType`
+    pub pending_prices: [PendingPrice; ASSET_COUNT],
+    /// Aggregate counters for dashboards, see `get_oracle_stats`
+    pub update_stats: UpdateStats,
+    /// Per-asset largest accepted APY move between two updates in basis points of the
+    /// stored APY, `0` for no limit
+    pub apy_change_limit_bps: [u32; ASSET_COUNT],
+    /// Key whose signed prices `update_price_signed` accepts, `Pubkey::default()` to disable it
+    pub trusted_publisher: Pubkey,
+    /// Signed timestamp of the last signed price consumed for each asset, against replays
+    pub signed_price_times: [i64; ASSET_COUNT],
+    /// Sequence of the latest `attest_prices` payload, `0` before the first
+    pub attestation_sequence: u64,
+    /// Per-asset share of its value `get_collateral_value` discounts, in basis points,
+    /// `0` for none
+    pub haircut_bps: [u16; ASSET_COUNT],
+    /// Set by `initialize_audit_log`; privileged instructions then fail unless they are
+    /// passed the audit log
+    pub audit_log_enabled: bool,
+    /// Set by `set_updates_paused` for maintenance: update instructions fail with
+    /// `UpdatesPaused` while reads keep serving the stored prices. Independent of
+    /// `emergency_stop`, which also halts reads.
+    pub updates_paused: bool,
+    /// Automation thread, such as a Clockwork thread PDA, allowed to sign updates next to
+    /// the operator and the admin. `Pubkey::default()` for none; it holds no other role.
+    pub thread_authority: Pubkey,
+    /// Asset written from each position of the multi-asset feed, see `multi_asset_feed`
+    pub feed_mapping: FeedMapping,
+    /// Mapping queued by `propose_feed_mapping` behind the config timelock
+    pub pending_feed_mapping: PendingFeedMapping,
+    /// Weights of `get_trust_score`, see `trust_weights`
+    pub trust_weights: TrustWeights,
+    /// Namespace of this oracle's PDAs, `DEFAULT_INSTANCE` for the original one
+    pub instance: Pubkey,
+    /// Feed value each asset's stored price was last clamped short of under
+    /// `LargeMovePolicy::Clamp`, `0` once an update stores the feed's own value
+    pub clamped_prices: [u64; ASSET_COUNT],
+    /// Token mint registered for each asset by `set_asset_mint`, `Pubkey::default()` when unset
+    pub asset_mints: [Pubkey; ASSET_COUNT],
+    /// Decimals read from each registered mint, see `decimals`
+    pub mint_decimals: [u8; ASSET_COUNT],
+    /// Convention each asset's feed reports its yield in, APY by default
+    pub yield_configs: [YieldConfig; ASSET_COUNT],
+    /// Each asset's yield as last stored, before conversion to the APY, see `raw_yield`
+    pub raw_yields: [RawYield; ASSET_COUNT],
+    /// Times `reinitialize_data` rebuilt the data account, whose nonce restarts each time
+    pub data_generation: u32,
+    /// Latest per-asset validation rejection of each asset, see `last_error`
+    pub last_errors: [LastError; ASSET_COUNT],
+    /// How `update_asset_from_sources` combines each asset's sources, a median by default
+    pub source_policies: [SourcePolicy; ASSET_COUNT],
+    /// Time the emergency stop was last cleared, `0` if it never was
+    pub resumed_at: i64,
+    /// Seconds after `resumed_at` during which the change limit is widened, `0` for none
+    pub resume_grace_secs: u32,
+    /// Factor in basis points the change limit is widened by during the grace period,
+    /// `20_000` doubling it
+    pub resume_grace_multiplier_bps: u32,
+    /// Bit `i` is set when the latest update instruction changed the stored price or APY
+    /// of the asset with index `i`, see `changed_assets`
+    pub last_changed_mask: u32,
+    /// Slot of the update instruction that set `last_changed_mask`
+    pub last_changed_slot: u64,
+    /// Programs allowed to stop the oracle with `trigger_emergency_stop_cpi`, empty slots
+    /// `Pubkey::default()`
+    pub watchers: [Pubkey; MAX_WATCHERS],
+    /// Watcher program that began the current stop incident, `Pubkey::default()` when the
+    /// stop came from a signer or the circuit breaker
+    pub stop_watcher: Pubkey,
+    /// Premium of each asset's feed price over its stake pool rate, recorded by updates
+    /// that are passed the pool, see `premium`
+    pub premiums: [LstPremium; ASSET_COUNT],
+    /// Aggregators carrying assets beyond the primary multi-asset feed, see `FeedShard`
+    pub feed_shards: [FeedShard; MAX_FEED_SHARDS],
+    /// Time the current stop incident began, `0` while the oracle runs or when the stop
+    /// predates the field
+    pub stop_started_at: i64,
+    /// Seconds spent stopped over every incident cleared so far, see `stopped_secs`
+    pub total_stopped_secs: u64,
+    /// Key allowed the asset-scoped admin actions on each asset besides the global
+    /// authority, `Pubkey::default()` for none, see `asset_authority`
+    pub asset_authorities: [Pubkey; ASSET_COUNT],
+    /// `PriceOracleData::state_hash` of the data account as the last instruction that
+    /// changed it left it; zero on a header from before the field until the next change
+    pub state_hash: [u8; 32],
+    /// Queue every Switchboard On-Demand feed must belong to; `Pubkey::default()` refuses
+    /// them all
+    pub on_demand_queue: Pubkey,
+    /// Fallback source proposed for each asset, see `propose_asset_fallback`
+    pub pending_fallbacks: [PendingFallback; ASSET_COUNT],
+    /// Shard proposed for each of `feed_shards`, see `propose_feed_shard`
+    pub pending_feed_shards: [PendingFeedShard; MAX_FEED_SHARDS],
 }
 
-/// Represents the data storage for the price oracle
-#[account]
-#[derive(Default)]
-pub struct PriceOracleData {
-    pub price_data: [PriceData; 7], // 6 assets + SOL
-    pub bump: u8,
-}
+impl PriceOracleHeader {
+    /// Serialized size, excluding the 8-byte account discriminator
+    pub const LEN: usize = 8 // last_global_update
+        + 1 // emergency_stop
+        + 32 // authority
+        + 32 // switchboard_program_id
+        + 1 // bump
+        + 8 // max_feed_age_slots
+        + 8 // last_stop_change_time
+        + 8 // stop_change_count
+        + 8 // min_stop_change_interval
+        + 32 // operator
+        + 1 // locked
+        + 8 // min_lease_balance
+        + 1 // reject_empty_lease
+        + ASSET_COUNT // sources
+        + 1 // partial_updates
+        + 4 * ASSET_COUNT // rejection_codes
+        + 4 * ASSET_COUNT // max_age_secs
+        + 4 // price_change_limit_bps
+        + 4 // config_timelock_secs
+        + PendingConfig::LEN // pending_config
+        + 8 // stop_clear_effective_at
+        + 32 // emergency_council
+        + ASSET_COUNT // paused
+        + 32 * MAX_RESUME_APPROVERS // resume_approvers
+        + 1 // resume_threshold
+        + 1 // manual_resume_needs_approval
+        + 8 // stop_incident
+        + 1 // stop_automatic
+        + 8 * MAX_RESUME_APPROVERS // resume_approvals
+        + ASSET_COUNT // large_move_policy
+        + 4 // move_confirm_delay_secs
+        + 4 // move_confirm_tolerance_bps
+        + PendingPrice::LEN * ASSET_COUNT // pending_prices
+        + UpdateStats::LEN // update_stats
+        + 4 * ASSET_COUNT // apy_change_limit_bps
+        + 32 // trusted_publisher
+        + 8 * ASSET_COUNT // signed_price_times
+        + 8 // attestation_sequence
+        + 2 * ASSET_COUNT // haircut_bps
+        + 1 // audit_log_enabled
+        + 1 // updates_paused
+        + 32 // thread_authority
+        + FeedMapping::LEN // feed_mapping
+        + PendingFeedMapping::LEN // pending_feed_mapping
+        + TrustWeights::LEN // trust_weights
+        + 32 // instance
+        + 8 * ASSET_COUNT // clamped_prices
+        + 32 * ASSET_COUNT // asset_mints
+        + ASSET_COUNT // mint_decimals
+        + YieldConfig::LEN * ASSET_COUNT // yield_configs
+        + RawYield::LEN * ASSET_COUNT // raw_yields
+        + 4 // data_generation
+        + LastError::LEN * ASSET_COUNT // last_errors
+        + ASSET_COUNT // source_policies
+        + 8 // resumed_at
+        + 4 // resume_grace_secs
+        + 4 // resume_grace_multiplier_bps
+        + 4 // last_changed_mask
+        + 8 // last_changed_slot
+        + 32 * MAX_WATCHERS // watchers
+        + 32 // stop_watcher
+        + LstPremium::LEN * ASSET_COUNT // premiums
+        + FeedShard::LEN * MAX_FEED_SHARDS // feed_shards
+        + 8 // stop_started_at
+        + 8 // total_stopped_secs
+        + 32 * ASSET_COUNT // asset_authorities
+        + 32 // state_hash
+        + 32 // on_demand_queue
+        + PendingFallback::LEN * ASSET_COUNT // pending_fallbacks
+        + PendingFeedShard::LEN * MAX_FEED_SHARDS; // pending_feed_shards
 
-/// Main struct for the Price Oracle
-pub struct PriceOracle;
+    /// Seed following the family seed in this oracle's PDAs, see `PriceOracle::instance_seed`
+    pub fn instance_seed(&self) -> &[u8] {
+        PriceOracle::instance_seed(&self.instance)
+    }
 
-impl PriceOracle {
-    pub const HEADER_SEED: &'static [u8] = b"price_oracle_header";
-    pub const DATA_SEED: &'static [u8] = b"price_oracle_data";
+    /// Whether the asset's stored price is a clamped step short of its feed
+    pub fn is_clamped(&self, asset_type: AssetType) -> bool {
+        self.clamped_prices[asset_type.index()] != 0
+    }
 
-    /// Initializes the price oracle
-    pub fn initialize(
-        header: &mut Account<PriceOracleHeader>,
-        data: &mut Account<PriceOracleData>,
-        authority: &Signer,
-        switchboard_program_id: Pubkey,
-        header_bump: u8,
-        data_bump: u8,
-    ) -> Result<()> {
-        header.last_global_update = 0;
-        header.emergency_stop = false;
-        header.authority = authority.key();
-        header.switchboard_program_id = switchboard_program_id;
-        header.bump = header_bump;
+    /// Weights of `get_trust_score`, `DEFAULT_TRUST_WEIGHTS` until the admin sets them
+    pub fn trust_weights(&self) -> TrustWeights {
+        if self.trust_weights.is_set() {
+            self.trust_weights
+        } else {
+            DEFAULT_TRUST_WEIGHTS
+        }
+    }
+
+    /// Assets of the multi-asset feed in its order, as mapped by the admin
+    pub fn multi_asset_feed(&self) -> [AssetType; MULTI_ASSET_FEED_LEN] {
+        self.feed_mapping.assets()
+    }
+
+    /// Registered shard reading `feed`, with its number, see `FeedShard`
+    pub fn feed_shard(&self, feed: &Pubkey) -> Option<(u8, &FeedShard)> {
+        self.feed_shards.iter().enumerate()
+            .find(|(_, shard)| shard.is_set() && shard.feed == *feed)
+            .map(|(index, shard)| (index as u8 + 1, shard))
+    }
+
+    /// Assets the registered shards carry, besides those of the primary feed
+    pub fn sharded_assets(&self) -> Vec<AssetType> {
+        self.feed_shards.iter().flat_map(FeedShard::asset_types).collect()
+    }
 
-        data.price_data = core::array::from_fn(|_| PriceData::default());
-        data.bump = data_bump;
+    /// Kind of the asset's registered feed
+    pub fn source(&self, asset_type: AssetType) -> SourceKind {
+        self.sources[asset_type.index()]
+    }
 
+    /// Fails with `WrongSourceKind` unless every asset in `assets` is sourced from `source`
+    pub fn require_source(&self, assets: &[AssetType], source: SourceKind) -> Result<()> {
+        if let Some(asset_type) = assets.iter().find(|asset_type| self.source(**asset_type) != source) {
+            msg!("{:?} is sourced from {:?}, not {:?}", asset_type, self.source(*asset_type), source);
+            return Err(error!(OracleError::WrongSourceKind));
+        }
         Ok(())
     }
 
-    /// Updates the prices and APYs for all assets
-    pub fn update_prices_and_apys(
-        header: &mut Account<PriceOracleHeader>,
-        data: &mut Account<PriceOracleData>,
-        feed: &AccountLoader<AggregatorAccountData>,
-        clock: &Clock
-    ) -> Result<()> {
-        if header.emergency_stop {
-            msg!("Emergency stop is activated. Update aborted.");
-            return Err(error!(OracleError::EmergencyStop));
+    /// Whether the asset was rejected by the latest fixed-feed update that covered it
+    pub fn is_rejected(&self, asset_type: AssetType) -> bool {
+        self.rejection_codes[asset_type.index()] != 0
+    }
+
+    /// Staleness tolerance of the asset in seconds
+    pub fn max_age_secs(&self, asset_type: AssetType) -> i64 {
+        match self.max_age_secs[asset_type.index()] {
+            0 => MAX_SWITCHBOARD_DATA_AGE,
+            secs => secs as i64,
         }
+    }
 
-        let multi_asset_result = get_multi_asset_result(feed)?;
-        let current_time = clock.unix_timestamp;
+    /// Staleness policy applied to the asset's feeds in the update path and to its stored
+    /// price in `get_all_prices`; a non-zero `max_feed_age_slots` overrides it for every asset
+    pub fn feed_max_age(&self, asset_type: AssetType) -> MaxAge {
+        if self.max_feed_age_slots > 0 {
+            MaxAge::Slots(self.max_feed_age_slots)
+        } else {
+            MaxAge::Seconds(self.max_age_secs(asset_type))
+        }
+    }
 
-        for (i, asset_type) in AssetType::iter().enumerate() {
-            if asset_type == AssetType::SOL {
-                continue; // SOL is handled separately
-            }
+    pub fn is_paused(&self, asset_type: AssetType) -> bool {
+        self.paused[asset_type.index()]
+    }
 
-            let new_price = multi_asset_result.prices[i];
-            let new_apy = multi_asset_result.apys[i];
+    pub fn large_move_policy(&self, asset_type: AssetType) -> LargeMovePolicy {
+        self.large_move_policy[asset_type.index()]
+    }
 
-            let price_data = &mut data.price_data[i];
-            let price_change = (new_price - price_data.price).abs() / price_data.price;
-            if price_change > PRICE_CHANGE_LIMIT {
-                msg!("Price change exceeds 20% limit for {:?}. Old price: {}, New price: {}", asset_type, price_data.price, new_price);
-                header.emergency_stop = true;
-                return Err(error!(OracleError::PriceChangeExceedsLimit));
-            }
+    pub fn source_policy(&self, asset_type: AssetType) -> SourcePolicy {
+        self.source_policies[asset_type.index()]
+    }
 
-            price_data.last_price = price_data.price;
-            price_data.price = new_price;
-            price_data.apy = new_apy;
-            price_data.last_update_time = current_time;
-            msg!("Price and APY updated for {:?}. New price: {}, New APY: {}", asset_type, new_price, new_apy);
+    /// Largest accepted APY move of the asset in basis points, `None` when unlimited
+    pub fn apy_change_limit_bps(&self, asset_type: AssetType) -> Option<u32> {
+        match self.apy_change_limit_bps[asset_type.index()] {
+            0 => None,
+            bps => Some(bps),
         }
+    }
 
-        header.last_global_update = current_time;
-        Ok(())
+    /// Seconds a pending move waits before an observation can confirm it
+    pub fn move_confirm_delay_secs(&self) -> u32 {
+        match self.move_confirm_delay_secs {
+            0 => DEFAULT_MOVE_CONFIRM_DELAY_SECS,
+            secs => secs,
+        }
     }
 
-    /// Updates the SOL price
-    pub fn update_sol_price(
-        header: &mut Account<PriceOracleHeader>,
-        data: &mut Account<PriceOracleData>,
-        feed: &AccountLoader<AggregatorAccountData>,
-        clock: &Clock
-    ) -> Result<()> {
-        if header.emergency_stop {
-            msg!("Emergency stop is activated. SOL price update aborted.");
-            return Err(error!(OracleError::EmergencyStop));
+    /// Largest distance in basis points between a confirming observation and the pending move
+    pub fn move_confirm_tolerance_bps(&self) -> u32 {
+        match self.move_confirm_tolerance_bps {
+            0 => DEFAULT_MOVE_CONFIRM_TOLERANCE_BPS,
+            bps => bps,
         }
+    }
 
-        let sol_price_result = get_sol_price(feed)?;
-        let new_price = sol_price_result.value;
-        let current_time = clock.unix_timestamp;
+    pub fn haircut_bps(&self, asset_type: AssetType) -> u16 {
+        self.haircut_bps[asset_type.index()]
+    }
 
-        let price_data = &mut data.price_data[6]; // SOL is the last element
-        let price_change = (new_price - price_data.price).abs() / price_data.price;
-        if price_change > PRICE_CHANGE_LIMIT {
-            msg!("SOL price change exceeds 20% limit. Old price: {}, New price: {}", price_data.price, new_price);
-            header.emergency_stop = true;
-            return Err(error!(OracleError::PriceChangeExceedsLimit));
-        }
+    /// Convention the asset's feed reports its yield in
+    pub fn yield_config(&self, asset_type: AssetType) -> YieldConfig {
+        self.yield_configs[asset_type.index()]
+    }
 
-        price_data.last_price = price_data.price;
-        price_data.price = new_price;
-        price_data.last_update_time = current_time;
-        msg!("SOL price updated. New price: {}", new_price);
+    /// The asset's yield as last stored, in the convention it was reported in. A header
+    /// migrated from before conventions holds none until the asset's next update.
+    pub fn raw_yield(&self, asset_type: AssetType) -> RawYield {
+        self.raw_yields[asset_type.index()]
+    }
 
-        header.last_global_update = current_time;
-        Ok(())
+    /// Latest per-asset validation rejection of the asset that the rejecting instruction
+    /// survived, cleared by the next update that writes or confirms its price without
+    /// rejecting part of the observation
+    pub fn last_error(&self, asset_type: AssetType) -> LastError {
+        self.last_errors[asset_type.index()]
     }
 
-    /// Gets the current price for a specific asset
-    pub fn get_current_price(data: &Account<PriceOracleData>, asset_type: AssetType) -> Result<f64> {
-        let index = asset_type as usize;
-        data.price_data.get(index)
-            .map(|price_data| price_data.price)
-            .ok_or_else(|| error!(OracleError::PriceNotAvailable))
+    /// Records that an observation of the asset was rejected with `error_code` at `now`
+    pub fn record_asset_error(&mut self, asset_type: AssetType, error_code: u32, now: i64) {
+        self.last_errors[asset_type.index()] = LastError { code: error_code, time: now };
     }
 
-    /// Gets the current APY for a specific asset
-    pub fn get_current_apy(data: &Account<PriceOracleData>, asset_type: AssetType) -> Result<f64> {
-        let index = asset_type as usize;
-        data.price_data.get(index)
-            .map(|price_data| price_data.apy)
-            .ok_or_else(|| error!(OracleError::ApyNotAvailable))
+    pub fn clear_asset_error(&mut self, asset_type: AssetType) {
+        self.last_errors[asset_type.index()] = LastError::default();
     }
 
-    /// Checks if emergency stop is activated
-    pub fn is_emergency_stopped(header: &Account<PriceOracleHeader>) -> bool {
-        header.emergency_stop
+    /// Premium of the asset's feed over its stake pool rate as of the latest update passed
+    /// the pool; `time` is `0` when none was
+    pub fn premium(&self, asset_type: AssetType) -> LstPremium {
+        self.premiums[asset_type.index()]
     }
 
-    /// Sets the emergency stop status
-    pub fn set_emergency_stop(header: &mut Account<PriceOracleHeader>, stop: bool) {
-        header.emergency_stop = stop;
+    /// Records the premium of `feed_price` over `intrinsic_rate`, the asset's stake pool
+    /// rate in its quote currency, at `now`
+    pub fn record_premium(&mut self, asset_type: AssetType, feed_price: u64, intrinsic_rate: u64, now: i64) {
+        self.premiums[asset_type.index()] = LstPremium::between(feed_price, intrinsic_rate, now);
     }
 
-    /// Gets the PDA for the price oracle header
-    pub fn get_price_oracle_header_pda(program_id: &Pubkey) -> (Pubkey, u8) {
-        Pubkey::find_program_address(&[Self::HEADER_SEED], program_id)
+    /// Decimals of the asset's token amounts: those of its registered mint, otherwise
+    /// `AssetType::decimals`
+    pub fn decimals(&self, asset_type: AssetType) -> u8 {
+        if self.asset_mints[asset_type.index()] == Pubkey::default() {
+            asset_type.decimals()
+        } else {
+            self.mint_decimals[asset_type.index()]
+        }
     }
 
-    /// Gets the PDA for the price oracle data
-    pub fn get_price_oracle_data_pda(program_id: &Pubkey) -> (Pubkey, u8) {
-        Pubkey::find_program_address(&[Self::DATA_SEED], program_id)
+    /// Asset `set_asset_mint` registered `mint` for, if any
+    pub fn asset_for_mint(&self, mint: &Pubkey) -> Option<AssetType> {
+        if *mint == Pubkey::default() {
+            return None;
+        }
+        AssetType::iter().find(|asset_type| self.asset_mints[asset_type.index()] == *mint)
     }
-}
 
-/// Custom error types for the Oracle
-#[error_code]
-pub enum OracleError {
-    #[msg("Unauthorized access")]
-    UnauthorizedAccess,
-    #[msg("Invalid asset type")]
-    InvalidAssetType,
-    #[msg("Data not available")]
-    DataNotAvailable,
-    #[msg("Invalid account data")]
-    InvalidAccountData,
-    #[msg("Price not available")]
-    PriceNotAvailable,
-    #[msg("APY not available")]
-    ApyNotAvailable,
-    #[msg("Price change exceeds 20% limit")]
-    PriceChangeExceedsLimit,
-    #[msg("Emergency stop activated")]
-    EmergencyStop,
-    #[msg("Invalid Switchboard account")]
-    InvalidSwitchboardAccount,
-    #[msg("Stale data")]
-    StaleData,
-    #[msg("Invalid Switchboard data")]
-    InvalidSwitchboardData,
-}
+    /// Stops the oracle under a new incident id begun at `now`, unless it is already
+    /// stopped, so a repeated stop neither counts a new incident nor moves its start
+    pub fn begin_stop_incident(&mut self, automatic: bool, now: i64) {
+        if self.emergency_stop {
+            return;
+        }
+        self.emergency_stop = true;
+        self.stop_incident = self.stop_incident.saturating_add(1);
+        self.stop_automatic = automatic;
+        self.stop_watcher = Pubkey::default();
+        self.stop_started_at = now;
+    }
 
-/// Helper trait to iterate over AssetType
-trait AssetTypeIter {
-    fn iter() -> impl Iterator<Item = AssetType>;
-}
+    /// Trips the circuit breaker on a move of `asset_type` past the change limit at `now`: an
+    /// automatic stop that counts as a stop change, with the rejection recorded for the asset.
+    /// The update carries on to succeed, so both are kept.
+    pub fn trip_breaker(&mut self, asset_type: AssetType, now: i64) {
+        self.begin_stop_incident(true, now);
+        self.last_stop_change_time = now;
+        self.stop_change_count = self.stop_change_count.saturating_add(1);
+        let error_code = u32::from(OracleError::PriceChangeExceedsLimit);
+        self.rejection_codes[asset_type.index()] = error_code;
+        self.count_rejection(error_code);
+        self.record_asset_error(asset_type, error_code, now);
+    }
 
-impl AssetTypeIter for AssetType {
-    fn iter() -> impl Iterator<Item = AssetType> {
-        [
-            AssetType::JupSOL,
-            AssetType::VSOL,
-            AssetType::BSOL,
-            AssetType::MSOL,
-            AssetType::HSOL,
-            AssetType::JitoSOL,
-            AssetType::SOL,
-        ].into_iter()
+    /// Clears the stop at `now`, adding the incident's duration to `total_stopped_secs`,
+    /// and returns that duration. A stop begun before `stop_started_at` existed has no
+    /// known start and counts for nothing.
+    pub fn end_stop_incident(&mut self, now: i64) -> u64 {
+        let duration = self.stop_duration(now);
+        self.emergency_stop = false;
+        self.resumed_at = now;
+        self.stop_started_at = 0;
+        self.total_stopped_secs = self.total_stopped_secs.saturating_add(duration);
+        duration
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Seconds the current stop incident has lasted at `now`, `0` while the oracle runs
+    pub fn stop_duration(&self, now: i64) -> u64 {
+        if !self.emergency_stop || self.stop_started_at == 0 {
+            return 0;
+        }
+        now.saturating_sub(self.stop_started_at).max(0) as u64
+    }
+
+    /// Seconds spent stopped up to `now`, the current incident included
+    pub fn stopped_secs(&self, now: i64) -> u64 {
+        self.total_stopped_secs.saturating_add(self.stop_duration(now))
+    }
+
+    /// Key allowed the asset-scoped admin actions on `asset_type` besides the global authority
+    pub fn asset_authority(&self, asset_type: AssetType) -> Option<Pubkey> {
+        Some(self.asset_authorities[asset_type.index()]).filter(|authority| *authority != Pubkey::default())
+    }
 
-    // Add tests here as needed
-}
\ No newline at end of file
+    /// Whether `signer` is the asset authority of any asset
+    pub fn is_asset_authority(&self, signer: &Pubkey) -> bool {
+        *signer != Pubkey::default() && self.asset_authorities.contains(signer)
+    }
+
+    /// Whether `program_id` is on the watcher allowlist
+    pub fn is_watcher(&self, program_id: &Pubkey) -> bool {
+        *program_id != Pubkey::default() && self.watchers.contains(program_id)
+    }
+
+    /// Whether clearing the current stop needs `resume_threshold` approvals
+    pub fn resume_needs_approval(&self) -> bool {
+        self.resume_threshold > 0 && (self.stop_automatic || self.manual_resume_needs_approval)
+    }
+
+    /// Approvals recorded against the current stop incident
+    pub fn resume_approval_count(&self) -> u8 {
+        self.resume_approvers.iter().zip(self.resume_approvals.iter())
+            .filter(|(approver, incident)| **approver != Pubkey::default() && **incident == self.stop_incident)
+            .count() as u8
+    }
+
+    /// Stamps an update that stored `updated` prices at `now`, keeping the longest gap
+    /// since the previous one in `update_stats`. The stamp never moves backwards.
+    pub fn record_global_update(&mut self, updated: usize, now: i64) {
+        let stats = &mut self.update_stats;
+        stats.successful_updates = stats.successful_updates.saturating_add(updated as u64);
+        if now < self.last_global_update {
+            return;
+        }
+        if self.last_global_update > 0 {
+            let gap = now.saturating_sub(self.last_global_update).max(0) as u64;
+            if gap > stats.longest_gap_secs {
+                stats.longest_gap_secs = gap;
+                stats.longest_gap_at = now;
+            }
+        }
+        self.last_global_update = now;
+    }
+
+    /// Counts a rejection an update records and carries on from, by its error code
+    pub fn count_rejection(&mut self, error_code: u32) {
+        let stats = &mut self.update_stats;
+        if error_code == u32::from(OracleError::StaleData) {
+            stats.rejected_stale = stats.rejected_stale.saturating_add(1);
+        } else if error_code == u32::from(OracleError::PriceChangeExceedsLimit) {
+            stats.rejected_change_limit = stats.rejected_change_limit.saturating_add(1);
+        }
+    }
+
+    /// Largest accepted move between two updates in basis points
+    pub fn price_change_limit_bps(&self) -> u32 {
+        match self.price_change_limit_bps {
+            0 => PRICE_CHANGE_LIMIT_BPS,
+            bps => bps,
+        }
+    }
+
+    /// End of the grace period after the latest resume, `None` when the stop was never
+    /// cleared or no grace is configured
+    pub fn resume_grace_ends_at(&self) -> Option<i64> {
+        if self.resumed_at == 0 || self.resume_grace_secs == 0 {
+            return None;
+        }
+        Some(self.resumed_at.saturating_add(self.resume_grace_secs as i64))
+    }
+
+    /// Whether an update at `now` falls in the grace period after the latest resume
+    pub fn in_resume_grace(&self, now: i64) -> bool {
+        self.resume_grace_ends_at().is_some_and(|ends_at| (self.resumed_at..ends_at).contains(&now))
+    }
+
+    /// Change limit an update at `now` is held to: `price_change_limit_bps`, widened by
+    /// `resume_grace_multiplier_bps` during the grace period after a resume
+    pub fn effective_price_change_limit_bps(&self, now: i64) -> u32 {
+        let limit_bps = self.price_change_limit_bps();
+        if !self.in_resume_grace(now) {
+            return limit_bps;
+        }
+        let widened = limit_bps as u64 * self.resume_grace_multiplier_bps.max(MIN_RESUME_GRACE_MULTIPLIER_BPS) as u64 / 10_000;
+        widened.min(u32::MAX as u64) as u32
+    }
+
+    /// Replaces `last_changed_mask` with the assets whose price or APY differs in `data` from
+    /// `before`, taken by `PriceOracleData::stored_values` ahead of an update run at `slot`
+    pub fn record_changed_assets(&mut self, before: &[(u64, u64); ASSET_COUNT], data: &PriceOracleData, slot: u64) {
+        let after = data.stored_values();
+        self.last_changed_mask = AssetType::iter()
+            .filter(|asset_type| after[asset_type.index()] != before[asset_type.index()])
+            .fold(0, |mask, asset_type| mask | 1 << asset_type.index());
+        self.last_changed_slot = slot;
+    }
+
+    /// Assets the latest update instruction changed, in index order
+    pub fn changed_assets(&self) -> Vec<AssetType> {
+        AssetType::iter().filter(|asset_type| self.last_changed_mask & (1 << asset_type.index()) != 0).collect()
+    }
+
+    /// Delay in seconds before a proposed config or a stop clear takes effect
+    pub fn config_timelock_secs(&self) -> u32 {
+        match self.config_timelock_secs {
+            0 => DEFAULT_CONFIG_TIMELOCK_SECS,
+            secs => secs,
+        }
+    }
+}
+
+/// Settings that can only change through `propose_config` and `apply_config`, so
+/// integrators get `timelock_secs` of notice before any of them moves
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct OracleConfig {
+    /// Largest accepted move between two updates in basis points
+    pub price_change_limit_bps: u32,
+    /// Delay before a later proposal takes effect; a change to it waits for the current delay
+    pub timelock_secs: u32,
+    /// Registered feed of each asset, indexed like `AssetType`; `Pubkey::default()` unregisters
+    pub feeds: [Pubkey; ASSET_COUNT],
+    /// Keys allowed to approve resuming from a stop; `Pubkey::default()` leaves a slot empty
+    pub resume_approvers: [Pubkey; MAX_RESUME_APPROVERS],
+    /// Approvals needed to resume, `0` to let the admin clear every stop alone
+    pub resume_threshold: u8,
+    /// Apply the threshold to manual stops as well as to breaker trips
+    pub manual_resume_needs_approval: bool,
+}
+
+impl OracleConfig {
+    /// Serialized size
+    pub const LEN: usize = 4 // price_change_limit_bps
+        + 4 // timelock_secs
+        + 32 * ASSET_COUNT // feeds
+        + 32 * MAX_RESUME_APPROVERS // resume_approvers
+        + 1 // resume_threshold
+        + 1; // manual_resume_needs_approval
+
+    /// Fails with `InvalidConfig` on out-of-range values, repeated approvers or a threshold
+    /// above the approver count, and `DuplicateFeed` on a feed proposed for two assets
+    pub fn validate(&self) -> Result<()> {
+        if !(MIN_PRICE_CHANGE_LIMIT_BPS..=MAX_PRICE_CHANGE_LIMIT_BPS).contains(&self.price_change_limit_bps) {
+            msg!("Price change limit must be between {} and {} bps", MIN_PRICE_CHANGE_LIMIT_BPS, MAX_PRICE_CHANGE_LIMIT_BPS);
+            return Err(error!(OracleError::InvalidConfig));
+        }
+        if !(MIN_CONFIG_TIMELOCK_SECS..=MAX_CONFIG_TIMELOCK_SECS).contains(&self.timelock_secs) {
+            msg!("Timelock must be between {}s and {}s", MIN_CONFIG_TIMELOCK_SECS, MAX_CONFIG_TIMELOCK_SECS);
+            return Err(error!(OracleError::InvalidConfig));
+        }
+        for (i, feed) in self.feeds.iter().enumerate() {
+            if *feed != Pubkey::default() && self.feeds[..i].contains(feed) {
+                msg!("Feed {} is proposed for more than one asset", feed);
+                return Err(error!(OracleError::DuplicateFeed));
+            }
+        }
+        for (i, approver) in self.resume_approvers.iter().enumerate() {
+            if *approver != Pubkey::default() && self.resume_approvers[..i].contains(approver) {
+                msg!("Resume approver {} is listed more than once", approver);
+                return Err(error!(OracleError::InvalidConfig));
+            }
+        }
+        let approvers = self.resume_approvers.iter().filter(|approver| **approver != Pubkey::default()).count();
+        if self.resume_threshold as usize > approvers {
+            msg!("Resume threshold {} exceeds the {} approver(s)", self.resume_threshold, approvers);
+            return Err(error!(OracleError::InvalidConfig));
+        }
+        Ok(())
+    }
+}
+
+/// Running counters kept in the header; each saturates instead of wrapping. Rejections that
+/// fail the whole update are rolled back with it, so only those an update carries on from count.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct UpdateStats {
+    /// Asset prices stored by the update instructions
+    pub successful_updates: u64,
+    /// Sources `update_asset_from_sources` discarded as stale
+    pub rejected_stale: u64,
+    /// Assets a partial fixed-feed update skipped for moving past the price change limit
+    pub rejected_change_limit: u64,
+    /// Longest time between two consecutive updates that stored a price, in seconds
+    pub longest_gap_secs: u64,
+    /// Time of the update that ended the longest gap, `0` before the second update
+    pub longest_gap_at: i64,
+}
+
+impl UpdateStats {
+    /// Serialized size
+    pub const LEN: usize = 8 // successful_updates
+        + 8 // rejected_stale
+        + 8 // rejected_change_limit
+        + 8 // longest_gap_secs
+        + 8; // longest_gap_at
+}
+
+/// Header counters as returned by `get_oracle_stats`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct OracleStats {
+    pub update_stats: UpdateStats,
+    /// Emergency stops begun so far, manual or tripped by the breaker
+    pub stop_incidents: u64,
+    pub last_global_update: i64,
+    /// Time the current stop incident began, `0` while the oracle runs
+    pub stopped_since: i64,
+    /// Seconds spent stopped so far, the current incident included
+    pub total_stopped_secs: u64,
+}
+
+/// A proposed `OracleConfig` and the time `apply_config` may activate it from
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct PendingConfig {
+    pub config: OracleConfig,
+    /// `0` when nothing is pending
+    pub effective_at: i64,
+}
+
+impl PendingConfig {
+    /// Serialized size
+    pub const LEN: usize = OracleConfig::LEN // config
+        + 8; // effective_at
+
+    pub fn is_pending(&self) -> bool {
+        self.effective_at != 0
+    }
+}
+
+/// One entry of a proposed `FeedMapping`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FeedPosition {
+    pub position: u8,
+    pub asset_type: AssetType,
+}
+
+/// Asset written from each position of the multi-asset feed
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FeedMapping {
+    /// Unset in headers from before the mapping, which follow `AssetType::MULTI_ASSET_FEED`
+    pub configured: bool,
+    pub assets: [AssetType; MULTI_ASSET_FEED_LEN],
+}
+
+impl Default for FeedMapping {
+    fn default() -> Self {
+        FeedMapping { configured: false, assets: AssetType::MULTI_ASSET_FEED }
+    }
+}
+
+impl FeedMapping {
+    /// Serialized size
+    pub const LEN: usize = 1 // configured
+        + MULTI_ASSET_FEED_LEN; // assets
+
+    /// Builds a mapping from entries naming every position once, each with a different
+    /// asset other than SOL, which has its own feed. Fails with `InvalidFeedMapping` otherwise.
+    pub fn from_positions(positions: &[FeedPosition]) -> Result<Self> {
+        let assets = map_feed_positions(positions)?;
+        if let Some(position) = assets.iter().position(Option::is_none) {
+            msg!("Position {} is not mapped", position);
+            return Err(error!(OracleError::InvalidFeedMapping));
+        }
+        Ok(FeedMapping { configured: true, assets: assets.map(Option::unwrap) })
+    }
+
+    /// The asset at each position of the feed
+    pub fn assets(&self) -> [AssetType; MULTI_ASSET_FEED_LEN] {
+        if self.configured {
+            self.assets
+        } else {
+            AssetType::MULTI_ASSET_FEED
+        }
+    }
+}
+
+/// The asset each entry names at its position, failing with `InvalidFeedMapping` when a
+/// position is past the feed or named twice, or an asset is SOL or named twice
+fn map_feed_positions(positions: &[FeedPosition]) -> Result<[Option<AssetType>; MULTI_ASSET_FEED_LEN]> {
+    let mut assets: [Option<AssetType>; MULTI_ASSET_FEED_LEN] = [None; MULTI_ASSET_FEED_LEN];
+    for entry in positions {
+        if entry.position as usize >= MULTI_ASSET_FEED_LEN {
+            msg!("Position {} is past the {} positions of the multi-asset feed", entry.position, MULTI_ASSET_FEED_LEN);
+            return Err(error!(OracleError::InvalidFeedMapping));
+        }
+        if assets[entry.position as usize].is_some() {
+            msg!("Position {} is mapped twice", entry.position);
+            return Err(error!(OracleError::InvalidFeedMapping));
+        }
+        if entry.asset_type == AssetType::SOL || assets.contains(&Some(entry.asset_type)) {
+            msg!("{:?} cannot be mapped to position {}", entry.asset_type, entry.position);
+            return Err(error!(OracleError::InvalidFeedMapping));
+        }
+        assets[entry.position as usize] = Some(entry.asset_type);
+    }
+    Ok(assets)
+}
+
+/// An aggregator publishing the multi-asset payload for assets of its own, registered with
+/// `propose_feed_shard`. Shard `0` is the primary feed with the header's `FeedMapping`; shard
+/// `n` is `feed_shards[n - 1]`. No asset is carried by two shards.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FeedShard {
+    /// `Pubkey::default()` for an unused slot
+    pub feed: Pubkey,
+    /// Asset at each position; only the positions in `mapped` are read
+    pub assets: [AssetType; MULTI_ASSET_FEED_LEN],
+    /// Bit `i` is set when position `i` is read
+    pub mapped: u8,
+}
+
+impl Default for FeedShard {
+    fn default() -> Self {
+        FeedShard { feed: Pubkey::default(), assets: AssetType::MULTI_ASSET_FEED, mapped: 0 }
+    }
+}
+
+impl FeedShard {
+    /// Serialized size
+    pub const LEN: usize = 32 // feed
+        + MULTI_ASSET_FEED_LEN // assets
+        + 1; // mapped
+
+    /// Builds a shard reading `feed` at the positions named, each with a different asset
+    /// other than SOL; the positions left out are not read. Fails with `InvalidFeedMapping`
+    /// as `FeedMapping::from_positions` does, and with `InvalidFeedShard` when no position is named.
+    pub fn from_positions(feed: Pubkey, positions: &[FeedPosition]) -> Result<Self> {
+        let mapped_assets = map_feed_positions(positions)?;
+        let mut shard = FeedShard { feed, ..FeedShard::default() };
+        for (position, asset_type) in mapped_assets.iter().enumerate() {
+            if let Some(asset_type) = asset_type {
+                shard.assets[position] = *asset_type;
+                shard.mapped |= 1 << position;
+            }
+        }
+        if shard.mapped == 0 {
+            msg!("A shard must map at least one position");
+            return Err(error!(OracleError::InvalidFeedShard));
+        }
+        Ok(shard)
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.mapped != 0
+    }
+
+    /// Read positions and their assets, in position order
+    pub fn positions(&self) -> Vec<(usize, AssetType)> {
+        (0..MULTI_ASSET_FEED_LEN)
+            .filter(|position| self.mapped & (1 << position) != 0)
+            .map(|position| (position, self.assets[position]))
+            .collect()
+    }
+
+    pub fn asset_types(&self) -> Vec<AssetType> {
+        self.positions().into_iter().map(|(_, asset_type)| asset_type).collect()
+    }
+}
+
+/// Freshness of one shard of the multi-asset feed, as `get_feed_shard_health` reports it
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Debug)]
+pub struct FeedShardHealth {
+    /// `0` for the primary feed, `1..=MAX_FEED_SHARDS` for the registered shards
+    pub shard: u8,
+    pub feed: Pubkey,
+    /// Assets the shard carries, in position order
+    pub assets: Vec<AssetType>,
+    /// Oldest round time among the assets the shard still writes, those neither delisted
+    /// nor paused; `0` when one was never written or there are none
+    pub oldest_feed_timestamp: i64,
+    /// Whether one of those assets is past its staleness tolerance
+    pub stale: bool,
+}
+
+/// A proposed `FeedMapping` and the time `apply_feed_mapping` may activate it from
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct PendingFeedMapping {
+    pub mapping: FeedMapping,
+    /// `0` when nothing is pending
+    pub effective_at: i64,
+}
+
+impl PendingFeedMapping {
+    /// Serialized size
+    pub const LEN: usize = FeedMapping::LEN // mapping
+        + 8; // effective_at
+
+    pub fn is_pending(&self) -> bool {
+        self.effective_at != 0
+    }
+}
+
+/// A proposed fallback source for one asset and the time `apply_asset_fallback` may
+/// register it from
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct PendingFallback {
+    /// Default when the proposal clears the fallback
+    pub fallback: SourceSlot,
+    /// `0` when nothing is pending
+    pub effective_at: i64,
+}
+
+impl PendingFallback {
+    /// Serialized size
+    pub const LEN: usize = SourceSlot::LEN // fallback
+        + 8; // effective_at
+
+    pub fn is_pending(&self) -> bool {
+        self.effective_at != 0
+    }
+
+    /// The proposed fallback, `None` when the proposal clears it
+    pub fn fallback(&self) -> Option<SourceSlot> {
+        (self.fallback.feed != Pubkey::default()).then_some(self.fallback)
+    }
+}
+
+/// A proposed feed shard and the time `apply_feed_shard` may register it from
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct PendingFeedShard {
+    /// Default when the proposal clears the shard
+    pub shard: FeedShard,
+    /// `0` when nothing is pending
+    pub effective_at: i64,
+}
+
+impl PendingFeedShard {
+    /// Serialized size
+    pub const LEN: usize = FeedShard::LEN // shard
+        + 8; // effective_at
+
+    pub fn is_pending(&self) -> bool {
+        self.effective_at != 0
+    }
+}
+
+/// Role a privileged instruction requires of its signer
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Role {
+    /// Only the admin authority: feeds, accounts, the operator and hard limits
+    Admin,
+    /// The operator or the admin: soft parameters
+    OperatorOrAdmin,
+    /// The emergency council, the operator or the admin: stopping the oracle and pausing assets
+    Guardian,
+    /// Keys allowed to push feed updates: the operator, the admin or the thread authority
+    Updater,
+}
+
+/// Represents the data storage for the price oracle
+#[account]
+#[derive(Default)]
+pub struct PriceOracleData {
+    pub price_data: [PriceData; ASSET_COUNT],
+    pub bump: u8,
+    /// Advanced once by every instruction that changes `price_data`, so a reader can tell
+    /// an unchanged account from one rewritten with the same values. Wraps at `u64::MAX`.
+    pub nonce: u64,
+}
+
+impl PriceOracleData {
+    /// Serialized size, excluding the 8-byte account discriminator
+    pub const LEN: usize = PriceData::LEN * ASSET_COUNT // price_data
+        + 1 // bump
+        + 8; // nonce
+
+    /// Data account as `initialize` writes it: every asset unpriced under its default quote
+    pub fn new(bump: u8) -> Self {
+        PriceOracleData {
+            price_data: AssetType::ALL.map(|asset_type| PriceData { quote: asset_type.default_quote(), ..PriceData::default() }),
+            bump,
+            nonce: 0,
+        }
+    }
+
+    /// Price and APY of every asset, for `PriceOracleHeader::record_changed_assets` to
+    /// compare with once an update has run
+    pub fn stored_values(&self) -> [(u64, u64); ASSET_COUNT] {
+        self.price_data.map(|price_data| (price_data.price, price_data.apy))
+    }
+
+    /// Records a change of `price_data` by the current instruction
+    pub fn advance_nonce(&mut self) {
+        self.nonce = self.nonce.wrapping_add(1);
+    }
+
+    /// Canonical encoding `state_hash` commits to: for every asset in index order, its id
+    /// as one byte followed by its price, APY and feed timestamp as little-endian 8-byte
+    /// integers. Delisted assets are encoded with the values this account holds for them.
+    pub fn state_encoding(&self) -> [u8; STATE_ENTRY_LEN * ASSET_COUNT] {
+        let mut encoding = [0u8; STATE_ENTRY_LEN * ASSET_COUNT];
+        for (entry, asset_type) in encoding.chunks_exact_mut(STATE_ENTRY_LEN).zip(AssetType::iter()) {
+            let price_data = &self.price_data[asset_type.index()];
+            entry[0] = asset_type as u8;
+            entry[1..9].copy_from_slice(&price_data.price.to_le_bytes());
+            entry[9..17].copy_from_slice(&price_data.apy.to_le_bytes());
+            entry[17..25].copy_from_slice(&price_data.feed_timestamp.to_le_bytes());
+        }
+        encoding
+    }
+
+    /// SHA-256 of `state_encoding`, the commitment to every stored price
+    pub fn state_hash(&self) -> [u8; 32] {
+        hash(&self.state_encoding()).to_bytes()
+    }
+
+    /// Stored data of an asset, failing with `AssetDelisted` once it has been delisted
+    pub fn listed(&self, asset_type: AssetType) -> Result<&PriceData> {
+        let price_data = &self.price_data[asset_type.index()];
+        if price_data.delisted {
+            msg!("{:?} is delisted", asset_type);
+            return Err(error!(OracleError::AssetDelisted));
+        }
+        Ok(price_data)
+    }
+}
+
+/// One of the feeds an asset's price is aggregated from
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct SourceSlot {
+    pub kind: SourceKind,
+    /// `Pubkey::default()` when the slot is unused
+    pub feed: Pubkey,
+}
+
+impl SourceSlot {
+    /// Serialized size
+    pub const LEN: usize = 1 // kind
+        + 32; // feed
+}
+
+/// Per-asset configuration kept in the asset registry
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct AssetConfig {
+    /// Dedicated single-value Switchboard feed for the asset, `Pubkey::default()` when unset
+    pub feed: Pubkey,
+    /// Feeds aggregated by `update_asset_from_sources`, used slots first
+    pub sources: [SourceSlot; MAX_ASSET_SOURCES],
+    /// Fresh sources `update_asset_from_sources` needs before it stores a median
+    pub min_sources: u8,
+    /// Stake pool whose exchange rate bounds the asset's price, `Pubkey::default()` when unset
+    pub stake_pool: Pubkey,
+    /// `0` for `DEFAULT_NAV_TOLERANCE_BPS`
+    pub nav_tolerance_bps: u32,
+    /// How `stake_pool` is read
+    pub stake_pool_kind: PoolKind,
+    /// Secondary feed `update_assets` reads when the asset's registered feed is stale,
+    /// `Pubkey::default()` when unset
+    pub fallback: SourceSlot,
+}
+
+impl AssetConfig {
+    /// Serialized size
+    pub const LEN: usize = 32 // feed
+        + SourceSlot::LEN * MAX_ASSET_SOURCES // sources
+        + 1 // min_sources
+        + 32 // stake_pool
+        + 4 // nav_tolerance_bps
+        + 1 // stake_pool_kind
+        + SourceSlot::LEN; // fallback
+
+    /// The registered source reading from `feed`, if any
+    pub fn source_for_feed(&self, feed: &Pubkey) -> Option<SourceSlot> {
+        if *feed == Pubkey::default() {
+            return None;
+        }
+        self.sources.iter().find(|slot| slot.feed == *feed).copied()
+    }
+
+    /// The registered fallback source, if any
+    pub fn fallback(&self) -> Option<SourceSlot> {
+        (self.fallback.feed != Pubkey::default()).then_some(self.fallback)
+    }
+
+    /// Whether `account` is the asset's registered stake pool
+    pub fn is_stake_pool(&self, account: &Pubkey) -> bool {
+        self.stake_pool != Pubkey::default() && self.stake_pool == *account
+    }
+
+    /// Largest deviation from the stake pool rate accepted, in basis points
+    pub fn nav_tolerance_bps(&self) -> u32 {
+        match self.nav_tolerance_bps {
+            0 => DEFAULT_NAV_TOLERANCE_BPS,
+            tolerance => tolerance,
+        }
+    }
+}
+
+/// Registry of per-asset feeds and configuration
+#[account]
+#[derive(Default)]
+pub struct AssetRegistry {
+    pub queue: Pubkey,
+    pub assets: [AssetConfig; ASSET_COUNT],
+    pub bump: u8,
+    /// Pyth feed id each asset's Pyth sources must carry, zero when none is bound
+    pub pyth_feed_ids: [[u8; 32]; ASSET_COUNT],
+}
+
+impl AssetRegistry {
+    /// Serialized size, excluding the 8-byte account discriminator
+    pub const LEN: usize = 32 // queue
+        + AssetConfig::LEN * ASSET_COUNT // assets
+        + 1 // bump
+        + 32 * ASSET_COUNT; // pyth_feed_ids
+
+    /// Pyth feed id the asset's Pyth sources must carry: the bound one, or for SOL the
+    /// SOL/USD feed when none is bound. All zeros, which no update matches, otherwise.
+    pub fn pyth_feed_id(&self, asset_type: AssetType) -> [u8; 32] {
+        let feed_id = self.pyth_feed_ids[asset_type.index()];
+        if feed_id == [0; 32] && asset_type == AssetType::SOL {
+            return PYTH_SOL_USD_FEED_ID;
+        }
+        feed_id
+    }
+
+    /// Finds the asset whose registered feed matches `feed`
+    pub fn asset_for_feed(&self, feed: &Pubkey) -> Option<AssetType> {
+        if *feed == Pubkey::default() {
+            return None;
+        }
+        AssetType::iter().find(|asset_type| self.assets[asset_type.index()].feed == *feed)
+    }
+
+    /// Finds the asset whose registered fallback source reads from `feed`
+    pub fn asset_for_fallback(&self, feed: &Pubkey) -> Option<AssetType> {
+        AssetType::iter().find(|asset_type| matches!(self.assets[asset_type.index()].fallback(), Some(slot) if slot.feed == *feed))
+    }
+
+    /// Finds the asset whose registered stake pool is `account`
+    pub fn asset_for_stake_pool(&self, account: &Pubkey) -> Option<AssetType> {
+        AssetType::iter().find(|asset_type| self.assets[asset_type.index()].is_stake_pool(account))
+    }
+}
+
+/// Would-be outcome of an update for a single asset, as reported by `preview_update`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Debug)]
+pub struct AssetUpdatePreview {
+    pub asset_type: AssetType,
+    pub accepted: bool,
+    /// `0` when accepted, otherwise the `OracleError` code the real update would fail with
+    pub reason_code: u32,
+    /// Fixed-point values that would be stored if accepted
+    pub price: u64,
+    pub apy: u64,
+}
+
+/// What `update_asset_from_sources` made of one supplied source
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SourceReading {
+    pub kind: SourceKind,
+    pub feed: Pubkey,
+    /// Fixed-point value read from the source, `0` when it was discarded
+    pub price: u64,
+    /// Slot of the source round the value came from, `0` when it was discarded
+    pub round_slot: u64,
+    /// Time the source produced that round, `0` when it was discarded
+    pub round_timestamp: i64,
+    /// Whether the value took part in the median, or served the price under
+    /// `SourcePolicy::Priority`
+    pub accepted: bool,
+    /// `0` when accepted, otherwise the `OracleError` code the source was discarded with
+    pub reason_code: u32,
+}
+
+impl SourceReading {
+    fn accepted(slot: &SourceSlot, source: &SourcePrice) -> Self {
+        SourceReading {
+            kind: slot.kind,
+            feed: slot.feed,
+            price: source.price,
+            round_slot: source.round_slot,
+            round_timestamp: source.round_timestamp,
+            accepted: true,
+            reason_code: 0,
+        }
+    }
+
+    fn discarded(slot: &SourceSlot, error: &Error) -> Self {
+        SourceReading {
+            kind: slot.kind,
+            feed: slot.feed,
+            price: 0,
+            round_slot: 0,
+            round_timestamp: 0,
+            accepted: false,
+            reason_code: error_code(error),
+        }
+    }
+}
+
+/// Outcome of `update_asset_from_sources`
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SourceAggregation {
+    /// Median of the accepted readings, or the serving source's value under
+    /// `SourcePolicy::Priority`, now the asset's price unless `pending`
+    pub price: u64,
+    /// The price was held as the asset's pending move instead of being stored
+    pub pending: bool,
+    /// The price moved past the change limit and tripped the emergency stop instead of
+    /// being stored
+    pub halted: bool,
+    /// Level of the source that served the price under `SourcePolicy::Priority`, `0`
+    /// for the primary; `None` for a median
+    pub priority_level: Option<u8>,
+    /// For a median, one reading per supplied source in the order supplied; under
+    /// `SourcePolicy::Priority`, one per source tried in priority order
+    pub readings: Vec<SourceReading>,
+}
+
+/// The value `update_asset_from_sources` selected under the asset's `SourcePolicy`
+struct SourceSelection {
+    price: u64,
+    confidence: Confidence,
+    provenance: PriceProvenance,
+    feed_timestamp: i64,
+    priority_level: Option<u8>,
+    readings: Vec<SourceReading>,
+    /// Stake pool rate the price was checked against, when the pool was supplied
+    intrinsic_rate: Option<u64>,
+}
+
+/// Assets an `update_assets` run wrote, those it only checked and those it skipped for
+/// repeating a round
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct UpdatedAssets {
+    /// Assets whose price was stored, in the order their feeds were supplied
+    pub updated: Vec<AssetType>,
+    /// Assets whose feed confirmed the stored price, so only their check time was written
+    pub unchanged: Vec<AssetType>,
+    /// Assets whose feed had not published a new round since their stored price
+    pub no_new_round: Vec<AssetType>,
+    /// Assets read from their fallback source because their registered feed was stale
+    pub failed_over: Vec<(AssetType, SourceSlot)>,
+}
+
+/// What a fixed-feed update did with one asset, as reported by `AssetsUpdated`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct AssetOutcome {
+    pub asset_type: AssetType,
+    pub updated: bool,
+    /// `0` when updated, otherwise the `OracleError` code the asset was rejected with
+    pub error_code: u32,
+    /// Incoming APY held back by the asset's APY change limit while its price was written,
+    /// or the yield as reported when it does not convert to an APY
+    pub rejected_apy: Option<u64>,
+    /// The feed confirmed the stored price and APY; only the check time was written
+    pub unchanged: bool,
+}
+
+/// One asset's stored price with the staleness policy in force, as returned by `get_all_prices`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct AssetPrice {
+    pub asset_type: AssetType,
+    pub price: u64,
+    pub quote: QuoteCurrency,
+    pub apy: u64,
+    pub last_update_time: i64,
+    pub last_update_slot: u64,
+    /// Time a feed last confirmed the price, on our clock
+    pub last_checked_time: i64,
+    /// Time the source produced the confirming round, which staleness is measured from
+    pub feed_timestamp: i64,
+    /// Staleness tolerance of the asset in seconds
+    pub max_age_secs: u32,
+    /// Never updated, or older than the asset's staleness policy
+    pub stale: bool,
+    pub delisted: bool,
+    pub confidence: Confidence,
+}
+
+/// An asset's price with the spread of the round behind it, as returned by
+/// `get_price_with_confidence` for risk engines sizing haircuts
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PriceWithConfidence {
+    pub price: u64,
+    pub quote: QuoteCurrency,
+    pub confidence: Confidence,
+    pub last_update_time: i64,
+    /// Time a feed last confirmed the price, on our clock
+    pub last_checked_time: i64,
+    /// Time the source produced the confirming round, which staleness is measured from
+    pub feed_timestamp: i64,
+}
+
+/// An asset's last stored price however old, as returned by `get_price_lenient` for
+/// display and analytics; `degraded` prices must not back liquidations or other value
+/// transfers, which should use `get_price_no_older_than` or `assert_price_fresh` instead
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LenientPrice {
+    pub price: u64,
+    pub quote: QuoteCurrency,
+    pub last_update_time: i64,
+    /// Time the source produced the round that last confirmed the price
+    pub feed_timestamp: i64,
+    /// Seconds since `feed_timestamp`, zero when the clock is behind it
+    pub staleness_secs: u64,
+    /// Older than the asset's staleness policy
+    pub degraded: bool,
+}
+
+/// Result of `preview_update`, returned via return data
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Debug)]
+pub struct UpdatePreview {
+    pub emergency_stop: bool,
+    pub updates_paused: bool,
+    pub assets: Vec<AssetUpdatePreview>,
+}
+
+/// One check `simulate_price_change` ran, with the threshold it applied
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct SimulatedCheck {
+    pub passed: bool,
+    /// `OracleError` code of the failure, `0` when it passed
+    pub error_code: u32,
+    /// Value the candidate was compared with: the stored price or APY, or the stake pool's
+    /// rate in the asset's quote
+    pub reference: u64,
+    /// Deviation from `reference` allowed in basis points, `0` for an APY without a limit
+    pub limit_bps: u32,
+}
+
+impl SimulatedCheck {
+    fn new(result: std::result::Result<(), OracleError>, reference: u64, limit_bps: u32) -> Self {
+        SimulatedCheck { passed: result.is_ok(), error_code: result.err().map_or(0, u32::from), reference, limit_bps }
+    }
+}
+
+/// Result of `simulate_price_change`, returned via return data
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PriceChangeSimulation {
+    pub asset_type: AssetType,
+    pub candidate_price: u64,
+    /// Whether an update reading the candidate would store its price
+    pub accepted: bool,
+    /// `0` when accepted, otherwise the `OracleError` code the update would report
+    pub reason_code: u32,
+    /// Code of what keeps the asset from being updated whatever its value: the emergency
+    /// stop, a maintenance pause, or the asset being delisted or paused. `0` when nothing
+    /// does. The checks below run regardless.
+    pub blocked_code: u32,
+    /// Price change limit against the stored price, at the limit in force
+    pub change_limit: SimulatedCheck,
+    /// What the asset's large move policy makes of the move
+    pub move_verdict: MoveVerdict,
+    /// Whether the candidate would trip the circuit breaker and stop the oracle
+    pub trips_breaker: bool,
+    /// Stake pool rate check, run when the pool account was passed
+    pub stake_pool: Option<SimulatedCheck>,
+    /// APY change limit against the stored APY, run when a candidate APY was given. A yield
+    /// that does not convert under the asset's `YieldConfig` fails it with the conversion's code.
+    pub apy_limit: Option<SimulatedCheck>,
+    /// Price and APY the asset would hold after the update
+    pub price: u64,
+    pub apy: u64,
+}
+
+/// Main struct for the Price Oracle
+pub struct PriceOracle;
+
+impl PriceOracle {
+    pub const HEADER_SEED: &'static [u8] = b"price_oracle_header";
+    pub const DATA_SEED: &'static [u8] = b"price_oracle_data";
+    pub const REGISTRY_SEED: &'static [u8] = b"asset_registry";
+    pub const HISTORY_SEED: &'static [u8] = b"price_history";
+    pub const APY_HISTORY_SEED: &'static [u8] = b"apy_history";
+    pub const EPOCH_SNAPSHOTS_SEED: &'static [u8] = b"epoch_snapshots";
+    pub const UPDATER_STATS_SEED: &'static [u8] = b"updater_stats";
+    pub const SUBSCRIBER_SEED: &'static [u8] = b"subscriber";
+    pub const AUDIT_LOG_SEED: &'static [u8] = b"audit_log";
+    pub const CHECKPOINT_SEED: &'static [u8] = b"checkpoint";
+    pub const THRESHOLDS_SEED: &'static [u8] = b"price_thresholds";
+    pub const BASKET_SEED: &'static [u8] = b"basket";
+    pub const QUOTE_BUFFER_SEED: &'static [u8] = b"quote_buffer";
+    /// Seed of the PDA, under the watcher program's own id, that signs a watcher's
+    /// `trigger_emergency_stop_cpi`
+    pub const WATCHER_SEED: &'static [u8] = b"oracle_watcher";
+
+    /// Seed following the family seed in the PDAs of the oracle `instance`. Empty for
+    /// `DEFAULT_INSTANCE`, which keeps the addresses from before instances existed.
+    pub fn instance_seed(instance: &Pubkey) -> &[u8] {
+        if *instance == DEFAULT_INSTANCE {
+            &[]
+        } else {
+            instance.as_ref()
+        }
+    }
+
+    /// Instance seed for reads that take the header optionally: the header's, or the
+    /// default instance's when it is omitted
+    pub fn optional_instance_seed<'a>(header: &'a Option<Box<Account<'_, PriceOracleHeader>>>) -> &'a [u8] {
+        header.as_deref().map_or(&[], |header| header.instance_seed())
+    }
+
+    /// Initializes the price oracle
+    pub fn initialize(
+        header: &mut PriceOracleHeader,
+        data: &mut PriceOracleData,
+        authority: &Signer,
+        switchboard_program_id: Pubkey,
+        instance: Pubkey,
+        header_bump: u8,
+        data_bump: u8,
+    ) -> Result<()> {
+        header.last_global_update = 0;
+        header.emergency_stop = false;
+        header.authority = authority.key();
+        header.switchboard_program_id = switchboard_program_id;
+        header.bump = header_bump;
+        header.max_feed_age_slots = 0;
+        header.last_stop_change_time = 0;
+        header.stop_change_count = 0;
+        header.min_stop_change_interval = DEFAULT_MIN_STOP_CHANGE_INTERVAL;
+        header.operator = Pubkey::default();
+        header.locked = false;
+        header.min_lease_balance = 0;
+        header.reject_empty_lease = false;
+        header.sources = [SourceKind::Switchboard; ASSET_COUNT];
+        header.partial_updates = false;
+        header.rejection_codes = [0; ASSET_COUNT];
+        header.max_age_secs = [MAX_SWITCHBOARD_DATA_AGE as u32; ASSET_COUNT];
+        header.price_change_limit_bps = PRICE_CHANGE_LIMIT_BPS;
+        header.config_timelock_secs = DEFAULT_CONFIG_TIMELOCK_SECS;
+        header.pending_config = PendingConfig::default();
+        header.stop_clear_effective_at = 0;
+        header.emergency_council = Pubkey::default();
+        header.paused = [false; ASSET_COUNT];
+        header.resume_approvers = [Pubkey::default(); MAX_RESUME_APPROVERS];
+        header.resume_threshold = 0;
+        header.manual_resume_needs_approval = false;
+        header.stop_incident = 0;
+        header.stop_automatic = false;
+        header.resume_approvals = [0; MAX_RESUME_APPROVERS];
+        header.feed_mapping = FeedMapping { configured: true, assets: AssetType::MULTI_ASSET_FEED };
+        header.pending_feed_mapping = PendingFeedMapping::default();
+        header.pending_fallbacks = [PendingFallback::default(); ASSET_COUNT];
+        header.pending_feed_shards = [PendingFeedShard::default(); MAX_FEED_SHARDS];
+        header.trust_weights = DEFAULT_TRUST_WEIGHTS;
+        header.instance = instance;
+
+        *data = PriceOracleData::new(data_bump);
+        header.state_hash = data.state_hash();
+
+        Ok(())
+    }
+
+    /// Updates the prices and APYs for all assets
+    pub fn update_prices_and_apys(
+        header: &mut PriceOracleHeader,
+        data: &mut PriceOracleData,
+        feed: &AggregatorFeed,
+        clock: &Clock
+    ) -> Result<Vec<AssetOutcome>> {
+        if header.emergency_stop {
+            msg!("Emergency stop is activated. Update aborted.");
+            return Err(error!(OracleError::EmergencyStop));
+        }
+        Self::require_updates_not_paused(header)?;
+
+        let outcomes = Self::apply_multi_asset_update(header, data, feed, 0..MULTI_ASSET_FEED_LEN, clock)?;
+
+        Self::finish_fixed_feed_update(header, data, outcomes, clock)
+    }
+
+    /// Positions `start_index..start_index + count` of the multi-asset feed, failing with
+    /// `InvalidUpdateChunk` when the range is empty or runs past the feed
+    pub fn multi_asset_feed_chunk(start_index: u8, count: u8) -> Result<Range<usize>> {
+        let start = start_index as usize;
+        let end = start + count as usize;
+        if count == 0 || end > MULTI_ASSET_FEED_LEN {
+            msg!("Chunk {}..{} is not within the {} positions of the multi-asset feed", start, end, MULTI_ASSET_FEED_LEN);
+            return Err(error!(OracleError::InvalidUpdateChunk));
+        }
+        Ok(start..end)
+    }
+
+    /// `update_prices_and_apys` restricted to `count` assets of the multi-asset feed from
+    /// position `start_index`, so a keeper can spread one round over several transactions.
+    ///
+    /// Each asset keeps its own check time. `last_global_update` only advances to the oldest
+    /// check time of the assets the feed writes, so it never claims an asset a later chunk
+    /// has yet to refresh. Chunks may overlap: an asset already written from the feed's
+    /// current round is skipped with `NoNewRound`, as in a full update.
+    pub fn update_prices_and_apys_chunk(
+        header: &mut PriceOracleHeader,
+        data: &mut PriceOracleData,
+        feed: &AggregatorFeed,
+        start_index: u8,
+        count: u8,
+        clock: &Clock
+    ) -> Result<Vec<AssetOutcome>> {
+        if header.emergency_stop {
+            msg!("Emergency stop is activated. Update aborted.");
+            return Err(error!(OracleError::EmergencyStop));
+        }
+        Self::require_updates_not_paused(header)?;
+        let positions = Self::multi_asset_feed_chunk(start_index, count)?;
+
+        let outcomes = Self::apply_multi_asset_update(header, data, feed, positions, clock)?;
+
+        Self::finish_feed_chunk_update(header, data, outcomes, clock)
+    }
+
+    /// Updates the SOL price from `feed`, cross-checking it against the feed's history buffer
+    /// when supplied. With `expected_price`, fails with `ExpectedPriceMismatch` when the feed
+    /// reads further than `tolerance_bps` from it.
+    pub fn update_sol_price(
+        header: &mut PriceOracleHeader,
+        data: &mut PriceOracleData,
+        feed: &AggregatorFeed,
+        history_buffer: Option<&AccountInfo>,
+        expected_price: Option<u64>,
+        tolerance_bps: u16,
+        clock: &Clock
+    ) -> Result<Vec<AssetOutcome>> {
+        if header.emergency_stop {
+            msg!("Emergency stop is activated. SOL price update aborted.");
+            return Err(error!(OracleError::EmergencyStop));
+        }
+        Self::require_updates_not_paused(header)?;
+
+        let outcome = Self::apply_sol_price_update(header, data, feed, history_buffer, expected_price, tolerance_bps, clock)?;
+
+        Self::finish_fixed_feed_update(header, data, vec![outcome], clock)
+    }
+
+    /// Updates the SOL price from a Chainlink feed registered among SOL's sources, a
+    /// secondary source next to the Switchboard SOL feed. It goes through the same checks
+    /// as `update_sol_price`, and the stored provenance records that Chainlink produced
+    /// the value.
+    pub fn update_sol_price_chainlink(
+        header: &mut PriceOracleHeader,
+        data: &mut PriceOracleData,
+        registry: &AssetRegistry,
+        feed_info: &AccountInfo,
+        expected_price: Option<u64>,
+        tolerance_bps: u16,
+        clock: &Clock
+    ) -> Result<Vec<AssetOutcome>> {
+        if header.emergency_stop {
+            msg!("Emergency stop is activated. SOL price update aborted.");
+            return Err(error!(OracleError::EmergencyStop));
+        }
+        Self::require_updates_not_paused(header)?;
+
+        if !matches!(registry.assets[AssetType::SOL.index()].source_for_feed(feed_info.key), Some(SourceSlot { kind: SourceKind::Chainlink, .. })) {
+            msg!("Account {} is not a registered Chainlink source of SOL", feed_info.key);
+            return Err(error!(OracleError::UnregisteredFeed));
+        }
+        let SourcePrice { price, round_slot, round_timestamp, .. } = get_chainlink_price(feed_info, clock, header.feed_max_age(AssetType::SOL))?;
+        Self::check_expected_price(AssetType::SOL, price, expected_price, tolerance_bps)?;
+        let provenance = PriceProvenance::from_feed(SourceKind::Chainlink, *feed_info.key, round_slot);
+        let outcome = Self::apply_asset_value(header, data, AssetType::SOL, price, None, provenance, round_timestamp, clock)?;
+
+        Self::finish_fixed_feed_update(header, data, vec![outcome], clock)
+    }
+
+    /// Updates the prices and APYs for all assets and the SOL price together,
+    /// so both land with a single `last_global_update`
+    pub fn update_all(
+        header: &mut PriceOracleHeader,
+        data: &mut PriceOracleData,
+        multi_asset_feed: &AggregatorFeed,
+        sol_feed: &AggregatorFeed,
+        sol_history_buffer: Option<&AccountInfo>,
+        clock: &Clock
+    ) -> Result<Vec<AssetOutcome>> {
+        if header.emergency_stop {
+            msg!("Emergency stop is activated. Update aborted.");
+            return Err(error!(OracleError::EmergencyStop));
+        }
+        Self::require_updates_not_paused(header)?;
+
+        let mut outcomes = Self::apply_multi_asset_update(header, data, multi_asset_feed, 0..MULTI_ASSET_FEED_LEN, clock)?;
+        outcomes.push(Self::apply_sol_price_update(header, data, sol_feed, sol_history_buffer, None, 0, clock)?);
+
+        Self::finish_fixed_feed_update(header, data, outcomes, clock)
+    }
+
+    /// Updates the assets of whichever multi-asset feed shards are supplied: the primary feed
+    /// with the header's mapping, and the aggregators registered with `propose_feed_shard`. A
+    /// feed that is neither fails with `UnregisteredFeed`, and one supplied twice with
+    /// `DuplicateFeed`. The update is stamped like a chunk, see `update_prices_and_apys_chunk`.
+    pub fn update_feed_shards(
+        header: &mut PriceOracleHeader,
+        data: &mut PriceOracleData,
+        feeds: &[AggregatorFeed],
+        clock: &Clock
+    ) -> Result<Vec<AssetOutcome>> {
+        if header.emergency_stop {
+            msg!("Emergency stop is activated. Update aborted.");
+            return Err(error!(OracleError::EmergencyStop));
+        }
+        Self::require_updates_not_paused(header)?;
+        if feeds.is_empty() {
+            msg!("No feed shard was supplied");
+            return Err(error!(OracleError::InvalidFeedShard));
+        }
+
+        // Every feed is checked before any is read
+        let mut shards = Vec::with_capacity(feeds.len());
+        for (index, feed) in feeds.iter().enumerate() {
+            if feeds[..index].iter().any(|other| other.key() == feed.key()) {
+                msg!("Feed shard {} was supplied more than once", feed.key());
+                return Err(error!(OracleError::DuplicateFeed));
+            }
+            let shard: Vec<(usize, AssetType)> = if feed.key() == DEVNET_AGGREGATOR_PUBKEY {
+                header.multi_asset_feed().into_iter().enumerate().collect()
+            } else {
+                match header.feed_shard(&feed.key()) {
+                    Some((_, shard)) => shard.positions(),
+                    None => {
+                        msg!("Account {} is not a shard of the multi-asset feed", feed.key());
+                        return Err(error!(OracleError::UnregisteredFeed));
+                    }
+                }
+            };
+            shards.push(shard);
+        }
+
+        let mut outcomes = Vec::new();
+        for (feed, shard) in feeds.iter().zip(&shards) {
+            outcomes.extend(Self::apply_shard_update(header, data, feed, shard, clock)?);
+        }
+
+        Self::finish_feed_chunk_update(header, data, outcomes, clock)
+    }
+
+    /// Freshness of the primary multi-asset feed, shard `0`, and of every registered shard
+    pub fn get_feed_shard_health(header: &PriceOracleHeader, data: &PriceOracleData, clock: &Clock) -> Vec<FeedShardHealth> {
+        let primary = (0, DEVNET_AGGREGATOR_PUBKEY, header.multi_asset_feed().to_vec());
+        let shards = header.feed_shards.iter().enumerate()
+            .filter(|(_, shard)| shard.is_set())
+            .map(|(index, shard)| (index as u8 + 1, shard.feed, shard.asset_types()));
+        std::iter::once(primary).chain(shards).map(|(shard, feed, assets)| {
+            let written: Vec<AssetType> = assets.iter().copied()
+                .filter(|asset_type| Self::shard_writes(header, data, *asset_type))
+                .collect();
+            FeedShardHealth {
+                shard,
+                feed,
+                oldest_feed_timestamp: written.iter().map(|asset_type| data.price_data[asset_type.index()].feed_timestamp).min().unwrap_or(0),
+                stale: written.iter().any(|asset_type| data.price_data[asset_type.index()].is_stale(clock, header.feed_max_age(*asset_type))),
+                assets,
+            }
+        }).collect()
+    }
+
+    /// Multi-asset feed assets last confirmed longer ago than their staleness tolerance, the
+    /// ones `refresh_if_stale` updates for. Assets the feed no longer writes, because they are
+    /// delisted or paused, never count.
+    pub fn stale_feed_assets(header: &PriceOracleHeader, data: &PriceOracleData, clock: &Clock) -> Vec<AssetType> {
+        header.multi_asset_feed()
+            .into_iter()
+            .filter(|asset_type| {
+                Self::shard_writes(header, data, *asset_type)
+                    && data.price_data[asset_type.index()].is_stale(clock, header.feed_max_age(*asset_type))
+            })
+            .collect()
+    }
+
+    /// Fails with `NoAssetUpdated` when every asset was rejected, otherwise stamps the update.
+    /// Moves held for confirmation and unchanged prices count as progress, so the pending
+    /// state and check times are kept. When no asset got past the round check, the update
+    /// fails with `NoNewRound` instead.
+    fn finish_fixed_feed_update(
+        header: &mut PriceOracleHeader,
+        data: &mut PriceOracleData,
+        outcomes: Vec<AssetOutcome>,
+        clock: &Clock,
+    ) -> Result<Vec<AssetOutcome>> {
+        Self::require_fixed_feed_progress(header, &outcomes)?;
+        let updated = outcomes.iter().filter(|outcome| outcome.updated).count();
+        if outcomes.iter().any(|outcome| outcome.updated || outcome.unchanged) {
+            header.record_global_update(updated, clock.unix_timestamp);
+            data.advance_nonce();
+        }
+        Ok(outcomes)
+    }
+
+    /// `finish_fixed_feed_update` for a chunk of the multi-asset feed, stamping the update
+    /// with `feed_confirmed_at` rather than the current time
+    fn finish_feed_chunk_update(
+        header: &mut PriceOracleHeader,
+        data: &mut PriceOracleData,
+        outcomes: Vec<AssetOutcome>,
+        clock: &Clock,
+    ) -> Result<Vec<AssetOutcome>> {
+        Self::require_fixed_feed_progress(header, &outcomes)?;
+        let updated = outcomes.iter().filter(|outcome| outcome.updated).count();
+        if outcomes.iter().any(|outcome| outcome.updated || outcome.unchanged) {
+            let confirmed_at = Self::feed_confirmed_at(header, data, clock);
+            header.record_global_update(updated, confirmed_at);
+            data.advance_nonce();
+        }
+        Ok(outcomes)
+    }
+
+    /// Fails with `NoNewRound` or `NoAssetUpdated` when no outcome made progress, unless
+    /// the update tripped the emergency stop, which must be kept
+    fn require_fixed_feed_progress(header: &PriceOracleHeader, outcomes: &[AssetOutcome]) -> Result<()> {
+        let held = u32::from(OracleError::PriceMovePending);
+        if !header.emergency_stop && !outcomes.iter().any(|outcome| outcome.updated || outcome.unchanged || outcome.error_code == held) {
+            if no_round_advanced(outcomes.iter().map(|outcome| outcome.error_code)) {
+                msg!("No feed published a new round since the last update");
+                return Err(error!(OracleError::NoNewRound));
+            }
+            msg!("Every asset in the update was rejected");
+            return Err(error!(OracleError::NoAssetUpdated));
+        }
+        Ok(())
+    }
+
+    /// Whether a multi-asset feed shard still writes the asset: it is neither delisted nor
+    /// paused
+    fn shard_writes(header: &PriceOracleHeader, data: &PriceOracleData, asset_type: AssetType) -> bool {
+        !data.price_data[asset_type.index()].delisted
+            && !header.is_paused(asset_type)
+    }
+
+    /// Oldest check time of the assets the multi-asset feed and its shards still write, see
+    /// `shard_writes`. The current time when there are none.
+    pub fn feed_confirmed_at(header: &PriceOracleHeader, data: &PriceOracleData, clock: &Clock) -> i64 {
+        header.multi_asset_feed()
+            .into_iter()
+            .chain(header.sharded_assets())
+            .filter(|asset_type| Self::shard_writes(header, data, *asset_type))
+            .map(|asset_type| data.price_data[asset_type.index()].last_checked_time)
+            .min()
+            .unwrap_or(clock.unix_timestamp)
+    }
+
+    /// Writes the multi-asset feed values into the slots of the assets at `positions`
+    fn apply_multi_asset_update(
+        header: &mut PriceOracleHeader,
+        data: &mut PriceOracleData,
+        feed: &AggregatorFeed,
+        positions: Range<usize>,
+        clock: &Clock,
+    ) -> Result<Vec<AssetOutcome>> {
+        let feed_assets = header.multi_asset_feed();
+        let shard: Vec<(usize, AssetType)> = positions.map(|position| (position, feed_assets[position])).collect();
+        Self::apply_shard_update(header, data, feed, &shard, clock)
+    }
+
+    /// Writes the values of a multi-asset payload feed into the assets `shard` maps its
+    /// positions to
+    fn apply_shard_update(
+        header: &mut PriceOracleHeader,
+        data: &mut PriceOracleData,
+        feed: &AggregatorFeed,
+        shard: &[(usize, AssetType)],
+        clock: &Clock,
+    ) -> Result<Vec<AssetOutcome>> {
+        let assets: Vec<AssetType> = shard.iter().map(|(_, asset_type)| *asset_type).collect();
+        header.require_source(&assets, SourceKind::Switchboard)?;
+        let multi_asset_result = get_multi_asset_result(feed)?;
+        let provenance = PriceProvenance::from_feed(SourceKind::Switchboard, feed.key(), feed.latest_round_slot()?);
+
+        Self::apply_shard_values(header, data, &multi_asset_result, shard, provenance, feed.latest_round_timestamp()?, clock)
+    }
+
+    /// Writes the values at `positions` of a decoded multi-asset payload, from a round
+    /// produced at `feed_timestamp`, into the assets the header maps them to
+    #[cfg(test)]
+    fn apply_multi_asset_values(
+        header: &mut PriceOracleHeader,
+        data: &mut PriceOracleData,
+        multi_asset_result: &MultiAssetResult,
+        positions: Range<usize>,
+        provenance: PriceProvenance,
+        feed_timestamp: i64,
+        clock: &Clock,
+    ) -> Result<Vec<AssetOutcome>> {
+        let feed_assets = header.multi_asset_feed();
+        let shard: Vec<(usize, AssetType)> = positions.map(|position| (position, feed_assets[position])).collect();
+        Self::apply_shard_values(header, data, multi_asset_result, &shard, provenance, feed_timestamp, clock)
+    }
+
+    /// Writes the values of a decoded multi-asset payload, from a round produced at
+    /// `feed_timestamp`, into the asset `shard` pairs each position with
+    fn apply_shard_values(
+        header: &mut PriceOracleHeader,
+        data: &mut PriceOracleData,
+        multi_asset_result: &MultiAssetResult,
+        shard: &[(usize, AssetType)],
+        provenance: PriceProvenance,
+        feed_timestamp: i64,
+        clock: &Clock,
+    ) -> Result<Vec<AssetOutcome>> {
+        let mut outcomes = Vec::with_capacity(shard.len());
+        for &(position, asset_type) in shard {
+            let new_price = multi_asset_result.prices[position];
+            let new_apy = multi_asset_result.apys[position];
+            outcomes.push(Self::apply_asset_value(header, data, asset_type, new_price, Some(new_apy), provenance, feed_timestamp, clock)?);
+        }
+
+        Ok(outcomes)
+    }
+
+    /// Writes one asset of a fixed-feed update, read from a round the source produced at
+    /// `feed_timestamp`, after per-asset validation.
+    ///
+    /// A move past the change limit trips the emergency stop, unless `partial_updates` is
+    /// set: then the asset is left untouched, its rejection code is recorded and the update
+    /// carries on with the other assets. A tripped stop leaves the asset and the ones after it
+    /// untouched the same way, with `PriceChangeExceedsLimit` and `EmergencyStop`. A move
+    /// held for confirmation is recorded with `PriceMovePending`.
+    ///
+    /// `new_apy` is the yield as the feed reports it, converted to an APY under the
+    /// asset's `YieldConfig` before it is compared or stored.
+    #[allow(clippy::too_many_arguments)]
+    fn apply_asset_value(
+        header: &mut PriceOracleHeader,
+        data: &mut PriceOracleData,
+        asset_type: AssetType,
+        new_price: u64,
+        new_apy: Option<u64>,
+        provenance: PriceProvenance,
+        feed_timestamp: i64,
+        clock: &Clock,
+    ) -> Result<AssetOutcome> {
+        // Updates start with the oracle running, so a stop was tripped by an earlier asset
+        if header.emergency_stop {
+            verbose_msg!("Skipping {:?}: the emergency stop was tripped", asset_type);
+            let error_code = u32::from(OracleError::EmergencyStop);
+            header.rejection_codes[asset_type.index()] = error_code;
+            return Ok(AssetOutcome { asset_type, updated: false, error_code, rejected_apy: None, unchanged: false });
+        }
+        if let Some(reason) = Self::update_blocker(header, data, asset_type) {
+            verbose_msg!("Skipping {:?}: {}", asset_type, reason);
+            let error_code = u32::from(reason);
+            header.rejection_codes[asset_type.index()] = error_code;
+            return Ok(AssetOutcome { asset_type, updated: false, error_code, rejected_apy: None, unchanged: false });
+        }
+        let price_data = &mut data.price_data[asset_type.index()];
+        if provenance.repeats_round(&price_data.provenance) {
+            verbose_msg!("Skipping {:?}: round at slot {} was already stored", asset_type, provenance.source_round_slot);
+            let error_code = u32::from(OracleError::NoNewRound);
+            header.rejection_codes[asset_type.index()] = error_code;
+            return Ok(AssetOutcome { asset_type, updated: false, error_code, rejected_apy: None, unchanged: false });
+        }
+        let yield_config = header.yield_config(asset_type);
+        let converted_apy = new_apy.map(|raw_apy| yield_config.to_apy(raw_apy));
+        // A held move is settled by the next observation, even one equal to the stored price
+        let comparable_apy = converted_apy.and_then(|apy| apy.ok());
+        if price_data.is_unchanged(new_price, comparable_apy) && !header.pending_prices[asset_type.index()].is_pending() {
+            verbose_msg!("{:?} unchanged at {}", asset_type, fixed_to_f64(new_price));
+            price_data.record_check(provenance, feed_timestamp, clock);
+            header.rejection_codes[asset_type.index()] = 0;
+            header.clear_asset_error(asset_type);
+            return Ok(AssetOutcome { asset_type, updated: false, error_code: 0, rejected_apy: None, unchanged: true });
+        }
+        if let Some(e) = Self::partial_update_skip(header, asset_type, price_data.price, new_price, clock.unix_timestamp) {
+            msg!("Skipping {:?}: {}. Old price: {}, New price: {}", asset_type, e, fixed_to_f64(price_data.price), fixed_to_f64(new_price));
+            let error_code = u32::from(e);
+            header.rejection_codes[asset_type.index()] = error_code;
+            header.count_rejection(error_code);
+            header.record_asset_error(asset_type, error_code, clock.unix_timestamp);
+            return Ok(AssetOutcome { asset_type, updated: false, error_code, rejected_apy: None, unchanged: false });
+        }
+        let price = match Self::enforce_price_change_limit(header, asset_type, price_data.price, new_price, clock.unix_timestamp) {
+            Ok(price) => price,
+            Err(e) => {
+                let error_code = u32::from(e);
+                header.rejection_codes[asset_type.index()] = error_code;
+                return Ok(AssetOutcome { asset_type, updated: false, error_code, rejected_apy: None, unchanged: false });
+            }
+        };
+
+        // Fixed feeds publish encoded documents, whose deviation is not in price units
+        price_data.record_price(price, provenance, Confidence::default(), feed_timestamp, clock);
+        let mut rejected_apy = None;
+        header.clear_asset_error(asset_type);
+        match (new_apy, converted_apy) {
+            (Some(raw_apy), Some(Ok(new_apy))) => {
+                if let Err(e) = Self::validate_apy_change(price_data.apy, new_apy, header.apy_change_limit_bps(asset_type)) {
+                    msg!("Keeping the APY of {:?}: {} bps limit exceeded. Old APY: {}, New APY: {}", asset_type, header.apy_change_limit_bps[asset_type.index()], fixed_to_f64(price_data.apy), fixed_to_f64(new_apy));
+                    rejected_apy = Some(new_apy);
+                    header.record_asset_error(asset_type, u32::from(e), clock.unix_timestamp);
+                } else {
+                    price_data.apy = new_apy;
+                    header.raw_yields[asset_type.index()] = RawYield { value: raw_apy, config: yield_config };
+                }
+            }
+            (Some(raw_apy), Some(Err(e))) => {
+                msg!("Keeping the APY of {:?}: {:?} of {} does not convert: {}", asset_type, yield_config.convention, fixed_to_f64(raw_apy), e);
+                rejected_apy = Some(raw_apy);
+                header.record_asset_error(asset_type, u32::from(e), clock.unix_timestamp);
+            }
+            _ => {}
+        }
+        header.rejection_codes[asset_type.index()] = 0;
+        verbose_msg!("Price updated for {:?}. New price: {}, New APY: {}", asset_type, fixed_to_f64(price), fixed_to_f64(price_data.apy));
+        Ok(AssetOutcome { asset_type, updated: true, error_code: 0, rejected_apy, unchanged: false })
+    }
+
+    /// Why the data account updates leave `asset_type` alone whatever its value, if they
+    /// do: delisted or paused, checked in that order
+    pub fn update_blocker(header: &PriceOracleHeader, data: &PriceOracleData, asset_type: AssetType) -> Option<OracleError> {
+        if data.price_data[asset_type.index()].delisted {
+            Some(OracleError::AssetDelisted)
+        } else if header.is_paused(asset_type) {
+            Some(OracleError::AssetPaused)
+        } else {
+            None
+        }
+    }
+
+    /// Rejection a fixed-feed update skips the asset with under `partial_updates`, for a
+    /// move past the limit that `LargeMovePolicy::Halt` would otherwise stop the oracle for
+    fn partial_update_skip(header: &PriceOracleHeader, asset_type: AssetType, old_price: u64, new_price: u64, now: i64) -> Option<OracleError> {
+        if !header.partial_updates || header.large_move_policy(asset_type) != LargeMovePolicy::Halt {
+            return None;
+        }
+        Self::validate_price_change(old_price, new_price, header.effective_price_change_limit_bps(now)).err()
+    }
+
+    /// Runs the checks of a fixed-feed update against `candidate_price` and
+    /// `candidate_apy` for `asset_type`, without writing anything. Each check is the update
+    /// path's own; `stake_pool` adds the pool rate check of `update_assets`. The feed is not
+    /// read, so the round and feed checks are left out, and a candidate equal to the stored
+    /// values is accepted as the update would confirm it.
+    #[allow(clippy::too_many_arguments)]
+    pub fn simulate_price_change(
+        header: &PriceOracleHeader,
+        data: &PriceOracleData,
+        registry: &AssetRegistry,
+        asset_type: AssetType,
+        candidate_price: u64,
+        candidate_apy: Option<u64>,
+        stake_pool: Option<&AccountInfo>,
+        now: i64,
+    ) -> PriceChangeSimulation {
+        let stored = &data.price_data[asset_type.index()];
+        let blocker = if header.emergency_stop {
+            Some(OracleError::EmergencyStop)
+        } else if header.updates_paused {
+            Some(OracleError::UpdatesPaused)
+        } else {
+            Self::update_blocker(header, data, asset_type)
+        };
+
+        let stake_pool = stake_pool.map(|pool| {
+            let tolerance_bps = registry.assets[asset_type.index()].nav_tolerance_bps();
+            match Self::stake_pool_reference(data, registry, asset_type, pool) {
+                Ok(reference) => SimulatedCheck::new(Self::validate_nav_deviation(reference, candidate_price, tolerance_bps), reference, tolerance_bps),
+                Err(e) => SimulatedCheck { passed: false, error_code: error_code(&e), reference: 0, limit_bps: tolerance_bps },
+            }
+        });
+        let limit_bps = header.effective_price_change_limit_bps(now);
+        let change_limit = SimulatedCheck::new(Self::validate_price_change(stored.price, candidate_price, limit_bps), stored.price, limit_bps);
+        let partial_skip = Self::partial_update_skip(header, asset_type, stored.price, candidate_price, now);
+        let move_verdict = Self::move_verdict(header, asset_type, stored.price, candidate_price, now);
+
+        let rejection = blocker
+            .map(u32::from)
+            .or(stake_pool.filter(|check| !check.passed).map(|check| check.error_code))
+            .or(partial_skip.map(u32::from))
+            .or(match move_verdict {
+                MoveVerdict::Accept | MoveVerdict::Clamp => None,
+                MoveVerdict::Hold | MoveVerdict::Wait => Some(u32::from(OracleError::PriceMovePending)),
+                MoveVerdict::Halt => Some(u32::from(OracleError::PriceChangeExceedsLimit)),
+            });
+        let trips_breaker = blocker.is_none() && stake_pool.is_none_or(|check| check.passed) && partial_skip.is_none() && move_verdict == MoveVerdict::Halt;
+
+        let apy_limit = candidate_apy.map(|raw_apy| {
+            let limit = header.apy_change_limit_bps(asset_type);
+            match header.yield_config(asset_type).to_apy(raw_apy) {
+                Ok(apy) => (SimulatedCheck::new(Self::validate_apy_change(stored.apy, apy, limit), stored.apy, limit.unwrap_or(0)), apy),
+                Err(e) => (SimulatedCheck::new(Err(e), stored.apy, limit.unwrap_or(0)), stored.apy),
+            }
+        });
+        let (price, apy) = match rejection {
+            Some(_) => (stored.price, stored.apy),
+            None => {
+                let price = if move_verdict == MoveVerdict::Clamp { clamp_to_limit(stored.price, candidate_price, limit_bps) } else { candidate_price };
+                let apy = match apy_limit {
+                    Some((check, apy)) if check.passed => apy,
+                    _ => stored.apy,
+                };
+                (price, apy)
+            }
+        };
+
+        PriceChangeSimulation {
+            asset_type,
+            candidate_price,
+            accepted: rejection.is_none(),
+            reason_code: rejection.unwrap_or(0),
+            blocked_code: blocker.map_or(0, u32::from),
+            change_limit,
+            move_verdict,
+            trips_breaker,
+            stake_pool,
+            apy_limit: apy_limit.map(|(check, _)| check),
+            price,
+            apy,
+        }
+    }
+
+    /// Writes the SOL feed value into the SOL slot
+    fn apply_sol_price_update(
+        header: &mut PriceOracleHeader,
+        data: &mut PriceOracleData,
+        feed: &AggregatorFeed,
+        history_buffer: Option<&AccountInfo>,
+        expected_price: Option<u64>,
+        tolerance_bps: u16,
+        clock: &Clock,
+    ) -> Result<AssetOutcome> {
+        header.require_source(&[AssetType::SOL], SourceKind::Switchboard)?;
+        let sol_price_result = get_sol_price(feed)?;
+        let new_price = sol_price_result.value;
+        Self::check_expected_price(AssetType::SOL, new_price, expected_price, tolerance_bps)?;
+        let provenance = PriceProvenance::from_feed(SourceKind::Switchboard, feed.key(), feed.latest_round_slot()?);
+
+        if let Some(history_buffer) = history_buffer {
+            let history_median = get_history_median(feed, history_buffer, clock.unix_timestamp, HISTORY_MEDIAN_WINDOW)?;
+            Self::validate_history_deviation(history_median, new_price).map_err(|e| {
+                msg!("SOL price {} deviates more than 10% from the history median {:?}", fixed_to_f64(new_price), history_median.map(fixed_to_f64));
+                error!(e)
+            })?;
+        }
+
+        Self::apply_asset_value(header, data, AssetType::SOL, new_price, None, provenance, feed.latest_round_timestamp()?, clock)
+    }
+
+    /// Fails with `ExpectedPriceMismatch` when `price`, as read from the feed, is further than
+    /// `tolerance_bps` of `expected_price` from it, guarding a keeper against the feed moving
+    /// between its simulation and the update landing. An expected price of zero never
+    /// matches; `None` skips the check.
+    pub fn check_expected_price(asset_type: AssetType, price: u64, expected_price: Option<u64>, tolerance_bps: u16) -> Result<()> {
+        match expected_price {
+            Some(expected_price) if expected_price == 0 || exceeds_deviation_bps(expected_price, price, tolerance_bps as u64) => {
+                msg!("{:?} feed reads {}, more than {} bps from the expected {}", asset_type, fixed_to_f64(price), tolerance_bps, fixed_to_f64(expected_price));
+                Err(error!(OracleError::ExpectedPriceMismatch))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Key whose signed prices are accepted, failing with `SignedPriceDisabled` until the
+    /// admin has set one
+    pub fn trusted_publisher(header: &PriceOracleHeader) -> Result<Pubkey> {
+        if header.trusted_publisher == Pubkey::default() {
+            msg!("Signed prices are disabled until a trusted publisher is set");
+            return Err(error!(OracleError::SignedPriceDisabled));
+        }
+        Ok(header.trusted_publisher)
+    }
+
+    /// Checks a signed price is non-zero, within the asset's max age of `clock` and newer
+    /// than both the stored price and the last signed price consumed for the asset
+    pub fn validate_signed_price(
+        header: &PriceOracleHeader,
+        price_data: &PriceData,
+        asset_type: AssetType,
+        price: u64,
+        timestamp: i64,
+        clock: &Clock,
+    ) -> std::result::Result<(), OracleError> {
+        if price == 0 || timestamp > clock.unix_timestamp.saturating_add(SIGNED_PRICE_MAX_CLOCK_SKEW_SECS) {
+            return Err(OracleError::InvalidSignedPrice);
+        }
+        if clock.unix_timestamp.saturating_sub(timestamp) > header.max_age_secs(asset_type) {
+            return Err(OracleError::StaleData);
+        }
+        if timestamp <= header.signed_price_times[asset_type.index()] || timestamp <= price_data.last_checked_time {
+            return Err(OracleError::SignedPriceReplayed);
+        }
+        Ok(())
+    }
+
+    /// Writes a price signed by the trusted publisher at `timestamp`, whose signature the
+    /// caller has verified. The price goes through the same per-asset checks as a fixed-feed
+    /// update and keeps the stored APY. The timestamp is recorded even when the move is held
+    /// for confirmation, so the same message can never confirm itself.
+    pub fn update_price_signed(
+        header: &mut PriceOracleHeader,
+        data: &mut PriceOracleData,
+        asset_type: AssetType,
+        price: u64,
+        timestamp: i64,
+        clock: &Clock,
+    ) -> Result<Vec<AssetOutcome>> {
+        if header.emergency_stop {
+            msg!("Emergency stop is activated. Signed price update aborted.");
+            return Err(error!(OracleError::EmergencyStop));
+        }
+        Self::require_updates_not_paused(header)?;
+        let publisher = Self::trusted_publisher(header)?;
+        Self::validate_signed_price(header, &data.price_data[asset_type.index()], asset_type, price, timestamp, clock).map_err(|e| {
+            msg!("Rejecting signed {:?} price {} timestamped {}: {}", asset_type, fixed_to_f64(price), timestamp, e);
+            error!(e)
+        })?;
+
+        let provenance = PriceProvenance::from_publisher(publisher, clock.slot);
+        let outcome = Self::apply_asset_value(header, data, asset_type, price, None, provenance, timestamp, clock)?;
+        header.signed_price_times[asset_type.index()] = timestamp;
+
+        Self::finish_fixed_feed_update(header, data, vec![outcome], clock)
+    }
+
+    /// Updates exactly the assets whose registered feeds are passed in `feeds`,
+    /// returning the assets that were updated in the order they were supplied.
+    ///
+    /// An asset whose feed has not published a new round since its stored price is skipped
+    /// and reported in `UpdatedAssets::no_new_round`. When that is all the run found, it fails
+    /// with `NoNewRound`. One whose new round carries the stored price only has its check
+    /// time written and is reported in `UpdatedAssets::unchanged`.
+    ///
+    /// Every feed costs one aggregator load, so a transaction carrying all seven
+    /// registered feeds stays well inside the default compute budget; the practical
+    /// limit is the account list, which is already bounded by the number of assets.
+    ///
+    /// A registered stake pool may be passed among the feeds; the price of its asset is
+    /// then checked against the pool's exchange rate. So may an asset's registered fallback
+    /// source, which is read in place of the feed only when the feed is stale; the price then
+    /// records the fallback as its provenance and the asset is reported in
+    /// `UpdatedAssets::failed_over`.
+    pub fn update_assets<'info>(
+        header: &mut Account<PriceOracleHeader>,
+        data: &mut Account<PriceOracleData>,
+        registry: &Account<AssetRegistry>,
+        feeds: &[AccountInfo<'info>],
+        clock: &Clock
+    ) -> Result<UpdatedAssets> {
+        if header.emergency_stop {
+            msg!("Emergency stop is activated. Update aborted.");
+            return Err(error!(OracleError::EmergencyStop));
+        }
+        Self::require_updates_not_paused(header)?;
+
+        let current_time = clock.unix_timestamp;
+        let mut updated: Vec<AssetType> = Vec::with_capacity(feeds.len());
+        let mut unchanged: Vec<AssetType> = Vec::new();
+        let mut no_new_round: Vec<AssetType> = Vec::new();
+        let mut failed_over: Vec<(AssetType, SourceSlot)> = Vec::new();
+        let mut advanced = false;
+
+        let mut pools: Vec<(AssetType, &AccountInfo<'info>)> = Vec::new();
+        let mut fallbacks: Vec<(AssetType, &AccountInfo<'info>)> = Vec::new();
+        let mut feed_infos: Vec<&AccountInfo<'info>> = Vec::with_capacity(feeds.len());
+        for account in feeds {
+            if let Some(asset_type) = registry.asset_for_stake_pool(account.key) {
+                if pools.iter().any(|(other, _)| *other == asset_type) {
+                    msg!("Stake pool for {:?} was supplied more than once", asset_type);
+                    return Err(error!(OracleError::DuplicateFeed));
+                }
+                pools.push((asset_type, account));
+            } else if let Some(asset_type) = registry.asset_for_fallback(account.key) {
+                if fallbacks.iter().any(|(other, _)| *other == asset_type) {
+                    msg!("Fallback source for {:?} was supplied more than once", asset_type);
+                    return Err(error!(OracleError::DuplicateFeed));
+                }
+                fallbacks.push((asset_type, account));
+            } else {
+                feed_infos.push(account);
+            }
+        }
+
+        for feed_info in feed_infos {
+            let asset_type = registry.asset_for_feed(feed_info.key).ok_or_else(|| {
+                msg!("Account {} is not the registered feed of any asset", feed_info.key);
+                error!(OracleError::UnregisteredFeed)
+            })?;
+            if [&updated, &unchanged, &no_new_round].iter().any(|assets| assets.contains(&asset_type)) {
+                msg!("Feed for {:?} was supplied more than once", asset_type);
+                return Err(error!(OracleError::DuplicateFeed));
+            }
+            if header.emergency_stop {
+                verbose_msg!("Skipping {:?}: the emergency stop was tripped", asset_type);
+                continue;
+            }
+            if let Some(reason) = Self::update_blocker(header, data, asset_type) {
+                verbose_msg!("Skipping {:?}: {}", asset_type, reason);
+                continue;
+            }
+
+            let fallback = fallbacks.iter().find(|(fallback_asset, _)| *fallback_asset == asset_type).map(|(_, fallback_info)| *fallback_info);
+            let (slot, SourcePrice { price: new_price, round_slot, round_timestamp, confidence }) =
+                Self::read_feed_or_fallback(header, registry, asset_type, feed_info, fallback, clock)?;
+            if slot.feed != *feed_info.key {
+                failed_over.push((asset_type, slot));
+            }
+            let provenance = PriceProvenance::from_feed(slot.kind, slot.feed, round_slot);
+            if provenance.repeats_round(&data.price_data[asset_type.index()].provenance) {
+                verbose_msg!("Skipping {:?}: round at slot {} was already stored", asset_type, round_slot);
+                no_new_round.push(asset_type);
+                continue;
+            }
+            advanced = true;
+            if let Some((_, pool)) = pools.iter().find(|(pool_asset, _)| *pool_asset == asset_type) {
+                let intrinsic_rate = Self::check_stake_pool_rate(data, registry, asset_type, new_price, pool)?;
+                header.record_premium(asset_type, new_price, intrinsic_rate, current_time);
+            }
+
+            let price_data = &mut data.price_data[asset_type.index()];
+            if price_data.is_unchanged(new_price, None) && !header.pending_prices[asset_type.index()].is_pending() {
+                verbose_msg!("{:?} unchanged at {}", asset_type, fixed_to_f64(new_price));
+                price_data.record_check(provenance, round_timestamp, clock);
+                header.clear_asset_error(asset_type);
+                unchanged.push(asset_type);
+                continue;
+            }
+            let price = match Self::enforce_price_change_limit(header, asset_type, price_data.price, new_price, current_time) {
+                Ok(price) => price,
+                Err(_) => continue,
+            };
+
+            price_data.record_price(price, provenance, confidence, round_timestamp, clock);
+            header.clear_asset_error(asset_type);
+            verbose_msg!("Price updated for {:?}. New price: {}", asset_type, fixed_to_f64(price));
+
+            updated.push(asset_type);
+        }
+
+        if !advanced && !no_new_round.is_empty() {
+            msg!("No feed published a new round since the last update");
+            return Err(error!(OracleError::NoNewRound));
+        }
+        if !updated.is_empty() || !unchanged.is_empty() {
+            header.record_global_update(updated.len(), current_time);
+            data.advance_nonce();
+        }
+        Ok(UpdatedAssets { updated, unchanged, no_new_round, failed_over })
+    }
+
+    /// Reads `asset_type` from its registered feed, or from its registered fallback source
+    /// when the feed is stale and the fallback was supplied, and returns the source that
+    /// served with its price. Any other failure of the feed, or of the fallback, fails the read.
+    fn read_feed_or_fallback(
+        header: &PriceOracleHeader,
+        registry: &AssetRegistry,
+        asset_type: AssetType,
+        feed_info: &AccountInfo,
+        fallback_info: Option<&AccountInfo>,
+        clock: &Clock,
+    ) -> Result<(SourceSlot, SourcePrice)> {
+        let slot = SourceSlot { kind: header.source(asset_type), feed: registry.assets[asset_type.index()].feed };
+        match Self::read_source(header, registry, asset_type, slot, feed_info, clock) {
+            Err(e) if error_code(&e) == u32::from(OracleError::StaleData) => {
+                match (registry.assets[asset_type.index()].fallback(), fallback_info) {
+                    (Some(fallback), Some(fallback_info)) => {
+                        msg!("Failing over from stale {:?} feed {} of {:?} to {:?} source {}", slot.kind, slot.feed, asset_type, fallback.kind, fallback.feed);
+                        let source = Self::read_source(header, registry, asset_type, fallback, fallback_info, clock)?;
+                        Ok((fallback, source))
+                    }
+                    _ => Err(e),
+                }
+            }
+            result => Ok((slot, result?)),
+        }
+    }
+
+    /// Updates one asset from its registered sources supplied in `feeds`, as its
+    /// `SourcePolicy` combines them.
+    ///
+    /// Under `SourcePolicy::Median` each source is validated on its own and discarded if
+    /// stale or invalid, and the update fails with `InsufficientSources` when fewer than
+    /// `min_sources` remain. Under `SourcePolicy::Priority` the sources are tried in
+    /// registration order and the first acceptable one serves the price, see
+    /// `first_acceptable_source`. Accounts that are not registered sources of the asset, or
+    /// repeat one, fail the whole update. The asset's registered stake pool may be passed
+    /// too, to check the price against it.
+    pub fn update_asset_from_sources<'info>(
+        header: &mut Account<PriceOracleHeader>,
+        data: &mut Account<PriceOracleData>,
+        registry: &Account<AssetRegistry>,
+        asset_type: AssetType,
+        feeds: &[AccountInfo<'info>],
+        clock: &Clock,
+    ) -> Result<SourceAggregation> {
+        if header.emergency_stop {
+            msg!("Emergency stop is activated. Update aborted.");
+            return Err(error!(OracleError::EmergencyStop));
+        }
+        Self::require_updates_not_paused(header)?;
+
+        data.listed(asset_type)?;
+        if header.is_paused(asset_type) {
+            msg!("{:?} is paused", asset_type);
+            return Err(error!(OracleError::AssetPaused));
+        }
+        let config = &registry.assets[asset_type.index()];
+        let mut supplied: Vec<(SourceSlot, &AccountInfo<'info>)> = Vec::with_capacity(feeds.len());
+        let mut pool: Option<&AccountInfo<'info>> = None;
+        for feed_info in feeds {
+            if config.is_stake_pool(feed_info.key) {
+                if pool.replace(feed_info).is_some() {
+                    msg!("Stake pool for {:?} was supplied more than once", asset_type);
+                    return Err(error!(OracleError::DuplicateFeed));
+                }
+                continue;
+            }
+            let slot = config.source_for_feed(feed_info.key).ok_or_else(|| {
+                msg!("Account {} is not a registered source of {:?}", feed_info.key, asset_type);
+                error!(OracleError::UnregisteredFeed)
+            })?;
+            if supplied.iter().any(|(other, _)| other.feed == slot.feed) {
+                msg!("Source {} was supplied more than once", slot.feed);
+                return Err(error!(OracleError::DuplicateFeed));
+            }
+            supplied.push((slot, feed_info));
+        }
+
+        let SourceSelection { price: new_price, confidence, provenance, feed_timestamp, priority_level, readings, intrinsic_rate } = match header.source_policy(asset_type) {
+            SourcePolicy::Median => Self::median_of_sources(header, data, registry, asset_type, &supplied, pool, clock)?,
+            SourcePolicy::Priority => Self::first_acceptable_source(header, data, registry, asset_type, &supplied, pool, clock)?,
+        };
+        if let Some(intrinsic_rate) = intrinsic_rate {
+            header.record_premium(asset_type, new_price, intrinsic_rate, clock.unix_timestamp);
+        }
+        for reading in readings.iter().filter(|reading| !reading.accepted) {
+            header.count_rejection(reading.reason_code);
+            header.record_asset_error(asset_type, reading.reason_code, clock.unix_timestamp);
+        }
+
+        let price_data = &mut data.price_data[asset_type.index()];
+        let price = match Self::enforce_price_change_limit(header, asset_type, price_data.price, new_price, clock.unix_timestamp) {
+            Ok(price) => price,
+            Err(e) => {
+                let halted = matches!(e, OracleError::PriceChangeExceedsLimit);
+                return Ok(SourceAggregation { price: new_price, pending: !halted, halted, priority_level, readings });
+            }
+        };
+
+        price_data.record_price(price, provenance, confidence, feed_timestamp, clock);
+        // A discarded source stays recorded, since it was rejected by this very update
+        if readings.iter().all(|reading| reading.accepted) {
+            header.clear_asset_error(asset_type);
+        }
+        verbose_msg!("Price updated for {:?} from {} source(s). New price: {}", asset_type, readings.len(), fixed_to_f64(price));
+
+        header.record_global_update(1, clock.unix_timestamp);
+        data.advance_nonce();
+        Ok(SourceAggregation { price: new_price, pending: false, halted: false, priority_level, readings })
+    }
+
+    /// The median of the `supplied` sources that read fresh and valid, as the provenance of
+    /// its oldest contributing round
+    fn median_of_sources(
+        header: &PriceOracleHeader,
+        data: &PriceOracleData,
+        registry: &Account<AssetRegistry>,
+        asset_type: AssetType,
+        supplied: &[(SourceSlot, &AccountInfo)],
+        pool: Option<&AccountInfo>,
+        clock: &Clock,
+    ) -> Result<SourceSelection> {
+        let config = &registry.assets[asset_type.index()];
+        let readings: Vec<SourceReading> = supplied
+            .iter()
+            .map(|(slot, feed_info)| match Self::read_source(header, registry, asset_type, *slot, feed_info, clock) {
+                Ok(source) => SourceReading::accepted(slot, &source),
+                Err(e) => {
+                    msg!("Discarding {:?} source {} for {:?}: {}", slot.kind, slot.feed, asset_type, e);
+                    SourceReading::discarded(slot, &e)
+                }
+            })
+            .collect();
+
+        let mut prices: Vec<u64> = readings.iter().filter(|reading| reading.accepted).map(|reading| reading.price).collect();
+        let required = config.min_sources.max(1) as usize;
+        if prices.len() < required {
+            msg!("{:?} has {} fresh source(s), at least {} are needed", asset_type, prices.len(), required);
+            return Err(error!(OracleError::InsufficientSources));
+        }
+        let new_price = median(&mut prices).ok_or_else(|| error!(OracleError::InsufficientSources))?;
+        let accepted_rounds = || readings.iter().filter(|reading| reading.accepted).map(|reading| reading.round_slot);
+        let provenance = PriceProvenance {
+            source_feed: registry.key(),
+            source_round_slot: accepted_rounds().min().unwrap_or(0),
+            source_kind: PriceProvenance::MEDIAN,
+        };
+        // The stored median keeps only its oldest round, so a round is new when it is past the
+        // slot the stored median was written in
+        let stored = &data.price_data[asset_type.index()];
+        let stored_median = stored.provenance.source_kind == provenance.source_kind && stored.provenance.source_feed == provenance.source_feed;
+        if stored_median && accepted_rounds().all(|round_slot| round_slot <= stored.last_update_slot) {
+            msg!("No source of {:?} published a round after slot {}", asset_type, stored.last_update_slot);
+            return Err(error!(OracleError::NoNewRound));
+        }
+
+        let intrinsic_rate = match pool {
+            Some(pool) => Some(Self::check_stake_pool_rate(data, registry, asset_type, new_price, pool)?),
+            None => None,
+        };
+
+        // Like the provenance, the median is as old as its oldest contributing round
+        let feed_timestamp = readings.iter().filter(|reading| reading.accepted).map(|reading| reading.round_timestamp).min().unwrap_or(0);
+        Ok(SourceSelection { price: new_price, confidence: Confidence::default(), provenance, feed_timestamp, priority_level: None, readings, intrinsic_rate })
+    }
+
+    /// The value of the asset's highest-priority registered source that reads fresh and
+    /// valid and, when the asset's stake pool is passed as `pool`, agrees with its rate. A source
+    /// that fails either is recorded as discarded and the next one in registration order
+    /// is tried; every source up to the one that serves must be supplied, so a keeper
+    /// cannot skip the primary. A source that is fine but has not published a round since
+    /// the stored price fails the update with `NoNewRound` rather than falling back.
+    /// Fails with `InsufficientSources` when no source is acceptable.
+    fn first_acceptable_source(
+        header: &PriceOracleHeader,
+        data: &PriceOracleData,
+        registry: &AssetRegistry,
+        asset_type: AssetType,
+        supplied: &[(SourceSlot, &AccountInfo)],
+        pool: Option<&AccountInfo>,
+        clock: &Clock,
+    ) -> Result<SourceSelection> {
+        let config = &registry.assets[asset_type.index()];
+        let mut readings: Vec<SourceReading> = Vec::with_capacity(MAX_ASSET_SOURCES);
+        for (level, slot) in config.sources.iter().filter(|slot| slot.feed != Pubkey::default()).enumerate() {
+            let feed_info = supplied.iter().find(|(supplied_slot, _)| supplied_slot.feed == slot.feed).map(|(_, feed_info)| *feed_info).ok_or_else(|| {
+                msg!("Source {} of {:?} at priority {} was not supplied", slot.feed, asset_type, level);
+                error!(OracleError::InsufficientSources)
+            })?;
+            let source = match Self::read_source(header, registry, asset_type, *slot, feed_info, clock) {
+                Ok(source) => source,
+                Err(e) => {
+                    msg!("Failing over from {:?} source {} of {:?} at priority {}: {}", slot.kind, slot.feed, asset_type, level, e);
+                    readings.push(SourceReading::discarded(slot, &e));
+                    continue;
+                }
+            };
+            let provenance = PriceProvenance { source_feed: slot.feed, source_round_slot: source.round_slot, source_kind: PriceProvenance::priority(level as u8) };
+            if provenance.repeats_round(&data.price_data[asset_type.index()].provenance) {
+                msg!("{:?} source {} has not published a round after slot {}", asset_type, slot.feed, source.round_slot);
+                return Err(error!(OracleError::NoNewRound));
+            }
+            let intrinsic_rate = match pool {
+                Some(pool) => match Self::check_stake_pool_rate(data, registry, asset_type, source.price, pool) {
+                    Err(e) if error_code(&e) == u32::from(OracleError::PriceDeviatesFromNav) => {
+                        msg!("Failing over from {:?} source {} of {:?} at priority {}: {}", slot.kind, slot.feed, asset_type, level, e);
+                        readings.push(SourceReading::discarded(slot, &e));
+                        continue;
+                    }
+                    result => Some(result?),
+                },
+                None => None,
+            };
+            readings.push(SourceReading::accepted(slot, &source));
+            return Ok(SourceSelection {
+                price: source.price,
+                confidence: source.confidence,
+                provenance,
+                feed_timestamp: source.round_timestamp,
+                priority_level: Some(level as u8),
+                readings,
+                intrinsic_rate,
+            });
+        }
+        msg!("None of the {} source(s) of {:?} is acceptable", readings.len(), asset_type);
+        Err(error!(OracleError::InsufficientSources))
+    }
+
+    /// Rejects `price` when it strays from the intrinsic value of one token of the asset's
+    /// stake pool by more than the asset's tolerance, and otherwise returns that value.
+    /// SOL-quoted prices are compared with the pool rate directly, USD-quoted ones after
+    /// converting it at the stored SOL price.
+    fn check_stake_pool_rate(
+        data: &PriceOracleData,
+        registry: &AssetRegistry,
+        asset_type: AssetType,
+        price: u64,
+        pool: &AccountInfo,
+    ) -> Result<u64> {
+        let reference = Self::stake_pool_reference(data, registry, asset_type, pool)?;
+        let tolerance_bps = registry.assets[asset_type.index()].nav_tolerance_bps();
+        Self::validate_nav_deviation(reference, price, tolerance_bps).map_err(|e| {
+            msg!("{:?} price {} is more than {} bps away from its stake pool rate {}",
+                asset_type, fixed_to_f64(price), tolerance_bps, fixed_to_f64(reference));
+            error!(e)
+        })?;
+        Ok(reference)
+    }
+
+    /// Intrinsic value of one token of the asset's registered stake pool, read from `pool`,
+    /// in the asset's quote
+    fn stake_pool_reference(
+        data: &PriceOracleData,
+        registry: &AssetRegistry,
+        asset_type: AssetType,
+        pool: &AccountInfo,
+    ) -> Result<u64> {
+        let config = &registry.assets[asset_type.index()];
+        let pool_rate = match config.stake_pool_kind {
+            PoolKind::Spl => get_stake_pool_price(pool, &config.stake_pool)?,
+            PoolKind::Marinade => get_marinade_price(pool, &config.stake_pool)?,
+        };
+        let reference = match data.price_data[asset_type.index()].quote {
+            QuoteCurrency::SOL => pool_rate,
+            QuoteCurrency::USD => {
+                let sol = &data.price_data[AssetType::SOL.index()];
+                if sol.quote != QuoteCurrency::USD || sol.price == 0 {
+                    msg!("No SOL/USD price to convert the {:?} stake pool rate through", asset_type);
+                    return Err(error!(OracleError::PriceNotAvailable));
+                }
+                mul_div(pool_rate, sol.price, PRICE_SCALE)?
+            }
+        };
+        Ok(reference)
+    }
+
+    /// Writes the price of the asset's registered feed as `update_assets` reads it, without
+    /// comparing it with the stored price, and returns the price it replaced.
+    ///
+    /// For an authority recovering from a genuine move past the change limit. Every check
+    /// of the normal path still applies: the stop and the maintenance pause, delisting,
+    /// pausing, the registered feed, its owner, queue and staleness, and a new round. Only
+    /// the change limit is skipped. The forced price becomes the baseline later updates are
+    /// limited against, any held move or clamp is dropped and the provenance is marked
+    /// `FORCED`.
+    pub fn force_update(
+        header: &mut PriceOracleHeader,
+        data: &mut PriceOracleData,
+        registry: &AssetRegistry,
+        asset_type: AssetType,
+        feed_info: &AccountInfo,
+        clock: &Clock,
+    ) -> Result<u64> {
+        if header.emergency_stop {
+            msg!("Emergency stop is activated. Update aborted.");
+            return Err(error!(OracleError::EmergencyStop));
+        }
+        Self::require_updates_not_paused(header)?;
+        data.listed(asset_type)?;
+        if header.is_paused(asset_type) {
+            msg!("{:?} is paused", asset_type);
+            return Err(error!(OracleError::AssetPaused));
+        }
+        if registry.asset_for_feed(feed_info.key) != Some(asset_type) {
+            msg!("Account {} is not the registered feed of {:?}", feed_info.key, asset_type);
+            return Err(error!(OracleError::UnregisteredFeed));
+        }
+
+        let kind = header.source(asset_type);
+        let slot = SourceSlot { kind, feed: registry.assets[asset_type.index()].feed };
+        let SourcePrice { price: new_price, round_slot, round_timestamp, confidence } = Self::read_source(header, registry, asset_type, slot, feed_info, clock)?;
+        let provenance = PriceProvenance { source_feed: *feed_info.key, source_round_slot: round_slot, source_kind: PriceProvenance::FORCED };
+        let price_data = &mut data.price_data[asset_type.index()];
+        let read = PriceProvenance::from_feed(kind, *feed_info.key, round_slot);
+        if read.repeats_round(&price_data.provenance) || provenance.repeats_round(&price_data.provenance) {
+            msg!("{:?} feed has not published a round after slot {}", asset_type, price_data.provenance.source_round_slot);
+            return Err(error!(OracleError::NoNewRound));
+        }
+
+        let old_price = price_data.price;
+        price_data.record_price(new_price, provenance, confidence, round_timestamp, clock);
+        header.pending_prices[asset_type.index()] = PendingPrice::default();
+        header.clamped_prices[asset_type.index()] = 0;
+        header.rejection_codes[asset_type.index()] = 0;
+        header.clear_asset_error(asset_type);
+        header.record_global_update(1, clock.unix_timestamp);
+        data.advance_nonce();
+        msg!("Forced {:?} past the change limit. Old price: {}, New price: {}", asset_type, fixed_to_f64(old_price), fixed_to_f64(new_price));
+        Ok(old_price)
+    }
+
+    /// Reads a fresh, validated fixed-point price for `asset_type` from `feed_info`, read as
+    /// the registered source `slot`
+    fn read_source(
+        header: &PriceOracleHeader,
+        registry: &AssetRegistry,
+        asset_type: AssetType,
+        slot: SourceSlot,
+        feed_info: &AccountInfo,
+        clock: &Clock,
+    ) -> Result<SourcePrice> {
+        match slot.kind {
+            SourceKind::Switchboard => {
+                let feed = Self::load_feed(header, feed_info)?;
+                let price = get_validated_feed_result(&feed, &registry.queue, clock, header.feed_max_age(asset_type))?.value;
+                Ok(SourcePrice {
+                    price,
+                    round_slot: feed.latest_round_slot()?,
+                    round_timestamp: feed.latest_round_timestamp()?,
+                    confidence: Confidence::from_std_deviation(feed.latest_round_std_deviation()?),
+                })
+            }
+            SourceKind::Chainlink => get_chainlink_price(feed_info, clock, header.feed_max_age(asset_type)),
+            SourceKind::Pyth => get_pyth_price(feed_info, &registry.pyth_feed_id(asset_type), clock, header.feed_max_age(asset_type)),
+            SourceKind::SwitchboardOnDemand => get_pull_feed_price(feed_info, &slot.feed, &header.on_demand_queue, clock, header.feed_max_age(asset_type)),
+        }
+    }
+
+    /// Wraps an aggregator after checking it is owned by the Switchboard program recorded in the header
+    pub fn load_feed<'info>(header: &PriceOracleHeader, feed: &AccountInfo<'info>) -> Result<AggregatorFeed<'info>> {
+        Self::validate_feed_owner(header, feed)?;
+        Ok(AggregatorFeed::new(feed))
+    }
+
+    /// Checks that a feed account is owned by the Switchboard program recorded in the header
+    pub fn validate_feed_owner(header: &PriceOracleHeader, feed: &AccountInfo) -> Result<()> {
+        if feed.owner != &header.switchboard_program_id {
+            msg!("Invalid Switchboard account owner: expected {}, found {}",
+                header.switchboard_program_id,
+                feed.owner);
+            return Err(error!(OracleError::InvalidSwitchboardAccount));
+        }
+        Ok(())
+    }
+
+    /// Applies the asset's large move policy to a new observation, returning the price to
+    /// write now. Otherwise nothing is written, and the error says why: `PriceMovePending`
+    /// for a held move, `PriceChangeExceedsLimit` for one that tripped the breaker.
+    ///
+    /// Under `Halt` a move past the limit is rejected and trips the emergency stop, see
+    /// `PriceOracleHeader::trip_breaker`. The caller leaves the asset unchanged and goes on
+    /// to succeed, so the stop is not rolled back with the update. Under
+    /// `Confirm` it is held as the asset's pending move instead; a later observation within
+    /// tolerance of it, once the confirmation delay has elapsed, commits it, while one back
+    /// within the limit of the stored price discards it and is written as usual. Under
+    /// `Clamp` the price moved by the limit toward the observation is written, and the
+    /// observation is kept in `clamped_prices` until a price is written unclamped.
+    fn enforce_price_change_limit(
+        header: &mut PriceOracleHeader,
+        asset_type: AssetType,
+        old_price: u64,
+        new_price: u64,
+        now: i64,
+    ) -> std::result::Result<u64, OracleError> {
+        let i = asset_type.index();
+        match Self::move_verdict(header, asset_type, old_price, new_price, now) {
+            MoveVerdict::Accept => {
+                header.pending_prices[i] = PendingPrice::default();
+                header.clamped_prices[i] = 0;
+                Ok(new_price)
+            }
+            MoveVerdict::Hold => {
+                msg!("Holding {:?} move for confirmation. Old price: {}, New price: {}", asset_type, fixed_to_f64(old_price), fixed_to_f64(new_price));
+                header.pending_prices[i] = PendingPrice { price: new_price, observed_at: now };
+                Err(OracleError::PriceMovePending)
+            }
+            MoveVerdict::Wait => {
+                verbose_msg!("{:?} move to {} is waiting for its confirmation delay", asset_type, fixed_to_f64(header.pending_prices[i].price));
+                Err(OracleError::PriceMovePending)
+            }
+            MoveVerdict::Clamp => {
+                let limit_bps = header.effective_price_change_limit_bps(now);
+                let price = clamp_to_limit(old_price, new_price, limit_bps);
+                msg!("Clamping {:?} to the {} bps limit. Old price: {}, Feed price: {}, Stored price: {}", asset_type, limit_bps, fixed_to_f64(old_price), fixed_to_f64(new_price), fixed_to_f64(price));
+                header.pending_prices[i] = PendingPrice::default();
+                header.clamped_prices[i] = new_price;
+                Ok(price)
+            }
+            MoveVerdict::Halt => {
+                msg!("Price change exceeds {} bps limit for {:?}, tripping the emergency stop. Old price: {}, New price: {}", header.effective_price_change_limit_bps(now), asset_type, fixed_to_f64(old_price), fixed_to_f64(new_price));
+                header.trip_breaker(asset_type, now);
+                Err(OracleError::PriceChangeExceedsLimit)
+            }
+        }
+    }
+
+    /// Decision `enforce_price_change_limit` takes for an observation, shared with `preview_update`
+    pub fn move_verdict(
+        header: &PriceOracleHeader,
+        asset_type: AssetType,
+        old_price: u64,
+        new_price: u64,
+        now: i64,
+    ) -> MoveVerdict {
+        if Self::validate_price_change(old_price, new_price, header.effective_price_change_limit_bps(now)).is_ok() {
+            return MoveVerdict::Accept;
+        }
+        match header.large_move_policy(asset_type) {
+            LargeMovePolicy::Halt => return MoveVerdict::Halt,
+            LargeMovePolicy::Clamp => return MoveVerdict::Clamp,
+            LargeMovePolicy::Confirm => {}
+        }
+        let pending = header.pending_prices[asset_type.index()];
+        if !pending.is_pending() || exceeds_deviation_bps(pending.price, new_price, header.move_confirm_tolerance_bps() as u64) {
+            return MoveVerdict::Hold;
+        }
+        if now.saturating_sub(pending.observed_at) >= header.move_confirm_delay_secs() as i64 {
+            MoveVerdict::Accept
+        } else {
+            MoveVerdict::Wait
+        }
+    }
+
+    /// Whether `price`, just stored for an asset, committed its former pending move `pending`
+    /// rather than discarding it
+    pub fn confirms_pending_move(header: &PriceOracleHeader, pending: &PendingPrice, price: u64) -> bool {
+        pending.is_pending() && !exceeds_deviation_bps(pending.price, price, header.move_confirm_tolerance_bps() as u64)
+    }
+
+    /// Validation shared by the real update path and `preview_update`.
+    /// A zero stored price is a first observation and is always accepted.
+    pub fn validate_price_change(old_price: u64, new_price: u64, limit_bps: u32) -> std::result::Result<(), OracleError> {
+        if exceeds_deviation_bps(old_price, new_price, limit_bps as u64) {
+            return Err(OracleError::PriceChangeExceedsLimit);
+        }
+        Ok(())
+    }
+
+    /// Rejects an APY moving more than `limit_bps` from the stored one; `None` imposes no
+    /// limit and a zero stored APY is a first observation, always accepted
+    pub fn validate_apy_change(old_apy: u64, new_apy: u64, limit_bps: Option<u32>) -> std::result::Result<(), OracleError> {
+        match limit_bps {
+            Some(limit_bps) if exceeds_deviation_bps(old_apy, new_apy, limit_bps as u64) => Err(OracleError::ApyChangeExceedsLimit),
+            _ => Ok(()),
+        }
+    }
+
+    /// Rejects a price more than `tolerance_bps` away from its stake pool rate `reference`
+    pub fn validate_nav_deviation(reference: u64, price: u64, tolerance_bps: u32) -> std::result::Result<(), OracleError> {
+        if exceeds_deviation_bps(reference, price, tolerance_bps as u64) {
+            return Err(OracleError::PriceDeviatesFromNav);
+        }
+        Ok(())
+    }
+
+    /// Rejects a value deviating more than `HISTORY_DEVIATION_LIMIT_BPS` from the history median.
+    /// An empty history window imposes no constraint.
+    pub fn validate_history_deviation(history_median: Option<u64>, new_price: u64) -> std::result::Result<(), OracleError> {
+        match history_median {
+            Some(median) if exceeds_deviation_bps(median, new_price, HISTORY_DEVIATION_LIMIT_BPS) => {
+                Err(OracleError::HistoryDeviationExceedsLimit)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Runs every validation of `update_all` against the supplied feeds without writing anything
+    pub fn preview_update(
+        header: &PriceOracleHeader,
+        data: &PriceOracleData,
+        multi_asset_feed: &AggregatorFeed,
+        sol_feed: &AggregatorFeed,
+        sol_history_buffer: Option<&AccountInfo>,
+        current_time: i64,
+    ) -> UpdatePreview {
+        let provenance = |feed: &AggregatorFeed| {
+            feed.latest_round_slot().map(|round_slot| PriceProvenance::from_feed(SourceKind::Switchboard, feed.key(), round_slot))
+        };
+        let multi_asset_result = Self::validate_feed_owner(header, &multi_asset_feed.to_account_info())
+            .and_then(|_| Ok((get_multi_asset_result(multi_asset_feed)?, provenance(multi_asset_feed)?)))
+            .map_err(|e| error_code(&e));
+        let sol_result = Self::validate_feed_owner(header, &sol_feed.to_account_info())
+            .and_then(|_| get_sol_price(sol_feed))
+            .and_then(|result| match sol_history_buffer {
+                Some(history_buffer) => {
+                    let history_median = get_history_median(sol_feed, history_buffer, current_time, HISTORY_MEDIAN_WINDOW)?;
+                    Self::validate_history_deviation(history_median, result.value)?;
+                    Ok(result)
+                }
+                None => Ok(result),
+            })
+            .and_then(|result| Ok((result, provenance(sol_feed)?)))
+            .map_err(|e| error_code(&e));
+
+        let assets = header.multi_asset_feed().into_iter().chain([AssetType::SOL]).enumerate().map(|(position, asset_type)| {
+            let i = asset_type.index();
+            let incoming = if asset_type == AssetType::SOL {
+                sol_result.as_ref().map(|(result, provenance)| (result.value, data.price_data[i].apy, provenance))
+            } else {
+                // The APY is shown converted from the asset's yield convention, as it would be stored
+                let apy = |raw_apy: u64| header.yield_config(asset_type).to_apy(raw_apy).unwrap_or(data.price_data[i].apy);
+                multi_asset_result.as_ref().map(|(result, provenance)| (result.prices[position], apy(result.apys[position]), provenance))
+            };
+
+            match incoming {
+                Ok((price, apy, provenance)) => {
+                    let verdict = if header.emergency_stop {
+                        Err(OracleError::EmergencyStop)
+                    } else if header.updates_paused {
+                        Err(OracleError::UpdatesPaused)
+                    } else if let Some(reason) = Self::update_blocker(header, data, asset_type) {
+                        Err(reason)
+                    } else if provenance.repeats_round(&data.price_data[i].provenance) {
+                        Err(OracleError::NoNewRound)
+                    } else {
+                        match Self::move_verdict(header, asset_type, data.price_data[i].price, price, current_time) {
+                            MoveVerdict::Accept | MoveVerdict::Clamp => Ok(()),
+                            MoveVerdict::Hold | MoveVerdict::Wait => Err(OracleError::PriceMovePending),
+                            MoveVerdict::Halt => Err(OracleError::PriceChangeExceedsLimit),
+                        }
+                    };
+                    AssetUpdatePreview {
+                        asset_type,
+                        accepted: verdict.is_ok(),
+                        reason_code: verdict.err().map_or(0, u32::from),
+                        price,
+                        apy,
+                    }
+                }
+                Err(code) => AssetUpdatePreview {
+                    asset_type,
+                    accepted: false,
+                    reason_code: *code,
+                    price: 0,
+                    apy: 0,
+                },
+            }
+        }).collect();
+
+        UpdatePreview {
+            emergency_stop: header.emergency_stop,
+            updates_paused: header.updates_paused,
+            assets,
+        }
+    }
+
+    /// Initializes the asset registry
+    pub fn initialize_registry(registry: &mut Account<AssetRegistry>, queue: Pubkey, bump: u8) -> Result<()> {
+        registry.queue = queue;
+        registry.assets = core::array::from_fn(|_| AssetConfig::default());
+        registry.bump = bump;
+        Ok(())
+    }
+
+    /// Registers the dedicated feed for an asset
+    pub fn set_asset_feed(registry: &mut Account<AssetRegistry>, asset_type: AssetType, feed: Pubkey) -> Result<()> {
+        if matches!(registry.asset_for_feed(&feed), Some(other) if other != asset_type) {
+            msg!("Feed {} is already registered for another asset", feed);
+            return Err(error!(OracleError::DuplicateFeed));
+        }
+        if registry.asset_for_fallback(&feed).is_some() {
+            msg!("Feed {} is already registered as a fallback source", feed);
+            return Err(error!(OracleError::DuplicateFeed));
+        }
+        // Only a first registration is immediate; replacing a feed waits for the timelock
+        let current = registry.assets[asset_type.index()].feed;
+        if current != Pubkey::default() && current != feed {
+            msg!("{:?} already has feed {}; propose the change with propose_config", asset_type, current);
+            return Err(error!(OracleError::TimelockRequired));
+        }
+        registry.assets[asset_type.index()].feed = feed;
+        Ok(())
+    }
+
+    /// Binds the Pyth feed id the asset's Pyth sources must carry, returning the one it
+    /// replaced. All zeros unbinds it, which refuses every Pyth read of the asset except
+    /// SOL's, held to the SOL/USD feed again.
+    ///
+    /// Immediate, unlike replacing a feed: the feed accounts stay the registered ones, so
+    /// a wrong id can only refuse their updates, never let another account's price in.
+    pub fn set_pyth_feed_id(registry: &mut AssetRegistry, asset_type: AssetType, feed_id: [u8; 32]) -> [u8; 32] {
+        std::mem::replace(&mut registry.pyth_feed_ids[asset_type.index()], feed_id)
+    }
+
+    /// The settings currently in force, as a starting point for a proposal
+    pub fn current_config(header: &PriceOracleHeader, registry: &AssetRegistry) -> OracleConfig {
+        OracleConfig {
+            price_change_limit_bps: header.price_change_limit_bps(),
+            timelock_secs: header.config_timelock_secs(),
+            feeds: AssetType::ALL.map(|asset_type| registry.assets[asset_type.index()].feed),
+            resume_approvers: header.resume_approvers,
+            resume_threshold: header.resume_threshold,
+            manual_resume_needs_approval: header.manual_resume_needs_approval,
+        }
+    }
+
+    /// Records `config` to take effect once the current timelock has elapsed, returning that
+    /// time. Only one proposal can be pending; cancel it to propose another.
+    pub fn propose_config(header: &mut PriceOracleHeader, config: OracleConfig, current_time: i64) -> Result<i64> {
+        if header.pending_config.is_pending() {
+            msg!("A config change is pending until {}; cancel it first", header.pending_config.effective_at);
+            return Err(error!(OracleError::ConfigChangePending));
+        }
+        config.validate()?;
+        let effective_at = current_time.saturating_add(header.config_timelock_secs() as i64);
+        header.pending_config = PendingConfig { config, effective_at };
+        Ok(effective_at)
+    }
+
+    /// Activates the pending config from its `effective_at` on, returning it
+    pub fn apply_config(header: &mut PriceOracleHeader, registry: &mut AssetRegistry, current_time: i64) -> Result<OracleConfig> {
+        let pending = header.pending_config;
+        if !pending.is_pending() {
+            return Err(error!(OracleError::NoPendingConfig));
+        }
+        if header.locked {
+            msg!("The oracle is locked; the pending config can no longer be applied");
+            return Err(error!(OracleError::OracleLocked));
+        }
+        if current_time < pending.effective_at {
+            msg!("Config takes effect at {}, {}s from now", pending.effective_at, pending.effective_at - current_time);
+            return Err(error!(OracleError::TimelockNotElapsed));
+        }
+
+        let config = pending.config;
+        header.price_change_limit_bps = config.price_change_limit_bps;
+        header.config_timelock_secs = config.timelock_secs;
+        for asset_type in AssetType::iter() {
+            registry.assets[asset_type.index()].feed = config.feeds[asset_type.index()];
+        }
+        if header.resume_approvers != config.resume_approvers {
+            // Votes are kept by slot, so they cannot carry over to a different set
+            header.resume_approvals = [0; MAX_RESUME_APPROVERS];
+        }
+        header.resume_approvers = config.resume_approvers;
+        header.resume_threshold = config.resume_threshold;
+        header.manual_resume_needs_approval = config.manual_resume_needs_approval;
+        header.pending_config = PendingConfig::default();
+        Ok(config)
+    }
+
+    /// Drops the pending config, returning it
+    pub fn cancel_config(header: &mut PriceOracleHeader) -> Result<OracleConfig> {
+        if !header.pending_config.is_pending() {
+            return Err(error!(OracleError::NoPendingConfig));
+        }
+        let config = header.pending_config.config;
+        header.pending_config = PendingConfig::default();
+        Ok(config)
+    }
+
+    /// Queues a new feed mapping behind the config timelock, returning it and the time it
+    /// takes effect. Fails with `ConfigChangePending` while another mapping is queued.
+    pub fn propose_feed_mapping(header: &mut PriceOracleHeader, positions: &[FeedPosition], current_time: i64) -> Result<(FeedMapping, i64)> {
+        if header.pending_feed_mapping.is_pending() {
+            msg!("A feed mapping is pending until {}; cancel it first", header.pending_feed_mapping.effective_at);
+            return Err(error!(OracleError::ConfigChangePending));
+        }
+        let mapping = FeedMapping::from_positions(positions)?;
+        Self::require_unsharded(header, &mapping.assets, None)?;
+        let effective_at = current_time.saturating_add(header.config_timelock_secs() as i64);
+        header.pending_feed_mapping = PendingFeedMapping { mapping, effective_at };
+        Ok((mapping, effective_at))
+    }
+
+    /// Activates the pending feed mapping from its `effective_at` on, returning it
+    pub fn apply_feed_mapping(header: &mut PriceOracleHeader, current_time: i64) -> Result<FeedMapping> {
+        let pending = header.pending_feed_mapping;
+        if !pending.is_pending() {
+            return Err(error!(OracleError::NoPendingConfig));
+        }
+        if header.locked {
+            msg!("The oracle is locked; the pending feed mapping can no longer be applied");
+            return Err(error!(OracleError::OracleLocked));
+        }
+        if current_time < pending.effective_at {
+            msg!("Feed mapping takes effect at {}, {}s from now", pending.effective_at, pending.effective_at - current_time);
+            return Err(error!(OracleError::TimelockNotElapsed));
+        }
+        Self::require_unsharded(header, &pending.mapping.assets, None)?;
+        header.feed_mapping = pending.mapping;
+        header.pending_feed_mapping = PendingFeedMapping::default();
+        Ok(pending.mapping)
+    }
+
+    /// Drops the pending feed mapping, returning it
+    pub fn cancel_feed_mapping(header: &mut PriceOracleHeader) -> Result<FeedMapping> {
+        if !header.pending_feed_mapping.is_pending() {
+            return Err(error!(OracleError::NoPendingConfig));
+        }
+        let mapping = header.pending_feed_mapping.mapping;
+        header.pending_feed_mapping = PendingFeedMapping::default();
+        Ok(mapping)
+    }
+
+    /// Registers `feed` as shard `shard` of the multi-asset feed, read at `positions`, or
+    /// clears the shard when `positions` is empty, at once; the instructions go through
+    /// `propose_feed_shard` and `apply_feed_shard`. Shard `0` is the primary feed, mapped with
+    /// `propose_feed_mapping`. Fails with `FeedShardOverlap` when an asset is already carried
+    /// by another shard, the primary feed's current or pending mapping included.
+    pub fn set_feed_shard(header: &mut PriceOracleHeader, shard: u8, feed: Pubkey, positions: &[FeedPosition]) -> Result<FeedShard> {
+        let index = Self::feed_shard_index(shard)?;
+        let new_shard = if positions.is_empty() { FeedShard::default() } else { FeedShard::from_positions(feed, positions)? };
+        Self::require_feed_shard_available(header, index, &new_shard)?;
+        header.feed_shards[index] = new_shard;
+        Ok(new_shard)
+    }
+
+    /// Queues `feed` as shard `shard` of the multi-asset feed, read at `positions`, behind the
+    /// config timelock, returning the shard and the time it takes effect. Empty `positions`
+    /// queue clearing it. Fails with `ConfigChangePending` while another change of the shard
+    /// is queued, and as `set_feed_shard` does.
+    pub fn propose_feed_shard(
+        header: &mut PriceOracleHeader,
+        shard: u8,
+        feed: Pubkey,
+        positions: &[FeedPosition],
+        current_time: i64,
+    ) -> Result<(FeedShard, i64)> {
+        let index = Self::feed_shard_index(shard)?;
+        let pending = header.pending_feed_shards[index];
+        if pending.is_pending() {
+            msg!("A change of shard {} is pending until {}; cancel it first", shard, pending.effective_at);
+            return Err(error!(OracleError::ConfigChangePending));
+        }
+        let new_shard = if positions.is_empty() { FeedShard::default() } else { FeedShard::from_positions(feed, positions)? };
+        Self::require_feed_shard_available(header, index, &new_shard)?;
+        let effective_at = current_time.saturating_add(header.config_timelock_secs() as i64);
+        header.pending_feed_shards[index] = PendingFeedShard { shard: new_shard, effective_at };
+        Ok((new_shard, effective_at))
+    }
+
+    /// Registers the shard's pending change from its `effective_at` on, returning the new
+    /// shard. It is checked again, as the other shards and the mapping may have changed since
+    /// it was proposed.
+    pub fn apply_feed_shard(header: &mut PriceOracleHeader, shard: u8, current_time: i64) -> Result<FeedShard> {
+        let index = Self::feed_shard_index(shard)?;
+        let pending = header.pending_feed_shards[index];
+        if !pending.is_pending() {
+            return Err(error!(OracleError::NoPendingConfig));
+        }
+        if header.locked {
+            msg!("The oracle is locked; the pending feed shard can no longer be applied");
+            return Err(error!(OracleError::OracleLocked));
+        }
+        if current_time < pending.effective_at {
+            msg!("Feed shard {} takes effect at {}, {}s from now", shard, pending.effective_at, pending.effective_at - current_time);
+            return Err(error!(OracleError::TimelockNotElapsed));
+        }
+        Self::require_feed_shard_available(header, index, &pending.shard)?;
+        header.feed_shards[index] = pending.shard;
+        header.pending_feed_shards[index] = PendingFeedShard::default();
+        Ok(pending.shard)
+    }
+
+    /// Drops the shard's pending change, returning the proposed shard
+    pub fn cancel_feed_shard(header: &mut PriceOracleHeader, shard: u8) -> Result<FeedShard> {
+        let index = Self::feed_shard_index(shard)?;
+        let pending = header.pending_feed_shards[index];
+        if !pending.is_pending() {
+            return Err(error!(OracleError::NoPendingConfig));
+        }
+        header.pending_feed_shards[index] = PendingFeedShard::default();
+        Ok(pending.shard)
+    }
+
+    /// Index in `feed_shards` of shard `shard`, failing with `InvalidFeedShard` outside 1 to
+    /// `MAX_FEED_SHARDS`
+    fn feed_shard_index(shard: u8) -> Result<usize> {
+        if shard == 0 || shard as usize > MAX_FEED_SHARDS {
+            msg!("Shard {} is not one of the shards 1 to {}", shard, MAX_FEED_SHARDS);
+            return Err(error!(OracleError::InvalidFeedShard));
+        }
+        Ok(shard as usize - 1)
+    }
+
+    /// Fails unless `new_shard` can be registered as `feed_shards[index]`: its feed must be a
+    /// real aggregator no other shard reads, and its assets carried by no other shard. A
+    /// cleared shard always can.
+    fn require_feed_shard_available(header: &PriceOracleHeader, index: usize, new_shard: &FeedShard) -> Result<()> {
+        if !new_shard.is_set() {
+            return Ok(());
+        }
+        let (feed, shard) = (new_shard.feed, index as u8 + 1);
+        let feed_taken = matches!(header.feed_shard(&feed), Some((other, _)) if other != shard);
+        if feed == Pubkey::default() || feed == DEVNET_AGGREGATOR_PUBKEY || feed_taken {
+            msg!("{} cannot be read as shard {}", feed, shard);
+            return Err(error!(OracleError::InvalidFeedShard));
+        }
+        Self::require_unsharded(header, &new_shard.asset_types(), Some(index))
+    }
+
+    /// Fails with `FeedShardOverlap` when one of `assets` is carried by a registered shard
+    /// other than `feed_shards[skip]`, or, for a registered shard, by the primary feed
+    fn require_unsharded(header: &PriceOracleHeader, assets: &[AssetType], skip: Option<usize>) -> Result<()> {
+        let mut taken: Vec<AssetType> = header.feed_shards.iter().enumerate()
+            .filter(|(index, _)| Some(*index) != skip)
+            .flat_map(|(_, shard)| shard.asset_types())
+            .collect();
+        if skip.is_some() {
+            taken.extend(header.multi_asset_feed());
+            if header.pending_feed_mapping.is_pending() {
+                taken.extend(header.pending_feed_mapping.mapping.assets);
+            }
+        }
+        if let Some(asset_type) = assets.iter().find(|asset_type| taken.contains(asset_type)) {
+            msg!("{:?} is already carried by another shard of the multi-asset feed", asset_type);
+            return Err(error!(OracleError::FeedShardOverlap));
+        }
+        Ok(())
+    }
+
+    /// Registers the sources `update_asset_from_sources` aggregates for an asset, replacing
+    /// any previous ones. An empty list clears them and requires `min_sources` of `0`.
+    pub fn set_asset_sources(
+        registry: &mut AssetRegistry,
+        asset_type: AssetType,
+        sources: &[SourceSlot],
+        min_sources: u8,
+    ) -> Result<()> {
+        if sources.len() > MAX_ASSET_SOURCES {
+            msg!("At most {} sources can be registered per asset", MAX_ASSET_SOURCES);
+            return Err(error!(OracleError::InvalidConfig));
+        }
+        for (i, slot) in sources.iter().enumerate() {
+            if slot.feed == Pubkey::default() || sources[..i].iter().any(|other| other.feed == slot.feed) {
+                msg!("Source feeds must be set and distinct, found {}", slot.feed);
+                return Err(error!(OracleError::InvalidConfig));
+            }
+        }
+        let min_sources_valid = if sources.is_empty() {
+            min_sources == 0
+        } else {
+            min_sources >= 1 && min_sources as usize <= sources.len()
+        };
+        if !min_sources_valid {
+            msg!("min_sources {} is not achievable with {} source(s)", min_sources, sources.len());
+            return Err(error!(OracleError::InvalidConfig));
+        }
+
+        let config = &mut registry.assets[asset_type.index()];
+        config.sources = [SourceSlot::default(); MAX_ASSET_SOURCES];
+        config.sources[..sources.len()].copy_from_slice(sources);
+        config.min_sources = min_sources;
+        Ok(())
+    }
+
+    /// Registers the stake pool that bounds `asset_type`'s price, read as `kind`, with its
+    /// tolerance in basis points (`0` for the default). `Pubkey::default()` clears it.
+    /// Marinade's state only prices mSOL.
+    pub fn set_asset_stake_pool(
+        registry: &mut AssetRegistry,
+        asset_type: AssetType,
+        stake_pool: Pubkey,
+        kind: PoolKind,
+        nav_tolerance_bps: u32,
+    ) -> Result<()> {
+        let cleared = stake_pool == Pubkey::default();
+        if asset_type == AssetType::SOL && !cleared {
+            msg!("SOL is not a stake pool token");
+            return Err(error!(OracleError::InvalidConfig));
+        }
+        if kind == PoolKind::Marinade && asset_type != AssetType::MSOL && !cleared {
+            msg!("Only mSOL can be checked against the Marinade state");
+            return Err(error!(OracleError::InvalidConfig));
+        }
+        if nav_tolerance_bps > MAX_NAV_TOLERANCE_BPS {
+            msg!("Stake pool tolerance must be at most {} bps", MAX_NAV_TOLERANCE_BPS);
+            return Err(error!(OracleError::InvalidConfig));
+        }
+        if matches!(registry.asset_for_stake_pool(&stake_pool), Some(other) if other != asset_type) {
+            msg!("Stake pool {} is already registered for another asset", stake_pool);
+            return Err(error!(OracleError::DuplicateFeed));
+        }
+        let config = &mut registry.assets[asset_type.index()];
+        config.stake_pool = stake_pool;
+        config.stake_pool_kind = if cleared { PoolKind::default() } else { kind };
+        config.nav_tolerance_bps = if cleared { 0 } else { nav_tolerance_bps };
+        Ok(())
+    }
+
+    /// Queues `fallback` as the source `update_assets` reads for `asset_type` when its
+    /// registered feed is stale, behind the config timelock, returning the time it takes
+    /// effect. `None` queues clearing it. Fails with `ConfigChangePending` while another
+    /// fallback is queued for the asset, and as `set_asset_fallback` does.
+    pub fn propose_asset_fallback(
+        header: &mut PriceOracleHeader,
+        registry: &AssetRegistry,
+        asset_type: AssetType,
+        fallback: Option<SourceSlot>,
+        current_time: i64,
+    ) -> Result<i64> {
+        let pending = &mut header.pending_fallbacks[asset_type.index()];
+        if pending.is_pending() {
+            msg!("A fallback for {:?} is pending until {}; cancel it first", asset_type, pending.effective_at);
+            return Err(error!(OracleError::ConfigChangePending));
+        }
+        Self::require_fallback_available(registry, asset_type, fallback)?;
+        let effective_at = current_time.saturating_add(header.config_timelock_secs() as i64);
+        header.pending_fallbacks[asset_type.index()] = PendingFallback { fallback: fallback.unwrap_or_default(), effective_at };
+        Ok(effective_at)
+    }
+
+    /// Registers the asset's pending fallback from its `effective_at` on, returning the
+    /// fallback it replaces and the new one. The fallback is checked again, as the registry
+    /// may have changed since it was proposed.
+    pub fn apply_asset_fallback(
+        header: &mut PriceOracleHeader,
+        registry: &mut AssetRegistry,
+        asset_type: AssetType,
+        current_time: i64,
+    ) -> Result<(Option<SourceSlot>, Option<SourceSlot>)> {
+        let pending = header.pending_fallbacks[asset_type.index()];
+        if !pending.is_pending() {
+            return Err(error!(OracleError::NoPendingConfig));
+        }
+        if header.locked {
+            msg!("The oracle is locked; the pending fallback can no longer be applied");
+            return Err(error!(OracleError::OracleLocked));
+        }
+        if current_time < pending.effective_at {
+            msg!("Fallback takes effect at {}, {}s from now", pending.effective_at, pending.effective_at - current_time);
+            return Err(error!(OracleError::TimelockNotElapsed));
+        }
+        let old_fallback = Self::set_asset_fallback(registry, asset_type, pending.fallback())?;
+        header.pending_fallbacks[asset_type.index()] = PendingFallback::default();
+        Ok((old_fallback, pending.fallback()))
+    }
+
+    /// Drops the asset's pending fallback, returning it
+    pub fn cancel_asset_fallback(header: &mut PriceOracleHeader, asset_type: AssetType) -> Result<Option<SourceSlot>> {
+        let pending = header.pending_fallbacks[asset_type.index()];
+        if !pending.is_pending() {
+            return Err(error!(OracleError::NoPendingConfig));
+        }
+        header.pending_fallbacks[asset_type.index()] = PendingFallback::default();
+        Ok(pending.fallback())
+    }
+
+    /// Fails unless `fallback` can be registered for `asset_type`: it must not be a feed,
+    /// fallback or stake pool registered for any asset. `None` always can.
+    fn require_fallback_available(registry: &AssetRegistry, asset_type: AssetType, fallback: Option<SourceSlot>) -> Result<()> {
+        if let Some(slot) = fallback {
+            if slot.feed == Pubkey::default() {
+                msg!("Fallback feed must be set; pass none to clear it");
+                return Err(error!(OracleError::InvalidConfig));
+            }
+            let taken = registry.asset_for_feed(&slot.feed).is_some()
+                || registry.asset_for_stake_pool(&slot.feed).is_some()
+                || matches!(registry.asset_for_fallback(&slot.feed), Some(other) if other != asset_type);
+            if taken {
+                msg!("{} is already registered in the asset registry", slot.feed);
+                return Err(error!(OracleError::DuplicateFeed));
+            }
+        }
+        Ok(())
+    }
+
+    /// Registers the source `update_assets` reads for `asset_type` when its registered feed
+    /// is stale, returning the one it replaces. `None` clears it. Fails unless the fallback
+    /// is available, see `require_fallback_available`; the instructions reach this only
+    /// through `apply_asset_fallback`.
+    pub fn set_asset_fallback(registry: &mut AssetRegistry, asset_type: AssetType, fallback: Option<SourceSlot>) -> Result<Option<SourceSlot>> {
+        Self::require_fallback_available(registry, asset_type, fallback)?;
+        let config = &mut registry.assets[asset_type.index()];
+        let old_fallback = config.fallback();
+        config.fallback = fallback.unwrap_or_default();
+        Ok(old_fallback)
+    }
+
+    /// Gets the current price for a specific asset
+    pub fn get_current_price(data: &Account<PriceOracleData>, asset_type: AssetType) -> Result<u64> {
+        data.listed(asset_type).map(|price_data| price_data.price)
+    }
+
+    /// Gets the current price for a specific asset, rejecting values older than `max_age`
+    /// and, with `reject_same_slot`, values written in the current slot
+    pub fn get_price_no_older_than(
+        data: &PriceOracleData,
+        asset_type: AssetType,
+        max_age: MaxAge,
+        reject_same_slot: bool,
+        clock: &Clock,
+    ) -> Result<QuotedPrice> {
+        let price_data = data.listed(asset_type)?;
+        if price_data.last_update_time == 0 {
+            return Err(error!(OracleError::PriceNotAvailable));
+        }
+        if price_data.is_stale(clock, max_age) {
+            msg!("Price for {:?} is stale. Produced by its feed at time: {}, last checked at slot: {}", asset_type, price_data.feed_timestamp, price_data.last_checked_slot);
+            return Err(error!(OracleError::StaleData));
+        }
+        if reject_same_slot && price_data.updated_in_slot(clock) {
+            msg!("Price for {:?} was updated in the current slot {}", asset_type, clock.slot);
+            return Err(error!(OracleError::PriceUpdatedThisSlot));
+        }
+        Ok(QuotedPrice { price: price_data.price, quote: price_data.quote })
+    }
+
+    /// Gets an asset's price with its confidence, update and check times. Fails for assets
+    /// never updated or delisted; staleness is left to the caller, who gets the timestamps.
+    pub fn get_price_with_confidence(data: &PriceOracleData, asset_type: AssetType) -> Result<PriceWithConfidence> {
+        let price_data = data.listed(asset_type)?;
+        if price_data.last_update_time == 0 {
+            return Err(error!(OracleError::PriceNotAvailable));
+        }
+        Ok(PriceWithConfidence {
+            price: price_data.price,
+            quote: price_data.quote,
+            confidence: price_data.confidence,
+            last_update_time: price_data.last_update_time,
+            last_checked_time: price_data.last_checked_time,
+            feed_timestamp: price_data.feed_timestamp,
+        })
+    }
+
+    /// Gets the last stored price for an asset whatever its age, flagged `degraded` when it
+    /// is stale under the asset's staleness policy. Fails only for assets never updated or delisted.
+    pub fn get_price_lenient(
+        header: &PriceOracleHeader,
+        data: &PriceOracleData,
+        asset_type: AssetType,
+        clock: &Clock,
+    ) -> Result<LenientPrice> {
+        let price_data = data.listed(asset_type)?;
+        if price_data.last_update_time == 0 {
+            return Err(error!(OracleError::PriceNotAvailable));
+        }
+        Ok(LenientPrice {
+            price: price_data.price,
+            quote: price_data.quote,
+            last_update_time: price_data.last_update_time,
+            feed_timestamp: price_data.feed_timestamp,
+            staleness_secs: price_data.feed_age_secs(clock),
+            degraded: price_data.is_stale(clock, header.feed_max_age(asset_type)),
+        })
+    }
+
+    /// Fails unless the oracle is running and the asset's price is no older than
+    /// `max_age_secs` and, with `reject_same_slot`, was not written in the current slot;
+    /// reads nothing but the two flags, timestamps and slot and writes nothing
+    pub fn assert_price_fresh(
+        header: &PriceOracleHeader,
+        data: &PriceOracleData,
+        asset_type: AssetType,
+        max_age_secs: u32,
+        reject_same_slot: bool,
+        clock: &Clock,
+    ) -> Result<()> {
+        if header.emergency_stop {
+            return Err(error!(OracleError::EmergencyStop));
+        }
+        let price_data = data.listed(asset_type)?;
+        if price_data.last_update_time == 0 {
+            return Err(error!(OracleError::PriceNotAvailable));
+        }
+        if price_data.is_stale(clock, MaxAge::Seconds(max_age_secs as i64)) {
+            return Err(error!(OracleError::StaleData));
+        }
+        if reject_same_slot && price_data.updated_in_slot(clock) {
+            return Err(error!(OracleError::PriceUpdatedThisSlot));
+        }
+        Ok(())
+    }
+
+    /// Every asset's stored price, flagged stale against its own staleness policy
+    pub fn get_all_prices(header: &PriceOracleHeader, data: &PriceOracleData, clock: &Clock) -> Vec<AssetPrice> {
+        AssetType::iter().map(|asset_type| {
+            let price_data = &data.price_data[asset_type.index()];
+            AssetPrice {
+                asset_type,
+                price: price_data.price,
+                quote: price_data.quote,
+                apy: price_data.apy,
+                last_update_time: price_data.last_update_time,
+                last_update_slot: price_data.last_update_slot,
+                last_checked_time: price_data.last_checked_time,
+                feed_timestamp: price_data.feed_timestamp,
+                max_age_secs: header.max_age_secs(asset_type) as u32,
+                stale: price_data.last_update_time == 0 || price_data.is_stale(clock, header.feed_max_age(asset_type)),
+                delisted: price_data.delisted,
+                confidence: price_data.confidence,
+            }
+        }).collect()
+    }
+
+    /// Encodes the current price set under the next attestation sequence, refused while
+    /// the oracle is stopped
+    pub fn attest_prices(header: &mut PriceOracleHeader, data: &PriceOracleData, clock: &Clock) -> Result<PriceAttestation> {
+        if header.emergency_stop {
+            msg!("Emergency stop is activated. Attestation aborted.");
+            return Err(error!(OracleError::EmergencyStop));
+        }
+        header.attestation_sequence = header.attestation_sequence.checked_add(1).ok_or(error!(OracleError::ConversionOverflow))?;
+        Ok(PriceAttestation::new(data, header.attestation_sequence, clock.unix_timestamp))
+    }
+
+    /// Where an asset's stored price came from
+    pub fn get_price_provenance(data: &PriceOracleData, asset_type: AssetType) -> Result<PriceProvenance> {
+        let price_data = &data.price_data[asset_type.index()];
+        if price_data.last_update_time == 0 {
+            return Err(error!(OracleError::PriceNotAvailable));
+        }
+        Ok(price_data.provenance)
+    }
+
+    /// Sets the currency an asset is quoted in. Prices in the old currency are not
+    /// comparable with new ones, so a change clears the asset's price and change-limit
+    /// baseline; returns the previous quote.
+    pub fn set_asset_quote(data: &mut PriceOracleData, asset_type: AssetType, quote: QuoteCurrency) -> QuoteCurrency {
+        let price_data = &mut data.price_data[asset_type.index()];
+        let old_quote = price_data.quote;
+        if old_quote != quote {
+            price_data.set_quote(quote);
+            data.advance_nonce();
+        }
+        old_quote
+    }
+
+    /// Suspends or resumes updates of one asset and returns the previous state. Unlike
+    /// `delist_asset` this is reversible and leaves the stored price readable.
+    pub fn set_asset_paused(header: &mut PriceOracleHeader, asset_type: AssetType, paused: bool) -> bool {
+        std::mem::replace(&mut header.paused[asset_type.index()], paused)
+    }
+
+    /// Retires an asset for good: updates skip it and its price and APY are no longer
+    /// served, while its history and epoch snapshots stay readable
+    pub fn delist_asset(data: &mut PriceOracleData, asset_type: AssetType) -> Result<()> {
+        data.listed(asset_type)?;
+        data.price_data[asset_type.index()].delisted = true;
+        data.advance_nonce();
+        Ok(())
+    }
+
+    /// How many units of `counter` one unit of `base` is worth, fixed point.
+    ///
+    /// Both assets must be quoted in the same currency unless `convert_quotes` is set,
+    /// in which case SOL-quoted prices are first converted to USD at the SOL price.
+    pub fn get_exchange_rate(data: &PriceOracleData, base: AssetType, counter: AssetType, convert_quotes: bool) -> Result<u64> {
+        let (base_price, counter_price) = Self::comparable_prices(data, base, counter, convert_quotes)?;
+        mul_div(base_price, PRICE_SCALE, counter_price)
+    }
+
+    /// Converts `amount` of `from` into `to` at the exchange rate, truncating once at the end
+    pub fn convert_amount(data: &PriceOracleData, from: AssetType, to: AssetType, amount: u64, convert_quotes: bool) -> Result<u64> {
+        let (from_price, to_price) = Self::comparable_prices(data, from, to, convert_quotes)?;
+        mul_div(amount, from_price, to_price)
+    }
+
+    /// Non-zero prices of `base` and `counter` in a common quote currency
+    fn comparable_prices(data: &PriceOracleData, base: AssetType, counter: AssetType, convert_quotes: bool) -> Result<(u64, u64)> {
+        let base_data = data.listed(base)?;
+        let counter_data = data.listed(counter)?;
+        let (base_price, counter_price) = if base_data.quote == counter_data.quote {
+            (base_data.price, counter_data.price)
+        } else if convert_quotes {
+            (Self::usd_price(data, base)?, Self::usd_price(data, counter)?)
+        } else {
+            msg!("{:?} is quoted in {:?} but {:?} in {:?}", base, base_data.quote, counter, counter_data.quote);
+            return Err(error!(OracleError::QuoteCurrencyMismatch));
+        };
+        if base_price == 0 || counter_price == 0 {
+            return Err(error!(OracleError::PriceNotAvailable));
+        }
+        Ok((base_price, counter_price))
+    }
+
+    /// USD value of `amount` of the asset, in units of its `decimals`, net of its haircut,
+    /// fixed point and truncated. For liquidations, so it fails unless the oracle is running
+    /// and the asset, and SOL when the asset is quoted in it, is listed, priced, not paused
+    /// and fresh under its staleness policy.
+    pub fn get_collateral_value(
+        header: &PriceOracleHeader,
+        data: &PriceOracleData,
+        asset_type: AssetType,
+        amount: u64,
+        clock: &Clock,
+    ) -> Result<u64> {
+        if header.emergency_stop {
+            return Err(error!(OracleError::EmergencyStop));
+        }
+        let price = Self::usable_usd_price(header, data, asset_type, clock)?;
+        collateral_value(amount, price, header.decimals(asset_type), header.haircut_bps(asset_type)).map_err(|e| error!(e))
+    }
+
+    /// USD value with 9 decimals of the token accounts in `accounts`, and of each of them,
+    /// see the `portfolio` module. Fails with `TooManyPositions` past
+    /// `MAX_PORTFOLIO_POSITIONS` accounts.
+    pub fn get_portfolio_value(
+        header: &PriceOracleHeader,
+        data: &PriceOracleData,
+        accounts: &[AccountInfo],
+        skip_unpriced: bool,
+        clock: &Clock,
+    ) -> Result<PortfolioValue> {
+        if header.emergency_stop {
+            return Err(error!(OracleError::EmergencyStop));
+        }
+        if accounts.len() > MAX_PORTFOLIO_POSITIONS {
+            msg!("{} token accounts passed, at most {} are valued at once", accounts.len(), MAX_PORTFOLIO_POSITIONS);
+            return Err(error!(OracleError::TooManyPositions));
+        }
+        let mut total: u128 = 0;
+        let mut positions = Vec::with_capacity(accounts.len());
+        for account in accounts {
+            let balance = read_token_account(account)?;
+            let asset_type = header.asset_for_mint(&balance.mint);
+            let valued = match asset_type {
+                Some(asset_type) => Self::usable_usd_price(header, data, asset_type, clock)
+                    .and_then(|price| collateral_value(balance.amount, price, header.decimals(asset_type), 0).map_err(|e| error!(e))),
+                None => {
+                    msg!("Mint {} of token account {} is not registered for any asset", balance.mint, account.key);
+                    Err(error!(OracleError::UnknownMint))
+                }
+            };
+            let (value, code) = match valued {
+                Ok(value) => (value, 0),
+                Err(e) if skip_unpriced && is_skippable(error_code(&e)) => {
+                    msg!("Skipping token account {}", account.key);
+                    (0, error_code(&e))
+                }
+                Err(e) => return Err(e),
+            };
+            total = total.checked_add(value as u128).ok_or(error!(OracleError::ConversionOverflow))?;
+            positions.push(PortfolioPosition { token_account: *account.key, mint: balance.mint, asset_type, amount: balance.amount, value, error_code: code });
+        }
+        let total = u64::try_from(total).map_err(|_| error!(OracleError::ConversionOverflow))?;
+        Ok(PortfolioValue { total, positions })
+    }
+
+    /// Creates the creator's basket `id` with `weights`
+    pub fn create_basket(basket: &mut Basket, data: &PriceOracleData, creator: Pubkey, id: u64, weights: &[BasketWeight], bump: u8) -> Result<()> {
+        basket.set_weights(data, weights)?;
+        basket.creator = creator;
+        basket.id = id;
+        basket.bump = bump;
+        Ok(())
+    }
+
+    /// USD value of one unit of the basket with 9 decimals: the weighted sum of its
+    /// components' USD prices, truncated once. Every component must be usable as for
+    /// `get_collateral_value`.
+    pub fn get_basket_price(header: &PriceOracleHeader, data: &PriceOracleData, basket: &Basket, clock: &Clock) -> Result<u64> {
+        if header.emergency_stop {
+            return Err(error!(OracleError::EmergencyStop));
+        }
+        let mut weighted: u128 = 0;
+        for component in basket.weights() {
+            let asset_type = component.asset_type;
+            let usable = Self::require_usable_price(header, data, asset_type, clock).and_then(|_| match data.price_data[asset_type.index()].quote {
+                QuoteCurrency::SOL => Self::require_usable_price(header, data, AssetType::SOL, clock),
+                QuoteCurrency::USD => Ok(()),
+            });
+            if let Err(e) = usable {
+                msg!("Basket {} of {} cannot price its {:?} component", basket.id, basket.creator, asset_type);
+                return Err(e);
+            }
+            weighted += Self::usd_price(data, asset_type)? as u128 * component.weight_bps as u128;
+        }
+        u64::try_from(weighted / BASKET_TOTAL_WEIGHT_BPS as u128).map_err(|_| error!(OracleError::ConversionOverflow))
+    }
+
+    /// The asset's quote for `write_quote_to`. Fails unless the oracle is running and the
+    /// asset is usable as for `get_collateral_value`, in whichever currency it is quoted.
+    pub fn pushed_quote(header: &PriceOracleHeader, data: &PriceOracleData, asset_type: AssetType, clock: &Clock) -> Result<PushedQuote> {
+        if header.emergency_stop {
+            return Err(error!(OracleError::EmergencyStop));
+        }
+        Self::require_usable_price(header, data, asset_type, clock)?;
+        let price_data = &data.price_data[asset_type.index()];
+        Ok(PushedQuote {
+            asset_type,
+            quote: price_data.quote,
+            price: price_data.price,
+            apy: price_data.apy,
+            timestamp: price_data.feed_timestamp,
+            nonce: data.nonce,
+        })
+    }
+
+    /// Fails unless the asset's stored price is listed, set, not paused and fresh
+    fn require_usable_price(header: &PriceOracleHeader, data: &PriceOracleData, asset_type: AssetType, clock: &Clock) -> Result<()> {
+        let price_data = data.listed(asset_type)?;
+        if price_data.last_update_time == 0 || price_data.price == 0 {
+            msg!("{:?} has no price", asset_type);
+            return Err(error!(OracleError::PriceNotAvailable));
+        }
+        if header.is_paused(asset_type) {
+            msg!("{:?} is paused", asset_type);
+            return Err(error!(OracleError::AssetPaused));
+        }
+        if price_data.is_stale(clock, header.feed_max_age(asset_type)) {
+            msg!("Price for {:?} is stale. Produced by its feed at time: {}, last checked at slot: {}", asset_type, price_data.feed_timestamp, price_data.last_checked_slot);
+            return Err(error!(OracleError::StaleData));
+        }
+        Ok(())
+    }
+
+    /// USD price of an asset usable as for `get_collateral_value`, which for a SOL-quoted
+    /// asset takes a usable SOL price as well
+    fn usable_usd_price(header: &PriceOracleHeader, data: &PriceOracleData, asset_type: AssetType, clock: &Clock) -> Result<u64> {
+        Self::require_usable_price(header, data, asset_type, clock)?;
+        if data.price_data[asset_type.index()].quote == QuoteCurrency::SOL {
+            Self::require_usable_price(header, data, AssetType::SOL, clock)?;
+        }
+        Self::usd_price(data, asset_type)
+    }
+
+    /// Price of an asset in USD, converting SOL-quoted prices at the USD-quoted SOL price
+    fn usd_price(data: &PriceOracleData, asset_type: AssetType) -> Result<u64> {
+        let price_data = &data.price_data[asset_type.index()];
+        match price_data.quote {
+            QuoteCurrency::USD => Ok(price_data.price),
+            QuoteCurrency::SOL => {
+                let sol = data.listed(AssetType::SOL)?;
+                if sol.quote != QuoteCurrency::USD || sol.price == 0 {
+                    msg!("No SOL/USD price to convert {:?} through", asset_type);
+                    return Err(error!(OracleError::PriceNotAvailable));
+                }
+                mul_div(price_data.price, sol.price, PRICE_SCALE)
+            }
+        }
+    }
+
+    /// Initializes the per-asset price history
+    pub fn initialize_history(history: &mut PriceHistory, bump: u8) -> Result<()> {
+        history.assets = [AssetHistory::default(); ASSET_COUNT];
+        history.bump = bump;
+        Ok(())
+    }
+
+    /// Appends the stored price of each of `assets` to the history
+    pub fn record_history(history: &mut PriceHistory, data: &PriceOracleData, assets: &[AssetType]) {
+        for asset_type in assets {
+            let price_data = &data.price_data[asset_type.index()];
+            history.assets[asset_type.index()].push(price_data.price, price_data.last_update_time);
+        }
+    }
+
+    /// Volatility of simple returns over the observations within `window` seconds, in basis points
+    pub fn get_volatility(history: &PriceHistory, asset_type: AssetType, window: i64, current_time: i64) -> Result<u64> {
+        if window <= 0 {
+            return Err(error!(OracleError::InvalidConfig));
+        }
+        let prices = history.asset(asset_type).prices_within(current_time, window);
+        if prices.len() < MIN_VOLATILITY_OBSERVATIONS {
+            msg!("{:?} has {} observation(s) in the last {}s, at least {} are needed", asset_type, prices.len(), window, MIN_VOLATILITY_OBSERVATIONS);
+            return Err(error!(OracleError::DataNotAvailable));
+        }
+        simple_return_volatility_bps(&prices).ok_or_else(|| error!(OracleError::DataNotAvailable))
+    }
+
+    /// Recorded price of an asset in effect at `timestamp`, with the time it was observed
+    pub fn get_price_at(history: &PriceHistory, asset_type: AssetType, timestamp: i64) -> Result<PriceObservation> {
+        history.asset(asset_type).at_or_before(timestamp).ok_or_else(|| {
+            msg!("{:?} has no recorded price at or before {}", asset_type, timestamp);
+            error!(OracleError::DataNotAvailable)
+        })
+    }
+
+    /// Drops all but the `keep_last_n` most recent observations of an asset from the price
+    /// history, all of them when zero. Returns the number dropped.
+    pub fn prune_price_history(history: &mut PriceHistory, asset_type: AssetType, keep_last_n: u8) -> u8 {
+        history.assets[asset_type.index()].retain_latest(keep_last_n as usize) as u8
+    }
+
+    /// Initializes the per-asset APY history
+    pub fn initialize_apy_history(history: &mut ApyHistory, min_samples: u8, bump: u8) -> Result<()> {
+        history.assets = [AssetApyHistory::default(); ASSET_COUNT];
+        history.bump = bump;
+        Self::set_min_apy_samples(history, min_samples)?;
+        Ok(())
+    }
+
+    /// Sets the fewest samples `get_average_apy` needs in its window. Returns the previous
+    /// minimum.
+    pub fn set_min_apy_samples(history: &mut ApyHistory, min_samples: u8) -> Result<u8> {
+        if min_samples == 0 {
+            return Err(error!(OracleError::InvalidConfig));
+        }
+        Ok(std::mem::replace(&mut history.min_samples, min_samples))
+    }
+
+    /// Samples the stored APY of each of `assets`, skipping those sampled less than
+    /// `APY_SAMPLE_INTERVAL_SECS` ago
+    pub fn record_apy_history(history: &mut ApyHistory, data: &PriceOracleData, assets: &[AssetType], current_time: i64) {
+        for asset_type in assets {
+            match fixed_to_bps(data.price_data[asset_type.index()].apy) {
+                Ok(apy_bps) => {
+                    history.assets[asset_type.index()].push(apy_bps, current_time);
+                }
+                Err(e) => msg!("Not sampling the APY of {:?}: {}", asset_type, e),
+            }
+        }
+    }
+
+    /// Drops all but the `keep_last_n` most recent APY samples of an asset, all of them when
+    /// zero. Returns the number dropped.
+    pub fn prune_apy_history(history: &mut ApyHistory, asset_type: AssetType, keep_last_n: u8) -> u8 {
+        history.assets[asset_type.index()].retain_latest(keep_last_n as usize) as u8
+    }
+
+    /// Simple average of the APY samples within `window` seconds, in basis points (truncated)
+    pub fn get_average_apy(history: &ApyHistory, asset_type: AssetType, window: i64, current_time: i64) -> Result<u32> {
+        if window <= 0 {
+            return Err(error!(OracleError::InvalidConfig));
+        }
+        let (count, average) = history.asset(asset_type).average_within(current_time, window);
+        match average {
+            Some(average) if count >= history.min_samples as usize => Ok(average),
+            _ => {
+                msg!("{:?} has {} APY sample(s) in the last {}s, at least {} are needed", asset_type, count, window, history.min_samples);
+                Err(error!(OracleError::DataNotAvailable))
+            }
+        }
+    }
+
+    /// Records the asset's current price and APY for the current epoch, once per epoch
+    pub fn snapshot_epoch(
+        snapshots: &mut EpochSnapshots,
+        data: &PriceOracleData,
+        asset_type: AssetType,
+        bump: u8,
+        clock: &Clock,
+    ) -> Result<EpochSnapshot> {
+        let price_data = data.listed(asset_type)?;
+        if price_data.last_update_time == 0 {
+            msg!("{:?} has never been updated", asset_type);
+            return Err(error!(OracleError::DataNotAvailable));
+        }
+
+        snapshots.asset_type = asset_type;
+        snapshots.bump = bump;
+        let snapshot = EpochSnapshot { epoch: clock.epoch, price: price_data.price, apy: price_data.apy, quote: price_data.quote };
+        snapshots.record(snapshot)?;
+        Ok(snapshot)
+    }
+
+    /// Removes an asset's snapshots of epochs before `before_epoch`. Returns the number
+    /// removed.
+    pub fn prune_epoch_snapshots(snapshots: &mut EpochSnapshots, before_epoch: u64) -> u32 {
+        snapshots.prune_before(before_epoch) as u32
+    }
+
+    /// Gets the price recorded for an asset in a given epoch
+    pub fn get_epoch_price(snapshots: &EpochSnapshots, epoch: u64) -> Result<QuotedPrice> {
+        snapshots.get(epoch).map(|snapshot| QuotedPrice { price: snapshot.price, quote: snapshot.quote }).ok_or_else(|| {
+            msg!("{:?} has no snapshot for epoch {}", snapshots.asset_type, epoch);
+            error!(OracleError::DataNotAvailable)
+        })
+    }
+
+    /// Change between the two most recent observations of an asset
+    pub fn get_price_change(data: &Account<PriceOracleData>, asset_type: AssetType) -> Result<PriceChange> {
+        data.price_data[asset_type.index()].price_change().ok_or_else(|| {
+            msg!("{:?} has fewer than two observations", asset_type);
+            error!(OracleError::DataNotAvailable)
+        })
+    }
+
+    /// Stores `price` and `apy` as the asset's latest observation with no feed and no
+    /// validation, for localnet testing. Must never be compiled into a production build.
+    #[cfg(feature = "mock-oracle")]
+    pub fn set_mock_price(data: &mut PriceOracleData, asset_type: AssetType, price: u64, apy: u64, clock: &Clock) -> Result<()> {
+        data.listed(asset_type)?;
+        let price_data = &mut data.price_data[asset_type.index()];
+        price_data.record_price(price, PriceProvenance::default(), Confidence::default(), clock.unix_timestamp, clock);
+        price_data.apy = apy;
+        data.advance_nonce();
+        Ok(())
+    }
+
+    /// Highest and lowest prices accepted for the asset since the last reset
+    pub fn get_watermarks(data: &PriceOracleData, asset_type: AssetType) -> Result<Watermarks> {
+        data.price_data[asset_type.index()].watermarks().ok_or_else(|| {
+            msg!("{:?} has no accepted price since its watermarks were reset", asset_type);
+            error!(OracleError::DataNotAvailable)
+        })
+    }
+
+    /// Forgets the asset's watermarks, so the next accepted price starts both. Returns the
+    /// watermarks that were cleared, if any.
+    pub fn reset_watermarks(data: &mut PriceOracleData, asset_type: AssetType) -> Option<Watermarks> {
+        let price_data = &mut data.price_data[asset_type.index()];
+        let old = price_data.watermarks();
+        price_data.high_watermark = 0;
+        price_data.high_watermark_time = 0;
+        price_data.low_watermark = 0;
+        price_data.low_watermark_time = 0;
+        if old.is_some() {
+            data.advance_nonce();
+        }
+        old
+    }
+
+    /// Gets the current APY for a specific asset
+    pub fn get_current_apy(data: &Account<PriceOracleData>, asset_type: AssetType) -> Result<u64> {
+        data.listed(asset_type).map(|price_data| price_data.apy)
+    }
+
+    /// Gets the current APY for a specific asset in basis points, rounded as in `fixed_to_bps`
+    pub fn get_current_apy_bps(data: &PriceOracleData, asset_type: AssetType) -> Result<u32> {
+        let apy = data.listed(asset_type)?.apy;
+        fixed_to_bps(apy).map_err(|e| {
+            msg!("APY of {:?} does not fit in basis points: {}", asset_type, fixed_to_f64(apy));
+            error!(e)
+        })
+    }
+
+    /// Fails with `UpdatesPaused` while `set_updates_paused` holds ingestion for maintenance
+    pub fn require_updates_not_paused(header: &PriceOracleHeader) -> Result<()> {
+        if header.updates_paused {
+            msg!("Updates are paused for maintenance. Update aborted.");
+            return Err(error!(OracleError::UpdatesPaused));
+        }
+        Ok(())
+    }
+
+    /// Pauses or resumes every update instruction, leaving reads alone. Returns the previous state.
+    pub fn set_updates_paused(header: &mut PriceOracleHeader, paused: bool) -> bool {
+        std::mem::replace(&mut header.updates_paused, paused)
+    }
+
+    /// Checks if emergency stop is activated
+    pub fn is_emergency_stopped(header: &Account<PriceOracleHeader>) -> bool {
+        header.emergency_stop
+    }
+
+    /// Sets the emergency stop status and returns the previous one.
+    ///
+    /// Stopping takes effect at once. Clearing goes through the config timelock: the first
+    /// call schedules the clear and leaves the stop in place, a call from the scheduled time
+    /// on clears it, and stopping again cancels a scheduled clear. Toggles closer together
+    /// than `min_stop_change_interval` are rejected unless `force` is set; setting the state
+    /// it already has is a no-op that is not counted.
+    pub fn set_emergency_stop(
+        header: &mut PriceOracleHeader,
+        stop: bool,
+        force: bool,
+        current_time: i64,
+    ) -> Result<bool> {
+        let old_state = header.emergency_stop;
+        if old_state == stop {
+            if stop {
+                header.stop_clear_effective_at = 0;
+            }
+            return Ok(old_state);
+        }
+        // A locked oracle can still be stopped, but a stop can no longer be cleared
+        if header.locked && !stop {
+            msg!("The oracle is locked; the emergency stop can no longer be cleared");
+            return Err(error!(OracleError::OracleLocked));
+        }
+        if !stop && header.resume_needs_approval() {
+            msg!("Stop incident {} needs {} approvals; use approve_resume and execute_resume", header.stop_incident, header.resume_threshold);
+            return Err(error!(OracleError::ResumeApprovalRequired));
+        }
+
+        let elapsed = current_time.saturating_sub(header.last_stop_change_time);
+        if !force && header.stop_change_count > 0 && elapsed < header.min_stop_change_interval {
+            msg!("Emergency stop changed {}s ago, minimum interval is {}s", elapsed, header.min_stop_change_interval);
+            return Err(error!(OracleError::StopChangeTooFrequent));
+        }
+
+        if !stop {
+            if header.stop_clear_effective_at == 0 {
+                header.stop_clear_effective_at = current_time.saturating_add(header.config_timelock_secs() as i64);
+                return Ok(old_state);
+            }
+            if current_time < header.stop_clear_effective_at {
+                msg!("Emergency stop clears at {}, {}s from now", header.stop_clear_effective_at, header.stop_clear_effective_at - current_time);
+                return Err(error!(OracleError::TimelockNotElapsed));
+            }
+        }
+
+        if stop {
+            header.begin_stop_incident(false, current_time);
+        } else {
+            header.end_stop_incident(current_time);
+        }
+        header.stop_clear_effective_at = 0;
+        header.last_stop_change_time = current_time;
+        header.stop_change_count = header.stop_change_count.saturating_add(1);
+        Ok(old_state)
+    }
+
+    /// Records `approver`'s vote to resume from the current stop incident and returns the
+    /// approvals it now has. Votes from earlier incidents do not count.
+    pub fn approve_resume(header: &mut PriceOracleHeader, approver: &Pubkey) -> Result<u8> {
+        if !header.emergency_stop {
+            return Err(error!(OracleError::NotStopped));
+        }
+        if header.locked {
+            msg!("The oracle is locked; the emergency stop can no longer be cleared");
+            return Err(error!(OracleError::OracleLocked));
+        }
+        let slot = header.resume_approvers.iter()
+            .position(|key| *key != Pubkey::default() && key == approver)
+            .ok_or_else(|| error!(OracleError::NotResumeApprover))?;
+        if header.resume_approvals[slot] == header.stop_incident {
+            msg!("{} already approved resuming from incident {}", approver, header.stop_incident);
+            return Err(error!(OracleError::AlreadyApproved));
+        }
+        header.resume_approvals[slot] = header.stop_incident;
+        Ok(header.resume_approval_count())
+    }
+
+    /// Clears the stop once the current incident has `resume_threshold` approvals and
+    /// returns how long the incident lasted. The approvals stand in for the admin's
+    /// timelocked clear, so no delay applies.
+    pub fn execute_resume(header: &mut PriceOracleHeader, current_time: i64) -> Result<u64> {
+        if !header.emergency_stop {
+            return Err(error!(OracleError::NotStopped));
+        }
+        if header.locked {
+            msg!("The oracle is locked; the emergency stop can no longer be cleared");
+            return Err(error!(OracleError::OracleLocked));
+        }
+        let approvals = header.resume_approval_count();
+        if header.resume_threshold == 0 || approvals < header.resume_threshold {
+            msg!("Stop incident {} has {} of {} approvals", header.stop_incident, approvals, header.resume_threshold);
+            return Err(error!(OracleError::ResumeThresholdNotMet));
+        }
+        let duration = header.end_stop_incident(current_time);
+        header.stop_clear_effective_at = 0;
+        header.last_stop_change_time = current_time;
+        header.stop_change_count = header.stop_change_count.saturating_add(1);
+        Ok(duration)
+    }
+
+    /// Checks that `signer` holds `role`. A known key lacking the role fails with
+    /// `WrongRole`; any other key with `UnauthorizedAccess`.
+    pub fn require_role(header: &PriceOracleHeader, signer: &Pubkey, role: Role) -> Result<()> {
+        let is_admin = *signer == header.authority;
+        let is_operator = header.operator != Pubkey::default() && *signer == header.operator;
+        let is_council = Self::is_council(header, signer);
+        let is_thread = header.thread_authority != Pubkey::default() && *signer == header.thread_authority;
+        let allowed = match role {
+            Role::Admin => is_admin,
+            Role::OperatorOrAdmin => is_admin || is_operator,
+            Role::Updater => is_admin || is_operator || is_thread,
+            Role::Guardian => is_admin || is_operator || is_council,
+        };
+        if allowed {
+            Ok(())
+        } else if is_admin || is_operator || is_council || is_thread || header.is_asset_authority(signer) {
+            msg!("Signer {} does not hold the {:?} role", signer, role);
+            Err(error!(OracleError::WrongRole))
+        } else {
+            Err(error!(OracleError::UnauthorizedAccess))
+        }
+    }
+
+    /// `require_role` for governance mutations, which are refused for good once the oracle is locked
+    pub fn require_unlocked_role(header: &PriceOracleHeader, signer: &Pubkey, role: Role) -> Result<()> {
+        Self::require_role(header, signer, role)?;
+        Self::require_unlocked(header)
+    }
+
+    /// `require_role` for actions scoped to one asset, which its asset authority may also take
+    pub fn require_asset_role(header: &PriceOracleHeader, signer: &Pubkey, asset_type: AssetType, role: Role) -> Result<()> {
+        if header.asset_authority(asset_type) == Some(*signer) {
+            return Ok(());
+        }
+        Self::require_role(header, signer, role)
+    }
+
+    /// `require_unlocked_role` for actions scoped to one asset, see `require_asset_role`
+    pub fn require_unlocked_asset_role(header: &PriceOracleHeader, signer: &Pubkey, asset_type: AssetType, role: Role) -> Result<()> {
+        Self::require_asset_role(header, signer, asset_type, role)?;
+        Self::require_unlocked(header)
+    }
+
+    fn require_unlocked(header: &PriceOracleHeader) -> Result<()> {
+        if header.locked {
+            msg!("The oracle is locked; feeds and configuration can no longer change");
+            return Err(error!(OracleError::OracleLocked));
+        }
+        Ok(())
+    }
+
+    /// Permanently locks the oracle. There is no instruction that clears the flag.
+    pub fn lock(header: &mut PriceOracleHeader, confirmation: &str) -> Result<()> {
+        if confirmation != LOCK_CONFIRMATION {
+            msg!("Confirmation must be exactly \"{}\"", LOCK_CONFIRMATION);
+            return Err(error!(OracleError::LockNotConfirmed));
+        }
+        header.locked = true;
+        Ok(())
+    }
+
+    /// Stopping needs a guardian (council, operator or admin); clearing the stop and
+    /// bypassing the rate limit are admin-only
+    pub fn require_emergency_stop_role(header: &PriceOracleHeader, signer: &Pubkey, stop: bool, force: bool) -> Result<()> {
+        let role = if stop && !force { Role::Guardian } else { Role::Admin };
+        Self::require_role(header, signer, role)
+    }
+
+    /// Points feed validation at a new Switchboard program and returns the previous id.
+    /// `program` must be the new program's executable account, so a mistyped id cannot
+    /// leave every feed failing the owner check.
+    pub fn set_switchboard_program_id(header: &mut PriceOracleHeader, program: &AccountInfo) -> Result<Pubkey> {
+        if *program.key == Pubkey::default() || !program.executable {
+            msg!("{} is not an executable program", program.key);
+            return Err(error!(OracleError::InvalidConfig));
+        }
+        let old_program_id = header.switchboard_program_id;
+        header.switchboard_program_id = *program.key;
+        Ok(old_program_id)
+    }
+
+    /// Sets or clears (`Pubkey::default()`) the operator key
+    pub fn set_operator(header: &mut PriceOracleHeader, operator: Pubkey) {
+        header.operator = operator;
+    }
+
+    /// Sets or clears (`Pubkey::default()`) the emergency council key and returns the previous one
+    pub fn set_emergency_council(header: &mut PriceOracleHeader, council: Pubkey) -> Pubkey {
+        std::mem::replace(&mut header.emergency_council, council)
+    }
+
+    /// Sets or clears (`Pubkey::default()`) the thread authority and returns the previous one
+    pub fn set_thread_authority(header: &mut PriceOracleHeader, thread_authority: Pubkey) -> Pubkey {
+        std::mem::replace(&mut header.thread_authority, thread_authority)
+    }
+
+    /// Sets or clears (`None`) the asset's authority and returns the previous one
+    pub fn set_asset_authority(header: &mut PriceOracleHeader, asset_type: AssetType, authority: Option<Pubkey>) -> Option<Pubkey> {
+        let old_authority = header.asset_authority(asset_type);
+        header.asset_authorities[asset_type.index()] = authority.unwrap_or_default();
+        old_authority
+    }
+
+    /// Sets or clears (`Pubkey::default()`) the On-Demand queue and returns the previous one
+    pub fn set_on_demand_queue(header: &mut PriceOracleHeader, queue: Pubkey) -> Pubkey {
+        std::mem::replace(&mut header.on_demand_queue, queue)
+    }
+
+    /// Sets or clears (`Pubkey::default()`) the trusted publisher and returns the previous one
+    pub fn set_trusted_publisher(header: &mut PriceOracleHeader, publisher: Pubkey) -> Pubkey {
+        std::mem::replace(&mut header.trusted_publisher, publisher)
+    }
+
+    /// Adds `program_id` to the watcher allowlist, or removes it when `!allowed`. Returns
+    /// whether the list changed. Fails with `InvalidConfig` for the default key or when the
+    /// list is full.
+    pub fn set_watcher(header: &mut PriceOracleHeader, program_id: Pubkey, allowed: bool) -> Result<bool> {
+        if program_id == Pubkey::default() {
+            return Err(error!(OracleError::InvalidConfig));
+        }
+        let slot = header.watchers.iter().position(|watcher| *watcher == program_id);
+        match (slot, allowed) {
+            (Some(_), true) | (None, false) => Ok(false),
+            (Some(index), false) => {
+                header.watchers[index] = Pubkey::default();
+                Ok(true)
+            }
+            (None, true) => {
+                let empty = header.watchers.iter().position(|watcher| *watcher == Pubkey::default()).ok_or_else(|| {
+                    msg!("The watcher list is full ({} programs)", MAX_WATCHERS);
+                    error!(OracleError::InvalidConfig)
+                })?;
+                header.watchers[empty] = program_id;
+                Ok(true)
+            }
+        }
+    }
+
+    /// Stops the oracle on behalf of an allowlisted watcher program. `watcher_authority`
+    /// must have signed and be the program's `WATCHER_SEED` PDA, which only the program can
+    /// sign for. Cancels a scheduled clear but never clears a stop itself. Returns the
+    /// previous stop state.
+    pub fn trigger_watcher_stop(header: &mut PriceOracleHeader, watcher_program: &Pubkey, watcher_authority: &Pubkey, current_time: i64) -> Result<bool> {
+        if !header.is_watcher(watcher_program) {
+            msg!("{} is not an allowlisted watcher program", watcher_program);
+            return Err(error!(OracleError::WatcherNotAllowed));
+        }
+        let expected = Self::get_watcher_authority_pda(watcher_program).0;
+        if *watcher_authority != expected {
+            msg!("Watcher authority {} is not {}, the PDA of {}", watcher_authority, expected, watcher_program);
+            return Err(error!(OracleError::WatcherNotAllowed));
+        }
+
+        let old_state = header.emergency_stop;
+        header.stop_clear_effective_at = 0;
+        if !old_state {
+            header.begin_stop_incident(true, current_time);
+            header.stop_watcher = *watcher_program;
+            header.last_stop_change_time = current_time;
+            header.stop_change_count = header.stop_change_count.saturating_add(1);
+        }
+        Ok(old_state)
+    }
+
+    /// Whether `signer` is the emergency council, so events can tell its actions apart
+    pub fn is_council(header: &PriceOracleHeader, signer: &Pubkey) -> bool {
+        header.emergency_council != Pubkey::default() && *signer == header.emergency_council
+    }
+
+    /// Sets the minimum interval between emergency stop toggles
+    pub fn set_min_stop_change_interval(header: &mut PriceOracleHeader, interval: i64) -> Result<()> {
+        if interval < 0 {
+            return Err(error!(OracleError::InvalidConfig));
+        }
+        header.min_stop_change_interval = interval;
+        Ok(())
+    }
+
+    /// Switches update-path feed staleness to slots (`max_feed_age_slots > 0`) or back to seconds (`0`)
+    pub fn set_max_feed_age_slots(header: &mut Account<PriceOracleHeader>, max_feed_age_slots: u64) {
+        header.max_feed_age_slots = max_feed_age_slots;
+    }
+
+    /// Sets the kind of feed `set_asset_feed` registers for an asset
+    pub fn set_asset_source(header: &mut PriceOracleHeader, asset_type: AssetType, source: SourceKind) {
+        header.sources[asset_type.index()] = source;
+    }
+
+    /// Sets how old the asset's feeds and stored price may be, within
+    /// `MIN_ASSET_MAX_AGE_SECS..=MAX_ASSET_MAX_AGE_SECS`
+    pub fn set_asset_max_age(header: &mut PriceOracleHeader, asset_type: AssetType, max_age_secs: u32) -> Result<()> {
+        if !(MIN_ASSET_MAX_AGE_SECS..=MAX_ASSET_MAX_AGE_SECS).contains(&max_age_secs) {
+            msg!("Max age must be between {}s and {}s", MIN_ASSET_MAX_AGE_SECS, MAX_ASSET_MAX_AGE_SECS);
+            return Err(error!(OracleError::InvalidConfig));
+        }
+        header.max_age_secs[asset_type.index()] = max_age_secs;
+        Ok(())
+    }
+
+    /// Sets how `update_asset_from_sources` combines the asset's sources and returns the
+    /// previous policy. Under `SourcePolicy::Priority` the sources are tried in the order
+    /// `set_asset_sources` registered them.
+    pub fn set_source_policy(header: &mut PriceOracleHeader, asset_type: AssetType, policy: SourcePolicy) -> SourcePolicy {
+        std::mem::replace(&mut header.source_policies[asset_type.index()], policy)
+    }
+
+    /// Sets how an asset handles moves past the price change limit; leaving `Confirm`
+    /// drops its pending move. Returns the previous policy.
+    pub fn set_large_move_policy(header: &mut PriceOracleHeader, asset_type: AssetType, policy: LargeMovePolicy) -> LargeMovePolicy {
+        let i = asset_type.index();
+        let old_policy = header.large_move_policy[i];
+        header.large_move_policy[i] = policy;
+        if policy != LargeMovePolicy::Confirm {
+            header.pending_prices[i] = PendingPrice::default();
+        }
+        old_policy
+    }
+
+    /// Sets the confirmation delay and tolerance of `LargeMovePolicy::Confirm`, `0` for the defaults
+    pub fn set_move_confirmation(header: &mut PriceOracleHeader, delay_secs: u32, tolerance_bps: u32) -> Result<()> {
+        if delay_secs > MAX_MOVE_CONFIRM_DELAY_SECS || tolerance_bps > MAX_MOVE_CONFIRM_TOLERANCE_BPS {
+            msg!("Confirmation delay must be at most {}s and tolerance at most {} bps", MAX_MOVE_CONFIRM_DELAY_SECS, MAX_MOVE_CONFIRM_TOLERANCE_BPS);
+            return Err(error!(OracleError::InvalidConfig));
+        }
+        header.move_confirm_delay_secs = delay_secs;
+        header.move_confirm_tolerance_bps = tolerance_bps;
+        Ok(())
+    }
+
+    /// Sets the grace period after a resume and the factor in basis points the change limit
+    /// is widened by during it; a zero period disables the grace
+    pub fn set_resume_grace(header: &mut PriceOracleHeader, grace_secs: u32, multiplier_bps: u32) -> Result<()> {
+        if grace_secs > MAX_RESUME_GRACE_SECS
+            || (grace_secs > 0 && !(MIN_RESUME_GRACE_MULTIPLIER_BPS..=MAX_RESUME_GRACE_MULTIPLIER_BPS).contains(&multiplier_bps))
+        {
+            msg!("Grace period must be at most {}s and its multiplier between {} and {} bps", MAX_RESUME_GRACE_SECS, MIN_RESUME_GRACE_MULTIPLIER_BPS, MAX_RESUME_GRACE_MULTIPLIER_BPS);
+            return Err(error!(OracleError::InvalidConfig));
+        }
+        header.resume_grace_secs = grace_secs;
+        header.resume_grace_multiplier_bps = multiplier_bps;
+        Ok(())
+    }
+
+    /// Whether `price`, just stored in place of `previous_price` at `now`, moved past the
+    /// change limit and was only accepted under the widened limit of a resume grace period
+    pub fn uses_resume_grace(header: &PriceOracleHeader, previous_price: u64, price: u64, now: i64) -> bool {
+        header.in_resume_grace(now)
+            && Self::validate_price_change(previous_price, price, header.price_change_limit_bps()).is_err()
+            && Self::validate_price_change(previous_price, price, header.effective_price_change_limit_bps(now)).is_ok()
+    }
+
+    /// Forgets the header's per-asset state tied to the stored prices, for
+    /// `reinitialize_data` rebuilding the data account, and returns the new data generation.
+    /// Fails with `NotStopped` unless the emergency stop is active.
+    pub fn reinitialize_data(header: &mut PriceOracleHeader) -> Result<u32> {
+        if !header.emergency_stop {
+            msg!("The data account can only be rebuilt while the emergency stop is active");
+            return Err(error!(OracleError::NotStopped));
+        }
+        header.pending_prices = [PendingPrice::default(); ASSET_COUNT];
+        header.clamped_prices = [0; ASSET_COUNT];
+        header.raw_yields = [RawYield::default(); ASSET_COUNT];
+        header.rejection_codes = [0; ASSET_COUNT];
+        header.last_errors = [LastError::default(); ASSET_COUNT];
+        // The rebuilt account holds no prices
+        header.state_hash = PriceOracleData::default().state_hash();
+        header.data_generation = header.data_generation.wrapping_add(1);
+        Ok(header.data_generation)
+    }
+
+    /// Sets the convention the asset's feed reports its yield in, applied from the next
+    /// update, and returns the one it replaces. The stored APY and the last raw yield are
+    /// left as they are.
+    pub fn set_yield_convention(header: &mut PriceOracleHeader, asset_type: AssetType, config: YieldConfig) -> Result<YieldConfig> {
+        config.validate()?;
+        Ok(std::mem::replace(&mut header.yield_configs[asset_type.index()], config))
+    }
+
+    /// Returns the asset's yield as last stored, in the convention its feed reported it in
+    pub fn get_raw_yield(header: &PriceOracleHeader, data: &PriceOracleData, asset_type: AssetType) -> Result<RawYield> {
+        data.listed(asset_type)?;
+        Ok(header.raw_yield(asset_type))
+    }
+
+    /// Sets the asset's APY change limit in basis points, `0` to lift it
+    pub fn set_apy_change_limit(header: &mut PriceOracleHeader, asset_type: AssetType, limit_bps: u32) {
+        header.apy_change_limit_bps[asset_type.index()] = limit_bps;
+    }
+
+    /// Sets the share of the asset's value `get_collateral_value` discounts, in basis points
+    pub fn set_haircut(header: &mut PriceOracleHeader, asset_type: AssetType, haircut_bps: u16) -> Result<()> {
+        if haircut_bps > MAX_HAIRCUT_BPS {
+            msg!("Haircut must be at most {} bps", MAX_HAIRCUT_BPS);
+            return Err(error!(OracleError::InvalidConfig));
+        }
+        header.haircut_bps[asset_type.index()] = haircut_bps;
+        Ok(())
+    }
+
+    /// Registers `mint` as the asset's token mint with the decimals read from it, and
+    /// returns the mint it replaced along with what was read. Fails with `InvalidMint`
+    /// unless it is an initialized SPL Token or Token-2022 mint, with `FrozenMint` when its
+    /// accounts start frozen and with `DuplicateMint` when another asset has it.
+    pub fn set_asset_mint(header: &mut PriceOracleHeader, asset_type: AssetType, mint: &AccountInfo) -> Result<(Pubkey, MintInfo)> {
+        if let Some(other) = AssetType::iter().find(|other| *other != asset_type && header.asset_mints[other.index()] == *mint.key) {
+            msg!("Mint {} is already registered for {:?}", mint.key, other);
+            return Err(error!(OracleError::DuplicateMint));
+        }
+        let info = read_mint(mint)?;
+        let i = asset_type.index();
+        let old_mint = header.asset_mints[i];
+        header.asset_mints[i] = *mint.key;
+        header.mint_decimals[i] = info.decimals;
+        Ok((old_mint, info))
+    }
+
+    /// Sets the weights of `get_trust_score`, which must add up to 10000 bps
+    pub fn set_trust_weights(header: &mut PriceOracleHeader, weights: TrustWeights) -> Result<()> {
+        weights.validate()?;
+        header.trust_weights = weights;
+        Ok(())
+    }
+
+    /// The asset's trust score at `clock`, see `trust_score`
+    pub fn get_trust_score(header: &PriceOracleHeader, data: &PriceOracleData, asset_type: AssetType, clock: &Clock) -> TrustScore {
+        TrustScore::compute(header, &data.price_data[asset_type.index()], asset_type, clock)
+    }
+
+    /// Makes the audit log mandatory for every privileged instruction from now on
+    pub fn initialize_audit_log(header: &mut PriceOracleHeader, audit_log: &mut AuditLog, bump: u8) {
+        audit_log.bump = bump;
+        header.audit_log_enabled = true;
+    }
+
+    /// Appends `entry` to the audit log. Without the log, fails with `AuditLogRequired`
+    /// once it exists and records nothing before.
+    pub fn record_audit(header: &PriceOracleHeader, audit_log: Option<&mut AuditLog>, entry: AuditEntry) -> Result<()> {
+        match audit_log {
+            Some(audit_log) => {
+                audit_log.record(entry);
+                Ok(())
+            }
+            None if header.audit_log_enabled => {
+                msg!("{:?} must be recorded in the audit log", entry.action);
+                Err(error!(OracleError::AuditLogRequired))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Fills `checkpoint` with the current state
+    pub fn create_checkpoint(
+        checkpoint: &mut Checkpoint,
+        header: &PriceOracleHeader,
+        data: &PriceOracleData,
+        id: u64,
+        bump: u8,
+        creator: Pubkey,
+        clock: &Clock,
+    ) -> Result<()> {
+        *checkpoint = Checkpoint::capture(id, bump, creator, header, data, clock);
+        Ok(())
+    }
+
+    /// Fails with `CheckpointTooRecent` until the checkpoint is `CHECKPOINT_MIN_CLOSE_AGE_SECS` old
+    pub fn require_checkpoint_closable(checkpoint: &Checkpoint, clock: &Clock) -> Result<()> {
+        let age = clock.unix_timestamp.saturating_sub(checkpoint.timestamp);
+        if age < CHECKPOINT_MIN_CLOSE_AGE_SECS {
+            msg!("Checkpoint {} is {}s old, it can be closed after {}s", checkpoint.id, age, CHECKPOINT_MIN_CLOSE_AGE_SECS);
+            return Err(error!(OracleError::CheckpointTooRecent));
+        }
+        Ok(())
+    }
+
+    /// Sets the asset's threshold at `index`, in the currency the asset is quoted in now
+    pub fn set_threshold(
+        thresholds: &mut PriceThresholds,
+        data: &PriceOracleData,
+        asset_type: AssetType,
+        index: u8,
+        direction: ThresholdDirection,
+        price: u64,
+        bump: u8,
+    ) -> Result<()> {
+        let quote = data.listed(asset_type)?.quote;
+        thresholds.set(asset_type, index, direction, price, quote, bump)
+    }
+
+    /// Triggers the thresholds the stored prices cross, see `PriceThresholds::check`. Fails
+    /// during an emergency stop, when the stored prices are not to be acted on.
+    pub fn check_thresholds(thresholds: &mut PriceThresholds, header: &PriceOracleHeader, data: &PriceOracleData, clock: &Clock) -> Result<Vec<ThresholdCrossing>> {
+        if header.emergency_stop {
+            return Err(error!(OracleError::EmergencyStop));
+        }
+        Ok(thresholds.check(data, clock.unix_timestamp))
+    }
+
+    /// Update counters, stop incidents and downtime up to `now` and the last update time,
+    /// for dashboards
+    pub fn get_oracle_stats(header: &PriceOracleHeader, now: i64) -> OracleStats {
+        OracleStats {
+            update_stats: header.update_stats,
+            stop_incidents: header.stop_incident,
+            last_global_update: header.last_global_update,
+            stopped_since: if header.emergency_stop { header.stop_started_at } else { 0 },
+            total_stopped_secs: header.stopped_secs(now),
+        }
+    }
+
+    /// The asset's move held for confirmation; a zero `price` means nothing is pending
+    pub fn get_pending_price(header: &PriceOracleHeader, asset_type: AssetType) -> PendingPrice {
+        header.pending_prices[asset_type.index()]
+    }
+
+    /// Returns the asset's latest recorded validation rejection, see `PriceOracleHeader::last_error`
+    pub fn get_last_error(header: &PriceOracleHeader, asset_type: AssetType) -> LastError {
+        header.last_error(asset_type)
+    }
+
+    /// Every asset the program supports, in index order, see `asset_info`
+    pub fn get_supported_assets(header: &PriceOracleHeader, data: &PriceOracleData) -> Vec<AssetInfo> {
+        AssetType::ALL.into_iter().map(|asset_type| AssetInfo::new(header, data, asset_type)).collect()
+    }
+
+    /// The asset's premium over its stake pool rate as of the latest update passed the
+    /// pool. Fails with `DataNotAvailable` when the asset has no registered stake pool or
+    /// no such update has run yet.
+    pub fn get_premium(header: &PriceOracleHeader, registry: &AssetRegistry, asset_type: AssetType) -> Result<LstPremium> {
+        if registry.assets[asset_type.index()].stake_pool == Pubkey::default() {
+            msg!("{:?} has no registered stake pool", asset_type);
+            return Err(error!(OracleError::DataNotAvailable));
+        }
+        let premium = header.premium(asset_type);
+        if premium.time == 0 {
+            msg!("No update of {:?} was passed its stake pool yet", asset_type);
+            return Err(error!(OracleError::DataNotAvailable));
+        }
+        Ok(premium)
+    }
+
+    /// Checks `header` and `data` against the invariants of the `invariants` module and
+    /// returns the mask of those violated, `0` when all hold. `previous_nonce` and
+    /// `previous_generation` are the caller's last reading of `data.nonce` and
+    /// `header.data_generation`; zeros always pass.
+    pub fn verify_invariants(
+        program_id: &Pubkey,
+        header: &Account<PriceOracleHeader>,
+        data: &Account<PriceOracleData>,
+        previous_nonce: u64,
+        previous_generation: u32,
+        now: i64,
+    ) -> u32 {
+        let mut violations = 0;
+        let mut flag = |violated: bool, bit: u32| {
+            if violated {
+                violations |= bit;
+            }
+        };
+
+        let (header_pda, header_bump) = Self::get_price_oracle_header_pda(program_id, &header.instance);
+        flag(header.key() != header_pda || header.bump != header_bump, invariants::HEADER_ADDRESS);
+        let (data_pda, data_bump) = Self::get_price_oracle_data_pda(program_id, &header.instance);
+        flag(data.key() != data_pda || data.bump != data_bump, invariants::DATA_ADDRESS);
+
+        flag(header.last_global_update > now, invariants::UPDATE_TIME_IN_FUTURE);
+        flag(header.last_global_update < 0, invariants::NEGATIVE_UPDATE_TIME);
+        for asset_type in AssetType::ALL {
+            let i = asset_type.index();
+            let price_data = &data.price_data[i];
+            for time in [price_data.last_update_time, price_data.last_checked_time] {
+                flag(time > now, invariants::UPDATE_TIME_IN_FUTURE);
+                flag(time < 0, invariants::NEGATIVE_UPDATE_TIME);
+            }
+            flag(price_data.last_update_time > header.last_global_update, invariants::GLOBAL_UPDATE_BEHIND);
+            if let Some(watermarks) = price_data.watermarks() {
+                let price = price_data.price;
+                flag(price != 0 && (price < watermarks.low || price > watermarks.high), invariants::PRICE_OUTSIDE_WATERMARKS);
+            }
+            flag(header.pending_prices[i].is_pending() && header.large_move_policy[i] != LargeMovePolicy::Confirm, invariants::PENDING_WITHOUT_CONFIRM);
+        }
+        flag(header.stop_clear_effective_at != 0 && !header.emergency_stop, invariants::CLEAR_WITHOUT_STOP);
+        flag(
+            header.data_generation < previous_generation || (header.data_generation == previous_generation && data.nonce < previous_nonce),
+            invariants::NONCE_REGRESSED,
+        );
+        violations
+    }
+
+    /// Chooses between all-or-nothing fixed-feed updates and skipping rejected assets
+    pub fn set_partial_updates(header: &mut PriceOracleHeader, enabled: bool) {
+        header.partial_updates = enabled;
+    }
+
+    /// Sets the lease balance that triggers `LeaseLow` and whether an empty lease blocks updates
+    pub fn set_lease_policy(header: &mut PriceOracleHeader, min_lease_balance: u64, reject_empty_lease: bool) {
+        header.min_lease_balance = min_lease_balance;
+        header.reject_empty_lease = reject_empty_lease;
+    }
+
+    /// Applies the lease policy to `feed`'s lease and returns the escrow balance when it
+    /// is below `min_lease_balance`, so the caller can warn about it
+    pub fn check_lease(
+        header: &PriceOracleHeader,
+        feed: &AggregatorFeed,
+        lease: &AccountInfo,
+        escrow: &AccountInfo,
+    ) -> Result<Option<u64>> {
+        let balance = get_lease_balance(feed, lease, escrow, &header.switchboard_program_id)?;
+        if balance == 0 && header.reject_empty_lease {
+            msg!("Lease {} of aggregator {} is empty. Update aborted.", lease.key(), feed.key());
+            return Err(error!(OracleError::LeaseExhausted));
+        }
+        Ok((balance < header.min_lease_balance).then_some(balance))
+    }
+
+    /// Gets the PDA for the price oracle header
+    pub fn get_price_oracle_header_pda(program_id: &Pubkey, instance: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[Self::HEADER_SEED, Self::instance_seed(instance)], program_id)
+    }
+
+    /// Gets the PDA for the price oracle data
+    pub fn get_price_oracle_data_pda(program_id: &Pubkey, instance: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[Self::DATA_SEED, Self::instance_seed(instance)], program_id)
+    }
+
+    /// Gets the PDA a watcher program signs `trigger_emergency_stop_cpi` with
+    pub fn get_watcher_authority_pda(watcher_program: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[Self::WATCHER_SEED], watcher_program)
+    }
+
+    /// Gets the PDA for the price history
+    pub fn get_price_history_pda(program_id: &Pubkey, instance: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[Self::HISTORY_SEED, Self::instance_seed(instance)], program_id)
+    }
+
+    /// Gets the PDA for the APY history
+    pub fn get_apy_history_pda(program_id: &Pubkey, instance: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[Self::APY_HISTORY_SEED, Self::instance_seed(instance)], program_id)
+    }
+
+    /// Gets the PDA for an updater's stats
+    pub fn get_updater_stats_pda(program_id: &Pubkey, instance: &Pubkey, updater: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[Self::UPDATER_STATS_SEED, Self::instance_seed(instance), updater.as_ref()], program_id)
+    }
+
+    /// Gets the PDA for an asset's epoch snapshots
+    pub fn get_epoch_snapshots_pda(program_id: &Pubkey, instance: &Pubkey, asset_type: AssetType) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[Self::EPOCH_SNAPSHOTS_SEED, Self::instance_seed(instance), &[u8::from(asset_type)]], program_id)
+    }
+
+    /// Gets the PDA for the asset registry
+    pub fn get_asset_registry_pda(program_id: &Pubkey, instance: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[Self::REGISTRY_SEED, Self::instance_seed(instance)], program_id)
+    }
+
+    /// Gets the PDA for the audit log
+    pub fn get_audit_log_pda(program_id: &Pubkey, instance: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[Self::AUDIT_LOG_SEED, Self::instance_seed(instance)], program_id)
+    }
+
+    /// Gets the PDA for the subscriber registration
+    pub fn get_subscriber_pda(program_id: &Pubkey, instance: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[Self::SUBSCRIBER_SEED, Self::instance_seed(instance)], program_id)
+    }
+
+    /// Gets the PDA for the price thresholds
+    pub fn get_thresholds_pda(program_id: &Pubkey, instance: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[Self::THRESHOLDS_SEED, Self::instance_seed(instance)], program_id)
+    }
+
+    /// Gets the PDA for the checkpoint with the given id
+    pub fn get_checkpoint_pda(program_id: &Pubkey, instance: &Pubkey, id: u64) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[Self::CHECKPOINT_SEED, Self::instance_seed(instance), &id.to_le_bytes()], program_id)
+    }
+
+    /// Gets the PDA for the creator's basket with the given id
+    pub fn get_basket_pda(program_id: &Pubkey, creator: &Pubkey, id: u64) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[Self::BASKET_SEED, creator.as_ref(), &id.to_le_bytes()], program_id)
+    }
+
+    /// Gets the PDA for the authority's quote buffer with the given id
+    pub fn get_quote_buffer_pda(program_id: &Pubkey, instance: &Pubkey, authority: &Pubkey, id: u64) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[Self::QUOTE_BUFFER_SEED, Self::instance_seed(instance), authority.as_ref(), &id.to_le_bytes()], program_id)
+    }
+}
+
+/// Custom error types for the Oracle
+#[error_code]
+pub enum OracleError {
+    #[msg("Unauthorized access")]
+    UnauthorizedAccess,
+    #[msg("Invalid asset type")]
+    InvalidAssetType,
+    #[msg("Data not available")]
+    DataNotAvailable,
+    #[msg("Invalid account data")]
+    InvalidAccountData,
+    #[msg("Price not available")]
+    PriceNotAvailable,
+    #[msg("APY not available")]
+    ApyNotAvailable,
+    #[msg("Price change exceeds 20% limit")]
+    PriceChangeExceedsLimit,
+    #[msg("Emergency stop activated")]
+    EmergencyStop,
+    #[msg("Invalid Switchboard account")]
+    InvalidSwitchboardAccount,
+    #[msg("Stale data")]
+    StaleData,
+    #[msg("Invalid Switchboard data")]
+    InvalidSwitchboardData,
+    #[msg("Feed is not registered for any asset")]
+    UnregisteredFeed,
+    #[msg("Feed supplied more than once")]
+    DuplicateFeed,
+    #[msg("Emergency stop changed too recently")]
+    StopChangeTooFrequent,
+    #[msg("Invalid configuration value")]
+    InvalidConfig,
+    #[msg("History buffer does not match the aggregator")]
+    InvalidHistoryBuffer,
+    #[msg("Price deviates too far from the history median")]
+    HistoryDeviationExceedsLimit,
+    #[msg("Asset already has a snapshot for this epoch")]
+    AlreadySnapshotted,
+    #[msg("Epoch snapshot list is full")]
+    EpochSnapshotsFull,
+    #[msg("Signer does not hold the role this instruction requires")]
+    WrongRole,
+    #[msg("Oracle is locked")]
+    OracleLocked,
+    #[msg("Lock confirmation does not match")]
+    LockNotConfirmed,
+    #[msg("Lease account does not fund this aggregator")]
+    InvalidLeaseAccount,
+    #[msg("Aggregator lease is empty")]
+    LeaseExhausted,
+    #[msg("Assets are quoted in different currencies")]
+    QuoteCurrencyMismatch,
+    #[msg("Conversion overflows")]
+    ConversionOverflow,
+    #[msg("Invalid Chainlink account")]
+    InvalidChainlinkAccount,
+    #[msg("Invalid Chainlink data")]
+    InvalidChainlinkData,
+    #[msg("Feed does not match the asset's configured source")]
+    WrongSourceKind,
+    #[msg("Too few fresh sources to aggregate")]
+    InsufficientSources,
+    #[msg("Every asset in the update was rejected")]
+    NoAssetUpdated,
+    #[msg("Asset is delisted")]
+    AssetDelisted,
+    #[msg("Timelock has not elapsed")]
+    TimelockNotElapsed,
+    #[msg("No configuration change is pending")]
+    NoPendingConfig,
+    #[msg("A configuration change is already pending")]
+    ConfigChangePending,
+    #[msg("Change must go through the config timelock")]
+    TimelockRequired,
+    #[msg("Asset updates are paused")]
+    AssetPaused,
+    #[msg("Resuming from this stop needs approvals")]
+    ResumeApprovalRequired,
+    #[msg("Signer is not a resume approver")]
+    NotResumeApprover,
+    #[msg("Resume already approved for this stop")]
+    AlreadyApproved,
+    #[msg("Not enough approvals to resume")]
+    ResumeThresholdNotMet,
+    #[msg("Emergency stop is not active")]
+    NotStopped,
+    #[msg("Price move is held for confirmation")]
+    PriceMovePending,
+    #[msg("Invalid stake pool account")]
+    InvalidStakePoolAccount,
+    #[msg("Invalid stake pool data")]
+    InvalidStakePoolData,
+    #[msg("Price deviates too far from the stake pool exchange rate")]
+    PriceDeviatesFromNav,
+    #[msg("Invalid Marinade state account")]
+    InvalidMarinadeState,
+    #[msg("APY change exceeds the asset's limit")]
+    ApyChangeExceedsLimit,
+    #[msg("Invalid subscriber registration")]
+    InvalidSubscriber,
+    #[msg("Callback accounts do not match the subscriber registration")]
+    InvalidSubscriberAccounts,
+    #[msg("No trusted publisher is set")]
+    SignedPriceDisabled,
+    #[msg("Publisher signature is missing or does not match")]
+    InvalidPublisherSignature,
+    #[msg("Signed price is not newer than the stored one")]
+    SignedPriceReplayed,
+    #[msg("Signed price is zero or timestamped in the future")]
+    InvalidSignedPrice,
+    #[msg("Price was updated in the current slot")]
+    PriceUpdatedThisSlot,
+    #[msg("An earlier instruction of this transaction updates prices")]
+    PriceUpdatedThisTransaction,
+    #[msg("The source has not published a new round since the stored price")]
+    NoNewRound,
+    #[msg("Privileged instructions must be passed the audit log")]
+    AuditLogRequired,
+    #[msg("Price updates are paused for maintenance")]
+    UpdatesPaused,
+    #[msg("Checkpoint is too recent to be closed")]
+    CheckpointTooRecent,
+    #[msg("Basket weights must be positive, name each asset once and add up to 10000 bps")]
+    InvalidBasketWeights,
+    #[msg("Update chunk must name at least one position of the multi-asset feed, and no more")]
+    InvalidUpdateChunk,
+    #[msg("Feed mapping must name every position of the multi-asset feed once, with distinct assets other than SOL")]
+    InvalidFeedMapping,
+    #[msg("Quote buffer must fit a quote at its offset, after its own fields")]
+    InvalidQuoteBuffer,
+    #[msg("Account is not an initialized SPL Token or Token-2022 mint")]
+    InvalidMint,
+    #[msg("Mint creates its token accounts frozen")]
+    FrozenMint,
+    #[msg("Mint is already registered for another asset")]
+    DuplicateMint,
+    #[msg("Clock sysvar could not be read")]
+    ClockUnavailable,
+    #[msg("No PDA bump was found for an account the instruction initializes")]
+    MissingBump,
+    #[msg("Token account mint is not registered for any asset")]
+    UnknownMint,
+    #[msg("Account is not an initialized SPL Token or Token-2022 account")]
+    InvalidTokenAccount,
+    #[msg("Too many token accounts to value at once")]
+    TooManyPositions,
+    #[msg("Caller is not an allowlisted watcher program")]
+    WatcherNotAllowed,
+    #[msg("Feed price differs from the price the keeper expected")]
+    ExpectedPriceMismatch,
+    #[msg("Feed shard must be numbered 1 to 3, read its own aggregator and map at least one position")]
+    InvalidFeedShard,
+    #[msg("An asset can be carried by only one shard of the multi-asset feed")]
+    FeedShardOverlap,
+    #[msg("Invalid Pyth price update account")]
+    InvalidPythAccount,
+    #[msg("Invalid Pyth price data")]
+    InvalidPythData,
+    #[msg("Pyth price update is not for the feed id bound to the asset")]
+    PythFeedIdMismatch,
+}
+
+/// Returns true when `value` differs from `reference` by strictly more than `limit_bps`
+/// basis points of `reference`. Exact integer comparison; a zero reference never exceeds.
+pub fn exceeds_deviation_bps(reference: u64, value: u64, limit_bps: u64) -> bool {
+    if reference == 0 {
+        return false;
+    }
+    let difference = reference.abs_diff(value) as u128;
+    difference * 10_000 > reference as u128 * limit_bps as u128
+}
+
+/// `value` moved no further than `limit_bps` of `reference` from it, so that
+/// `exceeds_deviation_bps(reference, result, limit_bps)` is false
+pub fn clamp_to_limit(reference: u64, value: u64, limit_bps: u32) -> u64 {
+    let step = (reference as u128 * limit_bps as u128 / 10_000).min(u64::MAX as u128) as u64;
+    if value > reference {
+        value.min(reference.saturating_add(step))
+    } else {
+        value.max(reference.saturating_sub(step))
+    }
+}
+
+/// `|value - reference|` in basis points of `reference`, truncated toward zero.
+/// `None` for a zero reference, where a relative change is undefined.
+pub fn change_magnitude_bps(reference: u64, value: u64) -> Option<u128> {
+    if reference == 0 {
+        return None;
+    }
+    Some(reference.abs_diff(value) as u128 * 10_000 / reference as u128)
+}
+
+/// Converts a fixed-point fraction with `PRICE_DECIMALS` decimals (`PRICE_SCALE` is 100%)
+/// to basis points, rounding half up: 7.995% is 800 bps, 7.99499999% is 799. This is the
+/// only conversion used for APYs; fails if the result does not fit a u32.
+pub fn fixed_to_bps(value: u64) -> std::result::Result<u32, OracleError> {
+    let fixed_per_bps = (PRICE_SCALE / 10_000) as u128;
+    let bps = (value as u128 + fixed_per_bps / 2) / fixed_per_bps;
+    u32::try_from(bps).map_err(|_| OracleError::ConversionOverflow)
+}
+
+/// `amount * price * (1 - haircut)` for an amount with `decimals` decimals and a fixed-point
+/// price, in u128 and truncated once. A haircut of `MAX_HAIRCUT_BPS` values it at zero.
+pub fn collateral_value(amount: u64, price: u64, decimals: u8, haircut_bps: u16) -> std::result::Result<u64, OracleError> {
+    let retained_bps = (MAX_HAIRCUT_BPS as u128).checked_sub(haircut_bps as u128).ok_or(OracleError::InvalidConfig)?;
+    let denominator = 10u128.checked_pow(decimals as u32)
+        .and_then(|scale| scale.checked_mul(MAX_HAIRCUT_BPS as u128))
+        .ok_or(OracleError::ConversionOverflow)?;
+    let value = (amount as u128)
+        .checked_mul(price as u128)
+        .and_then(|value| value.checked_mul(retained_bps))
+        .ok_or(OracleError::ConversionOverflow)?
+        / denominator;
+    u64::try_from(value).map_err(|_| OracleError::ConversionOverflow)
+}
+
+/// `value * numerator / denominator` in u128, truncated; fails if the result does not fit a u64
+fn mul_div(value: u64, numerator: u64, denominator: u64) -> Result<u64> {
+    let result = value as u128 * numerator as u128 / denominator as u128;
+    u64::try_from(result).map_err(|_| error!(OracleError::ConversionOverflow))
+}
+
+/// Numeric error code carried by an error, for reporting through return data
+fn error_code(error: &Error) -> u32 {
+    match error {
+        Error::AnchorError(e) => e.error_code_number,
+        Error::ProgramError(_) => u32::from(OracleError::InvalidAccountData),
+    }
+}
+
+/// Whether a run that stored nothing left at least one asset alone because its source
+/// repeated a round, and every other one without reading it at all
+fn no_round_advanced(error_codes: impl Iterator<Item = u32>) -> bool {
+    let unread = [OracleError::AssetDelisted, OracleError::AssetPaused].map(u32::from);
+    let mut repeated = false;
+    for error_code in error_codes {
+        if error_code == u32::from(OracleError::NoNewRound) {
+            repeated = true;
+        } else if !unread.contains(&error_code) {
+            return false;
+        }
+    }
+    repeated
+}
+
+/// Helper trait to iterate over AssetType
+trait AssetTypeIter {
+    fn iter() -> impl Iterator<Item = AssetType>;
+}
+
+impl AssetTypeIter for AssetType {
+    fn iter() -> impl Iterator<Item = AssetType> {
+        AssetType::ALL.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit_log::AuditAction;
+    use crate::history::HISTORY_CAPACITY;
+    use crate::marinade_utils::MSOL_PRICE_DENOMINATOR;
+    use crate::switchboard_utils::PRICE_SCALE;
+    use anchor_spl::token_2022::spl_token_2022;
+    use crate::yield_convention::YieldConvention;
+    use crate::test_utils::{advance_clock, aggregator_loader, clock_at, mock_lease, mock_mint, mock_token_2022_account, mock_token_2022_mint, mock_token_account, mock_token_account_of, MockAccount, MockAggregator, MockChainlinkFeed, MockMarinadeState, MockPullFeed, MockPythPriceUpdate, MockStakePool};
+
+    #[test]
+    fn test_asset_for_feed() {
+        let sol_feed = Pubkey::new_unique();
+        let msol_feed = Pubkey::new_unique();
+        let mut registry = AssetRegistry::default();
+        registry.assets[AssetType::SOL.index()].feed = sol_feed;
+        registry.assets[AssetType::MSOL.index()].feed = msol_feed;
+
+        assert_eq!(registry.asset_for_feed(&sol_feed), Some(AssetType::SOL));
+        assert_eq!(registry.asset_for_feed(&msol_feed), Some(AssetType::MSOL));
+        assert_eq!(registry.asset_for_feed(&Pubkey::new_unique()), None);
+        // Unset slots must never match the default key
+        assert_eq!(registry.asset_for_feed(&Pubkey::default()), None);
+    }
+
+    #[test]
+    fn test_slot_staleness_with_frozen_timestamp() {
+        let price_data = PriceData {
+            price: 150 * PRICE_SCALE,
+            last_update_time: 1_000,
+            last_update_slot: 5_000,
+            last_checked_time: 1_000,
+            feed_timestamp: 1_000,
+            last_checked_slot: 5_000,
+            ..PriceData::default()
+        };
+
+        // The slot advances by 500 while the cluster timestamp does not move
+        let clock = clock_at(1_000, 5_500);
+        assert!(!price_data.is_stale(&clock, MaxAge::Seconds(60)));
+        assert!(price_data.is_stale(&clock, MaxAge::Slots(150)));
+        assert!(!price_data.is_stale(&clock, MaxAge::Slots(500)));
+    }
+
+    #[test]
+    fn test_timestamp_staleness_with_frozen_slot() {
+        let price_data = PriceData {
+            price: 150 * PRICE_SCALE,
+            last_update_time: 1_000,
+            last_update_slot: 5_000,
+            last_checked_time: 1_000,
+            feed_timestamp: 1_000,
+            last_checked_slot: 5_000,
+            ..PriceData::default()
+        };
+
+        // The timestamp jumps ahead while the slot stays put
+        let clock = clock_at(1_120, 5_000);
+        assert!(price_data.is_stale(&clock, MaxAge::Seconds(60)));
+        assert!(!price_data.is_stale(&clock, MaxAge::Slots(150)));
+    }
+
+    #[test]
+    fn test_staleness_boundaries_as_the_clock_advances() {
+        let price_data = PriceData {
+            price: 150 * PRICE_SCALE,
+            last_update_time: 1_000,
+            last_update_slot: 5_000,
+            last_checked_time: 1_000,
+            feed_timestamp: 1_000,
+            last_checked_slot: 5_000,
+            ..PriceData::default()
+        };
+        let stored = clock_at(1_000, 5_000);
+
+        // Exactly the max age is still fresh, one more second or slot is stale
+        let at_limit = advance_clock(&stored, 60, 150);
+        assert!(!price_data.is_stale(&at_limit, MaxAge::Seconds(60)));
+        assert!(!price_data.is_stale(&at_limit, MaxAge::Slots(150)));
+        assert!(price_data.is_stale(&advance_clock(&at_limit, 1, 0), MaxAge::Seconds(60)));
+        assert!(!price_data.is_stale(&advance_clock(&at_limit, 1, 0), MaxAge::Slots(150)));
+        assert!(price_data.is_stale(&advance_clock(&at_limit, 0, 1), MaxAge::Slots(150)));
+        assert_eq!(price_data.feed_age_secs(&advance_clock(&at_limit, 1, 0)), 61);
+
+        // The header's per-asset tolerance moves the boundary with it
+        let mut header = PriceOracleHeader::default();
+        header.max_age_secs[AssetType::SOL.index()] = 30;
+        let max_age = header.feed_max_age(AssetType::SOL);
+        assert!(!price_data.is_stale(&advance_clock(&stored, 30, 0), max_age));
+        assert!(price_data.is_stale(&advance_clock(&stored, 31, 0), max_age));
+    }
+
+    #[test]
+    fn test_move_confirmation_delay_as_the_clock_advances() {
+        let mut header = oracle_header();
+        PriceOracle::set_large_move_policy(&mut header, AssetType::SOL, LargeMovePolicy::Confirm);
+        PriceOracle::set_move_confirmation(&mut header, 120, 0).unwrap();
+        let mut data = data_with_sol_price(100 * PRICE_SCALE);
+        let far = 150 * PRICE_SCALE;
+
+        // The move is held, then waits until exactly the delay has elapsed
+        let mut clock = clock_at(1_000, 5_000);
+        let outcome = PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::SOL, far, None, new_round(), clock.unix_timestamp, &clock).unwrap();
+        assert_eq!(outcome.error_code, u32::from(OracleError::PriceMovePending));
+        for _ in 0..3 {
+            clock = advance_clock(&clock, 40, 100);
+            let expected = if clock.unix_timestamp < 1_120 { MoveVerdict::Wait } else { MoveVerdict::Accept };
+            assert_eq!(PriceOracle::move_verdict(&header, AssetType::SOL, 100 * PRICE_SCALE, far, clock.unix_timestamp), expected);
+        }
+        let outcome = PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::SOL, far, None, new_round(), clock.unix_timestamp, &clock).unwrap();
+        assert!(outcome.updated);
+        assert_eq!(data.price_data[AssetType::SOL.index()].price, far);
+        assert_eq!(data.price_data[AssetType::SOL.index()].last_update_slot, 5_300);
+    }
+
+    fn stoppable_header() -> PriceOracleHeader {
+        PriceOracleHeader {
+            min_stop_change_interval: 60,
+            ..PriceOracleHeader::default()
+        }
+    }
+
+    #[test]
+    fn test_emergency_stop_rate_limit_boundary() {
+        let mut header = stoppable_header();
+
+        // The first toggle is never rate limited
+        assert!(!PriceOracle::set_emergency_stop(&mut header, true, false, 1_000).unwrap());
+        assert!(header.emergency_stop);
+        assert_eq!(header.stop_change_count, 1);
+        assert_eq!(header.last_stop_change_time, 1_000);
+
+        // One second short of the interval is rejected and leaves state untouched
+        assert!(PriceOracle::set_emergency_stop(&mut header, false, false, 1_059).is_err());
+        assert!(header.emergency_stop);
+        assert_eq!(header.stop_change_count, 1);
+
+        // Exactly at the interval is allowed and schedules the clear
+        assert!(PriceOracle::set_emergency_stop(&mut header, false, false, 1_060).unwrap());
+        assert!(header.emergency_stop);
+        assert_eq!(header.stop_clear_effective_at, 1_060 + DEFAULT_CONFIG_TIMELOCK_SECS as i64);
+
+        let effective_at = header.stop_clear_effective_at;
+        assert!(PriceOracle::set_emergency_stop(&mut header, false, false, effective_at).unwrap());
+        assert!(!header.emergency_stop);
+        assert_eq!(header.stop_change_count, 2);
+        assert_eq!(header.last_stop_change_time, 1_060 + DEFAULT_CONFIG_TIMELOCK_SECS as i64);
+    }
+
+    #[test]
+    fn test_emergency_stop_force_bypasses_rate_limit() {
+        let mut header = stoppable_header();
+        PriceOracle::set_emergency_stop(&mut header, true, false, 1_000).unwrap();
+
+        // Force skips the rate limit but not the timelock
+        assert!(PriceOracle::set_emergency_stop(&mut header, false, true, 1_001).unwrap());
+        assert!(header.emergency_stop);
+        assert_eq!(header.stop_clear_effective_at, 1_001 + DEFAULT_CONFIG_TIMELOCK_SECS as i64);
+        assert_eq!(header.stop_change_count, 1);
+    }
+
+    #[test]
+    fn test_emergency_stop_clear_waits_for_timelock() {
+        let mut header = PriceOracleHeader { config_timelock_secs: 3_600, ..stoppable_header() };
+        PriceOracle::set_emergency_stop(&mut header, true, false, 1_000).unwrap();
+        PriceOracle::set_emergency_stop(&mut header, false, false, 2_000).unwrap();
+        assert_eq!(header.stop_clear_effective_at, 5_600);
+
+        // One second early is refused, even when forced
+        for force in [false, true] {
+            let err = PriceOracle::set_emergency_stop(&mut header, false, force, 5_599).unwrap_err();
+            assert_eq!(error_code(&err), u32::from(OracleError::TimelockNotElapsed));
+        }
+        assert!(header.emergency_stop);
+
+        // Stopping again cancels the scheduled clear, which then has to start over
+        assert!(PriceOracle::set_emergency_stop(&mut header, true, false, 5_599).unwrap());
+        assert_eq!(header.stop_clear_effective_at, 0);
+        PriceOracle::set_emergency_stop(&mut header, false, false, 5_600).unwrap();
+        assert!(header.emergency_stop);
+        assert_eq!(header.stop_clear_effective_at, 9_200);
+
+        assert!(PriceOracle::set_emergency_stop(&mut header, false, false, 9_200).unwrap());
+        assert!(!header.emergency_stop);
+        assert_eq!(header.stop_clear_effective_at, 0);
+
+        // Stopping needs no delay
+        assert!(!PriceOracle::set_emergency_stop(&mut header, true, true, 9_201).unwrap());
+        assert!(header.emergency_stop);
+    }
+
+    #[test]
+    fn test_emergency_stop_same_state_is_not_counted() {
+        let mut header = stoppable_header();
+        PriceOracle::set_emergency_stop(&mut header, true, false, 1_000).unwrap();
+
+        // Re-asserting the current state neither errors nor counts as a toggle
+        assert!(PriceOracle::set_emergency_stop(&mut header, true, false, 1_001).unwrap());
+        assert_eq!(header.stop_change_count, 1);
+        assert_eq!(header.last_stop_change_time, 1_000);
+    }
+
+    #[test]
+    fn test_stop_downtime_accumulates_over_incidents() {
+        let (mut header, [a, b, _]) = two_of_three_header(false);
+        assert_eq!((header.stop_started_at, header.stopped_secs(1_400)), (1_000, 400));
+        // Stopping again while stopped neither moves the start nor counts twice
+        PriceOracle::set_emergency_stop(&mut header, true, true, 1_500).unwrap();
+        assert_eq!((header.stop_incident, header.stop_started_at), (1, 1_000));
+
+        // A manual stop clears through the timelock, whose wait counts as downtime
+        PriceOracle::set_emergency_stop(&mut header, false, false, 2_000).unwrap();
+        let cleared_at = header.stop_clear_effective_at;
+        assert_eq!(header.stopped_secs(cleared_at - 1), (cleared_at - 1_001) as u64);
+        PriceOracle::set_emergency_stop(&mut header, false, false, cleared_at).unwrap();
+        let first = (cleared_at - 1_000) as u64;
+        assert_eq!(header.total_stopped_secs, first);
+        assert_eq!((header.stop_started_at, header.stopped_secs(cleared_at + 10)), (0, first));
+
+        // The breaker's stop resumes on approvals and adds its own duration
+        let tripped_at = cleared_at + 5_000;
+        header.begin_stop_incident(true, tripped_at);
+        header.begin_stop_incident(true, tripped_at + 100);
+        let stats = PriceOracle::get_oracle_stats(&header, tripped_at + 300);
+        assert_eq!((stats.stop_incidents, stats.stopped_since, stats.total_stopped_secs), (2, tripped_at, first + 300));
+        PriceOracle::approve_resume(&mut header, &a).unwrap();
+        PriceOracle::approve_resume(&mut header, &b).unwrap();
+        assert_eq!(PriceOracle::execute_resume(&mut header, tripped_at + 720).unwrap(), 720);
+        let stats = PriceOracle::get_oracle_stats(&header, tripped_at + 10_000);
+        assert_eq!((stats.stop_incidents, stats.stopped_since, stats.total_stopped_secs), (2, 0, first + 720));
+
+        // A stop begun before its start was recorded counts for nothing
+        header.emergency_stop = true;
+        assert_eq!(header.stopped_secs(i64::MAX), first + 720);
+        assert_eq!(header.end_stop_incident(tripped_at + 20_000), 0);
+        assert_eq!(header.total_stopped_secs, first + 720);
+    }
+
+    fn config_with_limit(registry: &AssetRegistry, header: &PriceOracleHeader, limit_bps: u32) -> OracleConfig {
+        OracleConfig { price_change_limit_bps: limit_bps, ..PriceOracle::current_config(header, registry) }
+    }
+
+    #[test]
+    fn test_config_applies_from_effective_at() {
+        let mut header = PriceOracleHeader::default();
+        let mut registry = AssetRegistry::default();
+        let config = config_with_limit(&registry, &header, 500);
+        assert_eq!(config.timelock_secs, DEFAULT_CONFIG_TIMELOCK_SECS);
+
+        let effective_at = PriceOracle::propose_config(&mut header, config, 1_000).unwrap();
+        assert_eq!(effective_at, 1_000 + DEFAULT_CONFIG_TIMELOCK_SECS as i64);
+        assert_eq!(header.price_change_limit_bps(), PRICE_CHANGE_LIMIT_BPS);
+
+        // A second proposal has to wait for the first to be applied or cancelled
+        let err = PriceOracle::propose_config(&mut header, config, 1_001).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::ConfigChangePending));
+
+        let err = PriceOracle::apply_config(&mut header, &mut registry, effective_at - 1).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::TimelockNotElapsed));
+        assert_eq!(header.price_change_limit_bps(), PRICE_CHANGE_LIMIT_BPS);
+
+        assert_eq!(PriceOracle::apply_config(&mut header, &mut registry, effective_at).unwrap(), config);
+        assert_eq!(header.price_change_limit_bps(), 500);
+        assert!(!header.pending_config.is_pending());
+        let err = PriceOracle::apply_config(&mut header, &mut registry, effective_at).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::NoPendingConfig));
+    }
+
+    #[test]
+    fn test_config_timelock_change_waits_for_the_old_delay() {
+        let mut header = PriceOracleHeader { config_timelock_secs: 7_200, ..PriceOracleHeader::default() };
+        let mut registry = AssetRegistry::default();
+        let config = OracleConfig { timelock_secs: MIN_CONFIG_TIMELOCK_SECS, ..PriceOracle::current_config(&header, &registry) };
+
+        let effective_at = PriceOracle::propose_config(&mut header, config, 0).unwrap();
+        assert_eq!(effective_at, 7_200);
+        assert!(PriceOracle::apply_config(&mut header, &mut registry, 3_600).is_err());
+        PriceOracle::apply_config(&mut header, &mut registry, 7_200).unwrap();
+
+        // Later proposals use the new delay
+        let effective_at = PriceOracle::propose_config(&mut header, config, 10_000).unwrap();
+        assert_eq!(effective_at, 10_000 + MIN_CONFIG_TIMELOCK_SECS as i64);
+    }
+
+    #[test]
+    fn test_config_apply_replaces_feeds() {
+        let mut header = PriceOracleHeader::default();
+        let mut registry = AssetRegistry::default();
+        let old_feed = Pubkey::new_unique();
+        let new_feed = Pubkey::new_unique();
+        registry.assets[AssetType::SOL.index()].feed = old_feed;
+
+        let mut config = PriceOracle::current_config(&header, &registry);
+        config.feeds[AssetType::SOL.index()] = new_feed;
+        let effective_at = PriceOracle::propose_config(&mut header, config, 0).unwrap();
+        assert_eq!(registry.assets[AssetType::SOL.index()].feed, old_feed);
+        PriceOracle::apply_config(&mut header, &mut registry, effective_at).unwrap();
+        assert_eq!(registry.assets[AssetType::SOL.index()].feed, new_feed);
+    }
+
+    #[test]
+    fn test_config_validation() {
+        let header = PriceOracleHeader::default();
+        let registry = AssetRegistry::default();
+        let valid = PriceOracle::current_config(&header, &registry);
+        assert!(valid.validate().is_ok());
+        let approver = Pubkey::new_unique();
+        let two = approvers(&[approver, Pubkey::new_unique()]);
+        assert!(OracleConfig { resume_approvers: two, resume_threshold: 2, ..valid }.validate().is_ok());
+        let err = OracleConfig { resume_approvers: two, resume_threshold: 3, ..valid }.validate().unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::InvalidConfig));
+
+        let invalid = [
+            OracleConfig { price_change_limit_bps: MIN_PRICE_CHANGE_LIMIT_BPS - 1, ..valid },
+            OracleConfig { price_change_limit_bps: MAX_PRICE_CHANGE_LIMIT_BPS + 1, ..valid },
+            OracleConfig { timelock_secs: MIN_CONFIG_TIMELOCK_SECS - 1, ..valid },
+            OracleConfig { timelock_secs: MAX_CONFIG_TIMELOCK_SECS + 1, ..valid },
+            // A threshold needs that many approvers, each listed once
+            OracleConfig { resume_threshold: 1, ..valid },
+            OracleConfig { resume_approvers: approvers(&[approver, approver]), resume_threshold: 2, ..valid },
+        ];
+        for config in invalid {
+            let err = config.validate().unwrap_err();
+            assert_eq!(error_code(&err), u32::from(OracleError::InvalidConfig));
+        }
+
+        let feed = Pubkey::new_unique();
+        let mut duplicate = valid;
+        duplicate.feeds[AssetType::SOL.index()] = feed;
+        duplicate.feeds[AssetType::MSOL.index()] = feed;
+        let err = duplicate.validate().unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::DuplicateFeed));
+    }
+
+    #[test]
+    fn test_config_cancel_clears_pending() {
+        let mut header = PriceOracleHeader::default();
+        let mut registry = AssetRegistry::default();
+        let err = PriceOracle::cancel_config(&mut header).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::NoPendingConfig));
+
+        let config = config_with_limit(&registry, &header, 500);
+        let effective_at = PriceOracle::propose_config(&mut header, config, 0).unwrap();
+        assert_eq!(PriceOracle::cancel_config(&mut header).unwrap(), config);
+        let err = PriceOracle::apply_config(&mut header, &mut registry, effective_at).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::NoPendingConfig));
+        assert_eq!(header.price_change_limit_bps(), PRICE_CHANGE_LIMIT_BPS);
+    }
+
+    fn feed_positions(assets: &[AssetType]) -> Vec<FeedPosition> {
+        assets.iter().enumerate().map(|(position, asset_type)| FeedPosition { position: position as u8, asset_type: *asset_type }).collect()
+    }
+
+    #[test]
+    fn test_feed_mapping_rejects_duplicate_or_missing_positions() {
+        let permuted = [AssetType::JitoSOL, AssetType::HSOL, AssetType::MSOL, AssetType::BSOL, AssetType::VSOL, AssetType::JupSOL];
+        let mapping = FeedMapping::from_positions(&feed_positions(&permuted)).unwrap();
+        assert_eq!(mapping.assets(), permuted);
+
+        // Entries may come in any order
+        let mut shuffled = feed_positions(&permuted);
+        shuffled.reverse();
+        assert_eq!(FeedMapping::from_positions(&shuffled).unwrap(), mapping);
+
+        let mut duplicate_position = feed_positions(&permuted);
+        duplicate_position[5].position = 0;
+        let mut past_the_feed = feed_positions(&permuted);
+        past_the_feed[5].position = MULTI_ASSET_FEED_LEN as u8;
+        let invalid = [
+            feed_positions(&permuted[..5]),
+            duplicate_position,
+            past_the_feed,
+            feed_positions(&[AssetType::JitoSOL, AssetType::JitoSOL, AssetType::MSOL, AssetType::BSOL, AssetType::VSOL, AssetType::JupSOL]),
+            feed_positions(&[AssetType::SOL, AssetType::HSOL, AssetType::MSOL, AssetType::BSOL, AssetType::VSOL, AssetType::JupSOL]),
+        ];
+        for positions in invalid {
+            let err = FeedMapping::from_positions(&positions).unwrap_err();
+            assert_eq!(error_code(&err), u32::from(OracleError::InvalidFeedMapping), "{:?}", positions);
+        }
+
+        // Headers from before the mapping follow the original order
+        assert_eq!(PriceOracleHeader::default().multi_asset_feed(), AssetType::MULTI_ASSET_FEED);
+        let unset = PriceOracleHeader::try_from_slice(&vec![0; PriceOracleHeader::LEN]).unwrap();
+        assert_eq!(unset.multi_asset_feed(), AssetType::MULTI_ASSET_FEED);
+    }
+
+    #[test]
+    fn test_feed_mapping_applies_after_the_timelock() {
+        let mut header = PriceOracleHeader::default();
+        let permuted = [AssetType::VSOL, AssetType::JupSOL, AssetType::BSOL, AssetType::MSOL, AssetType::HSOL, AssetType::INF];
+        let err = PriceOracle::cancel_feed_mapping(&mut header).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::NoPendingConfig));
+
+        let (mapping, effective_at) = PriceOracle::propose_feed_mapping(&mut header, &feed_positions(&permuted), 1_000).unwrap();
+        assert_eq!(effective_at, 1_000 + DEFAULT_CONFIG_TIMELOCK_SECS as i64);
+        let err = PriceOracle::propose_feed_mapping(&mut header, &feed_positions(&permuted), 1_001).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::ConfigChangePending));
+        let err = PriceOracle::apply_feed_mapping(&mut header, effective_at - 1).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::TimelockNotElapsed));
+        assert_eq!(header.multi_asset_feed(), AssetType::MULTI_ASSET_FEED);
+
+        assert_eq!(PriceOracle::apply_feed_mapping(&mut header, effective_at).unwrap(), mapping);
+        assert_eq!(header.multi_asset_feed(), permuted);
+        let err = PriceOracle::apply_feed_mapping(&mut header, effective_at).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::NoPendingConfig));
+
+        // A cancelled mapping is never applied, and a locked oracle keeps its mapping
+        PriceOracle::propose_feed_mapping(&mut header, &feed_positions(&AssetType::MULTI_ASSET_FEED), 2_000).unwrap();
+        assert_eq!(PriceOracle::cancel_feed_mapping(&mut header).unwrap().assets(), AssetType::MULTI_ASSET_FEED);
+        let (_, effective_at) = PriceOracle::propose_feed_mapping(&mut header, &feed_positions(&AssetType::MULTI_ASSET_FEED), 3_000).unwrap();
+        header.locked = true;
+        let err = PriceOracle::apply_feed_mapping(&mut header, effective_at).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::OracleLocked));
+        assert_eq!(header.multi_asset_feed(), permuted);
+    }
+
+    #[test]
+    fn test_permuted_feed_mapping_writes_each_value_to_its_asset() {
+        let permuted = [AssetType::JitoSOL, AssetType::HSOL, AssetType::MSOL, AssetType::BSOL, AssetType::VSOL, AssetType::JupSOL];
+        let mut header = PriceOracleHeader { feed_mapping: FeedMapping::from_positions(&feed_positions(&permuted)).unwrap(), ..oracle_header() };
+        let mut data = PriceOracleData::default();
+        // Position `i` carries `i + 1` SOL and an APY of `i` percent
+        let payload = MultiAssetResult {
+            prices: [1, 2, 3, 4, 5, 6].map(|sol| sol * PRICE_SCALE),
+            apys: [0, 1, 2, 3, 4, 5].map(|percent| percent * PRICE_SCALE / 100),
+        };
+
+        let outcomes = PriceOracle::apply_multi_asset_values(&mut header, &mut data, &payload, 0..MULTI_ASSET_FEED_LEN, new_round(), 1_000, &clock_at(1_000, 5_000)).unwrap();
+        assert_eq!(outcomes.iter().map(|outcome| outcome.asset_type).collect::<Vec<_>>(), permuted.to_vec());
+        for (position, asset_type) in permuted.into_iter().enumerate() {
+            let price_data = &data.price_data[asset_type.index()];
+            assert_eq!((price_data.price, price_data.apy), (payload.prices[position], payload.apys[position]), "{:?}", asset_type);
+        }
+        assert_eq!(data.price_data[AssetType::JitoSOL.index()].price, PRICE_SCALE);
+        assert_eq!(data.price_data[AssetType::JupSOL.index()].price, 6 * PRICE_SCALE);
+    }
+
+    #[test]
+    fn test_feed_shards_carry_disjoint_assets() {
+        let mut header = PriceOracleHeader::default();
+        let feed = Pubkey::new_unique();
+        let inf_and_stsol = [FeedPosition { position: 0, asset_type: AssetType::INF }, FeedPosition { position: 3, asset_type: AssetType::StSOL }];
+        for shard in [0, MAX_FEED_SHARDS as u8 + 1] {
+            let err = PriceOracle::set_feed_shard(&mut header, shard, feed, &inf_and_stsol).unwrap_err();
+            assert_eq!(error_code(&err), u32::from(OracleError::InvalidFeedShard));
+        }
+        for reserved in [Pubkey::default(), DEVNET_AGGREGATOR_PUBKEY] {
+            let err = PriceOracle::set_feed_shard(&mut header, 1, reserved, &inf_and_stsol).unwrap_err();
+            assert_eq!(error_code(&err), u32::from(OracleError::InvalidFeedShard));
+        }
+        let sol = [FeedPosition { position: 0, asset_type: AssetType::SOL }];
+        let err = PriceOracle::set_feed_shard(&mut header, 1, feed, &sol).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::InvalidFeedMapping));
+        // An asset of the primary feed
+        let msol = [FeedPosition { position: 1, asset_type: AssetType::MSOL }];
+        let err = PriceOracle::set_feed_shard(&mut header, 1, feed, &msol).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::FeedShardOverlap));
+
+        let shard = PriceOracle::set_feed_shard(&mut header, 1, feed, &inf_and_stsol).unwrap();
+        assert_eq!(shard.positions(), vec![(0, AssetType::INF), (3, AssetType::StSOL)]);
+        assert_eq!(header.feed_shard(&feed), Some((1, &shard)));
+        assert_eq!(header.sharded_assets(), vec![AssetType::INF, AssetType::StSOL]);
+
+        // Another shard can neither take one of its assets nor read its aggregator
+        let other_feed = Pubkey::new_unique();
+        let stsol = [FeedPosition { position: 5, asset_type: AssetType::StSOL }];
+        let err = PriceOracle::set_feed_shard(&mut header, 2, other_feed, &stsol).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::FeedShardOverlap));
+        let bonksol = [FeedPosition { position: 0, asset_type: AssetType::BonkSOL }];
+        let err = PriceOracle::set_feed_shard(&mut header, 2, feed, &bonksol).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::InvalidFeedShard));
+        PriceOracle::set_feed_shard(&mut header, 2, other_feed, &bonksol).unwrap();
+        // The shard itself can be remapped over its own assets
+        PriceOracle::set_feed_shard(&mut header, 1, feed, &stsol).unwrap();
+        assert_eq!(header.sharded_assets(), vec![AssetType::StSOL, AssetType::BonkSOL]);
+
+        // The primary mapping cannot take a sharded asset, proposed or applied
+        let with_bonksol = [AssetType::VSOL, AssetType::JupSOL, AssetType::BSOL, AssetType::MSOL, AssetType::HSOL, AssetType::BonkSOL];
+        let err = PriceOracle::propose_feed_mapping(&mut header, &feed_positions(&with_bonksol), 1_000).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::FeedShardOverlap));
+        let with_inf = [AssetType::VSOL, AssetType::JupSOL, AssetType::BSOL, AssetType::MSOL, AssetType::HSOL, AssetType::INF];
+        let (_, effective_at) = PriceOracle::propose_feed_mapping(&mut header, &feed_positions(&with_inf), 1_000).unwrap();
+        // Nor can a shard take an asset of the pending mapping
+        let inf = [FeedPosition { position: 0, asset_type: AssetType::INF }];
+        let err = PriceOracle::set_feed_shard(&mut header, 3, Pubkey::new_unique(), &inf).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::FeedShardOverlap));
+        PriceOracle::cancel_feed_mapping(&mut header).unwrap();
+        PriceOracle::set_feed_shard(&mut header, 3, Pubkey::new_unique(), &inf).unwrap();
+        header.pending_feed_mapping = PendingFeedMapping { mapping: FeedMapping::from_positions(&feed_positions(&with_inf)).unwrap(), effective_at };
+        let err = PriceOracle::apply_feed_mapping(&mut header, effective_at).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::FeedShardOverlap));
+
+        // Clearing a shard frees its assets
+        assert_eq!(PriceOracle::set_feed_shard(&mut header, 3, Pubkey::new_unique(), &[]).unwrap(), FeedShard::default());
+        PriceOracle::apply_feed_mapping(&mut header, effective_at).unwrap();
+        assert_eq!(header.multi_asset_feed(), with_inf);
+    }
+
+    #[test]
+    fn test_feed_shard_applies_after_the_timelock() {
+        let mut header = PriceOracleHeader::default();
+        let feed = Pubkey::new_unique();
+        let inf = [FeedPosition { position: 0, asset_type: AssetType::INF }];
+        let err = PriceOracle::cancel_feed_shard(&mut header, 1).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::NoPendingConfig));
+
+        let (shard, effective_at) = PriceOracle::propose_feed_shard(&mut header, 1, feed, &inf, 1_000).unwrap();
+        assert_eq!(effective_at, 1_000 + DEFAULT_CONFIG_TIMELOCK_SECS as i64);
+        let err = PriceOracle::propose_feed_shard(&mut header, 1, feed, &[], 1_001).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::ConfigChangePending));
+        let err = PriceOracle::apply_feed_shard(&mut header, 1, effective_at - 1).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::TimelockNotElapsed));
+        assert_eq!(header.feed_shard(&feed), None);
+
+        // Each shard has its own proposal, checked as `set_feed_shard` checks it
+        let err = PriceOracle::propose_feed_shard(&mut header, MAX_FEED_SHARDS as u8 + 1, Pubkey::new_unique(), &inf, 1_001).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::InvalidFeedShard));
+        let msol = [FeedPosition { position: 1, asset_type: AssetType::MSOL }];
+        let err = PriceOracle::propose_feed_shard(&mut header, 2, Pubkey::new_unique(), &msol, 1_001).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::FeedShardOverlap));
+        assert!(!header.pending_feed_shards[1].is_pending());
+
+        assert_eq!(PriceOracle::apply_feed_shard(&mut header, 1, effective_at).unwrap(), shard);
+        assert_eq!(header.feed_shard(&feed), Some((1, &shard)));
+        let err = PriceOracle::apply_feed_shard(&mut header, 1, effective_at).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::NoPendingConfig));
+
+        // An asset taken by another shard since the proposal is refused when it is applied
+        let stsol = [FeedPosition { position: 0, asset_type: AssetType::StSOL }];
+        let (_, effective_at) = PriceOracle::propose_feed_shard(&mut header, 2, Pubkey::new_unique(), &stsol, 2_000).unwrap();
+        PriceOracle::set_feed_shard(&mut header, 3, Pubkey::new_unique(), &stsol).unwrap();
+        let err = PriceOracle::apply_feed_shard(&mut header, 2, effective_at).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::FeedShardOverlap));
+        assert_eq!(PriceOracle::cancel_feed_shard(&mut header, 2).unwrap().asset_types(), vec![AssetType::StSOL]);
+
+        // Clearing goes through the timelock too, and a locked oracle keeps its shards
+        let (cleared, effective_at) = PriceOracle::propose_feed_shard(&mut header, 1, feed, &[], 3_000).unwrap();
+        assert_eq!(cleared, FeedShard::default());
+        header.locked = true;
+        let err = PriceOracle::apply_feed_shard(&mut header, 1, effective_at).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::OracleLocked));
+        assert_eq!(header.feed_shard(&feed), Some((1, &shard)));
+        header.locked = false;
+        PriceOracle::apply_feed_shard(&mut header, 1, effective_at).unwrap();
+        assert_eq!(header.sharded_assets(), vec![AssetType::StSOL]);
+    }
+
+    #[test]
+    fn test_feed_shard_writes_its_assets_and_reports_its_health() {
+        let mut header = oracle_header();
+        let mut data = PriceOracleData::default();
+        let feed = Pubkey::new_unique();
+        let positions = [FeedPosition { position: 1, asset_type: AssetType::INF }, FeedPosition { position: 4, asset_type: AssetType::BonkSOL }];
+        PriceOracle::set_feed_shard(&mut header, 1, feed, &positions).unwrap();
+        let payload = MultiAssetResult {
+            prices: [1, 2, 3, 4, 5, 6].map(|sol| sol * PRICE_SCALE),
+            apys: [0, 1, 2, 3, 4, 5].map(|percent| percent * PRICE_SCALE / 100),
+        };
+
+        let shard = header.feed_shards[0].positions();
+        let outcomes = PriceOracle::apply_shard_values(&mut header, &mut data, &payload, &shard, new_round(), 1_000, &clock_at(1_000, 5_000)).unwrap();
+        assert_eq!(outcomes.iter().map(|outcome| outcome.asset_type).collect::<Vec<_>>(), vec![AssetType::INF, AssetType::BonkSOL]);
+        assert_eq!(data.price_data[AssetType::INF.index()].price, 2 * PRICE_SCALE);
+        assert_eq!(data.price_data[AssetType::BonkSOL.index()].apy, 4 * PRICE_SCALE / 100);
+        // Positions the shard does not map are not read
+        assert_eq!(data.price_data[AssetType::VSOL.index()].price, 0);
+
+        // The primary feed never published; the shard is fresh until its assets go stale
+        let health = PriceOracle::get_feed_shard_health(&header, &data, &clock_at(1_100, 5_100));
+        assert_eq!(health.len(), 2);
+        assert_eq!((health[0].shard, health[0].feed, health[0].oldest_feed_timestamp, health[0].stale), (0, DEVNET_AGGREGATOR_PUBKEY, 0, true));
+        assert_eq!(health[1], FeedShardHealth { shard: 1, feed, assets: vec![AssetType::INF, AssetType::BonkSOL], oldest_feed_timestamp: 1_000, stale: false });
+        assert!(PriceOracle::get_feed_shard_health(&header, &data, &clock_at(1_000 + MAX_SWITCHBOARD_DATA_AGE + 1, 6_000))[1].stale);
+        // Its assets hold back the time a chunked update is stamped with
+        for asset_type in header.multi_asset_feed() {
+            data.price_data[asset_type.index()].last_checked_time = 2_000;
+        }
+        assert_eq!(PriceOracle::feed_confirmed_at(&header, &data, &clock_at(2_000, 7_000)), 1_000);
+
+        // An asset the shard no longer writes leaves its health alone
+        data.price_data[AssetType::INF.index()].feed_timestamp = 0;
+        data.price_data[AssetType::INF.index()].delisted = true;
+        assert_eq!(PriceOracle::get_feed_shard_health(&header, &data, &clock_at(1_100, 5_100))[1].oldest_feed_timestamp, 1_000);
+    }
+
+    /// `keys` padded with empty slots to `MAX_RESUME_APPROVERS`
+    fn approvers(keys: &[Pubkey]) -> [Pubkey; MAX_RESUME_APPROVERS] {
+        let mut approvers = [Pubkey::default(); MAX_RESUME_APPROVERS];
+        approvers[..keys.len()].copy_from_slice(keys);
+        approvers
+    }
+
+    /// A stopped header whose resume needs 2 of the 3 returned keys
+    fn two_of_three_header(manual_needs_approval: bool) -> (PriceOracleHeader, [Pubkey; 3]) {
+        let keys = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        let mut header = PriceOracleHeader {
+            resume_approvers: approvers(&keys),
+            resume_threshold: 2,
+            manual_resume_needs_approval: manual_needs_approval,
+            ..stoppable_header()
+        };
+        PriceOracle::set_emergency_stop(&mut header, true, false, 1_000).unwrap();
+        (header, keys)
+    }
+
+    #[test]
+    fn test_resume_needs_threshold_approvals() {
+        let (mut header, [a, b, c]) = two_of_three_header(true);
+        assert_eq!(header.stop_incident, 1);
+
+        // The admin alone can no longer clear the stop
+        let err = PriceOracle::set_emergency_stop(&mut header, false, true, 2_000).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::ResumeApprovalRequired));
+
+        let err = PriceOracle::approve_resume(&mut header, &Pubkey::new_unique()).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::NotResumeApprover));
+        let err = PriceOracle::approve_resume(&mut header, &Pubkey::default()).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::NotResumeApprover));
+
+        assert_eq!(PriceOracle::approve_resume(&mut header, &a).unwrap(), 1);
+        // Voting twice does not count twice
+        let err = PriceOracle::approve_resume(&mut header, &a).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::AlreadyApproved));
+        let err = PriceOracle::execute_resume(&mut header, 2_000).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::ResumeThresholdNotMet));
+        assert!(header.emergency_stop);
+
+        // Exactly the threshold is enough, and a vote past it is still accepted
+        assert_eq!(PriceOracle::approve_resume(&mut header, &b).unwrap(), 2);
+        assert_eq!(PriceOracle::approve_resume(&mut header, &c).unwrap(), 3);
+        PriceOracle::execute_resume(&mut header, 2_000).unwrap();
+        assert!(!header.emergency_stop);
+        assert_eq!((header.stop_change_count, header.resumed_at), (2, 2_000));
+
+        let err = PriceOracle::execute_resume(&mut header, 2_001).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::NotStopped));
+        let err = PriceOracle::approve_resume(&mut header, &a).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::NotStopped));
+    }
+
+    #[test]
+    fn test_resume_approvals_do_not_carry_over_incidents() {
+        let (mut header, [a, b, _]) = two_of_three_header(true);
+        PriceOracle::approve_resume(&mut header, &a).unwrap();
+        PriceOracle::approve_resume(&mut header, &b).unwrap();
+        PriceOracle::execute_resume(&mut header, 2_000).unwrap();
+
+        // Stopping again while stopped keeps the incident; a new stop starts a fresh one
+        PriceOracle::set_emergency_stop(&mut header, true, false, 3_000).unwrap();
+        assert_eq!(header.stop_incident, 2);
+        PriceOracle::set_emergency_stop(&mut header, true, false, 3_001).unwrap();
+        assert_eq!(header.stop_incident, 2);
+        assert_eq!(header.resume_approval_count(), 0);
+
+        assert_eq!(PriceOracle::approve_resume(&mut header, &a).unwrap(), 1);
+        let err = PriceOracle::execute_resume(&mut header, 3_100).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::ResumeThresholdNotMet));
+
+        // A change of approvers drops the votes cast so far
+        let mut registry = AssetRegistry::default();
+        let config = OracleConfig { resume_approvers: approvers(&[b, a]), ..PriceOracle::current_config(&header, &registry) };
+        let effective_at = PriceOracle::propose_config(&mut header, config, 3_100).unwrap();
+        PriceOracle::apply_config(&mut header, &mut registry, effective_at).unwrap();
+        assert_eq!(header.resume_approval_count(), 0);
+    }
+
+    #[test]
+    fn test_manual_stop_resume_follows_configuration() {
+        // Configured so, the admin clears manual stops alone through the timelock
+        let (mut header, _) = two_of_three_header(false);
+        assert!(!header.resume_needs_approval());
+        PriceOracle::set_emergency_stop(&mut header, false, false, 2_000).unwrap();
+        let effective_at = header.stop_clear_effective_at;
+        PriceOracle::set_emergency_stop(&mut header, false, false, effective_at).unwrap();
+        assert!(!header.emergency_stop);
+
+        // Breaker trips always need the approvals
+        let err = PriceOracle::enforce_price_change_limit(&mut header, AssetType::SOL, 100 * PRICE_SCALE, 200 * PRICE_SCALE, 2_000).unwrap_err();
+        assert!(matches!(err, OracleError::PriceChangeExceedsLimit));
+        assert!(header.emergency_stop);
+        assert!(header.stop_automatic);
+        assert_eq!(header.stop_incident, 2);
+        let err = PriceOracle::set_emergency_stop(&mut header, false, true, effective_at + 100).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::ResumeApprovalRequired));
+
+        // Without a threshold every stop is the admin's to clear
+        header.resume_threshold = 0;
+        assert!(!header.resume_needs_approval());
+        let err = PriceOracle::execute_resume(&mut header, effective_at + 100).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::ResumeThresholdNotMet));
+    }
+
+    #[test]
+    fn test_require_role_distinguishes_wrong_role_from_unknown_signer() {
+        let admin = Pubkey::new_unique();
+        let operator = Pubkey::new_unique();
+        let stranger = Pubkey::new_unique();
+        let mut header = PriceOracleHeader { authority: admin, ..PriceOracleHeader::default() };
+
+        let outcome = |header: &PriceOracleHeader, signer: &Pubkey, role: Role| {
+            PriceOracle::require_role(header, signer, role).map_err(|err| error_code(&err))
+        };
+        let wrong_role = Err(u32::from(OracleError::WrongRole));
+        let unknown = Err(u32::from(OracleError::UnauthorizedAccess));
+
+        // Without an operator only the admin is known
+        for role in [Role::Admin, Role::OperatorOrAdmin, Role::Updater, Role::Guardian] {
+            assert_eq!(outcome(&header, &admin, role), Ok(()));
+            assert_eq!(outcome(&header, &operator, role), unknown);
+            assert_eq!(outcome(&header, &stranger, role), unknown);
+            // The unset operator key must never be accepted
+            assert_eq!(outcome(&header, &Pubkey::default(), role), unknown);
+        }
+
+        PriceOracle::set_operator(&mut header, operator);
+        assert_eq!(outcome(&header, &operator, Role::Admin), wrong_role);
+        assert_eq!(outcome(&header, &operator, Role::OperatorOrAdmin), Ok(()));
+        assert_eq!(outcome(&header, &operator, Role::Updater), Ok(()));
+        assert_eq!(outcome(&header, &stranger, Role::OperatorOrAdmin), unknown);
+
+        assert!(PriceOracle::require_emergency_stop_role(&header, &operator, true, false).is_ok());
+        for (stop, force) in [(true, true), (false, false), (false, true)] {
+            let err = PriceOracle::require_emergency_stop_role(&header, &operator, stop, force).unwrap_err();
+            assert_eq!(error_code(&err), u32::from(OracleError::WrongRole));
+        }
+
+        // Clearing the operator revokes it
+        PriceOracle::set_operator(&mut header, Pubkey::default());
+        assert_eq!(outcome(&header, &operator, Role::OperatorOrAdmin), unknown);
+    }
+
+    #[test]
+    fn test_asset_authority_is_scoped_to_its_asset() {
+        let admin = Pubkey::new_unique();
+        let partner = Pubkey::new_unique();
+        let mut header = PriceOracleHeader { authority: admin, ..PriceOracleHeader::default() };
+        let outcome = |header: &PriceOracleHeader, signer: &Pubkey, asset_type: AssetType, role: Role| {
+            PriceOracle::require_unlocked_asset_role(header, signer, asset_type, role).map_err(|err| error_code(&err))
+        };
+
+        assert_eq!(outcome(&header, &partner, AssetType::JitoSOL, Role::Admin), Err(u32::from(OracleError::UnauthorizedAccess)));
+        assert_eq!(PriceOracle::set_asset_authority(&mut header, AssetType::JitoSOL, Some(partner)), None);
+        assert_eq!(header.asset_authority(AssetType::JitoSOL), Some(partner));
+
+        // In scope: its own asset, whatever role the action needs of the global keys
+        for role in [Role::Admin, Role::OperatorOrAdmin, Role::Guardian] {
+            assert_eq!(outcome(&header, &partner, AssetType::JitoSOL, role), Ok(()));
+            assert_eq!(outcome(&header, &admin, AssetType::JitoSOL, role), Ok(()));
+        }
+        // Out of scope: another asset, or anything global
+        assert_eq!(outcome(&header, &partner, AssetType::SOL, Role::Admin), Err(u32::from(OracleError::WrongRole)));
+        for role in [Role::Admin, Role::OperatorOrAdmin, Role::Guardian, Role::Updater] {
+            let err = PriceOracle::require_role(&header, &partner, role).unwrap_err();
+            assert_eq!(error_code(&err), u32::from(OracleError::WrongRole));
+        }
+
+        // Locking the oracle binds the asset authority too
+        header.locked = true;
+        assert_eq!(outcome(&header, &partner, AssetType::JitoSOL, Role::Admin), Err(u32::from(OracleError::OracleLocked)));
+        assert!(PriceOracle::require_asset_role(&header, &partner, AssetType::JitoSOL, Role::Guardian).is_ok());
+        header.locked = false;
+
+        // The unset key is never an asset authority, and clearing revokes it
+        assert!(!header.is_asset_authority(&Pubkey::default()));
+        assert_eq!(outcome(&header, &Pubkey::default(), AssetType::SOL, Role::Admin), Err(u32::from(OracleError::UnauthorizedAccess)));
+        assert_eq!(PriceOracle::set_asset_authority(&mut header, AssetType::JitoSOL, Some(Pubkey::default())), Some(partner));
+        assert_eq!(header.asset_authority(AssetType::JitoSOL), None);
+        assert_eq!(outcome(&header, &partner, AssetType::JitoSOL, Role::Admin), Err(u32::from(OracleError::UnauthorizedAccess)));
+    }
+
+    #[test]
+    fn test_emergency_council_can_only_halt() {
+        let admin = Pubkey::new_unique();
+        let council = Pubkey::new_unique();
+        let mut header = PriceOracleHeader { authority: admin, ..PriceOracleHeader::default() };
+        assert!(!PriceOracle::is_council(&header, &Pubkey::default()));
+
+        assert_eq!(PriceOracle::set_emergency_council(&mut header, council), Pubkey::default());
+        assert!(PriceOracle::is_council(&header, &council));
+        assert!(PriceOracle::require_role(&header, &council, Role::Guardian).is_ok());
+        for role in [Role::Admin, Role::OperatorOrAdmin, Role::Updater] {
+            let err = PriceOracle::require_role(&header, &council, role).unwrap_err();
+            assert_eq!(error_code(&err), u32::from(OracleError::WrongRole));
+        }
+
+        // The council may stop, but neither force the stop nor clear it
+        assert!(PriceOracle::require_emergency_stop_role(&header, &council, true, false).is_ok());
+        for (stop, force) in [(true, true), (false, false), (false, true)] {
+            let err = PriceOracle::require_emergency_stop_role(&header, &council, stop, force).unwrap_err();
+            assert_eq!(error_code(&err), u32::from(OracleError::WrongRole));
+        }
+
+        // Revoking the council makes it an unknown key again
+        PriceOracle::set_emergency_council(&mut header, Pubkey::default());
+        let err = PriceOracle::require_role(&header, &council, Role::Guardian).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::UnauthorizedAccess));
+    }
+
+    #[test]
+    fn test_watcher_program_can_only_stop() {
+        let watcher = Pubkey::new_unique();
+        let watcher_authority = PriceOracle::get_watcher_authority_pda(&watcher).0;
+        let mut header = PriceOracleHeader { resume_threshold: 1, ..stoppable_header() };
+
+        // Unlisted programs, and listed ones signing with another key, are refused
+        let err = PriceOracle::trigger_watcher_stop(&mut header, &watcher, &watcher_authority, 1_000).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::WatcherNotAllowed));
+        assert!(PriceOracle::set_watcher(&mut header, watcher, true).unwrap());
+        assert!(!PriceOracle::set_watcher(&mut header, watcher, true).unwrap());
+        let err = PriceOracle::trigger_watcher_stop(&mut header, &watcher, &watcher, 1_000).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::WatcherNotAllowed));
+        assert!(!header.emergency_stop);
+
+        // The stop is automatic, so clearing it needs the approvals, and records the watcher
+        assert!(!PriceOracle::trigger_watcher_stop(&mut header, &watcher, &watcher_authority, 1_000).unwrap());
+        assert!(header.emergency_stop && header.stop_automatic);
+        assert_eq!((header.stop_watcher, header.stop_incident, header.last_stop_change_time), (watcher, 1, 1_000));
+        assert!(header.resume_needs_approval());
+
+        // Triggering again cancels a scheduled clear but starts no new incident
+        header.stop_clear_effective_at = 5_000;
+        assert!(PriceOracle::trigger_watcher_stop(&mut header, &watcher, &watcher_authority, 1_010).unwrap());
+        assert_eq!((header.stop_clear_effective_at, header.stop_incident, header.stop_change_count), (0, 1, 1));
+
+        // A later stop by a signer is not attributed to the watcher
+        header.emergency_stop = false;
+        PriceOracle::set_emergency_stop(&mut header, true, true, 2_000).unwrap();
+        assert_eq!((header.stop_watcher, header.stop_incident), (Pubkey::default(), 2));
+
+        // Removing the program revokes it; the list holds `MAX_WATCHERS` programs
+        assert!(PriceOracle::set_watcher(&mut header, watcher, false).unwrap());
+        assert!(!PriceOracle::set_watcher(&mut header, watcher, false).unwrap());
+        let err = PriceOracle::trigger_watcher_stop(&mut header, &watcher, &watcher_authority, 3_000).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::WatcherNotAllowed));
+        for _ in 0..MAX_WATCHERS {
+            PriceOracle::set_watcher(&mut header, Pubkey::new_unique(), true).unwrap();
+        }
+        let err = PriceOracle::set_watcher(&mut header, watcher, true).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::InvalidConfig));
+        let err = PriceOracle::set_watcher(&mut header, Pubkey::default(), true).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::InvalidConfig));
+        assert!(!header.is_watcher(&Pubkey::default()));
+    }
+
+    #[test]
+    fn test_thread_authority_can_only_update() {
+        let admin = Pubkey::new_unique();
+        let thread = Pubkey::new_unique();
+        let mut header = PriceOracleHeader { authority: admin, ..PriceOracleHeader::default() };
+
+        assert_eq!(PriceOracle::set_thread_authority(&mut header, thread), Pubkey::default());
+        assert!(PriceOracle::require_role(&header, &thread, Role::Updater).is_ok());
+        for role in [Role::Admin, Role::OperatorOrAdmin, Role::Guardian] {
+            let err = PriceOracle::require_role(&header, &thread, role).unwrap_err();
+            assert_eq!(error_code(&err), u32::from(OracleError::WrongRole));
+        }
+
+        // Clearing it revokes the thread, and the unset key is never an updater
+        assert_eq!(PriceOracle::set_thread_authority(&mut header, Pubkey::default()), thread);
+        for signer in [thread, Pubkey::default()] {
+            let err = PriceOracle::require_role(&header, &signer, Role::Updater).unwrap_err();
+            assert_eq!(error_code(&err), u32::from(OracleError::UnauthorizedAccess));
+        }
+    }
+
+    #[test]
+    fn test_set_switchboard_program_id_revalidates_feeds() {
+        let old_program = Pubkey::new_unique();
+        let new_program = Pubkey::new_unique();
+        let mut header = PriceOracleHeader { switchboard_program_id: old_program, ..PriceOracleHeader::default() };
+
+        let (mut lamports, mut data, loader) = (1, Vec::new(), Pubkey::default());
+        let not_executable = AccountInfo::new(&new_program, false, false, &mut lamports, &mut data, &loader, false, 0);
+        let err = PriceOracle::set_switchboard_program_id(&mut header, &not_executable).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::InvalidConfig));
+        assert_eq!(header.switchboard_program_id, old_program);
+
+        let default_key = Pubkey::default();
+        let (mut lamports, mut data) = (1, Vec::new());
+        let default_program = AccountInfo::new(&default_key, false, false, &mut lamports, &mut data, &loader, true, 0);
+        assert!(PriceOracle::set_switchboard_program_id(&mut header, &default_program).is_err());
+
+        let (mut lamports, mut data) = (1, Vec::new());
+        let program = AccountInfo::new(&new_program, false, false, &mut lamports, &mut data, &loader, true, 0);
+        assert_eq!(PriceOracle::set_switchboard_program_id(&mut header, &program).unwrap(), old_program);
+        assert_eq!(header.switchboard_program_id, new_program);
+
+        // A feed still owned by the old program is now refused, one owned by the new one loads
+        let mut old_feed = MockAggregator::new().result(15_610, 2).into_account(Pubkey::new_unique());
+        old_feed.owner = old_program;
+        let err = PriceOracle::load_feed(&header, &old_feed.account_info()).err().unwrap();
+        assert_eq!(error_code(&err), u32::from(OracleError::InvalidSwitchboardAccount));
+
+        let mut new_feed = MockAggregator::new().result(15_610, 2).into_account(Pubkey::new_unique());
+        new_feed.owner = new_program;
+        let feed_info = new_feed.account_info();
+        let feed = PriceOracle::load_feed(&header, &feed_info).unwrap();
+        let round_result = feed.load().unwrap().latest_confirmed_round.result;
+        assert_eq!({ round_result.mantissa }, 15_610);
+    }
+
+    #[test]
+    fn test_lock_is_permanent() {
+        let admin = Pubkey::new_unique();
+        let mut header = PriceOracleHeader { authority: admin, ..stoppable_header() };
+
+        // The wrong phrase leaves the oracle unlocked
+        let err = PriceOracle::lock(&mut header, "lock oracle").unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::LockNotConfirmed));
+        assert!(!header.locked);
+        assert!(PriceOracle::require_unlocked_role(&header, &admin, Role::Admin).is_ok());
+
+        PriceOracle::lock(&mut header, LOCK_CONFIRMATION).unwrap();
+        assert!(header.locked);
+        for role in [Role::Admin, Role::OperatorOrAdmin, Role::Updater] {
+            let err = PriceOracle::require_unlocked_role(&header, &admin, role).unwrap_err();
+            assert_eq!(error_code(&err), u32::from(OracleError::OracleLocked));
+        }
+        // Role errors still take precedence for unknown signers
+        let err = PriceOracle::require_unlocked_role(&header, &Pubkey::new_unique(), Role::Admin).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::UnauthorizedAccess));
+        // Plain role checks, used by updates, are unaffected
+        assert!(PriceOracle::require_role(&header, &admin, Role::Updater).is_ok());
+
+        // Stopping still works, clearing does not, even when forced
+        PriceOracle::set_emergency_stop(&mut header, true, false, 1_000).unwrap();
+        for force in [false, true] {
+            let err = PriceOracle::set_emergency_stop(&mut header, false, force, 10_000).unwrap_err();
+            assert_eq!(error_code(&err), u32::from(OracleError::OracleLocked));
+        }
+        assert!(header.emergency_stop);
+        assert!(header.locked);
+    }
+
+    #[test]
+    fn test_validate_price_change_boundary() {
+        let old = 100 * PRICE_SCALE;
+        assert!(PriceOracle::validate_price_change(old, 120 * PRICE_SCALE, PRICE_CHANGE_LIMIT_BPS).is_ok());
+        assert!(PriceOracle::validate_price_change(old, 80 * PRICE_SCALE, PRICE_CHANGE_LIMIT_BPS).is_ok());
+        assert!(matches!(
+            PriceOracle::validate_price_change(old, 120 * PRICE_SCALE + 1, PRICE_CHANGE_LIMIT_BPS),
+            Err(OracleError::PriceChangeExceedsLimit)
+        ));
+        assert!(matches!(
+            PriceOracle::validate_price_change(old, 80 * PRICE_SCALE - 1, PRICE_CHANGE_LIMIT_BPS),
+            Err(OracleError::PriceChangeExceedsLimit)
+        ));
+    }
+
+    #[test]
+    fn test_validate_price_change_follows_configured_limit() {
+        let old = 100 * PRICE_SCALE;
+        assert!(PriceOracle::validate_price_change(old, 105 * PRICE_SCALE, 500).is_ok());
+        assert!(PriceOracle::validate_price_change(old, 105 * PRICE_SCALE + 1, 500).is_err());
+        assert!(PriceOracle::validate_price_change(old, 300 * PRICE_SCALE, MAX_PRICE_CHANGE_LIMIT_BPS).is_err());
+    }
+
+    #[test]
+    fn test_first_observation_bypasses_change_limit() {
+        assert!(PriceOracle::validate_price_change(0, 150 * PRICE_SCALE, PRICE_CHANGE_LIMIT_BPS).is_ok());
+    }
+
+    #[test]
+    fn test_validate_history_deviation() {
+        // No history in the window leaves the update unconstrained
+        assert!(PriceOracle::validate_history_deviation(None, 1_000 * PRICE_SCALE).is_ok());
+        assert!(PriceOracle::validate_history_deviation(Some(100 * PRICE_SCALE), 110 * PRICE_SCALE).is_ok());
+        assert!(PriceOracle::validate_history_deviation(Some(100 * PRICE_SCALE), 90 * PRICE_SCALE).is_ok());
+        assert!(matches!(
+            PriceOracle::validate_history_deviation(Some(100 * PRICE_SCALE), 110 * PRICE_SCALE + 1),
+            Err(OracleError::HistoryDeviationExceedsLimit)
+        ));
+    }
+
+    #[test]
+    fn test_fixed_to_bps_rounds_half_up() {
+        // 7.995% sits exactly on the boundary and rounds up
+        assert_eq!(fixed_to_bps(79_950_000).unwrap(), 800);
+        assert_eq!(fixed_to_bps(79_949_999).unwrap(), 799);
+        assert_eq!(fixed_to_bps(79_900_000).unwrap(), 799);
+        assert_eq!(fixed_to_bps(0).unwrap(), 0);
+        assert_eq!(fixed_to_bps(49_999).unwrap(), 0);
+        assert_eq!(fixed_to_bps(50_000).unwrap(), 1);
+        // APYs above 100%
+        assert_eq!(fixed_to_bps(PRICE_SCALE).unwrap(), 10_000);
+        assert_eq!(fixed_to_bps(3 * PRICE_SCALE + 250_000_000).unwrap(), 32_500);
+        // u32::MAX bps and beyond
+        assert_eq!(fixed_to_bps(u32::MAX as u64 * 100_000).unwrap(), u32::MAX);
+        assert!(matches!(fixed_to_bps(u32::MAX as u64 * 100_000 + 50_000), Err(OracleError::ConversionOverflow)));
+        assert!(matches!(fixed_to_bps(u64::MAX), Err(OracleError::ConversionOverflow)));
+    }
+
+    #[test]
+    fn test_get_current_apy_bps() {
+        let mut data = PriceOracleData::default();
+        data.price_data[AssetType::MSOL.index()].apy = 72_049_999;
+        assert_eq!(PriceOracle::get_current_apy_bps(&data, AssetType::MSOL).unwrap(), 720);
+        data.price_data[AssetType::MSOL.index()].apy = u64::MAX;
+        let err = PriceOracle::get_current_apy_bps(&data, AssetType::MSOL).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::ConversionOverflow));
+    }
+
+    #[test]
+    fn test_header_feed_max_age() {
+        let mut header = PriceOracleHeader::default();
+        assert_eq!(header.feed_max_age(AssetType::SOL), MaxAge::Seconds(MAX_SWITCHBOARD_DATA_AGE));
+        PriceOracle::set_asset_max_age(&mut header, AssetType::MSOL, 1_800).unwrap();
+        assert_eq!(header.feed_max_age(AssetType::MSOL), MaxAge::Seconds(1_800));
+        assert_eq!(header.feed_max_age(AssetType::SOL), MaxAge::Seconds(MAX_SWITCHBOARD_DATA_AGE));
+        header.max_feed_age_slots = 750;
+        assert_eq!(header.feed_max_age(AssetType::MSOL), MaxAge::Slots(750));
+
+        for max_age_secs in [0, MIN_ASSET_MAX_AGE_SECS - 1, MAX_ASSET_MAX_AGE_SECS + 1] {
+            let err = PriceOracle::set_asset_max_age(&mut header, AssetType::MSOL, max_age_secs).unwrap_err();
+            assert_eq!(error_code(&err), u32::from(OracleError::InvalidConfig));
+        }
+        assert_eq!(header.max_age_secs(AssetType::MSOL), 1_800);
+    }
+
+    fn test_queue() -> Pubkey {
+        Pubkey::new_from_array([7; 32])
+    }
+
+    fn oracle_header() -> PriceOracleHeader {
+        PriceOracleHeader {
+            switchboard_program_id: switchboard_v2::SWITCHBOARD_PROGRAM_ID,
+            min_stop_change_interval: DEFAULT_MIN_STOP_CHANGE_INTERVAL,
+            ..PriceOracleHeader::default()
+        }
+    }
+
+    fn data_with_sol_price(price: u64) -> PriceOracleData {
+        let mut data = PriceOracleData::default();
+        data.price_data[AssetType::SOL.index()].price = price;
+        data
+    }
+
+    /// A SOL feed on the test queue whose latest round opened at `clock_at(1_000, 5_000)`
+    fn sol_feed(mantissa: i128, scale: u32) -> MockAggregator {
+        MockAggregator::new()
+            .result(mantissa, scale)
+            .round_open_timestamp(1_000)
+            .round_open_slot(5_000)
+            .queue(test_queue())
+    }
+
+    /// Runs `update_assets` with `feed` registered as the SOL feed and returns the
+    /// outcome together with the resulting header and data
+    fn run_sol_update(
+        header: PriceOracleHeader,
+        data: PriceOracleData,
+        feed: MockAggregator,
+        clock: &Clock,
+    ) -> (Result<Vec<AssetType>>, PriceOracleHeader, PriceOracleData) {
+        run_asset_update(AssetType::SOL, header, data, feed, clock)
+    }
+
+    /// Provenance of a round never stored before, so repeated writes are not skipped as `NoNewRound`
+    fn new_round() -> PriceProvenance {
+        PriceProvenance::from_feed(SourceKind::Switchboard, Pubkey::new_unique(), 0)
+    }
+
+    /// `run_sol_update` with `feed` registered for `asset_type`
+    fn run_asset_update(
+        asset_type: AssetType,
+        header: PriceOracleHeader,
+        data: PriceOracleData,
+        feed: MockAggregator,
+        clock: &Clock,
+    ) -> (Result<Vec<AssetType>>, PriceOracleHeader, PriceOracleData) {
+        run_update_with_feed_account(asset_type, header, data, feed.into_account(Pubkey::new_unique()), clock)
+    }
+
+    /// `run_asset_update` with an arbitrary feed account, e.g. a Chainlink feed
+    fn run_update_with_feed_account(
+        asset_type: AssetType,
+        header: PriceOracleHeader,
+        data: PriceOracleData,
+        mut feed_account: MockAccount,
+        clock: &Clock,
+    ) -> (Result<Vec<AssetType>>, PriceOracleHeader, PriceOracleData) {
+        let mut registry = AssetRegistry { queue: test_queue(), ..AssetRegistry::default() };
+        registry.assets[asset_type.index()].feed = feed_account.key;
+        let (result, header, data) = run_with_accounts(&header, &data, &registry, vec![feed_account], |header, data, registry, feeds| {
+            PriceOracle::update_assets(header, data, registry, feeds, clock)
+        });
+        (result.map(|assets| assets.updated), header, data)
+    }
+
+    /// Runs `update` against program accounts holding `header`, `data` and `registry`, with
+    /// `accounts` as the remaining accounts, returning its result and the header and data it
+    /// left
+    fn run_with_accounts<R>(
+        header: &PriceOracleHeader,
+        data: &PriceOracleData,
+        registry: &AssetRegistry,
+        mut accounts: Vec<MockAccount>,
+        update: impl FnOnce(&mut Account<PriceOracleHeader>, &mut Account<PriceOracleData>, &Account<AssetRegistry>, &[AccountInfo]) -> R,
+    ) -> (R, PriceOracleHeader, PriceOracleData) {
+        let mut header_account = MockAccount::program_account(Pubkey::new_unique(), header);
+        let mut data_account = MockAccount::program_account(Pubkey::new_unique(), data);
+        let mut registry_account = MockAccount::program_account(Pubkey::new_unique(), registry);
+
+        let header_info = header_account.account_info();
+        let data_info = data_account.account_info();
+        let registry_info = registry_account.account_info();
+        let infos: Vec<AccountInfo> = accounts.iter_mut().map(|account| account.account_info()).collect();
+
+        let mut header = Account::<PriceOracleHeader>::try_from(&header_info).unwrap();
+        let mut data = Account::<PriceOracleData>::try_from(&data_info).unwrap();
+        let registry = Account::<AssetRegistry>::try_from(&registry_info).unwrap();
+
+        let result = update(&mut header, &mut data, &registry, &infos);
+        (result, (*header).clone(), (*data).clone())
+    }
+
+    #[test]
+    fn test_mock_aggregator_round_trips_through_loader() {
+        let mut account = sol_feed(15_610, 2).std_deviation(5, 1).num_success(3).into_account(Pubkey::new_unique());
+        let info = account.account_info();
+        let loader = aggregator_loader(&info).unwrap();
+        let feed = loader.load().unwrap();
+
+        let round = feed.latest_confirmed_round;
+        let result = round.result;
+        let std_deviation = round.std_deviation;
+        assert_eq!((result.mantissa, result.scale), (15_610, 2));
+        assert_eq!((std_deviation.mantissa, std_deviation.scale), (5, 1));
+        let num_success = round.num_success;
+        let queue = feed.queue_pubkey;
+        assert_eq!(num_success, 3);
+        assert_eq!(queue, test_queue());
+    }
+
+    #[test]
+    fn test_update_assets_writes_first_observation() {
+        let clock = clock_at(1_010, 5_020);
+        let (result, header, data) = run_sol_update(oracle_header(), data_with_sol_price(0), sol_feed(15_610, 2), &clock);
+
+        assert_eq!(result.unwrap(), vec![AssetType::SOL]);
+        let sol = data.price_data[AssetType::SOL.index()];
+        assert_eq!(sol.price, 156_100_000_000);
+        assert_eq!(sol.last_price, 0);
+        assert_eq!(sol.last_update_time, 1_010);
+        assert_eq!(sol.last_update_slot, 5_020);
+        assert_eq!(header.last_global_update, 1_010);
+    }
+
+    #[test]
+    fn test_record_changed_assets() {
+        let clock = clock_at(1_010, 5_020);
+        let mut header = oracle_header();
+        let mut data = data_with_sol_price(100 * PRICE_SCALE);
+        data.price_data[AssetType::MSOL.index()].price = 120 * PRICE_SCALE;
+        data.price_data[AssetType::MSOL.index()].apy = 7 * PRICE_SCALE / 100;
+        data.price_data[AssetType::JitoSOL.index()].price = 110 * PRICE_SCALE;
+
+        // A full update flags the price and the APY that moved, not the asset left as it was
+        let before = data.stored_values();
+        PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::SOL, 101 * PRICE_SCALE, None, new_round(), 1_010, &clock).unwrap();
+        PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::MSOL, 120 * PRICE_SCALE, Some(8 * PRICE_SCALE / 100), new_round(), 1_010, &clock).unwrap();
+        PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::JitoSOL, 110 * PRICE_SCALE, None, new_round(), 1_010, &clock).unwrap();
+        header.record_changed_assets(&before, &data, clock.slot);
+        assert_eq!(header.last_changed_mask, 1 << AssetType::SOL.index() | 1 << AssetType::MSOL.index());
+        assert_eq!(header.changed_assets(), vec![AssetType::MSOL, AssetType::SOL]);
+        assert_eq!(header.last_changed_slot, 5_020);
+
+        // A partial update flags only the asset it wrote, not the one it skipped
+        PriceOracle::set_partial_updates(&mut header, true);
+        let clock = clock_at(1_020, 5_030);
+        let before = data.stored_values();
+        let skipped = PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::SOL, 200 * PRICE_SCALE, None, new_round(), 1_020, &clock).unwrap();
+        assert!(!skipped.updated);
+        PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::JitoSOL, 111 * PRICE_SCALE, None, new_round(), 1_020, &clock).unwrap();
+        header.record_changed_assets(&before, &data, clock.slot);
+        assert_eq!(header.changed_assets(), vec![AssetType::JitoSOL]);
+
+        // An update that finds every price unchanged clears the mask but still stamps its slot
+        let clock = clock_at(1_030, 5_040);
+        let before = data.stored_values();
+        let outcome = PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::JitoSOL, 111 * PRICE_SCALE, None, new_round(), 1_030, &clock).unwrap();
+        assert!(outcome.unchanged);
+        header.record_changed_assets(&before, &data, clock.slot);
+        assert_eq!((header.last_changed_mask, header.last_changed_slot), (0, 5_040));
+    }
+
+    #[test]
+    fn test_delisted_asset_is_skipped_by_updates() {
+        let clock = clock_at(1_010, 5_020);
+        let mut data = data_with_sol_price(100 * PRICE_SCALE);
+        PriceOracle::delist_asset(&mut data, AssetType::SOL).unwrap();
+
+        // Registered feeds of delisted assets are passed over without failing the update
+        let (result, header, after) = run_sol_update(oracle_header(), data.clone(), sol_feed(15_610, 2), &clock);
+        assert_eq!(result.unwrap(), vec![]);
+        assert_eq!(after.price_data[AssetType::SOL.index()].price, 100 * PRICE_SCALE);
+        assert_eq!(header.last_global_update, 0);
+
+        // Fixed-feed updates skip it whatever the partial-update mode
+        let mut header = oracle_header();
+        let outcome = PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::SOL, 300 * PRICE_SCALE, None, new_round(), clock.unix_timestamp, &clock).unwrap();
+        let delisted_code = u32::from(OracleError::AssetDelisted);
+        assert_eq!(outcome, AssetOutcome { asset_type: AssetType::SOL, updated: false, error_code: delisted_code, rejected_apy: None, unchanged: false });
+        assert!(!header.emergency_stop);
+        assert_eq!(header.rejection_codes[AssetType::SOL.index()], delisted_code);
+        assert_eq!(data.price_data[AssetType::SOL.index()].price, 100 * PRICE_SCALE);
+    }
+
+    #[test]
+    fn test_paused_asset_is_skipped_until_resumed() {
+        let clock = clock_at(1_010, 5_020);
+        let data = data_with_sol_price(150 * PRICE_SCALE);
+        let mut header = oracle_header();
+        assert!(!PriceOracle::set_asset_paused(&mut header, AssetType::SOL, true));
+
+        let (result, paused_header, after) = run_sol_update(header.clone(), data.clone(), sol_feed(15_610, 2), &clock);
+        assert_eq!(result.unwrap(), vec![]);
+        assert_eq!(after.price_data[AssetType::SOL.index()].price, 150 * PRICE_SCALE);
+
+        let mut fixed_header = paused_header;
+        let mut fixed_data = data.clone();
+        let outcome = PriceOracle::apply_asset_value(&mut fixed_header, &mut fixed_data, AssetType::SOL, 151 * PRICE_SCALE, None, new_round(), clock.unix_timestamp, &clock).unwrap();
+        let paused_code = u32::from(OracleError::AssetPaused);
+        assert_eq!(outcome, AssetOutcome { asset_type: AssetType::SOL, updated: false, error_code: paused_code, rejected_apy: None, unchanged: false });
+        assert!(!fixed_header.emergency_stop);
+
+        assert!(PriceOracle::set_asset_paused(&mut header, AssetType::SOL, false));
+        let (result, _, after) = run_sol_update(header, data, sol_feed(15_610, 2), &clock);
+        assert_eq!(result.unwrap(), vec![AssetType::SOL]);
+        assert_eq!(after.price_data[AssetType::SOL.index()].price, 156_100_000_000);
+    }
+
+    #[test]
+    fn test_nonce_advances_once_per_change() {
+        let clock = clock_at(1_010, 5_020);
+        let mut data = data_with_sol_price(150 * PRICE_SCALE);
+        data.price_data[AssetType::SOL.index()].quote = QuoteCurrency::USD;
+
+        // An update writing a price advances it once; rejected or skipped updates leave it
+        let (result, _, updated) = run_sol_update(oracle_header(), data.clone(), sol_feed(15_610, 2), &clock);
+        assert_eq!(result.unwrap(), vec![AssetType::SOL]);
+        assert_eq!(updated.nonce, 1);
+        let (result, stopped, rejected) = run_sol_update(oracle_header(), data.clone(), sol_feed(30_000, 2), &clock);
+        assert_eq!(result.unwrap(), vec![]);
+        assert!(stopped.emergency_stop);
+        assert_eq!(rejected.nonce, 0);
+        let mut paused = oracle_header();
+        PriceOracle::set_asset_paused(&mut paused, AssetType::SOL, true);
+        let (result, _, skipped) = run_sol_update(paused, data.clone(), sol_feed(15_610, 2), &clock);
+        assert_eq!(result.unwrap(), vec![]);
+        assert_eq!(skipped.nonce, 0);
+
+        // So do admin changes, only when they change something
+        let mut data = updated;
+        PriceOracle::set_asset_quote(&mut data, AssetType::SOL, QuoteCurrency::USD);
+        assert_eq!(data.nonce, 1);
+        PriceOracle::reset_watermarks(&mut data, AssetType::SOL);
+        assert_eq!(data.nonce, 2);
+        PriceOracle::reset_watermarks(&mut data, AssetType::SOL);
+        PriceOracle::set_asset_quote(&mut data, AssetType::SOL, QuoteCurrency::SOL);
+        assert_eq!(data.nonce, 3);
+        PriceOracle::delist_asset(&mut data, AssetType::SOL).unwrap();
+        assert_eq!(data.nonce, 4);
+
+        // It wraps rather than overflowing
+        data.nonce = u64::MAX;
+        data.advance_nonce();
+        assert_eq!(data.nonce, 0);
+    }
+
+    #[test]
+    fn test_state_hash_golden_vectors() {
+        let hex = |bytes: &[u8]| bytes.iter().map(|byte| format!("{:02x}", byte)).collect::<String>();
+
+        // No prices: each entry is the asset's id and 24 zero bytes
+        let empty = PriceOracleData::default();
+        let encoding = empty.state_encoding();
+        assert_eq!(encoding.len(), 250);
+        for (i, entry) in encoding.chunks(STATE_ENTRY_LEN).enumerate() {
+            assert_eq!(entry[0], i as u8);
+            assert!(entry[1..].iter().all(|byte| *byte == 0));
+        }
+        assert_eq!(hex(&empty.state_hash()), "8b9e0f5a569d3ee56e6f3b0e1d3d8fab245b9eaf92e8053a27c8a13e5b9327c7");
+
+        let mut data = PriceOracleData::default();
+        data.price_data[AssetType::SOL.index()].price = 156_100_000_000;
+        data.price_data[AssetType::SOL.index()].feed_timestamp = 1_700_000_000;
+        let msol = &mut data.price_data[AssetType::MSOL.index()];
+        msol.price = 1_180_000_000;
+        msol.apy = 72_500_000;
+        msol.feed_timestamp = 1_700_000_060;
+        let encoding = data.state_encoding();
+        // Id, then price, APY and feed timestamp in little-endian
+        assert_eq!(hex(&encoding[3 * STATE_ENTRY_LEN..4 * STATE_ENTRY_LEN]), "03".to_owned() + "005f554600000000" + "2043520400000000" + "3cf1536500000000");
+        assert_eq!(hex(&encoding[6 * STATE_ENTRY_LEN..7 * STATE_ENTRY_LEN]), "06".to_owned() + "00f9485824000000" + "0000000000000000" + "00f1536500000000");
+        assert_eq!(hex(&data.state_hash()), "d8b7d6b4df3f534bec5cb9565f32cb47948545242aad30af3c9e2e401fa00954");
+
+        // Only the committed fields count: bookkeeping leaves the hash, a price moves it
+        let before = data.state_hash();
+        data.nonce = 9;
+        data.price_data[AssetType::SOL.index()].last_checked_time = 1_700_000_100;
+        data.price_data[AssetType::SOL.index()].delisted = true;
+        assert_eq!(data.state_hash(), before);
+        data.price_data[AssetType::BonkSOL.index()].feed_timestamp = 1;
+        assert_ne!(data.state_hash(), before);
+    }
+
+    #[test]
+    fn test_updates_record_provenance() {
+        let switchboard_key = Pubkey::new_unique();
+        let feed = sol_feed(15_610, 2).round_open_slot(4_990).into_account(switchboard_key);
+        let (result, _, data) = run_update_with_feed_account(AssetType::SOL, oracle_header(), PriceOracleData::default(), feed, &clock_at(1_010, 5_020));
+        result.unwrap();
+        let provenance = PriceOracle::get_price_provenance(&data, AssetType::SOL).unwrap();
+        assert_eq!(provenance, PriceProvenance { source_feed: switchboard_key, source_round_slot: 4_990, source_kind: 0 });
+
+        let chainlink_key = Pubkey::new_unique();
+        let feed = MockChainlinkFeed::new(8).live_rows(1).round(0, 1, 1_000, 4_995, 15_700_000_000).live_cursor(1).into_account(chainlink_key);
+        let mut header = oracle_header();
+        PriceOracle::set_asset_source(&mut header, AssetType::SOL, SourceKind::Chainlink);
+        let (result, _, data) = run_update_with_feed_account(AssetType::SOL, header, data, feed, &clock_at(1_020, 5_030));
+        result.unwrap();
+        assert_eq!(data.price_data[AssetType::SOL.index()].provenance, PriceProvenance::from_feed(SourceKind::Chainlink, chainlink_key, 4_995));
+
+        let pyth_key = Pubkey::new_unique();
+        let feed = MockPythPriceUpdate::new(15_750_000_000, -8, 1_025, 5_035).feed_id(PYTH_SOL_USD_FEED_ID).into_account(pyth_key);
+        let mut header = oracle_header();
+        PriceOracle::set_asset_source(&mut header, AssetType::SOL, SourceKind::Pyth);
+        let (result, _, data) = run_update_with_feed_account(AssetType::SOL, header, data, feed, &clock_at(1_030, 5_040));
+        result.unwrap();
+        let sol = data.price_data[AssetType::SOL.index()];
+        assert_eq!((sol.price, sol.feed_timestamp), (157_500_000_000, 1_025));
+        assert_eq!(sol.provenance, PriceProvenance::from_feed(SourceKind::Pyth, pyth_key, 5_035));
+
+        let pull_feed_key = Pubkey::new_unique();
+        let queue = Pubkey::new_unique();
+        let feed = MockPullFeed::new(queue, 157_800_000_000_000_000_000, 1_035, 5_045).into_account(pull_feed_key);
+        let mut header = oracle_header();
+        PriceOracle::set_asset_source(&mut header, AssetType::SOL, SourceKind::SwitchboardOnDemand);
+        PriceOracle::set_on_demand_queue(&mut header, queue);
+        let (result, _, data) = run_update_with_feed_account(AssetType::SOL, header, data, feed, &clock_at(1_040, 5_050));
+        result.unwrap();
+        let sol = data.price_data[AssetType::SOL.index()];
+        assert_eq!((sol.price, sol.feed_timestamp), (157_800_000_000, 1_035));
+        assert_eq!(sol.provenance, PriceProvenance::from_feed(SourceKind::SwitchboardOnDemand, pull_feed_key, 5_045));
+
+        // Assets never updated have no provenance to report
+        let err = PriceOracle::get_price_provenance(&data, AssetType::MSOL).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::PriceNotAvailable));
+    }
+
+    #[test]
+    fn test_pyth_sources_must_carry_the_bound_feed_id() {
+        let mut registry = AssetRegistry::default();
+        assert_eq!((registry.pyth_feed_id(AssetType::SOL), registry.pyth_feed_id(AssetType::MSOL)), (PYTH_SOL_USD_FEED_ID, [0; 32]));
+        assert_eq!(PriceOracle::set_pyth_feed_id(&mut registry, AssetType::MSOL, [4; 32]), [0; 32]);
+        assert_eq!(PriceOracle::set_pyth_feed_id(&mut registry, AssetType::SOL, [3; 32]), [0; 32]);
+        assert_eq!((registry.pyth_feed_id(AssetType::SOL), registry.pyth_feed_id(AssetType::MSOL)), ([3; 32], [4; 32]));
+        assert_eq!(PriceOracle::set_pyth_feed_id(&mut registry, AssetType::SOL, [0; 32]), [3; 32]);
+        assert_eq!(registry.pyth_feed_id(AssetType::SOL), PYTH_SOL_USD_FEED_ID);
+
+        // A valid update for another feed is refused without touching the price
+        let mut header = oracle_header();
+        PriceOracle::set_asset_source(&mut header, AssetType::SOL, SourceKind::Pyth);
+        let feed = MockPythPriceUpdate::new(3_000_000_000, -8, 1_025, 5_035).feed_id([9; 32]).into_account(Pubkey::new_unique());
+        let (result, _, data) = run_update_with_feed_account(AssetType::SOL, header, PriceOracleData::default(), feed, &clock_at(1_030, 5_040));
+        assert_eq!(error_code(&result.unwrap_err()), u32::from(OracleError::PythFeedIdMismatch));
+        assert_eq!(data.price_data[AssetType::SOL.index()].price, 0);
+    }
+
+    #[test]
+    fn test_repeated_rounds_are_not_written_again() {
+        let key = Pubkey::new_unique();
+        let (result, header, data) = run_update_with_feed_account(AssetType::SOL, oracle_header(), PriceOracleData::default(), sol_feed(15_610, 2).into_account(key), &clock_at(1_010, 5_020));
+        assert_eq!(result.unwrap(), vec![AssetType::SOL]);
+
+        // A second crank against the same round changes nothing, not even the update time
+        let (result, header, data) = run_update_with_feed_account(AssetType::SOL, header, data, sol_feed(15_610, 2).into_account(key), &clock_at(1_030, 5_040));
+        assert_eq!(error_code(&result.unwrap_err()), u32::from(OracleError::NoNewRound));
+        let sol = data.price_data[AssetType::SOL.index()];
+        assert_eq!((sol.last_update_time, sol.provenance.source_round_slot, data.nonce), (1_010, 5_000, 1));
+
+        // A new round is written
+        let feed = sol_feed(15_620, 2).round_open_slot(5_030).into_account(key);
+        let (result, _, data) = run_update_with_feed_account(AssetType::SOL, header, data, feed, &clock_at(1_040, 5_050));
+        assert_eq!(result.unwrap(), vec![AssetType::SOL]);
+        assert_eq!(data.price_data[AssetType::SOL.index()].last_update_time, 1_040);
+
+        // Fixed feeds report the repeated asset and write the rest
+        let mut header = oracle_header();
+        let mut data = PriceOracleData::default();
+        let round = new_round();
+        PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::MSOL, PRICE_SCALE, Some(0), round, 1_000, &clock_at(1_000, 5_000)).unwrap();
+        let repeated = PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::MSOL, PRICE_SCALE, Some(0), round, 1_010, &clock_at(1_010, 5_010)).unwrap();
+        let no_new_round = u32::from(OracleError::NoNewRound);
+        assert_eq!(repeated, AssetOutcome { asset_type: AssetType::MSOL, updated: false, error_code: no_new_round, rejected_apy: None, unchanged: false });
+        assert_eq!(data.price_data[AssetType::MSOL.index()].last_update_time, 1_000);
+        assert_eq!(header.rejection_codes[AssetType::MSOL.index()], no_new_round);
+        let accepted = PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::BSOL, PRICE_SCALE, Some(0), round, 1_010, &clock_at(1_010, 5_010)).unwrap();
+        PriceOracle::finish_fixed_feed_update(&mut header, &mut data, vec![repeated, accepted], &clock_at(1_010, 5_010)).unwrap();
+
+        // Only when no asset was read from a new round does the update fail with `NoNewRound`
+        let delisted = AssetOutcome { asset_type: AssetType::HSOL, updated: false, error_code: u32::from(OracleError::AssetDelisted), rejected_apy: None, unchanged: false };
+        let err = PriceOracle::finish_fixed_feed_update(&mut header, &mut data, vec![repeated, delisted], &clock_at(1_020, 5_020)).unwrap_err();
+        assert_eq!(error_code(&err), no_new_round);
+        let rejected = AssetOutcome { asset_type: AssetType::BSOL, updated: false, error_code: u32::from(OracleError::PriceChangeExceedsLimit), rejected_apy: None, unchanged: false };
+        let err = PriceOracle::finish_fixed_feed_update(&mut header, &mut data, vec![repeated, rejected], &clock_at(1_020, 5_020)).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::NoAssetUpdated));
+        let err = PriceOracle::finish_fixed_feed_update(&mut header, &mut data, vec![delisted], &clock_at(1_020, 5_020)).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::NoAssetUpdated));
+
+        // Signed prices have no round to repeat
+        let signed = PriceProvenance::from_publisher(Pubkey::new_unique(), 5_000);
+        assert!(!signed.repeats_round(&signed));
+    }
+
+    #[test]
+    fn test_unchanged_prices_only_move_the_check_time() {
+        let key = Pubkey::new_unique();
+        let (result, header, data) = run_update_with_feed_account(AssetType::SOL, oracle_header(), PriceOracleData::default(), sol_feed(15_610, 2).into_account(key), &clock_at(1_010, 5_020));
+        assert_eq!(result.unwrap(), vec![AssetType::SOL]);
+
+        // A new round at the stored price confirms it without rewriting it
+        let feed = sol_feed(15_610, 2).round_open_slot(5_030).round_open_timestamp(1_040).into_account(key);
+        let (result, header, data) = run_update_with_feed_account(AssetType::SOL, header, data, feed, &clock_at(1_040, 5_050));
+        assert_eq!(result.unwrap(), vec![]);
+        let sol = data.price_data[AssetType::SOL.index()];
+        assert_eq!((sol.last_update_time, sol.last_update_slot, sol.last_price_time), (1_010, 5_020, 0));
+        assert_eq!((sol.last_checked_time, sol.last_checked_slot, sol.provenance.source_round_slot), (1_040, 5_050, 5_030));
+        assert_eq!(sol.feed_timestamp, 1_040);
+        assert_eq!(data.nonce, 2);
+
+        // Staleness is measured from the confirming round: 330s after the update, 300s after the round
+        let clock = clock_at(1_340, 5_100);
+        PriceOracle::assert_price_fresh(&header, &data, AssetType::SOL, 300, false, &clock).unwrap();
+        assert!(!PriceOracle::get_all_prices(&header, &data, &clock)[AssetType::SOL.index()].stale);
+        assert_eq!(PriceOracle::get_price_lenient(&header, &data, AssetType::SOL, &clock).unwrap().staleness_secs, 300);
+
+        // Fixed feeds report an unchanged asset, count it as progress and write only its check time
+        let mut header = oracle_header();
+        let mut data = PriceOracleData::default();
+        PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::MSOL, PRICE_SCALE, Some(7), new_round(), 1_000, &clock_at(1_000, 5_000)).unwrap();
+        let unchanged = PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::MSOL, PRICE_SCALE, Some(7), new_round(), 1_010, &clock_at(1_010, 5_010)).unwrap();
+        assert_eq!(unchanged, AssetOutcome { asset_type: AssetType::MSOL, updated: false, error_code: 0, rejected_apy: None, unchanged: true });
+        PriceOracle::finish_fixed_feed_update(&mut header, &mut data, vec![unchanged], &clock_at(1_010, 5_010)).unwrap();
+        let msol = data.price_data[AssetType::MSOL.index()];
+        assert_eq!((msol.last_update_time, msol.last_checked_time, msol.feed_timestamp, data.nonce), (1_000, 1_010, 1_010, 1));
+        assert_eq!(header.update_stats.successful_updates, 0);
+
+        // A different APY is a change
+        let outcome = PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::MSOL, PRICE_SCALE, Some(8), new_round(), 1_020, &clock_at(1_020, 5_020)).unwrap();
+        assert!(outcome.updated && !outcome.unchanged);
+        assert_eq!(data.price_data[AssetType::MSOL.index()].last_update_time, 1_020);
+    }
+
+    #[test]
+    fn test_staleness_follows_the_feed_timestamp() {
+        // A keeper relays a round the feed published 200s before the update lands
+        let key = Pubkey::new_unique();
+        let feed = sol_feed(15_610, 2).round_open_timestamp(1_000).into_account(key);
+        let (result, header, data) = run_update_with_feed_account(AssetType::SOL, oracle_header(), PriceOracleData::default(), feed, &clock_at(1_200, 5_020));
+        assert_eq!(result.unwrap(), vec![AssetType::SOL]);
+        let sol = data.price_data[AssetType::SOL.index()];
+        assert_eq!((sol.last_update_time, sol.last_checked_time, sol.feed_timestamp), (1_200, 1_200, 1_000));
+
+        // The local times say the price is fresh, the feed says it is 210s old
+        let clock = clock_at(1_210, 5_030);
+        assert_eq!(sol.feed_age_secs(&clock), 210);
+        assert!(sol.is_stale(&clock, MaxAge::Seconds(120)));
+        assert!(!sol.is_stale(&clock, MaxAge::Seconds(300)));
+        let err = PriceOracle::assert_price_fresh(&header, &data, AssetType::SOL, 120, false, &clock).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::StaleData));
+        let lenient = PriceOracle::get_price_lenient(&header, &data, AssetType::SOL, &clock).unwrap();
+        assert_eq!((lenient.last_update_time, lenient.feed_timestamp, lenient.staleness_secs, lenient.degraded), (1_200, 1_000, 210, false));
+        let price = PriceOracle::get_price_with_confidence(&data, AssetType::SOL).unwrap();
+        assert_eq!((price.last_update_time, price.last_checked_time, price.feed_timestamp), (1_200, 1_200, 1_000));
+    }
+
+    /// SOL stored at $100 from a round at slot 4_000 of `feed`, the SOL feed registered in the
+    /// returned registry
+    fn forceable_sol(feed: &Pubkey) -> (PriceOracleData, AssetRegistry) {
+        let mut data = data_with_sol_price(100 * PRICE_SCALE);
+        data.price_data[AssetType::SOL.index()].provenance = PriceProvenance::from_feed(SourceKind::Switchboard, *feed, 4_000);
+        let mut registry = AssetRegistry { queue: test_queue(), ..AssetRegistry::default() };
+        registry.assets[AssetType::SOL.index()].feed = *feed;
+        (data, registry)
+    }
+
+    #[test]
+    fn test_force_update_skips_only_the_change_limit() {
+        let key = Pubkey::new_unique();
+        let (data, registry) = forceable_sol(&key);
+        let clock = clock_at(1_010, 5_020);
+
+        // A 56% move trips the limit and the stop on the normal path
+        let (result, stopped, unchanged) = run_update_with_feed_account(AssetType::SOL, oracle_header(), data.clone(), sol_feed(15_610, 2).into_account(key), &clock);
+        assert_eq!(result.unwrap(), vec![]);
+        assert!(stopped.emergency_stop);
+        assert_eq!(unchanged.price_data[AssetType::SOL.index()].price, 100 * PRICE_SCALE);
+
+        // Forced, it is written from the same round and marked as such
+        let mut header = oracle_header();
+        header.pending_prices[AssetType::SOL.index()] = PendingPrice { price: 130 * PRICE_SCALE, observed_at: 900 };
+        header.rejection_codes[AssetType::SOL.index()] = u32::from(OracleError::PriceChangeExceedsLimit);
+        header.clamped_prices[AssetType::SOL.index()] = 200 * PRICE_SCALE;
+        let mut data = data;
+        let mut feed = sol_feed(15_610, 2).std_deviation(12, 2).into_account(key);
+        let old_price = PriceOracle::force_update(&mut header, &mut data, &registry, AssetType::SOL, &feed.account_info(), &clock).unwrap();
+        assert_eq!(old_price, 100 * PRICE_SCALE);
+        let sol = data.price_data[AssetType::SOL.index()];
+        assert_eq!((sol.price, sol.last_price, sol.last_update_time, sol.feed_timestamp), (156_100_000_000, 100 * PRICE_SCALE, 1_010, 1_000));
+        assert_eq!(sol.provenance, PriceProvenance { source_feed: key, source_round_slot: 5_000, source_kind: PriceProvenance::FORCED });
+        assert_eq!(sol.confidence, Confidence { value: 120_000_000, status: ConfidenceStatus::Measured });
+        assert_eq!((header.pending_prices[AssetType::SOL.index()], header.rejection_codes[AssetType::SOL.index()]), (PendingPrice::default(), 0));
+        assert!(!header.emergency_stop && !header.is_clamped(AssetType::SOL));
+        assert_eq!((header.last_global_update, header.update_stats.successful_updates), (1_010, 1));
+        assert_eq!(data.nonce, 1);
+
+        // Forcing the same round again is a repeat
+        let err = PriceOracle::force_update(&mut header, &mut data, &registry, AssetType::SOL, &feed.account_info(), &clock).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::NoNewRound));
+
+        // The forced price is the baseline of the next normal update
+        let next = sol_feed(15_900, 2).round_open_slot(5_030).into_account(key);
+        let (result, header, data) = run_update_with_feed_account(AssetType::SOL, header, data, next, &clock_at(1_040, 5_050));
+        assert_eq!(result.unwrap(), vec![AssetType::SOL]);
+        let sol = data.price_data[AssetType::SOL.index()];
+        assert_eq!((sol.price, sol.provenance.source_kind), (159 * PRICE_SCALE, u8::from(SourceKind::Switchboard)));
+        assert!(!header.emergency_stop);
+    }
+
+    #[test]
+    fn test_force_update_keeps_every_other_check() {
+        let key = Pubkey::new_unique();
+        let (data, registry) = forceable_sol(&key);
+        let clock = clock_at(1_010, 5_020);
+        let force = |header: &mut PriceOracleHeader, data: &mut PriceOracleData, registry: &AssetRegistry, mut feed: MockAccount| {
+            let err = PriceOracle::force_update(header, data, registry, AssetType::SOL, &feed.account_info(), &clock).unwrap_err();
+            assert_eq!((data.price_data[AssetType::SOL.index()].price, data.nonce), (100 * PRICE_SCALE, 0));
+            error_code(&err)
+        };
+        let feed = || sol_feed(15_610, 2).into_account(key);
+
+        let stopped = PriceOracleHeader { emergency_stop: true, ..oracle_header() };
+        assert_eq!(force(&mut stopped.clone(), &mut data.clone(), &registry, feed()), u32::from(OracleError::EmergencyStop));
+        let paused = PriceOracleHeader { updates_paused: true, ..oracle_header() };
+        assert_eq!(force(&mut paused.clone(), &mut data.clone(), &registry, feed()), u32::from(OracleError::UpdatesPaused));
+        let mut asset_paused = oracle_header();
+        PriceOracle::set_asset_paused(&mut asset_paused, AssetType::SOL, true);
+        assert_eq!(force(&mut asset_paused, &mut data.clone(), &registry, feed()), u32::from(OracleError::AssetPaused));
+        let mut delisted = data.clone();
+        delisted.price_data[AssetType::SOL.index()].delisted = true;
+        assert_eq!(force(&mut oracle_header(), &mut delisted, &registry, feed()), u32::from(OracleError::AssetDelisted));
+
+        // The feed must be the asset's registered one, from the recorded program and queue, fresh and new
+        let other = sol_feed(15_610, 2).into_account(Pubkey::new_unique());
+        assert_eq!(force(&mut oracle_header(), &mut data.clone(), &registry, other), u32::from(OracleError::UnregisteredFeed));
+        let foreign = MockAccount::new(key, Pubkey::new_unique(), sol_feed(15_610, 2).to_account_data());
+        assert_eq!(force(&mut oracle_header(), &mut data.clone(), &registry, foreign), u32::from(OracleError::InvalidSwitchboardAccount));
+        let other_queue = sol_feed(15_610, 2).queue(Pubkey::new_unique()).into_account(key);
+        assert_eq!(force(&mut oracle_header(), &mut data.clone(), &registry, other_queue), u32::from(OracleError::InvalidSwitchboardAccount));
+        let stale = sol_feed(15_610, 2).round_open_timestamp(1_010 - MAX_SWITCHBOARD_DATA_AGE - 1).into_account(key);
+        assert_eq!(force(&mut oracle_header(), &mut data.clone(), &registry, stale), u32::from(OracleError::StaleData));
+        let repeated = sol_feed(15_610, 2).round_open_slot(4_000).into_account(key);
+        assert_eq!(force(&mut oracle_header(), &mut data.clone(), &registry, repeated), u32::from(OracleError::NoNewRound));
+    }
+
+    #[test]
+    fn test_updates_record_confidence() {
+        let clock = clock_at(1_010, 5_020);
+        // 0.25 USD across the round's responses
+        let feed = sol_feed(15_610, 2).std_deviation(25, 2).num_success(5);
+        let mut data = PriceOracleData::default();
+        data.price_data[AssetType::SOL.index()].quote = QuoteCurrency::USD;
+        let (result, _, data) = run_sol_update(oracle_header(), data, feed, &clock);
+        result.unwrap();
+        let expected = Confidence { value: 250_000_000, status: ConfidenceStatus::Measured };
+        assert_eq!(data.price_data[AssetType::SOL.index()].confidence, expected);
+        let price = PriceOracle::get_price_with_confidence(&data, AssetType::SOL).unwrap();
+        assert_eq!(price, PriceWithConfidence { price: 156_100_000_000, quote: QuoteCurrency::USD, confidence: expected, last_update_time: 1_010, last_checked_time: 1_010, feed_timestamp: 1_000 });
+
+        // A single-oracle round reports no deviation; that is flagged, not stored as a tight spread
+        let feed = sol_feed(15_620, 2).num_success(1);
+        let (result, _, data) = run_sol_update(oracle_header(), data, feed, &clock_at(1_020, 5_030));
+        result.unwrap();
+        let confidence = data.price_data[AssetType::SOL.index()].confidence;
+        assert_eq!(confidence, Confidence { value: 0, status: ConfidenceStatus::ZeroDeviation });
+
+        // Chainlink rounds carry no spread at all
+        let feed = MockChainlinkFeed::new(8).live_rows(1).round(0, 1, 1_025, 5_035, 15_700_000_000).live_cursor(1).into_account(Pubkey::new_unique());
+        let mut header = oracle_header();
+        PriceOracle::set_asset_source(&mut header, AssetType::SOL, SourceKind::Chainlink);
+        let (result, _, data) = run_update_with_feed_account(AssetType::SOL, header, data, feed, &clock_at(1_030, 5_040));
+        result.unwrap();
+        assert_eq!(data.price_data[AssetType::SOL.index()].confidence.status, ConfidenceStatus::Unavailable);
+
+        let err = PriceOracle::get_price_with_confidence(&data, AssetType::MSOL).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::PriceNotAvailable));
+    }
+
+    #[test]
+    fn test_partial_updates_skip_rejected_assets() {
+        let clock = clock_at(1_010, 5_020);
+        let mut data = PriceOracleData::default();
+        data.price_data[AssetType::BSOL.index()].price = 100 * PRICE_SCALE;
+        data.price_data[AssetType::MSOL.index()].price = 100 * PRICE_SCALE;
+
+        // Strict mode: one bad asset trips the stop, which leaves it and the rest untouched
+        let mut header = oracle_header();
+        let mut strict_data = data.clone();
+        let tripped = PriceOracle::apply_asset_value(&mut header, &mut strict_data, AssetType::BSOL, 130 * PRICE_SCALE, Some(1), new_round(), clock.unix_timestamp, &clock).unwrap();
+        let stopped = PriceOracle::apply_asset_value(&mut header, &mut strict_data, AssetType::MSOL, 101 * PRICE_SCALE, Some(2), new_round(), clock.unix_timestamp, &clock).unwrap();
+        assert_eq!((tripped.updated, tripped.error_code), (false, u32::from(OracleError::PriceChangeExceedsLimit)));
+        assert_eq!((stopped.updated, stopped.error_code), (false, u32::from(OracleError::EmergencyStop)));
+        assert!(header.emergency_stop);
+        for asset_type in [AssetType::BSOL, AssetType::MSOL] {
+            assert_eq!(strict_data.price_data[asset_type.index()].price, 100 * PRICE_SCALE);
+        }
+        PriceOracle::finish_fixed_feed_update(&mut header, &mut strict_data, vec![tripped, stopped], &clock).unwrap();
+        assert_eq!((header.last_global_update, strict_data.nonce), (0, 0));
+
+        // Partial mode: the bad asset is flagged and left untouched, the others are written
+        let mut header = oracle_header();
+        PriceOracle::set_partial_updates(&mut header, true);
+        let rejected = PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::BSOL, 130 * PRICE_SCALE, Some(1), new_round(), clock.unix_timestamp, &clock).unwrap();
+        let accepted = PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::MSOL, 101 * PRICE_SCALE, Some(2), new_round(), clock.unix_timestamp, &clock).unwrap();
+        let limit_code = u32::from(OracleError::PriceChangeExceedsLimit);
+        assert_eq!(rejected, AssetOutcome { asset_type: AssetType::BSOL, updated: false, error_code: limit_code, rejected_apy: None, unchanged: false });
+        assert_eq!(accepted, AssetOutcome { asset_type: AssetType::MSOL, updated: true, error_code: 0, rejected_apy: None, unchanged: false });
+        assert!(!header.emergency_stop);
+        assert!(header.is_rejected(AssetType::BSOL));
+        assert!(!header.is_rejected(AssetType::MSOL));
+        let bsol = data.price_data[AssetType::BSOL.index()];
+        assert_eq!((bsol.price, bsol.apy, bsol.last_update_time), (100 * PRICE_SCALE, 0, 0));
+        let msol = data.price_data[AssetType::MSOL.index()];
+        assert_eq!((msol.price, msol.apy, msol.last_update_time), (101 * PRICE_SCALE, 2, 1_010));
+
+        let outcomes = PriceOracle::finish_fixed_feed_update(&mut header, &mut data, vec![rejected, accepted], &clock).unwrap();
+        assert_eq!(outcomes.len(), 2);
+        assert_eq!(header.last_global_update, 1_010);
+        assert_eq!(data.nonce, 1);
+        assert_eq!(header.update_stats, UpdateStats { successful_updates: 1, rejected_change_limit: 1, ..UpdateStats::default() });
+
+        // Nothing written is still a failure, and a later accepted value clears the flag
+        let err = PriceOracle::finish_fixed_feed_update(&mut header, &mut data, vec![rejected], &clock_at(1_020, 5_030)).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::NoAssetUpdated));
+        assert_eq!(header.last_global_update, 1_010);
+        assert_eq!(data.nonce, 1);
+        PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::BSOL, 105 * PRICE_SCALE, Some(1), new_round(), clock.unix_timestamp, &clock).unwrap();
+        assert!(!header.is_rejected(AssetType::BSOL));
+    }
+
+    #[test]
+    fn test_last_error_is_set_on_rejection_and_cleared_on_acceptance() {
+        let mut data = PriceOracleData::default();
+        data.price_data[AssetType::BSOL.index()].price = 100 * PRICE_SCALE;
+        let mut header = oracle_header();
+        PriceOracle::set_partial_updates(&mut header, true);
+        PriceOracle::set_apy_change_limit(&mut header, AssetType::BSOL, 1_000);
+        assert_eq!(PriceOracle::get_last_error(&header, AssetType::BSOL), LastError::default());
+
+        // A skipped move past the change limit is recorded with its time
+        let limit_code = u32::from(OracleError::PriceChangeExceedsLimit);
+        PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::BSOL, 130 * PRICE_SCALE, Some(1), new_round(), 1_000, &clock_at(1_000, 5_000)).unwrap();
+        assert_eq!(header.last_error(AssetType::BSOL), LastError { code: limit_code, time: 1_000 });
+
+        // Skips that are no validation failure leave it as it was
+        header.paused[AssetType::BSOL.index()] = true;
+        PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::BSOL, 101 * PRICE_SCALE, Some(1), new_round(), 1_010, &clock_at(1_010, 5_010)).unwrap();
+        assert_eq!(header.last_error(AssetType::BSOL), LastError { code: limit_code, time: 1_000 });
+        header.paused[AssetType::BSOL.index()] = false;
+
+        // An accepted price clears it
+        PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::BSOL, 101 * PRICE_SCALE, Some(70_000_000), new_round(), 1_020, &clock_at(1_020, 5_020)).unwrap();
+        assert!(!header.last_error(AssetType::BSOL).is_set());
+
+        // A rejected APY is recorded though the price beside it is written
+        let outcome = PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::BSOL, 102 * PRICE_SCALE, Some(90_000_000), new_round(), 1_030, &clock_at(1_030, 5_030)).unwrap();
+        assert!(outcome.updated);
+        assert_eq!(header.last_error(AssetType::BSOL), LastError { code: u32::from(OracleError::ApyChangeExceedsLimit), time: 1_030 });
+
+        // So is a yield that does not convert
+        let daily_apr = YieldConfig { convention: YieldConvention::Apr, compounding_periods: 365 };
+        PriceOracle::set_yield_convention(&mut header, AssetType::BSOL, daily_apr).unwrap();
+        PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::BSOL, 103 * PRICE_SCALE, Some(u64::MAX), new_round(), 1_040, &clock_at(1_040, 5_040)).unwrap();
+        assert_eq!(header.last_error(AssetType::BSOL), LastError { code: u32::from(OracleError::ConversionOverflow), time: 1_040 });
+
+        // An unchanged observation passes validation too
+        PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::BSOL, 103 * PRICE_SCALE, None, new_round(), 1_050, &clock_at(1_050, 5_050)).unwrap();
+        assert!(!header.last_error(AssetType::BSOL).is_set());
+    }
+
+    #[test]
+    fn test_apy_change_limit_keeps_old_apy_and_writes_price() {
+        let mut header = oracle_header();
+        let mut data = PriceOracleData::default();
+        data.price_data[AssetType::MSOL.index()].price = 100 * PRICE_SCALE;
+        PriceOracle::set_apy_change_limit(&mut header, AssetType::MSOL, 5_000);
+        assert_eq!(header.apy_change_limit_bps(AssetType::MSOL), Some(5_000));
+        assert_eq!(header.apy_change_limit_bps(AssetType::BSOL), None);
+
+        // The first APY bypasses the limit
+        let outcome = PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::MSOL, 100 * PRICE_SCALE, Some(70_000_000), new_round(), 1_000, &clock_at(1_000, 5_000)).unwrap();
+        assert_eq!(outcome.rejected_apy, None);
+        assert_eq!(data.price_data[AssetType::MSOL.index()].apy, 70_000_000);
+
+        // 7% to 0% is held back, the price is still written
+        let outcome = PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::MSOL, 101 * PRICE_SCALE, Some(0), new_round(), 1_010, &clock_at(1_010, 5_010)).unwrap();
+        assert_eq!(outcome, AssetOutcome { asset_type: AssetType::MSOL, updated: true, error_code: 0, rejected_apy: Some(0), unchanged: false });
+        assert!(!header.is_rejected(AssetType::MSOL));
+        let msol = data.price_data[AssetType::MSOL.index()];
+        assert_eq!((msol.price, msol.apy, msol.last_update_time), (101 * PRICE_SCALE, 70_000_000, 1_010));
+
+        // Exactly at the limit is accepted; assets without a limit take any APY
+        let outcome = PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::MSOL, 101 * PRICE_SCALE, Some(105_000_000), new_round(), 1_020, &clock_at(1_020, 5_020)).unwrap();
+        assert_eq!(outcome.rejected_apy, None);
+        assert_eq!(data.price_data[AssetType::MSOL.index()].apy, 105_000_000);
+        data.price_data[AssetType::BSOL.index()].apy = 70_000_000;
+        PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::BSOL, 100 * PRICE_SCALE, Some(0), new_round(), 1_020, &clock_at(1_020, 5_020)).unwrap();
+        assert_eq!(data.price_data[AssetType::BSOL.index()].apy, 0);
+
+        // Lifting the limit lets the APY through again
+        PriceOracle::set_apy_change_limit(&mut header, AssetType::MSOL, 0);
+        PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::MSOL, 101 * PRICE_SCALE, Some(0), new_round(), 1_030, &clock_at(1_030, 5_030)).unwrap();
+        assert_eq!(data.price_data[AssetType::MSOL.index()].apy, 0);
+        assert!(matches!(PriceOracle::validate_apy_change(70_000_000, 0, Some(9_999)), Err(OracleError::ApyChangeExceedsLimit)));
+    }
+
+    #[test]
+    fn test_apr_yields_are_stored_as_apys() {
+        let mut header = oracle_header();
+        let mut data = PriceOracleData::default();
+        let daily_apr = YieldConfig { convention: YieldConvention::Apr, compounding_periods: 365 };
+        assert_eq!(PriceOracle::set_yield_convention(&mut header, AssetType::MSOL, daily_apr).unwrap(), YieldConfig::default());
+        assert_eq!(header.yield_config(AssetType::MSOL), daily_apr);
+        let invalid = YieldConfig { convention: YieldConvention::Apr, compounding_periods: 0 };
+        assert_eq!(PriceOracle::set_yield_convention(&mut header, AssetType::MSOL, invalid).unwrap_err(), error!(OracleError::InvalidConfig));
+
+        // A 10% APR compounded daily is stored as its APY, the reported value kept beside it
+        PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::MSOL, 100 * PRICE_SCALE, Some(100_000_000), new_round(), 1_000, &clock_at(1_000, 5_000)).unwrap();
+        assert_eq!(data.price_data[AssetType::MSOL.index()].apy, 105_155_781);
+        assert_eq!(PriceOracle::get_raw_yield(&header, &data, AssetType::MSOL).unwrap(), RawYield { value: 100_000_000, config: daily_apr });
+
+        // The same APR again is unchanged, compared after conversion
+        let outcome = PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::MSOL, 100 * PRICE_SCALE, Some(100_000_000), new_round(), 1_010, &clock_at(1_010, 5_010)).unwrap();
+        assert!(outcome.unchanged);
+
+        // APY assets store the reported value as is
+        PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::BSOL, 100 * PRICE_SCALE, Some(70_000_000), new_round(), 1_000, &clock_at(1_000, 5_000)).unwrap();
+        assert_eq!(data.price_data[AssetType::BSOL.index()].apy, 70_000_000);
+        assert_eq!(header.raw_yield(AssetType::BSOL), RawYield { value: 70_000_000, config: YieldConfig::default() });
+
+        // A yield that does not convert keeps the stored APY and still writes the price
+        let outcome = PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::MSOL, 101 * PRICE_SCALE, Some(u64::MAX), new_round(), 1_020, &clock_at(1_020, 5_020)).unwrap();
+        assert_eq!(outcome.rejected_apy, Some(u64::MAX));
+        let msol = data.price_data[AssetType::MSOL.index()];
+        assert_eq!((msol.price, msol.apy), (101 * PRICE_SCALE, 105_155_781));
+        assert_eq!(header.raw_yield(AssetType::MSOL).value, 100_000_000);
+    }
+
+    #[test]
+    fn test_reinitialize_data_forgets_per_asset_price_state() {
+        let mut header = oracle_header();
+        header.pending_prices[AssetType::SOL.index()] = PendingPrice { price: 200 * PRICE_SCALE, observed_at: 1_000 };
+        header.clamped_prices[AssetType::SOL.index()] = 300 * PRICE_SCALE;
+        header.raw_yields[AssetType::MSOL.index()] = RawYield { value: 70_000_000, config: YieldConfig::default() };
+        header.rejection_codes[AssetType::SOL.index()] = u32::from(OracleError::PriceChangeExceedsLimit);
+        header.record_asset_error(AssetType::SOL, u32::from(OracleError::PriceChangeExceedsLimit), 1_000);
+        let daily_apr = YieldConfig { convention: YieldConvention::Apr, compounding_periods: 365 };
+        header.yield_configs[AssetType::MSOL.index()] = daily_apr;
+        assert_eq!(PriceOracle::reinitialize_data(&mut header).unwrap_err(), error!(OracleError::NotStopped));
+        assert_eq!(header.data_generation, 0);
+
+        header.emergency_stop = true;
+        assert_eq!(PriceOracle::reinitialize_data(&mut header).unwrap(), 1);
+        assert_eq!(header.pending_prices[AssetType::SOL.index()], PendingPrice::default());
+        assert_eq!(header.clamped_prices[AssetType::SOL.index()], 0);
+        assert_eq!(header.raw_yield(AssetType::MSOL), RawYield::default());
+        assert_eq!(header.rejection_codes[AssetType::SOL.index()], 0);
+        assert!(!header.last_error(AssetType::SOL).is_set());
+        // Configuration kept in the header survives the rebuild
+        assert_eq!(header.yield_config(AssetType::MSOL), daily_apr);
+        assert!(header.emergency_stop);
+
+        header.data_generation = u32::MAX;
+        assert_eq!(PriceOracle::reinitialize_data(&mut header).unwrap(), 0);
+    }
+
+    /// Runs `verify_invariants` over `header` and `data` stored at `keys`, at time 1_010
+    fn run_verify_invariants(header: &PriceOracleHeader, data: &PriceOracleData, keys: (Pubkey, Pubkey), previous: (u64, u32)) -> u32 {
+        let mut header_account = MockAccount::program_account(keys.0, header);
+        let mut data_account = MockAccount::program_account(keys.1, data);
+        let header_info = header_account.account_info();
+        let data_info = data_account.account_info();
+        let header = Account::<PriceOracleHeader>::try_from(&header_info).unwrap();
+        let data = Account::<PriceOracleData>::try_from(&data_info).unwrap();
+        PriceOracle::verify_invariants(&crate::ID, &header, &data, previous.0, previous.1, 1_010)
+    }
+
+    #[test]
+    fn test_verify_invariants_flags_each_violation() {
+        let (header_key, header_bump) = PriceOracle::get_price_oracle_header_pda(&crate::ID, &DEFAULT_INSTANCE);
+        let (data_key, data_bump) = PriceOracle::get_price_oracle_data_pda(&crate::ID, &DEFAULT_INSTANCE);
+        let keys = (header_key, data_key);
+        let mut header = PriceOracleHeader { bump: header_bump, ..oracle_header() };
+        let mut data = PriceOracleData { bump: data_bump, ..PriceOracleData::default() };
+        data.price_data[AssetType::SOL.index()].record_price(100 * PRICE_SCALE, PriceProvenance::default(), Confidence::default(), 990, &clock_at(1_000, 10));
+        header.record_global_update(1, 1_000);
+        data.nonce = 3;
+        assert_eq!(run_verify_invariants(&header, &data, keys, (3, 0)), 0);
+
+        // Accounts at another address, or whose bump is not the canonical one
+        assert_eq!(run_verify_invariants(&header, &data, (Pubkey::new_unique(), data_key), (0, 0)), invariants::HEADER_ADDRESS);
+        assert_eq!(run_verify_invariants(&header, &data, (header_key, Pubkey::new_unique()), (0, 0)), invariants::DATA_ADDRESS);
+
+        type Corruption = fn(&mut PriceOracleHeader, &mut PriceOracleData);
+        let sol = AssetType::SOL.index();
+        let cases: [(Corruption, u32); 9] = [
+            (|header, _| header.bump = header.bump.wrapping_sub(1), invariants::HEADER_ADDRESS),
+            (|_, data| data.bump = data.bump.wrapping_sub(1), invariants::DATA_ADDRESS),
+            (|_, data| data.price_data[AssetType::SOL.index()].last_checked_time = 2_000, invariants::UPDATE_TIME_IN_FUTURE),
+            (|header, _| header.last_global_update = 2_000, invariants::UPDATE_TIME_IN_FUTURE),
+            (|_, data| data.price_data[AssetType::MSOL.index()].last_checked_time = -1, invariants::NEGATIVE_UPDATE_TIME),
+            (|header, _| header.last_global_update = 900, invariants::GLOBAL_UPDATE_BEHIND),
+            (|_, data| data.price_data[AssetType::SOL.index()].price = 200 * PRICE_SCALE, invariants::PRICE_OUTSIDE_WATERMARKS),
+            (|header, _| header.pending_prices[AssetType::MSOL.index()] = PendingPrice { price: PRICE_SCALE, observed_at: 1_000 }, invariants::PENDING_WITHOUT_CONFIRM),
+            (|header, _| header.stop_clear_effective_at = 5_000, invariants::CLEAR_WITHOUT_STOP),
+        ];
+        let mut seen = 0;
+        for (corrupt, bit) in cases {
+            let (mut header, mut data) = (header.clone(), data.clone());
+            corrupt(&mut header, &mut data);
+            assert_eq!(run_verify_invariants(&header, &data, keys, (3, 0)), bit);
+            seen |= bit;
+        }
+
+        // The nonce may only restart with a new data generation
+        assert_eq!(run_verify_invariants(&header, &data, keys, (4, 0)), invariants::NONCE_REGRESSED);
+        assert_eq!(run_verify_invariants(&header, &data, keys, (0, 1)), invariants::NONCE_REGRESSED);
+        header.data_generation = 1;
+        assert_eq!(run_verify_invariants(&header, &data, keys, (9, 0)), 0);
+        seen |= invariants::NONCE_REGRESSED;
+        assert_eq!(seen, invariants::ALL_INVARIANTS);
+
+        // Violations accumulate, and the ones that hold under their policy do not fire
+        header.large_move_policy[sol] = LargeMovePolicy::Confirm;
+        header.pending_prices[sol] = PendingPrice { price: PRICE_SCALE, observed_at: 1_000 };
+        header.emergency_stop = true;
+        header.stop_clear_effective_at = 5_000;
+        assert_eq!(run_verify_invariants(&header, &data, keys, (0, 0)), 0);
+        data.price_data[sol].last_update_time = 2_000;
+        data.price_data[sol].price = 50 * PRICE_SCALE;
+        assert_eq!(
+            run_verify_invariants(&header, &data, keys, (0, 0)),
+            invariants::UPDATE_TIME_IN_FUTURE | invariants::GLOBAL_UPDATE_BEHIND | invariants::PRICE_OUTSIDE_WATERMARKS,
+        );
+    }
+
+    #[test]
+    fn test_update_stats_count_updates_gaps_and_rejections() {
+        let mut header = oracle_header();
+        header.record_global_update(6, 1_000);
+        assert_eq!(header.update_stats, UpdateStats { successful_updates: 6, ..UpdateStats::default() });
+
+        // Only a longer gap replaces the recorded one
+        header.record_global_update(1, 1_300);
+        header.record_global_update(1, 1_400);
+        header.record_global_update(2, 2_000);
+        let stats = header.update_stats;
+        assert_eq!((stats.successful_updates, stats.longest_gap_secs, stats.longest_gap_at), (10, 600, 2_000));
+        assert_eq!(header.last_global_update, 2_000);
+
+        // Rejections count by reason; other codes are not tracked
+        header.count_rejection(u32::from(OracleError::StaleData));
+        header.count_rejection(u32::from(OracleError::PriceChangeExceedsLimit));
+        header.count_rejection(u32::from(OracleError::PriceChangeExceedsLimit));
+        header.count_rejection(u32::from(OracleError::InvalidAccountData));
+        assert_eq!((header.update_stats.rejected_stale, header.update_stats.rejected_change_limit), (1, 2));
+
+        // Counters saturate
+        header.update_stats.successful_updates = u64::MAX - 1;
+        header.update_stats.rejected_stale = u64::MAX;
+        header.record_global_update(7, 2_010);
+        header.count_rejection(u32::from(OracleError::StaleData));
+        assert_eq!((header.update_stats.successful_updates, header.update_stats.rejected_stale), (u64::MAX, u64::MAX));
+
+        // A strict update that trips the breaker counts the rejection and begins a stop incident
+        let mut header = oracle_header();
+        let mut data = data_with_sol_price(100 * PRICE_SCALE);
+        let clock = clock_at(1_010, 5_020);
+        let outcome = PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::SOL, 130 * PRICE_SCALE, None, new_round(), clock.unix_timestamp, &clock).unwrap();
+        assert_eq!(outcome.error_code, u32::from(OracleError::PriceChangeExceedsLimit));
+        assert_eq!(header.update_stats, UpdateStats { rejected_change_limit: 1, ..UpdateStats::default() });
+        let stats = PriceOracle::get_oracle_stats(&header, 1_070);
+        assert_eq!((stats.stop_incidents, stats.last_global_update), (header.stop_incident, 0));
+        assert_eq!(stats.stop_incidents, 1);
+        assert_eq!((stats.stopped_since, stats.total_stopped_secs), (1_010, 60));
+    }
+
+    #[test]
+    fn test_simulated_price_change_matches_the_update() {
+        let registry = AssetRegistry::default();
+        let clock = clock_at(1_010, 5_020);
+        let i = AssetType::MSOL.index();
+        let mut base = data_with_sol_price(150 * PRICE_SCALE);
+        base.price_data[i].price = 100 * PRICE_SCALE;
+        base.price_data[i].apy = 7 * PRICE_SCALE / 100;
+        let policy = |policy| {
+            let mut header = oracle_header();
+            PriceOracle::set_large_move_policy(&mut header, AssetType::MSOL, policy);
+            header
+        };
+        let apy_limited = PriceOracleHeader { apy_change_limit_bps: [100; ASSET_COUNT], ..oracle_header() };
+        let partial = PriceOracleHeader { partial_updates: true, ..oracle_header() };
+        let mut paused = oracle_header();
+        paused.paused[i] = true;
+        let scenarios = [
+            (oracle_header(), 110 * PRICE_SCALE, 75, None),
+            (oracle_header(), 125 * PRICE_SCALE, 70, Some(OracleError::PriceChangeExceedsLimit)),
+            (partial, 125 * PRICE_SCALE, 70, Some(OracleError::PriceChangeExceedsLimit)),
+            (policy(LargeMovePolicy::Clamp), 125 * PRICE_SCALE, 70, None),
+            (policy(LargeMovePolicy::Confirm), 125 * PRICE_SCALE, 70, Some(OracleError::PriceMovePending)),
+            (paused, 110 * PRICE_SCALE, 70, Some(OracleError::AssetPaused)),
+            (apy_limited, 110 * PRICE_SCALE, 80, Some(OracleError::ApyChangeExceedsLimit)),
+            (oracle_header(), 100 * PRICE_SCALE, 70, None),
+        ];
+
+        for (header, price, apy_permille, expected) in scenarios {
+            let apy = apy_permille * PRICE_SCALE / 1_000;
+            let simulation = PriceOracle::simulate_price_change(&header, &base, &registry, AssetType::MSOL, price, Some(apy), None, clock.unix_timestamp);
+            let (mut updated_header, mut data) = (header.clone(), base.clone());
+            let outcome = PriceOracle::apply_asset_value(&mut updated_header, &mut data, AssetType::MSOL, price, Some(apy), new_round(), clock.unix_timestamp, &clock).unwrap();
+
+            // Whatever the update did, the simulation said it beforehand
+            assert_eq!(simulation.accepted, outcome.updated || outcome.unchanged);
+            assert_eq!(simulation.reason_code, outcome.error_code);
+            assert_eq!(simulation.trips_breaker, updated_header.emergency_stop);
+            let apy_rejected = simulation.apy_limit.is_some_and(|check| !check.passed);
+            assert_eq!(apy_rejected && simulation.accepted, outcome.rejected_apy.is_some());
+            assert_eq!((simulation.price, simulation.apy), (data.price_data[i].price, data.price_data[i].apy));
+            let failed = [simulation.reason_code, simulation.apy_limit.map_or(0, |check| check.error_code)];
+            match expected {
+                Some(expected) => assert!(failed.contains(&u32::from(expected)), "{:?}", simulation),
+                None => assert_eq!(failed, [0, 0], "{:?}", simulation),
+            }
+        }
+
+        // Nothing was written, and the thresholds used are reported
+        let header = policy(LargeMovePolicy::Clamp);
+        let simulation = PriceOracle::simulate_price_change(&header, &base, &registry, AssetType::MSOL, 125 * PRICE_SCALE, None, None, clock.unix_timestamp);
+        assert_eq!(simulation.change_limit, SimulatedCheck {
+            passed: false,
+            error_code: u32::from(OracleError::PriceChangeExceedsLimit),
+            reference: 100 * PRICE_SCALE,
+            limit_bps: PRICE_CHANGE_LIMIT_BPS,
+        });
+        assert_eq!((simulation.move_verdict, simulation.price, simulation.apy_limit), (MoveVerdict::Clamp, 120 * PRICE_SCALE, None));
+        assert_eq!(header.clamped_prices[i], 0);
+
+        // A stopped oracle is reported as blocked, with the checks still run
+        let stopped = PriceOracleHeader { emergency_stop: true, ..oracle_header() };
+        let simulation = PriceOracle::simulate_price_change(&stopped, &base, &registry, AssetType::MSOL, 125 * PRICE_SCALE, None, None, clock.unix_timestamp);
+        assert_eq!((simulation.blocked_code, simulation.reason_code), (u32::from(OracleError::EmergencyStop), u32::from(OracleError::EmergencyStop)));
+        assert_eq!((simulation.move_verdict, simulation.trips_breaker, simulation.price), (MoveVerdict::Halt, false, 100 * PRICE_SCALE));
+        assert!(!simulation.change_limit.passed);
+    }
+
+    #[test]
+    fn test_update_assets_change_limit_trips_emergency_stop() {
+        let clock = clock_at(1_010, 5_020);
+
+        // Exactly +20% is accepted
+        let (result, header, data) = run_sol_update(oracle_header(), data_with_sol_price(100 * PRICE_SCALE), sol_feed(120, 0), &clock);
+        assert!(result.is_ok());
+        assert!(!header.emergency_stop);
+        assert_eq!(data.price_data[AssetType::SOL.index()].price, 120 * PRICE_SCALE);
+
+        // One unit past the limit is rejected and trips the stop, which the update keeps along
+        // with the rejection
+        let (result, header, data) = run_sol_update(
+            oracle_header(),
+            data_with_sol_price(100 * PRICE_SCALE),
+            sol_feed(120_000_000_001, 9),
+            &clock,
+        );
+        assert_eq!(result.unwrap(), vec![]);
+        assert!(header.emergency_stop && header.stop_automatic);
+        assert_eq!((header.stop_incident, header.stop_started_at, header.stop_change_count), (1, 1_010, 1));
+        assert_eq!(header.rejection_codes[AssetType::SOL.index()], u32::from(OracleError::PriceChangeExceedsLimit));
+        assert_eq!(header.last_error(AssetType::SOL), LastError { code: u32::from(OracleError::PriceChangeExceedsLimit), time: 1_010 });
+        assert_eq!(header.update_stats.rejected_change_limit, 1);
+        assert_eq!(data.price_data[AssetType::SOL.index()].price, 100 * PRICE_SCALE);
+    }
+
+    #[test]
+    fn test_confirm_policy_commits_a_confirmed_move_after_the_delay() {
+        let mut data = PriceOracleData::default();
+        data.price_data[AssetType::BSOL.index()].price = 100 * PRICE_SCALE;
+        let mut header = oracle_header();
+        PriceOracle::set_large_move_policy(&mut header, AssetType::BSOL, LargeMovePolicy::Confirm);
+        PriceOracle::set_move_confirmation(&mut header, 60, 100).unwrap();
+
+        // The move is held rather than written, and does not trip the stop
+        let held = PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::BSOL, 150 * PRICE_SCALE, None, new_round(), 1_000, &clock_at(1_000, 5_000)).unwrap();
+        let pending_code = u32::from(OracleError::PriceMovePending);
+        assert_eq!(held, AssetOutcome { asset_type: AssetType::BSOL, updated: false, error_code: pending_code, rejected_apy: None, unchanged: false });
+        assert!(!header.emergency_stop);
+        assert_eq!(data.price_data[AssetType::BSOL.index()].price, 100 * PRICE_SCALE);
+        assert_eq!(PriceOracle::get_pending_price(&header, AssetType::BSOL), PendingPrice { price: 150 * PRICE_SCALE, observed_at: 1_000 });
+
+        // A hold alone keeps the update, so the pending state is not rolled back
+        PriceOracle::finish_fixed_feed_update(&mut header, &mut data, vec![held], &clock_at(1_000, 5_000)).unwrap();
+        assert_eq!(header.last_global_update, 0);
+        assert_eq!(data.nonce, 0);
+
+        // A matching observation before the delay keeps waiting on the original candidate
+        PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::BSOL, 151 * PRICE_SCALE, None, new_round(), 1_059, &clock_at(1_059, 5_059)).unwrap();
+        assert_eq!(header.pending_prices[AssetType::BSOL.index()].observed_at, 1_000);
+
+        // At the delay an observation within 1% of the candidate commits the move
+        let committed = PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::BSOL, 1515 * PRICE_SCALE / 10, None, new_round(), 1_060, &clock_at(1_060, 5_060)).unwrap();
+        assert!(committed.updated);
+        assert!(!header.is_rejected(AssetType::BSOL));
+        assert_eq!(data.price_data[AssetType::BSOL.index()].price, 1515 * PRICE_SCALE / 10);
+        assert!(!header.pending_prices[AssetType::BSOL.index()].is_pending());
+        let pending = PendingPrice { price: 150 * PRICE_SCALE, observed_at: 1_000 };
+        assert!(PriceOracle::confirms_pending_move(&header, &pending, 1515 * PRICE_SCALE / 10));
+    }
+
+    #[test]
+    fn test_confirm_policy_discards_reverting_and_replaced_moves() {
+        let mut data = PriceOracleData::default();
+        data.price_data[AssetType::BSOL.index()].price = 100 * PRICE_SCALE;
+        let mut header = oracle_header();
+        PriceOracle::set_large_move_policy(&mut header, AssetType::BSOL, LargeMovePolicy::Confirm);
+        let delay = header.move_confirm_delay_secs() as i64;
+
+        PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::BSOL, 150 * PRICE_SCALE, None, new_round(), 1_000, &clock_at(1_000, 5_000)).unwrap();
+
+        // A different large move restarts the wait with the new candidate
+        PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::BSOL, 60 * PRICE_SCALE, None, new_round(), 1_000 + delay, &clock_at(1_000 + delay, 5_100)).unwrap();
+        assert_eq!(header.pending_prices[AssetType::BSOL.index()], PendingPrice { price: 60 * PRICE_SCALE, observed_at: 1_000 + delay });
+        assert_eq!(data.price_data[AssetType::BSOL.index()].price, 100 * PRICE_SCALE);
+
+        // An observation back within the limit is written and drops the candidate
+        let outcome = PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::BSOL, 102 * PRICE_SCALE, None, new_round(), 2_000, &clock_at(2_000, 5_200)).unwrap();
+        assert!(outcome.updated);
+        assert!(!header.pending_prices[AssetType::BSOL.index()].is_pending());
+        assert!(!PriceOracle::confirms_pending_move(&header, &PendingPrice { price: 60 * PRICE_SCALE, observed_at: 1_000 + delay }, 102 * PRICE_SCALE));
+
+        // Going back to halting drops the candidate, and the next large move trips the stop
+        PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::BSOL, 150 * PRICE_SCALE, None, new_round(), 2_010, &clock_at(2_010, 5_210)).unwrap();
+        assert_eq!(PriceOracle::set_large_move_policy(&mut header, AssetType::BSOL, LargeMovePolicy::Halt), LargeMovePolicy::Confirm);
+        assert!(!header.pending_prices[AssetType::BSOL.index()].is_pending());
+        let outcome = PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::BSOL, 150 * PRICE_SCALE, None, new_round(), 2_020, &clock_at(2_020, 5_220)).unwrap();
+        assert_eq!((outcome.updated, outcome.error_code), (false, u32::from(OracleError::PriceChangeExceedsLimit)));
+        assert!(header.emergency_stop);
+    }
+
+    #[test]
+    fn test_move_verdict_and_confirmation_bounds() {
+        let mut header = oracle_header();
+        let (old, far) = (100 * PRICE_SCALE, 150 * PRICE_SCALE);
+        assert_eq!(PriceOracle::move_verdict(&header, AssetType::SOL, old, 110 * PRICE_SCALE, 0), MoveVerdict::Accept);
+        assert_eq!(PriceOracle::move_verdict(&header, AssetType::SOL, old, far, 0), MoveVerdict::Halt);
+        PriceOracle::set_large_move_policy(&mut header, AssetType::SOL, LargeMovePolicy::Confirm);
+        assert_eq!(PriceOracle::move_verdict(&header, AssetType::SOL, old, far, 0), MoveVerdict::Hold);
+
+        // Partial mode holds rather than skips under the confirm policy
+        PriceOracle::set_partial_updates(&mut header, true);
+        let mut data = data_with_sol_price(old);
+        let outcome = PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::SOL, far, None, new_round(), 1_000, &clock_at(1_000, 5_000)).unwrap();
+        assert_eq!(outcome.error_code, u32::from(OracleError::PriceMovePending));
+        assert!(header.pending_prices[AssetType::SOL.index()].is_pending());
+
+        // Exactly the tolerance away still confirms, one unit more is a new candidate
+        let tolerance = header.move_confirm_tolerance_bps() as u64;
+        let edge = far + far * tolerance / 10_000;
+        let confirm_at = 1_000 + header.move_confirm_delay_secs() as i64;
+        assert_eq!(PriceOracle::move_verdict(&header, AssetType::SOL, old, edge, confirm_at), MoveVerdict::Accept);
+        assert_eq!(PriceOracle::move_verdict(&header, AssetType::SOL, old, edge + 1, confirm_at), MoveVerdict::Hold);
+        assert_eq!(PriceOracle::move_verdict(&header, AssetType::SOL, old, edge, confirm_at - 1), MoveVerdict::Wait);
+
+        assert!(PriceOracle::set_move_confirmation(&mut header, MAX_MOVE_CONFIRM_DELAY_SECS, MAX_MOVE_CONFIRM_TOLERANCE_BPS).is_ok());
+        let err = PriceOracle::set_move_confirmation(&mut header, MAX_MOVE_CONFIRM_DELAY_SECS + 1, 0).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::InvalidConfig));
+        let err = PriceOracle::set_move_confirmation(&mut header, 0, MAX_MOVE_CONFIRM_TOLERANCE_BPS + 1).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::InvalidConfig));
+        PriceOracle::set_move_confirmation(&mut header, 0, 0).unwrap();
+        assert_eq!(header.move_confirm_delay_secs(), DEFAULT_MOVE_CONFIRM_DELAY_SECS);
+        assert_eq!(header.move_confirm_tolerance_bps(), DEFAULT_MOVE_CONFIRM_TOLERANCE_BPS);
+    }
+
+    #[test]
+    fn test_resume_grace_widens_change_limit() {
+        let mut header = oracle_header();
+        PriceOracle::set_resume_grace(&mut header, 1_800, 20_000).unwrap();
+        let (old, far) = (100 * PRICE_SCALE, 135 * PRICE_SCALE);
+
+        // No stop was ever cleared, so there is no grace yet
+        assert_eq!(header.effective_price_change_limit_bps(1_000), PRICE_CHANGE_LIMIT_BPS);
+        assert_eq!(PriceOracle::move_verdict(&header, AssetType::SOL, old, far, 1_000), MoveVerdict::Halt);
+
+        PriceOracle::set_emergency_stop(&mut header, true, false, 1_000).unwrap();
+        PriceOracle::set_emergency_stop(&mut header, false, false, 2_000).unwrap();
+        let resumed_at = header.stop_clear_effective_at;
+        PriceOracle::set_emergency_stop(&mut header, false, false, resumed_at).unwrap();
+        assert_eq!((header.resumed_at, header.resume_grace_ends_at()), (resumed_at, Some(resumed_at + 1_800)));
+
+        // Just inside the window the limit is doubled, and a move it lets through is told apart
+        let inside = resumed_at + 1_799;
+        assert_eq!(header.effective_price_change_limit_bps(inside), 2 * PRICE_CHANGE_LIMIT_BPS);
+        assert_eq!(PriceOracle::move_verdict(&header, AssetType::SOL, old, far, inside), MoveVerdict::Accept);
+        assert_eq!(PriceOracle::move_verdict(&header, AssetType::SOL, old, 141 * PRICE_SCALE, inside), MoveVerdict::Halt);
+        assert!(PriceOracle::uses_resume_grace(&header, old, far, inside));
+        assert!(!PriceOracle::uses_resume_grace(&header, old, 110 * PRICE_SCALE, inside));
+        let mut data = data_with_sol_price(old);
+        let outcome = PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::SOL, far, None, new_round(), inside, &clock_at(inside, 9_000)).unwrap();
+        assert!(outcome.updated);
+        assert_eq!(data.price_data[AssetType::SOL.index()].price, far);
+
+        // From the end of the window the normal limit applies again
+        let outside = resumed_at + 1_800;
+        assert_eq!(header.effective_price_change_limit_bps(outside), PRICE_CHANGE_LIMIT_BPS);
+        assert_eq!(PriceOracle::move_verdict(&header, AssetType::SOL, old, far, outside), MoveVerdict::Halt);
+        assert!(!PriceOracle::uses_resume_grace(&header, old, far, outside));
+        let mut data = data_with_sol_price(old);
+        let outcome = PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::SOL, far, None, new_round(), outside, &clock_at(outside, 9_000)).unwrap();
+        assert_eq!(outcome.error_code, u32::from(OracleError::PriceChangeExceedsLimit));
+        assert_eq!(data.price_data[AssetType::SOL.index()].price, old);
+        header.emergency_stop = false;
+
+        // A first observation is accepted either way and owes nothing to the grace
+        for now in [inside, outside] {
+            assert_eq!(PriceOracle::move_verdict(&header, AssetType::SOL, 0, 10 * far, now), MoveVerdict::Accept);
+            assert!(!PriceOracle::uses_resume_grace(&header, 0, 10 * far, now));
+        }
+
+        let err = PriceOracle::set_resume_grace(&mut header, MAX_RESUME_GRACE_SECS + 1, 20_000).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::InvalidConfig));
+        let err = PriceOracle::set_resume_grace(&mut header, 1_800, MIN_RESUME_GRACE_MULTIPLIER_BPS - 1).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::InvalidConfig));
+        let err = PriceOracle::set_resume_grace(&mut header, 1_800, MAX_RESUME_GRACE_MULTIPLIER_BPS + 1).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::InvalidConfig));
+        // A zero period turns the grace off
+        PriceOracle::set_resume_grace(&mut header, 0, 0).unwrap();
+        assert_eq!(header.effective_price_change_limit_bps(inside), PRICE_CHANGE_LIMIT_BPS);
+    }
+
+    /// Runs `update_sol_price` from a round of `sol_feed(price, 0)` opened at `round`
+    fn run_sol_round(header: &mut PriceOracleHeader, data: &mut PriceOracleData, price: i128, round: u64) -> Result<Vec<AssetOutcome>> {
+        let clock = clock_at(1_000 + round as i64, 5_000 + round);
+        let mut feed_account = sol_feed(price, 0)
+            .round_open_timestamp(clock.unix_timestamp)
+            .round_open_slot(clock.slot)
+            .into_account(Pubkey::new_unique());
+        let feed_info = feed_account.account_info();
+        let feed = aggregator_loader(&feed_info).unwrap();
+        PriceOracle::update_sol_price(header, data, &feed, None, None, 0, &clock)
+    }
+
+    #[test]
+    fn test_expected_price_guards_sol_updates() {
+        let mut header = oracle_header();
+        let mut data = data_with_sol_price(100 * PRICE_SCALE);
+        data.price_data[AssetType::SOL.index()].quote = QuoteCurrency::USD;
+        let clock = clock_at(1_010, 5_010);
+        let mut feed_account = sol_feed(101, 0).round_open_timestamp(1_010).round_open_slot(5_010).into_account(Pubkey::new_unique());
+        let feed_info = feed_account.account_info();
+        let feed = aggregator_loader(&feed_info).unwrap();
+
+        // 101 is exactly 1% above an expected 100, so a 99 bps tolerance refuses it and
+        // nothing is written
+        for (expected_price, tolerance_bps) in [(Some(100 * PRICE_SCALE), 99), (Some(102 * PRICE_SCALE), 0), (Some(0), u16::MAX)] {
+            let err = PriceOracle::update_sol_price(&mut header, &mut data, &feed, None, expected_price, tolerance_bps, &clock).unwrap_err();
+            assert_eq!(error_code(&err), u32::from(OracleError::ExpectedPriceMismatch));
+            assert_eq!(data.price_data[AssetType::SOL.index()].price, 100 * PRICE_SCALE);
+        }
+
+        // At the tolerance the price is written, as it is without an expected price
+        PriceOracle::update_sol_price(&mut header, &mut data, &feed, None, Some(100 * PRICE_SCALE), 100, &clock).unwrap();
+        assert_eq!(data.price_data[AssetType::SOL.index()].price, 101 * PRICE_SCALE);
+        let mut data = data_with_sol_price(100 * PRICE_SCALE);
+        data.price_data[AssetType::SOL.index()].quote = QuoteCurrency::USD;
+        PriceOracle::update_sol_price(&mut oracle_header(), &mut data, &feed, None, None, 0, &clock).unwrap();
+        assert_eq!(data.price_data[AssetType::SOL.index()].price, 101 * PRICE_SCALE);
+
+        // Below the expected price is measured the same way
+        PriceOracle::check_expected_price(AssetType::SOL, 99 * PRICE_SCALE, Some(100 * PRICE_SCALE), 100).unwrap();
+        let err = PriceOracle::check_expected_price(AssetType::SOL, 99 * PRICE_SCALE, Some(100 * PRICE_SCALE), 99).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::ExpectedPriceMismatch));
+    }
+
+    #[test]
+    fn test_sol_price_from_a_secondary_chainlink_source() {
+        let mut header = oracle_header();
+        let mut data = data_with_sol_price(100 * PRICE_SCALE);
+        data.price_data[AssetType::SOL.index()].quote = QuoteCurrency::USD;
+        let clock = clock_at(1_010, 5_010);
+        let chainlink_key = Pubkey::new_unique();
+        let mut feed_account = MockChainlinkFeed::new(8).live_rows(1).round(0, 1, 1_005, 5_005, 10_100_000_000).live_cursor(1).into_account(chainlink_key);
+        let feed_info = feed_account.account_info();
+
+        // Only a feed registered among SOL's sources as a Chainlink one is read
+        let mut registry = AssetRegistry { queue: test_queue(), ..AssetRegistry::default() };
+        let unregistered = u32::from(OracleError::UnregisteredFeed);
+        let err = PriceOracle::update_sol_price_chainlink(&mut header, &mut data, &registry, &feed_info, None, 0, &clock).unwrap_err();
+        assert_eq!(error_code(&err), unregistered);
+        PriceOracle::set_asset_sources(&mut registry, AssetType::SOL, &[SourceSlot { kind: SourceKind::Switchboard, feed: chainlink_key }], 1).unwrap();
+        let err = PriceOracle::update_sol_price_chainlink(&mut header, &mut data, &registry, &feed_info, None, 0, &clock).unwrap_err();
+        assert_eq!(error_code(&err), unregistered);
+        let sources = [
+            SourceSlot { kind: SourceKind::Switchboard, feed: Pubkey::new_unique() },
+            SourceSlot { kind: SourceKind::Chainlink, feed: chainlink_key },
+        ];
+        PriceOracle::set_asset_sources(&mut registry, AssetType::SOL, &sources, 1).unwrap();
+
+        // The keeper's expected price guards it as it does `update_sol_price`
+        let err = PriceOracle::update_sol_price_chainlink(&mut header, &mut data, &registry, &feed_info, Some(100 * PRICE_SCALE), 99, &clock).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::ExpectedPriceMismatch));
+
+        // SOL stays sourced from Switchboard, while the stored provenance names the Chainlink feed
+        let outcomes = PriceOracle::update_sol_price_chainlink(&mut header, &mut data, &registry, &feed_info, Some(100 * PRICE_SCALE), 100, &clock).unwrap();
+        assert!(outcomes[0].updated);
+        assert_eq!(header.source(AssetType::SOL), SourceKind::Switchboard);
+        let sol = data.price_data[AssetType::SOL.index()];
+        assert_eq!((sol.price, sol.feed_timestamp, data.nonce), (101 * PRICE_SCALE, 1_005, 1));
+        assert_eq!(sol.provenance, PriceProvenance::from_feed(SourceKind::Chainlink, chainlink_key, 5_005));
+
+        // The same round is not written again
+        let err = PriceOracle::update_sol_price_chainlink(&mut header, &mut data, &registry, &feed_info, None, 0, &advance_clock(&clock, 10, 10)).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::NoNewRound));
+    }
+
+    #[test]
+    fn test_clamp_policy_walks_the_price_to_the_feed() {
+        let mut header = oracle_header();
+        let mut data = data_with_sol_price(100 * PRICE_SCALE);
+        PriceOracle::set_large_move_policy(&mut header, AssetType::SOL, LargeMovePolicy::Clamp);
+        assert_eq!(PriceOracle::move_verdict(&header, AssetType::SOL, 100 * PRICE_SCALE, 200 * PRICE_SCALE, 0), MoveVerdict::Clamp);
+
+        // Each round moves the stored price by the 20% limit toward the feed, and keeps serving
+        for (round, stored) in [(1, 120 * PRICE_SCALE), (2, 144 * PRICE_SCALE), (3, 1728 * PRICE_SCALE / 10)] {
+            let outcomes = run_sol_round(&mut header, &mut data, 200, round).unwrap();
+            assert!(outcomes[0].updated && outcomes[0].error_code == 0, "round {}", round);
+            let sol = &data.price_data[AssetType::SOL.index()];
+            assert_eq!(sol.price, stored, "round {}", round);
+            assert!(!exceeds_deviation_bps(sol.last_price, sol.price, header.price_change_limit_bps() as u64));
+            assert_eq!(header.clamped_prices[AssetType::SOL.index()], 200 * PRICE_SCALE);
+            assert!(header.is_clamped(AssetType::SOL) && !header.emergency_stop && !header.is_rejected(AssetType::SOL));
+            assert!(!header.pending_prices[AssetType::SOL.index()].is_pending());
+        }
+        assert_eq!((header.update_stats.successful_updates, data.nonce), (3, 3));
+
+        // Once the feed is within the limit its own value is stored and the clamp is cleared
+        run_sol_round(&mut header, &mut data, 200, 4).unwrap();
+        assert_eq!(data.price_data[AssetType::SOL.index()].price, 200 * PRICE_SCALE);
+        assert!(!header.is_clamped(AssetType::SOL));
+
+        // Downward moves are clamped the same way
+        run_sol_round(&mut header, &mut data, 100, 5).unwrap();
+        assert_eq!(data.price_data[AssetType::SOL.index()].price, 160 * PRICE_SCALE);
+        assert_eq!(header.clamped_prices[AssetType::SOL.index()], 100 * PRICE_SCALE);
+
+        // The step never overshoots the observation and saturates at the ends of the range
+        assert_eq!(clamp_to_limit(100, 1_000, 2_000), 120);
+        assert_eq!(clamp_to_limit(100, 0, 2_000), 80);
+        assert_eq!(clamp_to_limit(100, 110, 2_000), 110);
+        assert_eq!(clamp_to_limit(u64::MAX - 1, u64::MAX, 2_000), u64::MAX);
+        assert_eq!(clamp_to_limit(100, 0, 20_000), 0);
+    }
+
+    #[test]
+    fn test_halt_policy_is_unchanged_by_clamping() {
+        // The default policy still rejects the move and trips the stop, storing nothing
+        let mut header = oracle_header();
+        let mut data = data_with_sol_price(100 * PRICE_SCALE);
+        let outcomes = run_sol_round(&mut header, &mut data, 200, 1).unwrap();
+        assert_eq!(outcomes[0].error_code, u32::from(OracleError::PriceChangeExceedsLimit));
+        assert!(header.emergency_stop);
+        assert_eq!(data.price_data[AssetType::SOL.index()].price, 100 * PRICE_SCALE);
+        assert!(!header.is_clamped(AssetType::SOL));
+
+        // With partial updates the asset is skipped with its rejection code instead
+        let mut header = oracle_header();
+        PriceOracle::set_partial_updates(&mut header, true);
+        let outcome = PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::SOL, 200 * PRICE_SCALE, None, new_round(), 1_000, &clock_at(1_000, 5_000)).unwrap();
+        assert_eq!((outcome.updated, outcome.error_code), (false, u32::from(OracleError::PriceChangeExceedsLimit)));
+        assert!(!header.emergency_stop && !header.is_clamped(AssetType::SOL));
+        assert_eq!(data.price_data[AssetType::SOL.index()].price, 100 * PRICE_SCALE);
+
+        // Leaving Confirm for Clamp drops the held move, and a clamp leaves Halt assets alone
+        PriceOracle::set_large_move_policy(&mut header, AssetType::BSOL, LargeMovePolicy::Confirm);
+        header.pending_prices[AssetType::BSOL.index()] = PendingPrice { price: 150 * PRICE_SCALE, observed_at: 1_000 };
+        assert_eq!(PriceOracle::set_large_move_policy(&mut header, AssetType::BSOL, LargeMovePolicy::Clamp), LargeMovePolicy::Confirm);
+        assert!(!header.pending_prices[AssetType::BSOL.index()].is_pending());
+        assert_eq!(PriceOracle::move_verdict(&header, AssetType::SOL, 100 * PRICE_SCALE, 200 * PRICE_SCALE, 0), MoveVerdict::Halt);
+    }
+
+    #[test]
+    fn test_update_assets_rejects_stale_feed() {
+        // Round opened at 1_000; the default policy allows MAX_SWITCHBOARD_DATA_AGE seconds
+        let (result, _, _) = run_sol_update(oracle_header(), data_with_sol_price(0), sol_feed(15_610, 2), &clock_at(1_300, 5_020));
+        assert!(result.is_ok());
+        let (result, _, data) = run_sol_update(oracle_header(), data_with_sol_price(0), sol_feed(15_610, 2), &clock_at(1_301, 5_020));
+        assert_eq!(error_code(&result.unwrap_err()), u32::from(OracleError::StaleData));
+        assert_eq!(data.price_data[AssetType::SOL.index()].price, 0);
+
+        // Slot-based policy ignores a frozen timestamp
+        let header = PriceOracleHeader { max_feed_age_slots: 100, ..oracle_header() };
+        let (result, _, _) = run_sol_update(header.clone(), data_with_sol_price(0), sol_feed(15_610, 2), &clock_at(1_000, 5_100));
+        assert!(result.is_ok());
+        let (result, _, _) = run_sol_update(header, data_with_sol_price(0), sol_feed(15_610, 2), &clock_at(1_000, 5_101));
+        assert_eq!(error_code(&result.unwrap_err()), u32::from(OracleError::StaleData));
+    }
+
+    #[test]
+    fn test_per_asset_max_age() {
+        let mut header = oracle_header();
+        PriceOracle::set_asset_max_age(&mut header, AssetType::SOL, 120).unwrap();
+        PriceOracle::set_asset_max_age(&mut header, AssetType::MSOL, 1_800).unwrap();
+
+        // Both feeds' rounds opened at 1_000
+        let (result, _, data) = run_asset_update(AssetType::SOL, header.clone(), PriceOracleData::default(), sol_feed(15_610, 2), &clock_at(1_120, 5_020));
+        result.unwrap();
+        let (result, _, data) = run_asset_update(AssetType::MSOL, header.clone(), data, sol_feed(118, 2), &clock_at(1_120, 5_020));
+        result.unwrap();
+
+        // 200s later SOL's feed is too old for it while mSOL's is still fine
+        let clock = clock_at(1_200, 5_100);
+        let (result, _, _) = run_asset_update(AssetType::SOL, header.clone(), data.clone(), sol_feed(15_610, 2), &clock);
+        assert_eq!(error_code(&result.unwrap_err()), u32::from(OracleError::StaleData));
+        let (result, _, _) = run_asset_update(AssetType::MSOL, header.clone(), data.clone(), sol_feed(119, 2), &clock);
+        assert_eq!(result.unwrap(), vec![AssetType::MSOL]);
+
+        // The stored prices are judged by the same policy
+        let prices = PriceOracle::get_all_prices(&header, &data, &clock_at(1_300, 5_100));
+        let sol = prices[AssetType::SOL.index()];
+        let msol = prices[AssetType::MSOL.index()];
+        assert_eq!((sol.price, sol.max_age_secs, sol.stale), (156_100_000_000, 120, true));
+        assert_eq!((msol.price, msol.max_age_secs, msol.stale), (1_180_000_000, 1_800, false));
+        assert_eq!(msol.quote, QuoteCurrency::SOL);
+        // Never updated assets are always stale and report the default policy
+        assert!(prices[AssetType::BSOL.index()].stale);
+        assert_eq!(prices[AssetType::BSOL.index()].max_age_secs, MAX_SWITCHBOARD_DATA_AGE as u32);
+    }
+
+    #[test]
+    fn test_stale_feed_assets_follow_each_asset_tolerance() {
+        let mut header = oracle_header();
+        PriceOracle::set_asset_max_age(&mut header, AssetType::MSOL, 1_800).unwrap();
+        let mut data = PriceOracleData::default();
+        for asset_type in AssetType::MULTI_ASSET_FEED {
+            data.price_data[asset_type.index()].last_checked_time = 1_000;
+            data.price_data[asset_type.index()].feed_timestamp = 1_000;
+        }
+
+        // Within every tolerance nothing is due, and SOL is not the multi-asset feed's
+        let clock = clock_at(1_000 + MAX_SWITCHBOARD_DATA_AGE, 5_000);
+        assert!(PriceOracle::stale_feed_assets(&header, &data, &clock).is_empty());
+
+        // Past the default tolerance, every asset but mSOL is due
+        let clock = clock_at(1_001 + MAX_SWITCHBOARD_DATA_AGE, 5_000);
+        let stale = PriceOracle::stale_feed_assets(&header, &data, &clock);
+        assert_eq!(stale.len(), AssetType::MULTI_ASSET_FEED.len() - 1);
+        assert!(!stale.contains(&AssetType::MSOL));
+
+        // Assets the feed no longer writes never make a refresh due
+        let others: Vec<AssetType> = AssetType::MULTI_ASSET_FEED.into_iter().filter(|asset_type| *asset_type != AssetType::MSOL).collect();
+        for asset_type in &others {
+            header.paused[asset_type.index()] = true;
+        }
+        assert!(PriceOracle::stale_feed_assets(&header, &data, &clock).is_empty());
+        assert_eq!(PriceOracle::stale_feed_assets(&header, &data, &clock_at(2_801, 5_000)), vec![AssetType::MSOL]);
+        data.price_data[AssetType::MSOL.index()].delisted = true;
+        assert!(PriceOracle::stale_feed_assets(&header, &data, &clock_at(2_801, 5_000)).is_empty());
+    }
+
+    #[test]
+    fn test_update_assets_rejects_foreign_queue_and_emergency_stop() {
+        let clock = clock_at(1_010, 5_020);
+        let foreign = sol_feed(15_610, 2).queue(Pubkey::new_unique());
+        let (result, _, _) = run_sol_update(oracle_header(), data_with_sol_price(0), foreign, &clock);
+        assert_eq!(error_code(&result.unwrap_err()), u32::from(OracleError::InvalidSwitchboardAccount));
+
+        let stopped = PriceOracleHeader { emergency_stop: true, ..oracle_header() };
+        let (result, _, _) = run_sol_update(stopped, data_with_sol_price(0), sol_feed(15_610, 2), &clock);
+        assert_eq!(error_code(&result.unwrap_err()), u32::from(OracleError::EmergencyStop));
+    }
+
+    #[test]
+    fn test_updates_paused_and_emergency_stop_are_independent() {
+        let update_clock = clock_at(1_010, 5_020);
+        let read_clock = clock_at(1_100, 50);
+        let mut data = quoted_data();
+        data.price_data[AssetType::SOL.index()].last_update_time = 1_000;
+        data.price_data[AssetType::SOL.index()].last_checked_time = 1_000;
+        data.price_data[AssetType::SOL.index()].feed_timestamp = 1_000;
+
+        for (emergency_stop, updates_paused) in [(false, false), (false, true), (true, false), (true, true)] {
+            let mut header = oracle_header();
+            PriceOracle::set_updates_paused(&mut header, updates_paused);
+            header.emergency_stop = emergency_stop;
+            let case = format!("stop {}, paused {}", emergency_stop, updates_paused);
+
+            // Updates need both flags clear, and report the stop first
+            let (result, _, _) = run_sol_update(header.clone(), data_with_sol_price(0), sol_feed(15_610, 2), &update_clock);
+            let expected = if emergency_stop {
+                Some(OracleError::EmergencyStop)
+            } else if updates_paused {
+                Some(OracleError::UpdatesPaused)
+            } else {
+                None
+            };
+            match expected {
+                Some(code) => assert_eq!(error_code(&result.unwrap_err()), u32::from(code), "{}", case),
+                None => assert_eq!(result.unwrap(), vec![AssetType::SOL], "{}", case),
+            }
+
+            // Reads only refuse under the emergency stop
+            let fresh = PriceOracle::assert_price_fresh(&header, &data, AssetType::SOL, 300, false, &read_clock);
+            let value = PriceOracle::get_collateral_value(&header, &data, AssetType::SOL, PRICE_SCALE, &read_clock);
+            if emergency_stop {
+                assert_eq!(error_code(&fresh.unwrap_err()), u32::from(OracleError::EmergencyStop), "{}", case);
+                assert_eq!(error_code(&value.unwrap_err()), u32::from(OracleError::EmergencyStop), "{}", case);
+            } else {
+                assert!(fresh.is_ok(), "{}", case);
+                assert_eq!(value.unwrap(), 150 * PRICE_SCALE, "{}", case);
+            }
+        }
+    }
+
+    #[test]
+    fn test_update_prices_and_apys_paths() {
+        let clock = clock_at(1_010, 5_020);
+        let mut feed_account = sol_feed(15_610, 2).into_account(Pubkey::new_unique());
+        let feed_info = feed_account.account_info();
+        let feed = aggregator_loader(&feed_info).unwrap();
+
+        // Emergency stop aborts before the feed is read
+        let stopped = PriceOracleHeader { emergency_stop: true, ..oracle_header() };
+        let mut header_account = MockAccount::program_account(Pubkey::new_unique(), &stopped);
+        let mut data_account = MockAccount::program_account(Pubkey::new_unique(), &PriceOracleData::default());
+        let header_info = header_account.account_info();
+        let data_info = data_account.account_info();
+        let mut header = Account::<PriceOracleHeader>::try_from(&header_info).unwrap();
+        let mut data = Account::<PriceOracleData>::try_from(&data_info).unwrap();
+        let err = PriceOracle::update_prices_and_apys(&mut header, &mut data, &feed, &clock).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::EmergencyStop));
+
+        // A single value is not a multi-asset payload and leaves state untouched
+        header.emergency_stop = false;
+        let err = PriceOracle::update_prices_and_apys(&mut header, &mut data, &feed, &clock).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::InvalidSwitchboardData));
+        assert_eq!(header.last_global_update, 0);
+        assert!(data.price_data.iter().all(|price_data| price_data.price == 0));
+    }
+
+    #[test]
+    fn test_update_chunks_advance_the_global_update_once_every_asset_is_confirmed() {
+        for (start_index, count) in [(0, 0), (5, 2), (6, 1), (u8::MAX, u8::MAX)] {
+            let err = PriceOracle::multi_asset_feed_chunk(start_index, count).unwrap_err();
+            assert_eq!(error_code(&err), u32::from(OracleError::InvalidUpdateChunk), "{}..+{}", start_index, count);
+        }
+        assert_eq!(PriceOracle::multi_asset_feed_chunk(0, 6).unwrap(), 0..6);
+        assert_eq!(PriceOracle::multi_asset_feed_chunk(5, 1).unwrap(), 5..6);
+
+        let payload = MultiAssetResult { prices: [PRICE_SCALE; 6], apys: [0; 6] };
+        let run_chunk = |header: &mut PriceOracleHeader, data: &mut PriceOracleData, positions: Range<usize>, round: PriceProvenance, clock: &Clock| {
+            let outcomes = PriceOracle::apply_multi_asset_values(header, data, &payload, positions, round, clock.unix_timestamp, clock).unwrap();
+            PriceOracle::finish_feed_chunk_update(header, data, outcomes, clock)
+        };
+        let mut header = oracle_header();
+        let mut data = PriceOracleData::default();
+        let round = new_round();
+
+        // The first half writes its assets without vouching for the others
+        let outcomes = run_chunk(&mut header, &mut data, 0..3, round, &clock_at(1_000, 5_000)).unwrap();
+        assert_eq!(outcomes.iter().map(|outcome| outcome.asset_type).collect::<Vec<_>>(), AssetType::MULTI_ASSET_FEED[0..3].to_vec());
+        assert_eq!((header.last_global_update, header.update_stats.successful_updates), (0, 3));
+        assert_eq!(data.price_data[AssetType::MSOL.index()].last_update_time, 0);
+
+        // An overlapping chunk skips what the round already wrote, then every asset is confirmed
+        let outcomes = run_chunk(&mut header, &mut data, 2..6, round, &clock_at(1_010, 5_010)).unwrap();
+        assert_eq!(outcomes[0].error_code, u32::from(OracleError::NoNewRound));
+        assert_eq!((header.last_global_update, header.update_stats.successful_updates), (1_000, 6));
+        assert_eq!(data.nonce, 2);
+
+        // A chunk with nothing new fails and changes nothing
+        let err = run_chunk(&mut header, &mut data, 3..6, round, &clock_at(1_020, 5_020)).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::NoNewRound));
+
+        // The next round advances the stamp only to its oldest confirmation
+        let next_round = new_round();
+        run_chunk(&mut header, &mut data, 0..3, next_round, &clock_at(1_060, 5_060)).unwrap();
+        assert_eq!(header.last_global_update, 1_010);
+        assert_eq!(header.update_stats.longest_gap_secs, 10);
+
+        // Assets the feed no longer writes are not waited for
+        data.price_data[AssetType::HSOL.index()].delisted = true;
+        header.paused[AssetType::JitoSOL.index()] = true;
+        run_chunk(&mut header, &mut data, 3..4, next_round, &clock_at(1_070, 5_070)).unwrap();
+        assert_eq!(header.last_global_update, 1_060);
+    }
+
+    #[test]
+    fn test_check_lease_policy() {
+        let (feed_key, lease_key, escrow_key) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mut feed = sol_feed(15_610, 2).into_account(feed_key);
+        let feed_info = feed.account_info();
+        let feed = aggregator_loader(&feed_info).unwrap();
+        let mut lease = mock_lease(lease_key, feed_key, escrow_key);
+        let lease_info = lease.account_info();
+        let check = |header: &PriceOracleHeader, amount: u64| {
+            let mut escrow = mock_token_account(escrow_key, amount);
+            PriceOracle::check_lease(header, &feed, &lease_info, &escrow.account_info())
+        };
+
+        // Defaults neither warn nor reject, even on an empty lease
+        let mut header = oracle_header();
+        assert_eq!(check(&header, 0).unwrap(), None);
+
+        PriceOracle::set_lease_policy(&mut header, 1_000, true);
+        assert_eq!(check(&header, 1_000).unwrap(), None);
+        assert_eq!(check(&header, 999).unwrap(), Some(999));
+        assert_eq!(error_code(&check(&header, 0).unwrap_err()), u32::from(OracleError::LeaseExhausted));
+
+        PriceOracle::set_lease_policy(&mut header, 1_000, false);
+        assert_eq!(check(&header, 0).unwrap(), Some(0));
+    }
+
+    #[test]
+    fn test_check_lease_rejects_foreign_accounts() {
+        let header = oracle_header();
+        let (feed_key, lease_key, escrow_key) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mut feed = sol_feed(15_610, 2).into_account(feed_key);
+        let feed_info = feed.account_info();
+        let feed = aggregator_loader(&feed_info).unwrap();
+        let mut escrow = mock_token_account(escrow_key, 1_000);
+        let escrow_info = escrow.account_info();
+
+        let mut other_aggregator = mock_lease(lease_key, Pubkey::new_unique(), escrow_key);
+        let mut other_escrow = mock_lease(lease_key, feed_key, Pubkey::new_unique());
+        let mut other_owner = mock_lease(lease_key, feed_key, escrow_key);
+        other_owner.owner = Pubkey::new_unique();
+        // An aggregator account is not a lease even though the owner matches
+        let mut not_a_lease = sol_feed(15_610, 2).into_account(lease_key);
+        for lease in [&mut other_aggregator, &mut other_escrow, &mut other_owner, &mut not_a_lease] {
+            let err = PriceOracle::check_lease(&header, &feed, &lease.account_info(), &escrow_info).unwrap_err();
+            assert_eq!(error_code(&err), u32::from(OracleError::InvalidLeaseAccount));
+        }
+
+        // The escrow must be a token account
+        let mut lease = mock_lease(lease_key, feed_key, escrow_key);
+        let mut fake_escrow = mock_token_account(escrow_key, 1_000);
+        fake_escrow.owner = Pubkey::new_unique();
+        let err = PriceOracle::check_lease(&header, &feed, &lease.account_info(), &fake_escrow.account_info()).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::InvalidLeaseAccount));
+        assert_eq!(PriceOracle::check_lease(&header, &feed, &lease.account_info(), &escrow_info).unwrap(), None);
+    }
+
+    #[test]
+    fn test_update_assets_reads_the_configured_source() {
+        let chainlink_feed = || MockChainlinkFeed::new(8).live_rows(2).round(0, 3, 1_000, 5_000, 15_610_000_000).live_cursor(1);
+        let mut header = oracle_header();
+        PriceOracle::set_asset_source(&mut header, AssetType::SOL, SourceKind::Chainlink);
+
+        let feed = chainlink_feed().into_account(Pubkey::new_unique());
+        let (result, _, data) = run_update_with_feed_account(AssetType::SOL, header.clone(), PriceOracleData::default(), feed, &clock_at(1_030, 5_010));
+        assert_eq!(result.unwrap(), vec![AssetType::SOL]);
+        assert_eq!(data.price_data[AssetType::SOL.index()].price, 156_100_000_000);
+
+        // The header's staleness policy applies to Chainlink rounds too
+        let feed = chainlink_feed().into_account(Pubkey::new_unique());
+        let (result, _, _) = run_update_with_feed_account(AssetType::SOL, header.clone(), PriceOracleData::default(), feed, &clock_at(1_301, 5_010));
+        assert_eq!(error_code(&result.unwrap_err()), u32::from(OracleError::StaleData));
+
+        // A Switchboard aggregator registered for a Chainlink-sourced asset is refused, and vice versa
+        let (result, _, _) = run_sol_update(header.clone(), PriceOracleData::default(), sol_feed(15_610, 2), &clock_at(1_030, 5_010));
+        assert_eq!(error_code(&result.unwrap_err()), u32::from(OracleError::InvalidChainlinkAccount));
+        let feed = chainlink_feed().into_account(Pubkey::new_unique());
+        let (result, _, _) = run_update_with_feed_account(AssetType::SOL, oracle_header(), PriceOracleData::default(), feed, &clock_at(1_030, 5_010));
+        assert_eq!(error_code(&result.unwrap_err()), u32::from(OracleError::InvalidSwitchboardAccount));
+
+        // The fixed Switchboard instructions refuse assets sourced elsewhere
+        let err = header.require_source(&[AssetType::SOL], SourceKind::Switchboard).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::WrongSourceKind));
+        assert!(header.require_source(&AssetType::MULTI_ASSET_FEED, SourceKind::Switchboard).is_ok());
+    }
+
+    /// Runs `update_asset_from_sources` for SOL with `feeds` registered as its sources,
+    /// requiring `min_sources`, and returns the outcome with the resulting header and data
+    fn run_sources_update(
+        feeds: Vec<(SourceKind, MockAccount)>,
+        min_sources: u8,
+        data: PriceOracleData,
+        clock: &Clock,
+    ) -> (Result<SourceAggregation>, PriceOracleHeader, PriceOracleData) {
+        let supplied = feeds.len();
+        run_sources_update_with(feeds, min_sources, supplied, SourcePolicy::Median, data, clock)
+    }
+
+    /// `run_sources_update` passing only the first `supplied` of the registered feeds,
+    /// combined under `policy`
+    fn run_sources_update_with(
+        feeds: Vec<(SourceKind, MockAccount)>,
+        min_sources: u8,
+        supplied: usize,
+        policy: SourcePolicy,
+        data: PriceOracleData,
+        clock: &Clock,
+    ) -> (Result<SourceAggregation>, PriceOracleHeader, PriceOracleData) {
+        let mut registry = AssetRegistry { queue: test_queue(), ..AssetRegistry::default() };
+        let slots: Vec<SourceSlot> = feeds.iter().map(|(kind, account)| SourceSlot { kind: *kind, feed: account.key }).collect();
+        PriceOracle::set_asset_sources(&mut registry, AssetType::SOL, &slots, min_sources).unwrap();
+        let mut header = oracle_header();
+        PriceOracle::set_source_policy(&mut header, AssetType::SOL, policy);
+
+        let supplied_feeds = feeds.into_iter().take(supplied).map(|(_, account)| account).collect();
+        run_with_accounts(&header, &data, &registry, supplied_feeds, |header, data, registry, feeds| {
+            PriceOracle::update_asset_from_sources(header, data, registry, AssetType::SOL, feeds, clock)
+        })
+    }
+
+    fn switchboard_source(mantissa: i128) -> (SourceKind, MockAccount) {
+        (SourceKind::Switchboard, sol_feed(mantissa, 2).into_account(Pubkey::new_unique()))
+    }
+
+    fn chainlink_source(answer: i128, timestamp: u32) -> (SourceKind, MockAccount) {
+        let feed = MockChainlinkFeed::new(8).live_rows(1).round(0, 1, timestamp, 5_000, answer).live_cursor(1);
+        (SourceKind::Chainlink, feed.into_account(Pubkey::new_unique()))
+    }
+
+    #[test]
+    fn test_sources_all_agree() {
+        let feeds = vec![switchboard_source(15_610), switchboard_source(15_610), chainlink_source(15_610_000_000, 1_000)];
+        let (result, header, data) = run_sources_update(feeds, 3, PriceOracleData::default(), &clock_at(1_010, 5_020));
+
+        let aggregation = result.unwrap();
+        assert_eq!(aggregation.price, 156_100_000_000);
+        assert!(aggregation.readings.iter().all(|reading| reading.accepted && reading.price == 156_100_000_000));
+        assert_eq!(aggregation.readings[2].kind, SourceKind::Chainlink);
+        assert_eq!(data.price_data[AssetType::SOL.index()].price, 156_100_000_000);
+        let provenance = data.price_data[AssetType::SOL.index()].provenance;
+        assert_eq!(provenance.source_kind, PriceProvenance::MEDIAN);
+        assert_eq!(provenance.source_round_slot, 5_000);
+        assert_ne!(provenance.source_feed, Pubkey::default());
+        assert_eq!(header.update_stats, UpdateStats { successful_updates: 1, ..UpdateStats::default() });
+    }
+
+    #[test]
+    fn test_sources_median_ignores_one_outlier() {
+        let feeds = vec![switchboard_source(15_600), chainlink_source(99_900_000_000, 1_000), switchboard_source(15_620)];
+        let (result, header, data) = run_sources_update(feeds, 3, data_with_sol_price(156 * PRICE_SCALE), &clock_at(1_010, 5_020));
+
+        let aggregation = result.unwrap();
+        assert_eq!(aggregation.price, 156_200_000_000);
+        assert!(aggregation.readings.iter().all(|reading| reading.accepted));
+        assert_eq!(aggregation.readings[1].price, 999 * PRICE_SCALE);
+        assert_eq!(data.price_data[AssetType::SOL.index()].price, 156_200_000_000);
+        // An outlier is outvoted, not rejected
+        assert_eq!(header.update_stats.rejected_change_limit, 0);
+    }
+
+    #[test]
+    fn test_sources_discard_stale_source() {
+        // The Chainlink round is 310s old, past the 300s default
+        let feeds = vec![switchboard_source(15_600), switchboard_source(15_620), chainlink_source(15_000_000_000, 700)];
+        let (result, header, data) = run_sources_update(feeds, 2, PriceOracleData::default(), &clock_at(1_010, 5_020));
+
+        let aggregation = result.unwrap();
+        assert_eq!(aggregation.price, 156_100_000_000);
+        let stale = aggregation.readings[2];
+        assert!(!stale.accepted);
+        assert_eq!(stale.price, 0);
+        assert_eq!(stale.reason_code, u32::from(OracleError::StaleData));
+        assert_eq!(data.price_data[AssetType::SOL.index()].price, 156_100_000_000);
+        assert_eq!(header.update_stats.rejected_stale, 1);
+        assert_eq!(header.update_stats.successful_updates, 1);
+    }
+
+    #[test]
+    fn test_sources_below_minimum_count_are_rejected() {
+        let mut invalid_owner = switchboard_source(15_610);
+        invalid_owner.1.owner = Pubkey::new_unique();
+        let feeds = vec![switchboard_source(15_600), invalid_owner, chainlink_source(15_000_000_000, 700)];
+        let (result, header, data) = run_sources_update(feeds, 2, PriceOracleData::default(), &clock_at(1_010, 5_020));
+
+        assert_eq!(error_code(&result.unwrap_err()), u32::from(OracleError::InsufficientSources));
+        assert_eq!(data.price_data[AssetType::SOL.index()].price, 0);
+        assert_eq!(header.update_stats.successful_updates, 0);
+
+        // Unsupplied sources count as missing
+        let feeds = vec![switchboard_source(15_600), switchboard_source(15_610)];
+        let (result, _, _) = run_sources_update_with(feeds, 2, 1, SourcePolicy::Median, PriceOracleData::default(), &clock_at(1_010, 5_020));
+        assert_eq!(error_code(&result.unwrap_err()), u32::from(OracleError::InsufficientSources));
+    }
+
+    /// `run_sources_update` under `SourcePolicy::Priority`, passing every feed
+    fn run_priority_update(feeds: Vec<(SourceKind, MockAccount)>, clock: &Clock) -> (Result<SourceAggregation>, PriceOracleHeader, PriceOracleData) {
+        let supplied = feeds.len();
+        run_sources_update_with(feeds, 1, supplied, SourcePolicy::Priority, PriceOracleData::default(), clock)
+    }
+
+    #[test]
+    fn test_priority_serves_the_primary_when_acceptable() {
+        let feeds = vec![switchboard_source(15_610), chainlink_source(15_000_000_000, 1_000), switchboard_source(15_620)];
+        let primary = feeds[0].1.key;
+        let (result, header, data) = run_priority_update(feeds, &clock_at(1_010, 5_020));
+
+        let aggregation = result.unwrap();
+        assert_eq!(aggregation.price, 156_100_000_000);
+        assert_eq!(aggregation.priority_level, Some(0));
+        // The fallbacks are not read once the primary serves
+        assert_eq!(aggregation.readings.len(), 1);
+        let price_data = data.price_data[AssetType::SOL.index()];
+        assert_eq!(price_data.price, 156_100_000_000);
+        assert_eq!(price_data.provenance.source_feed, primary);
+        assert_eq!(price_data.provenance.source_kind, PriceProvenance::priority(0));
+        assert!(!header.last_error(AssetType::SOL).is_set());
+    }
+
+    #[test]
+    fn test_priority_fails_over_from_a_stale_primary() {
+        // The Chainlink primary is 310s old, past the 300s default
+        let feeds = vec![chainlink_source(15_000_000_000, 700), switchboard_source(15_620), switchboard_source(15_600)];
+        let secondary = feeds[1].1.key;
+        let (result, header, data) = run_priority_update(feeds, &clock_at(1_010, 5_020));
+
+        let aggregation = result.unwrap();
+        assert_eq!(aggregation.price, 156_200_000_000);
+        assert_eq!(aggregation.priority_level, Some(1));
+        assert_eq!(aggregation.readings.len(), 2);
+        assert!(!aggregation.readings[0].accepted);
+        assert_eq!(aggregation.readings[0].reason_code, u32::from(OracleError::StaleData));
+        assert!(aggregation.readings[1].accepted);
+
+        let provenance = data.price_data[AssetType::SOL.index()].provenance;
+        assert_eq!(provenance.source_feed, secondary);
+        assert_eq!(provenance.priority_level(), Some(1));
+        assert_eq!(header.update_stats.rejected_stale, 1);
+        assert_eq!(header.update_stats.successful_updates, 1);
+        // The primary's failure stays visible while the fallback serves
+        assert_eq!(header.last_error(AssetType::SOL).code, u32::from(OracleError::StaleData));
+    }
+
+    #[test]
+    fn test_priority_without_an_acceptable_source_is_rejected() {
+        let mut invalid_owner = switchboard_source(15_610);
+        invalid_owner.1.owner = Pubkey::new_unique();
+        let feeds = vec![chainlink_source(15_000_000_000, 700), invalid_owner];
+        let (result, header, data) = run_priority_update(feeds, &clock_at(1_010, 5_020));
+        assert_eq!(error_code(&result.unwrap_err()), u32::from(OracleError::InsufficientSources));
+        assert_eq!(data.price_data[AssetType::SOL.index()].price, 0);
+        assert_eq!(header.update_stats.successful_updates, 0);
+
+        // A fallback that is needed must be supplied
+        let feeds = vec![chainlink_source(15_000_000_000, 700), switchboard_source(15_620)];
+        let (result, _, _) = run_sources_update_with(feeds, 1, 1, SourcePolicy::Priority, PriceOracleData::default(), &clock_at(1_010, 5_020));
+        assert_eq!(error_code(&result.unwrap_err()), u32::from(OracleError::InsufficientSources));
+    }
+
+    #[test]
+    fn test_priority_provenance_encoding() {
+        let provenance = |source_kind| PriceProvenance { source_kind, ..PriceProvenance::default() };
+        for level in 0..MAX_ASSET_SOURCES as u8 {
+            assert_eq!(provenance(PriceProvenance::priority(level)).priority_level(), Some(level));
+        }
+        for source_kind in [
+            PriceProvenance::MEDIAN,
+            PriceProvenance::SIGNED_PUBLISHER,
+            PriceProvenance::FORCED,
+            PriceProvenance::priority(MAX_ASSET_SOURCES as u8),
+            u8::from(SourceKind::Switchboard),
+            u8::from(SourceKind::Chainlink),
+        ] {
+            assert_eq!(provenance(source_kind).priority_level(), None);
+        }
+
+        let mut header = oracle_header();
+        assert_eq!(header.source_policy(AssetType::MSOL), SourcePolicy::Median);
+        assert_eq!(PriceOracle::set_source_policy(&mut header, AssetType::MSOL, SourcePolicy::Priority), SourcePolicy::Median);
+        assert_eq!(header.source_policy(AssetType::MSOL), SourcePolicy::Priority);
+        assert_eq!(header.source_policy(AssetType::SOL), SourcePolicy::Median);
+    }
+
+    #[test]
+    fn test_set_asset_sources_validation() {
+        let mut registry = AssetRegistry::default();
+        let slot = |kind| SourceSlot { kind, feed: Pubkey::new_unique() };
+        let slots = [slot(SourceKind::Switchboard), slot(SourceKind::Chainlink), slot(SourceKind::Switchboard)];
+
+        PriceOracle::set_asset_sources(&mut registry, AssetType::MSOL, &slots, 2).unwrap();
+        let config = registry.assets[AssetType::MSOL.index()];
+        assert_eq!(config.sources, slots);
+        assert_eq!(config.min_sources, 2);
+        assert_eq!(config.source_for_feed(&slots[1].feed), Some(slots[1]));
+
+        let invalid: [(&[SourceSlot], u8); 6] = [
+            (&[slots[0], slots[1], slots[2], slot(SourceKind::Chainlink)], 1),
+            (&[slots[0], slots[0]], 1),
+            (&[SourceSlot::default()], 1),
+            (&slots[..2], 0),
+            (&slots[..2], 3),
+            (&[], 1),
+        ];
+        for (sources, min_sources) in invalid {
+            let err = PriceOracle::set_asset_sources(&mut registry, AssetType::MSOL, sources, min_sources).unwrap_err();
+            assert_eq!(error_code(&err), u32::from(OracleError::InvalidConfig));
+        }
+        assert_eq!(registry.assets[AssetType::MSOL.index()].sources, slots);
+
+        // Shrinking clears the unused slots, which never match
+        PriceOracle::set_asset_sources(&mut registry, AssetType::MSOL, &slots[..1], 1).unwrap();
+        let config = registry.assets[AssetType::MSOL.index()];
+        assert_eq!(config.sources[1..], [SourceSlot::default(); 2]);
+        assert_eq!(config.source_for_feed(&slots[2].feed), None);
+        assert_eq!(config.source_for_feed(&Pubkey::default()), None);
+        PriceOracle::set_asset_sources(&mut registry, AssetType::MSOL, &[], 0).unwrap();
+        assert_eq!(registry.assets[AssetType::MSOL.index()].min_sources, 0);
+    }
+
+    /// Registers `feed` as the SOL feed and `fallback` as its fallback source, then runs
+    /// `update_assets` with the feed followed by `extra` accounts
+    fn run_fallback_update(
+        feed: MockAggregator,
+        fallback: SourceSlot,
+        extra: Vec<MockAccount>,
+        clock: &Clock,
+    ) -> (Result<UpdatedAssets>, PriceOracleData) {
+        let feed_account = feed.into_account(Pubkey::new_unique());
+        let mut registry = AssetRegistry { queue: test_queue(), ..AssetRegistry::default() };
+        registry.assets[AssetType::SOL.index()].feed = feed_account.key;
+        PriceOracle::set_asset_fallback(&mut registry, AssetType::SOL, Some(fallback)).unwrap();
+
+        let accounts = std::iter::once(feed_account).chain(extra).collect();
+        let (result, _, data) = run_with_accounts(&oracle_header(), &PriceOracleData::default(), &registry, accounts, |header, data, registry, accounts| {
+            PriceOracle::update_assets(header, data, registry, accounts, clock)
+        });
+        (result, data)
+    }
+
+    #[test]
+    fn test_stale_feed_fails_over_to_fallback_source() {
+        let stale_at = 1_000 + MAX_SWITCHBOARD_DATA_AGE + 1;
+        let pyth_key = Pubkey::new_unique();
+        let fallback = SourceSlot { kind: SourceKind::Pyth, feed: pyth_key };
+        let pyth = |publish_time: i64| MockPythPriceUpdate::new(15_750_000_000, -8, publish_time, 9_000).feed_id(PYTH_SOL_USD_FEED_ID).into_account(pyth_key);
+        let sol = |data: &PriceOracleData| data.price_data[AssetType::SOL.index()];
+
+        // A fresh feed serves; the fallback is not read
+        let (result, data) = run_fallback_update(sol_feed(15_610, 2), fallback, vec![pyth(1_000)], &clock_at(1_010, 5_020));
+        assert_eq!(result.unwrap().failed_over, vec![]);
+        assert_eq!(sol(&data).price, 156_100_000_000);
+        assert_eq!(sol(&data).provenance.source_kind, u8::from(SourceKind::Switchboard));
+
+        // A stale one gives way to the fallback, recorded as the price's source
+        let (result, data) = run_fallback_update(sol_feed(15_610, 2), fallback, vec![pyth(stale_at - 5)], &clock_at(stale_at, 9_010));
+        let updated = result.unwrap();
+        assert_eq!((updated.updated, updated.failed_over), (vec![AssetType::SOL], vec![(AssetType::SOL, fallback)]));
+        assert_eq!((sol(&data).price, sol(&data).feed_timestamp), (157_500_000_000, stale_at - 5));
+        assert_eq!(sol(&data).provenance, PriceProvenance::from_feed(SourceKind::Pyth, pyth_key, 9_000));
+
+        // Without the fallback, or with a stale one, the update fails as before
+        let (result, _) = run_fallback_update(sol_feed(15_610, 2), fallback, vec![], &clock_at(stale_at, 9_010));
+        assert_eq!(error_code(&result.unwrap_err()), u32::from(OracleError::StaleData));
+        let (result, data) = run_fallback_update(sol_feed(15_610, 2), fallback, vec![pyth(1_000)], &clock_at(stale_at, 9_010));
+        assert_eq!(error_code(&result.unwrap_err()), u32::from(OracleError::StaleData));
+        assert_eq!(sol(&data).price, 0);
+
+        // Only staleness fails over; a feed failing otherwise fails the update
+        let (result, _) = run_fallback_update(sol_feed(15_610, 2).queue(Pubkey::new_unique()), fallback, vec![pyth(1_000)], &clock_at(1_010, 5_020));
+        assert_eq!(error_code(&result.unwrap_err()), u32::from(OracleError::InvalidSwitchboardAccount));
+
+        // The fallback may be passed only once
+        let (result, _) = run_fallback_update(sol_feed(15_610, 2), fallback, vec![pyth(1_000), pyth(1_000)], &clock_at(1_010, 5_020));
+        assert_eq!(error_code(&result.unwrap_err()), u32::from(OracleError::DuplicateFeed));
+    }
+
+    #[test]
+    fn test_set_asset_fallback() {
+        let mut registry = AssetRegistry::default();
+        let sol_feed = Pubkey::new_unique();
+        registry.assets[AssetType::SOL.index()].feed = sol_feed;
+        let pool = Pubkey::new_unique();
+        PriceOracle::set_asset_stake_pool(&mut registry, AssetType::JitoSOL, pool, PoolKind::Spl, 0).unwrap();
+        let fallback = SourceSlot { kind: SourceKind::Chainlink, feed: Pubkey::new_unique() };
+
+        assert_eq!(PriceOracle::set_asset_fallback(&mut registry, AssetType::SOL, Some(fallback)).unwrap(), None);
+        assert_eq!(registry.assets[AssetType::SOL.index()].fallback(), Some(fallback));
+        assert_eq!(registry.asset_for_fallback(&fallback.feed), Some(AssetType::SOL));
+
+        // The fallback must be set and not registered as anything else
+        let err = PriceOracle::set_asset_fallback(&mut registry, AssetType::SOL, Some(SourceSlot::default())).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::InvalidConfig));
+        for taken in [sol_feed, pool, fallback.feed] {
+            let err = PriceOracle::set_asset_fallback(&mut registry, AssetType::MSOL, Some(SourceSlot { kind: SourceKind::Pyth, feed: taken })).unwrap_err();
+            assert_eq!(error_code(&err), u32::from(OracleError::DuplicateFeed));
+        }
+        let mut registry_account = MockAccount::program_account(Pubkey::new_unique(), &registry);
+        let registry_info = registry_account.account_info();
+        let mut registry_handle = Account::<AssetRegistry>::try_from(&registry_info).unwrap();
+        let err = PriceOracle::set_asset_feed(&mut registry_handle, AssetType::MSOL, fallback.feed).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::DuplicateFeed));
+
+        // Clearing returns the replaced fallback
+        assert_eq!(PriceOracle::set_asset_fallback(&mut registry, AssetType::SOL, None).unwrap(), Some(fallback));
+        assert_eq!(registry.assets[AssetType::SOL.index()].fallback(), None);
+        assert_eq!(registry.asset_for_fallback(&Pubkey::default()), None);
+    }
+
+    #[test]
+    fn test_asset_fallback_applies_after_the_timelock() {
+        let mut header = PriceOracleHeader::default();
+        let mut registry = AssetRegistry::default();
+        let fallback = SourceSlot { kind: SourceKind::Pyth, feed: Pubkey::new_unique() };
+        let err = PriceOracle::cancel_asset_fallback(&mut header, AssetType::SOL).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::NoPendingConfig));
+
+        let effective_at = PriceOracle::propose_asset_fallback(&mut header, &registry, AssetType::SOL, Some(fallback), 1_000).unwrap();
+        assert_eq!(effective_at, 1_000 + DEFAULT_CONFIG_TIMELOCK_SECS as i64);
+        let err = PriceOracle::propose_asset_fallback(&mut header, &registry, AssetType::SOL, None, 1_001).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::ConfigChangePending));
+        let err = PriceOracle::apply_asset_fallback(&mut header, &mut registry, AssetType::SOL, effective_at - 1).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::TimelockNotElapsed));
+        assert_eq!(registry.assets[AssetType::SOL.index()].fallback(), None);
+
+        // Each asset has its own proposal, checked as `set_asset_fallback` checks it
+        let err = PriceOracle::propose_asset_fallback(&mut header, &registry, AssetType::MSOL, Some(SourceSlot::default()), 1_001).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::InvalidConfig));
+        assert!(!header.pending_fallbacks[AssetType::MSOL.index()].is_pending());
+
+        assert_eq!(PriceOracle::apply_asset_fallback(&mut header, &mut registry, AssetType::SOL, effective_at).unwrap(), (None, Some(fallback)));
+        assert_eq!(registry.assets[AssetType::SOL.index()].fallback(), Some(fallback));
+        let err = PriceOracle::apply_asset_fallback(&mut header, &mut registry, AssetType::SOL, effective_at).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::NoPendingConfig));
+
+        // A feed registered since the proposal is refused when it is applied
+        let taken = SourceSlot { kind: SourceKind::Switchboard, feed: Pubkey::new_unique() };
+        let effective_at = PriceOracle::propose_asset_fallback(&mut header, &registry, AssetType::MSOL, Some(taken), 2_000).unwrap();
+        registry.assets[AssetType::JitoSOL.index()].feed = taken.feed;
+        let err = PriceOracle::apply_asset_fallback(&mut header, &mut registry, AssetType::MSOL, effective_at).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::DuplicateFeed));
+        assert_eq!(PriceOracle::cancel_asset_fallback(&mut header, AssetType::MSOL).unwrap(), Some(taken));
+
+        // Clearing goes through the timelock too, and a locked oracle keeps its fallback
+        let effective_at = PriceOracle::propose_asset_fallback(&mut header, &registry, AssetType::SOL, None, 3_000).unwrap();
+        assert_eq!(header.pending_fallbacks[AssetType::SOL.index()].fallback(), None);
+        header.locked = true;
+        let err = PriceOracle::apply_asset_fallback(&mut header, &mut registry, AssetType::SOL, effective_at).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::OracleLocked));
+        assert_eq!(registry.assets[AssetType::SOL.index()].fallback(), Some(fallback));
+    }
+
+    /// Registers `feed` as the asset's feed and sole source and `pool` (key, kind and
+    /// tolerance) as its stake pool, then runs `update_assets` (or `update_asset_from_sources`
+    /// when `from_sources` is set) with the feed followed by `extra` accounts
+    fn run_stake_pool_update(
+        data: PriceOracleData,
+        asset_type: AssetType,
+        feed: MockAggregator,
+        pool: (Pubkey, PoolKind, u32),
+        extra: Vec<MockAccount>,
+        from_sources: bool,
+    ) -> (Result<()>, PriceOracleData) {
+        let (result, data, _, _) = run_stake_pool_update_with_header(data, asset_type, feed, pool, extra, from_sources);
+        (result, data)
+    }
+
+    /// `run_stake_pool_update`, also returning the header and registry as the update left them
+    fn run_stake_pool_update_with_header(
+        data: PriceOracleData,
+        asset_type: AssetType,
+        feed: MockAggregator,
+        pool: (Pubkey, PoolKind, u32),
+        extra: Vec<MockAccount>,
+        from_sources: bool,
+    ) -> (Result<()>, PriceOracleData, PriceOracleHeader, AssetRegistry) {
+        let feed_account = feed.into_account(Pubkey::new_unique());
+        let mut registry = AssetRegistry { queue: test_queue(), ..AssetRegistry::default() };
+        registry.assets[asset_type.index()].feed = feed_account.key;
+        let slot = SourceSlot { kind: SourceKind::Switchboard, feed: feed_account.key };
+        PriceOracle::set_asset_sources(&mut registry, asset_type, &[slot], 1).unwrap();
+        let (stake_pool, kind, tolerance_bps) = pool;
+        PriceOracle::set_asset_stake_pool(&mut registry, asset_type, stake_pool, kind, tolerance_bps).unwrap();
+
+        let accounts = std::iter::once(feed_account).chain(extra).collect();
+        let clock = clock_at(1_010, 5_020);
+        let (result, header, data) = run_with_accounts(&oracle_header(), &data, &registry, accounts, |header, data, registry, accounts| {
+            if from_sources {
+                PriceOracle::update_asset_from_sources(header, data, registry, asset_type, accounts, &clock).map(|_| ())
+            } else {
+                PriceOracle::update_assets(header, data, registry, accounts, &clock).map(|_| ())
+            }
+        });
+        (result, data, header, registry)
+    }
+
+    #[test]
+    fn test_stake_pool_rate_bounds_lst_prices() {
+        // 1.15 SOL per JitoSOL
+        let pool_key = Pubkey::new_unique();
+        let pool = || MockStakePool::new(1_150_000_000_000, 1_000_000_000_000).into_account(pool_key);
+        let spl = (pool_key, PoolKind::Spl, 0);
+        let jitosol = |data: &PriceOracleData| data.price_data[AssetType::JitoSOL.index()].price;
+
+        for from_sources in [false, true] {
+            // Within and exactly at the default 5% the price is stored
+            let (result, data) = run_stake_pool_update(PriceOracleData::default(), AssetType::JitoSOL, sol_feed(116, 2), spl, vec![pool()], from_sources);
+            result.unwrap();
+            assert_eq!(jitosol(&data), 1_160_000_000);
+            let (result, _) = run_stake_pool_update(PriceOracleData::default(), AssetType::JitoSOL, sol_feed(12_075, 4), spl, vec![pool()], from_sources);
+            result.unwrap();
+
+            // Past it the update fails and nothing is written
+            let (result, data) = run_stake_pool_update(PriceOracleData::default(), AssetType::JitoSOL, sol_feed(121, 2), spl, vec![pool()], from_sources);
+            assert_eq!(error_code(&result.unwrap_err()), u32::from(OracleError::PriceDeviatesFromNav));
+            assert_eq!(jitosol(&data), 0);
+            let (result, _) = run_stake_pool_update(PriceOracleData::default(), AssetType::JitoSOL, sol_feed(117, 2), (pool_key, PoolKind::Spl, 100), vec![pool()], from_sources);
+            assert_eq!(error_code(&result.unwrap_err()), u32::from(OracleError::PriceDeviatesFromNav));
+
+            // Without the pool account the check is skipped
+            let (result, data) = run_stake_pool_update(PriceOracleData::default(), AssetType::JitoSOL, sol_feed(121, 2), spl, vec![], from_sources);
+            result.unwrap();
+            assert_eq!(jitosol(&data), 1_210_000_000);
+
+            // The pool may be passed only once, and must be owned by the stake pool program
+            let (result, _) = run_stake_pool_update(PriceOracleData::default(), AssetType::JitoSOL, sol_feed(116, 2), spl, vec![pool(), pool()], from_sources);
+            assert_eq!(error_code(&result.unwrap_err()), u32::from(OracleError::DuplicateFeed));
+            let mut foreign = pool();
+            foreign.owner = Pubkey::new_unique();
+            let (result, _) = run_stake_pool_update(PriceOracleData::default(), AssetType::JitoSOL, sol_feed(116, 2), spl, vec![foreign], from_sources);
+            assert_eq!(error_code(&result.unwrap_err()), u32::from(OracleError::InvalidStakePoolAccount));
+        }
+
+        // A USD-quoted price is compared with the rate converted at the SOL price
+        let mut data = data_with_sol_price(100 * PRICE_SCALE);
+        data.price_data[AssetType::SOL.index()].quote = QuoteCurrency::USD;
+        data.price_data[AssetType::JitoSOL.index()].quote = QuoteCurrency::USD;
+        let (result, after) = run_stake_pool_update(data.clone(), AssetType::JitoSOL, sol_feed(118, 0), spl, vec![pool()], false);
+        result.unwrap();
+        assert_eq!(jitosol(&after), 118 * PRICE_SCALE);
+        let (result, _) = run_stake_pool_update(data.clone(), AssetType::JitoSOL, sol_feed(125, 0), spl, vec![pool()], false);
+        assert_eq!(error_code(&result.unwrap_err()), u32::from(OracleError::PriceDeviatesFromNav));
+        data.price_data[AssetType::SOL.index()].price = 0;
+        let (result, _) = run_stake_pool_update(data, AssetType::JitoSOL, sol_feed(118, 0), spl, vec![pool()], false);
+        assert_eq!(error_code(&result.unwrap_err()), u32::from(OracleError::PriceNotAvailable));
+    }
+
+    #[test]
+    fn test_marinade_state_bounds_msol_price() {
+        // 1.25 SOL per mSOL
+        let state_key = Pubkey::new_unique();
+        let state = || MockMarinadeState::new(5 * MSOL_PRICE_DENOMINATOR / 4).into_account(state_key);
+        let marinade = (state_key, PoolKind::Marinade, 0);
+        let msol = |data: &PriceOracleData| data.price_data[AssetType::MSOL.index()].price;
+
+        for from_sources in [false, true] {
+            let (result, data) = run_stake_pool_update(PriceOracleData::default(), AssetType::MSOL, sol_feed(127, 2), marinade, vec![state()], from_sources);
+            result.unwrap();
+            assert_eq!(msol(&data), 1_270_000_000);
+
+            let (result, data) = run_stake_pool_update(PriceOracleData::default(), AssetType::MSOL, sol_feed(118, 2), marinade, vec![state()], from_sources);
+            assert_eq!(error_code(&result.unwrap_err()), u32::from(OracleError::PriceDeviatesFromNav));
+            assert_eq!(msol(&data), 0);
+
+            // The state is read as Marinade's, never as an SPL pool, and the other way round
+            let spl_pool = MockStakePool::new(1_250, 1_000).into_account(state_key);
+            let (result, _) = run_stake_pool_update(PriceOracleData::default(), AssetType::MSOL, sol_feed(127, 2), marinade, vec![spl_pool], from_sources);
+            assert_eq!(error_code(&result.unwrap_err()), u32::from(OracleError::InvalidMarinadeState));
+            let (result, _) = run_stake_pool_update(PriceOracleData::default(), AssetType::MSOL, sol_feed(127, 2), (state_key, PoolKind::Spl, 0), vec![state()], from_sources);
+            assert_eq!(error_code(&result.unwrap_err()), u32::from(OracleError::InvalidStakePoolAccount));
+        }
+    }
+
+    #[test]
+    fn test_premium_over_stake_pool_rate() {
+        // 1.15 SOL per JitoSOL
+        let pool_key = Pubkey::new_unique();
+        let pool = || MockStakePool::new(1_150_000_000_000, 1_000_000_000_000).into_account(pool_key);
+        let spl = (pool_key, PoolKind::Spl, 0);
+        let premium = |feed, extra, from_sources| {
+            let (result, _, header, registry) = run_stake_pool_update_with_header(PriceOracleData::default(), AssetType::JitoSOL, feed, spl, extra, from_sources);
+            result.unwrap();
+            PriceOracle::get_premium(&header, &registry, AssetType::JitoSOL)
+        };
+
+        for from_sources in [false, true] {
+            // 1.16 / 1.15 - 1 = 0.869565...%, truncated to 86 bps
+            let expected = LstPremium { premium_bps: 86, feed_price: 1_160_000_000, intrinsic_rate: 1_150_000_000, time: 1_010 };
+            assert_eq!(premium(sol_feed(116, 2), vec![pool()], from_sources).unwrap(), expected);
+            // 1.2075 / 1.15 - 1 = 5% exactly
+            assert_eq!(premium(sol_feed(12_075, 4), vec![pool()], from_sources).unwrap().premium_bps, 500);
+            // 1.10 / 1.15 - 1 = -4.347826...%, truncated toward zero to -434 bps
+            assert_eq!(premium(sol_feed(110, 2), vec![pool()], from_sources).unwrap().premium_bps, -434);
+            assert_eq!(premium(sol_feed(115, 2), vec![pool()], from_sources).unwrap().premium_bps, 0);
+
+            // An update without the pool computes none
+            let err = premium(sol_feed(116, 2), vec![], from_sources).unwrap_err();
+            assert_eq!(error_code(&err), u32::from(OracleError::DataNotAvailable));
+        }
+
+        // A USD-quoted price is compared with the rate at the SOL price: 118 / 115 - 1 is
+        // 2.608695...%, truncated to 260 bps
+        let mut data = data_with_sol_price(100 * PRICE_SCALE);
+        data.price_data[AssetType::SOL.index()].quote = QuoteCurrency::USD;
+        data.price_data[AssetType::JitoSOL.index()].quote = QuoteCurrency::USD;
+        let (result, _, header, registry) = run_stake_pool_update_with_header(data, AssetType::JitoSOL, sol_feed(118, 0), spl, vec![pool()], false);
+        result.unwrap();
+        let expected = LstPremium { premium_bps: 260, feed_price: 118 * PRICE_SCALE, intrinsic_rate: 115 * PRICE_SCALE, time: 1_010 };
+        assert_eq!(PriceOracle::get_premium(&header, &registry, AssetType::JitoSOL).unwrap(), expected);
+
+        // Marinade's rate works the same: 1.27 / 1.25 - 1 = 1.6%
+        let state_key = Pubkey::new_unique();
+        let state = MockMarinadeState::new(5 * MSOL_PRICE_DENOMINATOR / 4).into_account(state_key);
+        let (result, _, header, registry) = run_stake_pool_update_with_header(PriceOracleData::default(), AssetType::MSOL, sol_feed(127, 2), (state_key, PoolKind::Marinade, 0), vec![state], true);
+        result.unwrap();
+        assert_eq!(PriceOracle::get_premium(&header, &registry, AssetType::MSOL).unwrap().premium_bps, 160);
+
+        // Assets without a registered pool have none, whatever the header holds
+        let err = PriceOracle::get_premium(&header, &registry, AssetType::BSOL).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::DataNotAvailable));
+        assert_eq!(LstPremium::between(1, 0, 1_000).premium_bps, 0);
+    }
+
+    #[test]
+    fn test_set_asset_stake_pool_validation() {
+        let mut registry = AssetRegistry::default();
+        let pool = Pubkey::new_unique();
+        PriceOracle::set_asset_stake_pool(&mut registry, AssetType::BSOL, pool, PoolKind::Spl, 0).unwrap();
+        let config = registry.assets[AssetType::BSOL.index()];
+        assert!(config.is_stake_pool(&pool));
+        assert_eq!(config.nav_tolerance_bps(), DEFAULT_NAV_TOLERANCE_BPS);
+        assert_eq!(registry.asset_for_stake_pool(&pool), Some(AssetType::BSOL));
+
+        let invalid = [
+            (AssetType::SOL, Pubkey::new_unique(), 0, OracleError::InvalidConfig),
+            (AssetType::BSOL, pool, MAX_NAV_TOLERANCE_BPS + 1, OracleError::InvalidConfig),
+            (AssetType::HSOL, pool, 0, OracleError::DuplicateFeed),
+        ];
+        for (asset_type, stake_pool, tolerance_bps, expected) in invalid {
+            let err = PriceOracle::set_asset_stake_pool(&mut registry, asset_type, stake_pool, PoolKind::Spl, tolerance_bps).unwrap_err();
+            assert_eq!(error_code(&err), u32::from(expected));
+        }
+
+        // Marinade's state only prices mSOL
+        let state = Pubkey::new_unique();
+        PriceOracle::set_asset_stake_pool(&mut registry, AssetType::MSOL, state, PoolKind::Marinade, 0).unwrap();
+        assert_eq!(registry.assets[AssetType::MSOL.index()].stake_pool_kind, PoolKind::Marinade);
+        for asset_type in [AssetType::JitoSOL, AssetType::SOL] {
+            let err = PriceOracle::set_asset_stake_pool(&mut registry, asset_type, Pubkey::new_unique(), PoolKind::Marinade, 0).unwrap_err();
+            assert_eq!(error_code(&err), u32::from(OracleError::InvalidConfig));
+        }
+
+        PriceOracle::set_asset_stake_pool(&mut registry, AssetType::BSOL, pool, PoolKind::Spl, MAX_NAV_TOLERANCE_BPS).unwrap();
+        assert_eq!(registry.assets[AssetType::BSOL.index()].nav_tolerance_bps(), MAX_NAV_TOLERANCE_BPS);
+        // Clearing resets the tolerance and matches nothing
+        PriceOracle::set_asset_stake_pool(&mut registry, AssetType::BSOL, Pubkey::default(), PoolKind::Marinade, MAX_NAV_TOLERANCE_BPS).unwrap();
+        assert_eq!(registry.assets[AssetType::BSOL.index()].nav_tolerance_bps, 0);
+        assert_eq!(registry.assets[AssetType::BSOL.index()].stake_pool_kind, PoolKind::Spl);
+        assert_eq!(registry.asset_for_stake_pool(&Pubkey::default()), None);
+    }
+
+    #[test]
+    fn test_account_len_matches_serialized_size() {
+        let price_data = PriceData {
+            price: u64::MAX,
+            last_price: u64::MAX,
+            last_update_time: i64::MAX,
+            apy: u64::MAX,
+            last_update_slot: u64::MAX,
+            last_price_time: i64::MAX,
+            quote: QuoteCurrency::SOL,
+            provenance: PriceProvenance { source_feed: Pubkey::new_unique(), source_round_slot: u64::MAX, source_kind: PriceProvenance::MEDIAN },
+            delisted: true,
+            confidence: Confidence { value: u64::MAX, status: ConfidenceStatus::ZeroDeviation },
+            high_watermark: u64::MAX,
+            high_watermark_time: i64::MAX,
+            low_watermark: u64::MAX,
+            low_watermark_time: i64::MAX,
+            last_checked_time: i64::MAX,
+            last_checked_slot: u64::MAX,
+            feed_timestamp: i64::MAX,
+        };
+        assert_eq!(price_data.try_to_vec().unwrap().len(), PriceData::LEN);
+
+        let header = PriceOracleHeader {
+            last_global_update: i64::MAX,
+            emergency_stop: true,
+            authority: Pubkey::new_unique(),
+            switchboard_program_id: Pubkey::new_unique(),
+            bump: u8::MAX,
+            max_feed_age_slots: u64::MAX,
+            last_stop_change_time: i64::MAX,
+            stop_change_count: u64::MAX,
+            min_stop_change_interval: i64::MAX,
+            operator: Pubkey::new_unique(),
+            locked: true,
+            min_lease_balance: u64::MAX,
+            reject_empty_lease: true,
+            sources: [SourceKind::Chainlink; ASSET_COUNT],
+            partial_updates: true,
+            rejection_codes: [u32::MAX; ASSET_COUNT],
+            max_age_secs: [u32::MAX; ASSET_COUNT],
+            price_change_limit_bps: u32::MAX,
+            config_timelock_secs: u32::MAX,
+            pending_config: PendingConfig {
+                config: OracleConfig {
+                    price_change_limit_bps: u32::MAX,
+                    timelock_secs: u32::MAX,
+                    feeds: [Pubkey::new_unique(); ASSET_COUNT],
+                    resume_approvers: [Pubkey::new_unique(); MAX_RESUME_APPROVERS],
+                    resume_threshold: u8::MAX,
+                    manual_resume_needs_approval: true,
+                },
+                effective_at: i64::MAX,
+            },
+            stop_clear_effective_at: i64::MAX,
+            emergency_council: Pubkey::new_unique(),
+            paused: [true; ASSET_COUNT],
+            resume_approvers: [Pubkey::new_unique(); MAX_RESUME_APPROVERS],
+            resume_threshold: u8::MAX,
+            manual_resume_needs_approval: true,
+            stop_incident: u64::MAX,
+            stop_automatic: true,
+            resume_approvals: [u64::MAX; MAX_RESUME_APPROVERS],
+            large_move_policy: [LargeMovePolicy::Confirm; ASSET_COUNT],
+            move_confirm_delay_secs: u32::MAX,
+            move_confirm_tolerance_bps: u32::MAX,
+            pending_prices: [PendingPrice { price: u64::MAX, observed_at: i64::MAX }; ASSET_COUNT],
+            update_stats: UpdateStats {
+                successful_updates: u64::MAX,
+                rejected_stale: u64::MAX,
+                rejected_change_limit: u64::MAX,
+                longest_gap_secs: u64::MAX,
+                longest_gap_at: i64::MAX,
+            },
+            apy_change_limit_bps: [u32::MAX; ASSET_COUNT],
+            trusted_publisher: Pubkey::new_unique(),
+            signed_price_times: [i64::MAX; ASSET_COUNT],
+            attestation_sequence: u64::MAX,
+            haircut_bps: [u16::MAX; ASSET_COUNT],
+            audit_log_enabled: true,
+            updates_paused: true,
+            thread_authority: Pubkey::new_unique(),
+            feed_mapping: FeedMapping { configured: true, assets: AssetType::MULTI_ASSET_FEED },
+            pending_feed_mapping: PendingFeedMapping { mapping: FeedMapping::default(), effective_at: i64::MAX },
+            trust_weights: TrustWeights { freshness_bps: u16::MAX, confidence_bps: u16::MAX, source_bps: u16::MAX, stability_bps: u16::MAX },
+            instance: Pubkey::new_unique(),
+            clamped_prices: [u64::MAX; ASSET_COUNT],
+            asset_mints: [Pubkey::new_unique(); ASSET_COUNT],
+            mint_decimals: [u8::MAX; ASSET_COUNT],
+            yield_configs: [YieldConfig { convention: YieldConvention::Apr, compounding_periods: u16::MAX }; ASSET_COUNT],
+            raw_yields: [RawYield { value: u64::MAX, config: YieldConfig::default() }; ASSET_COUNT],
+            data_generation: u32::MAX,
+            last_errors: [LastError { code: u32::MAX, time: i64::MAX }; ASSET_COUNT],
+            source_policies: [SourcePolicy::Priority; ASSET_COUNT],
+            resumed_at: i64::MAX,
+            resume_grace_secs: u32::MAX,
+            resume_grace_multiplier_bps: u32::MAX,
+            last_changed_mask: u32::MAX,
+            last_changed_slot: u64::MAX,
+            watchers: [Pubkey::new_from_array([u8::MAX; 32]); MAX_WATCHERS],
+            stop_watcher: Pubkey::new_from_array([u8::MAX; 32]),
+            premiums: [LstPremium { premium_bps: i64::MIN, feed_price: u64::MAX, intrinsic_rate: u64::MAX, time: i64::MAX }; ASSET_COUNT],
+            feed_shards: [FeedShard { feed: Pubkey::new_from_array([u8::MAX; 32]), assets: [AssetType::BonkSOL; MULTI_ASSET_FEED_LEN], mapped: u8::MAX }; MAX_FEED_SHARDS],
+            stop_started_at: i64::MAX,
+            total_stopped_secs: u64::MAX,
+            asset_authorities: [Pubkey::new_from_array([u8::MAX; 32]); ASSET_COUNT],
+            state_hash: [u8::MAX; 32],
+            on_demand_queue: Pubkey::new_from_array([u8::MAX; 32]),
+            pending_fallbacks: [PendingFallback { fallback: SourceSlot { kind: SourceKind::SwitchboardOnDemand, feed: Pubkey::new_unique() }, effective_at: i64::MAX }; ASSET_COUNT],
+            pending_feed_shards: [PendingFeedShard { shard: FeedShard { feed: Pubkey::new_unique(), assets: [AssetType::BonkSOL; MULTI_ASSET_FEED_LEN], mapped: u8::MAX }, effective_at: i64::MAX }; MAX_FEED_SHARDS],
+        };
+        assert_eq!(header.try_to_vec().unwrap().len(), PriceOracleHeader::LEN);
+
+        let data = PriceOracleData { price_data: [price_data; ASSET_COUNT], bump: u8::MAX, nonce: u64::MAX };
+        assert_eq!(data.try_to_vec().unwrap().len(), PriceOracleData::LEN);
+
+        let registry = AssetRegistry {
+            queue: Pubkey::new_unique(),
+            assets: [AssetConfig {
+                feed: Pubkey::new_unique(),
+                sources: [SourceSlot { kind: SourceKind::Chainlink, feed: Pubkey::new_unique() }; MAX_ASSET_SOURCES],
+                min_sources: u8::MAX,
+                stake_pool: Pubkey::new_unique(),
+                nav_tolerance_bps: u32::MAX,
+                stake_pool_kind: PoolKind::Marinade,
+                fallback: SourceSlot { kind: SourceKind::SwitchboardOnDemand, feed: Pubkey::new_unique() },
+            }; ASSET_COUNT],
+            bump: u8::MAX,
+            pyth_feed_ids: [[u8::MAX; 32]; ASSET_COUNT],
+        };
+        assert_eq!(registry.try_to_vec().unwrap().len(), AssetRegistry::LEN);
+
+        assert_eq!(PriceHistory::default().try_to_vec().unwrap().len(), PriceHistory::LEN);
+    }
+
+    #[test]
+    fn test_asset_type_u8_round_trip() {
+        for (index, asset_type) in AssetType::ALL.into_iter().enumerate() {
+            assert_eq!(asset_type.index(), index);
+            assert_eq!(u8::from(asset_type) as usize, index);
+            assert_eq!(AssetType::try_from(index as u8).ok(), Some(asset_type));
+            // The Borsh encoding used in instruction data and accounts is the same byte
+            assert_eq!(asset_type.try_to_vec().unwrap(), vec![index as u8]);
+        }
+        assert_eq!(AssetType::try_from(0).ok(), Some(AssetType::JupSOL));
+        assert_eq!(AssetType::try_from(6).ok(), Some(AssetType::SOL));
+        // Later assets are appended, so earlier discriminants never move
+        assert_eq!(AssetType::try_from(9).ok(), Some(AssetType::BonkSOL));
+        assert!(matches!(AssetType::try_from(ASSET_COUNT as u8), Err(OracleError::InvalidAssetType)));
+        assert!(matches!(AssetType::try_from(u8::MAX), Err(OracleError::InvalidAssetType)));
+    }
+
+    #[test]
+    fn test_price_change_between() {
+        let up = PriceChange::between(100 * PRICE_SCALE, 1_000, 105 * PRICE_SCALE, 1_060, QuoteCurrency::SOL);
+        assert_eq!(up.absolute_delta, 5 * PRICE_SCALE);
+        assert_eq!(up.change_bps, 500);
+        assert_eq!((up.previous_update_time, up.current_update_time), (1_000, 1_060));
+
+        // Truncated toward zero in both directions
+        let down = PriceChange::between(3 * PRICE_SCALE, 1_000, 2 * PRICE_SCALE, 1_060, QuoteCurrency::SOL);
+        assert_eq!(down.absolute_delta, PRICE_SCALE);
+        assert_eq!(down.change_bps, -3_333);
+        assert_eq!(PriceChange::between(3, 0, 4, 0, QuoteCurrency::SOL).change_bps, 3_333);
+
+        assert_eq!(PriceChange::between(7, 0, 7, 0, QuoteCurrency::SOL).change_bps, 0);
+        // Unbounded moves saturate instead of overflowing
+        assert_eq!(PriceChange::between(1, 0, u64::MAX, 0, QuoteCurrency::SOL).change_bps, i64::MAX);
+    }
+
+    #[test]
+    fn test_price_change_requires_two_observations() {
+        let mut price_data = PriceData::default();
+        assert!(price_data.price_change().is_none());
+
+        price_data.record_price(100 * PRICE_SCALE, PriceProvenance::default(), Confidence::default(), 1_000, &clock_at(1_000, 10));
+        assert!(price_data.price_change().is_none());
+
+        price_data.record_price(90 * PRICE_SCALE, PriceProvenance::default(), Confidence::default(), 1_060, &clock_at(1_060, 20));
+        let change = price_data.price_change().unwrap();
+        assert_eq!(change.previous_price, 100 * PRICE_SCALE);
+        assert_eq!(change.current_price, 90 * PRICE_SCALE);
+        assert_eq!(change.change_bps, -1_000);
+        assert_eq!((change.previous_update_time, change.current_update_time), (1_000, 1_060));
+    }
+
+    #[test]
+    fn test_watermarks_follow_monotonic_sequences() {
+        let mut price_data = PriceData::default();
+        assert_eq!(price_data.watermarks(), None);
+
+        // Rising: only the high moves
+        for (i, price) in [100, 105, 110, 115].into_iter().enumerate() {
+            price_data.record_price(price * PRICE_SCALE, PriceProvenance::default(), Confidence::default(), 1_000 + i as i64 * 60, &clock_at(1_000 + i as i64 * 60, 10));
+        }
+        let watermarks = price_data.watermarks().unwrap();
+        assert_eq!((watermarks.high, watermarks.high_time), (115 * PRICE_SCALE, 1_180));
+        assert_eq!((watermarks.low, watermarks.low_time), (100 * PRICE_SCALE, 1_000));
+
+        // Falling: only the low moves, and revisiting a watermark does not restamp it
+        for (i, price) in [110, 100, 95, 90, 90].into_iter().enumerate() {
+            price_data.record_price(price * PRICE_SCALE, PriceProvenance::default(), Confidence::default(), 2_000 + i as i64 * 60, &clock_at(2_000 + i as i64 * 60, 20));
+        }
+        let watermarks = price_data.watermarks().unwrap();
+        assert_eq!((watermarks.high, watermarks.high_time), (115 * PRICE_SCALE, 1_180));
+        assert_eq!((watermarks.low, watermarks.low_time), (90 * PRICE_SCALE, 2_180));
+    }
+
+    #[test]
+    fn test_rejected_and_held_moves_leave_watermarks() {
+        let mut header = oracle_header();
+        let mut data = PriceOracleData::default();
+        PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::BSOL, 100 * PRICE_SCALE, None, new_round(), 1_000, &clock_at(1_000, 5_000)).unwrap();
+        let before = PriceOracle::get_watermarks(&data, AssetType::BSOL).unwrap();
+
+        // Skipped past the change limit in partial mode
+        PriceOracle::set_partial_updates(&mut header, true);
+        let outcome = PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::BSOL, 150 * PRICE_SCALE, None, new_round(), 1_010, &clock_at(1_010, 5_010)).unwrap();
+        assert!(!outcome.updated);
+        assert_eq!(PriceOracle::get_watermarks(&data, AssetType::BSOL).unwrap(), before);
+
+        // Held for confirmation
+        PriceOracle::set_large_move_policy(&mut header, AssetType::BSOL, LargeMovePolicy::Confirm);
+        let outcome = PriceOracle::apply_asset_value(&mut header, &mut data, AssetType::BSOL, 50 * PRICE_SCALE, None, new_round(), 1_020, &clock_at(1_020, 5_020)).unwrap();
+        assert!(!outcome.updated);
+        assert_eq!(PriceOracle::get_watermarks(&data, AssetType::BSOL).unwrap(), before);
+    }
+
+    #[test]
+    fn test_reset_watermarks() {
+        let mut data = PriceOracleData::default();
+        let err = PriceOracle::get_watermarks(&data, AssetType::MSOL).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::DataNotAvailable));
+        assert_eq!(PriceOracle::reset_watermarks(&mut data, AssetType::MSOL), None);
+
+        let msol = &mut data.price_data[AssetType::MSOL.index()];
+        msol.record_price(1_300_000_000, PriceProvenance::default(), Confidence::default(), 1_000, &clock_at(1_000, 10));
+        msol.record_price(1_100_000_000, PriceProvenance::default(), Confidence::default(), 1_060, &clock_at(1_060, 20));
+        let cleared = PriceOracle::reset_watermarks(&mut data, AssetType::MSOL).unwrap();
+        assert_eq!((cleared.high, cleared.low), (1_300_000_000, 1_100_000_000));
+        // The price itself is kept; the record restarts with the next accepted one
+        assert_eq!(data.price_data[AssetType::MSOL.index()].price, 1_100_000_000);
+        assert!(PriceOracle::get_watermarks(&data, AssetType::MSOL).is_err());
+
+        data.price_data[AssetType::MSOL.index()].record_price(1_200_000_000, PriceProvenance::default(), Confidence::default(), 1_120, &clock_at(1_120, 30));
+        let watermarks = PriceOracle::get_watermarks(&data, AssetType::MSOL).unwrap();
+        assert_eq!(watermarks, Watermarks { high: 1_200_000_000, high_time: 1_120, low: 1_200_000_000, low_time: 1_120, quote: QuoteCurrency::SOL });
+    }
+
+    #[test]
+    fn test_update_price_signed() {
+        let publisher = Pubkey::new_unique();
+        let mut header = oracle_header();
+        let mut data = PriceOracleData::default();
+        let clock = clock_at(10_000, 50_000);
+        let err = PriceOracle::update_price_signed(&mut header, &mut data, AssetType::SOL, 150 * PRICE_SCALE, 9_990, &clock).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::SignedPriceDisabled));
+
+        PriceOracle::set_trusted_publisher(&mut header, publisher);
+        data.price_data[AssetType::SOL.index()].apy = 70_000_000;
+        PriceOracle::update_price_signed(&mut header, &mut data, AssetType::SOL, 150 * PRICE_SCALE, 9_990, &clock).unwrap();
+        let sol = data.price_data[AssetType::SOL.index()];
+        assert_eq!((sol.price, sol.apy, sol.last_update_time), (150 * PRICE_SCALE, 70_000_000, 10_000));
+        assert_eq!(sol.provenance, PriceProvenance { source_feed: publisher, source_round_slot: 50_000, source_kind: PriceProvenance::SIGNED_PUBLISHER });
+        assert_eq!(header.signed_price_times[AssetType::SOL.index()], 9_990);
+        assert_eq!(header.last_global_update, 10_000);
+
+        // Replays, timestamps older than the stored price, stale, future and zero prices
+        let later = clock_at(10_100, 50_100);
+        let rejected = [
+            (150 * PRICE_SCALE, 9_990, OracleError::SignedPriceReplayed),
+            (151 * PRICE_SCALE, 9_995, OracleError::SignedPriceReplayed),
+            (151 * PRICE_SCALE, 10_000, OracleError::SignedPriceReplayed),
+            (151 * PRICE_SCALE, 10_100 - MAX_SWITCHBOARD_DATA_AGE - 1, OracleError::StaleData),
+            (151 * PRICE_SCALE, 10_100 + SIGNED_PRICE_MAX_CLOCK_SKEW_SECS + 1, OracleError::InvalidSignedPrice),
+            (0, 10_050, OracleError::InvalidSignedPrice),
+        ];
+        for (price, timestamp, expected) in rejected {
+            let err = PriceOracle::update_price_signed(&mut header, &mut data, AssetType::SOL, price, timestamp, &later).unwrap_err();
+            assert_eq!(error_code(&err), u32::from(expected));
+        }
+        PriceOracle::update_price_signed(&mut header, &mut data, AssetType::SOL, 151 * PRICE_SCALE, 10_100 + SIGNED_PRICE_MAX_CLOCK_SKEW_SECS, &later).unwrap();
+        assert_eq!(data.price_data[AssetType::SOL.index()].price, 151 * PRICE_SCALE);
+
+        // The change limit applies as for feeds: a halted move keeps the stop and the old price,
+        // and like a held move it consumes the message
+        let much_later = clock_at(10_200, 50_200);
+        let outcomes = PriceOracle::update_price_signed(&mut header, &mut data, AssetType::SOL, 200 * PRICE_SCALE, 10_185, &much_later).unwrap();
+        assert_eq!((outcomes[0].updated, outcomes[0].error_code), (false, u32::from(OracleError::PriceChangeExceedsLimit)));
+        assert_eq!(data.price_data[AssetType::SOL.index()].price, 151 * PRICE_SCALE);
+        assert!(header.emergency_stop && header.stop_automatic);
+        assert_eq!(header.signed_price_times[AssetType::SOL.index()], 10_185);
+        header.emergency_stop = false;
+        PriceOracle::set_large_move_policy(&mut header, AssetType::SOL, LargeMovePolicy::Confirm);
+        PriceOracle::update_price_signed(&mut header, &mut data, AssetType::SOL, 200 * PRICE_SCALE, 10_190, &much_later).unwrap();
+        assert!(header.pending_prices[AssetType::SOL.index()].is_pending());
+        let err = PriceOracle::update_price_signed(&mut header, &mut data, AssetType::SOL, 200 * PRICE_SCALE, 10_190, &clock_at(10_400, 50_400)).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::SignedPriceReplayed));
+    }
+
+    #[test]
+    fn test_attest_prices_advances_the_sequence() {
+        let mut header = oracle_header();
+        let data = quoted_data();
+        let first = PriceOracle::attest_prices(&mut header, &data, &clock_at(1_000, 10)).unwrap();
+        let second = PriceOracle::attest_prices(&mut header, &data, &clock_at(1_000, 10)).unwrap();
+        assert_eq!((first.sequence, second.sequence, header.attestation_sequence), (1, 2, 2));
+        assert_eq!(first.prices, second.prices);
+        assert_eq!(first.prices.iter().map(|price| price.asset_type).collect::<Vec<_>>(), vec![AssetType::MSOL, AssetType::JitoSOL, AssetType::SOL]);
+
+        header.emergency_stop = true;
+        let err = PriceOracle::attest_prices(&mut header, &data, &clock_at(1_010, 11)).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::EmergencyStop));
+        assert_eq!(header.attestation_sequence, 2);
+    }
+
+    /// Default quotes, SOL at $150, mSOL at 1.2 SOL and JitoSOL at 1.1 SOL
+    fn quoted_data() -> PriceOracleData {
+        let mut data = PriceOracleData::default();
+        for asset_type in AssetType::ALL {
+            data.price_data[asset_type.index()].quote = asset_type.default_quote();
+        }
+        data.price_data[AssetType::SOL.index()].price = 150 * PRICE_SCALE;
+        data.price_data[AssetType::MSOL.index()].price = 1_200_000_000;
+        data.price_data[AssetType::JitoSOL.index()].price = 1_100_000_000;
+        data
+    }
+
+    #[test]
+    fn test_exchange_rate_same_quote() {
+        let data = quoted_data();
+        // 1.2 / 1.1, truncated
+        assert_eq!(PriceOracle::get_exchange_rate(&data, AssetType::MSOL, AssetType::JitoSOL, false).unwrap(), 1_090_909_090);
+        assert_eq!(PriceOracle::convert_amount(&data, AssetType::JitoSOL, AssetType::MSOL, 12 * PRICE_SCALE, false).unwrap(), 11 * PRICE_SCALE);
+        // An asset that never received a price has no rate
+        let err = PriceOracle::get_exchange_rate(&data, AssetType::MSOL, AssetType::BSOL, false).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::PriceNotAvailable));
+    }
+
+    #[test]
+    fn test_exchange_rate_mixed_quotes() {
+        let mut data = quoted_data();
+        // SOL is quoted in USD and mSOL in SOL: refused unless conversion is requested
+        for (base, counter) in [(AssetType::MSOL, AssetType::SOL), (AssetType::SOL, AssetType::MSOL)] {
+            let err = PriceOracle::get_exchange_rate(&data, base, counter, false).unwrap_err();
+            assert_eq!(error_code(&err), u32::from(OracleError::QuoteCurrencyMismatch));
+        }
+        let err = PriceOracle::convert_amount(&data, AssetType::MSOL, AssetType::SOL, PRICE_SCALE, false).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::QuoteCurrencyMismatch));
+
+        // Through SOL/USD: 1 mSOL = 1.2 SOL = $180
+        assert_eq!(PriceOracle::get_exchange_rate(&data, AssetType::MSOL, AssetType::SOL, true).unwrap(), 1_200_000_000);
+        assert_eq!(PriceOracle::convert_amount(&data, AssetType::SOL, AssetType::MSOL, 6 * PRICE_SCALE, true).unwrap(), 5 * PRICE_SCALE);
+
+        // A USD-quoted mSOL compares directly with SOL
+        PriceOracle::set_asset_quote(&mut data, AssetType::MSOL, QuoteCurrency::USD);
+        data.price_data[AssetType::MSOL.index()].price = 180 * PRICE_SCALE;
+        assert_eq!(PriceOracle::get_exchange_rate(&data, AssetType::MSOL, AssetType::SOL, false).unwrap(), 1_200_000_000);
+
+        // Conversion needs a SOL price in USD
+        data.price_data[AssetType::SOL.index()].price = 0;
+        let err = PriceOracle::get_exchange_rate(&data, AssetType::JitoSOL, AssetType::MSOL, true).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::PriceNotAvailable));
+
+        // Results that do not fit a u64 are refused rather than truncated
+        data.price_data[AssetType::SOL.index()].price = 150 * PRICE_SCALE;
+        let err = PriceOracle::convert_amount(&data, AssetType::MSOL, AssetType::SOL, u64::MAX, false).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::ConversionOverflow));
+    }
+
+    #[test]
+    fn test_collateral_value_properties() {
+        let amounts = [0, 1, 999_999_999, PRICE_SCALE, 12_345 * PRICE_SCALE + 6_789, u32::MAX as u64 * 1_000];
+        let prices = [1, 1_200_000_000, 150 * PRICE_SCALE, 71_234 * PRICE_SCALE + 5];
+        for amount in amounts {
+            for price in prices {
+                for decimals in [0, 6, 9] {
+                    let scale = 10u64.pow(decimals as u32);
+                    // No haircut is the plain conversion, a full one values the asset at zero
+                    let plain = mul_div(amount, price, scale);
+                    let full = collateral_value(amount, price, decimals, 0);
+                    assert_eq!(full.ok(), plain.ok(), "{} at {} with {} decimals", amount, price, decimals);
+                    assert_eq!(collateral_value(amount, price, decimals, MAX_HAIRCUT_BPS).unwrap(), 0);
+                    // A larger haircut never values it higher
+                    if let Ok(full) = full {
+                        let values = [1, 2_500, 9_999].map(|bps| collateral_value(amount, price, decimals, bps).unwrap());
+                        assert!(full >= values[0] && values[0] >= values[1] && values[1] >= values[2]);
+                    }
+                }
+            }
+        }
+
+        // 3 tokens at $180 less 25%, truncated once
+        assert_eq!(collateral_value(3 * PRICE_SCALE, 180 * PRICE_SCALE, 9, 2_500).unwrap(), 405 * PRICE_SCALE);
+        assert!(matches!(collateral_value(1, 1, 0, 1), Ok(0)));
+        assert!(matches!(collateral_value(1, 1, 0, MAX_HAIRCUT_BPS + 1), Err(OracleError::InvalidConfig)));
+        assert!(matches!(collateral_value(u64::MAX, u64::MAX, 9, 0), Err(OracleError::ConversionOverflow)));
+    }
+
+    #[test]
+    fn test_get_collateral_value() {
+        let mut header = oracle_header();
+        let mut data = quoted_data();
+        for asset_type in [AssetType::SOL, AssetType::MSOL] {
+            let price_data = &mut data.price_data[asset_type.index()];
+            price_data.last_update_time = 1_000;
+            price_data.last_checked_time = 1_000;
+            price_data.feed_timestamp = 1_000;
+        }
+        let clock = clock_at(1_100, 50);
+        let value = |header: &PriceOracleHeader, data: &PriceOracleData, asset_type: AssetType| {
+            PriceOracle::get_collateral_value(header, data, asset_type, 2 * PRICE_SCALE, &clock)
+        };
+
+        // 2 mSOL at 1.2 SOL, $180 through SOL/USD
+        assert_eq!(value(&header, &data, AssetType::MSOL).unwrap(), 360 * PRICE_SCALE);
+        PriceOracle::set_haircut(&mut header, AssetType::MSOL, 2_500).unwrap();
+        assert_eq!(value(&header, &data, AssetType::MSOL).unwrap(), 270 * PRICE_SCALE);
+        assert_eq!(value(&header, &data, AssetType::SOL).unwrap(), 300 * PRICE_SCALE);
+        let err = PriceOracle::set_haircut(&mut header, AssetType::MSOL, MAX_HAIRCUT_BPS + 1).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::InvalidConfig));
+        assert_eq!(header.haircut_bps(AssetType::MSOL), 2_500);
+
+        // Never priced, paused, stale or delisted, for the asset itself or for SOL
+        let err = value(&header, &data, AssetType::JitoSOL).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::PriceNotAvailable));
+        for asset_type in [AssetType::MSOL, AssetType::SOL] {
+            let mut paused = header.clone();
+            PriceOracle::set_asset_paused(&mut paused, asset_type, true);
+            assert_eq!(error_code(&value(&paused, &data, AssetType::MSOL).unwrap_err()), u32::from(OracleError::AssetPaused));
+
+            let mut stale = data.clone();
+            stale.price_data[asset_type.index()].last_checked_time = 1_100 - MAX_SWITCHBOARD_DATA_AGE - 1;
+            stale.price_data[asset_type.index()].feed_timestamp = 1_100 - MAX_SWITCHBOARD_DATA_AGE - 1;
+            assert_eq!(error_code(&value(&header, &stale, AssetType::MSOL).unwrap_err()), u32::from(OracleError::StaleData));
+
+            let mut delisted = data.clone();
+            PriceOracle::delist_asset(&mut delisted, asset_type).unwrap();
+            assert_eq!(error_code(&value(&header, &delisted, AssetType::MSOL).unwrap_err()), u32::from(OracleError::AssetDelisted));
+        }
+        // A USD-quoted asset does not depend on SOL
+        let mut paused = header.clone();
+        PriceOracle::set_asset_paused(&mut paused, AssetType::MSOL, true);
+        assert_eq!(value(&paused, &data, AssetType::SOL).unwrap(), 300 * PRICE_SCALE);
+
+        header.emergency_stop = true;
+        assert_eq!(error_code(&value(&header, &data, AssetType::SOL).unwrap_err()), u32::from(OracleError::EmergencyStop));
+    }
+
+    #[test]
+    fn test_set_asset_mint_sets_collateral_decimals() {
+        let mut header = oracle_header();
+        let mut data = quoted_data();
+        for asset_type in [AssetType::SOL, AssetType::MSOL] {
+            let price_data = &mut data.price_data[asset_type.index()];
+            price_data.last_update_time = 1_000;
+            price_data.last_checked_time = 1_000;
+            price_data.feed_timestamp = 1_000;
+        }
+        let clock = clock_at(1_100, 50);
+        assert_eq!(header.decimals(AssetType::MSOL), AssetType::MSOL.decimals());
+
+        // The same raw amount is 1000 times as many tokens of a 6-decimal mint
+        let mut mint = mock_mint(Pubkey::new_unique(), 6);
+        let (old_mint, info) = PriceOracle::set_asset_mint(&mut header, AssetType::MSOL, &mint.account_info()).unwrap();
+        assert_eq!((old_mint, info.decimals), (Pubkey::default(), 6));
+        assert_eq!((header.asset_mints[AssetType::MSOL.index()], header.decimals(AssetType::MSOL)), (mint.key, 6));
+        let value = PriceOracle::get_collateral_value(&header, &data, AssetType::MSOL, 2 * PRICE_SCALE, &clock).unwrap();
+        assert_eq!(value, 360_000 * PRICE_SCALE);
+
+        // Another asset cannot share it, and a rejected mint leaves the registered one
+        let err = PriceOracle::set_asset_mint(&mut header, AssetType::JitoSOL, &mint.account_info()).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::DuplicateMint));
+        let mut frozen = mock_token_2022_mint(Pubkey::new_unique(), 9, spl_token_2022::state::AccountState::Frozen);
+        let err = PriceOracle::set_asset_mint(&mut header, AssetType::MSOL, &frozen.account_info()).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::FrozenMint));
+        assert_eq!(header.asset_mints[AssetType::MSOL.index()], mint.key);
+
+        // Re-registering the same mint for its own asset is allowed
+        let (old_mint, _) = PriceOracle::set_asset_mint(&mut header, AssetType::MSOL, &mint.account_info()).unwrap();
+        assert_eq!(old_mint, mint.key);
+    }
+
+    #[test]
+    fn test_get_portfolio_value_sums_registered_positions() {
+        let mut header = oracle_header();
+        let mut data = quoted_data();
+        for asset_type in [AssetType::SOL, AssetType::MSOL, AssetType::JitoSOL] {
+            let price_data = &mut data.price_data[asset_type.index()];
+            price_data.last_update_time = 1_000;
+            price_data.last_checked_time = 1_000;
+            price_data.feed_timestamp = 1_000;
+        }
+        let clock = clock_at(1_100, 50);
+        let mut msol_mint = mock_mint(Pubkey::new_unique(), 6);
+        let mut jito_mint = mock_token_2022_mint(Pubkey::new_unique(), 9, spl_token_2022::state::AccountState::Initialized);
+        let mut bsol_mint = mock_mint(Pubkey::new_unique(), 9);
+        PriceOracle::set_asset_mint(&mut header, AssetType::MSOL, &msol_mint.account_info()).unwrap();
+        PriceOracle::set_asset_mint(&mut header, AssetType::JitoSOL, &jito_mint.account_info()).unwrap();
+        PriceOracle::set_asset_mint(&mut header, AssetType::BSOL, &bsol_mint.account_info()).unwrap();
+        assert_eq!(header.asset_for_mint(&jito_mint.key), Some(AssetType::JitoSOL));
+        assert_eq!(header.asset_for_mint(&Pubkey::default()), None);
+
+        // 2 mSOL at $180 and 0.5 JitoSOL at $165, each with its mint's decimals
+        let mut msol = mock_token_account_of(Pubkey::new_unique(), msol_mint.key, 2_000_000);
+        let mut jito = mock_token_2022_account(Pubkey::new_unique(), jito_mint.key, 500_000_000);
+        let accounts = [msol.account_info(), jito.account_info()];
+        let value = PriceOracle::get_portfolio_value(&header, &data, &accounts, false, &clock).unwrap();
+        assert_eq!(value.total, 442_500_000_000);
+        assert_eq!(value.positions[0], PortfolioPosition {
+            token_account: msol.key,
+            mint: msol_mint.key,
+            asset_type: Some(AssetType::MSOL),
+            amount: 2_000_000,
+            value: 360 * PRICE_SCALE,
+            error_code: 0,
+        });
+        assert_eq!(value.positions[1].value, 82_500_000_000);
+
+        // Unknown mints and unpriced assets fail with their own errors unless skipped
+        let mut unknown = mock_token_account_of(Pubkey::new_unique(), Pubkey::new_unique(), 1_000);
+        let mut bsol = mock_token_account_of(Pubkey::new_unique(), bsol_mint.key, 1_000);
+        let accounts = [msol.account_info(), unknown.account_info(), bsol.account_info()];
+        let err = PriceOracle::get_portfolio_value(&header, &data, &accounts, false, &clock).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::UnknownMint));
+        let err = PriceOracle::get_portfolio_value(&header, &data, &accounts[2..], false, &clock).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::PriceNotAvailable));
+        let value = PriceOracle::get_portfolio_value(&header, &data, &accounts, true, &clock).unwrap();
+        assert_eq!(value.total, 360 * PRICE_SCALE);
+        let skipped: Vec<_> = value.positions.iter().map(|position| (position.asset_type, position.value, position.error_code)).collect();
+        assert_eq!(skipped, vec![
+            (Some(AssetType::MSOL), 360 * PRICE_SCALE, 0),
+            (None, 0, u32::from(OracleError::UnknownMint)),
+            (Some(AssetType::BSOL), 0, u32::from(OracleError::PriceNotAvailable)),
+        ]);
+
+        // Paused and stale assets, and a SOL-quoted asset whose SOL price is stale, are skippable
+        header.paused[AssetType::MSOL.index()] = true;
+        let accounts = [msol.account_info()];
+        let err = PriceOracle::get_portfolio_value(&header, &data, &accounts, false, &clock).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::AssetPaused));
+        header.paused[AssetType::MSOL.index()] = false;
+        let stale = clock_at(1_000 + MAX_SWITCHBOARD_DATA_AGE + 1, 50);
+        let value = PriceOracle::get_portfolio_value(&header, &data, &accounts, true, &stale).unwrap();
+        assert_eq!((value.total, value.positions[0].error_code), (0, u32::from(OracleError::StaleData)));
+
+        // Anything but a token account fails even when skipping, as do too many accounts
+        let accounts = [msol_mint.account_info()];
+        let err = PriceOracle::get_portfolio_value(&header, &data, &accounts, true, &clock).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::InvalidTokenAccount));
+        let mut many: Vec<_> = (0..=MAX_PORTFOLIO_POSITIONS).map(|_| mock_token_account_of(Pubkey::new_unique(), msol_mint.key, 1)).collect();
+        let accounts: Vec<_> = many.iter_mut().map(|account| account.account_info()).collect();
+        let err = PriceOracle::get_portfolio_value(&header, &data, &accounts, true, &clock).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::TooManyPositions));
+        header.emergency_stop = true;
+        let err = PriceOracle::get_portfolio_value(&header, &data, &accounts[..1], true, &clock).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::EmergencyStop));
+    }
+
+    #[test]
+    fn test_pushed_quote_is_the_stored_quote_while_usable() {
+        let mut header = oracle_header();
+        let mut data = quoted_data();
+        let price_data = &mut data.price_data[AssetType::MSOL.index()];
+        price_data.last_update_time = 900;
+        price_data.last_checked_time = 1_000;
+        price_data.feed_timestamp = 1_000;
+        price_data.apy = 78_839_670;
+        data.nonce = 42;
+        let clock = clock_at(1_100, 50);
+
+        let quote = PriceOracle::pushed_quote(&header, &data, AssetType::MSOL, &clock).unwrap();
+        let price_data = &data.price_data[AssetType::MSOL.index()];
+        assert_eq!((quote.asset_type, quote.quote, quote.price, quote.apy), (AssetType::MSOL, QuoteCurrency::SOL, price_data.price, 78_839_670));
+        assert_eq!((quote.timestamp, quote.nonce), (1_000, 42));
+
+        // A SOL-quoted asset is pushed in SOL, so an unusable SOL price does not matter
+        PriceOracle::set_asset_paused(&mut header, AssetType::SOL, true);
+        assert!(PriceOracle::pushed_quote(&header, &data, AssetType::MSOL, &clock).is_ok());
+
+        let err = PriceOracle::pushed_quote(&header, &data, AssetType::MSOL, &clock_at(1_000 + MAX_SWITCHBOARD_DATA_AGE + 1, 50)).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::StaleData));
+        let err = PriceOracle::pushed_quote(&header, &data, AssetType::JitoSOL, &clock).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::PriceNotAvailable));
+        header.emergency_stop = true;
+        let err = PriceOracle::pushed_quote(&header, &data, AssetType::MSOL, &clock).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::EmergencyStop));
+    }
+
+    #[test]
+    fn test_get_basket_price_weights_usd_prices() {
+        let header = oracle_header();
+        let mut data = quoted_data();
+        for asset_type in [AssetType::SOL, AssetType::MSOL, AssetType::JitoSOL] {
+            let price_data = &mut data.price_data[asset_type.index()];
+            price_data.last_update_time = 1_000;
+            price_data.last_checked_time = 1_000;
+            price_data.feed_timestamp = 1_000;
+        }
+        let clock = clock_at(1_100, 50);
+        let mut basket = Basket::default();
+        let weights = [
+            BasketWeight { asset_type: AssetType::JitoSOL, weight_bps: 5_000 },
+            BasketWeight { asset_type: AssetType::MSOL, weight_bps: 3_000 },
+            BasketWeight { asset_type: AssetType::SOL, weight_bps: 2_000 },
+        ];
+        PriceOracle::create_basket(&mut basket, &data, Pubkey::new_unique(), 1, &weights, 254).unwrap();
+
+        // $165 JitoSOL, $180 mSOL and $150 SOL
+        assert_eq!(PriceOracle::get_basket_price(&header, &data, &basket, &clock).unwrap(), 166_500_000_000);
+
+        // Any component unusable fails the whole basket
+        let mut paused = header.clone();
+        PriceOracle::set_asset_paused(&mut paused, AssetType::MSOL, true);
+        let err = PriceOracle::get_basket_price(&paused, &data, &basket, &clock).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::AssetPaused));
+        let mut stale = data.clone();
+        stale.price_data[AssetType::SOL.index()].last_checked_time = 1_100 - MAX_SWITCHBOARD_DATA_AGE - 1;
+        stale.price_data[AssetType::SOL.index()].feed_timestamp = 1_100 - MAX_SWITCHBOARD_DATA_AGE - 1;
+        let err = PriceOracle::get_basket_price(&header, &stale, &basket, &clock).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::StaleData));
+        let mut delisted = data.clone();
+        PriceOracle::delist_asset(&mut delisted, AssetType::JitoSOL).unwrap();
+        let err = PriceOracle::get_basket_price(&header, &delisted, &basket, &clock).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::AssetDelisted));
+
+        let stopped = PriceOracleHeader { emergency_stop: true, ..header };
+        let err = PriceOracle::get_basket_price(&stopped, &data, &basket, &clock).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::EmergencyStop));
+    }
+
+    #[test]
+    fn test_audit_log_is_optional_until_initialized() {
+        let mut header = oracle_header();
+        let mut audit_log = AuditLog::default();
+        let entry = |action| AuditEntry::new(1_000, Pubkey::new_unique(), action, None, &[]);
+
+        PriceOracle::record_audit(&header, None, entry(AuditAction::SetOperator)).unwrap();
+        PriceOracle::initialize_audit_log(&mut header, &mut audit_log, 254);
+        assert!(header.audit_log_enabled);
+        assert_eq!((audit_log.bump, audit_log.total_entries), (254, 0));
+
+        let err = PriceOracle::record_audit(&header, None, entry(AuditAction::SetOperator)).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::AuditLogRequired));
+        PriceOracle::record_audit(&header, Some(&mut audit_log), entry(AuditAction::PauseAsset)).unwrap();
+        assert_eq!(audit_log.total_entries, 1);
+        assert_eq!(audit_log.recent(1)[0].action, AuditAction::PauseAsset);
+    }
+
+    #[test]
+    fn test_set_asset_quote_resets_change_baseline() {
+        let mut data = quoted_data();
+        let msol = &mut data.price_data[AssetType::MSOL.index()];
+        msol.record_price(1_200_000_000, PriceProvenance::default(), Confidence::default(), 1_000, &clock_at(1_000, 10));
+        msol.record_price(1_210_000_000, PriceProvenance::default(), Confidence::default(), 1_060, &clock_at(1_060, 20));
+        msol.apy = 72_000_000;
+
+        // Setting the current quote again keeps the baseline
+        assert_eq!(PriceOracle::set_asset_quote(&mut data, AssetType::MSOL, QuoteCurrency::SOL), QuoteCurrency::SOL);
+        assert_eq!(data.price_data[AssetType::MSOL.index()].price, 1_210_000_000);
+
+        assert_eq!(PriceOracle::set_asset_quote(&mut data, AssetType::MSOL, QuoteCurrency::USD), QuoteCurrency::SOL);
+        let msol = data.price_data[AssetType::MSOL.index()];
+        assert_eq!((msol.price, msol.last_price, msol.last_update_time, msol.last_price_time), (0, 0, 0, 0));
+        assert_eq!(msol.apy, 72_000_000);
+        assert_eq!(msol.quote, QuoteCurrency::USD);
+        assert!(msol.price_change().is_none());
+
+        // A USD price 150x the old SOL one is a first observation, not a breach of the change limit
+        let (result, header, data) = run_asset_update(AssetType::MSOL, oracle_header(), data, sol_feed(1_815, 1), &clock_at(1_000, 5_000));
+        assert_eq!(result.unwrap(), vec![AssetType::MSOL]);
+        assert!(!header.emergency_stop);
+        assert_eq!(data.price_data[AssetType::MSOL.index()].price, 181_500_000_000);
+    }
+
+    #[test]
+    fn test_get_volatility_window_and_minimum() {
+        let mut history = PriceHistory::default();
+        // An old observation far outside the window must not count
+        history.assets[AssetType::SOL.index()].push(500 * PRICE_SCALE, 100);
+        // Alternating +10% / -10%, one minute apart
+        let prices = [100 * PRICE_SCALE, 110 * PRICE_SCALE, 99 * PRICE_SCALE, 108_900_000_000, 98_010_000_000];
+        for (i, price) in prices.into_iter().enumerate() {
+            history.assets[AssetType::SOL.index()].push(price, 10_000 + i as i64 * 60);
+        }
+
+        assert_eq!(PriceOracle::get_volatility(&history, AssetType::SOL, 3_600, 10_240).unwrap(), 1_154);
+        // Shrinking the window below five observations is an error
+        let err = PriceOracle::get_volatility(&history, AssetType::SOL, 180, 10_240).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::DataNotAvailable));
+        let err = PriceOracle::get_volatility(&history, AssetType::MSOL, 3_600, 10_240).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::DataNotAvailable));
+        let err = PriceOracle::get_volatility(&history, AssetType::SOL, 0, 10_240).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::InvalidConfig));
+    }
+
+    #[test]
+    fn test_getters_after_pruning_history() {
+        let mut history = PriceHistory::default();
+        let prices = [100 * PRICE_SCALE, 110 * PRICE_SCALE, 99 * PRICE_SCALE, 108_900_000_000, 98_010_000_000, 107_811_000_000];
+        for (i, price) in prices.into_iter().enumerate() {
+            history.assets[AssetType::SOL.index()].push(price, 10_000 + i as i64 * 60);
+            history.assets[AssetType::MSOL.index()].push(price, 10_000 + i as i64 * 60);
+        }
+
+        // Five kept observations are still enough for a volatility figure, four are not
+        assert_eq!(PriceOracle::prune_price_history(&mut history, AssetType::SOL, 5), 1);
+        assert_eq!(PriceOracle::get_volatility(&history, AssetType::SOL, 3_600, 10_300).unwrap(), 1_154);
+        assert_eq!(PriceOracle::prune_price_history(&mut history, AssetType::SOL, 4), 1);
+        let err = PriceOracle::get_volatility(&history, AssetType::SOL, 3_600, 10_300).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::DataNotAvailable));
+        let err = PriceOracle::get_price_at(&history, AssetType::SOL, 10_060).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::DataNotAvailable));
+        assert_eq!(PriceOracle::get_price_at(&history, AssetType::SOL, 10_120).unwrap().price, 99 * PRICE_SCALE);
+
+        // Clearing leaves nothing to read, and other assets untouched
+        assert_eq!(PriceOracle::prune_price_history(&mut history, AssetType::SOL, 0), 4);
+        let err = PriceOracle::get_price_at(&history, AssetType::SOL, i64::MAX).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::DataNotAvailable));
+        assert_eq!(PriceOracle::get_volatility(&history, AssetType::MSOL, 3_600, 10_300).unwrap(), 1_095);
+
+        let mut apy_history = ApyHistory::default();
+        PriceOracle::initialize_apy_history(&mut apy_history, 2, 254).unwrap();
+        let day = 86_400;
+        for i in 0..3 {
+            apy_history.assets[AssetType::MSOL.index()].push(700 + i as u32 * 10, 10_000 + i * day);
+        }
+        assert_eq!(PriceOracle::prune_apy_history(&mut apy_history, AssetType::MSOL, 2), 1);
+        assert_eq!(PriceOracle::get_average_apy(&apy_history, AssetType::MSOL, 7 * day, 10_000 + 2 * day).unwrap(), 715);
+        assert_eq!(PriceOracle::prune_apy_history(&mut apy_history, AssetType::MSOL, 1), 1);
+        let err = PriceOracle::get_average_apy(&apy_history, AssetType::MSOL, 7 * day, 10_000 + 2 * day).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::DataNotAvailable));
+    }
+
+    #[test]
+    fn test_get_price_at_returns_the_observation_in_effect() {
+        let mut history = PriceHistory::default();
+        let sol = &mut history.assets[AssetType::SOL.index()];
+        for i in 0..HISTORY_CAPACITY as i64 + 2 {
+            sol.push((100 + i as u64) * PRICE_SCALE, 10_000 + i * 60);
+        }
+
+        // Between two observations, the earlier one is returned with its own timestamp
+        let observation = PriceOracle::get_price_at(&history, AssetType::SOL, 10_000 + 10 * 60 + 59).unwrap();
+        assert_eq!(observation, PriceObservation { price: 110 * PRICE_SCALE, timestamp: 10_000 + 10 * 60 });
+        // Across the wrap: the newest sits in the first slots of the ring
+        let newest = HISTORY_CAPACITY as i64 + 1;
+        let observation = PriceOracle::get_price_at(&history, AssetType::SOL, 20_000).unwrap();
+        assert_eq!(observation, PriceObservation { price: (100 + newest as u64) * PRICE_SCALE, timestamp: 10_000 + newest * 60 });
+
+        // Before the oldest held observation, which was overwritten, and with no history
+        let err = PriceOracle::get_price_at(&history, AssetType::SOL, 10_000 + 60).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::DataNotAvailable));
+        let err = PriceOracle::get_price_at(&history, AssetType::MSOL, 20_000).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::DataNotAvailable));
+    }
+
+    #[test]
+    fn test_get_average_apy_samples_the_served_apy() {
+        let mut history = ApyHistory::default();
+        let err = PriceOracle::initialize_apy_history(&mut history, 0, 254).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::InvalidConfig));
+        PriceOracle::initialize_apy_history(&mut history, 3, 254).unwrap();
+        assert_eq!((history.min_samples, history.bump), (3, 254));
+
+        let mut data = PriceOracleData::default();
+        let day = 86_400;
+        for (i, apy) in [70_000_000, 72_000_000, 74_050_000, 71_000_000].into_iter().enumerate() {
+            data.price_data[AssetType::MSOL.index()].apy = apy;
+            PriceOracle::record_apy_history(&mut history, &data, &[AssetType::MSOL], 10_000 + i as i64 * day);
+        }
+        // An update soon after the latest sample is not sampled
+        data.price_data[AssetType::MSOL.index()].apy = 500_000_000;
+        PriceOracle::record_apy_history(&mut history, &data, &[AssetType::MSOL], 10_000 + 3 * day + 60);
+
+        let now = 10_000 + 3 * day + 120;
+        // (700 + 720 + 741 + 710) / 4
+        assert_eq!(PriceOracle::get_average_apy(&history, AssetType::MSOL, 7 * day, now).unwrap(), 717);
+        // (741 + 710) / 2, below the minimum of three
+        let err = PriceOracle::get_average_apy(&history, AssetType::MSOL, 2 * day, now).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::DataNotAvailable));
+        assert_eq!(PriceOracle::set_min_apy_samples(&mut history, 2).unwrap(), 3);
+        assert_eq!(PriceOracle::get_average_apy(&history, AssetType::MSOL, 2 * day, now).unwrap(), 725);
+
+        let err = PriceOracle::get_average_apy(&history, AssetType::SOL, 7 * day, now).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::DataNotAvailable));
+        let err = PriceOracle::get_average_apy(&history, AssetType::MSOL, 0, now).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::InvalidConfig));
+    }
+
+    #[test]
+    fn test_snapshot_epoch_once_per_epoch() {
+        let mut data = PriceOracleData::default();
+        let mut snapshots = EpochSnapshots { asset_type: AssetType::MSOL, bump: 0, snapshots: Vec::new() };
+        let clock = Clock { epoch: 600, ..clock_at(10_000, 5_000) };
+
+        // Nothing to snapshot before the first update
+        let err = PriceOracle::snapshot_epoch(&mut snapshots, &data, AssetType::MSOL, 254, &clock).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::DataNotAvailable));
+
+        data.price_data[AssetType::MSOL.index()].record_price(180 * PRICE_SCALE, PriceProvenance::default(), Confidence::default(), clock.unix_timestamp, &clock);
+        data.price_data[AssetType::MSOL.index()].apy = 72_000_000;
+        let snapshot = PriceOracle::snapshot_epoch(&mut snapshots, &data, AssetType::MSOL, 254, &clock).unwrap();
+        assert_eq!(snapshot, EpochSnapshot { epoch: 600, price: 180 * PRICE_SCALE, apy: 72_000_000, quote: QuoteCurrency::SOL });
+        assert_eq!(snapshots.bump, 254);
+
+        // A later price in the same epoch does not overwrite the snapshot
+        data.price_data[AssetType::MSOL.index()].record_price(181 * PRICE_SCALE, PriceProvenance::default(), Confidence::default(), clock.unix_timestamp, &clock);
+        let err = PriceOracle::snapshot_epoch(&mut snapshots, &data, AssetType::MSOL, 254, &clock).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::AlreadySnapshotted));
+
+        let next_epoch = Clock { epoch: 601, ..clock.clone() };
+        PriceOracle::snapshot_epoch(&mut snapshots, &data, AssetType::MSOL, 254, &next_epoch).unwrap();
+        assert_eq!(PriceOracle::get_epoch_price(&snapshots, 600).unwrap(), QuotedPrice { price: 180 * PRICE_SCALE, quote: QuoteCurrency::SOL });
+        assert_eq!(PriceOracle::get_epoch_price(&snapshots, 601).unwrap(), QuotedPrice { price: 181 * PRICE_SCALE, quote: QuoteCurrency::SOL });
+        let err = PriceOracle::get_epoch_price(&snapshots, 599).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::DataNotAvailable));
+    }
+
+    #[test]
+    fn test_get_price_lenient_flags_instead_of_failing() {
+        let mut header = oracle_header();
+        header.max_age_secs[AssetType::MSOL.index()] = 60;
+        let mut data = quoted_data();
+        data.price_data[AssetType::MSOL.index()].record_price(1_200_000_000, PriceProvenance::default(), Confidence::default(), 1_000, &clock_at(1_000, 5_000));
+
+        let fresh = PriceOracle::get_price_lenient(&header, &data, AssetType::MSOL, &clock_at(1_060, 5_150)).unwrap();
+        assert_eq!(fresh, LenientPrice {
+            price: 1_200_000_000,
+            quote: QuoteCurrency::SOL,
+            last_update_time: 1_000,
+            feed_timestamp: 1_000,
+            staleness_secs: 60,
+            degraded: false,
+        });
+        // Where the strict getter fails, the lenient one still serves the price
+        let clock = clock_at(4_600, 14_000);
+        let err = PriceOracle::get_price_no_older_than(&data, AssetType::MSOL, MaxAge::Seconds(60), false, &clock).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::StaleData));
+        let stale = PriceOracle::get_price_lenient(&header, &data, AssetType::MSOL, &clock).unwrap();
+        assert_eq!((stale.price, stale.staleness_secs, stale.degraded), (1_200_000_000, 3_600, true));
+        // A clock behind the update counts as no staleness
+        let behind = PriceOracle::get_price_lenient(&header, &data, AssetType::MSOL, &clock_at(900, 4_800)).unwrap();
+        assert_eq!((behind.staleness_secs, behind.degraded), (0, false));
+
+        let err = PriceOracle::get_price_lenient(&header, &data, AssetType::BSOL, &clock).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::PriceNotAvailable));
+        PriceOracle::delist_asset(&mut data, AssetType::MSOL).unwrap();
+        let err = PriceOracle::get_price_lenient(&header, &data, AssetType::MSOL, &clock).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::AssetDelisted));
+    }
+
+    #[test]
+    fn test_assert_price_fresh() {
+        let mut header = oracle_header();
+        let mut data = quoted_data();
+        data.price_data[AssetType::MSOL.index()].record_price(1_200_000_000, PriceProvenance::default(), Confidence::default(), 1_000, &clock_at(1_000, 5_000));
+
+        PriceOracle::assert_price_fresh(&header, &data, AssetType::MSOL, 30, false, &clock_at(1_030, 5_075)).unwrap();
+        let err = PriceOracle::assert_price_fresh(&header, &data, AssetType::MSOL, 30, false, &clock_at(1_031, 5_077)).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::StaleData));
+        // Never updated is not the same as stale
+        let err = PriceOracle::assert_price_fresh(&header, &data, AssetType::BSOL, 30, false, &clock_at(1_030, 5_075)).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::PriceNotAvailable));
+
+        header.emergency_stop = true;
+        let err = PriceOracle::assert_price_fresh(&header, &data, AssetType::MSOL, 30, false, &clock_at(1_000, 5_000)).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::EmergencyStop));
+        header.emergency_stop = false;
+
+        PriceOracle::delist_asset(&mut data, AssetType::MSOL).unwrap();
+        let err = PriceOracle::assert_price_fresh(&header, &data, AssetType::MSOL, 30, false, &clock_at(1_000, 5_000)).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::AssetDelisted));
+    }
+
+    #[test]
+    fn test_reject_same_slot_is_opt_in() {
+        let header = oracle_header();
+        let mut data = quoted_data();
+        data.price_data[AssetType::MSOL.index()].record_price(1_200_000_000, PriceProvenance::default(), Confidence::default(), 1_000, &clock_at(1_000, 5_000));
+        let same_slot = clock_at(1_000, 5_000);
+        let next_slot = clock_at(1_000, 5_001);
+
+        // Without the flag a price written in this slot is served as usual
+        PriceOracle::assert_price_fresh(&header, &data, AssetType::MSOL, 30, false, &same_slot).unwrap();
+        PriceOracle::get_price_no_older_than(&data, AssetType::MSOL, MaxAge::Seconds(30), false, &same_slot).unwrap();
+
+        let err = PriceOracle::assert_price_fresh(&header, &data, AssetType::MSOL, 30, true, &same_slot).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::PriceUpdatedThisSlot));
+        let err = PriceOracle::get_price_no_older_than(&data, AssetType::MSOL, MaxAge::Slots(100), true, &same_slot).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::PriceUpdatedThisSlot));
+
+        // From the next slot on the flag changes nothing
+        PriceOracle::assert_price_fresh(&header, &data, AssetType::MSOL, 30, true, &next_slot).unwrap();
+        let price = PriceOracle::get_price_no_older_than(&data, AssetType::MSOL, MaxAge::Slots(100), true, &next_slot).unwrap();
+        assert_eq!(price, QuotedPrice { price: 1_200_000_000, quote: QuoteCurrency::SOL });
+        // A stale price still reports staleness first
+        let err = PriceOracle::assert_price_fresh(&header, &data, AssetType::MSOL, 30, true, &clock_at(1_031, 5_000)).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::StaleData));
+    }
+
+    #[test]
+    fn test_delist_asset_keeps_history_readable() {
+        let mut data = quoted_data();
+        let clock = Clock { epoch: 600, ..clock_at(10_000, 5_000) };
+        data.price_data[AssetType::MSOL.index()].record_price(1_200_000_000, PriceProvenance::default(), Confidence::default(), clock.unix_timestamp, &clock);
+        data.price_data[AssetType::MSOL.index()].apy = 72_000_000;
+        let mut snapshots = EpochSnapshots { asset_type: AssetType::MSOL, bump: 0, snapshots: Vec::new() };
+        PriceOracle::snapshot_epoch(&mut snapshots, &data, AssetType::MSOL, 254, &clock).unwrap();
+
+        PriceOracle::delist_asset(&mut data, AssetType::MSOL).unwrap();
+        // Delisting is terminal
+        let err = PriceOracle::delist_asset(&mut data, AssetType::MSOL).unwrap_err();
+        assert_eq!(error_code(&err), u32::from(OracleError::AssetDelisted));
+
+        // Its price and APY are no longer served, alone or as part of a rate
+        let delisted_code = u32::from(OracleError::AssetDelisted);
+        let err = PriceOracle::get_price_no_older_than(&data, AssetType::MSOL, MaxAge::Seconds(60), false, &clock).unwrap_err();
+        assert_eq!(error_code(&err), delisted_code);
+        let err = PriceOracle::get_current_apy_bps(&data, AssetType::MSOL).unwrap_err();
+        assert_eq!(error_code(&err), delisted_code);
+        let err = PriceOracle::get_exchange_rate(&data, AssetType::JitoSOL, AssetType::MSOL, false).unwrap_err();
+        assert_eq!(error_code(&err), delisted_code);
+        let next_epoch = Clock { epoch: 601, ..clock.clone() };
+        let err = PriceOracle::snapshot_epoch(&mut snapshots, &data, AssetType::MSOL, 254, &next_epoch).unwrap_err();
+        assert_eq!(error_code(&err), delisted_code);
+
+        // Its record stays readable, and other assets are unaffected
+        assert_eq!(PriceOracle::get_epoch_price(&snapshots, 600).unwrap(), QuotedPrice { price: 1_200_000_000, quote: QuoteCurrency::SOL });
+        assert_eq!(PriceOracle::get_price_provenance(&data, AssetType::MSOL).unwrap(), PriceProvenance::default());
+        let listed = PriceOracle::get_all_prices(&oracle_header(), &data, &clock);
+        assert!(listed[AssetType::MSOL.index()].delisted);
+        assert!(!listed[AssetType::JitoSOL.index()].delisted);
+        assert_eq!(PriceOracle::get_current_apy_bps(&data, AssetType::JitoSOL).unwrap(), 0);
+    }
+}
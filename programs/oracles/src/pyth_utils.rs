@@ -0,0 +1,249 @@
+//! Reads Pyth pull-oracle prices from the Pyth Solana Receiver program's `PriceUpdateV2`
+//! accounts, whether posted by a keeper or kept current as a price feed account.
+//!
+//! Layout (after the 8-byte Anchor discriminator, Borsh-encoded): `write_authority` (32
+//! bytes), `verification_level` (a tag byte, followed by `num_signatures` for `Partial`),
+//! the `PriceFeedMessage` fields `feed_id` (32), `price` (i64), `conf` (u64), `exponent`
+//! (i32), `publish_time` (i64), `prev_publish_time` (i64), `ema_price` (i64) and
+//! `ema_conf` (u64), then `posted_slot` (u64).
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::pubkey;
+use anchor_lang::solana_program::hash::hash;
+use crate::layout::{read_array, Field};
+use switchboard_v2::SwitchboardDecimal;
+use crate::price_oracle::{Confidence, MaxAge, OracleError, SourcePrice};
+use crate::switchboard_utils::{fixed_to_f64, switchboard_decimal_to_price};
+
+/// Pyth Solana Receiver program, the owner of every price update account
+pub const PYTH_RECEIVER_PROGRAM_ID: Pubkey = pubkey!("rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ");
+/// Pyth SOL/USD feed id, the same on every chain, `ef0d8b6f…c280b56d` in hex. SOL's Pyth
+/// feeds are held to it until the registry binds another id.
+pub const PYTH_SOL_USD_FEED_ID: [u8; 32] = [
+    0xef, 0x0d, 0x8b, 0x6f, 0xda, 0x2c, 0xeb, 0xa4, 0x1d, 0xa1, 0x5d, 0x40, 0x95, 0xd1, 0xda, 0x39,
+    0x2a, 0x0d, 0x2f, 0x8e, 0xd0, 0xc6, 0xc7, 0xbc, 0x0f, 0x4c, 0xfa, 0xc8, 0xc2, 0x80, 0xb5, 0x6d,
+];
+
+/// `verification_level` tag of an update checked against only some of the Wormhole guardians
+const PARTIAL_VERIFICATION: u8 = 0;
+/// `verification_level` tag of an update checked against a quorum of the Wormhole guardians
+const FULL_VERIFICATION: u8 = 1;
+/// Size of the `PriceFeedMessage` and `posted_slot` after the verification level
+pub const PRICE_MESSAGE_LEN: usize = 32 + 8 + 8 + 4 + 8 + 8 + 8 + 8 + 8;
+
+/// Price carried by a Pyth price update account
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PythPriceUpdate {
+    pub feed_id: [u8; 32],
+    /// Raw price with `exponent` as its power of ten
+    pub price: i64,
+    /// Confidence interval around `price`, with the same exponent
+    pub conf: u64,
+    pub exponent: i32,
+    pub publish_time: i64,
+    pub posted_slot: u64,
+    /// Whether a quorum of the Wormhole guardians signed the update
+    pub fully_verified: bool,
+}
+
+impl PythPriceUpdate {
+    /// Price in fixed point with `PRICE_DECIMALS` decimals
+    pub fn fixed_price(&self) -> std::result::Result<u64, OracleError> {
+        to_fixed(i128::from(self.price), self.exponent)
+    }
+
+    /// Confidence interval in fixed point with `PRICE_DECIMALS` decimals
+    pub fn fixed_conf(&self) -> std::result::Result<u64, OracleError> {
+        to_fixed(i128::from(self.conf), self.exponent)
+    }
+}
+
+/// Anchor discriminator of the receiver program's `PriceUpdateV2` account
+pub fn price_update_discriminator() -> [u8; 8] {
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&hash(b"account:PriceUpdateV2").to_bytes()[..8]);
+    discriminator
+}
+
+/// Decodes the price from the raw data of a `PriceUpdateV2` account
+pub fn decode_price_update(data: &[u8]) -> std::result::Result<PythPriceUpdate, OracleError> {
+    if data.len() < 8 + 32 + 1 || data[..8] != price_update_discriminator() {
+        return Err(OracleError::InvalidPythAccount);
+    }
+    let (fully_verified, message_offset) = match data[8 + 32] {
+        PARTIAL_VERIFICATION => (false, 8 + 32 + 2),
+        FULL_VERIFICATION => (true, 8 + 32 + 1),
+        _ => return Err(OracleError::InvalidPythAccount),
+    };
+    let message = data.get(message_offset..message_offset + PRICE_MESSAGE_LEN).ok_or(OracleError::InvalidPythAccount)?;
+
+    let feed_id = Field::at(0, 32);
+    let price = feed_id.then(8);
+    let conf = price.then(8);
+    let exponent = conf.then(4);
+    let publish_time = exponent.then(8);
+    // `prev_publish_time` and the EMA fields are not read
+    let prev_publish_time = publish_time.then(8);
+    let ema_price = prev_publish_time.then(8);
+    let ema_conf = ema_price.then(8);
+    let posted_slot = ema_conf.then(8);
+    Ok(PythPriceUpdate {
+        feed_id: read_array(message, feed_id).ok_or(OracleError::InvalidPythData)?,
+        price: read_array(message, price).map(i64::from_le_bytes).ok_or(OracleError::InvalidPythData)?,
+        conf: read_array(message, conf).map(u64::from_le_bytes).ok_or(OracleError::InvalidPythData)?,
+        exponent: read_array(message, exponent).map(i32::from_le_bytes).ok_or(OracleError::InvalidPythData)?,
+        publish_time: read_array(message, publish_time).map(i64::from_le_bytes).ok_or(OracleError::InvalidPythData)?,
+        posted_slot: read_array(message, posted_slot).map(u64::from_le_bytes).ok_or(OracleError::InvalidPythData)?,
+        fully_verified,
+    })
+}
+
+/// Loads a Pyth price update after checking its owner, that it carries `expected_feed_id`,
+/// that it is fully verified and that it is no older than `max_age`, and returns the price
+/// in fixed point with the slot it was posted in, its publish time and its confidence
+/// interval. An all-zero `expected_feed_id` means no id is bound and refuses every update.
+pub fn get_pyth_price(feed: &AccountInfo, expected_feed_id: &[u8; 32], clock: &Clock, max_age: MaxAge) -> Result<SourcePrice> {
+    if feed.owner != &PYTH_RECEIVER_PROGRAM_ID {
+        msg!("Invalid Pyth account owner: expected {}, found {}", PYTH_RECEIVER_PROGRAM_ID, feed.owner);
+        return Err(error!(OracleError::InvalidPythAccount));
+    }
+
+    let update = decode_price_update(&feed.try_borrow_data()?).map_err(|e| {
+        msg!("Failed to decode Pyth price update {}: {:?}", feed.key, e);
+        error!(e)
+    })?;
+    if update.feed_id != *expected_feed_id || *expected_feed_id == [0; 32] {
+        msg!("Pyth price update {} carries feed id {}, expected {}", feed.key, feed_id_hex(&update.feed_id), feed_id_hex(expected_feed_id));
+        return Err(error!(OracleError::PythFeedIdMismatch));
+    }
+    if !update.fully_verified {
+        msg!("Pyth price update {} is only partially verified", feed.key);
+        return Err(error!(OracleError::InvalidPythData));
+    }
+    if max_age.is_exceeded(update.publish_time, update.posted_slot, clock) {
+        msg!("Pyth price update {} is stale: published at {} (posted in slot {}), max age {:?}",
+            feed.key, update.publish_time, update.posted_slot, max_age);
+        return Err(error!(OracleError::StaleData));
+    }
+
+    let (price, conf) = update.fixed_price().and_then(|price| Ok((price, update.fixed_conf()?))).map_err(|e| {
+        msg!("Pyth price cannot be represented: price={}, conf={}, exponent={}", update.price, update.conf, update.exponent);
+        error!(e)
+    })?;
+    verbose_msg!("Pyth result converted successfully: {}", fixed_to_f64(price));
+    Ok(SourcePrice {
+        price,
+        round_slot: update.posted_slot,
+        round_timestamp: update.publish_time,
+        confidence: Confidence::from_std_deviation(conf),
+    })
+}
+
+/// Lowercase hex of a feed id, as Pyth publishes them
+pub fn feed_id_hex(feed_id: &[u8; 32]) -> String {
+    feed_id.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// `value * 10^exponent` in fixed point, failing for negative or unrepresentable values
+fn to_fixed(value: i128, exponent: i32) -> std::result::Result<u64, OracleError> {
+    let decimal = if exponent <= 0 {
+        SwitchboardDecimal { mantissa: value, scale: exponent.unsigned_abs() }
+    } else {
+        let factor = 10i128.checked_pow(exponent.unsigned_abs()).ok_or(OracleError::InvalidPythData)?;
+        SwitchboardDecimal { mantissa: value.checked_mul(factor).ok_or(OracleError::InvalidPythData)?, scale: 0 }
+    };
+    switchboard_decimal_to_price(&decimal).map_err(|_| OracleError::InvalidPythData)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::price_oracle::ConfidenceStatus;
+    use crate::switchboard_utils::PRICE_SCALE;
+    use crate::test_utils::{clock_at, MockPythPriceUpdate};
+
+    #[test]
+    fn test_decode_price_update_reads_either_verification_level() {
+        let update = MockPythPriceUpdate::new(15_612_345_678, -8, 1_000, 500).conf(4_000_000).feed_id([3; 32]);
+        let decoded = decode_price_update(&update.to_account_data()).unwrap();
+        assert_eq!(decoded, PythPriceUpdate {
+            feed_id: [3; 32],
+            price: 15_612_345_678,
+            conf: 4_000_000,
+            exponent: -8,
+            publish_time: 1_000,
+            posted_slot: 500,
+            fully_verified: true,
+        });
+        assert_eq!((decoded.fixed_price().unwrap(), decoded.fixed_conf().unwrap()), (156_123_456_780, 40_000_000));
+
+        // A partial level carries its signature count, shifting the message by a byte
+        let partial = decode_price_update(&update.partially_verified(5).to_account_data()).unwrap();
+        assert_eq!(partial, PythPriceUpdate { fully_verified: false, ..decoded });
+    }
+
+    #[test]
+    fn test_decode_rejects_malformed_accounts() {
+        let valid = MockPythPriceUpdate::new(1, 0, 1_000, 500).to_account_data();
+
+        let mut wrong_discriminator = valid.clone();
+        wrong_discriminator[0] ^= 0xff;
+        assert!(matches!(decode_price_update(&wrong_discriminator), Err(OracleError::InvalidPythAccount)));
+        let mut unknown_level = valid.clone();
+        unknown_level[8 + 32] = 2;
+        assert!(matches!(decode_price_update(&unknown_level), Err(OracleError::InvalidPythAccount)));
+        assert!(matches!(decode_price_update(&valid[..valid.len() - 1]), Err(OracleError::InvalidPythAccount)));
+        assert!(matches!(decode_price_update(&valid[..8 + 32]), Err(OracleError::InvalidPythAccount)));
+    }
+
+    #[test]
+    fn test_pyth_price_conversion() {
+        let update = |price: i64, exponent: i32| PythPriceUpdate { feed_id: [0; 32], price, conf: 0, exponent, publish_time: 0, posted_slot: 0, fully_verified: true };
+        assert_eq!(update(100, 0).fixed_price().unwrap(), 100 * PRICE_SCALE);
+        assert_eq!(update(15, 1).fixed_price().unwrap(), 150 * PRICE_SCALE);
+        // Rounded half away from zero past 9 decimals
+        assert_eq!(update(15_000_000_050, -11).fixed_price().unwrap(), 150_000_001);
+        assert!(matches!(update(-1, -8).fixed_price(), Err(OracleError::InvalidPythData)));
+        assert!(matches!(update(1, 40).fixed_price(), Err(OracleError::InvalidPythData)));
+    }
+
+    #[test]
+    fn test_get_pyth_price_checks_owner_verification_and_staleness() {
+        let update = MockPythPriceUpdate::new(15_600_000_000, -8, 1_000, 5_000).conf(10_000_000).feed_id(PYTH_SOL_USD_FEED_ID);
+        let mut account = update.into_account(Pubkey::new_unique());
+
+        let price = get_pyth_price(&account.account_info(), &PYTH_SOL_USD_FEED_ID, &clock_at(1_060, 5_100), MaxAge::Seconds(60)).unwrap();
+        assert_eq!(price, SourcePrice {
+            price: 156 * PRICE_SCALE,
+            round_slot: 5_000,
+            round_timestamp: 1_000,
+            confidence: Confidence { value: PRICE_SCALE / 10, status: ConfidenceStatus::Measured },
+        });
+        let err = get_pyth_price(&account.account_info(), &PYTH_SOL_USD_FEED_ID, &clock_at(1_061, 5_100), MaxAge::Seconds(60)).unwrap_err();
+        assert_eq!(err, error!(OracleError::StaleData));
+        let err = get_pyth_price(&account.account_info(), &PYTH_SOL_USD_FEED_ID, &clock_at(1_000, 5_101), MaxAge::Slots(100)).unwrap_err();
+        assert_eq!(err, error!(OracleError::StaleData));
+
+        account.owner = Pubkey::new_unique();
+        let err = get_pyth_price(&account.account_info(), &PYTH_SOL_USD_FEED_ID, &clock_at(1_000, 5_000), MaxAge::Seconds(60)).unwrap_err();
+        assert_eq!(err, error!(OracleError::InvalidPythAccount));
+
+        let partial = MockPythPriceUpdate::new(15_600_000_000, -8, 1_000, 5_000).feed_id(PYTH_SOL_USD_FEED_ID).partially_verified(5);
+        let mut account = partial.into_account(Pubkey::new_unique());
+        let err = get_pyth_price(&account.account_info(), &PYTH_SOL_USD_FEED_ID, &clock_at(1_000, 5_000), MaxAge::Seconds(60)).unwrap_err();
+        assert_eq!(err, error!(OracleError::InvalidPythData));
+    }
+
+    #[test]
+    fn test_get_pyth_price_checks_the_feed_id() {
+        let clock = clock_at(1_000, 5_000);
+        let mut other_feed = MockPythPriceUpdate::new(3_000_000_000, -8, 1_000, 5_000).feed_id([9; 32]).into_account(Pubkey::new_unique());
+        let err = get_pyth_price(&other_feed.account_info(), &PYTH_SOL_USD_FEED_ID, &clock, MaxAge::Seconds(60)).unwrap_err();
+        assert_eq!(err, error!(OracleError::PythFeedIdMismatch));
+        assert_eq!(get_pyth_price(&other_feed.account_info(), &[9; 32], &clock, MaxAge::Seconds(60)).unwrap().price, 30 * PRICE_SCALE);
+
+        // No bound id refuses every update, including one that carries the zero id
+        let mut unset = MockPythPriceUpdate::new(3_000_000_000, -8, 1_000, 5_000).into_account(Pubkey::new_unique());
+        let err = get_pyth_price(&unset.account_info(), &[0; 32], &clock, MaxAge::Seconds(60)).unwrap_err();
+        assert_eq!(err, error!(OracleError::PythFeedIdMismatch));
+    }
+}
@@ -0,0 +1,201 @@
+//! Quotes pushed into an account laid out by the consumer, for integrators that cannot read
+//! the oracle's PDAs.
+//!
+//! Only its owner program may write an account, so the buffer belongs to the oracle, at
+//! `[QUOTE_BUFFER_SEED, instance, authority, id]` for the oracle instance it takes quotes
+//! from. `create_quote_buffer` allocates it with the size
+//! the consumer chooses and records, after the discriminator, its `authority`, a
+//! `layout_version` the consumer is free to use and the `offset` quotes are written at.
+//! The offset must leave the buffer's own fields intact and the quote must fit before the
+//! end of the account, otherwise it fails with `InvalidQuoteBuffer`.
+//!
+//! `write_quote_to` then serializes the asset's current `PushedQuote` at that offset,
+//! leaving every other byte alone. Anyone may send it: it only copies what the oracle
+//! serves, and only into the buffers of the instance it reads, so another instance's admin
+//! cannot push its prices into them. It fails like `get_collateral_value` when the oracle is stopped or the asset is
+//! unpriced, delisted, paused or stale, so a quote in the buffer was fresh when written.
+//!
+//! A quote is 34 bytes, little-endian, from `offset`:
+//!
+//! | Bytes | Field |
+//! |-------|-------|
+//! | 0 | `asset_type`, the `AssetType` discriminant |
+//! | 1 | `quote`, the `QuoteCurrency` discriminant |
+//! | 2..10 | `price`, `u64` with 9 decimals |
+//! | 10..18 | `apy`, `u64` with 9 decimals |
+//! | 18..26 | `timestamp`, `i64`, when a feed round last confirmed the price |
+//! | 26..34 | `nonce`, `u64`, `PriceOracleData::nonce` when written |
+//!
+//! The bytes are zero until the first write.
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::entrypoint::MAX_PERMITTED_DATA_INCREASE;
+use crate::price_oracle::{AssetType, OracleError, PriceOracle, QuoteCurrency};
+
+/// Largest buffer `create_quote_buffer` allocates, the most one instruction can
+pub const MAX_QUOTE_BUFFER_LEN: usize = MAX_PERMITTED_DATA_INCREASE;
+
+/// An asset's quote as `write_quote_to` writes it, see the module documentation
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PushedQuote {
+    pub asset_type: AssetType,
+    pub quote: QuoteCurrency,
+    pub price: u64,
+    pub apy: u64,
+    /// Time the source produced the round that last confirmed the price, which staleness
+    /// is measured from
+    pub timestamp: i64,
+    /// `PriceOracleData::nonce` when the quote was written
+    pub nonce: u64,
+}
+
+impl PushedQuote {
+    /// Serialized size
+    pub const LEN: usize = 1 // asset_type
+        + 1 // quote
+        + 8 // price
+        + 8 // apy
+        + 8 // timestamp
+        + 8; // nonce
+}
+
+/// Fields at the start of a consumer's buffer, see the module documentation
+#[account]
+#[derive(Default, PartialEq, Eq, Debug)]
+pub struct QuoteBuffer {
+    /// Payer of the rent, which created the buffer
+    pub authority: Pubkey,
+    pub id: u64,
+    pub bump: u8,
+    /// Set by the consumer at creation, never read by the oracle
+    pub layout_version: u32,
+    /// Where quotes are written, in bytes from the start of the account
+    pub offset: u32,
+}
+
+impl QuoteBuffer {
+    /// Serialized size, excluding the 8-byte account discriminator
+    pub const LEN: usize = 32 // authority
+        + 8 // id
+        + 1 // bump
+        + 4 // layout_version
+        + 4; // offset
+
+    /// Fails unless a quote at `offset` is clear of the buffer's fields and fits in `space`
+    /// bytes, which the oracle can allocate
+    pub fn validate_layout(space: usize, offset: usize) -> Result<()> {
+        if space > MAX_QUOTE_BUFFER_LEN || offset < 8 + Self::LEN || offset + PushedQuote::LEN > space {
+            msg!("A quote at offset {} does not fit a {}-byte buffer after its {} bytes of fields", offset, space, 8 + Self::LEN);
+            return Err(error!(OracleError::InvalidQuoteBuffer));
+        }
+        Ok(())
+    }
+
+    /// Writes `quote` at the buffer's offset in `account_data`, which must hold a buffer of
+    /// the instance `instance_seed` names at `address`
+    pub fn write_quote(address: &Pubkey, account_data: &mut [u8], instance_seed: &[u8], quote: &PushedQuote) -> Result<()> {
+        let buffer = Self::try_deserialize(&mut &account_data[..])?;
+        let expected = Pubkey::create_program_address(
+            &[PriceOracle::QUOTE_BUFFER_SEED, instance_seed, buffer.authority.as_ref(), &buffer.id.to_le_bytes(), &[buffer.bump]],
+            &crate::ID,
+        );
+        if expected != Ok(*address) {
+            msg!("Account {} is not a quote buffer of this oracle instance", address);
+            return Err(error!(OracleError::InvalidQuoteBuffer));
+        }
+        let offset = buffer.offset as usize;
+        Self::validate_layout(account_data.len(), offset)?;
+        let mut target = &mut account_data[offset..offset + PushedQuote::LEN];
+        quote.serialize(&mut target)?;
+        Ok(())
+    }
+
+    /// The quote at the buffer's offset in `account_data`, zero until the first write
+    pub fn read_quote(account_data: &[u8]) -> Result<PushedQuote> {
+        let offset = Self::try_deserialize(&mut &account_data[..])?.offset as usize;
+        Self::validate_layout(account_data.len(), offset)?;
+        Ok(PushedQuote::deserialize(&mut &account_data[offset..offset + PushedQuote::LEN])?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::price_oracle::DEFAULT_INSTANCE;
+
+    /// A buffer of the default instance and its address
+    fn buffer(space: usize, offset: u32) -> (Pubkey, Vec<u8>) {
+        let authority = Pubkey::new_unique();
+        let (address, bump) = PriceOracle::get_quote_buffer_pda(&crate::ID, &DEFAULT_INSTANCE, &authority, 7);
+        let mut account_data = Vec::new();
+        QuoteBuffer { authority, id: 7, bump, layout_version: 3, offset }.try_serialize(&mut account_data).unwrap();
+        account_data.resize(space, 0xAA);
+        (address, account_data)
+    }
+
+    fn quote() -> PushedQuote {
+        PushedQuote { asset_type: AssetType::MSOL, quote: QuoteCurrency::SOL, price: 1_180_000_000, apy: 78_839_670, timestamp: 1_000, nonce: 42 }
+    }
+
+    #[test]
+    fn test_account_len_matches_serialized_size() {
+        assert_eq!(QuoteBuffer::default().try_to_vec().unwrap().len(), QuoteBuffer::LEN);
+        assert_eq!(quote().try_to_vec().unwrap().len(), PushedQuote::LEN);
+    }
+
+    #[test]
+    fn test_validate_layout_keeps_the_quote_clear_of_the_fields_and_inside_the_account() {
+        let first = 8 + QuoteBuffer::LEN;
+        QuoteBuffer::validate_layout(first + PushedQuote::LEN, first).unwrap();
+        QuoteBuffer::validate_layout(MAX_QUOTE_BUFFER_LEN, MAX_QUOTE_BUFFER_LEN - PushedQuote::LEN).unwrap();
+
+        let invalid = [
+            (first + PushedQuote::LEN, first - 1),
+            (first + PushedQuote::LEN - 1, first),
+            (MAX_QUOTE_BUFFER_LEN + 1, first),
+        ];
+        for (space, offset) in invalid {
+            let err = QuoteBuffer::validate_layout(space, offset).unwrap_err();
+            assert_eq!(err, error!(OracleError::InvalidQuoteBuffer), "{} bytes, offset {}", space, offset);
+        }
+    }
+
+    #[test]
+    fn test_write_quote_only_touches_the_quote_bytes() {
+        let offset = 100;
+        let (address, mut account_data) = buffer(200, offset as u32);
+        let fields = account_data[..8 + QuoteBuffer::LEN].to_vec();
+        QuoteBuffer::write_quote(&address, &mut account_data, &[], &quote()).unwrap();
+
+        assert_eq!(QuoteBuffer::read_quote(&account_data).unwrap(), quote());
+        assert_eq!(account_data[..8 + QuoteBuffer::LEN], fields[..]);
+        assert!(account_data[8 + QuoteBuffer::LEN..offset].iter().all(|byte| *byte == 0xAA));
+        assert!(account_data[offset + PushedQuote::LEN..].iter().all(|byte| *byte == 0xAA));
+        // The documented layout
+        assert_eq!(account_data[offset], AssetType::MSOL as u8);
+        assert_eq!(account_data[offset + 1], QuoteCurrency::SOL as u8);
+        assert_eq!(account_data[offset + 2..offset + 10], 1_180_000_000u64.to_le_bytes());
+        assert_eq!(account_data[offset + 26..offset + 34], 42u64.to_le_bytes());
+    }
+
+    #[test]
+    fn test_write_quote_rejects_other_accounts_and_short_buffers() {
+        // Any other account of the program, or a buffer cut short, is refused
+        let mut not_a_buffer = vec![0u8; 200];
+        assert!(QuoteBuffer::write_quote(&Pubkey::new_unique(), &mut not_a_buffer, &[], &quote()).is_err());
+        let (address, mut short) = buffer(100 + PushedQuote::LEN, 100);
+        short.truncate(100 + PushedQuote::LEN - 1);
+        let err = QuoteBuffer::write_quote(&address, &mut short, &[], &quote()).unwrap_err();
+        assert_eq!(err, error!(OracleError::InvalidQuoteBuffer));
+    }
+
+    #[test]
+    fn test_write_quote_only_fills_buffers_of_its_instance() {
+        let (address, mut account_data) = buffer(200, 100);
+        let other_instance = Pubkey::new_unique();
+        let err = QuoteBuffer::write_quote(&address, &mut account_data, other_instance.as_ref(), &quote()).unwrap_err();
+        assert_eq!(err, error!(OracleError::InvalidQuoteBuffer));
+        let err = QuoteBuffer::write_quote(&Pubkey::new_unique(), &mut account_data, &[], &quote()).unwrap_err();
+        assert_eq!(err, error!(OracleError::InvalidQuoteBuffer));
+        assert!(account_data[100..100 + PushedQuote::LEN].iter().all(|byte| *byte == 0xAA));
+    }
+}
@@ -0,0 +1,81 @@
+//! Opt-in protection for consumers against prices moved in the reading transaction.
+//!
+//! A searcher can bundle a price update with a consumer instruction and trade against the
+//! price they just wrote. Strict getters offer two guards, both off by default:
+//!
+//! - `reject_same_slot` fails when the price was written in the current slot. It also
+//!   catches updates made through CPI, but refuses a consumer that merely lands in the
+//!   same slot as the keeper.
+//! - Passing the instructions sysvar fails when an earlier top-level instruction of the
+//!   transaction is a price update of this program, whatever asset it targets. It never
+//!   refuses because of another transaction, but cannot see updates made through CPI.
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::sysvar::instructions::{load_current_index_checked, load_instruction_at_checked};
+use anchor_lang::Discriminator;
+use crate::price_oracle::OracleError;
+
+/// Discriminators of the instructions that can write a price
+pub const PRICE_UPDATE_DISCRIMINATORS: [[u8; 8]; 7] = [
+    crate::instruction::UpdatePricesAndApys::DISCRIMINATOR,
+    crate::instruction::UpdatePricesAndApysChunk::DISCRIMINATOR,
+    crate::instruction::UpdateSolPrice::DISCRIMINATOR,
+    crate::instruction::UpdateAll::DISCRIMINATOR,
+    crate::instruction::UpdatePriceSigned::DISCRIMINATOR,
+    crate::instruction::UpdateAssets::DISCRIMINATOR,
+    crate::instruction::UpdateAssetFromSources::DISCRIMINATOR,
+];
+
+/// Returns true when `instruction` is one of this program's price updates
+pub fn is_price_update(instruction: &Instruction) -> bool {
+    if instruction.program_id != crate::ID || instruction.data.len() < 8 {
+        return false;
+    }
+    let discriminator = &instruction.data[..8];
+    #[cfg(feature = "mock-oracle")]
+    if discriminator == crate::mock_oracle::set_mock_price_discriminator() {
+        return true;
+    }
+    PRICE_UPDATE_DISCRIMINATORS.iter().any(|update| update[..] == *discriminator)
+}
+
+/// Fails with `PriceUpdatedThisTransaction` when a top-level instruction before the current
+/// one updates prices. `instructions` must be the instructions sysvar.
+pub fn reject_earlier_price_update(instructions: &AccountInfo) -> Result<()> {
+    let current = load_current_index_checked(instructions)?;
+    for index in 0..current {
+        if is_price_update(&load_instruction_at_checked(index as usize, instructions)?) {
+            msg!("Instruction {} of this transaction updates prices before the read at {}", index, current);
+            return Err(error!(OracleError::PriceUpdatedThisTransaction));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::InstructionData;
+    use crate::price_oracle::{AssetType, MaxAge};
+
+    fn oracle_ix(data: Vec<u8>) -> Instruction {
+        Instruction { program_id: crate::ID, accounts: vec![], data }
+    }
+
+    #[test]
+    fn test_is_price_update() {
+        let update = crate::instruction::UpdateSolPrice { expected_price: None, tolerance_bps: 0 }.data();
+        assert!(is_price_update(&oracle_ix(update.clone())));
+        assert!(is_price_update(&oracle_ix(crate::instruction::UpdateAssets {}.data())));
+
+        // Reads, other programs and short data are not updates
+        let read = crate::instruction::GetPriceNoOlderThan {
+            asset_type: AssetType::SOL,
+            max_age: MaxAge::Seconds(60),
+            reject_same_slot: true,
+        }.data();
+        assert!(!is_price_update(&oracle_ix(read)));
+        assert!(!is_price_update(&Instruction { program_id: Pubkey::new_unique(), accounts: vec![], data: update.clone() }));
+        assert!(!is_price_update(&oracle_ix(update[..7].to_vec())));
+    }
+}
@@ -0,0 +1,195 @@
+//! Break-glass prices signed by the trusted publisher, for when Switchboard is down.
+//!
+//! The publisher signs `signed_price_message` off-chain. The transaction carries an Ed25519
+//! program instruction verifying that signature immediately before `update_price_signed`,
+//! which reads it back through the instructions sysvar and checks it covers the trusted
+//! publisher, the supplied signature and the exact message the oracle expects. The runtime
+//! verifies Ed25519 instructions before any program runs, so a transaction carrying a bad
+//! signature never reaches the oracle.
+//!
+//! # Replay protection
+//!
+//! A signature is not tied to a transaction: anyone who sees one can submit it again. The
+//! signed timestamp is what makes a message single-use. It must be within the asset's max
+//! age of the cluster clock and newer than both the stored price and the last signed price
+//! consumed for the asset, which is recorded in the header.
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::ed25519_program;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::sysvar::instructions::{load_current_index_checked, load_instruction_at_checked};
+use crate::price_oracle::{AssetType, OracleError, QuoteCurrency};
+
+/// Prefix of every signed price message, so the publisher key cannot be tricked into
+/// signing a price by signing something else
+pub const SIGNED_PRICE_DOMAIN: &[u8] = b"flexx-oracle:signed-price:v1";
+/// Size of a signed price message
+pub const SIGNED_PRICE_MESSAGE_LEN: usize = SIGNED_PRICE_DOMAIN.len()
+    + 32 // program id
+    + 1 // asset_type
+    + 1 // quote
+    + 8 // price
+    + 8; // timestamp
+
+// Layout of an Ed25519 program instruction carrying one signature: a count and a padding
+// byte, one row of seven u16 offsets, then the key, signature and message
+const ED25519_OFFSETS_START: usize = 2;
+const ED25519_OFFSETS_LEN: usize = 14;
+const ED25519_DATA_START: usize = ED25519_OFFSETS_START + ED25519_OFFSETS_LEN;
+const ED25519_SIGNATURE_LEN: usize = 64;
+/// Instruction index an Ed25519 offset uses to point into its own instruction
+const CURRENT_INSTRUCTION: u16 = u16::MAX;
+
+/// The message the publisher signs for `price` of `asset_type`, quoted in `quote`, as
+/// observed at `timestamp`. The program id keeps it from being accepted by another deployment.
+pub fn signed_price_message(asset_type: AssetType, quote: QuoteCurrency, price: u64, timestamp: i64) -> Vec<u8> {
+    let mut message = Vec::with_capacity(SIGNED_PRICE_MESSAGE_LEN);
+    message.extend_from_slice(SIGNED_PRICE_DOMAIN);
+    message.extend_from_slice(crate::ID.as_ref());
+    message.push(u8::from(asset_type));
+    message.push(quote as u8);
+    message.extend_from_slice(&price.to_le_bytes());
+    message.extend_from_slice(&timestamp.to_le_bytes());
+    message
+}
+
+/// Data of an Ed25519 program instruction verifying `signature` of `message` by `publisher`,
+/// with every offset pointing into the instruction itself
+pub fn ed25519_instruction_data(publisher: &Pubkey, signature: &[u8; 64], message: &[u8]) -> Vec<u8> {
+    let public_key_offset = ED25519_DATA_START;
+    let signature_offset = public_key_offset + 32;
+    let message_offset = signature_offset + ED25519_SIGNATURE_LEN;
+    let offsets = [
+        signature_offset as u16,
+        CURRENT_INSTRUCTION,
+        public_key_offset as u16,
+        CURRENT_INSTRUCTION,
+        message_offset as u16,
+        message.len() as u16,
+        CURRENT_INSTRUCTION,
+    ];
+
+    let mut data = Vec::with_capacity(message_offset + message.len());
+    data.extend_from_slice(&[1, 0]);
+    for offset in offsets {
+        data.extend_from_slice(&offset.to_le_bytes());
+    }
+    data.extend_from_slice(publisher.as_ref());
+    data.extend_from_slice(signature);
+    data.extend_from_slice(message);
+    data
+}
+
+/// Checks `instruction` is an Ed25519 program instruction verifying exactly one signature,
+/// `signature` of `message` by `publisher`, read from its own data
+pub fn check_ed25519_instruction(
+    instruction: &Instruction,
+    publisher: &Pubkey,
+    signature: &[u8; 64],
+    message: &[u8],
+) -> std::result::Result<(), OracleError> {
+    let data = &instruction.data;
+    if instruction.program_id != ed25519_program::ID || data.len() < ED25519_DATA_START || data[0] != 1 {
+        return Err(OracleError::InvalidPublisherSignature);
+    }
+    let offset = |i: usize| {
+        let start = ED25519_OFFSETS_START + 2 * i;
+        u16::from_le_bytes([data[start], data[start + 1]])
+    };
+    let (signature_offset, public_key_offset, message_offset, message_len) =
+        (offset(0) as usize, offset(2) as usize, offset(4) as usize, offset(5) as usize);
+    // Offsets into another instruction would have the runtime verify bytes this check never sees
+    if [offset(1), offset(3), offset(6)].iter().any(|index| *index != CURRENT_INSTRUCTION) {
+        return Err(OracleError::InvalidPublisherSignature);
+    }
+    let field = |start: usize, len: usize| data.get(start..start.saturating_add(len));
+    if field(public_key_offset, 32) != Some(publisher.as_ref())
+        || field(signature_offset, ED25519_SIGNATURE_LEN) != Some(&signature[..])
+        || field(message_offset, message_len) != Some(message)
+    {
+        return Err(OracleError::InvalidPublisherSignature);
+    }
+    Ok(())
+}
+
+/// Checks the instruction just before the current one verified `signature` of `message` by
+/// `publisher`. `instructions` must be the instructions sysvar.
+pub fn verify_publisher_signature(
+    instructions: &AccountInfo,
+    publisher: &Pubkey,
+    signature: &[u8; 64],
+    message: &[u8],
+) -> Result<()> {
+    let current = load_current_index_checked(instructions)?;
+    let verified = match current.checked_sub(1) {
+        Some(index) => check_ed25519_instruction(&load_instruction_at_checked(index as usize, instructions)?, publisher, signature, message),
+        None => Err(OracleError::InvalidPublisherSignature),
+    };
+    verified.map_err(|e| {
+        msg!("Instruction {} must follow an Ed25519 instruction verifying the signature of {} over the price", current, publisher);
+        error!(e)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn verify_ix(publisher: &Pubkey, signature: &[u8; 64], message: &[u8]) -> Instruction {
+        Instruction {
+            program_id: ed25519_program::ID,
+            accounts: vec![],
+            data: ed25519_instruction_data(publisher, signature, message),
+        }
+    }
+
+    #[test]
+    fn test_signed_price_message_layout() {
+        let message = signed_price_message(AssetType::SOL, QuoteCurrency::USD, 156_105_238_500, 1_700_000_000);
+        assert_eq!(message.len(), SIGNED_PRICE_MESSAGE_LEN);
+        assert!(message.starts_with(SIGNED_PRICE_DOMAIN));
+        let fields = &message[SIGNED_PRICE_DOMAIN.len()..];
+        assert_eq!(&fields[..32], crate::ID.as_ref());
+        assert_eq!(fields[32..34], [6, 0]);
+        assert_eq!(fields[34..42], 156_105_238_500u64.to_le_bytes());
+        assert_eq!(fields[42..], 1_700_000_000i64.to_le_bytes());
+
+        // Every field is covered
+        assert_ne!(message, signed_price_message(AssetType::JitoSOL, QuoteCurrency::USD, 156_105_238_500, 1_700_000_000));
+        assert_ne!(message, signed_price_message(AssetType::SOL, QuoteCurrency::SOL, 156_105_238_500, 1_700_000_000));
+        assert_ne!(message, signed_price_message(AssetType::SOL, QuoteCurrency::USD, 156_105_238_501, 1_700_000_000));
+        assert_ne!(message, signed_price_message(AssetType::SOL, QuoteCurrency::USD, 156_105_238_500, 1_700_000_001));
+    }
+
+    #[test]
+    fn test_check_ed25519_instruction() {
+        let publisher = Pubkey::new_unique();
+        let signature = [9u8; 64];
+        let message = signed_price_message(AssetType::MSOL, QuoteCurrency::SOL, 1_180_000_000, 1_700_000_000);
+        let ix = verify_ix(&publisher, &signature, &message);
+        assert!(check_ed25519_instruction(&ix, &publisher, &signature, &message).is_ok());
+
+        let other_message = signed_price_message(AssetType::MSOL, QuoteCurrency::SOL, 1_190_000_000, 1_700_000_000);
+        let mismatches = [
+            check_ed25519_instruction(&ix, &Pubkey::new_unique(), &signature, &message),
+            check_ed25519_instruction(&ix, &publisher, &[8u8; 64], &message),
+            check_ed25519_instruction(&ix, &publisher, &signature, &other_message),
+            check_ed25519_instruction(&ix, &publisher, &signature, &message[..message.len() - 1]),
+        ];
+        for result in mismatches {
+            assert!(matches!(result, Err(OracleError::InvalidPublisherSignature)));
+        }
+
+        // Another program, several signatures, truncated data or offsets into another instruction
+        let mut wrong_program = ix.clone();
+        wrong_program.program_id = Pubkey::new_unique();
+        let mut two_signatures = ix.clone();
+        two_signatures.data[0] = 2;
+        let mut truncated = ix.clone();
+        truncated.data.truncate(ED25519_DATA_START + 40);
+        let mut external_message = ix.clone();
+        external_message.data[ED25519_OFFSETS_START + 12..ED25519_DATA_START].copy_from_slice(&0u16.to_le_bytes());
+        for wrong in [wrong_program, two_signatures, truncated, external_message] {
+            assert!(matches!(check_ed25519_instruction(&wrong, &publisher, &signature, &message), Err(OracleError::InvalidPublisherSignature)));
+        }
+    }
+}
@@ -0,0 +1,124 @@
+//! Reads the intrinsic exchange rate of SPL stake pool tokens straight from the pool account.
+//!
+//! Layout (Borsh, no discriminator): `account_type` (`1` for a stake pool), three authority
+//! keys, the withdraw bump seed and five more keys, followed by `total_lamports`,
+//! `pool_token_supply` and `last_update_epoch`. Only that prefix is read.
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::pubkey;
+use crate::layout::{self, Field};
+use crate::price_oracle::OracleError;
+use crate::switchboard_utils::{fixed_to_f64, PRICE_SCALE};
+
+/// SPL stake pool program, the owner of every pool account
+pub const STAKE_POOL_PROGRAM_ID: Pubkey = pubkey!("SPoo1Ku8WFXoNDMHPsrGSTSG1Y47rzgn41SLUNakuHy");
+
+/// `AccountType::StakePool`
+const STAKE_POOL_ACCOUNT_TYPE: u8 = 1;
+
+// Offsets into the pool account
+pub const TOTAL_LAMPORTS_OFFSET: usize = 1 + 32 * 3 + 1 + 32 * 5;
+const POOL_TOKEN_SUPPLY_OFFSET: usize = TOTAL_LAMPORTS_OFFSET + 8;
+const LAST_UPDATE_EPOCH_OFFSET: usize = POOL_TOKEN_SUPPLY_OFFSET + 8;
+/// Size of the prefix read from a pool account
+pub const STAKE_POOL_PREFIX_LEN: usize = LAST_UPDATE_EPOCH_OFFSET + 8;
+
+/// Backing of a stake pool's token as of its last epoch update
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct StakePoolRate {
+    pub total_lamports: u64,
+    pub pool_token_supply: u64,
+    pub last_update_epoch: u64,
+}
+
+impl StakePoolRate {
+    /// SOL per pool token in fixed point with `PRICE_DECIMALS` decimals, truncated
+    pub fn price(&self) -> std::result::Result<u64, OracleError> {
+        if self.pool_token_supply == 0 {
+            return Err(OracleError::InvalidStakePoolData);
+        }
+        let price = self.total_lamports as u128 * PRICE_SCALE as u128 / self.pool_token_supply as u128;
+        u64::try_from(price).map_err(|_| OracleError::ConversionOverflow)
+    }
+}
+
+/// Decodes the exchange rate from the raw data of a stake pool account
+pub fn decode_stake_pool(data: &[u8]) -> std::result::Result<StakePoolRate, OracleError> {
+    if data.len() < STAKE_POOL_PREFIX_LEN || data[0] != STAKE_POOL_ACCOUNT_TYPE {
+        return Err(OracleError::InvalidStakePoolAccount);
+    }
+    Ok(StakePoolRate {
+        total_lamports: read_u64(data, TOTAL_LAMPORTS_OFFSET)?,
+        pool_token_supply: read_u64(data, POOL_TOKEN_SUPPLY_OFFSET)?,
+        last_update_epoch: read_u64(data, LAST_UPDATE_EPOCH_OFFSET)?,
+    })
+}
+
+/// Loads the stake pool registered as `expected` after checking its key and owner, and
+/// returns the intrinsic SOL value of one pool token in fixed point
+pub fn get_stake_pool_price(pool: &AccountInfo, expected: &Pubkey) -> Result<u64> {
+    if pool.key != expected {
+        msg!("Stake pool {} is not the registered pool {}", pool.key, expected);
+        return Err(error!(OracleError::InvalidStakePoolAccount));
+    }
+    if pool.owner != &STAKE_POOL_PROGRAM_ID {
+        msg!("Invalid stake pool account owner: expected {}, found {}", STAKE_POOL_PROGRAM_ID, pool.owner);
+        return Err(error!(OracleError::InvalidStakePoolAccount));
+    }
+
+    let rate = decode_stake_pool(&pool.try_borrow_data()?).map_err(|e| {
+        msg!("Failed to decode stake pool {}: {:?}", pool.key, e);
+        error!(e)
+    })?;
+    let price = rate.price().map_err(|e| {
+        msg!("Stake pool {} has no exchange rate: {} lamports, {} pool tokens", pool.key, rate.total_lamports, rate.pool_token_supply);
+        error!(e)
+    })?;
+    verbose_msg!("Stake pool {} rate as of epoch {}: {}", pool.key, rate.last_update_epoch, fixed_to_f64(price));
+    Ok(price)
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> std::result::Result<u64, OracleError> {
+    layout::read_u64(bytes, Field::at(offset, 8)).ok_or(OracleError::InvalidStakePoolAccount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::MockStakePool;
+
+    #[test]
+    fn test_decode_stake_pool_rate() {
+        let data = MockStakePool::new(1_150_000_000_000, 1_000_000_000_000).last_update_epoch(600).to_account_data();
+        let rate = decode_stake_pool(&data).unwrap();
+        assert_eq!(rate, StakePoolRate { total_lamports: 1_150_000_000_000, pool_token_supply: 1_000_000_000_000, last_update_epoch: 600 });
+        assert_eq!(rate.price().unwrap(), 1_150_000_000);
+
+        // Truncated toward zero
+        assert_eq!(StakePoolRate { total_lamports: 2, pool_token_supply: 3, last_update_epoch: 0 }.price().unwrap(), 666_666_666);
+        let empty = StakePoolRate { total_lamports: 0, pool_token_supply: 0, last_update_epoch: 0 };
+        assert!(matches!(empty.price(), Err(OracleError::InvalidStakePoolData)));
+    }
+
+    #[test]
+    fn test_decode_rejects_other_accounts() {
+        let valid = MockStakePool::new(1, 1).to_account_data();
+        assert!(matches!(decode_stake_pool(&valid[..STAKE_POOL_PREFIX_LEN - 1]), Err(OracleError::InvalidStakePoolAccount)));
+        // A validator list belongs to the same program but is not a pool
+        let mut validator_list = valid.clone();
+        validator_list[0] = 2;
+        assert!(matches!(decode_stake_pool(&validator_list), Err(OracleError::InvalidStakePoolAccount)));
+    }
+
+    #[test]
+    fn test_get_stake_pool_price_checks_key_and_owner() {
+        let key = Pubkey::new_unique();
+        let mut account = MockStakePool::new(1_100, 1_000).into_account(key);
+        assert_eq!(get_stake_pool_price(&account.account_info(), &key).unwrap(), 1_100_000_000);
+
+        let err = get_stake_pool_price(&account.account_info(), &Pubkey::new_unique()).unwrap_err();
+        assert_eq!(err, error!(OracleError::InvalidStakePoolAccount));
+        account.owner = Pubkey::new_unique();
+        let err = get_stake_pool_price(&account.account_info(), &key).unwrap_err();
+        assert_eq!(err, error!(OracleError::InvalidStakePoolAccount));
+    }
+}
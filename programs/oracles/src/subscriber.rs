@@ -0,0 +1,299 @@
+//! Subscriber program called back after the fixed-feed updates.
+//!
+//! When the keeper passes the `subscriber` account to `update_prices_and_apys` or
+//! `update_sol_price` and the registration is enabled, an update that wrote at least one
+//! asset ends with a CPI into `program_id`. The instruction data is the registered
+//! discriminator followed by a Borsh `PriceCallback`. The accounts are the oracle header,
+//! signed by its PDA so the subscriber can tell the call came from the oracle, followed by
+//! the registered accounts in order.
+//!
+//! # Required and best-effort callbacks
+//!
+//! A `required` registration fails the update when the callback cannot be delivered: the
+//! remaining accounts do not match or the subscriber program is not executable. A
+//! best-effort one skips the callback instead, emits `SubscriberSkipped` and keeps the
+//! update, see `Subscriber::check_deliverable`.
+//!
+//! Neither can isolate the update from the callback itself. A failed CPI cannot be caught:
+//! a callback that errors or exhausts the compute budget fails the whole transaction, the
+//! price update included. Leaving out the `subscriber` account (the crank's
+//! `--no-subscriber`), or disabling the registration, brings updates back without a callback.
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program::invoke_signed;
+use anchor_lang::error::ErrorCode;
+use crate::price_oracle::{AssetType, OracleError, PriceOracle, PriceOracleData, QuoteCurrency};
+
+/// Accounts a subscriber can have passed to its callback after the oracle header
+pub const MAX_SUBSCRIBER_ACCOUNTS: usize = 8;
+
+/// The subscriber registration, set by the admin with `set_subscriber`
+#[account]
+#[derive(Default, Debug, PartialEq, Eq)]
+pub struct Subscriber {
+    pub program_id: Pubkey,
+    /// First eight bytes of the callback's instruction data
+    pub discriminator: [u8; 8],
+    /// Accounts passed after the oracle header; only the first `account_count` are used
+    pub accounts: [Pubkey; MAX_SUBSCRIBER_ACCOUNTS],
+    /// Bit `i` marks `accounts[i]` writable
+    pub writable_mask: u8,
+    pub account_count: u8,
+    /// Updates call the subscriber back only while set
+    pub enabled: bool,
+    /// Whether an update whose callback cannot be delivered fails, rather than skipping it
+    pub required: bool,
+    pub bump: u8,
+}
+
+/// An asset written by the update, as stored after it
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CallbackPrice {
+    pub asset_type: AssetType,
+    /// Fixed point with `PRICE_DECIMALS` decimals, in `quote`
+    pub price: u64,
+    /// Fixed point with `PRICE_DECIMALS` decimals
+    pub apy: u64,
+    pub quote: QuoteCurrency,
+}
+
+/// Instruction data of the callback, after the registered discriminator
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Debug)]
+pub struct PriceCallback {
+    pub timestamp: i64,
+    pub prices: Vec<CallbackPrice>,
+}
+
+impl PriceCallback {
+    /// The stored price and APY of each of `assets`
+    pub fn new(data: &PriceOracleData, assets: &[AssetType], timestamp: i64) -> Self {
+        let prices = assets.iter().map(|asset_type| {
+            let price_data = &data.price_data[asset_type.index()];
+            CallbackPrice { asset_type: *asset_type, price: price_data.price, apy: price_data.apy, quote: price_data.quote }
+        }).collect();
+        PriceCallback { timestamp, prices }
+    }
+}
+
+impl Subscriber {
+    /// Serialized size, excluding the 8-byte account discriminator
+    pub const LEN: usize = 32 // program_id
+        + 8 // discriminator
+        + 32 * MAX_SUBSCRIBER_ACCOUNTS // accounts
+        + 1 // writable_mask
+        + 1 // account_count
+        + 1 // enabled
+        + 1 // required
+        + 1; // bump
+
+    /// Replaces the registration. The subscriber can be neither this program, which could
+    /// not be re-entered, nor the default key.
+    #[allow(clippy::too_many_arguments)]
+    pub fn set(
+        &mut self,
+        program_id: Pubkey,
+        discriminator: [u8; 8],
+        accounts: &[Pubkey],
+        writable_mask: u8,
+        enabled: bool,
+        required: bool,
+        bump: u8,
+    ) -> std::result::Result<(), OracleError> {
+        if program_id == Pubkey::default() || program_id == crate::ID {
+            return Err(OracleError::InvalidSubscriber);
+        }
+        if accounts.len() > MAX_SUBSCRIBER_ACCOUNTS || (writable_mask as u16) >> accounts.len() != 0 {
+            return Err(OracleError::InvalidSubscriber);
+        }
+        self.program_id = program_id;
+        self.discriminator = discriminator;
+        self.accounts = [Pubkey::default(); MAX_SUBSCRIBER_ACCOUNTS];
+        self.accounts[..accounts.len()].copy_from_slice(accounts);
+        self.writable_mask = writable_mask;
+        self.account_count = accounts.len() as u8;
+        self.enabled = enabled;
+        self.required = required;
+        self.bump = bump;
+        Ok(())
+    }
+
+    /// The registered accounts, in the order they are passed
+    pub fn callback_accounts(&self) -> &[Pubkey] {
+        &self.accounts[..self.account_count as usize]
+    }
+
+    pub fn is_writable(&self, index: usize) -> bool {
+        self.writable_mask & (1 << index) != 0
+    }
+
+    /// Metas of the registered accounts, writable where registered so
+    pub fn callback_account_metas(&self) -> Vec<AccountMeta> {
+        self.callback_accounts().iter().enumerate().map(|(index, key)| {
+            if self.is_writable(index) {
+                AccountMeta::new(*key, false)
+            } else {
+                AccountMeta::new_readonly(*key, false)
+            }
+        }).collect()
+    }
+
+    /// The callback instruction, with `header` as its first, signing account
+    pub fn callback_instruction(&self, header: Pubkey, callback: &PriceCallback) -> Result<Instruction> {
+        let mut accounts = vec![AccountMeta::new_readonly(header, true)];
+        accounts.extend(self.callback_account_metas());
+        let mut data = self.discriminator.to_vec();
+        callback.serialize(&mut data).map_err(|_| error!(ErrorCode::InstructionDidNotSerialize))?;
+        Ok(Instruction { program_id: self.program_id, accounts, data })
+    }
+
+    /// Checks `accounts` are the subscriber program followed by the registered accounts,
+    /// writable where registered so
+    pub fn check_callback_accounts(&self, accounts: &[AccountInfo]) -> std::result::Result<(), OracleError> {
+        let registered = self.callback_accounts();
+        if accounts.len() != registered.len() + 1 || *accounts[0].key != self.program_id {
+            return Err(OracleError::InvalidSubscriberAccounts);
+        }
+        for (index, (account, key)) in accounts[1..].iter().zip(registered).enumerate() {
+            if account.key != key || (self.is_writable(index) && !account.is_writable) {
+                return Err(OracleError::InvalidSubscriberAccounts);
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks what can be checked before the CPI: the accounts match the registration and
+    /// the subscriber program is executable
+    pub fn check_deliverable(&self, accounts: &[AccountInfo]) -> std::result::Result<(), OracleError> {
+        self.check_callback_accounts(accounts)?;
+        if !accounts[0].executable {
+            return Err(OracleError::InvalidSubscriberAccounts);
+        }
+        Ok(())
+    }
+
+    /// Calls the subscriber back, `accounts` being the program followed by the registered
+    /// accounts and `header` signing with its PDA seeds
+    pub fn invoke<'info>(
+        &self,
+        header: &AccountInfo<'info>,
+        instance_seed: &[u8],
+        header_bump: u8,
+        accounts: &[AccountInfo<'info>],
+        callback: &PriceCallback,
+    ) -> Result<()> {
+        self.check_deliverable(accounts).map_err(|e| {
+            msg!("Callback accounts do not match subscriber {}: expected the executable program and {} account(s)", self.program_id, self.account_count);
+            error!(e)
+        })?;
+        let instruction = self.callback_instruction(*header.key, callback)?;
+        let mut infos = Vec::with_capacity(accounts.len() + 1);
+        infos.push(header.clone());
+        infos.extend_from_slice(accounts);
+        invoke_signed(&instruction, &infos, &[&[PriceOracle::HEADER_SEED, instance_seed, &[header_bump]]])?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::MockAccount;
+
+    fn registered(accounts: &[Pubkey], writable_mask: u8) -> Subscriber {
+        let mut subscriber = Subscriber::default();
+        subscriber.set(Pubkey::new_unique(), [7; 8], accounts, writable_mask, true, true, 254).unwrap();
+        subscriber
+    }
+
+    #[test]
+    fn test_set_validates_the_registration() {
+        let accounts = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let subscriber = registered(&accounts, 0b10);
+        assert_eq!(subscriber.callback_accounts(), &accounts);
+        assert!(!subscriber.is_writable(0));
+        assert!(subscriber.is_writable(1));
+
+        let mut data = Vec::new();
+        subscriber.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), 8 + Subscriber::LEN);
+
+        let mut rejected = Subscriber::default();
+        let full = [Pubkey::new_unique(); MAX_SUBSCRIBER_ACCOUNTS];
+        assert!(rejected.set(Pubkey::new_unique(), [0; 8], &full, u8::MAX, true, false, 0).is_ok());
+        let too_many = [Pubkey::new_unique(); MAX_SUBSCRIBER_ACCOUNTS + 1];
+        assert!(matches!(rejected.set(Pubkey::new_unique(), [0; 8], &too_many, 0, true, false, 0), Err(OracleError::InvalidSubscriber)));
+        assert!(matches!(rejected.set(Pubkey::new_unique(), [0; 8], &accounts, 0b100, true, false, 0), Err(OracleError::InvalidSubscriber)));
+        assert!(matches!(rejected.set(crate::ID, [0; 8], &accounts, 0, true, false, 0), Err(OracleError::InvalidSubscriber)));
+        assert!(matches!(rejected.set(Pubkey::default(), [0; 8], &accounts, 0, true, false, 0), Err(OracleError::InvalidSubscriber)));
+
+        // Shrinking the account list clears the dropped keys
+        let mut shrunk = subscriber;
+        shrunk.set(Pubkey::new_unique(), [0; 8], &accounts[..1], 0, false, false, 254).unwrap();
+        assert_eq!(shrunk.accounts[1], Pubkey::default());
+        assert!(!shrunk.enabled && !shrunk.required);
+    }
+
+    #[test]
+    fn test_callback_instruction_layout() {
+        let accounts = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let subscriber = registered(&accounts, 0b01);
+        let header = Pubkey::new_unique();
+        let mut data = PriceOracleData::default();
+        data.price_data[AssetType::SOL.index()].price = 156_105_238_500;
+        data.price_data[AssetType::SOL.index()].apy = 70_000_000;
+        let callback = PriceCallback::new(&data, &[AssetType::SOL], 1_700_000_000);
+        assert_eq!(callback.prices, vec![CallbackPrice {
+            asset_type: AssetType::SOL,
+            price: 156_105_238_500,
+            apy: 70_000_000,
+            quote: data.price_data[AssetType::SOL.index()].quote,
+        }]);
+
+        let instruction = subscriber.callback_instruction(header, &callback).unwrap();
+        assert_eq!(instruction.program_id, subscriber.program_id);
+        assert_eq!(instruction.accounts, vec![
+            AccountMeta::new_readonly(header, true),
+            AccountMeta::new(accounts[0], false),
+            AccountMeta::new_readonly(accounts[1], false),
+        ]);
+        assert_eq!(instruction.data[..8], [7; 8]);
+        assert_eq!(PriceCallback::try_from_slice(&instruction.data[8..]).unwrap(), callback);
+    }
+
+    #[test]
+    fn test_check_callback_accounts() {
+        let accounts = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let subscriber = registered(&accounts, 0);
+        let mut program = MockAccount::new(subscriber.program_id, Pubkey::new_unique(), vec![]);
+        let mut first = MockAccount::new(accounts[0], Pubkey::new_unique(), vec![]);
+        let mut second = MockAccount::new(accounts[1], Pubkey::new_unique(), vec![]);
+        let mut stranger = MockAccount::new(Pubkey::new_unique(), Pubkey::new_unique(), vec![]);
+        let (program, first, second, stranger) = (program.account_info(), first.account_info(), second.account_info(), stranger.account_info());
+
+        assert!(subscriber.check_callback_accounts(&[program.clone(), first.clone(), second.clone()]).is_ok());
+        // Delivery also needs the program to be executable
+        let deliverable = subscriber.check_deliverable(&[program.clone(), first.clone(), second.clone()]);
+        assert!(matches!(deliverable, Err(OracleError::InvalidSubscriberAccounts)));
+        let mut executable = program.clone();
+        executable.executable = true;
+        assert!(subscriber.check_deliverable(&[executable.clone(), first.clone(), second.clone()]).is_ok());
+        assert!(matches!(subscriber.check_deliverable(&[executable, first.clone()]), Err(OracleError::InvalidSubscriberAccounts)));
+        for wrong in [
+            vec![program.clone(), first.clone()],
+            vec![program.clone(), second.clone(), first.clone()],
+            vec![stranger.clone(), first.clone(), second.clone()],
+            vec![program.clone(), first.clone(), second.clone(), stranger.clone()],
+        ] {
+            assert!(matches!(subscriber.check_callback_accounts(&wrong), Err(OracleError::InvalidSubscriberAccounts)));
+        }
+
+        // A registered writable account passed read-only is refused before the CPI
+        let writable = registered(&accounts, 0b10);
+        let mut writable_program = MockAccount::new(writable.program_id, Pubkey::new_unique(), vec![]);
+        let writable_program = writable_program.account_info();
+        let mut read_only = second.clone();
+        read_only.is_writable = false;
+        assert!(writable.check_callback_accounts(&[writable_program.clone(), first.clone(), second]).is_ok());
+        assert!(matches!(writable.check_callback_accounts(&[writable_program, first, read_only]), Err(OracleError::InvalidSubscriberAccounts)));
+    }
+}
@@ -1,31 +1,136 @@
 use anchor_lang::prelude::*;
-use switchboard_v2::{AggregatorAccountData, SwitchboardDecimal};
+use anchor_lang::solana_program::pubkey;
+use anchor_lang::solana_program::hash::hash;
+use anchor_lang::error::ErrorCode;
+use anchor_lang::Discriminator;
+use std::cell::Ref;
+use anchor_spl::token::TokenAccount;
+use switchboard_v2::{AggregatorAccountData, AggregatorHistoryBuffer, AggregatorRound, LeaseAccountData, SwitchboardDecimal};
 use std::convert::TryInto;
-use crate::price_oracle::OracleError;
+use crate::layout::{read_array, read_u8, Field};
+use crate::price_oracle::{Confidence, MaxAge, OracleError, SourcePrice};
 
-pub const DEVNET_AGGREGATOR_PUBKEY: &str = "4NiWaTuje7SVe9DN1vfnX7m1qBC7DnUxwRxbdgEDUGX1";
-pub const SOL_PRICE_AGGREGATOR_PUBKEY: &str = "GvDMxPzN1sCj7L26YDK2HnMRXEQmQ2aemov8YBtPS7vR";
+pub const DEVNET_AGGREGATOR_PUBKEY: Pubkey = pubkey!("4NiWaTuje7SVe9DN1vfnX7m1qBC7DnUxwRxbdgEDUGX1");
+pub const SOL_PRICE_AGGREGATOR_PUBKEY: Pubkey = pubkey!("GvDMxPzN1sCj7L26YDK2HnMRXEQmQ2aemov8YBtPS7vR");
 pub const DEFAULT_DEVNET_QUEUE: &str = "EYiAmGSdsQTuCw413V5BzaruWuCCSDgTPtBGvLkXHbe7";
 
+/// Number of decimals in the fixed-point representation of prices and APYs
+pub const PRICE_DECIMALS: u32 = 9;
+/// `10^PRICE_DECIMALS`, the fixed-point value of 1.0
+pub const PRICE_SCALE: u64 = 1_000_000_000;
+/// Largest `SwitchboardDecimal` scale accepted by the conversions
+pub const MAX_SUPPORTED_SCALE: u32 = 28;
+
+/// Switchboard On-Demand program, the owner of every pull feed account
+pub const SWITCHBOARD_ON_DEMAND_PROGRAM_ID: Pubkey = pubkey!("SBondMDrcV3K4kxZR1HNVT7osZxAHVHgYXL5Ze1oMUv");
+/// Decimals of the `i128` values in an On-Demand pull feed
+pub const ON_DEMAND_DECIMALS: u32 = 18;
+
+/// Positions in an On-Demand `PullFeedAccountData` account, discriminator included. The
+/// account is zero-copy (`repr(C)`); only the fields the oracle reads are listed.
+pub mod pull_feed {
+    use crate::layout::Field;
+
+    /// The 32 latest oracle submissions, 64 bytes each
+    const SUBMISSIONS: Field = Field::at(8, 32 * 64);
+    const AUTHORITY: Field = SUBMISSIONS.then(32);
+    pub const QUEUE: Field = AUTHORITY.then(32);
+    // feed_hash, initialized_at, permissions, max_variance, min_responses, name, padding
+    // and historical_result_idx
+    pub const MIN_SAMPLE_SIZE: Field = QUEUE.then(32 + 8 + 8 + 8 + 4 + 32 + 2 + 1).then(1);
+    pub const LAST_UPDATE_TIMESTAMP: Field = MIN_SAMPLE_SIZE.then(8);
+    // lut_slot and reserved bytes, then the `CurrentResult`
+    pub const VALUE: Field = LAST_UPDATE_TIMESTAMP.then(8 + 32).then(16);
+    pub const STD_DEV: Field = VALUE.then(16);
+    // mean, range, min_value and max_value
+    pub const NUM_SAMPLES: Field = STD_DEV.then(16 * 4).then(1);
+    /// Slot of the submissions behind the result
+    pub const SLOT: Field = NUM_SAMPLES.then(1 + 6).then(8);
+    /// Size of the account: the result's min and max slots, the staleness limit, the
+    /// historical results and reserved space follow
+    pub const LEN: usize = SLOT.end() + 16 + 16 + 32 * 16 + 288;
+}
+
+/// Read access to a Switchboard aggregator account.
+///
+/// Unlike `AccountLoader`, it does not require the compiled-in `SWITCHBOARD_PROGRAM_ID`
+/// as owner: callers check the owner against `header.switchboard_program_id` (see
+/// `PriceOracle::load_feed`), so feeds keep loading after `set_switchboard_program_id`.
+#[derive(Clone)]
+pub struct AggregatorFeed<'info> {
+    info: AccountInfo<'info>,
+}
+
+impl<'info> AggregatorFeed<'info> {
+    pub fn new(info: &AccountInfo<'info>) -> Self {
+        AggregatorFeed { info: info.clone() }
+    }
+
+    pub fn key(&self) -> Pubkey {
+        *self.info.key
+    }
+
+    pub fn to_account_info(&self) -> AccountInfo<'info> {
+        self.info.clone()
+    }
+
+    /// Borrows the aggregator after checking its discriminator
+    pub fn load(&self) -> Result<Ref<'_, AggregatorAccountData>> {
+        let data = self.info.try_borrow_data()?;
+        if data.len() < 8 + std::mem::size_of::<AggregatorAccountData>() {
+            return Err(ErrorCode::AccountDiscriminatorNotFound.into());
+        }
+        if data[..8] != AggregatorAccountData::discriminator() {
+            return Err(ErrorCode::AccountDiscriminatorMismatch.into());
+        }
+        Ok(Ref::map(data, |data| {
+            bytemuck::from_bytes(&data[8..8 + std::mem::size_of::<AggregatorAccountData>()])
+        }))
+    }
+
+    /// Slot the latest confirmed round opened in
+    pub fn latest_round_slot(&self) -> Result<u64> {
+        Ok(self.load()?.latest_confirmed_round.round_open_slot)
+    }
+
+    /// Time the latest confirmed round opened, on the aggregator's clock
+    pub fn latest_round_timestamp(&self) -> Result<i64> {
+        Ok(self.load()?.latest_confirmed_round.round_open_timestamp)
+    }
+
+    /// Std deviation of the oracle responses in the latest confirmed round, fixed point
+    pub fn latest_round_std_deviation(&self) -> Result<u64> {
+        let std_deviation = self.load()?.latest_confirmed_round.std_deviation;
+        switchboard_decimal_to_price(&std_deviation).map_err(|e| {
+            msg!("Invalid std deviation in Switchboard feed {}: {:?}", self.key(), e);
+            error!(e)
+        })
+    }
+}
+
+/// A single feed value in fixed point with `PRICE_DECIMALS` decimals
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
 pub struct SwitchboardResult {
-    pub value: f64,
+    pub value: u64,
 }
 
 impl SwitchboardResult {
-    pub fn new(value: f64) -> Self {
+    pub fn new(value: u64) -> Self {
         SwitchboardResult { value }
     }
 }
 
+/// Multi-asset feed values in fixed point with `PRICE_DECIMALS` decimals. The aggregator
+/// publishes them in the response slots of its latest confirmed round, see
+/// `parse_multi_asset_round`.
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
 pub struct MultiAssetResult {
-    pub prices: [f64; 6],
-    pub apys: [f64; 6],
+    pub prices: [u64; 6],
+    pub apys: [u64; 6],
 }
 
 pub fn get_switchboard_result(
-    switchboard_feed: &AccountLoader<AggregatorAccountData>,
+    switchboard_feed: &AggregatorFeed,
 ) -> Result<SwitchboardResult> {
     let feed = switchboard_feed.load().map_err(|e| {
         msg!("Failed to load Switchboard feed: {:?}", e);
@@ -44,26 +149,28 @@ pub fn get_switchboard_result(
 }
 
 pub fn get_multi_asset_result(
-    switchboard_feed: &AccountLoader<AggregatorAccountData>,
+    switchboard_feed: &AggregatorFeed,
 ) -> Result<MultiAssetResult> {
     let feed = switchboard_feed.load().map_err(|e| {
         msg!("Failed to load Switchboard feed: {:?}", e);
         Error::from(OracleError::InvalidAccountData)
     })?;
 
-    let result = feed.get_result().map_err(|e| {
+    // The round must still have enough oracle responses to count as a result
+    feed.get_result().map_err(|e| {
         msg!("Failed to get result from Switchboard feed: {:?}", e);
         Error::from(OracleError::InvalidAccountData)
     })?;
 
-    parse_multi_asset_data(&result).map_err(|e| {
+    let round = feed.latest_confirmed_round;
+    parse_multi_asset_round(&round).map_err(|e| {
         msg!("Failed to parse multi-asset data: {:?}", e);
         Error::from(OracleError::InvalidSwitchboardData)
     })
 }
 
 pub fn get_sol_price(
-    switchboard_feed: &AccountLoader<AggregatorAccountData>,
+    switchboard_feed: &AggregatorFeed,
 ) -> Result<SwitchboardResult> {
     let feed = switchboard_feed.load().map_err(|e| {
         msg!("Failed to load Switchboard feed for SOL price: {:?}", e);
@@ -81,76 +188,296 @@ pub fn get_sol_price(
     })
 }
 
-fn switchboard_decimal_to_result(decimal: &SwitchboardDecimal) -> std::result::Result<SwitchboardResult, OracleError> {
-    let mantissa = decimal.mantissa;
-    let scale = decimal.scale;
+/// Loads a single-value feed after checking it belongs to the expected queue and
+/// that its latest confirmed round is no older than `max_age`
+pub fn get_validated_feed_result(
+    switchboard_feed: &AggregatorFeed,
+    expected_queue: &Pubkey,
+    clock: &Clock,
+    max_age: MaxAge,
+) -> Result<SwitchboardResult> {
+    let feed = switchboard_feed.load().map_err(|e| {
+        msg!("Failed to load Switchboard feed {}: {:?}", switchboard_feed.key(), e);
+        Error::from(OracleError::InvalidAccountData)
+    })?;
 
-    let value = (mantissa as f64) * 10f64.powi(-(scale as i32));
-    
-    if value.is_finite() {
-        msg!("Switchboard result converted successfully: {}", value);
-        Ok(SwitchboardResult { value })
-    } else {
-        msg!("Switchboard result is not a finite number: mantissa={}, scale={}", mantissa, scale);
-        Err(OracleError::InvalidSwitchboardData)
+    if feed.queue_pubkey != *expected_queue {
+        msg!("Switchboard feed {} belongs to queue {}, expected {}", switchboard_feed.key(), feed.queue_pubkey, expected_queue);
+        return Err(error!(OracleError::InvalidSwitchboardAccount));
     }
+
+    let round_open_timestamp = feed.latest_confirmed_round.round_open_timestamp;
+    let round_open_slot = feed.latest_confirmed_round.round_open_slot;
+    if max_age.is_exceeded(round_open_timestamp, round_open_slot, clock) {
+        msg!("Switchboard feed {} is stale: round opened at {} (slot {}), max age {:?}",
+            switchboard_feed.key(), round_open_timestamp, round_open_slot, max_age);
+        return Err(error!(OracleError::StaleData));
+    }
+
+    let result = feed.get_result().map_err(|e| {
+        msg!("Failed to get result from Switchboard feed: {:?}", e);
+        Error::from(OracleError::InvalidAccountData)
+    })?;
+
+    switchboard_decimal_to_result(&result).map_err(|e| {
+        msg!("Failed to convert Switchboard result: {:?}", e);
+        Error::from(OracleError::InvalidSwitchboardData)
+    })
 }
 
-fn parse_sol_price(decimal: &SwitchboardDecimal) -> std::result::Result<SwitchboardResult, OracleError> {
-    let result_str = switchboard_decimal_to_string(decimal)?;
-    
-    // Parse the JSON string
-    let json: serde_json::Value = serde_json::from_str(&result_str)
-        .map_err(|_| OracleError::InvalidSwitchboardData)?;
-    
-    // Extract the "result" field
-    let result = json["result"].as_str()
-        .ok_or(OracleError::InvalidSwitchboardData)?;
-    
-    // Parse the result as f64
-    let value = result.parse::<f64>()
-        .map_err(|_| OracleError::InvalidSwitchboardData)?;
-    
-    Ok(SwitchboardResult { value })
-}
-
-fn parse_multi_asset_data(decimal: &SwitchboardDecimal) -> std::result::Result<MultiAssetResult, OracleError> {
-    let result_str = switchboard_decimal_to_string(decimal)?;
-    let values: Vec<f64> = result_str
-        .split(',')
-        .filter_map(|s| s.trim().parse().ok())
+/// Checks `history_buffer` is the buffer configured on the aggregator and returns the
+/// median of the rows recorded within `window` seconds of `current_time`, if any
+pub fn get_history_median(
+    switchboard_feed: &AggregatorFeed,
+    history_buffer: &AccountInfo,
+    current_time: i64,
+    window: i64,
+) -> Result<Option<u64>> {
+    let expected_buffer = switchboard_feed.load().map_err(|e| {
+        msg!("Failed to load Switchboard feed: {:?}", e);
+        Error::from(OracleError::InvalidAccountData)
+    })?.history_buffer;
+    if expected_buffer == Pubkey::default() || history_buffer.key() != expected_buffer {
+        msg!("History buffer {} does not match the aggregator's configured buffer {}", history_buffer.key(), expected_buffer);
+        return Err(error!(OracleError::InvalidHistoryBuffer));
+    }
+
+    let buffer = AggregatorHistoryBuffer::new(history_buffer).map_err(|e| {
+        msg!("Failed to load Switchboard history buffer: {:?}", e);
+        Error::from(OracleError::InvalidHistoryBuffer)
+    })?;
+
+    let mut values: Vec<u64> = buffer.rows.iter()
+        .filter(|row| row.timestamp > 0 && current_time.saturating_sub(row.timestamp) <= window)
+        .filter_map(|row| {
+            let value = row.value;
+            switchboard_decimal_to_result(&value).ok().map(|result| result.value)
+        })
         .collect();
 
-    if values.len() != 12 {
-        return Err(OracleError::InvalidSwitchboardData);
+    Ok(median(&mut values))
+}
+
+/// Checks `lease` is the lease funding `switchboard_feed` and `escrow` its token escrow,
+/// and returns the escrow balance. The lease must be owned by `switchboard_program_id`.
+pub fn get_lease_balance(
+    switchboard_feed: &AggregatorFeed,
+    lease: &AccountInfo,
+    escrow: &AccountInfo,
+    switchboard_program_id: &Pubkey,
+) -> Result<u64> {
+    if lease.owner != switchboard_program_id {
+        msg!("Invalid lease account owner: expected {}, found {}", switchboard_program_id, lease.owner);
+        return Err(error!(OracleError::InvalidLeaseAccount));
+    }
+    let (lease_aggregator, lease_escrow) = {
+        let data = lease.try_borrow_data()?;
+        if data.len() < 8 + std::mem::size_of::<LeaseAccountData>() || data[..8] != LeaseAccountData::discriminator() {
+            msg!("{} is not a Switchboard lease account", lease.key());
+            return Err(error!(OracleError::InvalidLeaseAccount));
+        }
+        let lease_data: &LeaseAccountData = bytemuck::from_bytes(&data[8..8 + std::mem::size_of::<LeaseAccountData>()]);
+        (lease_data.aggregator, lease_data.escrow)
+    };
+    if lease_aggregator != switchboard_feed.key() {
+        msg!("Lease {} funds aggregator {}, not {}", lease.key(), lease_aggregator, switchboard_feed.key());
+        return Err(error!(OracleError::InvalidLeaseAccount));
+    }
+    if escrow.key() != lease_escrow || escrow.owner != &anchor_spl::token::ID {
+        msg!("Escrow {} does not match the lease's escrow {}", escrow.key(), lease_escrow);
+        return Err(error!(OracleError::InvalidLeaseAccount));
     }
 
-    let prices = [
-        values[0], values[2], values[4], values[6], values[8], values[10]
-    ];
-    let apys = [
-        values[1], values[3], values[5], values[7], values[9], values[11]
-    ];
+    let escrow_account = TokenAccount::try_deserialize(&mut &escrow.try_borrow_data()?[..]).map_err(|e| {
+        msg!("Failed to load lease escrow: {:?}", e);
+        Error::from(OracleError::InvalidLeaseAccount)
+    })?;
+    Ok(escrow_account.amount)
+}
 
-    Ok(MultiAssetResult { prices, apys })
+/// Latest result of an On-Demand pull feed, values with `ON_DEMAND_DECIMALS` decimals
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PullFeedResult {
+    pub queue: Pubkey,
+    pub value: i128,
+    pub std_dev: i128,
+    pub num_samples: u8,
+    /// Fewest samples the feed's own configuration accepts for a result
+    pub min_sample_size: u8,
+    pub slot: u64,
+    pub last_update_timestamp: i64,
 }
 
-fn switchboard_decimal_to_string(decimal: &SwitchboardDecimal) -> std::result::Result<String, OracleError> {
+/// Anchor discriminator of the On-Demand program's `PullFeedAccountData` account
+pub fn pull_feed_discriminator() -> [u8; 8] {
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&hash(b"account:PullFeedAccountData").to_bytes()[..8]);
+    discriminator
+}
+
+/// Decodes the latest result from the raw data of a `PullFeedAccountData` account
+pub fn decode_pull_feed(data: &[u8]) -> std::result::Result<PullFeedResult, OracleError> {
+    if data.len() < pull_feed::SLOT.end() || data[..8] != pull_feed_discriminator() {
+        return Err(OracleError::InvalidSwitchboardAccount);
+    }
+    let read_i128 = |field: Field| read_array(data, field).map(i128::from_le_bytes).ok_or(OracleError::InvalidSwitchboardData);
+    Ok(PullFeedResult {
+        queue: read_array(data, pull_feed::QUEUE).map(Pubkey::new_from_array).ok_or(OracleError::InvalidSwitchboardData)?,
+        value: read_i128(pull_feed::VALUE)?,
+        std_dev: read_i128(pull_feed::STD_DEV)?,
+        num_samples: read_u8(data, pull_feed::NUM_SAMPLES).ok_or(OracleError::InvalidSwitchboardData)?,
+        min_sample_size: read_u8(data, pull_feed::MIN_SAMPLE_SIZE).ok_or(OracleError::InvalidSwitchboardData)?,
+        slot: read_array(data, pull_feed::SLOT).map(u64::from_le_bytes).ok_or(OracleError::InvalidSwitchboardData)?,
+        last_update_timestamp: read_array(data, pull_feed::LAST_UPDATE_TIMESTAMP).map(i64::from_le_bytes).ok_or(OracleError::InvalidSwitchboardData)?,
+    })
+}
+
+/// Loads an On-Demand pull feed after checking that it is the configured `expected_feed`,
+/// its owner, that it belongs to `expected_queue`, that it has as many samples as its
+/// configuration asks for and that its result is no older than `max_age`, and returns the
+/// value in fixed point with the slot and time of the result and its std deviation
+pub fn get_pull_feed_price(feed: &AccountInfo, expected_feed: &Pubkey, expected_queue: &Pubkey, clock: &Clock, max_age: MaxAge) -> Result<SourcePrice> {
+    if feed.key != expected_feed {
+        msg!("Switchboard pull feed {} is not the configured feed {}", feed.key, expected_feed);
+        return Err(error!(OracleError::UnregisteredFeed));
+    }
+    if feed.owner != &SWITCHBOARD_ON_DEMAND_PROGRAM_ID {
+        msg!("Invalid Switchboard On-Demand account owner: expected {}, found {}", SWITCHBOARD_ON_DEMAND_PROGRAM_ID, feed.owner);
+        return Err(error!(OracleError::InvalidSwitchboardAccount));
+    }
+
+    let result = decode_pull_feed(&feed.try_borrow_data()?).map_err(|e| {
+        msg!("Failed to decode Switchboard pull feed {}: {:?}", feed.key, e);
+        error!(e)
+    })?;
+    if *expected_queue == Pubkey::default() || result.queue != *expected_queue {
+        msg!("Switchboard pull feed {} belongs to queue {}, expected {}", feed.key, result.queue, expected_queue);
+        return Err(error!(OracleError::InvalidSwitchboardAccount));
+    }
+    if result.num_samples == 0 || result.num_samples < result.min_sample_size {
+        msg!("Switchboard pull feed {} has {} samples, needs {}", feed.key, result.num_samples, result.min_sample_size.max(1));
+        return Err(error!(OracleError::InvalidSwitchboardData));
+    }
+    if max_age.is_exceeded(result.last_update_timestamp, result.slot, clock) {
+        msg!("Switchboard pull feed {} is stale: updated at {} (slot {}), max age {:?}",
+            feed.key, result.last_update_timestamp, result.slot, max_age);
+        return Err(error!(OracleError::StaleData));
+    }
+
+    let to_price = |mantissa: i128| switchboard_decimal_to_price(&SwitchboardDecimal { mantissa, scale: ON_DEMAND_DECIMALS });
+    let (price, std_dev) = to_price(result.value).and_then(|price| Ok((price, to_price(result.std_dev)?))).map_err(|e| {
+        msg!("Switchboard pull feed value cannot be represented: value={}, std_dev={}", result.value, result.std_dev);
+        error!(e)
+    })?;
+    Ok(SourcePrice {
+        price,
+        round_slot: result.slot,
+        round_timestamp: result.last_update_timestamp,
+        confidence: Confidence::from_std_deviation(std_dev),
+    })
+}
+
+/// Median of `values`, averaging (rounding down) the two middle elements for even lengths
+pub fn median(values: &mut [u64]) -> Option<u64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_unstable();
+    let mid = values.len() / 2;
+    if values.len() % 2 == 1 {
+        Some(values[mid])
+    } else {
+        Some(((values[mid - 1] as u128 + values[mid] as u128) / 2) as u64)
+    }
+}
+
+/// Converts a decimal to a signed fixed-point integer with `target_decimals` decimals using
+/// integer math only. Dropped precision is rounded half away from zero; scales above
+/// `MAX_SUPPORTED_SCALE` and overflowing results are rejected.
+pub fn switchboard_decimal_to_fixed(
+    decimal: &SwitchboardDecimal,
+    target_decimals: u32,
+) -> std::result::Result<i128, OracleError> {
     let mantissa = decimal.mantissa;
     let scale = decimal.scale;
+    if scale > MAX_SUPPORTED_SCALE || target_decimals > MAX_SUPPORTED_SCALE {
+        return Err(OracleError::InvalidSwitchboardData);
+    }
 
-    let value = (mantissa as f64) * 10f64.powi(-(scale as i32));
-    
-    if value.is_finite() {
-        Ok(value.to_string())
+    if scale > target_decimals {
+        let divisor = 10i128.pow(scale - target_decimals);
+        let quotient = mantissa / divisor;
+        let remainder = mantissa % divisor;
+        if remainder.unsigned_abs() * 2 >= divisor.unsigned_abs() {
+            Ok(quotient + mantissa.signum())
+        } else {
+            Ok(quotient)
+        }
     } else {
-        Err(OracleError::InvalidSwitchboardData)
+        mantissa
+            .checked_mul(10i128.pow(target_decimals - scale))
+            .ok_or(OracleError::InvalidSwitchboardData)
     }
 }
 
+/// Converts a decimal to a non-negative fixed-point value with `PRICE_DECIMALS` decimals
+pub fn switchboard_decimal_to_price(decimal: &SwitchboardDecimal) -> std::result::Result<u64, OracleError> {
+    let value = switchboard_decimal_to_fixed(decimal, PRICE_DECIMALS)?;
+    u64::try_from(value).map_err(|_| OracleError::InvalidSwitchboardData)
+}
+
+/// Lossy float view of a fixed-point value, for display in logs only
+pub fn fixed_to_f64(value: u64) -> f64 {
+    value as f64 / PRICE_SCALE as f64
+}
+
+fn switchboard_decimal_to_result(decimal: &SwitchboardDecimal) -> std::result::Result<SwitchboardResult, OracleError> {
+    let mantissa = decimal.mantissa;
+    let scale = decimal.scale;
+
+    match switchboard_decimal_to_price(decimal) {
+        Ok(value) => {
+            verbose_msg!("Switchboard result converted successfully: {}", fixed_to_f64(value));
+            Ok(SwitchboardResult { value })
+        }
+        Err(e) => {
+            msg!("Switchboard result cannot be represented: mantissa={}, scale={}", mantissa, scale);
+            Err(e)
+        }
+    }
+}
+
+/// The SOL feed reports the price itself, so its decimal is rescaled directly
+fn parse_sol_price(decimal: &SwitchboardDecimal) -> std::result::Result<SwitchboardResult, OracleError> {
+    switchboard_decimal_to_price(decimal).map(SwitchboardResult::new)
+}
+
+/// Reads the multi-asset payload from a confirmed round: the price of feed position `i` is
+/// the response in slot `2 * i` and its yield the one in slot `2 * i + 1`. Each of the twelve
+/// slots must be fulfilled, and is rescaled with `switchboard_decimal_to_price`.
+fn parse_multi_asset_round(round: &AggregatorRound) -> std::result::Result<MultiAssetResult, OracleError> {
+    let (responses, fulfilled) = (round.medians_data, round.medians_fulfilled);
+    let value = |slot: usize| {
+        if !fulfilled[slot] {
+            return Err(OracleError::InvalidSwitchboardData);
+        }
+        switchboard_decimal_to_price(&responses[slot])
+    };
+
+    let mut result = MultiAssetResult { prices: [0; 6], apys: [0; 6] };
+    for position in 0..result.prices.len() {
+        result.prices[position] = value(2 * position)?;
+        result.apys[position] = value(2 * position + 1)?;
+    }
+
+    Ok(result)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::price_oracle::ConfidenceStatus;
+    use crate::test_utils::{clock_at, MockAggregator, MockPullFeed};
 
     #[test]
     fn test_switchboard_decimal_to_result() {
@@ -159,7 +486,7 @@ mod tests {
             scale: 5,
         };
         let result = switchboard_decimal_to_result(&decimal).unwrap();
-        assert_eq!(result.value, 123.4);
+        assert_eq!(result.value, 123_400_000_000);
 
         let invalid_decimal = SwitchboardDecimal {
             mantissa: i128::MAX,
@@ -169,23 +496,179 @@ mod tests {
     }
 
     #[test]
-    fn test_parse_multi_asset_data() {
-        let decimal = SwitchboardDecimal {
-            mantissa: 8114553583522887934,
-            scale: 17,
-        };
-        let result = parse_multi_asset_data(&decimal).unwrap();
-        assert_eq!(result.prices.len(), 6);
-        assert_eq!(result.apys.len(), 6);
+    fn test_parse_multi_asset_round() {
+        let prices = [156_558_200_000, 1, 2, 3, 4, u64::MAX];
+        let apys = [78_839_671, 0, 5, 6, 7, 8];
+        let round = MockAggregator::new().multi_asset_payload(prices, apys).build().latest_confirmed_round;
+        let result = parse_multi_asset_round(&round).unwrap();
+        assert_eq!((result.prices, result.apys), (prices, apys));
+
+        // Any scale is rescaled: 0.07883967045 rounds half up on the first dropped digit
+        let mut round = round;
+        round.medians_data[3] = decimal(7_883_967_045, 11);
+        round.medians_data[4] = decimal(1_565_582, 4);
+        let result = parse_multi_asset_round(&round).unwrap();
+        assert_eq!((result.apys[1], result.prices[2]), (78_839_670, 156_558_200_000));
+
+        // Every slot must carry a response, and none may be negative
+        let mut unfulfilled = round;
+        unfulfilled.medians_fulfilled[11] = false;
+        assert!(matches!(parse_multi_asset_round(&unfulfilled), Err(OracleError::InvalidSwitchboardData)));
+        let mut negative = round;
+        negative.medians_data[0] = decimal(-1, 9);
+        assert!(matches!(parse_multi_asset_round(&negative), Err(OracleError::InvalidSwitchboardData)));
+        // A single result never fills the payload
+        let single = MockAggregator::new().result(8_114_553_583_522_887_934, 17).build().latest_confirmed_round;
+        assert!(matches!(parse_multi_asset_round(&single), Err(OracleError::InvalidSwitchboardData)));
+    }
+
+    #[test]
+    fn test_median() {
+        assert_eq!(median(&mut []), None);
+        assert_eq!(median(&mut [3]), Some(3));
+        assert_eq!(median(&mut [5, 1, 3]), Some(3));
+        assert_eq!(median(&mut [4, 1, 3, 2]), Some(2));
+        assert_eq!(median(&mut [u64::MAX, u64::MAX]), Some(u64::MAX));
+    }
+
+    fn decimal(mantissa: i128, scale: u32) -> SwitchboardDecimal {
+        SwitchboardDecimal { mantissa, scale }
+    }
+
+    #[test]
+    fn test_fixed_conversion_around_f64_mantissa_limit() {
+        // 2^53 + 1 is the first integer an f64 cannot represent
+        let two_pow_53: i128 = 1 << 53;
+        for mantissa in [two_pow_53 - 1, two_pow_53, two_pow_53 + 1, two_pow_53 + 3] {
+            assert_eq!(switchboard_decimal_to_fixed(&decimal(mantissa, 9), 9).ok(), Some(mantissa));
+            assert_eq!(switchboard_decimal_to_fixed(&decimal(mantissa * 10, 10), 9).ok(), Some(mantissa));
+        }
+
+        // A 29-digit mantissa keeps every digit that fits in the target precision
+        assert_eq!(
+            switchboard_decimal_to_fixed(&decimal(15_610_523_850_000_000_000_000_000_001, 26), 9).ok(),
+            Some(156_105_238_500)
+        );
+        assert_eq!(
+            switchboard_decimal_to_fixed(&decimal(8_114_553_583_522_887_934, 17), 9).ok(),
+            Some(81_145_535_835)
+        );
+    }
+
+    #[test]
+    fn test_fixed_conversion_rounding() {
+        // Half away from zero on the first dropped digit
+        assert_eq!(switchboard_decimal_to_fixed(&decimal(1_234_567_894, 10), 9).ok(), Some(123_456_789));
+        assert_eq!(switchboard_decimal_to_fixed(&decimal(1_234_567_895, 10), 9).ok(), Some(123_456_790));
+        assert_eq!(switchboard_decimal_to_fixed(&decimal(1_234_567_899_999, 13), 9).ok(), Some(123_456_790));
+        // Scaling up is exact
+        assert_eq!(switchboard_decimal_to_fixed(&decimal(15, 1), 9).ok(), Some(1_500_000_000));
+        assert_eq!(switchboard_decimal_to_fixed(&decimal(7, 0), 9).ok(), Some(7_000_000_000));
+    }
+
+    #[test]
+    fn test_fixed_conversion_negative_mantissa() {
+        assert_eq!(switchboard_decimal_to_fixed(&decimal(-1_234_567_894, 10), 9).ok(), Some(-123_456_789));
+        assert_eq!(switchboard_decimal_to_fixed(&decimal(-1_234_567_895, 10), 9).ok(), Some(-123_456_790));
+        assert_eq!(switchboard_decimal_to_fixed(&decimal(-15, 1), 9).ok(), Some(-1_500_000_000));
+        // Prices can never be negative
+        assert!(switchboard_decimal_to_price(&decimal(-1, 9)).is_err());
+        assert!(switchboard_decimal_to_result(&decimal(-1, 9)).is_err());
+    }
+
+    #[test]
+    fn test_fixed_conversion_scale_and_overflow_limits() {
+        assert!(switchboard_decimal_to_fixed(&decimal(1, MAX_SUPPORTED_SCALE), 9).is_ok());
+        assert!(switchboard_decimal_to_fixed(&decimal(1, MAX_SUPPORTED_SCALE + 1), 9).is_err());
+        assert!(switchboard_decimal_to_fixed(&decimal(1, u32::MAX), 9).is_err());
+        // Largest scale with the largest mantissa rounds instead of overflowing
+        assert!(switchboard_decimal_to_fixed(&decimal(i128::MAX, MAX_SUPPORTED_SCALE), 9).is_ok());
+        assert!(switchboard_decimal_to_fixed(&decimal(i128::MIN, MAX_SUPPORTED_SCALE), 9).is_ok());
+        // Scaling up a huge mantissa overflows i128
+        assert!(switchboard_decimal_to_fixed(&decimal(i128::MAX / 10, 0), 9).is_err());
+        // Values beyond u64 are rejected as prices
+        assert!(switchboard_decimal_to_price(&decimal(u64::MAX as i128 + 1, 9)).is_err());
+        assert_eq!(switchboard_decimal_to_price(&decimal(u64::MAX as i128, 9)).ok(), Some(u64::MAX));
     }
 
     #[test]
     fn test_parse_sol_price() {
-        let decimal = SwitchboardDecimal {
-            mantissa: 15610523850000000000000000000,
-            scale: 26,
+        let parse = |mantissa, scale| parse_sol_price(&decimal(mantissa, scale)).map(|result| result.value);
+        assert_eq!(parse(15_610_523_850_000_000_000_000_000_000, 26).ok(), Some(156_105_238_500));
+        assert_eq!(parse(1_565_582, 4).ok(), Some(156_558_200_000));
+        assert_eq!(parse(156, 0).ok(), Some(156_000_000_000));
+        // Rounded half away from zero on the first dropped digit
+        assert_eq!(parse(1_561_052_385_004, 10).ok(), Some(156_105_238_500));
+        assert_eq!(parse(1_561_052_385_005, 10).ok(), Some(156_105_238_501));
+
+        assert!(matches!(parse(-1, 9), Err(OracleError::InvalidSwitchboardData)));
+        assert!(matches!(parse(1, MAX_SUPPORTED_SCALE + 1), Err(OracleError::InvalidSwitchboardData)));
+        assert!(matches!(parse(u64::MAX as i128 + 1, 9), Err(OracleError::InvalidSwitchboardData)));
+    }
+
+    #[test]
+    fn test_pull_feed_layout() {
+        // `PullFeedAccountData` is 3,200 bytes after the discriminator, its result at 2,256
+        assert_eq!((pull_feed::QUEUE.offset, pull_feed::VALUE.offset, pull_feed::LEN), (8 + 2_080, 8 + 2_256, 8 + 3_200));
+
+        let queue = Pubkey::new_unique();
+        let feed = MockPullFeed::new(queue, 156_123_456_789_500_000_000, 1_000, 500).std_dev(-1).samples(3, 2);
+        assert_eq!(decode_pull_feed(&feed.to_account_data()).unwrap(), PullFeedResult {
+            queue,
+            value: 156_123_456_789_500_000_000,
+            std_dev: -1,
+            num_samples: 3,
+            min_sample_size: 2,
+            slot: 500,
+            last_update_timestamp: 1_000,
+        });
+
+        let valid = feed.to_account_data();
+        let mut wrong_discriminator = valid.clone();
+        wrong_discriminator[0] ^= 0xff;
+        assert!(matches!(decode_pull_feed(&wrong_discriminator), Err(OracleError::InvalidSwitchboardAccount)));
+        assert!(matches!(decode_pull_feed(&valid[..pull_feed::SLOT.end() - 1]), Err(OracleError::InvalidSwitchboardAccount)));
+    }
+
+    #[test]
+    fn test_get_pull_feed_price_checks_feed_owner_queue_samples_and_staleness() {
+        let (key, queue) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let read = |feed: MockPullFeed, queue: &Pubkey, clock: &Clock| {
+            get_pull_feed_price(&feed.into_account(key).account_info(), &key, queue, clock, MaxAge::Seconds(60))
         };
-        let result = parse_sol_price(&decimal).unwrap();
-        assert_eq!(result.value, 156.10523850000000000000000000);
+        let feed = || MockPullFeed::new(queue, 156_123_456_789_500_000_000, 1_000, 5_000).std_dev(100_000_000_000_000_000);
+
+        // Rounded half away from zero to 9 decimals
+        assert_eq!(read(feed(), &queue, &clock_at(1_060, 5_100)).unwrap(), SourcePrice {
+            price: 156_123_456_790,
+            round_slot: 5_000,
+            round_timestamp: 1_000,
+            confidence: Confidence { value: PRICE_SCALE / 10, status: ConfidenceStatus::Measured },
+        });
+        assert_eq!(read(feed(), &queue, &clock_at(1_061, 5_100)).unwrap_err(), error!(OracleError::StaleData));
+
+        // A feed from another queue, or any feed while no queue is set, is refused
+        let invalid_account = error!(OracleError::InvalidSwitchboardAccount);
+        assert_eq!(read(feed(), &Pubkey::new_unique(), &clock_at(1_000, 5_000)).unwrap_err(), invalid_account);
+        assert_eq!(read(feed(), &Pubkey::default(), &clock_at(1_000, 5_000)).unwrap_err(), invalid_account);
+        let mut foreign = feed().into_account(key);
+        foreign.owner = Pubkey::new_unique();
+        let err = get_pull_feed_price(&foreign.account_info(), &key, &queue, &clock_at(1_000, 5_000), MaxAge::Seconds(60)).unwrap_err();
+        assert_eq!(err, invalid_account);
+
+        // A valid feed on the right queue is still refused in place of the configured one
+        let mut other = feed().into_account(Pubkey::new_unique());
+        let err = get_pull_feed_price(&other.account_info(), &key, &queue, &clock_at(1_000, 5_000), MaxAge::Seconds(60)).unwrap_err();
+        assert_eq!(err, error!(OracleError::UnregisteredFeed));
+
+        // Too few samples for the feed's own configuration, or none at all
+        let invalid_data = error!(OracleError::InvalidSwitchboardData);
+        assert_eq!(read(feed().samples(2, 3), &queue, &clock_at(1_000, 5_000)).unwrap_err(), invalid_data);
+        assert_eq!(read(feed().samples(0, 0), &queue, &clock_at(1_000, 5_000)).unwrap_err(), invalid_data);
+        assert!(read(feed().samples(3, 3), &queue, &clock_at(1_000, 5_000)).is_ok());
+
+        // Negative values do not convert
+        let negative = MockPullFeed::new(queue, -1_000_000_000_000_000_000, 1_000, 5_000);
+        assert_eq!(read(negative, &queue, &clock_at(1_000, 5_000)).unwrap_err(), invalid_data);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,467 @@
+//! In-memory account mocks for unit tests, enabled with the `test-utils` feature.
+//!
+//! ```ignore
+//! let mut feed = MockAggregator::new().result(15_610, 2).queue(queue).into_account(feed_key);
+//! let feed_info = feed.account_info();
+//! let loader = aggregator_loader(&feed_info)?;
+//! ```
+//!
+//! Mocks fail loudly on bad fixtures, so the program's lint against panics is lifted here.
+#![allow(clippy::unwrap_used, clippy::expect_used, clippy::panic)]
+use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+use bytemuck::Zeroable;
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_spl::token::spl_token;
+use anchor_spl::token_2022::spl_token_2022;
+use spl_token_2022::extension::default_account_state::DefaultAccountState;
+use spl_token_2022::extension::immutable_owner::ImmutableOwner;
+use spl_token_2022::extension::{ExtensionType, StateWithExtensionsMut};
+use switchboard_v2::{AggregatorAccountData, LeaseAccountData, SwitchboardDecimal, SWITCHBOARD_PROGRAM_ID};
+use crate::chainlink_utils::{transmissions_discriminator, CHAINLINK_STORE_PROGRAM_ID, TRANSMISSIONS_HEADER_SIZE, TRANSMISSION_LEN};
+use crate::pyth_utils::{price_update_discriminator, PYTH_RECEIVER_PROGRAM_ID};
+use crate::marinade_utils::{marinade_state_discriminator, MARINADE_PROGRAM_ID, MARINADE_STATE_PREFIX_LEN, MSOL_PRICE_OFFSET, MSOL_SUPPLY_OFFSET};
+use crate::stake_pool_utils::{STAKE_POOL_PROGRAM_ID, TOTAL_LAMPORTS_OFFSET};
+use crate::layout::Field;
+use crate::switchboard_utils::{pull_feed, pull_feed_discriminator, AggregatorFeed, PRICE_DECIMALS, SWITCHBOARD_ON_DEMAND_PROGRAM_ID};
+
+/// Owned storage behind an `AccountInfo`, so tests can hand out accounts without a bank
+pub struct MockAccount {
+    pub key: Pubkey,
+    pub owner: Pubkey,
+    pub lamports: u64,
+    pub data: Vec<u8>,
+}
+
+impl MockAccount {
+    pub fn new(key: Pubkey, owner: Pubkey, data: Vec<u8>) -> Self {
+        MockAccount { key, owner, lamports: 1, data }
+    }
+
+    /// Serializes an Anchor account, discriminator included. This program's accounts
+    /// are allocated at exactly `8 + LEN`, so no padding is added.
+    pub fn program_account<T: AccountSerialize + Owner>(key: Pubkey, account: &T) -> Self {
+        let mut data = Vec::new();
+        account.try_serialize(&mut data).expect("account serializes");
+        MockAccount::new(key, T::owner(), data)
+    }
+
+    /// Borrows the storage as a writable, non-signer `AccountInfo`
+    pub fn account_info(&mut self) -> AccountInfo<'_> {
+        AccountInfo::new(
+            &self.key,
+            false,
+            true,
+            &mut self.lamports,
+            &mut self.data,
+            &self.owner,
+            false,
+            0,
+        )
+    }
+}
+
+/// Builder for a Switchboard aggregator whose latest confirmed round carries a chosen result
+pub struct MockAggregator {
+    aggregator: AggregatorAccountData,
+}
+
+impl Default for MockAggregator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockAggregator {
+    /// A zeroed aggregator with one successful oracle response, which satisfies
+    /// `min_oracle_results` so `get_result` succeeds
+    pub fn new() -> Self {
+        let mut aggregator = AggregatorAccountData::zeroed();
+        aggregator.min_oracle_results = 1;
+        aggregator.latest_confirmed_round.num_success = 1;
+        aggregator.latest_confirmed_round.is_closed = true;
+        MockAggregator { aggregator }
+    }
+
+    pub fn result(mut self, mantissa: i128, scale: u32) -> Self {
+        self.aggregator.latest_confirmed_round.result = SwitchboardDecimal { mantissa, scale };
+        self
+    }
+
+    /// Fills the response slots with a multi-asset payload in fixed point, a price then an
+    /// APY per feed position, see `get_multi_asset_result`
+    pub fn multi_asset_payload(mut self, prices: [u64; 6], apys: [u64; 6]) -> Self {
+        let mut round = self.aggregator.latest_confirmed_round;
+        let (mut responses, mut fulfilled) = (round.medians_data, round.medians_fulfilled);
+        for (position, (price, apy)) in prices.into_iter().zip(apys).enumerate() {
+            responses[2 * position] = SwitchboardDecimal { mantissa: price as i128, scale: PRICE_DECIMALS };
+            responses[2 * position + 1] = SwitchboardDecimal { mantissa: apy as i128, scale: PRICE_DECIMALS };
+            fulfilled[2 * position] = true;
+            fulfilled[2 * position + 1] = true;
+        }
+        round.medians_data = responses;
+        round.medians_fulfilled = fulfilled;
+        self.aggregator.latest_confirmed_round = round;
+        self
+    }
+
+    pub fn std_deviation(mut self, mantissa: i128, scale: u32) -> Self {
+        self.aggregator.latest_confirmed_round.std_deviation = SwitchboardDecimal { mantissa, scale };
+        self
+    }
+
+    pub fn round_open_timestamp(mut self, unix_timestamp: i64) -> Self {
+        self.aggregator.latest_confirmed_round.round_open_timestamp = unix_timestamp;
+        self
+    }
+
+    pub fn round_open_slot(mut self, slot: u64) -> Self {
+        self.aggregator.latest_confirmed_round.round_open_slot = slot;
+        self
+    }
+
+    pub fn num_success(mut self, num_success: u32) -> Self {
+        self.aggregator.latest_confirmed_round.num_success = num_success;
+        self
+    }
+
+    pub fn min_oracle_results(mut self, min_oracle_results: u32) -> Self {
+        self.aggregator.min_oracle_results = min_oracle_results;
+        self
+    }
+
+    pub fn queue(mut self, queue: Pubkey) -> Self {
+        self.aggregator.queue_pubkey = queue;
+        self
+    }
+
+    pub fn history_buffer(mut self, history_buffer: Pubkey) -> Self {
+        self.aggregator.history_buffer = history_buffer;
+        self
+    }
+
+    pub fn build(self) -> AggregatorAccountData {
+        self.aggregator
+    }
+
+    /// Raw account data: discriminator followed by the zero-copy struct
+    pub fn to_account_data(&self) -> Vec<u8> {
+        let mut data = AggregatorAccountData::discriminator().to_vec();
+        data.extend_from_slice(bytemuck::bytes_of(&self.aggregator));
+        data
+    }
+
+    /// Account owned by the Switchboard program
+    pub fn into_account(self, key: Pubkey) -> MockAccount {
+        MockAccount::new(key, SWITCHBOARD_PROGRAM_ID, self.to_account_data())
+    }
+}
+
+/// Wraps a mock aggregator account the way the update instructions receive it
+pub fn aggregator_loader<'info>(info: &AccountInfo<'info>) -> Result<AggregatorFeed<'info>> {
+    AggregatorFeed::new(info).load()?;
+    Ok(AggregatorFeed::new(info))
+}
+
+/// Builder for a Chainlink store `Transmissions` account, laid out byte for byte like
+/// the store program writes it
+pub struct MockChainlinkFeed {
+    header: [u8; TRANSMISSIONS_HEADER_SIZE],
+    rows: Vec<[u8; TRANSMISSION_LEN]>,
+}
+
+impl MockChainlinkFeed {
+    /// A feed with `decimals` decimals and no live rows
+    pub fn new(decimals: u8) -> Self {
+        let mut header = [0u8; TRANSMISSIONS_HEADER_SIZE];
+        header[0] = 2; // version
+        header[130] = decimals;
+        MockChainlinkFeed { header, rows: Vec::new() }
+    }
+
+    /// Sizes the live ring buffer
+    pub fn live_rows(mut self, live_length: u32) -> Self {
+        self.header[140..144].copy_from_slice(&live_length.to_le_bytes());
+        self.rows = vec![[0u8; TRANSMISSION_LEN]; live_length as usize];
+        self
+    }
+
+    pub fn live_cursor(mut self, live_cursor: u32) -> Self {
+        self.header[144..148].copy_from_slice(&live_cursor.to_le_bytes());
+        self
+    }
+
+    /// Writes a round into live row `index`; the highest `round_id` written becomes the latest round id
+    pub fn round(mut self, index: usize, round_id: u32, timestamp: u32, slot: u64, answer: i128) -> Self {
+        let row = &mut self.rows[index];
+        row[0..8].copy_from_slice(&slot.to_le_bytes());
+        row[8..12].copy_from_slice(&timestamp.to_le_bytes());
+        row[16..32].copy_from_slice(&answer.to_le_bytes());
+        let latest_round_id = u32::from_le_bytes(self.header[135..139].try_into().unwrap());
+        self.header[135..139].copy_from_slice(&latest_round_id.max(round_id).to_le_bytes());
+        self
+    }
+
+    /// Raw account data: discriminator, header, then the live rows
+    pub fn to_account_data(&self) -> Vec<u8> {
+        let mut data = transmissions_discriminator().to_vec();
+        data.extend_from_slice(&self.header);
+        for row in &self.rows {
+            data.extend_from_slice(row);
+        }
+        data
+    }
+
+    /// Account owned by the Chainlink store program
+    pub fn into_account(self, key: Pubkey) -> MockAccount {
+        MockAccount::new(key, CHAINLINK_STORE_PROGRAM_ID, self.to_account_data())
+    }
+}
+
+/// Builder for a Switchboard On-Demand `PullFeedAccountData` account. Only the fields the
+/// oracle reads are written; the rest stays zeroed.
+pub struct MockPullFeed {
+    queue: Pubkey,
+    value: i128,
+    std_dev: i128,
+    num_samples: u8,
+    min_sample_size: u8,
+    slot: u64,
+    last_update_timestamp: i64,
+}
+
+impl MockPullFeed {
+    /// A result of `value` (18 decimals) from one sample, taken in `slot` and stored at
+    /// `last_update_timestamp`, on `queue`
+    pub fn new(queue: Pubkey, value: i128, last_update_timestamp: i64, slot: u64) -> Self {
+        MockPullFeed { queue, value, std_dev: 0, num_samples: 1, min_sample_size: 1, slot, last_update_timestamp }
+    }
+
+    pub fn std_dev(mut self, std_dev: i128) -> Self {
+        self.std_dev = std_dev;
+        self
+    }
+
+    /// Sets the samples behind the result and the fewest the feed accepts
+    pub fn samples(mut self, num_samples: u8, min_sample_size: u8) -> Self {
+        self.num_samples = num_samples;
+        self.min_sample_size = min_sample_size;
+        self
+    }
+
+    pub fn to_account_data(&self) -> Vec<u8> {
+        let mut data = vec![0u8; pull_feed::LEN];
+        data[..8].copy_from_slice(&pull_feed_discriminator());
+        let mut write = |field: Field, bytes: &[u8]| data[field.offset..field.end()].copy_from_slice(bytes);
+        write(pull_feed::QUEUE, self.queue.as_ref());
+        write(pull_feed::MIN_SAMPLE_SIZE, &[self.min_sample_size]);
+        write(pull_feed::LAST_UPDATE_TIMESTAMP, &self.last_update_timestamp.to_le_bytes());
+        write(pull_feed::VALUE, &self.value.to_le_bytes());
+        write(pull_feed::STD_DEV, &self.std_dev.to_le_bytes());
+        write(pull_feed::NUM_SAMPLES, &[self.num_samples]);
+        write(pull_feed::SLOT, &self.slot.to_le_bytes());
+        data
+    }
+
+    /// Account owned by the Switchboard On-Demand program
+    pub fn into_account(self, key: Pubkey) -> MockAccount {
+        MockAccount::new(key, SWITCHBOARD_ON_DEMAND_PROGRAM_ID, self.to_account_data())
+    }
+}
+
+/// Builder for a Pyth Solana Receiver `PriceUpdateV2` account, Borsh-encoded like the
+/// receiver program writes it
+pub struct MockPythPriceUpdate {
+    feed_id: [u8; 32],
+    price: i64,
+    conf: u64,
+    exponent: i32,
+    publish_time: i64,
+    posted_slot: u64,
+    /// `None` when fully verified, otherwise the number of guardian signatures checked
+    partial_signatures: Option<u8>,
+}
+
+impl MockPythPriceUpdate {
+    /// A fully verified update of `price * 10^exponent` with no confidence interval,
+    /// published at `publish_time` and posted in `posted_slot`
+    pub fn new(price: i64, exponent: i32, publish_time: i64, posted_slot: u64) -> Self {
+        MockPythPriceUpdate { feed_id: [0; 32], price, conf: 0, exponent, publish_time, posted_slot, partial_signatures: None }
+    }
+
+    pub fn conf(mut self, conf: u64) -> Self {
+        self.conf = conf;
+        self
+    }
+
+    pub fn feed_id(mut self, feed_id: [u8; 32]) -> Self {
+        self.feed_id = feed_id;
+        self
+    }
+
+    /// Marks the update as checked against only `num_signatures` guardians
+    pub fn partially_verified(mut self, num_signatures: u8) -> Self {
+        self.partial_signatures = Some(num_signatures);
+        self
+    }
+
+    /// Raw account data: discriminator, write authority, verification level, then the message
+    pub fn to_account_data(&self) -> Vec<u8> {
+        let mut data = price_update_discriminator().to_vec();
+        data.extend_from_slice(&[7; 32]); // write_authority
+        match self.partial_signatures {
+            Some(num_signatures) => data.extend_from_slice(&[0, num_signatures]),
+            None => data.push(1),
+        }
+        data.extend_from_slice(&self.feed_id);
+        data.extend_from_slice(&self.price.to_le_bytes());
+        data.extend_from_slice(&self.conf.to_le_bytes());
+        data.extend_from_slice(&self.exponent.to_le_bytes());
+        data.extend_from_slice(&self.publish_time.to_le_bytes());
+        data.extend_from_slice(&(self.publish_time - 1).to_le_bytes()); // prev_publish_time
+        data.extend_from_slice(&self.price.to_le_bytes()); // ema_price
+        data.extend_from_slice(&self.conf.to_le_bytes()); // ema_conf
+        data.extend_from_slice(&self.posted_slot.to_le_bytes());
+        data
+    }
+
+    /// Account owned by the Pyth receiver program
+    pub fn into_account(self, key: Pubkey) -> MockAccount {
+        MockAccount::new(key, PYTH_RECEIVER_PROGRAM_ID, self.to_account_data())
+    }
+}
+
+/// Builder for an SPL stake pool account. Only the prefix the oracle reads is meaningful;
+/// the rest of the pool is zero-filled to its real size.
+pub struct MockStakePool {
+    data: Vec<u8>,
+}
+
+impl MockStakePool {
+    /// Size of a stake pool account
+    pub const ACCOUNT_LEN: usize = 611;
+
+    /// A pool backing `pool_token_supply` tokens with `total_lamports`
+    pub fn new(total_lamports: u64, pool_token_supply: u64) -> Self {
+        let mut data = vec![0u8; Self::ACCOUNT_LEN];
+        data[0] = 1; // AccountType::StakePool
+        data[TOTAL_LAMPORTS_OFFSET..TOTAL_LAMPORTS_OFFSET + 8].copy_from_slice(&total_lamports.to_le_bytes());
+        data[TOTAL_LAMPORTS_OFFSET + 8..TOTAL_LAMPORTS_OFFSET + 16].copy_from_slice(&pool_token_supply.to_le_bytes());
+        MockStakePool { data }
+    }
+
+    pub fn last_update_epoch(mut self, epoch: u64) -> Self {
+        self.data[TOTAL_LAMPORTS_OFFSET + 16..TOTAL_LAMPORTS_OFFSET + 24].copy_from_slice(&epoch.to_le_bytes());
+        self
+    }
+
+    pub fn to_account_data(&self) -> Vec<u8> {
+        self.data.clone()
+    }
+
+    /// Account owned by the SPL stake pool program
+    pub fn into_account(self, key: Pubkey) -> MockAccount {
+        MockAccount::new(key, STAKE_POOL_PROGRAM_ID, self.data)
+    }
+}
+
+/// Builder for Marinade's `State` account. Only the prefix the oracle reads is written,
+/// zero-filled apart from the discriminator and the mSOL supply and price.
+pub struct MockMarinadeState {
+    data: Vec<u8>,
+}
+
+impl MockMarinadeState {
+    /// A state pricing one mSOL at `msol_price` SOL scaled by `MSOL_PRICE_DENOMINATOR`
+    pub fn new(msol_price: u64) -> Self {
+        let mut data = vec![0u8; MARINADE_STATE_PREFIX_LEN];
+        data[..8].copy_from_slice(&marinade_state_discriminator());
+        data[MSOL_PRICE_OFFSET..MSOL_PRICE_OFFSET + 8].copy_from_slice(&msol_price.to_le_bytes());
+        MockMarinadeState { data }
+    }
+
+    pub fn msol_supply(mut self, msol_supply: u64) -> Self {
+        self.data[MSOL_SUPPLY_OFFSET..MSOL_SUPPLY_OFFSET + 8].copy_from_slice(&msol_supply.to_le_bytes());
+        self
+    }
+
+    pub fn to_account_data(&self) -> Vec<u8> {
+        self.data.clone()
+    }
+
+    /// Account owned by the Marinade program
+    pub fn into_account(self, key: Pubkey) -> MockAccount {
+        MockAccount::new(key, MARINADE_PROGRAM_ID, self.data)
+    }
+}
+
+/// Switchboard lease account funding `aggregator` from the token account `escrow`
+pub fn mock_lease(key: Pubkey, aggregator: Pubkey, escrow: Pubkey) -> MockAccount {
+    let mut lease = LeaseAccountData::zeroed();
+    lease.aggregator = aggregator;
+    lease.escrow = escrow;
+    lease.is_active = true;
+    let mut data = LeaseAccountData::discriminator().to_vec();
+    data.extend_from_slice(bytemuck::bytes_of(&lease));
+    MockAccount::new(key, SWITCHBOARD_PROGRAM_ID, data)
+}
+
+/// Initialized SPL token account holding `amount`
+pub fn mock_token_account(key: Pubkey, amount: u64) -> MockAccount {
+    mock_token_account_of(key, Pubkey::default(), amount)
+}
+
+/// Initialized SPL token account holding `amount` of `mint`
+pub fn mock_token_account_of(key: Pubkey, mint: Pubkey, amount: u64) -> MockAccount {
+    let account = spl_token::state::Account {
+        mint,
+        amount,
+        state: spl_token::state::AccountState::Initialized,
+        ..Default::default()
+    };
+    let mut data = vec![0; spl_token::state::Account::LEN];
+    spl_token::state::Account::pack(account, &mut data).expect("token account packs");
+    MockAccount::new(key, spl_token::ID, data)
+}
+
+/// Initialized SPL Token mint with `decimals`
+pub fn mock_mint(key: Pubkey, decimals: u8) -> MockAccount {
+    let mint = spl_token::state::Mint { decimals, is_initialized: true, ..Default::default() };
+    let mut data = vec![0; spl_token::state::Mint::LEN];
+    spl_token::state::Mint::pack(mint, &mut data).expect("mint packs");
+    MockAccount::new(key, spl_token::ID, data)
+}
+
+/// Initialized Token-2022 mint with `decimals` whose `DefaultAccountState` extension
+/// creates token accounts in `default_state`
+pub fn mock_token_2022_mint(key: Pubkey, decimals: u8, default_state: spl_token_2022::state::AccountState) -> MockAccount {
+    let mut data = vec![0; ExtensionType::get_account_len::<spl_token_2022::state::Mint>(&[ExtensionType::DefaultAccountState])];
+    let mut mint = StateWithExtensionsMut::<spl_token_2022::state::Mint>::unpack_uninitialized(&mut data).expect("mint unpacks");
+    mint.init_extension::<DefaultAccountState>(true).expect("extension fits").state = default_state as u8;
+    mint.base = spl_token_2022::state::Mint { decimals, is_initialized: true, ..Default::default() };
+    mint.pack_base();
+    mint.init_account_type().expect("account type fits");
+    MockAccount::new(key, spl_token_2022::ID, data)
+}
+
+/// Initialized Token-2022 account holding `amount` of `mint`, with the `ImmutableOwner`
+/// extension every associated token account carries
+pub fn mock_token_2022_account(key: Pubkey, mint: Pubkey, amount: u64) -> MockAccount {
+    let mut data = vec![0; ExtensionType::get_account_len::<spl_token_2022::state::Account>(&[ExtensionType::ImmutableOwner])];
+    let mut account = StateWithExtensionsMut::<spl_token_2022::state::Account>::unpack_uninitialized(&mut data).expect("account unpacks");
+    account.init_extension::<ImmutableOwner>(true).expect("extension fits");
+    account.base = spl_token_2022::state::Account { mint, amount, state: spl_token_2022::state::AccountState::Initialized, ..Default::default() };
+    account.pack_base();
+    account.init_account_type().expect("account type fits");
+    MockAccount::new(key, spl_token_2022::ID, data)
+}
+
+/// Clock at a given timestamp and slot, everything else defaulted
+pub fn clock_at(unix_timestamp: i64, slot: u64) -> Clock {
+    Clock { slot, unix_timestamp, ..Clock::default() }
+}
+
+/// `clock` moved `secs` seconds and `slots` slots ahead
+pub fn advance_clock(clock: &Clock, secs: i64, slots: u64) -> Clock {
+    Clock { slot: clock.slot + slots, unix_timestamp: clock.unix_timestamp + secs, ..clock.clone() }
+}
@@ -0,0 +1,206 @@
+//! Price levels flagged on chain, for programs that act when an asset crosses one.
+//!
+//! The admin sets up to `MAX_THRESHOLDS_PER_ASSET` thresholds per asset with `set_threshold`,
+//! each a price in the asset's quote currency and a direction. Anyone may then send
+//! `check_thresholds`, which compares each asset's stored price with its thresholds and, for
+//! every one crossed, sets `triggered` with the time and price and emits `ThresholdCrossed`.
+//! A triggered threshold stays triggered, and is not reported again, until the admin calls
+//! `clear_threshold`, even if the price crosses back in between. Watchers read the flag from
+//! the account at `[THRESHOLDS_SEED]`.
+//!
+//! A threshold only applies while the asset is quoted in the currency it was set in. Assets
+//! never priced are not evaluated.
+use anchor_lang::prelude::*;
+use crate::price_oracle::{AssetType, OracleError, PriceOracleData, QuoteCurrency, ASSET_COUNT};
+
+/// Thresholds each asset can have, enough for two levels in each direction
+pub const MAX_THRESHOLDS_PER_ASSET: usize = 4;
+
+/// Side of the level a price must reach to trigger a threshold
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ThresholdDirection {
+    /// Triggered by a price strictly below the level
+    #[default]
+    Below,
+    /// Triggered by a price strictly above the level
+    Above,
+}
+
+/// One threshold of an asset
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct PriceThreshold {
+    /// Fixed point with `PRICE_DECIMALS` decimals, in `quote`. Zero when the slot is unused.
+    pub price: u64,
+    pub direction: ThresholdDirection,
+    pub quote: QuoteCurrency,
+    pub triggered: bool,
+    /// Time and stored price of the check that triggered it
+    pub triggered_at: i64,
+    pub triggered_price: u64,
+}
+
+impl PriceThreshold {
+    /// Serialized size
+    pub const LEN: usize = 8 // price
+        + 1 // direction
+        + 1 // quote
+        + 1 // triggered
+        + 8 // triggered_at
+        + 8; // triggered_price
+
+    pub fn is_set(&self) -> bool {
+        self.price > 0
+    }
+
+    pub fn is_crossed_by(&self, price: u64) -> bool {
+        match self.direction {
+            ThresholdDirection::Below => price < self.price,
+            ThresholdDirection::Above => price > self.price,
+        }
+    }
+}
+
+/// A threshold a check found crossed, as reported by `ThresholdCrossed`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ThresholdCrossing {
+    pub asset_type: AssetType,
+    pub index: u8,
+    pub threshold: PriceThreshold,
+}
+
+/// Every asset's thresholds, see the module documentation
+#[account]
+#[derive(Default)]
+pub struct PriceThresholds {
+    /// Indexed by `AssetType::index`
+    pub thresholds: [[PriceThreshold; MAX_THRESHOLDS_PER_ASSET]; ASSET_COUNT],
+    pub bump: u8,
+}
+
+impl PriceThresholds {
+    /// Serialized size, excluding the 8-byte account discriminator
+    pub const LEN: usize = PriceThreshold::LEN * MAX_THRESHOLDS_PER_ASSET * ASSET_COUNT // thresholds
+        + 1; // bump
+
+    pub fn get(&self, asset_type: AssetType, index: u8) -> Option<&PriceThreshold> {
+        self.thresholds[asset_type.index()].get(index as usize)
+    }
+
+    fn slot(&mut self, asset_type: AssetType, index: u8) -> Result<&mut PriceThreshold> {
+        self.thresholds[asset_type.index()].get_mut(index as usize).ok_or_else(|| {
+            msg!("Threshold index {} is out of range, assets have {}", index, MAX_THRESHOLDS_PER_ASSET);
+            error!(OracleError::InvalidConfig)
+        })
+    }
+
+    /// Replaces the threshold at `index` with an untriggered one. A zero `price` removes it.
+    pub fn set(&mut self, asset_type: AssetType, index: u8, direction: ThresholdDirection, price: u64, quote: QuoteCurrency, bump: u8) -> Result<()> {
+        *self.slot(asset_type, index)? = PriceThreshold { price, direction, quote, ..PriceThreshold::default() };
+        self.bump = bump;
+        Ok(())
+    }
+
+    /// Resets the threshold's trigger, so the next crossing is reported again. Returns
+    /// whether it was triggered.
+    pub fn clear(&mut self, asset_type: AssetType, index: u8) -> Result<bool> {
+        let threshold = self.slot(asset_type, index)?;
+        let was_triggered = threshold.triggered;
+        threshold.triggered = false;
+        threshold.triggered_at = 0;
+        threshold.triggered_price = 0;
+        Ok(was_triggered)
+    }
+
+    /// Triggers every set, untriggered threshold the stored prices cross, and returns them
+    pub fn check(&mut self, data: &PriceOracleData, current_time: i64) -> Vec<ThresholdCrossing> {
+        let mut crossings = Vec::new();
+        for asset_type in AssetType::ALL {
+            let price_data = &data.price_data[asset_type.index()];
+            if price_data.last_update_time == 0 {
+                continue;
+            }
+            for (index, threshold) in self.thresholds[asset_type.index()].iter_mut().enumerate() {
+                if !threshold.is_set() || threshold.triggered || threshold.quote != price_data.quote || !threshold.is_crossed_by(price_data.price) {
+                    continue;
+                }
+                threshold.triggered = true;
+                threshold.triggered_at = current_time;
+                threshold.triggered_price = price_data.price;
+                crossings.push(ThresholdCrossing { asset_type, index: index as u8, threshold: *threshold });
+            }
+        }
+        crossings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::switchboard_utils::PRICE_SCALE;
+
+    fn priced(asset_type: AssetType, price: u64, quote: QuoteCurrency) -> PriceOracleData {
+        let mut data = PriceOracleData::default();
+        let price_data = &mut data.price_data[asset_type.index()];
+        price_data.price = price;
+        price_data.quote = quote;
+        price_data.last_update_time = 1_000;
+        data
+    }
+
+    #[test]
+    fn test_account_len_matches_serialized_size() {
+        assert_eq!(PriceThresholds::default().try_to_vec().unwrap().len(), PriceThresholds::LEN);
+    }
+
+    #[test]
+    fn test_crossing_triggers_once_until_cleared() {
+        let mut thresholds = PriceThresholds::default();
+        thresholds.set(AssetType::SOL, 0, ThresholdDirection::Below, 140 * PRICE_SCALE, QuoteCurrency::USD, 254).unwrap();
+        thresholds.set(AssetType::SOL, 1, ThresholdDirection::Above, 200 * PRICE_SCALE, QuoteCurrency::USD, 254).unwrap();
+        let err = thresholds.set(AssetType::SOL, MAX_THRESHOLDS_PER_ASSET as u8, ThresholdDirection::Above, 1, QuoteCurrency::USD, 254).unwrap_err();
+        assert_eq!(err, error!(OracleError::InvalidConfig));
+
+        // At the level is not across it
+        assert!(thresholds.check(&priced(AssetType::SOL, 140 * PRICE_SCALE, QuoteCurrency::USD), 1_000).is_empty());
+
+        let crossings = thresholds.check(&priced(AssetType::SOL, 139 * PRICE_SCALE, QuoteCurrency::USD), 1_060);
+        assert_eq!(crossings.len(), 1);
+        assert_eq!((crossings[0].asset_type, crossings[0].index), (AssetType::SOL, 0));
+        let below = *thresholds.get(AssetType::SOL, 0).unwrap();
+        assert!(below.triggered);
+        assert_eq!((below.triggered_at, below.triggered_price), (1_060, 139 * PRICE_SCALE));
+        assert_eq!(crossings[0].threshold, below);
+
+        // Recovering and crossing again keeps the first trigger and reports nothing
+        assert!(thresholds.check(&priced(AssetType::SOL, 150 * PRICE_SCALE, QuoteCurrency::USD), 1_120).is_empty());
+        assert!(thresholds.check(&priced(AssetType::SOL, 130 * PRICE_SCALE, QuoteCurrency::USD), 1_180).is_empty());
+        assert_eq!(*thresholds.get(AssetType::SOL, 0).unwrap(), below);
+
+        // Once cleared, the next crossing is reported again
+        assert!(thresholds.clear(AssetType::SOL, 0).unwrap());
+        assert!(!thresholds.clear(AssetType::SOL, 0).unwrap());
+        let crossings = thresholds.check(&priced(AssetType::SOL, 130 * PRICE_SCALE, QuoteCurrency::USD), 1_240);
+        assert_eq!(crossings.len(), 1);
+        assert_eq!(thresholds.get(AssetType::SOL, 0).unwrap().triggered_at, 1_240);
+        assert!(!thresholds.get(AssetType::SOL, 1).unwrap().triggered);
+
+        let crossings = thresholds.check(&priced(AssetType::SOL, 201 * PRICE_SCALE, QuoteCurrency::USD), 1_300);
+        assert_eq!((crossings.len(), crossings[0].index), (1, 1));
+    }
+
+    #[test]
+    fn test_check_skips_unpriced_assets_and_other_quotes() {
+        let mut thresholds = PriceThresholds::default();
+        thresholds.set(AssetType::MSOL, 0, ThresholdDirection::Above, PRICE_SCALE, QuoteCurrency::SOL, 254).unwrap();
+        // Never priced
+        assert!(thresholds.check(&PriceOracleData::default(), 1_000).is_empty());
+        // Quoted in another currency than the threshold
+        assert!(thresholds.check(&priced(AssetType::MSOL, 180 * PRICE_SCALE, QuoteCurrency::USD), 1_000).is_empty());
+        assert_eq!(thresholds.check(&priced(AssetType::MSOL, 1_180_000_000, QuoteCurrency::SOL), 1_000).len(), 1);
+
+        // A removed threshold is never triggered
+        thresholds.set(AssetType::MSOL, 0, ThresholdDirection::Above, 0, QuoteCurrency::SOL, 254).unwrap();
+        assert!(!thresholds.get(AssetType::MSOL, 0).unwrap().triggered);
+        assert!(thresholds.check(&priced(AssetType::MSOL, 1_180_000_000, QuoteCurrency::SOL), 1_000).is_empty());
+    }
+}
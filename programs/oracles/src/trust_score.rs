@@ -0,0 +1,351 @@
+//! One number summing up how far a stored price can be trusted right now, for risk engines.
+//!
+//! `get_trust_score` rates four components of the asset's stored price from 0 to 10000 and
+//! returns their weighted mean, truncated, as `score`:
+//!
+//! - Freshness: `10000 * (max_age - age) / max_age`, where `age` is the time since the feed
+//!   produced the round that last confirmed the price, or the slots since that round was
+//!   read when `max_feed_age_slots` is set, and
+//!   `max_age` the asset's staleness limit. It reaches 0 at the limit.
+//! - Confidence: for a `Measured` spread, `10000 * (200 - width) / 200` where `width` is
+//!   the std deviation in basis points of the price, so a spread of 2% or more scores 0.
+//!   A source that reports no spread, or a zero one, says nothing and scores 5000.
+//! - Source: 10000 when the price came from the kind of feed the asset is sourced from, or
+//!   from a median of its sources; 0 for the signed publisher fallback or a feed of another
+//!   kind, such as a price kept from before `set_asset_source` switched it.
+//! - Stability: 0 while a large move waits for confirmation, or when the last stored move
+//!   was past the warning band, more than half of `price_change_limit_bps`, and less than
+//!   `TRUST_RECENT_MOVE_SECS` ago; 10000 otherwise.
+//!
+//! The weights are `PriceOracleHeader::trust_weights`, in basis points adding up to 10000,
+//! `DEFAULT_TRUST_WEIGHTS` until the admin sets them. The score is 0, with every component,
+//! when the oracle is stopped or the asset was never priced in the data account, is
+//! delisted or paused.
+//!
+//! The response starts with `TRUST_SCORE_VERSION`, which changes with the formula or the
+//! response layout, never with the weights, which are returned with every score.
+use anchor_lang::prelude::*;
+use crate::price_oracle::{AssetType, ConfidenceStatus, MaxAge, OracleError, PriceData, PriceOracleHeader, PriceProvenance};
+
+/// Version of the formula and layout of `TrustScore`
+pub const TRUST_SCORE_VERSION: u8 = 1;
+/// Score and weights of a fully trusted price, in basis points
+pub const TRUST_SCORE_MAX: u16 = 10_000;
+/// Confidence width, in basis points of the price, at which the confidence component is 0
+pub const TRUST_CONFIDENCE_ZERO_BPS: u64 = 200;
+/// Confidence component of a price whose source reports no spread
+pub const TRUST_UNKNOWN_CONFIDENCE_BPS: u16 = 5_000;
+/// How long a move past the warning band keeps the stability component at 0
+pub const TRUST_RECENT_MOVE_SECS: i64 = 3_600;
+/// Weights used until `set_trust_weights` is called
+pub const DEFAULT_TRUST_WEIGHTS: TrustWeights = TrustWeights { freshness_bps: 4_000, confidence_bps: 2_000, source_bps: 2_000, stability_bps: 2_000 };
+
+/// Weight of each component in the score, in basis points adding up to 10000
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct TrustWeights {
+    pub freshness_bps: u16,
+    pub confidence_bps: u16,
+    pub source_bps: u16,
+    pub stability_bps: u16,
+}
+
+impl TrustWeights {
+    /// Serialized size
+    pub const LEN: usize = 2 // freshness_bps
+        + 2 // confidence_bps
+        + 2 // source_bps
+        + 2; // stability_bps
+
+    /// Whether the weights were set; a header migrated from before them has none
+    pub fn is_set(&self) -> bool {
+        *self != TrustWeights::default()
+    }
+
+    /// Fails with `InvalidConfig` unless the weights add up to `TRUST_SCORE_MAX`
+    pub fn validate(&self) -> Result<()> {
+        let total = self.freshness_bps as u32 + self.confidence_bps as u32 + self.source_bps as u32 + self.stability_bps as u32;
+        if total != TRUST_SCORE_MAX as u32 {
+            msg!("Trust weights add up to {} bps, not {}", total, TRUST_SCORE_MAX);
+            return Err(error!(OracleError::InvalidConfig));
+        }
+        Ok(())
+    }
+}
+
+/// Score of an asset's stored price with the components and inputs behind it, as returned
+/// by `get_trust_score`. See the module documentation for the formula.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TrustScore {
+    /// `TRUST_SCORE_VERSION`, the first byte of the response
+    pub version: u8,
+    pub asset_type: AssetType,
+    /// Weighted mean of the components, 0 to 10000
+    pub score: u16,
+    /// False when the score is 0 because the oracle is stopped or the asset unpriced,
+    /// delisted or paused; the components and inputs are then 0
+    pub scored: bool,
+    pub freshness_bps: u16,
+    pub confidence_bps: u16,
+    pub source_bps: u16,
+    pub stability_bps: u16,
+    pub weights: TrustWeights,
+    /// Time since the last confirmation, in slots when `age_in_slots`, otherwise in seconds
+    pub age: u64,
+    pub max_age: u64,
+    pub age_in_slots: bool,
+    pub confidence_status: ConfidenceStatus,
+    /// Std deviation in basis points of the price, 0 unless `Measured`
+    pub confidence_width_bps: u64,
+    /// `PriceProvenance::source_kind` of the stored price
+    pub source_kind: u8,
+    /// Size of the last stored move in basis points of the price before it
+    pub move_bps: u64,
+    /// Time the last move was stored
+    pub moved_at: i64,
+    /// Whether a large move waits for confirmation
+    pub move_pending: bool,
+}
+
+impl TrustScore {
+    /// Scores `price_data`, the asset's slot in the data account, at `clock`
+    pub fn compute(header: &PriceOracleHeader, price_data: &PriceData, asset_type: AssetType, clock: &Clock) -> Self {
+        let weights = header.trust_weights();
+        let mut trust = TrustScore {
+            version: TRUST_SCORE_VERSION,
+            asset_type,
+            score: 0,
+            scored: false,
+            freshness_bps: 0,
+            confidence_bps: 0,
+            source_bps: 0,
+            stability_bps: 0,
+            weights,
+            age: 0,
+            max_age: 0,
+            age_in_slots: false,
+            confidence_status: ConfidenceStatus::Unavailable,
+            confidence_width_bps: 0,
+            source_kind: 0,
+            move_bps: 0,
+            moved_at: 0,
+            move_pending: false,
+        };
+        if header.emergency_stop || price_data.last_update_time == 0 || price_data.price == 0 || price_data.delisted || header.is_paused(asset_type) {
+            return trust;
+        }
+        trust.scored = true;
+
+        let (age, max_age) = match header.feed_max_age(asset_type) {
+            MaxAge::Seconds(max_age) => (price_data.feed_age_secs(clock), max_age.max(0) as u64),
+            MaxAge::Slots(max_age) => (clock.slot.saturating_sub(price_data.last_checked_slot), max_age),
+        };
+        trust.age = age;
+        trust.max_age = max_age;
+        trust.age_in_slots = matches!(header.feed_max_age(asset_type), MaxAge::Slots(_));
+        trust.freshness_bps = scale_down(max_age.saturating_sub(age), max_age);
+
+        trust.confidence_status = price_data.confidence.status;
+        trust.confidence_bps = match price_data.confidence.status {
+            ConfidenceStatus::Measured => {
+                trust.confidence_width_bps = bps_of(price_data.confidence.value, price_data.price);
+                scale_down(TRUST_CONFIDENCE_ZERO_BPS.saturating_sub(trust.confidence_width_bps), TRUST_CONFIDENCE_ZERO_BPS)
+            }
+            ConfidenceStatus::Unavailable | ConfidenceStatus::ZeroDeviation => TRUST_UNKNOWN_CONFIDENCE_BPS,
+        };
+
+        trust.source_kind = price_data.provenance.source_kind;
+        let expected = trust.source_kind == u8::from(header.source(asset_type)) || trust.source_kind == PriceProvenance::MEDIAN;
+        trust.source_bps = if expected { TRUST_SCORE_MAX } else { 0 };
+
+        if price_data.last_price_time != 0 {
+            trust.move_bps = bps_of(price_data.price.abs_diff(price_data.last_price), price_data.last_price);
+        }
+        trust.moved_at = price_data.last_update_time;
+        trust.move_pending = header.pending_prices[asset_type.index()].is_pending();
+        let recent_warning = trust.move_bps * 2 > header.price_change_limit_bps as u64 && clock.unix_timestamp - price_data.last_update_time < TRUST_RECENT_MOVE_SECS;
+        trust.stability_bps = if trust.move_pending || recent_warning { 0 } else { TRUST_SCORE_MAX };
+
+        let weighted = trust.freshness_bps as u64 * weights.freshness_bps as u64
+            + trust.confidence_bps as u64 * weights.confidence_bps as u64
+            + trust.source_bps as u64 * weights.source_bps as u64
+            + trust.stability_bps as u64 * weights.stability_bps as u64;
+        trust.score = (weighted / TRUST_SCORE_MAX as u64) as u16;
+        trust
+    }
+}
+
+/// `value` in basis points of `reference`, saturating; `u64::MAX` for a zero reference
+fn bps_of(value: u64, reference: u64) -> u64 {
+    if reference == 0 {
+        return u64::MAX;
+    }
+    u64::try_from(value as u128 * TRUST_SCORE_MAX as u128 / reference as u128).unwrap_or(u64::MAX)
+}
+
+/// `10000 * part / whole`, 0 for an empty `whole`
+fn scale_down(part: u64, whole: u64) -> u16 {
+    if whole == 0 {
+        return 0;
+    }
+    (part.min(whole) as u128 * TRUST_SCORE_MAX as u128 / whole as u128) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::price_oracle::{Confidence, PendingPrice, SourceKind};
+    use crate::switchboard_utils::PRICE_SCALE;
+    use crate::test_utils::clock_at;
+
+    /// mSOL at 1.2 SOL from its Switchboard feed, confirmed at 1_000 with no spread reported
+    /// and moved 1% from the previous price an hour before
+    fn priced() -> (PriceOracleHeader, PriceData) {
+        let header = PriceOracleHeader { price_change_limit_bps: 2_000, ..PriceOracleHeader::default() };
+        let price_data = PriceData {
+            price: 1_212_000_000,
+            last_price: 1_200_000_000,
+            last_price_time: 1,
+            last_update_time: 1_000 - TRUST_RECENT_MOVE_SECS,
+            last_checked_time: 1_000,
+            feed_timestamp: 1_000,
+            last_checked_slot: 5_000,
+            provenance: PriceProvenance::from_feed(SourceKind::Switchboard, Pubkey::new_unique(), 4_990),
+            ..PriceData::default()
+        };
+        (header, price_data)
+    }
+
+    fn score(header: &PriceOracleHeader, price_data: &PriceData, clock: &Clock) -> TrustScore {
+        TrustScore::compute(header, price_data, AssetType::MSOL, clock)
+    }
+
+    #[test]
+    fn test_len_matches_serialized_size() {
+        assert_eq!(DEFAULT_TRUST_WEIGHTS.try_to_vec().unwrap().len(), TrustWeights::LEN);
+        DEFAULT_TRUST_WEIGHTS.validate().unwrap();
+        let (header, price_data) = priced();
+        assert_eq!(score(&header, &price_data, &clock_at(1_000, 5_000)).try_to_vec().unwrap()[0], TRUST_SCORE_VERSION);
+    }
+
+    #[test]
+    fn test_weights_must_add_up_to_the_whole() {
+        for weights in [TrustWeights::default(), TrustWeights { freshness_bps: 10_000, source_bps: 1, ..TrustWeights::default() }] {
+            assert_eq!(weights.validate().unwrap_err(), error!(OracleError::InvalidConfig));
+        }
+        TrustWeights { stability_bps: 10_000, ..TrustWeights::default() }.validate().unwrap();
+    }
+
+    #[test]
+    fn test_freshness_falls_to_zero_at_the_max_age() {
+        let (mut header, price_data) = priced();
+        let max_age = header.max_age_secs(AssetType::MSOL);
+        let fresh = score(&header, &price_data, &clock_at(1_000, 5_000));
+        assert_eq!((fresh.freshness_bps, fresh.age, fresh.max_age as i64), (10_000, 0, max_age));
+        assert_eq!(score(&header, &price_data, &clock_at(1_000 + max_age / 4, 5_000)).freshness_bps, 7_500);
+        assert_eq!(score(&header, &price_data, &clock_at(1_000 + max_age, 5_000)).freshness_bps, 0);
+        assert_eq!(score(&header, &price_data, &clock_at(1_000 + 2 * max_age, 5_000)).freshness_bps, 0);
+
+        // Counted in slots once the oracle measures staleness in slots
+        header.max_feed_age_slots = 100;
+        let slots = score(&header, &price_data, &clock_at(1_000, 5_020));
+        assert_eq!((slots.freshness_bps, slots.age, slots.max_age, slots.age_in_slots), (8_000, 20, 100, true));
+    }
+
+    #[test]
+    fn test_confidence_scales_with_the_spread() {
+        let (header, mut price_data) = priced();
+        let clock = clock_at(1_000, 5_000);
+        assert_eq!(score(&header, &price_data, &clock).confidence_bps, TRUST_UNKNOWN_CONFIDENCE_BPS);
+        price_data.confidence = Confidence { value: 0, status: ConfidenceStatus::ZeroDeviation };
+        assert_eq!(score(&header, &price_data, &clock).confidence_bps, TRUST_UNKNOWN_CONFIDENCE_BPS);
+
+        // 0.5% of the price is a quarter of the way to 2%
+        price_data.confidence = Confidence::from_std_deviation(price_data.price / 200);
+        let trust = score(&header, &price_data, &clock);
+        assert_eq!((trust.confidence_bps, trust.confidence_width_bps), (7_500, 50));
+        price_data.confidence = Confidence::from_std_deviation(price_data.price / 50);
+        assert_eq!(score(&header, &price_data, &clock).confidence_bps, 0);
+        price_data.confidence = Confidence::from_std_deviation(price_data.price);
+        assert_eq!(score(&header, &price_data, &clock).confidence_bps, 0);
+    }
+
+    #[test]
+    fn test_source_counts_only_the_configured_kind() {
+        let (mut header, mut price_data) = priced();
+        let clock = clock_at(1_000, 5_000);
+        assert_eq!(score(&header, &price_data, &clock).source_bps, 10_000);
+        price_data.provenance.source_kind = PriceProvenance::MEDIAN;
+        assert_eq!(score(&header, &price_data, &clock).source_bps, 10_000);
+        price_data.provenance = PriceProvenance::from_publisher(Pubkey::new_unique(), 5_000);
+        let trust = score(&header, &price_data, &clock);
+        assert_eq!((trust.source_bps, trust.source_kind), (0, PriceProvenance::SIGNED_PUBLISHER));
+
+        // A Switchboard price kept after the asset was switched to Chainlink
+        price_data.provenance = PriceProvenance::from_feed(SourceKind::Switchboard, Pubkey::new_unique(), 4_990);
+        header.sources[AssetType::MSOL.index()] = SourceKind::Chainlink;
+        assert_eq!(score(&header, &price_data, &clock).source_bps, 0);
+    }
+
+    #[test]
+    fn test_stability_drops_for_a_recent_warning_band_move() {
+        let (mut header, mut price_data) = priced();
+        let clock = clock_at(1_000, 5_000);
+        // 1% moves are inside the 10% warning band of a 20% limit
+        assert_eq!(score(&header, &price_data, &clock).stability_bps, 10_000);
+
+        price_data.price = 1_400_000_000;
+        price_data.last_update_time = 1_000 - TRUST_RECENT_MOVE_SECS + 1;
+        let trust = score(&header, &price_data, &clock);
+        assert_eq!((trust.stability_bps, trust.move_bps, trust.moved_at), (0, 1_666, 1_000 - TRUST_RECENT_MOVE_SECS + 1));
+        // Once the move is old enough it no longer counts
+        price_data.last_update_time = 1_000 - TRUST_RECENT_MOVE_SECS;
+        assert_eq!(score(&header, &price_data, &clock).stability_bps, 10_000);
+
+        header.pending_prices[AssetType::MSOL.index()] = PendingPrice { price: 2 * PRICE_SCALE, observed_at: 990 };
+        let trust = score(&header, &price_data, &clock);
+        assert_eq!((trust.stability_bps, trust.move_pending), (0, true));
+    }
+
+    #[test]
+    fn test_score_is_the_weighted_mean() {
+        let (mut header, mut price_data) = priced();
+        let max_age = header.max_age_secs(AssetType::MSOL);
+        let clock = clock_at(1_000 + max_age / 2, 5_000);
+        price_data.confidence = Confidence::from_std_deviation(price_data.price / 200);
+
+        // 40% of 5000, 20% of 7500, 20% of 10000 and 20% of 10000
+        let trust = score(&header, &price_data, &clock);
+        assert_eq!((trust.freshness_bps, trust.confidence_bps, trust.source_bps, trust.stability_bps), (5_000, 7_500, 10_000, 10_000));
+        assert_eq!((trust.score, trust.weights), (7_500, DEFAULT_TRUST_WEIGHTS));
+
+        header.trust_weights = TrustWeights { freshness_bps: 10_000, ..TrustWeights::default() };
+        assert_eq!(score(&header, &price_data, &clock).score, 5_000);
+        header.trust_weights = TrustWeights { confidence_bps: 3_333, source_bps: 3_333, stability_bps: 3_334, ..TrustWeights::default() };
+        // 2499.75 + 3333 + 3334, truncated once
+        assert_eq!(score(&header, &price_data, &clock).score, 9_166);
+    }
+
+    #[test]
+    fn test_unusable_prices_score_zero() {
+        let (header, price_data) = priced();
+        let clock = clock_at(1_000, 5_000);
+        assert!(score(&header, &price_data, &clock).score > 0);
+
+        let never_updated = PriceData::default();
+        let mut delisted = price_data;
+        delisted.delisted = true;
+        let mut stopped = header.clone();
+        stopped.emergency_stop = true;
+        let mut paused = header.clone();
+        paused.paused[AssetType::MSOL.index()] = true;
+        for trust in [
+            score(&header, &never_updated, &clock),
+            score(&header, &delisted, &clock),
+            score(&stopped, &price_data, &clock),
+            score(&paused, &price_data, &clock),
+        ] {
+            assert_eq!((trust.score, trust.scored, trust.freshness_bps, trust.source_bps), (0, false, 0, 0));
+            assert_eq!((trust.version, trust.weights), (TRUST_SCORE_VERSION, DEFAULT_TRUST_WEIGHTS));
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use anchor_lang::prelude::*;
+
+/// Work done by one updater key, kept so keeper rewards and pruning can be based on
+/// who actually pushes updates. Created by the first update that passes it.
+#[account]
+#[derive(Default, Debug, PartialEq, Eq)]
+pub struct UpdaterStats {
+    pub updater: Pubkey,
+    pub bump: u8,
+    /// Asset prices the updater's runs stored
+    pub successful_updates: u64,
+    /// Assets the updater's runs left untouched after rejecting them. Updates that fail
+    /// as a whole are rolled back together with this account and are not counted.
+    pub rejected_attempts: u64,
+    /// Slot of the updater's latest run that stored a price, `0` before the first
+    pub last_update_slot: u64,
+    /// Keeper rewards paid to the updater in lamports; no instruction pays rewards yet
+    pub rewards_paid: u64,
+}
+
+impl UpdaterStats {
+    /// Serialized size, excluding the 8-byte account discriminator
+    pub const LEN: usize = 32 // updater
+        + 1 // bump
+        + 8 // successful_updates
+        + 8 // rejected_attempts
+        + 8 // last_update_slot
+        + 8; // rewards_paid
+
+    /// Adds a run that stored `updated` assets and rejected `rejected` at `slot`,
+    /// binding a freshly created account to `updater`
+    pub fn record(&mut self, updater: Pubkey, bump: u8, updated: usize, rejected: usize, slot: u64) {
+        if self.updater == Pubkey::default() {
+            self.updater = updater;
+            self.bump = bump;
+        }
+        self.successful_updates = self.successful_updates.saturating_add(updated as u64);
+        self.rejected_attempts = self.rejected_attempts.saturating_add(rejected as u64);
+        if updated > 0 {
+            self.last_update_slot = slot;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_accumulates_runs() {
+        let updater = Pubkey::new_unique();
+        let mut stats = UpdaterStats::default();
+        stats.record(updater, 254, 6, 1, 100);
+        stats.record(Pubkey::new_unique(), 1, 0, 2, 200);
+        stats.record(updater, 1, 1, 0, 300);
+
+        assert_eq!(stats, UpdaterStats {
+            updater,
+            bump: 254,
+            successful_updates: 7,
+            rejected_attempts: 3,
+            last_update_slot: 300,
+            rewards_paid: 0,
+        });
+
+        let mut data = Vec::new();
+        stats.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), 8 + UpdaterStats::LEN);
+    }
+}
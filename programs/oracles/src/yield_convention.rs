@@ -0,0 +1,197 @@
+//! Yield conventions of the APYs the feeds report, and the conversions between them.
+//!
+//! Providers publish either an APR, simple yearly interest, or an APY, interest compounded
+//! over the year. The oracle stores every asset's yield as an APY: a value the asset's
+//! `YieldConfig` marks as an APR is converted on ingestion, compounding
+//! `compounding_periods` times a year, and the value as reported is kept beside it as a
+//! `RawYield`.
+//!
+//! Both directions work on `PRICE_DECIMALS` fixed point and are deterministic:
+//!
+//! - `apr_to_apy` computes `(1 + apr / n)^n - 1` in 18-decimal fixed point, squaring and
+//!   multiplying with every product truncated, and truncates the result to 9 decimals. It
+//!   never rounds up: the result is the exact APY rounded toward zero, or one unit (1e-9)
+//!   below it.
+//! - `apy_to_apr` returns the largest APR whose `apr_to_apy` does not exceed the APY, so a
+//!   round trip never raises a yield.
+//!
+//! A growth factor `(1 + apr / n)^n` above about 18, an APY beyond 1700%, fails with
+//! `ConversionOverflow`.
+use anchor_lang::prelude::*;
+use crate::price_oracle::OracleError;
+use crate::switchboard_utils::PRICE_SCALE;
+
+/// Most times a year an APR can be compounded, hourly
+pub const MAX_COMPOUNDING_PERIODS: u16 = 8_760;
+
+/// Fixed point of the intermediate conversions, 18 decimals
+const WAD: u128 = 1_000_000_000_000_000_000;
+/// `WAD` over `PRICE_SCALE`
+const WAD_PER_UNIT: u128 = WAD / PRICE_SCALE as u128;
+
+/// How a feed reports an asset's yield
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[repr(u8)]
+pub enum YieldConvention {
+    /// Compounded yearly yield, stored as reported
+    #[default]
+    Apy = 0,
+    /// Simple yearly interest, compounded `compounding_periods` times a year on ingestion
+    Apr = 1,
+}
+
+/// The convention an asset's yield is reported in, set with `set_yield_convention`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct YieldConfig {
+    pub convention: YieldConvention,
+    /// Times a year an APR compounds, from 1 to `MAX_COMPOUNDING_PERIODS`; `0` for an APY
+    pub compounding_periods: u16,
+}
+
+impl YieldConfig {
+    /// Serialized size
+    pub const LEN: usize = 1 // convention
+        + 2; // compounding_periods
+
+    /// Fails with `InvalidConfig` for an APR compounded less than once or more than
+    /// `MAX_COMPOUNDING_PERIODS` times a year, or an APY given compounding periods
+    pub fn validate(&self) -> Result<()> {
+        let valid = match self.convention {
+            YieldConvention::Apy => self.compounding_periods == 0,
+            YieldConvention::Apr => (1..=MAX_COMPOUNDING_PERIODS).contains(&self.compounding_periods),
+        };
+        if !valid {
+            msg!("Invalid yield convention: {:?} compounded {} times a year", self.convention, self.compounding_periods);
+            return Err(error!(OracleError::InvalidConfig));
+        }
+        Ok(())
+    }
+
+    /// The APY of a yield reported in this convention
+    pub fn to_apy(&self, value: u64) -> std::result::Result<u64, OracleError> {
+        match self.convention {
+            YieldConvention::Apy => Ok(value),
+            YieldConvention::Apr => apr_to_apy(value, self.compounding_periods),
+        }
+    }
+}
+
+/// An asset's yield as its feed last reported it, before conversion to the stored APY
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct RawYield {
+    /// Fixed point with `PRICE_DECIMALS` decimals
+    pub value: u64,
+    /// Convention `value` was read in
+    pub config: YieldConfig,
+}
+
+impl RawYield {
+    /// Serialized size
+    pub const LEN: usize = 8 // value
+        + YieldConfig::LEN; // config
+}
+
+/// APY of `apr` compounded `periods` times a year, see the module documentation for rounding
+pub fn apr_to_apy(apr: u64, periods: u16) -> std::result::Result<u64, OracleError> {
+    if periods == 0 {
+        return Err(OracleError::InvalidConfig);
+    }
+    let rate = apr as u128 * WAD_PER_UNIT / periods as u128;
+    let growth = pow_wad(WAD + rate, periods as u32).ok_or(OracleError::ConversionOverflow)?;
+    u64::try_from((growth - WAD) / WAD_PER_UNIT).map_err(|_| OracleError::ConversionOverflow)
+}
+
+/// APR that compounds `periods` times a year into `apy`: the largest whose `apr_to_apy`
+/// does not exceed `apy`
+pub fn apy_to_apr(apy: u64, periods: u16) -> std::result::Result<u64, OracleError> {
+    if periods == 0 {
+        return Err(OracleError::InvalidConfig);
+    }
+    // Compounding never lowers a yield, so the APR lies in [0, apy]
+    let (mut low, mut high) = (0, apy);
+    while low < high {
+        let mid = high - (high - low) / 2;
+        match apr_to_apy(mid, periods) {
+            Ok(converted) if converted <= apy => low = mid,
+            _ => high = mid - 1,
+        }
+    }
+    Ok(low)
+}
+
+/// `base^exp` in `WAD` fixed point, `None` once a product overflows
+fn pow_wad(mut base: u128, mut exp: u32) -> Option<u128> {
+    let mut result = WAD;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = result.checked_mul(base)? / WAD;
+        }
+        exp >>= 1;
+        // Squared only when used, so a base whose last square would overflow still converts
+        if exp > 0 {
+            base = base.checked_mul(base)? / WAD;
+        }
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apr_to_apy_matches_reference_values() {
+        // 10% APR compounded daily is 10.515578161626...% APY
+        assert_eq!(apr_to_apy(100_000_000, 365).unwrap(), 105_155_781);
+        assert_eq!(apr_to_apy(100_000_000, 12).unwrap(), 104_713_067);
+        assert_eq!(apr_to_apy(100_000_000, 8_760).unwrap(), 105_170_287);
+        assert_eq!(apr_to_apy(70_000_000, 365).unwrap(), 72_500_983);
+        // Compounded once, an APR is its APY
+        assert_eq!(apr_to_apy(100_000_000, 1).unwrap(), 100_000_000);
+        assert_eq!(apr_to_apy(0, 365).unwrap(), 0);
+
+        assert!(matches!(apr_to_apy(100_000_000, 0), Err(OracleError::InvalidConfig)));
+        assert!(matches!(apr_to_apy(u64::MAX, 365), Err(OracleError::ConversionOverflow)));
+        // 280% APR daily is a growth factor of about 16, still in range
+        assert!(apr_to_apy(2_800_000_000, 365).is_ok());
+    }
+
+    #[test]
+    fn test_apy_to_apr_inverts_the_conversion() {
+        // 5% APY compounded daily is 4.879342524640...% APR; the truncated forward
+        // conversion lets the search land one unit above it
+        assert_eq!(apy_to_apr(50_000_000, 365).unwrap(), 48_793_426);
+        assert!(apr_to_apy(48_793_426, 365).unwrap() <= 50_000_000);
+        assert_eq!(apy_to_apr(105_155_781, 365).unwrap(), 100_000_000);
+        assert_eq!(apy_to_apr(100_000_000, 1).unwrap(), 100_000_000);
+        assert_eq!(apy_to_apr(0, 365).unwrap(), 0);
+
+        // A round trip never raises the yield, and moves it by at most a unit
+        for apy in [1, 999, 48_000_000, 72_500_983, 1_000_000_000] {
+            for periods in [1, 12, 365, MAX_COMPOUNDING_PERIODS] {
+                let apr = apy_to_apr(apy, periods).unwrap();
+                let back = apr_to_apy(apr, periods).unwrap();
+                assert!(back <= apy && apy - back <= 1, "{} over {} periods: {} -> {}", apy, periods, apr, back);
+            }
+        }
+    }
+
+    #[test]
+    fn test_yield_config_validates_and_converts() {
+        let apr = YieldConfig { convention: YieldConvention::Apr, compounding_periods: 365 };
+        assert!(apr.validate().is_ok());
+        assert_eq!(apr.to_apy(100_000_000).unwrap(), 105_155_781);
+        assert!(YieldConfig::default().validate().is_ok());
+        assert_eq!(YieldConfig::default().to_apy(100_000_000).unwrap(), 100_000_000);
+
+        for invalid in [
+            YieldConfig { convention: YieldConvention::Apr, compounding_periods: 0 },
+            YieldConfig { convention: YieldConvention::Apr, compounding_periods: MAX_COMPOUNDING_PERIODS + 1 },
+            YieldConfig { convention: YieldConvention::Apy, compounding_periods: 365 },
+        ] {
+            assert_eq!(invalid.validate().unwrap_err(), error!(OracleError::InvalidConfig));
+        }
+        assert_eq!(YieldConfig::LEN, YieldConfig::default().try_to_vec().unwrap().len());
+        assert_eq!(RawYield::LEN, RawYield::default().try_to_vec().unwrap().len());
+    }
+}
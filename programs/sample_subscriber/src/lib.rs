@@ -0,0 +1,160 @@
+//! Example subscriber of the oracle's update callback, kept in the workspace to exercise
+//! the interface end to end. It keeps the latest price and APY the oracle reported for
+//! each asset. Register it with `set_subscriber`:
+//!
+//! - `program_id`: this program's id
+//! - `discriminator`: `on_price_update_discriminator()`
+//! - `accounts`: `[last_prices_address()]`, with `writable_mask` `1`
+//!
+//! The callback is decoded with definitions of its own rather than the oracle crate's, as
+//! a third-party subscriber would. The oracle's `subscriber` module documents the layout.
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hash;
+
+declare_id!("EvW5S5nDKeH6A1Wrei5dQTyTSxCpLLufziGGqHtr4FR5");
+
+/// The oracle program, whose header PDA signs every callback
+pub const ORACLE_PROGRAM_ID: &str = "GqYaWFTAy3dTNZ8zRb9EyWLqTQ4gRHUUwCCuD5GmRihY";
+pub const ORACLE_HEADER_SEED: &[u8] = b"price_oracle_header";
+/// Assets the oracle prices, indexed by their Borsh tag
+pub const ASSET_COUNT: usize = 7;
+pub const LAST_PRICES_SEED: &[u8] = b"last_prices";
+
+/// Mirrors the oracle's `CallbackPrice`; `asset_type` and `quote` are the Borsh tags of its
+/// `AssetType` and `QuoteCurrency`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CallbackPrice {
+    pub asset_type: u8,
+    pub price: u64,
+    pub apy: u64,
+    pub quote: u8,
+}
+
+/// Mirrors the oracle's `PriceCallback`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Debug)]
+pub struct PriceCallback {
+    pub timestamp: i64,
+    pub prices: Vec<CallbackPrice>,
+}
+
+#[account]
+#[derive(Default, Debug)]
+pub struct LastPrices {
+    /// Fixed point with 9 decimals, by asset tag
+    pub prices: [u64; ASSET_COUNT],
+    pub apys: [u64; ASSET_COUNT],
+    pub quotes: [u8; ASSET_COUNT],
+    pub callbacks: u64,
+    pub last_callback_time: i64,
+    /// Fails every callback while set, to stand in for a broken subscriber
+    pub rejecting: bool,
+    pub bump: u8,
+}
+
+impl LastPrices {
+    /// Serialized size, excluding the 8-byte account discriminator
+    pub const LEN: usize = 8 * ASSET_COUNT // prices
+        + 8 * ASSET_COUNT // apys
+        + ASSET_COUNT // quotes
+        + 8 // callbacks
+        + 8 // last_callback_time
+        + 1 // rejecting
+        + 1; // bump
+}
+
+/// Address of the oracle header; only the oracle can have it sign
+pub fn oracle_header_address() -> Pubkey {
+    Pubkey::find_program_address(&[ORACLE_HEADER_SEED], &ORACLE_PROGRAM_ID.parse().unwrap()).0
+}
+
+/// Address of the `LastPrices` account
+pub fn last_prices_address() -> Pubkey {
+    Pubkey::find_program_address(&[LAST_PRICES_SEED], &ID).0
+}
+
+/// Discriminator to register with the oracle, the one Anchor gives `on_price_update`
+pub fn on_price_update_discriminator() -> [u8; 8] {
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&hash(b"global:on_price_update").to_bytes()[..8]);
+    discriminator
+}
+
+#[program]
+pub mod sample_subscriber {
+    use super::*;
+
+    pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
+        ctx.accounts.last_prices.bump = *ctx.bumps.get("last_prices").unwrap();
+        Ok(())
+    }
+
+    /// Test hook: anyone may make the callback fail, which real subscribers must not allow
+    pub fn set_rejecting(ctx: Context<SetRejecting>, rejecting: bool) -> Result<()> {
+        ctx.accounts.last_prices.rejecting = rejecting;
+        Ok(())
+    }
+
+    /// The oracle's callback: records the prices it carries
+    pub fn on_price_update(ctx: Context<OnPriceUpdate>, callback: PriceCallback) -> Result<()> {
+        let last_prices = &mut ctx.accounts.last_prices;
+        require!(!last_prices.rejecting, SubscriberError::Rejecting);
+        for price in &callback.prices {
+            let index = price.asset_type as usize;
+            require!(index < ASSET_COUNT, SubscriberError::UnknownAsset);
+            last_prices.prices[index] = price.price;
+            last_prices.apys[index] = price.apy;
+            last_prices.quotes[index] = price.quote;
+        }
+        last_prices.callbacks += 1;
+        last_prices.last_callback_time = callback.timestamp;
+        msg!("Recorded {} price(s) from the oracle", callback.prices.len());
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + LastPrices::LEN,
+        seeds = [LAST_PRICES_SEED],
+        bump
+    )]
+    pub last_prices: Account<'info, LastPrices>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetRejecting<'info> {
+    #[account(
+        mut,
+        seeds = [LAST_PRICES_SEED],
+        bump = last_prices.bump,
+    )]
+    pub last_prices: Account<'info, LastPrices>,
+}
+
+#[derive(Accounts)]
+pub struct OnPriceUpdate<'info> {
+    #[account(address = oracle_header_address() @ SubscriberError::NotTheOracle)]
+    pub oracle_header: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [LAST_PRICES_SEED],
+        bump = last_prices.bump,
+    )]
+    pub last_prices: Account<'info, LastPrices>,
+}
+
+#[error_code]
+pub enum SubscriberError {
+    #[msg("The callback was not signed by the oracle")]
+    NotTheOracle,
+    #[msg("Unknown asset in the callback")]
+    UnknownAsset,
+    #[msg("The subscriber is rejecting callbacks")]
+    Rejecting,
+}
@@ -0,0 +1,133 @@
+//! Example watcher that halts the oracle through CPI, kept in the workspace to exercise
+//! `trigger_emergency_stop_cpi` end to end. It compares a price against a reference and
+//! stops the oracle when they drift apart. Allowlist it with the oracle's `set_watcher`,
+//! passing this program's id.
+//!
+//! The CPI is signed by `watcher_authority_address()`, the `WATCHER_SEED` PDA under this
+//! program, which the oracle checks against the program it names. As with the sample
+//! subscriber, the oracle's instructions are built with definitions of its own rather than
+//! the oracle crate's.
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hash;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program::invoke_signed;
+
+declare_id!("GPwzLe4w6PLoxMu6toWfZBiQYbCFkjL4Qb39G92TSxBv");
+
+/// The oracle program this watcher stops
+pub const ORACLE_PROGRAM_ID: &str = "GqYaWFTAy3dTNZ8zRb9EyWLqTQ4gRHUUwCCuD5GmRihY";
+pub const ORACLE_HEADER_SEED: &[u8] = b"price_oracle_header";
+/// Seed of the PDA the oracle expects a watcher's CPI to be signed by
+pub const WATCHER_SEED: &[u8] = b"oracle_watcher";
+/// Reason code the watcher stops the oracle with, for the oracle's events
+pub const DEPEG_REASON_CODE: u8 = 42;
+
+pub fn oracle_program_id() -> Pubkey {
+    ORACLE_PROGRAM_ID.parse().unwrap()
+}
+
+/// Address of the oracle header
+pub fn oracle_header_address() -> Pubkey {
+    Pubkey::find_program_address(&[ORACLE_HEADER_SEED], &oracle_program_id()).0
+}
+
+/// Address of the PDA that signs this watcher's CPIs
+pub fn watcher_authority_address() -> Pubkey {
+    Pubkey::find_program_address(&[WATCHER_SEED], &ID).0
+}
+
+/// Discriminator Anchor gives the oracle instruction `name`
+fn oracle_discriminator(name: &str) -> [u8; 8] {
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&hash(format!("global:{}", name).as_bytes()).to_bytes()[..8]);
+    discriminator
+}
+
+#[program]
+pub mod sample_watcher {
+    use super::*;
+
+    /// Stops the oracle when `price` is more than `max_deviation_bps` away from
+    /// `reference`. Anyone may call it; a real watcher reads both from accounts.
+    pub fn check_peg(ctx: Context<CallOracle>, price: u64, reference: u64, max_deviation_bps: u32) -> Result<()> {
+        let deviation = reference.abs_diff(price) as u128 * 10_000;
+        if deviation <= reference as u128 * max_deviation_bps as u128 {
+            msg!("Price {} is within {} bps of {}", price, max_deviation_bps, reference);
+            return Ok(());
+        }
+        msg!("Price {} is more than {} bps from {}, stopping the oracle", price, max_deviation_bps, reference);
+        let mut data = oracle_discriminator("trigger_emergency_stop_cpi").to_vec();
+        data.push(DEPEG_REASON_CODE);
+        let accounts = vec![
+            AccountMeta::new(ctx.accounts.oracle_header.key(), false),
+            AccountMeta::new_readonly(ID, false),
+            AccountMeta::new_readonly(ctx.accounts.watcher_authority.key(), true),
+            audit_log_meta(&ctx.accounts.audit_log),
+        ];
+        invoke_oracle(&ctx, accounts, data)
+    }
+
+    /// Test hook: tries to clear the oracle's stop with the watcher's PDA, which the
+    /// oracle must refuse
+    pub fn try_clear(ctx: Context<CallOracle>) -> Result<()> {
+        let mut data = oracle_discriminator("set_emergency_stop").to_vec();
+        // stop: false, force: true, reason_code: 0
+        data.extend([0, 1, 0]);
+        let accounts = vec![
+            AccountMeta::new(ctx.accounts.oracle_header.key(), false),
+            AccountMeta::new_readonly(ctx.accounts.watcher_authority.key(), true),
+            audit_log_meta(&ctx.accounts.audit_log),
+        ];
+        invoke_oracle(&ctx, accounts, data)
+    }
+}
+
+/// The audit log when passed, else the oracle program id Anchor reads as a missing
+/// optional account
+fn audit_log_meta(audit_log: &Option<UncheckedAccount>) -> AccountMeta {
+    match audit_log {
+        Some(audit_log) => AccountMeta::new(audit_log.key(), false),
+        None => AccountMeta::new_readonly(oracle_program_id(), false),
+    }
+}
+
+fn invoke_oracle(ctx: &Context<CallOracle>, accounts: Vec<AccountMeta>, data: Vec<u8>) -> Result<()> {
+    let instruction = Instruction { program_id: oracle_program_id(), accounts, data };
+    let mut account_infos = vec![
+        ctx.accounts.oracle_header.to_account_info(),
+        ctx.accounts.watcher_program.to_account_info(),
+        ctx.accounts.watcher_authority.to_account_info(),
+        ctx.accounts.oracle_program.to_account_info(),
+    ];
+    if let Some(audit_log) = &ctx.accounts.audit_log {
+        account_infos.push(audit_log.to_account_info());
+    }
+    let bump = *ctx.bumps.get("watcher_authority").unwrap();
+    invoke_signed(&instruction, &account_infos, &[&[WATCHER_SEED, &[bump]]])?;
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct CallOracle<'info> {
+    /// CHECK: the oracle validates its own header
+    #[account(mut, address = oracle_header_address() @ WatcherError::NotTheOracle)]
+    pub oracle_header: UncheckedAccount<'info>,
+    /// CHECK: signs the CPI; holds nothing
+    #[account(seeds = [WATCHER_SEED], bump)]
+    pub watcher_authority: UncheckedAccount<'info>,
+    /// CHECK: this program, which the oracle checks against its allowlist
+    #[account(address = ID)]
+    pub watcher_program: UncheckedAccount<'info>,
+    /// CHECK: the program invoked
+    #[account(executable, address = oracle_program_id() @ WatcherError::NotTheOracle)]
+    pub oracle_program: UncheckedAccount<'info>,
+    /// CHECK: the oracle's audit log, required by the oracle once it exists
+    #[account(mut)]
+    pub audit_log: Option<UncheckedAccount<'info>>,
+}
+
+#[error_code]
+pub enum WatcherError {
+    #[msg("Account does not belong to the oracle")]
+    NotTheOracle,
+}
@@ -2,24 +2,136 @@ import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { assert } from "chai";
 
+// Devnet feeds publish a new round every few seconds. Updates reading a round the oracle
+// already stored fail with NoNewRound, so retry them until the feed has moved on.
+async function withNewRound<T>(update: () => Promise<T>): Promise<T> {
+  for (let attempt = 1; ; attempt++) {
+    try {
+      return await update();
+    } catch (error) {
+      if (attempt >= 30 || !error.toString().includes("has not published a new round")) {
+        throw error;
+      }
+      await new Promise((resolve) => setTimeout(resolve, 2_000));
+    }
+  }
+}
+
 interface PriceOracleHeader {
   lastGlobalUpdate: anchor.BN;
   emergencyStop: boolean;
   authority: anchor.web3.PublicKey;
   switchboardProgramId: anchor.web3.PublicKey;
   bump: number;
+  maxFeedAgeSlots: anchor.BN;
+  lastStopChangeTime: anchor.BN;
+  stopChangeCount: anchor.BN;
+  minStopChangeInterval: anchor.BN;
+  operator: anchor.web3.PublicKey;
+  locked: boolean;
+  minLeaseBalance: anchor.BN;
+  rejectEmptyLease: boolean;
+  sources: ({ switchboard?: {}; chainlink?: {} })[];
+  partialUpdates: boolean;
+  rejectionCodes: number[];
+  maxAgeSecs: number[];
+  priceChangeLimitBps: number;
+  configTimelockSecs: number;
+  pendingConfig: PendingConfig;
+  stopClearEffectiveAt: anchor.BN;
+  emergencyCouncil: anchor.web3.PublicKey;
+  paused: boolean[];
+  resumeApprovers: anchor.web3.PublicKey[];
+  resumeThreshold: number;
+  manualResumeNeedsApproval: boolean;
+  stopIncident: anchor.BN;
+  stopAutomatic: boolean;
+  resumeApprovals: anchor.BN[];
+  largeMovePolicy: ({ halt?: {}; confirm?: {}; clamp?: {} })[];
+  moveConfirmDelaySecs: number;
+  moveConfirmToleranceBps: number;
+  pendingPrices: { price: anchor.BN; observedAt: anchor.BN }[];
+  updateStats: {
+    successfulUpdates: anchor.BN;
+    rejectedStale: anchor.BN;
+    rejectedChangeLimit: anchor.BN;
+    longestGapSecs: anchor.BN;
+    longestGapAt: anchor.BN;
+  };
+  apyChangeLimitBps: number[];
+  attestationSequence: anchor.BN;
+  haircutBps: number[];
+  auditLogEnabled: boolean;
+  updatesPaused: boolean;
+  threadAuthority: anchor.web3.PublicKey;
+  instance: anchor.web3.PublicKey;
+  clampedPrices: anchor.BN[]; // 0 unless the stored price was clamped short of the feed
+}
+
+interface OracleConfig {
+  priceChangeLimitBps: number;
+  timelockSecs: number;
+  feeds: anchor.web3.PublicKey[];
+  resumeApprovers: anchor.web3.PublicKey[];
+  resumeThreshold: number;
+  manualResumeNeedsApproval: boolean;
+}
+
+interface PendingConfig {
+  config: OracleConfig;
+  effectiveAt: anchor.BN; // 0 when nothing is pending
 }
 
 interface PriceData {
-  price: number;
-  lastPrice: number;
+  price: anchor.BN; // fixed point, 9 decimals
+  lastPrice: anchor.BN;
+  lastUpdateTime: anchor.BN;
+  apy: anchor.BN; // fixed point, 9 decimals
+  lastUpdateSlot: anchor.BN;
+  lastPriceTime: anchor.BN;
+  quote: { usd?: {}; sol?: {} };
+  provenance: PriceProvenance;
+  delisted: boolean;
+  confidence: Confidence;
+  highWatermark: anchor.BN; // fixed point, 9 decimals
+  highWatermarkTime: anchor.BN;
+  lowWatermark: anchor.BN; // fixed point, 9 decimals
+  lowWatermarkTime: anchor.BN;
+  lastCheckedTime: anchor.BN; // our clock, for keeper liveness
+  lastCheckedSlot: anchor.BN;
+  feedTimestamp: anchor.BN; // the source's clock, staleness is measured from here
+}
+
+interface Confidence {
+  value: anchor.BN; // std deviation, fixed point, 9 decimals
+  status: { unavailable?: {}; measured?: {}; zeroDeviation?: {} };
+}
+
+interface PriceProvenance {
+  sourceFeed: anchor.web3.PublicKey;
+  sourceRoundSlot: anchor.BN;
+  sourceKind: number; // SourceKind as u8, 255 for a median of several sources
+}
+
+interface AssetPrice {
+  assetType: object;
+  price: anchor.BN;
+  quote: { usd?: {}; sol?: {} };
+  apy: anchor.BN;
   lastUpdateTime: anchor.BN;
-  apy: number;
+  lastUpdateSlot: anchor.BN;
+  lastCheckedTime: anchor.BN;
+  feedTimestamp: anchor.BN;
+  maxAgeSecs: number;
+  stale: boolean;
+  delisted: boolean;
+  confidence: Confidence;
 }
 
 interface PriceOracleData {
   priceData: PriceData[];
   bump: number;
+  nonce: anchor.BN;
 }
 
 describe("price_oracle", () => {
@@ -31,6 +143,9 @@ describe("price_oracle", () => {
 
   let priceOracleHeaderPda: anchor.web3.PublicKey;
   let priceOracleDataPda: anchor.web3.PublicKey;
+  let assetRegistryPda: anchor.web3.PublicKey;
+  let priceHistoryPda: anchor.web3.PublicKey;
+  let apyHistoryPda: anchor.web3.PublicKey;
   let oracleFeed: anchor.web3.PublicKey;
   let solOracleFeed: anchor.web3.PublicKey;
   let switchboardProgram: anchor.web3.PublicKey;
@@ -49,6 +164,24 @@ describe("price_oracle", () => {
       );
       priceOracleDataPda = dataPda;
 
+      const [registryPda] = await anchor.web3.PublicKey.findProgramAddress(
+        [Buffer.from("asset_registry")],
+        programId
+      );
+      assetRegistryPda = registryPda;
+
+      const [historyPda] = await anchor.web3.PublicKey.findProgramAddress(
+        [Buffer.from("price_history")],
+        programId
+      );
+      priceHistoryPda = historyPda;
+
+      const [apyPda] = await anchor.web3.PublicKey.findProgramAddress(
+        [Buffer.from("apy_history")],
+        programId
+      );
+      apyHistoryPda = apyPda;
+
       oracleFeed = new anchor.web3.PublicKey("4NiWaTuje7SVe9DN1vfnX7m1qBC7DnUxwRxbdgEDUGX1");
       solOracleFeed = new anchor.web3.PublicKey("GvDMxPzN1sCj7L26YDK2HnMRXEQmQ2aemov8YBtPS7vR");
       switchboardProgram = new anchor.web3.PublicKey("Aio4gaXjXzJNVLtzwtNVmSqGKpANtXhybbkhtAC94ji2");
@@ -58,9 +191,20 @@ describe("price_oracle", () => {
     }
   });
 
+  it("Exposes no mock instruction in the default build", () => {
+    const names = program.idl.instructions.map((ix) => ix.name);
+    assert.include(names, "updateAll", "The IDL should list the regular instructions");
+    assert.notInclude(names, "setMockPrice", "set_mock_price must only exist in mock-oracle builds");
+  });
+
+  it("Lists the errors that replace panics in the instruction handlers", () => {
+    const names = program.idl.errors.map((error) => error.name);
+    assert.includeMembers(names, ["ClockUnavailable", "MissingBump"]);
+  });
+
   it("Initializes the price oracle", async () => {
     try {
-      await program.methods.initialize(switchboardProgram)
+      await program.methods.initialize(switchboardProgram, anchor.web3.PublicKey.default)
         .accounts({
           header: priceOracleHeaderPda,
           data: priceOracleDataPda,
@@ -74,12 +218,90 @@ describe("price_oracle", () => {
       assert.isNotNull(headerAccount, "Header account should not be null");
       assert.isNotNull(dataAccount, "Data account should not be null");
       assert.equal(headerAccount.switchboardProgramId.toBase58(), switchboardProgram.toBase58(), "Switchboard program ID should match");
+      assert.isTrue(headerAccount.instance.equals(anchor.web3.PublicKey.default), "The original oracle should be the default instance");
+      headerAccount.sources.forEach((source, index) => {
+        assert.deepEqual(source, { switchboard: {} }, `Asset ${index} should start sourced from Switchboard`);
+      });
     } catch (error) {
       console.error("Error initializing price oracle:", error);
       throw error;
     }
   });
 
+  it("Initializes a second instance beside the default one", async () => {
+    const instance = anchor.web3.Keypair.generate().publicKey;
+    const [headerPda] = await anchor.web3.PublicKey.findProgramAddress(
+      [Buffer.from("price_oracle_header"), instance.toBuffer()],
+      programId
+    );
+    const [dataPda] = await anchor.web3.PublicKey.findProgramAddress(
+      [Buffer.from("price_oracle_data"), instance.toBuffer()],
+      programId
+    );
+
+    await program.methods.initialize(switchboardProgram, instance)
+      .accounts({
+        header: headerPda,
+        data: dataPda,
+        authority: provider.wallet.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .rpc();
+
+    const headerAccount = await program.account.priceOracleHeader.fetch(headerPda) as PriceOracleHeader;
+    assert.isTrue(headerAccount.instance.equals(instance), "The header should record its instance");
+
+    // Reads of the instance pass its header so its data is checked against its seeds
+    await program.methods.getCurrentPrice({ sol: {} })
+      .accounts({
+        data: dataPda,
+        header: headerPda,
+      })
+      .rpc();
+  });
+
+  it("Initializes the price history", async () => {
+    try {
+      await program.methods.initializeHistory()
+        .accounts({
+          header: priceOracleHeaderPda,
+          history: priceHistoryPda,
+          authority: provider.wallet.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .rpc();
+
+      const historyAccount = await program.account.priceHistory.fetch(priceHistoryPda) as any;
+      assert.equal(historyAccount.assets.length, 10, "Should keep a history for every asset");
+      historyAccount.assets.forEach((asset, index) => {
+        assert.equal(asset.len, 0, `Asset ${index} history should start empty`);
+      });
+    } catch (error) {
+      console.error("Error initializing price history:", error);
+      throw error;
+    }
+  });
+
+  it("Initializes the APY history", async () => {
+    try {
+      await program.methods.initializeApyHistory(1)
+        .accounts({
+          header: priceOracleHeaderPda,
+          apyHistory: apyHistoryPda,
+          authority: provider.wallet.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .rpc();
+
+      const apyHistoryAccount = await program.account.apyHistory.fetch(apyHistoryPda) as any;
+      assert.equal(apyHistoryAccount.minSamples, 1, "Should keep the minimum sample count");
+      assert.equal(apyHistoryAccount.assets.length, 10, "Should keep APY samples for every asset");
+    } catch (error) {
+      console.error("Error initializing APY history:", error);
+      throw error;
+    }
+  });
+
   it("Updates prices and APYs for all assets", async () => {
     try {
       await program.methods.updatePricesAndApys()
@@ -87,16 +309,21 @@ describe("price_oracle", () => {
           header: priceOracleHeaderPda,
           data: priceOracleDataPda,
           oracleFeed: oracleFeed,
+          lease: null,
+          leaseEscrow: null,
+          history: priceHistoryPda,
+          apyHistory: apyHistoryPda,
           authority: provider.wallet.publicKey,
         })
         .rpc();
 
       const dataAccount = await program.account.priceOracleData.fetch(priceOracleDataPda) as PriceOracleData;
-      assert.equal(dataAccount.priceData.length, 7, "Should have updated 7 assets (6 + SOL)");
+      assert.equal(dataAccount.priceData.length, 10, "Should have a slot for every asset");
       dataAccount.priceData.forEach((data, index) => {
+        // Only the assets carried by the multi-asset feed, before SOL, are updated
         if (index < 6) {
-          assert.isTrue(data.price > 0, `Asset ${index} price should be greater than 0`);
-          assert.isTrue(data.apy > 0, `Asset ${index} APY should be greater than 0`);
+          assert.isTrue(data.price.gtn(0), `Asset ${index} price should be greater than 0`);
+          assert.isTrue(data.apy.gtn(0), `Asset ${index} APY should be greater than 0`);
         }
       });
     } catch (error) {
@@ -105,16 +332,63 @@ describe("price_oracle", () => {
     }
   });
 
+  it("Skips a refresh while the prices are fresh, whoever signs", async () => {
+    const stranger = anchor.web3.Keypair.generate();
+    const before = await program.account.priceOracleData.fetch(priceOracleDataPda) as PriceOracleData;
+
+    await program.methods.refreshIfStale()
+      .accounts({
+        header: priceOracleHeaderPda,
+        data: priceOracleDataPda,
+        oracleFeed: oracleFeed,
+        lease: null,
+        leaseEscrow: null,
+        history: priceHistoryPda,
+        apyHistory: apyHistoryPda,
+        authority: stranger.publicKey,
+      })
+      .signers([stranger])
+      .rpc();
+
+    const after = await program.account.priceOracleData.fetch(priceOracleDataPda) as PriceOracleData;
+    assert.isTrue(after.nonce.eq(before.nonce), "A fresh refresh should not change the data");
+  });
+
+  it("Rejects an update chunk outside the multi-asset feed", async () => {
+    try {
+      await program.methods.updatePricesAndApysChunk(4, 3)
+        .accounts({
+          header: priceOracleHeaderPda,
+          data: priceOracleDataPda,
+          oracleFeed: oracleFeed,
+          lease: null,
+          leaseEscrow: null,
+          history: priceHistoryPda,
+          apyHistory: apyHistoryPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Update chunk must name at least one position");
+    }
+  });
+
   it("Updates SOL price", async () => {
     try {
       const beforeUpdate = await program.account.priceOracleData.fetch(priceOracleDataPda) as PriceOracleData;
       const beforeSolPrice = beforeUpdate.priceData[6].price;
 
-      await program.methods.updateSolPrice()
+      await program.methods.updateSolPrice(null, 0)
         .accounts({
           header: priceOracleHeaderPda,
           data: priceOracleDataPda,
           oracleFeed: solOracleFeed,
+          historyBuffer: null,
+          lease: null,
+          leaseEscrow: null,
+          history: priceHistoryPda,
           authority: provider.wallet.publicKey,
         })
         .rpc();
@@ -123,8 +397,8 @@ describe("price_oracle", () => {
       const afterSolPrice = afterUpdate.priceData[6].price;
 
       assert.isNotNull(afterSolPrice, "SOL price should not be null");
-      assert.isTrue(afterSolPrice > 0, "SOL price should be greater than 0");
-      assert.notEqual(beforeSolPrice, afterSolPrice, "SOL price should have changed");
+      assert.isTrue(afterSolPrice.gtn(0), "SOL price should be greater than 0");
+      assert.isFalse(beforeSolPrice.eq(afterSolPrice), "SOL price should have changed");
       
       console.log(`SOL price updated from ${beforeSolPrice} to ${afterSolPrice}`);
     } catch (error) {
@@ -133,57 +407,175 @@ describe("price_oracle", () => {
     }
   });
 
-  it("Gets current price for JupSOL", async () => {
+  it("Rejects a SOL price update that misses the expected price", async () => {
+    const before = await program.account.priceOracleData.fetch(priceOracleDataPda) as PriceOracleData;
+
     try {
-      const tx = await program.methods.getCurrentPrice({ jupSol: {} })
+      // An expected price of 1, that is 1e-9 USD, is far from anything the feed reads
+      await withNewRound(() => program.methods.updateSolPrice(new anchor.BN(1), 0)
         .accounts({
+          header: priceOracleHeaderPda,
           data: priceOracleDataPda,
+          oracleFeed: solOracleFeed,
+          historyBuffer: null,
+          lease: null,
+          leaseEscrow: null,
+          history: priceHistoryPda,
+          authority: provider.wallet.publicKey,
         })
-        .rpc();
+        .rpc());
 
-      const txLogs = await provider.connection.getTransaction(tx, { commitment: 'confirmed' });
-      assert.isTrue(txLogs.meta.logMessages.some(log => log.includes("Current price for JupSOL:")), "Transaction logs should include current price for JupSOL");
+      assert.fail("Should have thrown an error");
     } catch (error) {
-      console.error("Error getting current price for JupSOL:", error);
+      assert.include(error.toString(), "Feed price differs from the price the keeper expected");
+    }
+
+    const after = await program.account.priceOracleData.fetch(priceOracleDataPda) as PriceOracleData;
+    assert.isTrue(after.priceData[6].price.eq(before.priceData[6].price), "A mismatched update should not store a price");
+  });
+
+  it("Updates all prices in a single transaction", async () => {
+    try {
+      const tx = await withNewRound(() => program.methods.updateAll()
+        .accounts({
+          header: priceOracleHeaderPda,
+          data: priceOracleDataPda,
+          oracleFeed: oracleFeed,
+          solOracleFeed: solOracleFeed,
+          solHistoryBuffer: null,
+          lease: null,
+          leaseEscrow: null,
+          solLease: null,
+          solLeaseEscrow: null,
+          history: priceHistoryPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc({ commitment: "confirmed" }));
+
+      // Assets whose feed had no new round are left alone with their rejection code set.
+      // The others were written or found unchanged, and checked either way.
+      const headerAccount = await program.account.priceOracleHeader.fetch(priceOracleHeaderPda) as PriceOracleHeader;
+      const dataAccount = await program.account.priceOracleData.fetch(priceOracleDataPda) as PriceOracleData;
+      dataAccount.priceData.forEach((data, index) => {
+        if (headerAccount.rejectionCodes[index] !== 0) {
+          return;
+        }
+        assert.equal(
+          data.lastCheckedTime.toString(),
+          headerAccount.lastGlobalUpdate.toString(),
+          `Asset ${index} should share the global update timestamp`
+        );
+        assert.isTrue(data.lastUpdateTime.lte(data.lastCheckedTime), `Asset ${index} cannot change after it was last checked`);
+        assert.isTrue(data.feedTimestamp.lte(data.lastCheckedTime), `Asset ${index} cannot come from a round after it was checked`);
+      });
+
+      const txDetails = await provider.connection.getTransaction(tx, { commitment: "confirmed" });
+      console.log(`update_all consumed ${txDetails.meta.computeUnitsConsumed} compute units`);
+    } catch (error) {
+      console.error("Error updating all prices:", error);
       throw error;
     }
   });
 
-  it("Gets current price for SOL", async () => {
+  it("Previews an update without writing state", async () => {
     try {
-      const tx = await program.methods.getCurrentPrice({ sol: {} })
+      const before = await program.account.priceOracleData.fetch(priceOracleDataPda) as PriceOracleData;
+
+      const preview = await program.methods.previewUpdate()
         .accounts({
+          header: priceOracleHeaderPda,
           data: priceOracleDataPda,
+          oracleFeed: oracleFeed,
+          solOracleFeed: solOracleFeed,
+          solHistoryBuffer: null,
+        })
+        .view();
+
+      assert.equal(preview.assets.length, 7, "Preview should cover every asset update_all writes");
+      preview.assets.forEach((asset, index) => {
+        assert.equal(asset.accepted, asset.reasonCode === 0, `Asset ${index} reason code should match its verdict`);
+      });
+
+      const after = await program.account.priceOracleData.fetch(priceOracleDataPda) as PriceOracleData;
+      assert.deepEqual(after, before, "Preview must not write state");
+    } catch (error) {
+      console.error("Error previewing update:", error);
+      throw error;
+    }
+  });
+
+  it("Registers a dedicated SOL feed", async () => {
+    try {
+      const queue = new anchor.web3.PublicKey("EYiAmGSdsQTuCw413V5BzaruWuCCSDgTPtBGvLkXHbe7");
+      await program.methods.initializeRegistry(queue)
+        .accounts({
+          header: priceOracleHeaderPda,
+          registry: assetRegistryPda,
+          authority: provider.wallet.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
         })
         .rpc();
 
-      const txLogs = await provider.connection.getTransaction(tx, { commitment: 'confirmed' });
-      assert.isTrue(txLogs.meta.logMessages.some(log => log.includes("Current price for SOL:")), "Transaction logs should include current price for SOL");
+      await program.methods.setAssetFeed({ sol: {} }, solOracleFeed)
+        .accounts({
+          header: priceOracleHeaderPda,
+          registry: assetRegistryPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      const registryAccount = await program.account.assetRegistry.fetch(assetRegistryPda) as any;
+      assert.equal(registryAccount.assets[6].feed.toBase58(), solOracleFeed.toBase58(), "SOL feed should be registered");
     } catch (error) {
-      console.error("Error getting current price for SOL:", error);
+      console.error("Error registering SOL feed:", error);
       throw error;
     }
   });
 
-  it("Gets current APY for JupSOL", async () => {
+  it("Lets the operator tune soft parameters but not change feeds", async () => {
+    const operator = anchor.web3.Keypair.generate();
     try {
-      const tx = await program.methods.getCurrentApy({ jupSol: {} })
+      await program.methods.setOperator(operator.publicKey)
         .accounts({
-          data: priceOracleDataPda,
+          header: priceOracleHeaderPda,
+          authority: provider.wallet.publicKey,
         })
         .rpc();
 
-      const txLogs = await provider.connection.getTransaction(tx, { commitment: 'confirmed' });
-      assert.isTrue(txLogs.meta.logMessages.some(log => log.includes("Current APY for JupSOL:")), "Transaction logs should include current APY for JupSOL");
+      await program.methods.setMaxFeedAgeSlots(new anchor.BN(0))
+        .accounts({
+          header: priceOracleHeaderPda,
+          authority: operator.publicKey,
+        })
+        .signers([operator])
+        .rpc();
+
+      const headerAccount = await program.account.priceOracleHeader.fetch(priceOracleHeaderPda) as PriceOracleHeader;
+      assert.equal(headerAccount.operator.toBase58(), operator.publicKey.toBase58(), "Operator should be set");
     } catch (error) {
-      console.error("Error getting current APY for JupSOL:", error);
+      console.error("Error using the operator key:", error);
       throw error;
     }
+
+    try {
+      await program.methods.setAssetFeed({ sol: {} }, oracleFeed)
+        .accounts({
+          header: priceOracleHeaderPda,
+          registry: assetRegistryPda,
+          authority: operator.publicKey,
+        })
+        .signers([operator])
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Signer does not hold the role this instruction requires");
+    }
   });
 
-  it("Sets and checks emergency stop", async () => {
+  it("Sets the lease policy", async () => {
     try {
-      await program.methods.setEmergencyStop(true)
+      await program.methods.setLeasePolicy(new anchor.BN(1_000_000), false)
         .accounts({
           header: priceOracleHeaderPda,
           authority: provider.wallet.publicKey,
@@ -191,81 +583,1730 @@ describe("price_oracle", () => {
         .rpc();
 
       const headerAccount = await program.account.priceOracleHeader.fetch(priceOracleHeaderPda) as PriceOracleHeader;
-      assert.isTrue(headerAccount.emergencyStop, "Emergency stop should be set to true");
+      assert.equal(headerAccount.minLeaseBalance.toString(), "1000000", "Lease threshold should be set");
+      assert.isFalse(headerAccount.rejectEmptyLease, "Empty leases should only warn");
     } catch (error) {
-      console.error("Error setting or checking emergency stop:", error);
+      console.error("Error setting the lease policy:", error);
       throw error;
     }
   });
 
-  it("Fails to update prices and APYs when emergency stop is active", async () => {
+  it("Toggles partial updates", async () => {
     try {
-      await program.methods.updatePricesAndApys()
+      await program.methods.setPartialUpdates(true)
         .accounts({
           header: priceOracleHeaderPda,
-          data: priceOracleDataPda,
-          oracleFeed: oracleFeed,
           authority: provider.wallet.publicKey,
         })
         .rpc();
 
-      assert.fail("Should have thrown an error");
+      let headerAccount = await program.account.priceOracleHeader.fetch(priceOracleHeaderPda) as PriceOracleHeader;
+      assert.isTrue(headerAccount.partialUpdates, "Partial updates should be enabled");
+
+      // Later tests rely on a change-limit violation aborting the update
+      await program.methods.setPartialUpdates(false)
+        .accounts({
+          header: priceOracleHeaderPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      headerAccount = await program.account.priceOracleHeader.fetch(priceOracleHeaderPda) as PriceOracleHeader;
+      assert.isFalse(headerAccount.partialUpdates, "Partial updates should be disabled again");
     } catch (error) {
-      assert.include(error.toString(), "Emergency stop activated");
+      console.error("Error toggling partial updates:", error);
+      throw error;
     }
   });
 
-  it("Fails to update SOL price when emergency stop is active", async () => {
+  it("Updates registered assets from remaining accounts", async () => {
     try {
-      await program.methods.updateSolPrice()
+      const tx = await withNewRound(() => program.methods.updateAssets()
         .accounts({
           header: priceOracleHeaderPda,
           data: priceOracleDataPda,
-          oracleFeed: solOracleFeed,
+          registry: assetRegistryPda,
+          history: priceHistoryPda,
           authority: provider.wallet.publicKey,
         })
-        .rpc();
+        .remainingAccounts([{ pubkey: solOracleFeed, isWritable: false, isSigner: false }])
+        .rpc({ commitment: "confirmed" }));
+
+      const txDetails = await provider.connection.getTransaction(tx, { commitment: "confirmed" });
+      assert.isTrue(txDetails.meta.logMessages.some(log => log.includes("Updated assets: [SOL]")), "Only SOL should be updated");
+      console.log(`update_assets with 1 feed consumed ${txDetails.meta.computeUnitsConsumed} compute units`);
+
+      const historyAccount = await program.account.priceHistory.fetch(priceHistoryPda) as any;
+      assert.isAbove(historyAccount.assets[6].len, 0, "SOL update should be recorded in the history");
+    } catch (error) {
+      console.error("Error updating registered assets:", error);
+      throw error;
+    }
+  });
+
+  it("Records the keeper's work in its updater stats", async () => {
+    const [updaterStatsPda] = await anchor.web3.PublicKey.findProgramAddress(
+      [Buffer.from("updater_stats"), provider.wallet.publicKey.toBuffer()],
+      programId
+    );
+    try {
+      await withNewRound(() => program.methods.updateAssets()
+        .accounts({
+          header: priceOracleHeaderPda,
+          data: priceOracleDataPda,
+          registry: assetRegistryPda,
+          history: null,
+          updaterStats: updaterStatsPda,
+          authority: provider.wallet.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .remainingAccounts([{ pubkey: solOracleFeed, isWritable: false, isSigner: false }])
+        .rpc());
+
+      const stats = await program.methods.getUpdaterStats(provider.wallet.publicKey)
+        .accounts({
+          stats: updaterStatsPda,
+        })
+        .view();
+      assert.equal(stats.updater.toBase58(), provider.wallet.publicKey.toBase58());
+      assert.isTrue(stats.successfulUpdates.gte(new anchor.BN(1)), "The SOL update should be counted");
+      assert.isTrue(stats.lastUpdateSlot.gt(new anchor.BN(0)));
+    } catch (error) {
+      console.error("Error recording updater stats:", error);
+      throw error;
+    }
+  });
+
+  it("Reports oracle-wide update statistics", async () => {
+    try {
+      const stats = await program.methods.getOracleStats()
+        .accounts({
+          header: priceOracleHeaderPda,
+        })
+        .view();
+      const header = await program.account.priceOracleHeader.fetch(priceOracleHeaderPda) as PriceOracleHeader;
+      assert.isTrue(stats.updateStats.successfulUpdates.gte(new anchor.BN(1)), "Earlier updates should be counted");
+      assert.isTrue(stats.updateStats.successfulUpdates.eq(header.updateStats.successfulUpdates));
+      assert.isTrue(stats.stopIncidents.eq(header.stopIncident));
+      assert.isTrue(stats.lastGlobalUpdate.eq(header.lastGlobalUpdate));
+    } catch (error) {
+      console.error("Error fetching oracle stats:", error);
+      throw error;
+    }
+  });
+
+  it("Emits a keeper heartbeat between updates", async () => {
+    try {
+      const tx = await program.methods.heartbeat()
+        .accounts({
+          header: priceOracleHeaderPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc({ commitment: "confirmed" });
+
+      const txDetails = await provider.connection.getTransaction(tx, { commitment: "confirmed" });
+      const parser = new anchor.EventParser(program.programId, program.coder);
+      const heartbeats = [...parser.parseLogs(txDetails.meta.logMessages)].filter(event => event.name === "Heartbeat");
+      assert.equal(heartbeats.length, 1, "One heartbeat should be emitted");
+      assert.equal(heartbeats[0].data.keeper.toBase58(), provider.wallet.publicKey.toBase58(), "Heartbeat should name the keeper");
+      assert.equal(heartbeats[0].data.updated, 0, "A standalone heartbeat updates nothing");
+      assert.equal(heartbeats[0].data.skipped, 0, "A standalone heartbeat skips nothing");
+      assert.equal(heartbeats[0].data.unchanged, 0, "A standalone heartbeat checks nothing");
+    } catch (error) {
+      console.error("Error sending a heartbeat:", error);
+      throw error;
+    }
+  });
+
+  it("Fails to get volatility without enough observations", async () => {
+    try {
+      await program.methods.getVolatility({ sol: {} }, new anchor.BN(3600))
+        .accounts({
+          history: priceHistoryPda,
+        })
+        .view();
 
       assert.fail("Should have thrown an error");
     } catch (error) {
-      assert.include(error.toString(), "Emergency stop activated");
+      assert.include(error.toString(), "Data not available");
     }
   });
 
-  it("Fails to update prices and APYs with unauthorized user", async () => {
-    const unauthorizedUser = anchor.web3.Keypair.generate();
+  it("Averages the APYs sampled by the multi-asset update", async () => {
+    const window = new anchor.BN(7 * 86_400);
+    const averageBps = await program.methods.getAverageApy({ msol: {} }, window)
+      .accounts({
+        apyHistory: apyHistoryPda,
+      })
+      .view();
+    assert.isAbove(averageBps, 0, "mSOL's APY should have been sampled");
+
+    // SOL is not carried by the multi-asset feed, so it has no sample
     try {
-      await program.methods.updatePricesAndApys()
+      await program.methods.getAverageApy({ sol: {} }, window)
+        .accounts({
+          apyHistory: apyHistoryPda,
+        })
+        .view();
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Data not available");
+    }
+  });
+
+  it("Fails to get a price from before the oldest recorded observation", async () => {
+    try {
+      await program.methods.getPriceAt({ sol: {} }, new anchor.BN(0))
+        .accounts({
+          history: priceHistoryPda,
+        })
+        .view();
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Data not available");
+    }
+  });
+
+  it("Fails to update assets with an unregistered feed", async () => {
+    try {
+      await program.methods.updateAssets()
         .accounts({
           header: priceOracleHeaderPda,
           data: priceOracleDataPda,
-          oracleFeed: oracleFeed,
-          authority: unauthorizedUser.publicKey,
+          registry: assetRegistryPda,
+          authority: provider.wallet.publicKey,
         })
-        .signers([unauthorizedUser])
+        .remainingAccounts([{ pubkey: oracleFeed, isWritable: false, isSigner: false }])
         .rpc();
 
       assert.fail("Should have thrown an error");
     } catch (error) {
-      assert.include(error.toString(), "Unauthorized access");
+      assert.include(error.toString(), "Feed is not registered for any asset");
     }
   });
 
-  it("Updates Switchboard program ID", async () => {
-    const newSwitchboardProgramId = anchor.web3.Keypair.generate().publicKey;
+  it("Registers and clears the JitoSOL stake pool", async () => {
+    const jitoStakePool = new anchor.web3.PublicKey("Jito4APyf642JPZPx3hGc6WWJ8zPKtRbRs4P815Awbb");
     try {
-      await program.methods.updateSwitchboardProgramId(newSwitchboardProgramId)
+      await program.methods.setAssetStakePool({ jitoSol: {} }, jitoStakePool, { spl: {} }, 300)
         .accounts({
           header: priceOracleHeaderPda,
+          registry: assetRegistryPda,
           authority: provider.wallet.publicKey,
         })
         .rpc();
 
-      const headerAccount = await program.account.priceOracleHeader.fetch(priceOracleHeaderPda) as PriceOracleHeader;
-      assert.equal(headerAccount.switchboardProgramId.toBase58(), newSwitchboardProgramId.toBase58(), "Switchboard program ID should be updated");
+      let registryAccount = await program.account.assetRegistry.fetch(assetRegistryPda) as any;
+      assert.equal(registryAccount.assets[5].stakePool.toBase58(), jitoStakePool.toBase58(), "JitoSOL pool should be registered");
+      assert.equal(registryAccount.assets[5].navToleranceBps, 300);
+      assert.deepEqual(registryAccount.assets[5].stakePoolKind, { spl: {} });
+
+      await program.methods.setAssetStakePool({ jitoSol: {} }, anchor.web3.PublicKey.default, { spl: {} }, 0)
+        .accounts({
+          header: priceOracleHeaderPda,
+          registry: assetRegistryPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      registryAccount = await program.account.assetRegistry.fetch(assetRegistryPda) as any;
+      assert.isTrue(registryAccount.assets[5].stakePool.equals(anchor.web3.PublicKey.default), "JitoSOL pool should be cleared");
+    } catch (error) {
+      console.error("Error registering the JitoSOL stake pool:", error);
+      throw error;
+    }
+
+    try {
+      await program.methods.setAssetStakePool({ sol: {} }, jitoStakePool, { spl: {} }, 0)
+        .accounts({
+          header: priceOracleHeaderPda,
+          registry: assetRegistryPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Invalid configuration");
+    }
+  });
+
+  it("Checks mSOL against Marinade's state only", async () => {
+    const marinadeState = new anchor.web3.PublicKey("8szGkuLTAux9XMgZ2vtY39jVSowEcpBfFfD8hXSEqdGC");
+    try {
+      await program.methods.setAssetStakePool({ jitoSol: {} }, marinadeState, { marinade: {} }, 0)
+        .accounts({
+          header: priceOracleHeaderPda,
+          registry: assetRegistryPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Invalid configuration");
+    }
+
+    try {
+      await program.methods.setAssetStakePool({ msol: {} }, marinadeState, { marinade: {} }, 0)
+        .accounts({
+          header: priceOracleHeaderPda,
+          registry: assetRegistryPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      const registryAccount = await program.account.assetRegistry.fetch(assetRegistryPda) as any;
+      assert.equal(registryAccount.assets[3].stakePool.toBase58(), marinadeState.toBase58(), "Marinade state should be registered for mSOL");
+      assert.deepEqual(registryAccount.assets[3].stakePoolKind, { marinade: {} });
+    } catch (error) {
+      console.error("Error registering the Marinade state:", error);
+      throw error;
+    }
+  });
+
+  it("Aggregates the SOL price from its registered sources", async () => {
+    try {
+      await program.methods.setAssetSources({ sol: {} }, [{ kind: { switchboard: {} }, feed: solOracleFeed }], 1)
+        .accounts({
+          header: priceOracleHeaderPda,
+          registry: assetRegistryPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      const registryAccount = await program.account.assetRegistry.fetch(assetRegistryPda) as any;
+      assert.equal(registryAccount.assets[6].sources[0].feed.toBase58(), solOracleFeed.toBase58(), "SOL source should be registered");
+      assert.equal(registryAccount.assets[6].minSources, 1, "One fresh source should be required");
+
+      await program.methods.updateAssetFromSources({ sol: {} })
+        .accounts({
+          header: priceOracleHeaderPda,
+          data: priceOracleDataPda,
+          registry: assetRegistryPda,
+          history: null,
+          authority: provider.wallet.publicKey,
+        })
+        .remainingAccounts([{ pubkey: solOracleFeed, isWritable: false, isSigner: false }])
+        .rpc();
+
+      const dataAccount = await program.account.priceOracleData.fetch(priceOracleDataPda) as PriceOracleData;
+      assert.isTrue(dataAccount.priceData[6].price.gt(new anchor.BN(0)), "SOL price should be set from the median");
+    } catch (error) {
+      console.error("Error aggregating SOL sources:", error);
+      throw error;
+    }
+
+    try {
+      await program.methods.updateAssetFromSources({ sol: {} })
+        .accounts({
+          header: priceOracleHeaderPda,
+          data: priceOracleDataPda,
+          registry: assetRegistryPda,
+          history: null,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Too few fresh sources to aggregate");
+    }
+  });
+
+  it("Gets current price for JupSOL", async () => {
+    try {
+      const tx = await program.methods.getCurrentPrice({ jupSol: {} })
+        .accounts({
+          data: priceOracleDataPda,
+        })
+        .rpc();
+
+      const txLogs = await provider.connection.getTransaction(tx, { commitment: 'confirmed' });
+      assert.isTrue(txLogs.meta.logMessages.some(log => log.includes("Current price for JupSOL:")), "Transaction logs should include current price for JupSOL");
+    } catch (error) {
+      console.error("Error getting current price for JupSOL:", error);
+      throw error;
+    }
+  });
+
+  it("Gets current price for SOL", async () => {
+    try {
+      const tx = await program.methods.getCurrentPrice({ sol: {} })
+        .accounts({
+          data: priceOracleDataPda,
+        })
+        .rpc();
+
+      const txLogs = await provider.connection.getTransaction(tx, { commitment: 'confirmed' });
+      assert.isTrue(txLogs.meta.logMessages.some(log => log.includes("Current price for SOL:")), "Transaction logs should include current price for SOL");
+    } catch (error) {
+      console.error("Error getting current price for SOL:", error);
+      throw error;
+    }
+  });
+
+  it("Reports where the SOL price came from", async () => {
+    try {
+      const provenance = await program.methods.getPriceProvenance({ sol: {} })
+        .accounts({
+          data: priceOracleDataPda,
+        })
+        .view() as PriceProvenance;
+
+      const dataAccount = await program.account.priceOracleData.fetch(priceOracleDataPda) as PriceOracleData;
+      assert.equal(provenance.sourceFeed.toBase58(), dataAccount.priceData[6].provenance.sourceFeed.toBase58(), "Getter should match the stored provenance");
+      assert.equal(provenance.sourceKind, 255, "The last SOL update aggregated its registered sources");
+      assert.equal(provenance.sourceFeed.toBase58(), assetRegistryPda.toBase58(), "A median points at the asset registry");
+      assert.isTrue(provenance.sourceRoundSlot.gt(new anchor.BN(0)), "Source round slot should be recorded");
+    } catch (error) {
+      console.error("Error getting the SOL price provenance:", error);
+      throw error;
+    }
+  });
+
+  it("Lists every price with its own staleness tolerance", async () => {
+    try {
+      await program.methods.setAssetMaxAge({ sol: {} }, 600)
+        .accounts({
+          header: priceOracleHeaderPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      const headerAccount = await program.account.priceOracleHeader.fetch(priceOracleHeaderPda) as PriceOracleHeader;
+      assert.equal(headerAccount.maxAgeSecs[6], 600, "SOL max age should be updated");
+
+      const prices = await program.methods.getAllPrices()
+        .accounts({
+          header: priceOracleHeaderPda,
+          data: priceOracleDataPda,
+        })
+        .view() as AssetPrice[];
+
+      assert.equal(prices.length, 10, "Every asset should be listed");
+      assert.deepEqual(prices[7].assetType, { inf: {} }, "INF should follow SOL");
+      assert.deepEqual(prices[8].assetType, { stSol: {} }, "stSOL should follow INF");
+      assert.deepEqual(prices[9].assetType, { bonkSol: {} }, "bonkSOL should be listed last");
+      assert.isTrue(prices[9].stale, "bonkSOL has no registered feed yet");
+      assert.equal(prices[6].maxAgeSecs, 600, "SOL should report its own max age");
+      assert.isFalse(prices[6].stale, "SOL was just updated");
+      assert.isTrue(prices[6].price.gt(new anchor.BN(0)), "SOL price should be listed");
+    } catch (error) {
+      console.error("Error listing all prices:", error);
+      throw error;
+    }
+  });
+
+  it("Gets SOL price no older than a slot-based max age", async () => {
+    try {
+      const tx = await program.methods.getPriceNoOlderThan({ sol: {} }, { slots: { 0: new anchor.BN(1_000_000) } }, false)
+        .accounts({
+          data: priceOracleDataPda,
+          instructions: null,
+        })
+        .rpc({ commitment: "confirmed" });
+
+      const txLogs = await provider.connection.getTransaction(tx, { commitment: "confirmed" });
+      assert.isTrue(txLogs.meta.logMessages.some(log => log.includes("Current price for SOL:")), "Transaction logs should include current price for SOL");
+    } catch (error) {
+      console.error("Error getting SOL price with slot-based max age:", error);
+      throw error;
+    }
+  });
+
+  it("Fails to get a price older than a zero-second max age", async () => {
+    try {
+      await program.methods.getPriceNoOlderThan({ sol: {} }, { seconds: { 0: new anchor.BN(0) } }, false)
+        .accounts({
+          data: priceOracleDataPda,
+          instructions: null,
+        })
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Stale data");
+    }
+  });
+
+  it("Gets the SOL price with its confidence", async () => {
+    const price = await program.methods.getPriceWithConfidence({ sol: {} })
+      .accounts({
+        data: priceOracleDataPda,
+      })
+      .view();
+
+    assert.isTrue(price.price.gt(new anchor.BN(0)));
+    assert.isTrue(price.lastUpdateTime.gt(new anchor.BN(0)));
+    // The SOL feed publishes an encoded result, which carries no spread in price units
+    const confidence = price.confidence as Confidence;
+    assert.deepEqual(confidence.status, { unavailable: {} });
+    assert.isTrue(confidence.value.isZero());
+  });
+
+  it("Gets the last SOL price leniently with its staleness", async () => {
+    const price = await program.methods.getPriceLenient({ sol: {} })
+      .accounts({
+        header: priceOracleHeaderPda,
+        data: priceOracleDataPda,
+      })
+      .view();
+
+    assert.isTrue(price.price.gt(new anchor.BN(0)), "Lenient price should be the stored price");
+    assert.deepEqual(price.quote, { usd: {} });
+    assert.isTrue(price.stalenessSecs.gte(new anchor.BN(0)));
+    assert.isTrue(price.feedTimestamp.gt(new anchor.BN(0)), "Lenient price should carry its feed timestamp");
+    assert.isBoolean(price.degraded);
+  });
+
+  it("Switches SOL to confirming large moves and back", async () => {
+    try {
+      await program.methods.setLargeMovePolicy({ sol: {} }, { confirm: {} })
+        .accounts({
+          header: priceOracleHeaderPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+      await program.methods.setMoveConfirmation(120, 50)
+        .accounts({
+          header: priceOracleHeaderPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      const headerAccount = await program.account.priceOracleHeader.fetch(priceOracleHeaderPda) as PriceOracleHeader;
+      assert.deepEqual(headerAccount.largeMovePolicy[6], { confirm: {} });
+      assert.equal(headerAccount.moveConfirmDelaySecs, 120);
+      assert.equal(headerAccount.moveConfirmToleranceBps, 50);
+
+      const pending = await program.methods.getPendingPrice({ sol: {} })
+        .accounts({
+          header: priceOracleHeaderPda,
+          data: priceOracleDataPda,
+        })
+        .view();
+      assert.isTrue(pending.price.eq(new anchor.BN(0)), "Nothing should be pending yet");
+    } catch (error) {
+      console.error("Error setting the large move policy:", error);
+      throw error;
+    }
+
+    try {
+      await program.methods.setMoveConfirmation(120, 1_001)
+        .accounts({
+          header: priceOracleHeaderPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Invalid configuration value");
+    }
+
+    await program.methods.setLargeMovePolicy({ sol: {} }, { clamp: {} })
+      .accounts({
+        header: priceOracleHeaderPda,
+        authority: provider.wallet.publicKey,
+      })
+      .rpc();
+    const clamping = await program.account.priceOracleHeader.fetch(priceOracleHeaderPda) as PriceOracleHeader;
+    assert.deepEqual(clamping.largeMovePolicy[6], { clamp: {} });
+    assert.isTrue(clamping.pendingPrices[6].price.eq(new anchor.BN(0)), "Leaving Confirm should drop any held move");
+    assert.isTrue(clamping.clampedPrices[6].eq(new anchor.BN(0)), "Nothing has been clamped yet");
+
+    await program.methods.setLargeMovePolicy({ sol: {} }, { halt: {} })
+      .accounts({
+        header: priceOracleHeaderPda,
+        authority: provider.wallet.publicKey,
+      })
+      .rpc();
+    await program.methods.setMoveConfirmation(0, 0)
+      .accounts({
+        header: priceOracleHeaderPda,
+        authority: provider.wallet.publicKey,
+      })
+      .rpc();
+  });
+
+  it("Sets and lifts the mSOL APY change limit", async () => {
+    await program.methods.setApyChangeLimit({ msol: {} }, 5_000)
+      .accounts({
+        header: priceOracleHeaderPda,
+        authority: provider.wallet.publicKey,
+      })
+      .rpc();
+
+    let headerAccount = await program.account.priceOracleHeader.fetch(priceOracleHeaderPda) as PriceOracleHeader;
+    assert.equal(headerAccount.apyChangeLimitBps[3], 5_000, "mSOL APY change limit should be set");
+    assert.equal(headerAccount.apyChangeLimitBps[2], 0, "Other assets should stay unlimited");
+
+    await program.methods.setApyChangeLimit({ msol: {} }, 0)
+      .accounts({
+        header: priceOracleHeaderPda,
+        authority: provider.wallet.publicKey,
+      })
+      .rpc();
+
+    headerAccount = await program.account.priceOracleHeader.fetch(priceOracleHeaderPda) as PriceOracleHeader;
+    assert.equal(headerAccount.apyChangeLimitBps[3], 0, "mSOL APY change limit should be lifted");
+  });
+
+  it("Registers a disabled update subscriber", async () => {
+    const [subscriberPda] = await anchor.web3.PublicKey.findProgramAddress(
+      [Buffer.from("subscriber")],
+      programId
+    );
+    const subscriberProgram = anchor.web3.Keypair.generate().publicKey;
+    const callbackAccount = anchor.web3.Keypair.generate().publicKey;
+    await program.methods.setSubscriber(subscriberProgram, Array(8).fill(1), [callbackAccount], 1, false)
+      .accounts({
+        header: priceOracleHeaderPda,
+        subscriber: subscriberPda,
+        authority: provider.wallet.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .rpc();
+
+    const subscriber = await program.account.subscriber.fetch(subscriberPda);
+    assert.equal(subscriber.programId.toBase58(), subscriberProgram.toBase58());
+    assert.equal(subscriber.accountCount, 1);
+    assert.equal(subscriber.accounts[0].toBase58(), callbackAccount.toBase58());
+    assert.isFalse(subscriber.enabled, "A disabled subscriber is never called back");
+  });
+
+  it("Refuses signed prices until a trusted publisher is set", async () => {
+    const signedPrice = () => program.methods.updatePriceSigned(
+      { sol: {} },
+      new anchor.BN(150_000_000_000),
+      new anchor.BN(Math.floor(Date.now() / 1000)),
+      Array(64).fill(0)
+    )
+      .accounts({
+        header: priceOracleHeaderPda,
+        data: priceOracleDataPda,
+        history: null,
+        updaterStats: null,
+        authority: provider.wallet.publicKey,
+        systemProgram: null,
+        instructions: anchor.web3.SYSVAR_INSTRUCTIONS_PUBKEY,
+      })
+      .rpc();
+
+    try {
+      await signedPrice();
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "No trusted publisher is set");
+    }
+
+    // Once set, a price without the publisher's Ed25519 instruction is still refused
+    const publisher = anchor.web3.Keypair.generate().publicKey;
+    const setPublisher = (key: anchor.web3.PublicKey) => program.methods.setTrustedPublisher(key)
+      .accounts({
+        header: priceOracleHeaderPda,
+        authority: provider.wallet.publicKey,
+      })
+      .rpc();
+    await setPublisher(publisher);
+    const header = await program.account.priceOracleHeader.fetch(priceOracleHeaderPda);
+    assert.equal(header.trustedPublisher.toBase58(), publisher.toBase58());
+    try {
+      await signedPrice();
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Publisher signature is missing or does not match");
+    }
+
+    await setPublisher(anchor.web3.PublicKey.default);
+  });
+
+  it("Lets a registered automation thread sign updates and nothing else", async () => {
+    const thread = anchor.web3.Keypair.generate();
+    const setThread = (key: anchor.web3.PublicKey) => program.methods.setThreadAuthority(key)
+      .accounts({
+        header: priceOracleHeaderPda,
+        authority: provider.wallet.publicKey,
+      })
+      .rpc();
+    const heartbeat = () => program.methods.heartbeat()
+      .accounts({
+        header: priceOracleHeaderPda,
+        authority: thread.publicKey,
+      })
+      .signers([thread])
+      .rpc();
+
+    try {
+      await heartbeat();
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Unauthorized access");
+    }
+
+    await setThread(thread.publicKey);
+    const header = await program.account.priceOracleHeader.fetch(priceOracleHeaderPda) as PriceOracleHeader;
+    assert.equal(header.threadAuthority.toBase58(), thread.publicKey.toBase58());
+    await heartbeat();
+
+    // The thread holds the updater role only
+    try {
+      await program.methods.setThreadAuthority(anchor.web3.PublicKey.default)
+        .accounts({
+          header: priceOracleHeaderPda,
+          authority: thread.publicKey,
+        })
+        .signers([thread])
+        .rpc();
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Signer does not hold the role this instruction requires");
+    }
+
+    await setThread(anchor.web3.PublicKey.default);
+  });
+
+  it("Attests the current prices under an increasing sequence", async () => {
+    const attest = () => program.methods.attestPrices()
+      .accounts({
+        header: priceOracleHeaderPda,
+        data: priceOracleDataPda,
+        authority: provider.wallet.publicKey,
+      });
+
+    const before = await program.account.priceOracleHeader.fetch(priceOracleHeaderPda) as PriceOracleHeader;
+    const payload = Buffer.from(await attest().view() as Buffer);
+    assert.equal(payload.subarray(0, 4).toString(), "FXOP", "Payload should start with the magic");
+    assert.equal(payload[4], 1, "Payload should be version 1");
+    assert.isTrue(new anchor.BN(payload.subarray(6, 14), "be").eq(before.attestationSequence.addn(1)), "Payload should carry the next sequence");
+    assert.equal(payload.length, 23 + 26 * payload[22], "Payload should hold one 26-byte entry per attested price");
+
+    await attest().rpc();
+    const after = await program.account.priceOracleHeader.fetch(priceOracleHeaderPda) as PriceOracleHeader;
+    assert.isTrue(after.attestationSequence.eq(before.attestationSequence.addn(1)), "Attesting should advance the sequence");
+  });
+
+  it("Guards a transaction on SOL price freshness", async () => {
+    const getPrice = await program.methods.getCurrentPrice({ sol: {} })
+      .accounts({
+        data: priceOracleDataPda,
+      })
+      .instruction();
+
+    await program.methods.assertPriceFresh({ sol: {} }, 1_000_000, false)
+      .accounts({
+        header: priceOracleHeaderPda,
+        data: priceOracleDataPda,
+        instructions: null,
+      })
+      .postInstructions([getPrice])
+      .rpc();
+
+    try {
+      await program.methods.assertPriceFresh({ sol: {} }, 0, false)
+        .accounts({
+          header: priceOracleHeaderPda,
+          data: priceOracleDataPda,
+          instructions: null,
+        })
+        .postInstructions([getPrice])
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Stale data");
+    }
+  });
+
+  it("Refuses a guarded read after a price update in the same transaction", async () => {
+    const update = await program.methods.updateAssets()
+      .accounts({
+        header: priceOracleHeaderPda,
+        data: priceOracleDataPda,
+        registry: assetRegistryPda,
+        history: null,
+        updaterStats: null,
+        authority: provider.wallet.publicKey,
+        systemProgram: null,
+      })
+      .remainingAccounts([{ pubkey: solOracleFeed, isWritable: false, isSigner: false }])
+      .instruction();
+
+    try {
+      await withNewRound(() => program.methods.assertPriceFresh({ sol: {} }, 1_000_000, false)
+        .accounts({
+          header: priceOracleHeaderPda,
+          data: priceOracleDataPda,
+          instructions: anchor.web3.SYSVAR_INSTRUCTIONS_PUBKEY,
+        })
+        .preInstructions([update])
+        .rpc());
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "An earlier instruction of this transaction updates prices");
+    }
+  });
+
+  it("Refuses to mix quote currencies unless conversion is requested", async () => {
+    try {
+      await program.methods.getExchangeRate({ jitoSol: {} }, { sol: {} }, false)
+        .accounts({
+          data: priceOracleDataPda,
+        })
+        .view();
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Assets are quoted in different currencies");
+    }
+
+    try {
+      const rate = await program.methods.getExchangeRate({ jitoSol: {} }, { sol: {} }, true)
+        .accounts({
+          data: priceOracleDataPda,
+        })
+        .view();
+      const dataAccount = await program.account.priceOracleData.fetch(priceOracleDataPda) as PriceOracleData;
+      // Converting through USD truncates once per step
+      assert.isTrue(rate.sub(dataAccount.priceData[5].price).abs().lten(1), "JitoSOL in SOL should match its SOL-quoted price");
+    } catch (error) {
+      console.error("Error converting through SOL/USD:", error);
+      throw error;
+    }
+  });
+
+  it("Sets a collateral haircut and refuses to value unpriced collateral", async () => {
+    await program.methods.setHaircut({ jitoSol: {} }, 1_000)
+      .accounts({
+        header: priceOracleHeaderPda,
+        authority: provider.wallet.publicKey,
+      })
+      .rpc();
+
+    const headerAccount = await program.account.priceOracleHeader.fetch(priceOracleHeaderPda) as PriceOracleHeader;
+    assert.equal(headerAccount.haircutBps[5], 1_000, "JitoSOL haircut should be set");
+    assert.equal(headerAccount.haircutBps[6], 0, "SOL should have no haircut");
+
+    try {
+      await program.methods.setHaircut({ jitoSol: {} }, 10_001)
+        .accounts({
+          header: priceOracleHeaderPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Invalid configuration value");
+    }
+
+    try {
+      await program.methods.getCollateralValue({ bonkSol: {} }, new anchor.BN(1_000_000_000))
+        .accounts({
+          header: priceOracleHeaderPda,
+          data: priceOracleDataPda,
+        })
+        .view();
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Price not available");
+    }
+  });
+
+  it("Prices a basket that only its creator can change or close", async () => {
+    const id = new anchor.BN(1);
+    const [basketPda] = await anchor.web3.PublicKey.findProgramAddress(
+      [Buffer.from("basket"), provider.wallet.publicKey.toBuffer(), id.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    const basketAccounts = {
+      data: priceOracleDataPda,
+      basket: basketPda,
+      creator: provider.wallet.publicKey,
+    };
+
+    try {
+      await program.methods.createBasket(id, [{ assetType: { sol: {} }, weightBps: 6_000 }, { assetType: { msol: {} }, weightBps: 3_000 }])
+        .accounts({ ...basketAccounts, systemProgram: anchor.web3.SystemProgram.programId })
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Basket weights must be positive");
+    }
+
+    await program.methods.createBasket(id, [{ assetType: { sol: {} }, weightBps: 6_000 }, { assetType: { msol: {} }, weightBps: 4_000 }])
+      .accounts({ ...basketAccounts, systemProgram: anchor.web3.SystemProgram.programId })
+      .rpc();
+
+    const price = await program.methods.getBasketPrice()
+      .accounts({
+        header: priceOracleHeaderPda,
+        data: priceOracleDataPda,
+        basket: basketPda,
+      })
+      .view();
+    assert.isTrue(price.gtn(0), "The basket should have a USD price");
+
+    const stranger = anchor.web3.Keypair.generate();
+    try {
+      await program.methods.updateBasketWeights(id, [{ assetType: { sol: {} }, weightBps: 10_000 }])
+        .accounts({ ...basketAccounts, creator: stranger.publicKey })
+        .signers([stranger])
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Unauthorized access");
+    }
+
+    await program.methods.closeBasket(id)
+      .accounts({ basket: basketPda, creator: provider.wallet.publicKey })
+      .rpc();
+    assert.isNull(await provider.connection.getAccountInfo(basketPda), "The basket should be closed");
+  });
+
+  it("Pushes a fresh quote into a consumer's buffer at its offset", async () => {
+    const id = new anchor.BN(1);
+    const [bufferPda] = await anchor.web3.PublicKey.findProgramAddress(
+      [Buffer.from("quote_buffer"), provider.wallet.publicKey.toBuffer(), id.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    const bufferAccounts = {
+      buffer: bufferPda,
+      authority: provider.wallet.publicKey,
+      systemProgram: anchor.web3.SystemProgram.programId,
+    };
+
+    try {
+      await program.methods.createQuoteBuffer(id, 128, 16, 1)
+        .accounts(bufferAccounts)
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Quote buffer must fit a quote at its offset");
+    }
+
+    await program.methods.createQuoteBuffer(id, 128, 64, 1)
+      .accounts(bufferAccounts)
+      .rpc();
+    await program.methods.writeQuoteTo({ msol: {} })
+      .accounts({
+        header: priceOracleHeaderPda,
+        data: priceOracleDataPda,
+        buffer: bufferPda,
+      })
+      .rpc();
+
+    // asset, quote, price, apy, timestamp and nonce from offset 64
+    const bufferInfo = await provider.connection.getAccountInfo(bufferPda);
+    const dataAccount = await program.account.priceOracleData.fetch(priceOracleDataPda) as PriceOracleData;
+    const msol = dataAccount.priceData[3];
+    assert.equal(bufferInfo.data.length, 128);
+    assert.equal(bufferInfo.data[64], 3, "The quote should be for mSOL");
+    assert.isTrue(new anchor.BN(bufferInfo.data.subarray(66, 74), "le").eq(msol.price), "Price should match the stored price");
+    assert.isTrue(new anchor.BN(bufferInfo.data.subarray(82, 90), "le").eq(msol.feedTimestamp), "Timestamp should be the feed timestamp");
+  });
+
+  it("Scores how far the SOL price can be trusted and rejects weights not adding up", async () => {
+    const trust = await program.methods.getTrustScore({ sol: {} })
+      .accounts({
+        header: priceOracleHeaderPda,
+        data: priceOracleDataPda,
+      })
+      .view();
+    assert.equal(trust.version, 1, "The response layout should be the documented one");
+    assert.isTrue(trust.score >= 0 && trust.score <= 10_000, "The score should be in basis points");
+    assert.equal(trust.weights.freshnessBps + trust.weights.confidenceBps + trust.weights.sourceBps + trust.weights.stabilityBps, 10_000);
+
+    try {
+      await program.methods.setTrustWeights({ freshnessBps: 5_000, confidenceBps: 2_000, sourceBps: 2_000, stabilityBps: 2_000 })
+        .accounts({
+          header: priceOracleHeaderPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Invalid configuration value");
+    }
+  });
+
+  it("Gets the SOL price change since the previous update", async () => {
+    try {
+      const dataAccount = await program.account.priceOracleData.fetch(priceOracleDataPda) as PriceOracleData;
+      const sol = dataAccount.priceData[6];
+
+      const change = await program.methods.getPriceChange({ sol: {} })
+        .accounts({
+          data: priceOracleDataPda,
+        })
+        .view();
+
+      assert.isTrue(change.previousPrice.eq(sol.lastPrice), "Previous price should match last_price");
+      assert.isTrue(change.currentPrice.eq(sol.price), "Current price should match price");
+      assert.isTrue(change.previousUpdateTime.eq(sol.lastPriceTime), "Previous time should match last_price_time");
+      assert.isTrue(change.absoluteDelta.eq(sol.price.sub(sol.lastPrice).abs()), "Delta should be the absolute difference");
+      assert.deepEqual(change.quote, { usd: {} }, "SOL should be quoted in USD");
+    } catch (error) {
+      console.error("Error getting SOL price change:", error);
+      throw error;
+    }
+  });
+
+  it("Reports and resets the SOL watermarks", async () => {
+    const dataAccount = await program.account.priceOracleData.fetch(priceOracleDataPda) as PriceOracleData;
+    const sol = dataAccount.priceData[6];
+
+    const watermarks = await program.methods.getWatermarks({ sol: {} })
+      .accounts({
+        data: priceOracleDataPda,
+      })
+      .view();
+
+    assert.isTrue(watermarks.high.eq(sol.highWatermark), "High should match high_watermark");
+    assert.isTrue(watermarks.low.eq(sol.lowWatermark), "Low should match low_watermark");
+    assert.isTrue(watermarks.low.lte(sol.price) && sol.price.lte(watermarks.high), "The price should lie between the watermarks");
+    assert.deepEqual(watermarks.quote, { usd: {} }, "SOL should be quoted in USD");
+
+    await program.methods.resetWatermarks({ sol: {} })
+      .accounts({
+        header: priceOracleHeaderPda,
+        data: priceOracleDataPda,
+        authority: provider.wallet.publicKey,
+      })
+      .rpc();
+
+    const nonce = await program.methods.getDataNonce()
+      .accounts({
+        data: priceOracleDataPda,
+      })
+      .view();
+    assert.isTrue(nonce.eq(dataAccount.nonce.addn(1)), "The reset should advance the data nonce once");
+
+    try {
+      await program.methods.getWatermarks({ sol: {} })
+        .accounts({
+          data: priceOracleDataPda,
+        })
+        .view();
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Data not available");
+    }
+  });
+
+  it("Flags a crossed SOL threshold until it is cleared", async () => {
+    const [thresholdsPda] = await anchor.web3.PublicKey.findProgramAddress(
+      [Buffer.from("price_thresholds")],
+      program.programId
+    );
+
+    // Any stored SOL price is above one base unit
+    await program.methods.setThreshold({ sol: {} }, 0, { above: {} }, new anchor.BN(1))
+      .accounts({
+        header: priceOracleHeaderPda,
+        data: priceOracleDataPda,
+        thresholds: thresholdsPda,
+        authority: provider.wallet.publicKey,
+      })
+      .rpc();
+
+    await program.methods.checkThresholds()
+      .accounts({
+        header: priceOracleHeaderPda,
+        data: priceOracleDataPda,
+        thresholds: thresholdsPda,
+      })
+      .rpc();
+
+    let thresholds = await program.account.priceThresholds.fetch(thresholdsPda) as any;
+    const sol = thresholds.thresholds[6][0];
+    assert.isTrue(sol.triggered, "The SOL threshold should be triggered");
+    assert.isTrue(sol.triggeredPrice.gtn(1), "The triggering price should be recorded");
+
+    await program.methods.clearThreshold({ sol: {} }, 0)
+      .accounts({
+        header: priceOracleHeaderPda,
+        thresholds: thresholdsPda,
+        authority: provider.wallet.publicKey,
+      })
+      .rpc();
+
+    thresholds = await program.account.priceThresholds.fetch(thresholdsPda) as any;
+    assert.isFalse(thresholds.thresholds[6][0].triggered, "The SOL threshold should be cleared");
+  });
+
+  it("Snapshots the SOL price once per epoch", async () => {
+    const [solSnapshotsPda] = await anchor.web3.PublicKey.findProgramAddress(
+      [Buffer.from("epoch_snapshots"), Buffer.from([6])],
+      programId
+    );
+    const { epoch } = await provider.connection.getEpochInfo();
+
+    try {
+      await program.methods.snapshotEpoch({ sol: {} })
+        .accounts({
+          data: priceOracleDataPda,
+          snapshots: solSnapshotsPda,
+          payer: provider.wallet.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .rpc();
+
+      const dataAccount = await program.account.priceOracleData.fetch(priceOracleDataPda) as PriceOracleData;
+      const epochPrice = await program.methods.getEpochPrice({ sol: {} }, new anchor.BN(epoch))
+        .accounts({
+          snapshots: solSnapshotsPda,
+        })
+        .view();
+      assert.isTrue(epochPrice.price.eq(dataAccount.priceData[6].price), "Epoch price should match the SOL price");
+      assert.deepEqual(epochPrice.quote, dataAccount.priceData[6].quote, "Epoch price should carry the SOL quote");
+    } catch (error) {
+      console.error("Error snapshotting SOL price:", error);
+      throw error;
+    }
+
+    try {
+      await program.methods.snapshotEpoch({ sol: {} })
+        .accounts({
+          data: priceOracleDataPda,
+          snapshots: solSnapshotsPda,
+          payer: provider.wallet.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Asset already has a snapshot for this epoch");
+    }
+  });
+
+  it("Gets current APY for JupSOL", async () => {
+    try {
+      const tx = await program.methods.getCurrentApy({ jupSol: {} })
+        .accounts({
+          data: priceOracleDataPda,
+        })
+        .rpc();
+
+      const txLogs = await provider.connection.getTransaction(tx, { commitment: 'confirmed' });
+      assert.isTrue(txLogs.meta.logMessages.some(log => log.includes("Current APY for JupSOL:")), "Transaction logs should include current APY for JupSOL");
+    } catch (error) {
+      console.error("Error getting current APY for JupSOL:", error);
+      throw error;
+    }
+  });
+
+  it("Gets current APY for JupSOL in basis points", async () => {
+    try {
+      const apyBps = await program.methods.getCurrentApyBps({ jupSol: {} })
+        .accounts({
+          data: priceOracleDataPda,
+        })
+        .view() as number;
+
+      const dataAccount = await program.account.priceOracleData.fetch(priceOracleDataPda) as PriceOracleData;
+      // 100_000 fixed-point units per basis point, rounded half up
+      const expected = dataAccount.priceData[0].apy.add(new anchor.BN(50_000)).div(new anchor.BN(100_000)).toNumber();
+      assert.equal(apyBps, expected, "Basis points should match the stored fixed-point APY");
+    } catch (error) {
+      console.error("Error getting current APY for JupSOL in basis points:", error);
+      throw error;
+    }
+  });
+
+  it("Delists HSOL and stops serving its price", async () => {
+    try {
+      const tx = await program.methods.delistAsset({ hsol: {} })
+        .accounts({
+          header: priceOracleHeaderPda,
+          data: priceOracleDataPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc({ commitment: "confirmed" });
+
+      const txDetails = await provider.connection.getTransaction(tx, { commitment: "confirmed" });
+      const parser = new anchor.EventParser(program.programId, program.coder);
+      const delistings = [...parser.parseLogs(txDetails.meta.logMessages)].filter(event => event.name === "AssetDelisted");
+      assert.equal(delistings.length, 1, "Delisting should be emitted");
+
+      const dataAccount = await program.account.priceOracleData.fetch(priceOracleDataPda) as PriceOracleData;
+      assert.isTrue(dataAccount.priceData[4].delisted, "HSOL should be delisted");
+      assert.isFalse(dataAccount.priceData[0].delisted, "JupSOL should stay listed");
+    } catch (error) {
+      console.error("Error delisting HSOL:", error);
+      throw error;
+    }
+
+    try {
+      await program.methods.getCurrentPrice({ hsol: {} })
+        .accounts({
+          data: priceOracleDataPda,
+        })
+        .rpc();
+      assert.fail("Expected the price of a delisted asset to be refused");
+    } catch (error) {
+      assert.include(error.toString(), "Asset is delisted");
+    }
+  });
+
+  it("Freezes the current prices into a checkpoint", async () => {
+    const id = new anchor.BN(await provider.connection.getSlot());
+    const [checkpointPda] = await anchor.web3.PublicKey.findProgramAddress(
+      [Buffer.from("checkpoint"), id.toArrayLike(Buffer, "le", 8)],
+      programId
+    );
+    try {
+      await program.methods.createCheckpoint(id)
+        .accounts({
+          header: priceOracleHeaderPda,
+          data: priceOracleDataPda,
+          checkpoint: checkpointPda,
+          payer: provider.wallet.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .rpc({ commitment: "confirmed" });
+
+      const dataAccount = await program.account.priceOracleData.fetch(priceOracleDataPda) as PriceOracleData;
+      const checkpoint = await program.methods.getCheckpoint(id)
+        .accounts({
+          checkpoint: checkpointPda,
+        })
+        .view();
+      assert.equal(checkpoint.creator.toBase58(), provider.wallet.publicKey.toBase58());
+      assert.isTrue(checkpoint.prices[0].price.eq(dataAccount.priceData[0].price), "JupSOL price should be copied");
+      assert.isTrue(checkpoint.prices[4].delisted, "HSOL should be recorded as delisted");
+    } catch (error) {
+      console.error("Error creating a checkpoint:", error);
+      throw error;
+    }
+
+    try {
+      await program.methods.closeCheckpoint(id)
+        .accounts({
+          header: priceOracleHeaderPda,
+          checkpoint: checkpointPda,
+          creator: provider.wallet.publicKey,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Checkpoint is too recent to be closed");
+    }
+  });
+
+  it("Lets the emergency council pause an asset but not resume it", async () => {
+    const council = anchor.web3.Keypair.generate();
+    try {
+      await program.methods.setEmergencyCouncil(council.publicKey)
+        .accounts({
+          header: priceOracleHeaderPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      await program.methods.pauseAsset({ hsol: {} })
+        .accounts({
+          header: priceOracleHeaderPda,
+          authority: council.publicKey,
+        })
+        .signers([council])
+        .rpc();
+
+      const headerAccount = await program.account.priceOracleHeader.fetch(priceOracleHeaderPda) as PriceOracleHeader;
+      assert.equal(headerAccount.emergencyCouncil.toBase58(), council.publicKey.toBase58(), "Council should be set");
+      assert.isTrue(headerAccount.paused[4], "HSOL should be paused");
+    } catch (error) {
+      console.error("Error pausing with the emergency council:", error);
+      throw error;
+    }
+
+    try {
+      await program.methods.resumeAsset({ hsol: {} })
+        .accounts({
+          header: priceOracleHeaderPda,
+          authority: council.publicKey,
+        })
+        .signers([council])
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Signer does not hold the role this instruction requires");
+    }
+
+    try {
+      await program.methods.resumeAsset({ hsol: {} })
+        .accounts({
+          header: priceOracleHeaderPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      const headerAccount = await program.account.priceOracleHeader.fetch(priceOracleHeaderPda) as PriceOracleHeader;
+      assert.isFalse(headerAccount.paused[4], "HSOL should be resumed");
+    } catch (error) {
+      console.error("Error resuming HSOL:", error);
+      throw error;
+    }
+  });
+
+  it("Pauses updates for maintenance while reads keep working", async () => {
+    await program.methods.setUpdatesPaused(true)
+      .accounts({
+        header: priceOracleHeaderPda,
+        authority: provider.wallet.publicKey,
+      })
+      .rpc();
+
+    let headerAccount = await program.account.priceOracleHeader.fetch(priceOracleHeaderPda) as PriceOracleHeader;
+    assert.isTrue(headerAccount.updatesPaused, "Updates should be paused");
+    assert.isFalse(headerAccount.emergencyStop, "Pausing updates should not set the emergency stop");
+
+    try {
+      await program.methods.updateSolPrice(null, 0)
+        .accounts({
+          header: priceOracleHeaderPda,
+          data: priceOracleDataPda,
+          oracleFeed: solOracleFeed,
+          historyBuffer: null,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Price updates are paused for maintenance");
+    }
+
+    const tx = await program.methods.getCurrentPrice({ sol: {} })
+      .accounts({
+        data: priceOracleDataPda,
+      })
+      .rpc();
+    const txLogs = await provider.connection.getTransaction(tx, { commitment: 'confirmed' });
+    assert.isTrue(txLogs.meta.logMessages.some(log => log.includes("Current price for SOL:")), "Reads should keep serving the stored price");
+
+    await program.methods.setUpdatesPaused(false)
+      .accounts({
+        header: priceOracleHeaderPda,
+        authority: provider.wallet.publicKey,
+      })
+      .rpc();
+
+    headerAccount = await program.account.priceOracleHeader.fetch(priceOracleHeaderPda) as PriceOracleHeader;
+    assert.isFalse(headerAccount.updatesPaused, "Updates should be resumed");
+  });
+
+  it("Sets and checks emergency stop", async () => {
+    try {
+      await program.methods.setEmergencyStop(true, false, 1)
+        .accounts({
+          header: priceOracleHeaderPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      const headerAccount = await program.account.priceOracleHeader.fetch(priceOracleHeaderPda) as PriceOracleHeader;
+      assert.isTrue(headerAccount.emergencyStop, "Emergency stop should be set to true");
+      assert.isAbove(headerAccount.stopIncident.toNumber(), 0, "Stop should open an incident");
+      assert.isFalse(headerAccount.stopAutomatic, "Stop should be recorded as manual");
+    } catch (error) {
+      console.error("Error setting or checking emergency stop:", error);
+      throw error;
+    }
+  });
+
+  it("Fails to update prices and APYs when emergency stop is active", async () => {
+    try {
+      await program.methods.updatePricesAndApys()
+        .accounts({
+          header: priceOracleHeaderPda,
+          data: priceOracleDataPda,
+          oracleFeed: oracleFeed,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Emergency stop activated");
+    }
+  });
+
+  it("Fails to update SOL price when emergency stop is active", async () => {
+    try {
+      await program.methods.updateSolPrice(null, 0)
+        .accounts({
+          header: priceOracleHeaderPda,
+          data: priceOracleDataPda,
+          oracleFeed: solOracleFeed,
+          historyBuffer: null,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Emergency stop activated");
+    }
+  });
+
+  it("Fails to update prices and APYs with unauthorized user", async () => {
+    const unauthorizedUser = anchor.web3.Keypair.generate();
+    try {
+      await program.methods.updatePricesAndApys()
+        .accounts({
+          header: priceOracleHeaderPda,
+          data: priceOracleDataPda,
+          oracleFeed: oracleFeed,
+          authority: unauthorizedUser.publicKey,
+        })
+        .signers([unauthorizedUser])
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Unauthorized access");
+    }
+  });
+
+  it("Rejects clearing the emergency stop within the minimum interval", async () => {
+    try {
+      await program.methods.setEmergencyStop(false, false, 0)
+        .accounts({
+          header: priceOracleHeaderPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Emergency stop changed too recently");
+    }
+  });
+
+  it("Schedules clearing the emergency stop behind the timelock", async () => {
+    try {
+      const before = await program.account.priceOracleHeader.fetch(priceOracleHeaderPda) as PriceOracleHeader;
+
+      await program.methods.setEmergencyStop(false, true, 2)
+        .accounts({
+          header: priceOracleHeaderPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      const after = await program.account.priceOracleHeader.fetch(priceOracleHeaderPda) as PriceOracleHeader;
+      assert.isTrue(after.emergencyStop, "Emergency stop should stay set until the timelock elapses");
+      assert.isAbove(after.stopClearEffectiveAt.toNumber(), 0, "Clear should be scheduled");
+      assert.equal(after.stopChangeCount.toNumber(), before.stopChangeCount.toNumber(), "Scheduling is not a change");
+    } catch (error) {
+      console.error("Error scheduling the emergency stop clear:", error);
+      throw error;
+    }
+
+    try {
+      await program.methods.setEmergencyStop(false, true, 2)
+        .accounts({
+          header: priceOracleHeaderPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Timelock has not elapsed");
+    }
+  });
+
+  it("Refuses to execute a resume without approvals", async () => {
+    const stranger = anchor.web3.Keypair.generate();
+    try {
+      await program.methods.approveResume()
+        .accounts({
+          header: priceOracleHeaderPda,
+          approver: stranger.publicKey,
+        })
+        .signers([stranger])
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Signer is not a resume approver");
+    }
+
+    try {
+      await program.methods.executeResume()
+        .accounts({
+          header: priceOracleHeaderPda,
+          payer: stranger.publicKey,
+        })
+        .signers([stranger])
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Not enough approvals to resume");
+    }
+  });
+
+  it("Proposes a config change that cannot be applied early, then cancels it", async () => {
+    try {
+      const header = await program.account.priceOracleHeader.fetch(priceOracleHeaderPda) as PriceOracleHeader;
+      const registryAccount = await program.account.assetRegistry.fetch(assetRegistryPda) as any;
+      const config: OracleConfig = {
+        priceChangeLimitBps: 500,
+        timelockSecs: header.configTimelockSecs,
+        feeds: registryAccount.assets.map((asset: any) => asset.feed),
+        resumeApprovers: header.resumeApprovers,
+        resumeThreshold: header.resumeThreshold,
+        manualResumeNeedsApproval: header.manualResumeNeedsApproval,
+      };
+
+      await program.methods.proposeConfig(config)
+        .accounts({
+          header: priceOracleHeaderPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      const proposed = await program.account.priceOracleHeader.fetch(priceOracleHeaderPda) as PriceOracleHeader;
+      assert.equal(proposed.pendingConfig.config.priceChangeLimitBps, 500, "Config should be pending");
+      assert.isAbove(proposed.pendingConfig.effectiveAt.toNumber(), 0, "Config should have an effective time");
+      assert.equal(proposed.priceChangeLimitBps, header.priceChangeLimitBps, "Active limit should not change yet");
+    } catch (error) {
+      console.error("Error proposing a config change:", error);
+      throw error;
+    }
+
+    // Anyone may apply, but only once the timelock has elapsed
+    const stranger = anchor.web3.Keypair.generate();
+    try {
+      await program.methods.applyConfig()
+        .accounts({
+          header: priceOracleHeaderPda,
+          registry: assetRegistryPda,
+          payer: stranger.publicKey,
+        })
+        .signers([stranger])
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Timelock has not elapsed");
+    }
+
+    try {
+      await program.methods.cancelConfig()
+        .accounts({
+          header: priceOracleHeaderPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      const cancelled = await program.account.priceOracleHeader.fetch(priceOracleHeaderPda) as PriceOracleHeader;
+      assert.equal(cancelled.pendingConfig.effectiveAt.toNumber(), 0, "Nothing should be pending");
+    } catch (error) {
+      console.error("Error cancelling a config change:", error);
+      throw error;
+    }
+  });
+
+  it("Queues a permuted feed mapping behind the timelock, then cancels it", async () => {
+    const permuted = [{ vsol: {} }, { jupSol: {} }, { bsol: {} }, { msol: {} }, { jitoSol: {} }, { hsol: {} }];
+    const positions = permuted.map((assetType, position) => ({ position, assetType }));
+
+    try {
+      await program.methods.proposeFeedMapping([...positions.slice(0, 5), { position: 5, assetType: { jupSol: {} } }])
+        .accounts({
+          header: priceOracleHeaderPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Feed mapping must name every position");
+    }
+
+    await program.methods.proposeFeedMapping(positions)
+      .accounts({
+        header: priceOracleHeaderPda,
+        authority: provider.wallet.publicKey,
+      })
+      .rpc();
+    const proposed = await program.account.priceOracleHeader.fetch(priceOracleHeaderPda) as any;
+    assert.isAbove(proposed.pendingFeedMapping.effectiveAt.toNumber(), 0, "Mapping should be pending");
+    assert.deepEqual(proposed.pendingFeedMapping.mapping.assets, permuted);
+    assert.deepEqual(proposed.feedMapping.assets[0], { jupSol: {} }, "Active mapping should not change yet");
+
+    const stranger = anchor.web3.Keypair.generate();
+    try {
+      await program.methods.applyFeedMapping()
+        .accounts({
+          header: priceOracleHeaderPda,
+          payer: stranger.publicKey,
+        })
+        .signers([stranger])
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Timelock has not elapsed");
+    }
+
+    await program.methods.cancelFeedMapping()
+      .accounts({
+        header: priceOracleHeaderPda,
+        authority: provider.wallet.publicKey,
+      })
+      .rpc();
+    const cancelled = await program.account.priceOracleHeader.fetch(priceOracleHeaderPda) as any;
+    assert.equal(cancelled.pendingFeedMapping.effectiveAt.toNumber(), 0, "Nothing should be pending");
+  });
+
+  it("Rejects a Switchboard program ID without an executable account", async () => {
+    const typo = anchor.web3.Keypair.generate().publicKey;
+    try {
+      await program.methods.setSwitchboardProgramId(typo)
+        .accounts({
+          header: priceOracleHeaderPda,
+          switchboardProgram: typo,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Invalid configuration value");
+    }
+  });
+
+  it("Sets the Switchboard program ID", async () => {
+    try {
+      await program.methods.setSwitchboardProgramId(switchboardProgram)
+        .accounts({
+          header: priceOracleHeaderPda,
+          switchboardProgram: switchboardProgram,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      const headerAccount = await program.account.priceOracleHeader.fetch(priceOracleHeaderPda) as PriceOracleHeader;
+      assert.equal(headerAccount.switchboardProgramId.toBase58(), switchboardProgram.toBase58(), "Switchboard program ID should be updated");
+    } catch (error) {
+      console.error("Error setting Switchboard program ID:", error);
+      throw error;
+    }
+  });
+
+  // Near the end: every privileged instruction after it must pass the log
+  it("Records privileged actions in the audit log once it exists", async () => {
+    const [auditLogPda] = await anchor.web3.PublicKey.findProgramAddress(
+      [Buffer.from("audit_log")],
+      programId
+    );
+    await program.methods.initializeAuditLog()
+      .accounts({
+        header: priceOracleHeaderPda,
+        auditLog: auditLogPda,
+        authority: provider.wallet.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .rpc();
+
+    try {
+      await program.methods.setHaircut({ jitoSol: {} }, 1500)
+        .accounts({
+          header: priceOracleHeaderPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Privileged instructions must be passed the audit log");
+    }
+
+    await program.methods.setHaircut({ jitoSol: {} }, 1500)
+      .accounts({
+        header: priceOracleHeaderPda,
+        authority: provider.wallet.publicKey,
+        auditLog: auditLogPda,
+      })
+      .rpc();
+
+    const page = await program.methods.getAuditLog(10)
+      .accounts({
+        auditLog: auditLogPda,
+      })
+      .view();
+    assert.equal(page.totalEntries.toNumber(), 2);
+    assert.deepEqual(page.entries.map((entry: any) => Object.keys(entry.action)[0]), ["setHaircut", "initializeAuditLog"]);
+    assert.deepEqual(page.entries[0].subjectAsset, { jitoSol: {} });
+    assert.equal(page.entries[0].actor.toBase58(), provider.wallet.publicKey.toBase58());
+  });
+
+  // Runs last: the lock cannot be undone
+  it("Locks the oracle permanently", async () => {
+    const [auditLogPda] = await anchor.web3.PublicKey.findProgramAddress(
+      [Buffer.from("audit_log")],
+      programId
+    );
+    try {
+      await program.methods.lockOracle("lock")
+        .accounts({
+          header: priceOracleHeaderPda,
+          authority: provider.wallet.publicKey,
+          auditLog: auditLogPda,
+        })
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Lock confirmation does not match");
+    }
+
+    try {
+      await program.methods.lockOracle("lock oracle permanently")
+        .accounts({
+          header: priceOracleHeaderPda,
+          authority: provider.wallet.publicKey,
+          auditLog: auditLogPda,
+        })
+        .rpc();
+
+      const headerAccount = await program.account.priceOracleHeader.fetch(priceOracleHeaderPda) as PriceOracleHeader;
+      assert.isTrue(headerAccount.locked, "Oracle should be locked");
     } catch (error) {
-      console.error("Error updating Switchboard program ID:", error);
+      console.error("Error locking the oracle:", error);
       throw error;
     }
+
+    try {
+      await program.methods.setMaxFeedAgeSlots(new anchor.BN(10))
+        .accounts({
+          header: priceOracleHeaderPda,
+          authority: provider.wallet.publicKey,
+          auditLog: auditLogPda,
+        })
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error) {
+      assert.include(error.toString(), "Oracle is locked");
+    }
   });
 });
\ No newline at end of file